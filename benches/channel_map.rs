@@ -0,0 +1,91 @@
+//! Building the channel map from paginated responses, and subsequent lookups
+//! against it, at sizes beyond those of our own workspace:
+//!
+//! ```console
+//! $ cargo bench --features bench
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use mercury::bench::{ChannelName, SlackAccessToken, SlackClient};
+use mockito::{Matcher, ServerGuard};
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+const PAGE_SIZE: usize = 200;
+
+/// Serve a synthetic workspace of `n` channels named `channel-{i}`, paginated
+/// as Slack would.
+async fn mock_channels(srv: &mut ServerGuard, n: usize) {
+    // At least one page, even if it's empty.
+    let pages = n.saturating_sub(1) / PAGE_SIZE + 1;
+
+    for page in 0..pages {
+        let channels: Vec<_> = (page * PAGE_SIZE..n.min((page + 1) * PAGE_SIZE))
+            .map(|i| json!({ "id": format!("C{:010}", i), "name": format!("channel-{}", i) }))
+            .collect();
+        let next_cursor = match page + 1 {
+            x if x < pages => x.to_string(),
+            _ => String::new(),
+        };
+        let cursor = match page {
+            0 => String::new(),
+            x => format!("&cursor={}", x),
+        };
+
+        srv.mock("GET", "/conversations.list")
+            .match_query(Matcher::Exact(format!(
+                "limit={}&exclude_archived=true{}",
+                PAGE_SIZE, cursor
+            )))
+            .with_body(
+                json!({
+                    "ok": true,
+                    "channels": channels,
+                    "response_metadata": { "next_cursor": next_cursor },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+    }
+}
+
+fn token() -> SlackAccessToken {
+    SlackAccessToken("foobar".to_owned())
+}
+
+fn channel_map(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut fetch = c.benchmark_group("warm_channel_map");
+    fetch.sample_size(10);
+
+    for n in [10_000, 50_000] {
+        let mut srv = rt.block_on(mockito::Server::new_async());
+        rt.block_on(mock_channels(&mut srv, n));
+
+        fetch.bench_with_input(BenchmarkId::from_parameter(n), &n, |b, _| {
+            b.to_async(&rt).iter(|| async {
+                let mut client = SlackClient::new(srv.url());
+                client.warm_channel_map(&token()).await.ok()
+            });
+        });
+    }
+    fetch.finish();
+
+    let mut lookup = c.benchmark_group("get_channel_id");
+    for n in [10_000, 50_000] {
+        let mut srv = rt.block_on(mockito::Server::new_async());
+        rt.block_on(mock_channels(&mut srv, n));
+        let mut client = SlackClient::new(srv.url());
+        assert!(rt.block_on(client.warm_channel_map(&token())).is_ok());
+
+        let name = ChannelName(format!("channel-{}", n / 2));
+        lookup.bench_with_input(BenchmarkId::from_parameter(n), &name, |b, x| {
+            b.iter(|| rt.block_on(client.get_channel_id(x, &token())).ok());
+        });
+    }
+    lookup.finish();
+}
+
+criterion_group!(benches, channel_map);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+//! Generate the gRPC service definition. See `proto/mercury.proto`.
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Prefer a vendored compiler so that protoc needn't be installed.
+    if std::env::var_os("PROTOC").is_none() {
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["proto/mercury.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -24,6 +24,8 @@
           nativeBuildInputs = with pkgs; [
             pkg-config
           ];
+          # The vendored protoc won't run on NixOS.
+          PROTOC = "${pkgs.protobuf}/bin/protoc";
           buildInputs = with pkgs; [
             openssl
           ] ++ darwinDeps;
@@ -0,0 +1,11 @@
+//! Heroku webhook payloads have surprised us before. Deserialisation should
+//! never panic, whatever we're sent.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use mercury::fuzz::HookPayload;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = HookPayload::from_json_with_unknown(data);
+});
@@ -0,0 +1,19 @@
+## Heroku events. See `src/heroku/webhook.rs`.
+
+heroku-deploy = Deployment { $commit } ({ $author })
+heroku-rollback = Rollback auf { $version } ({ $author })
+heroku-config-change = Umgebungsvariablen geändert: { $change } ({ $author })
+heroku-dyno-crash = Dyno { $name } ist mit Statuscode { $code } abgestürzt
+heroku-dyno-crash-unknown = Dyno { $name } ist abgestürzt
+
+## Changelogs accompanying deploys and rollbacks.
+
+changelog-includes = Enthält { $count } { $count ->
+        [one] Commit
+       *[other] Commits
+    }:
+changelog-reverts = Macht { $count } { $count ->
+        [one] Commit
+       *[other] Commits
+    } rückgängig:
+changelog-more = …und { $count } weitere
@@ -0,0 +1,19 @@
+## Heroku events. See `src/heroku/webhook.rs`.
+
+heroku-deploy = Deploy { $commit } ({ $author })
+heroku-rollback = Rollback to { $version } ({ $author })
+heroku-config-change = Environment variables changed: { $change } ({ $author })
+heroku-dyno-crash = Dyno { $name } crashed with status code { $code }
+heroku-dyno-crash-unknown = Dyno { $name } crashed
+
+## Changelogs accompanying deploys and rollbacks.
+
+changelog-includes = Includes { $count } { $count ->
+        [one] commit
+       *[other] commits
+    }:
+changelog-reverts = Reverts { $count } { $count ->
+        [one] commit
+       *[other] commits
+    }:
+changelog-more = …and { $count } more
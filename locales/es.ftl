@@ -0,0 +1,19 @@
+## Heroku events. See `src/heroku/webhook.rs`.
+
+heroku-deploy = Despliegue { $commit } ({ $author })
+heroku-rollback = Reversión a { $version } ({ $author })
+heroku-config-change = Variables de entorno modificadas: { $change } ({ $author })
+heroku-dyno-crash = El dyno { $name } falló con el código de estado { $code }
+heroku-dyno-crash-unknown = El dyno { $name } falló
+
+## Changelogs accompanying deploys and rollbacks.
+
+changelog-includes = Incluye { $count } { $count ->
+        [one] commit
+       *[other] commits
+    }:
+changelog-reverts = Revierte { $count } { $count ->
+        [one] commit
+       *[other] commits
+    }:
+changelog-more = …y { $count } más
@@ -0,0 +1,19 @@
+## Heroku events. See `src/heroku/webhook.rs`.
+
+heroku-deploy = Déploiement { $commit } ({ $author })
+heroku-rollback = Retour à { $version } ({ $author })
+heroku-config-change = Variables d'environnement modifiées : { $change } ({ $author })
+heroku-dyno-crash = Le dyno { $name } a planté avec le code de sortie { $code }
+heroku-dyno-crash-unknown = Le dyno { $name } a planté
+
+## Changelogs accompanying deploys and rollbacks.
+
+changelog-includes = Inclut { $count } { $count ->
+        [one] commit
+       *[other] commits
+    } :
+changelog-reverts = Annule { $count } { $count ->
+        [one] commit
+       *[other] commits
+    } :
+changelog-more = …et { $count } de plus
@@ -0,0 +1,11 @@
+//! Operational controls for Mercury itself.
+//!
+//! Admin routes are only available if `$ADMIN_TOKEN` is set, and must be
+//! authenticated with it as a `Bearer` token.
+
+pub mod auth;
+pub mod graphql;
+pub mod router;
+pub mod ui;
+
+pub use auth::AdminToken;
@@ -0,0 +1,5 @@
+//! Helpers around authenticating administrative requests.
+
+/// A newtype wrapper around the admin token.
+#[derive(Clone)]
+pub struct AdminToken(pub String);
@@ -0,0 +1,300 @@
+//! A read-only GraphQL view over the audit history and configuration, for
+//! admin UIs which would otherwise need a bespoke endpoint per view.
+//!
+//! The schema is deliberately decoupled from the underlying types, mapping
+//! each to an object of its own.
+
+use crate::{
+    audit::{AuditEntry, Outcome},
+    delivery::Source,
+    router::Deps,
+    slack::{api::CacheState, Severity},
+};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, Object, Schema, SimpleObject,
+};
+use axum::{extract::State, Json};
+use chrono::{DateTime, Utc};
+use std::sync::atomic::Ordering;
+
+/// The most audit entries returned by default.
+const DEFAULT_LIMIT: usize = 50;
+
+pub type AdminSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+pub fn schema() -> AdminSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription).finish()
+}
+
+/// Handler for the POST subroute `/graphql`.
+///
+/// Accepts a GraphQL request in `application/json` format, responding in kind.
+pub async fn graphql_handler(
+    State(deps): State<Deps>,
+    Json(req): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema().execute(req.data(deps)).await)
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// An entry in the audit history by its ID, if it's still retained.
+    async fn audit_entry(&self, ctx: &Context<'_>, id: u64) -> Option<AuditEntryObject> {
+        deps(ctx).audit.get(id).map(Into::into)
+    }
+
+    /// The most recently retained entries in the audit history, newest first,
+    /// optionally filtered.
+    async fn audit_entries(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEFAULT_LIMIT")] limit: usize,
+        channel: Option<String>,
+        source: Option<SourceKind>,
+        status: Option<DeliveryStatus>,
+    ) -> Vec<AuditEntryObject> {
+        let channel = channel.as_deref().map(|x| x.trim_start_matches('#'));
+
+        deps(ctx)
+            .audit
+            .recent(limit, |x| {
+                channel.map_or(true, |c| x.message.channel.0.trim_start_matches('#') == c)
+                    && source.map_or(true, |s| x.source.map(SourceKind::from) == Some(s))
+                    && status.map_or(true, |s| DeliveryStatus::from(&x.outcome) == s)
+            })
+            .into_iter()
+            .map(Into::into)
+            .collect()
+    }
+
+    /// The state of the caches of Slack's channels and user groups, which
+    /// are refreshed periodically.
+    async fn caches(&self, ctx: &Context<'_>) -> Caches {
+        let client = deps(ctx).slack_client.lock().await;
+
+        Caches {
+            channels: client.channel_cache().map(Into::into),
+            user_groups: client.user_group_cache().map(Into::into),
+        }
+    }
+
+    /// How Mercury is currently configured, excluding secrets.
+    async fn config(&self, ctx: &Context<'_>) -> Config {
+        Config::from(deps(ctx))
+    }
+}
+
+fn deps<'a>(ctx: &Context<'a>) -> &'a Deps {
+    // Always supplied by the handler.
+    ctx.data_unchecked::<Deps>()
+}
+
+/// Where a message originated. See [Source].
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Api,
+    Heroku,
+    Selftest,
+    Replay,
+}
+
+impl From<Source> for SourceKind {
+    fn from(x: Source) -> Self {
+        match x {
+            Source::Api => SourceKind::Api,
+            Source::Heroku => SourceKind::Heroku,
+            Source::Selftest => SourceKind::Selftest,
+            Source::Replay => SourceKind::Replay,
+        }
+    }
+}
+
+/// How severe a message is. See [Severity].
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub enum SeverityKind {
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Critical,
+}
+
+impl From<Severity> for SeverityKind {
+    fn from(x: Severity) -> Self {
+        match x {
+            Severity::Debug => SeverityKind::Debug,
+            Severity::Info => SeverityKind::Info,
+            Severity::Success => SeverityKind::Success,
+            Severity::Warning => SeverityKind::Warning,
+            Severity::Critical => SeverityKind::Critical,
+        }
+    }
+}
+
+/// How a delivery attempt turned out. See [Outcome].
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Sent,
+    Suppressed,
+    Failed,
+}
+
+impl From<&Outcome> for DeliveryStatus {
+    fn from(x: &Outcome) -> Self {
+        match x {
+            Outcome::Sent => DeliveryStatus::Sent,
+            Outcome::Suppressed { .. } => DeliveryStatus::Suppressed,
+            Outcome::Failed { .. } => DeliveryStatus::Failed,
+        }
+    }
+}
+
+/// A single delivery attempt. See [AuditEntry].
+#[derive(SimpleObject)]
+pub struct AuditEntryObject {
+    id: u64,
+    at: DateTime<Utc>,
+    /// Absent from entries persisted before sources were recorded.
+    source: Option<SourceKind>,
+    channel: String,
+    title: String,
+    desc: String,
+    link: Option<String>,
+    cc: Option<String>,
+    severity: Option<SeverityKind>,
+    status: DeliveryStatus,
+    /// Why delivery was suppressed or failed, if it was.
+    detail: Option<String>,
+}
+
+impl From<AuditEntry> for AuditEntryObject {
+    fn from(x: AuditEntry) -> Self {
+        let status = DeliveryStatus::from(&x.outcome);
+        let detail = match x.outcome {
+            Outcome::Sent => None,
+            Outcome::Suppressed { reason } => Some(reason),
+            Outcome::Failed { error } => Some(error),
+        };
+        let msg = x.message;
+
+        AuditEntryObject {
+            id: x.id,
+            at: x.at,
+            source: x.source.map(Into::into),
+            channel: msg.channel.0,
+            title: msg.title,
+            desc: msg.desc,
+            link: msg.link.map(String::from),
+            cc: msg.cc.map(String::from),
+            severity: msg.severity.map(Into::into),
+            status,
+            detail,
+        }
+    }
+}
+
+/// Each cache, absent if it's yet to be populated.
+#[derive(SimpleObject)]
+pub struct Caches {
+    channels: Option<CacheObject>,
+    user_groups: Option<CacheObject>,
+}
+
+/// See [CacheState].
+#[derive(SimpleObject)]
+pub struct CacheObject {
+    entries: usize,
+    age_secs: u64,
+}
+
+impl From<CacheState> for CacheObject {
+    fn from(x: CacheState) -> Self {
+        CacheObject {
+            entries: x.entries,
+            age_secs: x.age.as_secs(),
+        }
+    }
+}
+
+/// Configuration which can be inspected without leaking secrets.
+#[derive(SimpleObject)]
+pub struct Config {
+    read_only: bool,
+    selftest_channel: Option<String>,
+    shadow_channel: Option<String>,
+    /// In order of precedence. See [crate::heroku::routing].
+    app_routes: Vec<AppRouteObject>,
+    /// Ordered by channel. See [crate::budget].
+    noise_budgets: Vec<NoiseBudgetObject>,
+    /// Stages in the order in which they're due. See [crate::escalation].
+    escalation_policy: Vec<EscalationStageObject>,
+    /// See [crate::threading].
+    thread_window_mins: Option<u64>,
+}
+
+#[derive(SimpleObject)]
+pub struct AppRouteObject {
+    pattern: String,
+    channel: String,
+    cc: Option<String>,
+}
+
+#[derive(SimpleObject)]
+pub struct NoiseBudgetObject {
+    channel: String,
+    /// Messages per hour.
+    limit: u32,
+}
+
+#[derive(SimpleObject)]
+pub struct EscalationStageObject {
+    after_mins: u64,
+    cc: String,
+}
+
+impl From<&Deps> for Config {
+    fn from(deps: &Deps) -> Self {
+        let app_routes = deps
+            .heroku_app_routes
+            .iter()
+            .map(|r| AppRouteObject {
+                pattern: r.pattern.clone(),
+                channel: r.channel.0.clone(),
+                cc: r.cc.clone().map(String::from),
+            })
+            .collect();
+
+        let mut noise_budgets: Vec<_> = deps
+            .noise_budgets
+            .iter()
+            .flat_map(|x| x.limits())
+            .map(|(channel, limit)| NoiseBudgetObject {
+                channel: channel.clone(),
+                limit: *limit,
+            })
+            .collect();
+        noise_budgets.sort_by(|x, y| x.channel.cmp(&y.channel));
+
+        let escalation_policy = deps
+            .escalations
+            .iter()
+            .flat_map(|x| x.policy())
+            .map(|s| EscalationStageObject {
+                after_mins: s.after.as_secs() / 60,
+                cc: String::from(s.cc.clone()),
+            })
+            .collect();
+
+        Config {
+            read_only: deps.read_only.load(Ordering::Relaxed),
+            selftest_channel: deps.selftest_channel.as_ref().map(|x| x.0.clone()),
+            shadow_channel: deps.shadow.as_ref().map(|x| x.channel.0.clone()),
+            app_routes,
+            noise_budgets,
+            escalation_policy,
+            thread_window_mins: deps.threads.as_ref().map(|x| x.window().as_secs() / 60),
+        }
+    }
+}
@@ -0,0 +1,589 @@
+//! Admin subrouter definition.
+//!
+//! The following subroutes are supported:
+//!
+//! - GET: `/read-only`
+//! - PUT: `/read-only`
+//! - DELETE: `/read-only`
+//! - GET: `/ingestion`
+//! - PUT: `/ingestion/:source`
+//! - DELETE: `/ingestion/:source`
+//! - PUT: `/secrets`
+//! - POST: `/selftest`
+//! - GET: `/stats`
+//! - POST: `/graphql`
+//! - GET: `/ui`
+//! - GET: `/captures/:source`
+//! - GET: `/fixtures`
+//! - PUT: `/fixtures`
+//! - DELETE: `/fixtures`
+//! - GET: `/audit/export`
+//! - GET: `/audit/:id`
+//! - POST: `/audit/:id/replay`
+//! - GET, PUT, DELETE: `/chaos`, with the `chaos` feature
+
+use super::{graphql::graphql_handler, ui::ui_handler, AdminToken};
+use crate::{
+    audit::{export::ExportFormat, AuditEntry},
+    auth::{is_valid_bearer, ApiToken},
+    cache::{self, ResponseCache},
+    capture::Capture,
+    delivery::{deliver, Delivery, Source, SUPPRESSED_HEADER},
+    heroku::HerokuSecret,
+    ingestion::{catch_up, PAUSABLE},
+    router::Deps,
+    slack::{channel::ChannelName, router::slack_err_response, Message, SlackAccessToken},
+    stats::StatsReport,
+};
+use axum::{
+    body::Body,
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    iter,
+    sync::{atomic::Ordering, Arc},
+    time::Instant,
+};
+use tracing::{info, warn};
+
+#[cfg(feature = "chaos")]
+use crate::slack::chaos::ChaosConfig;
+
+/// Instantiate a new admin subrouter, caching responses as per [crate::cache].
+pub fn admin_router(admin_token: &AdminToken, cache: &Arc<ResponseCache>) -> Router<Deps> {
+    let router = Router::new();
+
+    #[cfg(feature = "chaos")]
+    let router = router.route(
+        "/chaos",
+        get(get_chaos_handler)
+            .put(set_chaos_handler)
+            .delete(clear_chaos_handler),
+    );
+
+    router
+        .route(
+            "/read-only",
+            get(get_read_only_handler)
+                .put(enable_read_only_handler)
+                .delete(disable_read_only_handler),
+        )
+        .route("/ingestion", get(get_ingestion_handler))
+        .route(
+            "/ingestion/:source",
+            put(pause_ingestion_handler).delete(resume_ingestion_handler),
+        )
+        .route("/secrets", put(rotate_secrets_handler))
+        .route("/selftest", post(selftest_handler))
+        .route("/stats", get(get_stats_handler))
+        .route("/graphql", post(graphql_handler))
+        .route("/captures/:source", get(get_captures_handler))
+        .route(
+            "/fixtures",
+            get(get_fixtures_handler)
+                .put(start_fixtures_handler)
+                .delete(stop_fixtures_handler),
+        )
+        .route("/audit/export", get(export_audit_handler))
+        .route("/audit/:id", get(get_audit_entry_handler))
+        .route("/audit/:id/replay", post(replay_audit_entry_handler))
+        .layer(middleware::from_fn_with_state(cache.clone(), cache::cache))
+        .layer(middleware::from_fn_with_state(
+            admin_token.clone(),
+            authenticate,
+        ))
+        // The page authenticates its own requests to the above.
+        .route("/ui", get(ui_handler))
+}
+
+/// Authenticate requests by a `Bearer` `Authorization` header matching
+/// `$ADMIN_TOKEN`.
+async fn authenticate(State(token): State<AdminToken>, req: Request, next: Next) -> Response {
+    match req.headers().get(AUTHORIZATION) {
+        Some(x) if is_valid_bearer(x, iter::once(token.0.as_str())) => next.run(req).await,
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Secrets to replace at runtime. Omitted secrets are left unchanged.
+#[derive(Deserialize)]
+struct SecretsRotation {
+    slack_token: Option<String>,
+    /// When the new Slack token expires as a Unix timestamp, if it does. See
+    /// [crate::slack::expiry].
+    slack_token_expires_at: Option<i64>,
+    /// All of which will be accepted, allowing webhooks to be updated one at a
+    /// time before the old secret is dropped in a subsequent rotation.
+    heroku_secrets: Option<Vec<String>>,
+    /// All of which will be accepted, as per `$MERCURY_API_TOKEN`.
+    api_tokens: Option<Vec<String>>,
+}
+
+/// Query params for the GET subroute `/audit/export`.
+#[derive(Deserialize)]
+struct ExportOptions {
+    #[serde(default)]
+    format: ExportFormat,
+    /// Inclusive, in RFC 3339 format.
+    from: Option<DateTime<Utc>>,
+    /// Inclusive, in RFC 3339 format.
+    to: Option<DateTime<Utc>>,
+}
+
+/// Query params for the POST subroute `/audit/:id/replay`.
+#[derive(Deserialize)]
+struct ReplayOptions {
+    /// Where to deliver the message instead of its original channel.
+    channel: Option<ChannelName>,
+}
+
+/// The outcome of a self-test.
+#[derive(Serialize)]
+struct SelftestResult {
+    ok: bool,
+    /// How long delivery took end-to-end, including any channel lookups.
+    latency_ms: u128,
+    /// Why delivery was suppressed, if it was, in which case nothing was
+    /// really tested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suppressed: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The current state of read-only mode.
+#[derive(Serialize)]
+struct ReadOnlyStatus {
+    read_only: bool,
+}
+
+/// Handler for the GET subroute `/read-only`.
+///
+/// Responds with a [ReadOnlyStatus] in `application/json` format.
+async fn get_read_only_handler(State(deps): State<Deps>) -> Json<ReadOnlyStatus> {
+    Json(ReadOnlyStatus {
+        read_only: deps.read_only.load(Ordering::Relaxed),
+    })
+}
+
+/// Handler for the PUT subroute `/read-only`.
+///
+/// Enables read-only mode, in which requests continue to be accepted however
+/// all onward delivery is suppressed. See [crate::delivery].
+async fn enable_read_only_handler(State(deps): State<Deps>) -> Json<ReadOnlyStatus> {
+    deps.read_only.store(true, Ordering::Relaxed);
+    warn!("Read-only mode enabled");
+
+    get_read_only_handler(State(deps)).await
+}
+
+/// Handler for the DELETE subroute `/read-only`.
+///
+/// Disables read-only mode, resuming onward delivery.
+async fn disable_read_only_handler(State(deps): State<Deps>) -> Json<ReadOnlyStatus> {
+    deps.read_only.store(false, Ordering::Relaxed);
+    warn!("Read-only mode disabled");
+
+    get_read_only_handler(State(deps)).await
+}
+
+/// Whether ingestion from a source is paused. See [crate::ingestion].
+#[derive(Serialize)]
+struct IngestionStatus {
+    source: Source,
+    paused: bool,
+    /// How many requests have been held whilst paused.
+    held: usize,
+}
+
+/// Query params for the DELETE subroute `/ingestion/:source`.
+#[derive(Deserialize)]
+struct ResumeOptions {
+    /// Deliver the requests held whilst paused, rather than discarding them.
+    #[serde(default)]
+    catch_up: bool,
+}
+
+/// Handler for the GET subroute `/ingestion`.
+///
+/// Responds with an [IngestionStatus] for each source which can be paused, in
+/// `application/json` format.
+async fn get_ingestion_handler(State(deps): State<Deps>) -> Json<Vec<IngestionStatus>> {
+    Json(
+        PAUSABLE
+            .into_iter()
+            .map(|source| {
+                let held = deps.ingestion.held(source);
+
+                IngestionStatus {
+                    source,
+                    paused: held.is_some(),
+                    held: held.unwrap_or_default(),
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Respond to a request to pause or resume a source which can't be.
+fn unpausable(source: Source) -> Option<Response> {
+    (!PAUSABLE.contains(&source)).then(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Ingestion from {} can't be paused", source.as_str()),
+        )
+            .into_response()
+    })
+}
+
+/// Handler for the PUT subroute `/ingestion/:source`.
+///
+/// Pauses ingestion from a source, holding its requests whilst continuing to
+/// accept them. Responds as per [get_ingestion_handler].
+async fn pause_ingestion_handler(State(deps): State<Deps>, Path(source): Path<Source>) -> Response {
+    if let Some(res) = unpausable(source) {
+        return res;
+    }
+
+    if deps.ingestion.pause(source) {
+        warn!("Ingestion from {} paused", source.as_str());
+    }
+
+    get_ingestion_handler(State(deps)).await.into_response()
+}
+
+/// Handler for the DELETE subroute `/ingestion/:source`.
+///
+/// Resumes ingestion from a source, optionally catching up in the background
+/// on the requests it sent whilst paused as per [ResumeOptions]. Responds as
+/// per [get_ingestion_handler].
+async fn resume_ingestion_handler(
+    State(deps): State<Deps>,
+    Path(source): Path<Source>,
+    Query(opts): Query<ResumeOptions>,
+) -> Response {
+    if let Some(res) = unpausable(source) {
+        return res;
+    }
+
+    match deps.ingestion.resume(source) {
+        None => {}
+        Some(xs) if opts.catch_up => {
+            warn!("Ingestion from {} resumed, catching up", source.as_str());
+            tokio::spawn(catch_up(deps.clone(), source, xs));
+        }
+        Some(xs) => warn!(
+            "Ingestion from {} resumed, discarding {} held requests",
+            source.as_str(),
+            xs.len()
+        ),
+    }
+
+    get_ingestion_handler(State(deps)).await.into_response()
+}
+
+/// Handler for the GET subroute `/chaos`.
+///
+/// Responds with the [ChaosConfig] currently in effect, if any, in
+/// `application/json` format.
+#[cfg(feature = "chaos")]
+async fn get_chaos_handler(State(deps): State<Deps>) -> Json<Option<ChaosConfig>> {
+    Json(deps.slack_chaos.get())
+}
+
+/// Handler for the PUT subroute `/chaos`.
+///
+/// Accepts a [ChaosConfig] in `application/json` format, injecting its faults
+/// into subsequent calls to Slack. See [crate::slack::chaos].
+#[cfg(feature = "chaos")]
+async fn set_chaos_handler(
+    State(deps): State<Deps>,
+    Json(cfg): Json<ChaosConfig>,
+) -> Json<Option<ChaosConfig>> {
+    warn!("Injecting faults into calls to Slack: {:?}", cfg);
+    deps.slack_chaos.set(Some(cfg));
+
+    get_chaos_handler(State(deps)).await
+}
+
+/// Handler for the DELETE subroute `/chaos`.
+///
+/// Stops injecting faults.
+#[cfg(feature = "chaos")]
+async fn clear_chaos_handler(State(deps): State<Deps>) -> Json<Option<ChaosConfig>> {
+    deps.slack_chaos.set(None);
+    warn!("Stopped injecting faults into calls to Slack");
+
+    get_chaos_handler(State(deps)).await
+}
+
+/// Handler for the PUT subroute `/secrets`.
+///
+/// Accepts a [SecretsRotation] in `application/json` format, atomically
+/// replacing each supplied secret without interrupting in-flight requests.
+/// Rotated secrets aren't persisted, so the underlying configuration should be
+/// updated too.
+async fn rotate_secrets_handler(
+    State(deps): State<Deps>,
+    Json(x): Json<SecretsRotation>,
+) -> Response {
+    let is_empty = x.slack_token.as_ref().is_some_and(|t| t.is_empty())
+        || x.heroku_secrets
+            .iter()
+            .chain(x.api_tokens.iter())
+            .flatten()
+            .any(|t| t.is_empty());
+    if is_empty {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            String::from("Secrets must not be empty"),
+        )
+            .into_response();
+    }
+
+    let expires_at = match x
+        .slack_token_expires_at
+        .map(|x| DateTime::from_timestamp(x, 0))
+    {
+        None => None,
+        Some(Some(x)) => Some(x),
+        Some(None) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                String::from("Invalid Slack token expiry"),
+            )
+                .into_response()
+        }
+    };
+
+    if let Some(t) = x.slack_token {
+        deps.slack_token.store(Arc::new(SlackAccessToken(t)));
+        deps.slack_token_expires_at.store(expires_at.map(Arc::new));
+        warn!("Rotated Slack token");
+    }
+
+    if let Some(xs) = x.heroku_secrets {
+        let n = xs.len();
+        deps.heroku_secrets
+            .store(Arc::new(xs.into_iter().map(HerokuSecret).collect()));
+        warn!("Rotated Heroku secrets, {} now valid", n);
+    }
+
+    if let Some(xs) = x.api_tokens {
+        let n = xs.len();
+        deps.api_tokens.store(Arc::new(
+            xs.iter()
+                .enumerate()
+                .map(|(i, x)| ApiToken::parse(x, i))
+                .collect(),
+        ));
+        warn!("Rotated API tokens, {} now valid", n);
+    }
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+/// Handler for the POST subroute `/selftest`.
+///
+/// Sends a canary message to `$SELFTEST_CHANNEL` through the full delivery
+/// pipeline, responding with a [SelftestResult] in `application/json` format.
+/// Failed deliveries respond with a 502 for the benefit of synthetic
+/// monitoring.
+async fn selftest_handler(State(deps): State<Deps>) -> Response {
+    let Some(channel) = deps.selftest_channel.clone() else {
+        return (
+            StatusCode::PRECONDITION_FAILED,
+            String::from("No self-test channel configured"),
+        )
+            .into_response();
+    };
+
+    let msg = Message {
+        timestamp: Some(Utc::now()),
+        ..Message::new(
+            channel,
+            "Mercury self-test",
+            "This is a canary message and can be safely ignored.",
+        )
+    };
+
+    let start = Instant::now();
+    let res = deliver(&deps, &msg, Source::Selftest).await;
+    let latency_ms = start.elapsed().as_millis();
+
+    let (code, x) = match res {
+        Ok(d) => (
+            StatusCode::OK,
+            SelftestResult {
+                ok: true,
+                latency_ms,
+                suppressed: match d {
+                    Delivery::Sent => None,
+                    Delivery::Suppressed(reason) => Some(reason),
+                },
+                error: None,
+            },
+        ),
+        Err(e) => {
+            warn!("Self-test failed: {}", e);
+
+            (
+                StatusCode::BAD_GATEWAY,
+                SelftestResult {
+                    ok: false,
+                    latency_ms,
+                    suppressed: None,
+                    error: Some(e.to_string()),
+                },
+            )
+        }
+    };
+
+    (code, Json(x)).into_response()
+}
+
+/// Handler for the GET subroute `/stats`.
+///
+/// Responds with a [StatsReport] in `application/json` format. See
+/// [crate::stats].
+async fn get_stats_handler(State(deps): State<Deps>) -> Json<StatsReport> {
+    Json(deps.stats.report(Utc::now()))
+}
+
+/// Handler for the GET subroute `/captures/:source`.
+///
+/// Responds with the source's most recent [Capture]s, most recent first, in
+/// `application/json` format, if capturing is enabled. See [crate::capture].
+async fn get_captures_handler(
+    State(deps): State<Deps>,
+    Path(source): Path<Source>,
+) -> Result<Json<Vec<Capture>>, StatusCode> {
+    deps.captures
+        .as_ref()
+        .map(|x| Json(x.get(source)))
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// The current state of fixture recording.
+#[derive(Serialize)]
+struct FixtureStatus {
+    recording: bool,
+}
+
+/// Handler for the GET subroute `/fixtures`.
+///
+/// Responds with a [FixtureStatus] in `application/json` format, if fixture
+/// recording is enabled. See [crate::fixture].
+async fn get_fixtures_handler(State(deps): State<Deps>) -> Result<Json<FixtureStatus>, StatusCode> {
+    deps.fixtures
+        .as_ref()
+        .map(|x| {
+            Json(FixtureStatus {
+                recording: x.is_recording(),
+            })
+        })
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Handler for the PUT subroute `/fixtures`.
+///
+/// Starts recording every inbound payload as a fixture.
+async fn start_fixtures_handler(
+    State(deps): State<Deps>,
+) -> Result<Json<FixtureStatus>, StatusCode> {
+    let x = deps.fixtures.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    x.set_recording(true);
+    warn!("Fixture recording started");
+
+    get_fixtures_handler(State(deps)).await
+}
+
+/// Handler for the DELETE subroute `/fixtures`.
+///
+/// Stops recording fixtures.
+async fn stop_fixtures_handler(
+    State(deps): State<Deps>,
+) -> Result<Json<FixtureStatus>, StatusCode> {
+    let x = deps.fixtures.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    x.set_recording(false);
+    info!("Fixture recording stopped");
+
+    get_fixtures_handler(State(deps)).await
+}
+
+/// Handler for the GET subroute `/audit/:id`.
+///
+/// Responds with the [AuditEntry] in `application/json` format, if it's still
+/// retained.
+async fn get_audit_entry_handler(
+    State(deps): State<Deps>,
+    Path(id): Path<u64>,
+) -> Result<Json<AuditEntry>, StatusCode> {
+    deps.audit.get(id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Handler for the POST subroute `/audit/:id/replay`.
+///
+/// Delivers the message from an [AuditEntry] again, irrespective of whether
+/// it was originally delivered. The message is as it was decoded and routed at
+/// the time, as the inbound payload isn't retained, so only rendering reflects
+/// the current configuration. A mis-routed message can be redirected with a
+/// `channel` query param, as described by [ReplayOptions]. Responds as per
+/// [crate::slack::router].
+async fn replay_audit_entry_handler(
+    State(deps): State<Deps>,
+    Path(id): Path<u64>,
+    Query(opts): Query<ReplayOptions>,
+) -> Response {
+    let Some(mut entry) = deps.audit.get(id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if let Some(x) = opts.channel {
+        info!("Replaying audit entry {} to {}", id, x);
+        entry.message.channel = x;
+    } else {
+        info!("Replaying audit entry {}", id);
+    }
+
+    match deliver(&deps, &entry.message, Source::Replay).await {
+        Ok(Delivery::Sent) => (StatusCode::OK, String::new()).into_response(),
+        Ok(Delivery::Suppressed(reason)) => {
+            (StatusCode::OK, [(SUPPRESSED_HEADER, reason)], String::new()).into_response()
+        }
+        Err(e) => slack_err_response(&e),
+    }
+}
+
+/// Handler for the GET subroute `/audit/export`.
+///
+/// Streams audit history, optionally within a time range, as described by
+/// [ExportOptions]. See [crate::audit::export].
+async fn export_audit_handler(
+    State(deps): State<Deps>,
+    Query(opts): Query<ExportOptions>,
+) -> Response {
+    match deps.audit.export(opts.format, opts.from, opts.to).await {
+        Ok(x) => (
+            [(CONTENT_TYPE, opts.format.content_type())],
+            Body::from_stream(x),
+        )
+            .into_response(),
+        Err(e) => {
+            let msg = format!("Failed to read audit history: {}", e);
+            warn!(msg);
+
+            (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response()
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! A small web UI for routine operational checks and actions, so that
+//! operators needn't reach for curl.
+//!
+//! The page itself is static and embedded in the binary. It prompts for
+//! `$ADMIN_TOKEN`, which it keeps for the browser session, and otherwise talks
+//! to the admin API like any other client, principally via its GraphQL schema.
+//! See [super::graphql].
+
+use axum::{
+    http::header::CACHE_CONTROL,
+    response::{Html, IntoResponse},
+};
+
+const INDEX: &str = include_str!("ui/index.html");
+
+/// Handler for the GET subroute `/ui`.
+///
+/// Unauthenticated, as the page contains no data of its own.
+pub async fn ui_handler() -> impl IntoResponse {
+    ([(CACHE_CONTROL, "no-cache")], Html(INDEX))
+}
@@ -0,0 +1,233 @@
+//! A bounded, in-memory history of every message delivered, or that we
+//! attempted to deliver, enabling messages to be inspected and replayed.
+//!
+//! The most recent `$AUDIT_CAPACITY` entries are retained in memory,
+//! defaulting to [DEFAULT_CAPACITY]. For history to survive restarts and
+//! exceed this capacity, entries can additionally be appended as JSON Lines to
+//! the file at `$AUDIT_LOG_PATH`, from which they can be [export]ed.
+
+pub mod export;
+
+use crate::{
+    delivery::{Delivery, Source},
+    slack::Message,
+    slack::SlackError,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tracing::warn;
+
+/// How many entries are retained by default.
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// A single delivery attempt.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub message: Message,
+    pub outcome: Outcome,
+    /// Where the message originated. Absent from entries persisted before
+    /// this was recorded.
+    #[serde(default)]
+    pub source: Option<Source>,
+}
+
+/// How a delivery attempt turned out.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Outcome {
+    Sent,
+    Suppressed { reason: String },
+    Failed { error: String },
+}
+
+/// The history itself, safe to share across requests.
+pub struct AuditLog {
+    capacity: usize,
+    next_id: AtomicU64,
+    entries: Mutex<VecDeque<AuditEntry>>,
+    /// Where entries are persisted, if anywhere.
+    file: Option<(PathBuf, Mutex<File>)>,
+}
+
+impl AuditLog {
+    pub fn new(capacity: usize) -> Self {
+        AuditLog {
+            capacity,
+            next_id: AtomicU64::new(1),
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+            file: None,
+        }
+    }
+
+    /// How many entries are retained in memory.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Additionally persist entries to a file, creating it if necessary.
+    ///
+    /// IDs continue from the last persisted entry so that they remain unique
+    /// across restarts.
+    pub fn with_persistence(mut self, path: PathBuf) -> io::Result<Self> {
+        let last_id = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|l| serde_json::from_str::<AuditEntry>(l).ok())
+            .map(|x| x.id)
+            .max();
+        if let Some(x) = last_id {
+            self.next_id = AtomicU64::new(x + 1);
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.file = Some((path, Mutex::new(file)));
+
+        Ok(self)
+    }
+
+    /// Record a delivery attempt, evicting the oldest entry if we're at
+    /// capacity. Returns the new entry's ID.
+    pub fn record(&self, msg: &Message, source: Source, res: &Result<Delivery, SlackError>) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let outcome = match res {
+            Ok(Delivery::Sent) => Outcome::Sent,
+            Ok(Delivery::Suppressed(reason)) => Outcome::Suppressed {
+                reason: reason.to_string(),
+            },
+            Err(e) => Outcome::Failed {
+                error: e.to_string(),
+            },
+        };
+
+        let entry = AuditEntry {
+            id,
+            at: Utc::now(),
+            message: msg.clone(),
+            outcome,
+            source: Some(source),
+        };
+
+        if let Some((path, file)) = &self.file {
+            // Serializing can only fail on non-string map keys, of which we
+            // have none.
+            let line = serde_json::to_string(&entry).unwrap() + "\n";
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+
+            if let Err(e) = file.write_all(line.as_bytes()) {
+                warn!("Failed to persist audit entry {} to {:?}: {}", id, path, e);
+            }
+        }
+
+        // A poisoned lock would only imply a panic mid-push, leaving the
+        // history itself intact.
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        if self.capacity > 0 {
+            entries.push_back(entry);
+        }
+
+        id
+    }
+
+    /// Get an entry by its ID, if it's still retained.
+    pub fn get(&self, id: u64) -> Option<AuditEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Entries are ordered by ID, but may have gaps when capacity is zero.
+        entries
+            .binary_search_by_key(&id, |x| x.id)
+            .ok()
+            .and_then(|i| entries.get(i))
+            .cloned()
+    }
+
+    /// Get up to `limit` of the most recently retained entries satisfying a
+    /// predicate, newest first.
+    pub fn recent(&self, limit: usize, f: impl Fn(&AuditEntry) -> bool) -> Vec<AuditEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+
+        entries
+            .iter()
+            .rev()
+            .filter(|x| f(x))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::channel::ChannelName;
+
+    pub fn msg(title: &str) -> Message {
+        Message::new(ChannelName("any".into()), title, "any")
+    }
+
+    #[test]
+    fn test_record_and_get() {
+        let log = AuditLog::new(2);
+
+        let a = log.record(&msg("a"), Source::Api, &Ok(Delivery::Sent));
+        let b = log.record(
+            &msg("b"),
+            Source::Api,
+            &Ok(Delivery::Suppressed("read-only")),
+        );
+        let c = log.record(
+            &msg("c"),
+            Source::Api,
+            &Err(SlackError::APIResponseError("oops".into())),
+        );
+
+        assert_eq!((a, b, c), (1, 2, 3));
+
+        // Evicted.
+        assert!(log.get(a).is_none());
+
+        let b = log.get(b).unwrap();
+        assert_eq!(b.message.title, "b");
+        assert!(matches!(b.outcome, Outcome::Suppressed { reason } if reason == "read-only"));
+
+        let c = log.get(c).unwrap();
+        assert!(matches!(c.outcome, Outcome::Failed { .. }));
+
+        assert!(log.get(4).is_none());
+    }
+
+    #[test]
+    fn test_recent() {
+        let log = AuditLog::new(3);
+
+        log.record(&msg("a"), Source::Api, &Ok(Delivery::Sent));
+        log.record(&msg("b"), Source::Heroku, &Ok(Delivery::Sent));
+        log.record(&msg("c"), Source::Api, &Ok(Delivery::Sent));
+        log.record(&msg("d"), Source::Api, &Ok(Delivery::Sent));
+
+        let titles =
+            |xs: Vec<AuditEntry>| xs.into_iter().map(|x| x.message.title).collect::<Vec<_>>();
+
+        assert_eq!(titles(log.recent(10, |_| true)), vec!["d", "c", "b"]);
+        assert_eq!(titles(log.recent(1, |_| true)), vec!["d"]);
+        assert_eq!(
+            titles(log.recent(10, |x| x.source == Some(Source::Api))),
+            vec!["d", "c"]
+        );
+    }
+}
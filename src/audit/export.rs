@@ -0,0 +1,223 @@
+//! Export audit history over a time range as CSV or JSON Lines, streaming
+//! from the persisted history if there is one so as not to buffer it all.
+
+use super::{AuditEntry, AuditLog, Outcome};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{io, pin::Pin};
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader},
+};
+use tokio_stream::{wrappers::LinesStream, Stream, StreamExt};
+
+/// The supported export formats.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON-encoded [AuditEntry] per line.
+    #[default]
+    Json,
+    Csv,
+}
+
+/// A stream of export output, ready to be sent as a response body.
+pub type ExportStream = Pin<Box<dyn Stream<Item = io::Result<String>> + Send>>;
+
+/// The header row of CSV exports.
+const CSV_HEADER: &str = "id,at,channel,title,desc,status,detail\n";
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+
+    fn fmt_entry(&self, x: &AuditEntry) -> String {
+        match self {
+            // This can only fail on non-string map keys, of which we have none.
+            ExportFormat::Json => serde_json::to_string(x).unwrap() + "\n",
+            ExportFormat::Csv => fmt_csv_row(x),
+        }
+    }
+}
+
+/// Whether an entry falls within an optional, inclusive time range.
+fn in_range(x: &AuditEntry, from: Option<DateTime<Utc>>, to: Option<DateTime<Utc>>) -> bool {
+    from.map_or(true, |f| x.at >= f) && to.map_or(true, |t| x.at <= t)
+}
+
+impl AuditLog {
+    /// Stream every entry within an optional, inclusive time range, oldest
+    /// first. Entries are read from the persisted history if there is one,
+    /// and otherwise from memory.
+    pub async fn export(
+        &self,
+        format: ExportFormat,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> io::Result<ExportStream> {
+        let header = tokio_stream::iter(match format {
+            ExportFormat::Json => None,
+            ExportFormat::Csv => Some(Ok(CSV_HEADER.to_owned())),
+        });
+
+        let entries: Pin<Box<dyn Stream<Item = io::Result<AuditEntry>> + Send>> = match &self.file {
+            Some((path, _)) => {
+                let lines = LinesStream::new(BufReader::new(File::open(path).await?).lines());
+
+                // Skip anything unparseable, such as a line partially written
+                // during a crash.
+                Box::pin(lines.filter_map(|l| match l {
+                    Ok(l) => serde_json::from_str(&l).ok().map(Ok),
+                    Err(e) => Some(Err(e)),
+                }))
+            }
+            None => {
+                let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+                let xs: Vec<_> = entries.iter().cloned().map(Ok).collect();
+
+                Box::pin(tokio_stream::iter(xs))
+            }
+        };
+
+        let rows = entries.filter_map(move |x| match x {
+            Ok(x) => in_range(&x, from, to).then(|| Ok(format.fmt_entry(&x))),
+            Err(e) => Some(Err(e)),
+        });
+
+        Ok(Box::pin(header.chain(rows)))
+    }
+}
+
+fn fmt_csv_row(x: &AuditEntry) -> String {
+    let (status, detail) = match &x.outcome {
+        Outcome::Sent => ("sent", ""),
+        Outcome::Suppressed { reason } => ("suppressed", reason.as_str()),
+        Outcome::Failed { error } => ("failed", error.as_str()),
+    };
+
+    let fields = [
+        x.id.to_string(),
+        x.at.to_rfc3339(),
+        x.message.channel.to_string(),
+        x.message.title.to_owned(),
+        x.message.desc.to_owned(),
+        status.to_owned(),
+        detail.to_owned(),
+    ];
+
+    fields.map(|x| escape_csv(&x)).join(",") + "\n"
+}
+
+/// Quote a CSV field if necessary, as per RFC 4180.
+///
+/// ```
+/// assert_eq!(escape_csv("a, \"b\""), "\"a, \"\"b\"\"\"");
+/// ```
+fn escape_csv(x: &str) -> String {
+    if x.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", x.replace('"', "\"\""))
+    } else {
+        x.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audit::tests::msg,
+        delivery::{Delivery, Source},
+        slack::SlackError,
+    };
+
+    async fn collect(s: ExportStream) -> String {
+        s.collect::<io::Result<Vec<_>>>().await.unwrap().concat()
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a, b"), "\"a, b\"");
+        assert_eq!(escape_csv("a \"b\""), "\"a \"\"b\"\"\"");
+        assert_eq!(escape_csv("a\nb"), "\"a\nb\"");
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() {
+        let log = AuditLog::new(10);
+        log.record(&msg("a"), Source::Api, &Ok(Delivery::Sent));
+        log.record(
+            &msg("b, c"),
+            Source::Api,
+            &Err(SlackError::APIResponseError("oops".into())),
+        );
+
+        let out = collect(log.export(ExportFormat::Csv, None, None).await.unwrap()).await;
+        let lines: Vec<_> = out.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert!(lines[1].starts_with("1,"));
+        assert!(lines[1].ends_with(",any,a,any,sent,"));
+        assert!(lines[2].ends_with(",any,\"b, c\",any,failed,Slack API returned error: oops"));
+    }
+
+    #[tokio::test]
+    async fn test_export_range() {
+        let log = AuditLog::new(10);
+        log.record(&msg("a"), Source::Api, &Ok(Delivery::Sent));
+
+        let past = Utc::now() - chrono::Duration::days(1);
+        let future = Utc::now() + chrono::Duration::days(1);
+
+        let within = collect(
+            log.export(ExportFormat::Json, Some(past), Some(future))
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(within.lines().count(), 1);
+
+        let outside = collect(
+            log.export(ExportFormat::Json, Some(future), None)
+                .await
+                .unwrap(),
+        )
+        .await;
+        assert!(outside.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_persisted() {
+        let path = std::env::temp_dir().join("mercury-test-export-persisted.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let log = AuditLog::new(1).with_persistence(path.clone()).unwrap();
+        log.record(&msg("a"), Source::Api, &Ok(Delivery::Sent));
+        log.record(
+            &msg("b"),
+            Source::Api,
+            &Ok(Delivery::Suppressed("read-only")),
+        );
+
+        // Exceeds the in-memory capacity.
+        let out = collect(log.export(ExportFormat::Json, None, None).await.unwrap()).await;
+        let entries: Vec<AuditEntry> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].message.title, "a");
+        assert_eq!(entries[1].message.title, "b");
+
+        // IDs continue across restarts.
+        let log = AuditLog::new(1).with_persistence(path.clone()).unwrap();
+        assert_eq!(log.record(&msg("c"), Source::Api, &Ok(Delivery::Sent)), 3);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
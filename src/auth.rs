@@ -0,0 +1,219 @@
+//! Bearer token authentication of inbound requests to Mercury's own API.
+//!
+//! Clients authenticate with any of the tokens in `$MERCURY_API_TOKEN`, a
+//! comma-separated list to allow for rotation. Historically clients instead
+//! used `$SLACK_TOKEN`, which is still accepted in compatibility mode, enabled
+//! by default only if `$MERCURY_API_TOKEN` is unset.
+//!
+//! Tokens may be named, as `name:token`, identifying clients in traces and
+//! metrics. Unnamed tokens are named by their position.
+
+use axum::http::{Extensions, HeaderValue};
+use sha2::{Digest, Sha256};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use subtle::ConstantTimeEq;
+
+/// A token for Mercury's own API, distinct from the Slack access token used to
+/// talk to Slack.
+#[derive(Clone)]
+pub struct ApiToken {
+    /// Identifies the client, not secret.
+    pub name: String,
+    pub token: String,
+}
+
+impl ApiToken {
+    /// Parse a token as `name:token`, or a bare token which is named by its
+    /// zero-indexed position. Names consist only of ASCII letters, digits,
+    /// hyphens, and underscores, so that a bare token containing a colon
+    /// isn't mistaken for a name.
+    pub fn parse(x: &str, position: usize) -> Self {
+        match x.split_once(':').filter(|(name, _)| is_valid_name(name)) {
+            Some((name, token)) => ApiToken {
+                name: name.to_owned(),
+                token: token.to_owned(),
+            },
+            None => ApiToken {
+                name: format!("token-{}", position),
+                token: x.to_owned(),
+            },
+        }
+    }
+}
+
+fn is_valid_name(x: &str) -> bool {
+    !x.is_empty()
+        && x.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Whether a request has been authenticated, however its route does so, for
+/// the sake of middleware wrapping routes which authenticate independently.
+///
+/// Tracked only once [Authenticated::track] has been called on a request's
+/// extensions, after which [Authenticated::mark] within records success.
+#[derive(Clone, Default)]
+pub struct Authenticated(Arc<AtomicBool>);
+
+impl Authenticated {
+    /// Start tracking a request, or continue tracking it if something further
+    /// out already is.
+    pub fn track(x: &mut Extensions) -> Self {
+        x.get_or_insert_default::<Self>().clone()
+    }
+
+    /// Record that a request has been authenticated, if it's being tracked.
+    pub fn mark(x: &Extensions) {
+        if let Some(x) = x.get::<Self>() {
+            x.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_marked(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Parse API tokens from their environment variable representation.
+///
+/// ```
+/// let xs = parse_api_tokens("foo, ci:bar");
+/// assert_eq!(xs.len(), 2);
+/// ```
+pub fn parse_api_tokens(x: &str) -> Vec<ApiToken> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .enumerate()
+        .map(|(i, x)| ApiToken::parse(x, i))
+        .collect()
+}
+
+/// Test an `Authorization` header value against a set of accepted `Bearer`
+/// tokens.
+///
+/// Every accepted token is compared against so as not to leak which matched.
+pub fn is_valid_bearer<'a>(val: &HeaderValue, accepted: impl Iterator<Item = &'a str>) -> bool {
+    find_bearer(val, accepted.map(|t| ((), t))).is_some()
+}
+
+/// Find which of a set of accepted `Bearer` tokens an `Authorization` header
+/// value matches, if any, identified by whatever accompanies each token.
+///
+/// As per [is_valid_bearer] every accepted token is compared against.
+pub fn find_bearer<'a, T>(
+    val: &HeaderValue,
+    accepted: impl Iterator<Item = (T, &'a str)>,
+) -> Option<T> {
+    let x = val.to_str().ok()?.strip_prefix("Bearer ")?;
+
+    accepted.fold(None, |acc, (id, t)| match constant_time_eq(t, x) {
+        true => Some(id),
+        false => acc,
+    })
+}
+
+/// Compare two secrets in constant time to avoid leaking how much of them
+/// matches via timing. They're hashed first so that their lengths are
+/// similarly not leaked.
+pub fn constant_time_eq(x: &str, y: &str) -> bool {
+    Sha256::digest(x).ct_eq(&Sha256::digest(y)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_tokens() {
+        let xs = parse_api_tokens("foo, ci:bar,,");
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].name, "token-0");
+        assert_eq!(xs[0].token, "foo");
+        assert_eq!(xs[1].name, "ci");
+        assert_eq!(xs[1].token, "bar");
+
+        let xs = parse_api_tokens("a+b/c:d=, ci:e:f, :g");
+
+        assert_eq!(xs[0].name, "token-0");
+        assert_eq!(xs[0].token, "a+b/c:d=");
+        assert_eq!(xs[1].name, "ci");
+        assert_eq!(xs[1].token, "e:f");
+        assert_eq!(xs[2].name, "token-2");
+        assert_eq!(xs[2].token, ":g");
+    }
+
+    #[test]
+    fn test_authenticated() {
+        let mut xs = Extensions::new();
+        Authenticated::mark(&xs);
+        assert!(xs.get::<Authenticated>().is_none());
+
+        let outer = Authenticated::track(&mut xs);
+        let inner = Authenticated::track(&mut xs);
+        assert!(!outer.is_marked());
+
+        Authenticated::mark(&xs);
+        assert!(outer.is_marked());
+        assert!(inner.is_marked());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("foo", "foo"));
+        assert!(constant_time_eq("", ""));
+        assert!(!constant_time_eq("foo", "bar"));
+        assert!(!constant_time_eq("foo", "foobar"));
+        assert!(!constant_time_eq("foo", ""));
+    }
+
+    quickcheck! {
+      fn test_constant_time_eq_matches_eq(x: String, y: String) -> bool {
+          constant_time_eq(&x, &y) == (x == y)
+      }
+    }
+
+    #[test]
+    fn test_is_valid_bearer() {
+        let accepted = || ["foo", "bar"].into_iter();
+
+        assert!(is_valid_bearer(
+            &HeaderValue::from_static("Bearer foo"),
+            accepted()
+        ));
+        assert!(is_valid_bearer(
+            &HeaderValue::from_static("Bearer bar"),
+            accepted()
+        ));
+        assert!(!is_valid_bearer(
+            &HeaderValue::from_static("Bearer baz"),
+            accepted()
+        ));
+        assert!(!is_valid_bearer(
+            &HeaderValue::from_static("foo"),
+            accepted()
+        ));
+        assert!(!is_valid_bearer(
+            &HeaderValue::from_static("Bearer "),
+            accepted()
+        ));
+    }
+
+    #[test]
+    fn test_find_bearer() {
+        let accepted = || [("a", "foo"), ("b", "bar")].into_iter();
+
+        assert_eq!(
+            find_bearer(&HeaderValue::from_static("Bearer bar"), accepted()),
+            Some("b")
+        );
+        assert_eq!(
+            find_bearer(&HeaderValue::from_static("Bearer baz"), accepted()),
+            None
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! A map with a maximum number of entries, for in-memory state keyed by
+//! something outside of our control, such as app names or channels, which
+//! would otherwise grow for the life of the process. Once full, inserting a new
+//! key evicts the least recently written entry.
+//!
+//! Each map reports how many entries it holds and how many it's evicted as
+//! metrics labelled by name, so that a capacity that's too small shows up as
+//! evictions rather than as subtly forgotten state.
+
+use crate::metrics::Metrics;
+use prometheus::{IntCounter, IntGauge};
+use std::{borrow::Borrow, collections::HashMap, hash::Hash};
+
+/// A map's occupancy and eviction metrics.
+#[derive(Clone)]
+pub struct StateMetrics {
+    entries: IntGauge,
+    evictions: IntCounter,
+}
+
+impl StateMetrics {
+    /// The metrics of the map with the given name.
+    pub fn new(metrics: &Metrics, name: &str) -> Self {
+        StateMetrics {
+            entries: metrics.state_entries.with_label_values(&[name]),
+            evictions: metrics.state_evictions.with_label_values(&[name]),
+        }
+    }
+}
+
+struct Slot<V> {
+    value: V,
+    /// When the entry was last written, relative to other entries.
+    written: u64,
+}
+
+/// A map holding at most a given number of entries.
+pub struct BoundedMap<K, V> {
+    capacity: usize,
+    slots: HashMap<K, Slot<V>>,
+    clock: u64,
+    metrics: StateMetrics,
+}
+
+impl<K: Clone + Eq + Hash, V> BoundedMap<K, V> {
+    pub fn new(capacity: usize, metrics: StateMetrics) -> Self {
+        BoundedMap {
+            capacity,
+            slots: HashMap::new(),
+            clock: 0,
+            metrics,
+        }
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.slots.get(k).map(|x| &x.value)
+    }
+
+    /// Get an entry to modify, which counts as writing to it.
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let written = self.tick();
+
+        self.slots.get_mut(k).map(|x| {
+            x.written = written;
+            &mut x.value
+        })
+    }
+
+    /// Insert an entry, returning the value it replaced, if any. If the map's
+    /// full and the key's new then the least recently written entry is
+    /// evicted.
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let written = self.tick();
+
+        if self.capacity == 0 {
+            self.metrics.evictions.inc();
+            return None;
+        }
+
+        if self.slots.len() >= self.capacity && !self.slots.contains_key(&k) {
+            self.evict();
+        }
+
+        let prev = self
+            .slots
+            .insert(k, Slot { value: v, written })
+            .map(|x| x.value);
+        self.report();
+
+        prev
+    }
+
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let x = self.slots.remove(k).map(|x| x.value);
+        self.report();
+
+        x
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.slots.retain(|k, x| f(k, &mut x.value));
+        self.report();
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.report();
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Evict the least recently written entry. Linear, but only when full.
+    fn evict(&mut self) {
+        let oldest = self
+            .slots
+            .iter()
+            .min_by_key(|(_, x)| x.written)
+            .map(|(k, _)| k.clone());
+
+        if let Some(k) = oldest {
+            self.slots.remove(&k);
+            self.metrics.evictions.inc();
+        }
+    }
+
+    fn report(&self) {
+        self.metrics.entries.set(self.slots.len() as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_map() {
+        let metrics = Metrics::new();
+        let mut x = BoundedMap::new(2, StateMetrics::new(&metrics, "test"));
+
+        x.insert("a", 1);
+        x.insert("b", 2);
+        assert_eq!(x.insert("a", 3), Some(1));
+
+        // "b" was written least recently.
+        x.insert("c", 4);
+        assert_eq!(x.get("b"), None);
+
+        // Modifying counts as writing.
+        *x.get_mut("a").unwrap() += 1;
+        x.insert("d", 5);
+        assert_eq!(x.get("a"), Some(&4));
+        assert_eq!(x.get("c"), None);
+
+        x.remove("a");
+        let encoded = metrics.encode();
+        assert!(encoded.contains("mercury_state_entries{state=\"test\"} 1\n"));
+        assert!(encoded.contains("mercury_state_evictions_total{state=\"test\"} 2\n"));
+    }
+}
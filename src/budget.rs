@@ -0,0 +1,271 @@
+//! Cap how many messages a channel receives per hour, so that a misbehaving
+//! source can't drown out everything else.
+//!
+//! Budgets are configured via `$NOISE_BUDGETS` as a comma-separated list of
+//! `channel:max` pairs, for example `alerts:20,deploys:50`. Once a channel's
+//! budget is spent, further messages within the hour are suppressed, remaining
+//! visible in the [crate::audit] history. When the window resets a single
+//! summary of how many messages were suppressed is posted in their place.
+
+use crate::{
+    router::Deps,
+    slack::{channel::ChannelName, Message, Severity},
+};
+use chrono::Utc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// How long each budget lasts before it's replenished.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How often to check for windows which have reset, so that summaries are
+/// posted even if a channel has since gone quiet.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Maps channel names, without any leading hash, to their maximum messages
+/// per [WINDOW].
+pub type BudgetLimits = HashMap<String, u32>;
+
+/// Parse budgets from their environment variable representation, ignoring
+/// invalid pairs.
+///
+/// ```
+/// let xs = parse_budget_limits("alerts:20, #deploys:50");
+/// assert_eq!(xs["deploys"], 50);
+/// ```
+pub fn parse_budget_limits(x: &str) -> BudgetLimits {
+    x.split(',')
+        .filter_map(|pair| {
+            let (channel, max) = pair.trim().split_once(':')?;
+
+            Some((normalise(channel), max.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Channel names can't contain hashes, so this lets consumers supply a leading
+/// hash or not.
+fn normalise(channel: &str) -> String {
+    channel.trim_start_matches('#').to_owned()
+}
+
+/// Whether a message fits within its channel's budget.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+    /// The message may be sent. If the previous window suppressed any
+    /// messages, a summary of how many is due first.
+    Allowed {
+        summary: Option<u32>,
+    },
+    Exceeded,
+}
+
+/// A channel's usage within the current window.
+struct Window {
+    started_at: Instant,
+    sent: u32,
+    suppressed: u32,
+}
+
+/// Tracks each budgeted channel's usage, safe to share across requests.
+pub struct NoiseBudgets {
+    limits: BudgetLimits,
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+impl NoiseBudgets {
+    pub fn new(limits: BudgetLimits) -> Self {
+        NoiseBudgets {
+            limits,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> &BudgetLimits {
+        &self.limits
+    }
+
+    /// Spend from a channel's budget, if it has one.
+    pub fn admit(&self, channel: &ChannelName) -> Admission {
+        let channel = normalise(&channel.0);
+        let Some(max) = self.limits.get(&channel) else {
+            return Admission::Allowed { summary: None };
+        };
+
+        let mut windows = self.windows.lock().unwrap();
+        let w = windows.entry(channel).or_insert_with(new_window);
+
+        let summary = match w.started_at.elapsed() >= WINDOW {
+            true => reset(w),
+            false => None,
+        };
+
+        if w.sent < *max {
+            w.sent += 1;
+            Admission::Allowed { summary }
+        } else {
+            w.suppressed += 1;
+            Admission::Exceeded
+        }
+    }
+
+    /// Whether a channel's budget is currently spent, without spending from it.
+    pub fn is_exceeded(&self, channel: &ChannelName) -> bool {
+        let channel = normalise(&channel.0);
+        let Some(max) = self.limits.get(&channel) else {
+            return false;
+        };
+
+        self.windows
+            .lock()
+            .unwrap()
+            .get(&channel)
+            .is_some_and(|w| w.started_at.elapsed() < WINDOW && w.sent >= *max)
+    }
+
+    /// Reset windows which have elapsed, returning the channels owed a summary
+    /// and how many messages each had suppressed.
+    fn take_summaries(&self) -> Vec<(ChannelName, u32)> {
+        let mut windows = self.windows.lock().unwrap();
+
+        windows
+            .iter_mut()
+            .filter(|(_, w)| w.started_at.elapsed() >= WINDOW)
+            .filter_map(|(c, w)| reset(w).map(|n| (ChannelName(c.to_owned()), n)))
+            .collect()
+    }
+}
+
+fn new_window() -> Window {
+    Window {
+        started_at: Instant::now(),
+        sent: 0,
+        suppressed: 0,
+    }
+}
+
+/// Start a new window, returning how many messages the old one suppressed if
+/// any.
+fn reset(w: &mut Window) -> Option<u32> {
+    let suppressed = w.suppressed;
+    *w = new_window();
+
+    (suppressed > 0).then_some(suppressed)
+}
+
+/// Build a message summarising how many messages a channel had suppressed.
+pub fn summary_message(channel: ChannelName, suppressed: u32) -> Message {
+    let noun = match suppressed {
+        1 => "event",
+        _ => "events",
+    };
+
+    Message {
+        severity: Some(Severity::Warning),
+        timestamp: Some(Utc::now()),
+        ..Message::new(
+            channel,
+            "Mercury",
+            format!(
+                "{} more {} suppressed by this channel's noise budget (see audit log)",
+                suppressed, noun
+            ),
+        )
+    }
+}
+
+/// Indefinitely post summaries for windows which have reset, even if their
+/// channels have since gone quiet.
+pub async fn watch_budgets(deps: Deps, budgets: Arc<NoiseBudgets>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for (channel, n) in budgets.take_summaries() {
+            let token = deps.slack_token.load_full();
+            let msg = summary_message(channel, n);
+
+            match deps
+                .slack_client
+                .lock()
+                .await
+                .post_message(&msg, &token)
+                .await
+            {
+                Ok(_) => info!("Posted noise budget summary to {}", msg.channel),
+                Err(e) => warn!(
+                    "Failed to post noise budget summary to {}: {}",
+                    msg.channel, e
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_instant::MockClock;
+
+    #[test]
+    fn test_parse_budget_limits() {
+        let xs = parse_budget_limits("alerts:20, #deploys:50,invalid,oops:x");
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs["alerts"], 20);
+        assert_eq!(xs["deploys"], 50);
+    }
+
+    #[test]
+    fn test_admit() {
+        let x = NoiseBudgets::new(parse_budget_limits("alerts:2"));
+        let alerts = ChannelName("#alerts".into());
+        let allowed = Admission::Allowed { summary: None };
+
+        assert_eq!(x.admit(&ChannelName("other".into())), allowed);
+        assert!(!x.is_exceeded(&ChannelName("other".into())));
+
+        assert_eq!(x.admit(&alerts), allowed);
+        assert!(!x.is_exceeded(&alerts));
+        assert_eq!(x.admit(&alerts), allowed);
+        assert!(x.is_exceeded(&alerts));
+        assert_eq!(x.admit(&alerts), Admission::Exceeded);
+        assert_eq!(x.admit(&alerts), Admission::Exceeded);
+
+        MockClock::advance(WINDOW);
+        assert!(!x.is_exceeded(&alerts));
+        assert_eq!(x.admit(&alerts), Admission::Allowed { summary: Some(2) });
+        assert_eq!(x.admit(&alerts), allowed);
+    }
+
+    #[test]
+    fn test_take_summaries() {
+        let x = NoiseBudgets::new(parse_budget_limits("alerts:1,deploys:1"));
+        let alerts = ChannelName("alerts".into());
+
+        x.admit(&alerts);
+        x.admit(&alerts);
+        x.admit(&ChannelName("deploys".into()));
+        assert!(x.take_summaries().is_empty());
+
+        MockClock::advance(WINDOW);
+        let xs = x.take_summaries();
+        assert_eq!(xs.len(), 1);
+        assert_eq!(xs[0].0 .0, "alerts");
+        assert_eq!(xs[0].1, 1);
+
+        // Each summary is only taken once.
+        assert!(x.take_summaries().is_empty());
+        assert_eq!(x.admit(&alerts), Admission::Allowed { summary: None });
+    }
+}
@@ -0,0 +1,298 @@
+//! Briefly cache the responses of idempotent `GET` endpoints, namely those
+//! for administration, feeds, and deep health checks, and tag them with an
+//! `ETag`, so that dashboards polling Mercury during an incident neither
+//! recompute responses nor redownload them when they're unchanged.
+//!
+//! Responses are cached for `$RESPONSE_CACHE_SECS`, 5 by default, or 0 to
+//! disable caching whilst keeping `ETag`s. Any other request through the same
+//! endpoints, such as enabling read-only mode, clears the cache so that its
+//! effects are seen immediately.
+//!
+//! The cache sits behind any authentication, so only authenticated requests
+//! are served from it.
+
+use crate::bounded::{BoundedMap, StateMetrics};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{OriginalUri, Request, State},
+    http::{
+        header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// The default duration for which responses are cached.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(5);
+
+/// The most responses cached at once, beyond which the least recently cached
+/// are evicted.
+const MAX_ENTRIES: usize = 1024;
+
+/// A successful response, as cached.
+struct Entry {
+    at: Instant,
+    etag: HeaderValue,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Responses by URI, safe to share across requests.
+pub struct ResponseCache {
+    ttl: Duration,
+    entries: Mutex<BoundedMap<String, Arc<Entry>>>,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Duration, metrics: StateMetrics) -> Self {
+        ResponseCache {
+            ttl,
+            entries: Mutex::new(BoundedMap::new(MAX_ENTRIES, metrics)),
+        }
+    }
+
+    /// How long responses are cached for.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn get(&self, key: &str) -> Option<Arc<Entry>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|x| x.at.elapsed() < self.ttl)
+            .cloned()
+    }
+
+    fn insert(&self, key: String, entry: Arc<Entry>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, x| x.at.elapsed() < self.ttl);
+        entries.insert(key, entry);
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn cache_control(&self) -> HeaderValue {
+        match self.ttl.as_secs() {
+            0 => HeaderValue::from_static("private, no-cache"),
+            x => format!("private, max-age={}", x).parse().unwrap(),
+        }
+    }
+}
+
+/// Middleware serving `GET` requests from the cache, and clearing it upon any
+/// other request.
+pub async fn cache(State(cache): State<Arc<ResponseCache>>, req: Request, next: Next) -> Response {
+    match *req.method() {
+        Method::GET => {}
+        Method::HEAD | Method::OPTIONS => return next.run(req).await,
+        _ => {
+            let res = next.run(req).await;
+            cache.clear();
+
+            return res;
+        }
+    }
+
+    // Nested routers only see the remainder of the path.
+    let key = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(req.uri(), |x| &x.0)
+        .to_string();
+    let if_none_match = req.headers().get(IF_NONE_MATCH).cloned();
+
+    let entry = match cache.get(&key) {
+        Some(x) => x,
+        None => {
+            let res = next.run(req).await;
+            if res.status() != StatusCode::OK {
+                return res;
+            }
+
+            let (parts, body) = res.into_parts();
+            let Ok(body) = to_bytes(body, usize::MAX).await else {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+
+            let entry = Arc::new(Entry {
+                at: Instant::now(),
+                etag: to_etag(&body),
+                headers: parts.headers,
+                body,
+            });
+            cache.insert(key, entry.clone());
+
+            entry
+        }
+    };
+
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, entry.etag.clone());
+    headers.insert(CACHE_CONTROL, cache.cache_control());
+
+    if if_none_match.is_some_and(|x| matches_etag(&x, &entry.etag)) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    let mut res = Response::new(Body::from(entry.body.clone()));
+    res.headers_mut().extend(entry.headers.clone());
+    res.headers_mut().extend(headers);
+
+    res
+}
+
+/// A strong `ETag` of a body's digest.
+fn to_etag(body: &[u8]) -> HeaderValue {
+    format!("\"{}\"", hex::encode(Sha256::digest(body)))
+        .parse()
+        .unwrap()
+}
+
+/// Whether an `If-None-Match` header, which may list several tags, any of
+/// them weak, or be `*`, matches a tag.
+fn matches_etag(header: &HeaderValue, etag: &HeaderValue) -> bool {
+    let Ok(x) = header.to_str() else {
+        return false;
+    };
+
+    x.split(',')
+        .map(str::trim)
+        .any(|x| x == "*" || x.trim_start_matches("W/").as_bytes() == etag.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use axum::{middleware, routing::get, Router};
+    use mock_instant::MockClock;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn router(ttl: Duration) -> (Router, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_ = calls.clone();
+
+        let x = Router::new()
+            .route(
+                "/",
+                get(move || {
+                    let n = calls_.fetch_add(1, Ordering::Relaxed);
+                    async move { format!("{}", n / 2) }
+                })
+                .post(|| async { StatusCode::OK }),
+            )
+            .layer(middleware::from_fn_with_state(
+                Arc::new(ResponseCache::new(
+                    ttl,
+                    StateMetrics::new(&Metrics::new(), "responses"),
+                )),
+                cache,
+            ));
+
+        (x, calls)
+    }
+
+    fn req(method: Method, if_none_match: Option<&str>) -> Request {
+        let mut req = Request::builder().method(method).uri("/");
+        if let Some(x) = if_none_match {
+            req = req.header(IF_NONE_MATCH, x);
+        }
+
+        req.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_matches_etag() {
+        let etag = HeaderValue::from_static("\"abc\"");
+        let test = |x| matches_etag(&HeaderValue::from_static(x), &etag);
+
+        assert!(test("\"abc\""));
+        assert!(test("\"xyz\", W/\"abc\""));
+        assert!(test("*"));
+        assert!(!test("\"xyz\""));
+        assert!(!test("abc"));
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let (app, calls) = router(Duration::from_secs(5));
+
+        let res = app.clone().oneshot(req(Method::GET, None)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CACHE_CONTROL], "private, max-age=5");
+        let etag = res.headers()[ETAG].to_str().unwrap().to_owned();
+
+        let res = app.clone().oneshot(req(Method::GET, None)).await.unwrap();
+        assert_eq!(res.headers()[ETAG], etag.as_str());
+        assert_eq!(
+            to_bytes(res.into_body(), usize::MAX).await.unwrap(),
+            Bytes::from("0")
+        );
+
+        let res = app
+            .clone()
+            .oneshot(req(Method::GET, Some(&etag)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // Expired.
+        MockClock::advance(Duration::from_secs(5));
+        let res = app
+            .clone()
+            .oneshot(req(Method::GET, Some(&etag)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+        // Cleared, and changed.
+        app.clone().oneshot(req(Method::POST, None)).await.unwrap();
+        let res = app
+            .clone()
+            .oneshot(req(Method::GET, Some(&etag)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_ne!(res.headers()[ETAG], etag.as_str());
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cache_disabled() {
+        let (app, calls) = router(Duration::ZERO);
+
+        let res = app.clone().oneshot(req(Method::GET, None)).await.unwrap();
+        assert_eq!(res.headers()[CACHE_CONTROL], "private, no-cache");
+        let etag = res.headers()[ETAG].to_str().unwrap().to_owned();
+
+        let res = app
+            .clone()
+            .oneshot(req(Method::GET, Some(&etag)))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
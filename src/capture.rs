@@ -0,0 +1,189 @@
+//! Optionally retain the most recent inbound payloads from each source, so
+//! that "why didn't this webhook produce a message?" can be answered by
+//! inspecting exactly what arrived, and how it was responded to.
+//!
+//! Enabled by setting `$CAPTURE_PAYLOADS` to how many payloads to retain per
+//! source, for example `CAPTURE_PAYLOADS=20`. Payloads are passed through
+//! [crate::redact] first, and only kept in memory. They're accessible via the
+//! admin API. See [crate::admin].
+//!
+//! Only authenticated requests are captured, so that anyone else can't push
+//! genuine payloads out.
+
+use crate::{
+    auth::Authenticated,
+    delivery::{Source, SUPPRESSED_HEADER},
+    redact::redact,
+    router::Deps,
+};
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// The largest request body we'll buffer in order to capture it, matching
+/// Axum's default limit for extractors.
+const MAX_BUFFERED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// How much of a body is retained, bounding memory usage.
+const MAX_CAPTURED_BODY_BYTES: usize = 64 * 1024;
+
+/// An inbound request and the response to it.
+#[derive(Clone, Serialize)]
+pub struct Capture {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    /// Including the query string.
+    pub uri: String,
+    pub body: String,
+    /// Whether the body was cut short.
+    pub truncated: bool,
+    pub status: u16,
+    /// Why onward delivery was suppressed, if it was. See
+    /// [crate::delivery::SUPPRESSED_HEADER].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<String>,
+}
+
+/// The most recent captures from each source, safe to share across requests.
+pub struct Captures {
+    capacity: usize,
+    captures: Mutex<HashMap<Source, VecDeque<Capture>>>,
+}
+
+impl Captures {
+    pub fn new(capacity: usize) -> Self {
+        Captures {
+            capacity,
+            captures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn record(&self, source: Source, x: Capture) {
+        let mut captures = self.captures.lock().unwrap();
+        let xs = captures.entry(source).or_default();
+
+        if xs.len() >= self.capacity {
+            xs.pop_front();
+        }
+        xs.push_back(x);
+    }
+
+    /// A source's captures, most recent first.
+    pub fn get(&self, source: Source) -> Vec<Capture> {
+        self.captures
+            .lock()
+            .unwrap()
+            .get(&source)
+            .map(|xs| xs.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Capture requests to a subrouter as being from a source, if enabled.
+pub fn capture_from(router: Router<Deps>, deps: &Deps, source: Source) -> Router<Deps> {
+    match &deps.captures {
+        None => router,
+        Some(x) => router.layer(middleware::from_fn_with_state(
+            (x.clone(), source),
+            capture_inbound,
+        )),
+    }
+}
+
+/// Middleware recording each authenticated inbound request's redacted payload
+/// alongside the response status.
+async fn capture_inbound(
+    State((captures, source)): State<(Arc<Captures>, Source)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let authenticated = Authenticated::track(&mut parts.extensions);
+
+    let Ok(bytes) = body::to_bytes(body, MAX_BUFFERED_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    let at = Utc::now();
+    let method = parts.method.to_string();
+    let uri = redact(&parts.uri.to_string());
+    let truncated = bytes.len() > MAX_CAPTURED_BODY_BYTES;
+    let body = redact(&String::from_utf8_lossy(
+        &bytes[..bytes.len().min(MAX_CAPTURED_BODY_BYTES)],
+    ));
+
+    let res = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+
+    if !authenticated.is_marked() {
+        return res;
+    }
+
+    captures.record(
+        source,
+        Capture {
+            at,
+            method,
+            uri,
+            body,
+            truncated,
+            status: res.status().as_u16(),
+            suppressed: res
+                .headers()
+                .get(SUPPRESSED_HEADER)
+                .and_then(|x| x.to_str().ok())
+                .map(str::to_owned),
+        },
+    );
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(body: &str) -> Capture {
+        Capture {
+            at: Utc::now(),
+            method: String::from("POST"),
+            uri: String::from("/"),
+            body: body.to_owned(),
+            truncated: false,
+            status: 200,
+            suppressed: None,
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let x = Captures::new(2);
+        assert!(x.get(Source::Heroku).is_empty());
+
+        for body in ["a", "b", "c"] {
+            x.record(Source::Heroku, capture(body));
+        }
+        x.record(Source::Api, capture("d"));
+
+        let bodies = |source| {
+            x.get(source)
+                .into_iter()
+                .map(|x| x.body)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bodies(Source::Heroku), vec!["c", "b"]);
+        assert_eq!(bodies(Source::Api), vec!["d"]);
+    }
+}
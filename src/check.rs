@@ -0,0 +1,509 @@
+//! Lint configuration ahead of deploying via `mercury check-config`, for
+//! example in CI, so that mistakes fail a build rather than a deploy, or worse
+//! go unnoticed because invalid entries are ignored at runtime.
+//!
+//! Every environment variable Mercury reads at startup is parsed as it would
+//! be, including any `.env`, and every secret is loaded from wherever it's
+//! configured. Passing `--live` additionally looks up each channel referenced
+//! by the configuration in Slack. Any problems are printed and the process
+//! exits nonzero.
+
+use crate::{
+    budget::parse_budget_limits,
+    delivery::Source,
+    escalation::parse_policy,
+    eventlog::parse_target,
+    heartbeat::parse_heartbeats,
+    heroku::{
+        description::DescriptionPatterns,
+        emoji::EmojiRules,
+        jira::parse_jira_routes,
+        platform::slack::APP_PLACEHOLDER,
+        poll::parse_poll_apps,
+        push::{parse_push_routes, Pusher},
+        routing::parse_app_routes,
+        runbook::Runbooks,
+    },
+    locale::ChannelLocales,
+    proxy::TrustedProxies,
+    secrets,
+    slack::{api::API_BASE, channel::ChannelName, SlackAccessToken, SlackClient},
+    sms::parse_recipients,
+    stream::parse_list,
+    telemetry::TraceFilter,
+    tls::{self, ClientTls},
+};
+use std::{env, fmt, net::SocketAddr, str::FromStr};
+
+/// Every secret Mercury loads. See [crate::secrets].
+const SECRETS: [&str; 27] = [
+    "SLACK_TOKEN",
+    "HEROKU_SECRET",
+    "GITHUB_TOKEN",
+    "ADMIN_TOKEN",
+    "MERCURY_API_TOKEN",
+    "SIGNING_SECRETS",
+    "SLACK_SIGNING_SECRET",
+    "META_ALERT_PAGERDUTY_KEY",
+    "PAGERDUTY_TOKEN",
+    "OPSGENIE_TOKEN",
+    "HEROKU_API_TOKEN",
+    "NTFY_TOKEN",
+    "PUSHOVER_TOKEN",
+    "TWILIO_AUTH_TOKEN",
+    "MATRIX_ACCESS_TOKEN",
+    "ZULIP_API_KEY",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+    "JIRA_API_TOKEN",
+    "LINEAR_API_KEY",
+    "STATUSPAGE_API_KEY",
+    "GRAFANA_API_KEY",
+    "DATADOG_API_KEY",
+    "HONEYCOMB_API_KEY",
+    "GOOGLE_CLIENT_SECRET",
+    "GOOGLE_REFRESH_TOKEN",
+    "OTTO_SECRET",
+];
+
+/// Environment variables which require others, aside from secrets.
+const REQUIREMENTS: [(&str, &str); 8] = [
+    ("SMS_RECIPIENTS", "TWILIO_ACCOUNT_SID"),
+    ("SMS_RECIPIENTS", "TWILIO_FROM"),
+    ("ZULIP_SITE", "ZULIP_EMAIL"),
+    ("EVENTBRIDGE_BUS", "AWS_REGION"),
+    ("EVENTBRIDGE_BUS", "AWS_ACCESS_KEY_ID"),
+    ("JIRA_ROUTES", "JIRA_BASE"),
+    ("JIRA_ROUTES", "JIRA_EMAIL"),
+    ("GOOGLE_CALENDAR_ID", "GOOGLE_CLIENT_ID"),
+];
+
+/// Validates an environment variable's value.
+type Validator = fn(&str) -> Result<(), String>;
+
+/// Every environment variable Mercury parses, aside from secrets, and how.
+const VALIDATORS: [(&str, Validator); 43] = [
+    ("BIND_ADDR", |x| typed::<SocketAddr>(x, "a socket address")),
+    ("PORT", |x| typed::<u16>(x, "a port")),
+    ("INTERNAL_BIND_ADDR", |x| {
+        typed::<SocketAddr>(x, "a socket address")
+    }),
+    ("GRPC_PORT", |x| typed::<u16>(x, "a port")),
+    ("MAX_BACKLOG", |x| typed::<usize>(x, "a number")),
+    ("READ_ONLY", |x| typed::<bool>(x, "true or false")),
+    ("SLACK_TOKEN_COMPAT", |x| typed::<bool>(x, "true or false")),
+    ("DEBUG_PAYLOADS", |x| typed::<bool>(x, "true or false")),
+    ("CONSOLE_TEE", |x| typed::<bool>(x, "true or false")),
+    ("EVENTBRIDGE_ENDPOINT", |x| typed::<url::Url>(x, "a URL")),
+    ("HONEYCOMB_API", |x| typed::<url::Url>(x, "a URL")),
+    ("OTTO_URL", |x| typed::<url::Url>(x, "a URL")),
+    ("EVENT_LOG", |x| parse_target(x).map(|_| ())),
+    ("WARM_CHANNEL_CACHE_SECS", |x| typed::<u64>(x, "seconds")),
+    ("SHADOW_SAMPLE_EVERY", |x| typed::<u64>(x, "a number")),
+    ("CAPTURE_PAYLOADS", |x| typed::<usize>(x, "a number")),
+    ("META_ALERT_THRESHOLD", |x| typed::<u32>(x, "a number")),
+    ("MAX_SIGNATURE_SKEW_SECS", |x| typed::<u64>(x, "seconds")),
+    ("SLACK_TOKEN_EXPIRES_AT", |x| {
+        typed::<i64>(x, "a Unix timestamp")
+    }),
+    ("AUDIT_CAPACITY", |x| typed::<usize>(x, "a number")),
+    ("RESPONSE_CACHE_SECS", |x| typed::<u64>(x, "seconds")),
+    ("THREAD_WINDOW_MINS", |x| typed::<u64>(x, "minutes")),
+    ("LINEAR_CRASH_THRESHOLD", |x| typed::<usize>(x, "a number")),
+    ("LINEAR_CRASH_WINDOW_MINS", |x| typed::<u64>(x, "minutes")),
+    ("HEROKU_POLL_INTERVAL_SECS", |x| typed::<u64>(x, "seconds")),
+    ("HEROKU_EMOJI", |x| EmojiRules::parse(x).map(|_| ())),
+    ("CHANNEL_LOCALES", |x| ChannelLocales::parse(x).map(|_| ())),
+    ("HEROKU_DESCRIPTION_PATTERNS", |x| {
+        DescriptionPatterns::parse(x).map(|_| ())
+    }),
+    ("TRACE_EXCLUDE", |x| TraceFilter::parse(x).map(|_| ())),
+    ("TRUSTED_PROXIES", |x| TrustedProxies::parse(x).map(|_| ())),
+    ("HEARTBEATS", |x| parse_heartbeats(x).map(|_| ())),
+    ("HEROKU_PUSH_ROUTES", |x| parse_push_routes(x).map(|_| ())),
+    ("JIRA_ROUTES", |x| parse_jira_routes(x).map(|_| ())),
+    ("SMS_RECIPIENTS", |x| parse_recipients(x).map(|_| ())),
+    ("SMS_MAX_PER_HOUR", |x| typed::<usize>(x, "a number")),
+    ("STRICT_SOURCES", |x| {
+        parse_list::<Source>(x)
+            .map(|_| ())
+            .map_err(|e| format!("{}, expected a list of api and heroku", e))
+    }),
+    ("ESCALATION_POLICY", |x| {
+        parse_policy(x)
+            .map(|_| ())
+            .ok_or_else(|| String::from("expected comma-separated mins:@handle stages"))
+    }),
+    // These are parsed leniently at runtime, ignoring invalid entries.
+    ("HEROKU_APP_ROUTES", |x| {
+        entries(
+            x,
+            |x| !parse_app_routes(x).is_empty(),
+            "pattern:channel or pattern:channel:mention",
+        )
+    }),
+    ("HEROKU_NAMED_ROUTES", |x| {
+        entries(
+            x,
+            |x| !parse_app_routes(x).is_empty(),
+            "name:channel or name:channel:mention",
+        )
+    }),
+    ("HEROKU_RUNBOOKS", |x| {
+        entries(x, |x| !Runbooks::parse(x).is_empty(), "pattern:url")
+    }),
+    ("HEROKU_POLL_APPS", |x| {
+        entries(x, |x| !parse_poll_apps(x).is_empty(), "app or app:channel")
+    }),
+    ("NOISE_BUDGETS", |x| {
+        entries(x, |x| !parse_budget_limits(x).is_empty(), "channel:max")
+    }),
+    ("STATUS_CHANNELS", |x| {
+        entries(x, |x| !x.trim_start_matches('#').is_empty(), "channel")
+    }),
+];
+
+/// Something wrong with the configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    /// The environment variable at fault, without its leading `$`.
+    pub var: String,
+    pub error: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}: {}", self.var, self.error)
+    }
+}
+
+/// Check the configuration in the environment, printing any problems, and
+/// returning the process's exit code.
+pub async fn run(live: bool) -> i32 {
+    let get = |x: &str| env::var(x).ok();
+
+    let mut problems = lint(get);
+    problems.extend(check_secrets(get).await);
+
+    if live {
+        // A missing or unloadable token has already been reported.
+        if let Ok(Some(x)) = secrets::load("SLACK_TOKEN").await {
+            let base_url = env::var("SLACK_API_BASE").unwrap_or_else(|_| API_BASE.into());
+            let xs = channels(get);
+
+            problems.extend(check_channels(base_url, &SlackAccessToken(x), xs).await);
+        }
+    }
+
+    if problems.is_empty() {
+        println!("Configuration is valid");
+
+        return 0;
+    }
+
+    for x in &problems {
+        eprintln!("{}", x);
+    }
+    eprintln!("Found {} configuration problems", problems.len());
+
+    1
+}
+
+/// Parse every environment variable as it would be at startup.
+pub fn lint(get: impl Fn(&str) -> Option<String>) -> Vec<Problem> {
+    let invalid = VALIDATORS.iter().filter_map(|(var, validate)| {
+        let error = validate(&get(var)?).err()?;
+
+        Some(Problem {
+            var: (*var).to_owned(),
+            error,
+        })
+    });
+
+    let missing = REQUIREMENTS
+        .iter()
+        .filter(|(by, var)| get(by).is_some() && get(var).is_none())
+        .map(|(by, var)| Problem {
+            var: (*var).to_owned(),
+            error: format!("required by ${}", by),
+        });
+
+    let tls = tls::PLATFORMS
+        .iter()
+        .filter_map(|x| ClientTls::from_env(&get, x).err())
+        .map(|e| Problem {
+            var: e.var,
+            error: e.error,
+        });
+
+    invalid.chain(missing).chain(tls).collect()
+}
+
+/// Load every secret, requiring those which are needed by the rest of the
+/// configuration.
+async fn check_secrets(get: impl Fn(&str) -> Option<String>) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for name in SECRETS {
+        let required_by = match name {
+            "SLACK_TOKEN" => Some("Mercury"),
+            "HEROKU_API_TOKEN" if get("HEROKU_POLL_APPS").is_some() => Some("$HEROKU_POLL_APPS"),
+            "PUSHOVER_TOKEN" if needs_pushover(&get) => Some("$HEROKU_PUSH_ROUTES"),
+            "TWILIO_AUTH_TOKEN" if get("SMS_RECIPIENTS").is_some() => Some("$SMS_RECIPIENTS"),
+            "MATRIX_ACCESS_TOKEN" if get("MATRIX_HOMESERVER").is_some() => {
+                Some("$MATRIX_HOMESERVER")
+            }
+            "ZULIP_API_KEY" if get("ZULIP_SITE").is_some() => Some("$ZULIP_SITE"),
+            "AWS_SECRET_ACCESS_KEY" if get("EVENTBRIDGE_BUS").is_some() => Some("$EVENTBRIDGE_BUS"),
+            "JIRA_API_TOKEN" if get("JIRA_ROUTES").is_some() => Some("$JIRA_ROUTES"),
+            "LINEAR_API_KEY" if get("LINEAR_TEAM_ID").is_some() => Some("$LINEAR_TEAM_ID"),
+            "GRAFANA_API_KEY" if get("GRAFANA_URL").is_some() => Some("$GRAFANA_URL"),
+            "GOOGLE_CLIENT_SECRET" | "GOOGLE_REFRESH_TOKEN"
+                if get("GOOGLE_CALENDAR_ID").is_some() =>
+            {
+                Some("$GOOGLE_CALENDAR_ID")
+            }
+            "STATUSPAGE_API_KEY" if get("STATUSPAGE_PAGE_ID").is_some() => {
+                Some("$STATUSPAGE_PAGE_ID")
+            }
+            _ => None,
+        };
+
+        let error = match secrets::load(name).await {
+            Ok(Some(_)) => continue,
+            Ok(None) => match required_by {
+                None => continue,
+                Some(x) => format!(
+                    "required by {0}, set it or one of ${1}_FILE, ${1}_AWS_SECRET, or ${1}_VAULT",
+                    x, name
+                ),
+            },
+            Err(e) => format!("could not load: {}", e),
+        };
+
+        problems.push(Problem {
+            var: name.to_owned(),
+            error,
+        });
+    }
+
+    problems
+}
+
+/// Whether any push routes are to Pushover. See [crate::heroku::push].
+fn needs_pushover(get: impl Fn(&str) -> Option<String>) -> bool {
+    get("HEROKU_PUSH_ROUTES")
+        .and_then(|x| parse_push_routes(&x).ok())
+        .is_some_and(|xs| Pusher::new(xs).needs_pushover())
+}
+
+/// The channels referenced by the configuration, alongside where. Templated
+/// channels are omitted, as they're only known once expanded.
+pub fn channels(get: impl Fn(&str) -> Option<String>) -> Vec<(&'static str, ChannelName)> {
+    let mut xs = Vec::new();
+
+    if let Some(x) = get("HEROKU_APP_ROUTES") {
+        xs.extend(
+            parse_app_routes(&x)
+                .into_iter()
+                .map(|r| ("HEROKU_APP_ROUTES", r.channel)),
+        );
+    }
+
+    if let Some(x) = get("HEROKU_NAMED_ROUTES") {
+        xs.extend(
+            parse_app_routes(&x)
+                .into_iter()
+                .map(|r| ("HEROKU_NAMED_ROUTES", r.channel)),
+        );
+    }
+
+    if let Some(x) = get("HEROKU_POLL_APPS") {
+        xs.extend(
+            parse_poll_apps(&x)
+                .into_iter()
+                .filter_map(|x| Some(("HEROKU_POLL_APPS", x.channel?))),
+        );
+    }
+
+    if let Some(x) = get("NOISE_BUDGETS") {
+        let mut names: Vec<_> = parse_budget_limits(&x).into_keys().collect();
+        names.sort();
+
+        xs.extend(names.into_iter().map(|x| ("NOISE_BUDGETS", ChannelName(x))));
+    }
+
+    if let Some(x) = get("STATUS_CHANNELS") {
+        xs.extend(
+            x.split(',')
+                .map(|x| x.trim().trim_start_matches('#'))
+                .filter(|x| !x.is_empty())
+                .map(|x| ("STATUS_CHANNELS", ChannelName(x.to_owned()))),
+        );
+    }
+
+    for var in ["SHADOW_CHANNEL", "SELFTEST_CHANNEL", "OPS_CHANNEL"] {
+        if let Some(x) = get(var) {
+            xs.push((var, ChannelName(x)));
+        }
+    }
+
+    xs.retain(|(_, x)| !x.0.contains(APP_PLACEHOLDER));
+
+    xs
+}
+
+/// Look up channels in Slack.
+async fn check_channels(
+    base_url: String,
+    token: &SlackAccessToken,
+    xs: Vec<(&'static str, ChannelName)>,
+) -> Vec<Problem> {
+    let mut client = SlackClient::new(base_url);
+    let mut problems = Vec::new();
+
+    for (var, channel) in xs {
+        if let Err(e) = client.get_channel_id(&channel, token).await {
+            problems.push(Problem {
+                var: var.to_owned(),
+                error: format!(
+                    "{}, check that it exists and that Mercury has been added to it",
+                    e
+                ),
+            });
+        }
+    }
+
+    problems
+}
+
+/// Parse a value to a type, describing what was expected if it can't be.
+fn typed<T: FromStr>(x: &str, expected: &str) -> Result<(), String>
+where
+    T::Err: fmt::Display,
+{
+    x.parse::<T>()
+        .map(|_| ())
+        .map_err(|e| format!("{}, expected {}", e, expected))
+}
+
+/// Validate each of a comma-separated list's entries.
+fn entries(x: &str, is_valid: fn(&str) -> bool, expected: &str) -> Result<(), String> {
+    let invalid: Vec<_> = x
+        .split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !is_valid(x))
+        .collect();
+
+    match invalid.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "invalid entries {}, expected {}",
+            invalid.join(", "),
+            expected
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(xs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let xs: HashMap<String, String> = xs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        move |x| xs.get(x).cloned()
+    }
+
+    #[test]
+    fn test_lint() {
+        assert!(lint(env(&[])).is_empty());
+
+        assert!(lint(env(&[
+            ("PORT", "8080"),
+            ("READ_ONLY", "true"),
+            (
+                "HEROKU_APP_ROUTES",
+                "api-*:api-deploys:@api-team, web:web-deploys"
+            ),
+            (
+                "HEROKU_DESCRIPTION_PATTERNS",
+                "deploy:^Deployed (?P<commit>[0-9a-f]+)$"
+            ),
+            ("NOISE_BUDGETS", "alerts:20"),
+        ]))
+        .is_empty());
+
+        let problems = lint(env(&[
+            ("PORT", "eighty"),
+            ("HEROKU_APP_ROUTES", "api-*:api-deploys, web, :x"),
+            ("HEROKU_DESCRIPTION_PATTERNS", "deploy:^Deployed ("),
+            ("ESCALATION_POLICY", "15"),
+            ("SMS_RECIPIENTS", "+14155550100"),
+            ("TWILIO_FROM", "+15005550006"),
+            ("GRAFANA_TLS_KEY", "/nonexistent/key.pem"),
+        ]));
+        let vars: Vec<_> = problems.iter().map(|x| x.var.as_str()).collect();
+        assert_eq!(
+            vars,
+            vec![
+                "PORT",
+                "HEROKU_DESCRIPTION_PATTERNS",
+                "ESCALATION_POLICY",
+                "HEROKU_APP_ROUTES",
+                "TWILIO_ACCOUNT_SID",
+                "GRAFANA_TLS_KEY"
+            ]
+        );
+        assert_eq!(
+            problems[3].error,
+            "invalid entries web, :x, expected pattern:channel or pattern:channel:mention"
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        let xs = channels(env(&[
+            ("HEROKU_APP_ROUTES", "api-*:api-deploys, *:deploys-{app}"),
+            ("HEROKU_POLL_APPS", "web:web-deploys, worker"),
+            ("STATUS_CHANNELS", "#status"),
+            ("OPS_CHANNEL", "ops"),
+        ]));
+        let xs: Vec<_> = xs.iter().map(|(var, x)| (*var, x.0.as_str())).collect();
+
+        assert_eq!(
+            xs,
+            vec![
+                ("HEROKU_APP_ROUTES", "api-deploys"),
+                ("HEROKU_POLL_APPS", "web-deploys"),
+                ("STATUS_CHANNELS", "status"),
+                ("OPS_CHANNEL", "ops"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_channels() {
+        let mut srv = mockito::Server::new_async().await;
+        srv.mock("GET", "/conversations.list")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"ok": true, "channels": [{"id": "C1", "name": "ops"}], "response_metadata": {"next_cursor": ""}}"#,
+            )
+            .create_async()
+            .await;
+
+        let xs = vec![
+            ("OPS_CHANNEL", ChannelName("#ops".into())),
+            ("SHADOW_CHANNEL", ChannelName("shadow".into())),
+        ];
+        let problems = check_channels(srv.url(), &SlackAccessToken("foobar".into()), xs).await;
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].var, "SHADOW_CHANNEL");
+    }
+}
@@ -0,0 +1,274 @@
+//! A typed client for Mercury's HTTP API, for Rust services which would rather
+//! not hand-roll form posts.
+//!
+//! ```no_run
+//! # use mercury::client::*;
+//! # async fn example() -> Result<(), ClientError> {
+//! let client = MercuryClient::new("https://mercury.proxy.unsplash.com", "<MERCURY_API_TOKEN>");
+//!
+//! let msg = Message {
+//!     severity: Some(Severity::Warning),
+//!     ..Message::new("playground", "Mercury", "Running the example")
+//! };
+//!
+//! match client.send_message(&msg).await? {
+//!     Delivery::Sent => {}
+//!     Delivery::Suppressed(reason) => println!("Suppressed: {}", reason),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The types here deliberately mirror, rather than reuse, those of the server,
+//! so that depending on this doesn't couple consumers to its internals.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+use url::Url;
+
+/// The response header present when delivery was deliberately suppressed.
+const SUPPRESSED_HEADER: &str = "Mercury-Suppressed";
+
+/// A client for a Mercury instance, authenticated with a token from
+/// `$MERCURY_API_TOKEN`. Cheap to clone, reusing connections.
+#[derive(Clone)]
+pub struct MercuryClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+/// A message to send, as accepted by `POST /api/v1/slack`.
+#[derive(Clone, Serialize)]
+pub struct Message {
+    /// With or without the leading hash.
+    pub channel: String,
+    pub title: String,
+    pub desc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<Url>,
+    /// A user group handle such as `@web-team`, or `oncall:<schedule>`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// When the subject of the message occurred.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        with = "chrono::serde::ts_seconds_option"
+    )]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Structured metadata rendered in two columns, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty", serialize_with = "ser_fields")]
+    pub fields: Vec<(String, String)>,
+    /// Render the title as a header rather than as the username, for
+    /// workspaces which don't grant the `chat:write.customize` scope.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub title_as_header: bool,
+    /// Reply in the thread of the message with this timestamp rather than
+    /// posting top-level.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+}
+
+/// Serialize fields as the newline-separated `key: value` lines the API
+/// expects.
+fn ser_fields<S: serde::Serializer>(xs: &[(String, String)], s: S) -> Result<S::Ok, S::Error> {
+    let lines: Vec<_> = xs.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+
+    s.serialize_str(&lines.join("\n"))
+}
+
+impl Message {
+    /// A message with only the required fields.
+    pub fn new(
+        channel: impl Into<String>,
+        title: impl Into<String>,
+        desc: impl Into<String>,
+    ) -> Self {
+        Message {
+            channel: channel.into(),
+            title: title.into(),
+            desc: desc.into(),
+            link: None,
+            cc: None,
+            avatar: None,
+            severity: None,
+            timestamp: None,
+            fields: Vec::new(),
+            title_as_header: false,
+            thread_ts: None,
+        }
+    }
+}
+
+/// How severe, or otherwise, the subject of a message is. Ordered from least
+/// to most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Debug,
+    Info,
+    Success,
+    Warning,
+    Critical,
+}
+
+/// The outcome of a request which Mercury accepted.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// Delivery was deliberately skipped, for example in read-only mode, for
+    /// the given reason.
+    Suppressed(String),
+}
+
+/// Every possible unexceptional fail case when talking to Mercury.
+#[derive(Debug)]
+pub enum ClientError {
+    /// General request failure.
+    RequestFailed(reqwest::Error),
+    /// The token wasn't accepted.
+    Unauthorized,
+    /// Mercury rejected the request, or failed to deliver it, with the given
+    /// status and explanation.
+    Rejected(reqwest::StatusCode, String),
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::RequestFailed(e)
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = match self {
+            ClientError::RequestFailed(e) => format!("Mercury request failed: {:?}", e),
+            ClientError::Unauthorized => String::from("Mercury rejected the API token"),
+            ClientError::Rejected(s, e) => format!("Mercury responded with {}: {}", s, e),
+        };
+
+        write!(f, "{}", x)
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl MercuryClient {
+    /// Instantiate against the base URL of a Mercury instance, for example
+    /// `https://mercury.proxy.unsplash.com`.
+    pub fn new(base_url: impl Into<String>, token: impl Into<String>) -> Self {
+        MercuryClient {
+            client: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            token: token.into(),
+        }
+    }
+
+    /// Send a message, subject to any suppression on Mercury's end.
+    pub async fn send_message(&self, msg: &Message) -> Result<Delivery, ClientError> {
+        let res = self
+            .client
+            .post(format!("{}/api/v1/slack", self.base_url))
+            .bearer_auth(&self.token)
+            .form(msg)
+            .send()
+            .await?;
+
+        let status = res.status();
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(ClientError::Unauthorized);
+        }
+        if !status.is_success() {
+            return Err(ClientError::Rejected(status, res.text().await?));
+        }
+
+        let suppressed = res
+            .headers()
+            .get(SUPPRESSED_HEADER)
+            .map(|x| String::from_utf8_lossy(x.as_bytes()).into_owned());
+
+        Ok(match suppressed {
+            Some(reason) => Delivery::Suppressed(reason),
+            None => Delivery::Sent,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_send_message() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/api/v1/slack")
+            .match_header("Authorization", "Bearer foobar")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("channel".into(), "playground".into()),
+                Matcher::UrlEncoded("severity".into(), "critical".into()),
+                Matcher::UrlEncoded("timestamp".into(), "1691056830".into()),
+                Matcher::UrlEncoded("fields".into(), "App: web\nRegion: eu".into()),
+            ]))
+            .create_async()
+            .await;
+
+        let client = MercuryClient::new(srv.url() + "/", "foobar");
+        let msg = Message {
+            severity: Some(Severity::Critical),
+            timestamp: DateTime::from_timestamp(1691056830, 0),
+            fields: vec![("App".into(), "web".into()), ("Region".into(), "eu".into())],
+            ..Message::new("playground", "a title", "a description")
+        };
+
+        assert_eq!(client.send_message(&msg).await.unwrap(), Delivery::Sent);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_send_message_suppressed() {
+        let mut srv = mockito::Server::new_async().await;
+
+        srv.mock("POST", "/api/v1/slack")
+            .with_header(SUPPRESSED_HEADER, "read-only")
+            .create_async()
+            .await;
+
+        let client = MercuryClient::new(srv.url(), "foobar");
+        let res = client.send_message(&Message::new("a", "b", "c")).await;
+
+        assert_eq!(res.unwrap(), Delivery::Suppressed("read-only".into()));
+    }
+
+    #[tokio::test]
+    async fn test_send_message_failure() {
+        let mut srv = mockito::Server::new_async().await;
+
+        srv.mock("POST", "/api/v1/slack")
+            .match_header("Authorization", "Bearer wrong")
+            .with_status(401)
+            .create_async()
+            .await;
+        srv.mock("POST", "/api/v1/slack")
+            .match_header("Authorization", "Bearer foobar")
+            .with_status(400)
+            .with_body("Unknown Slack channel: nope")
+            .create_async()
+            .await;
+
+        let client = MercuryClient::new(srv.url(), "foobar");
+        let res = client.send_message(&Message::new("nope", "b", "c")).await;
+        assert!(matches!(res, Err(ClientError::Rejected(s, e)) if s == 400 && e.ends_with("nope")));
+
+        let client = MercuryClient::new(srv.url(), "wrong");
+        let res = client.send_message(&Message::new("a", "b", "c")).await;
+        assert!(matches!(res, Err(ClientError::Unauthorized)));
+    }
+}
@@ -0,0 +1,140 @@
+//! Write messages to stdout, stderr, or a file as JSON lines, useful in
+//! development, in tests, and as a record of what's sent.
+//!
+//! Messages are written either in place of delivery, via the Heroku `stdout`
+//! platform (see [crate::heroku::platform]), or alongside delivery, by setting
+//! `CONSOLE_TEE=true`, in which case every message sent is also written.
+//!
+//! Written to stdout unless `$CONSOLE_OUTPUT` is set to `stderr` or a path to
+//! append to, for example `/var/log/mercury/messages.jsonl`.
+
+use crate::{delivery::Source, slack::Message};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+use tracing::warn;
+
+/// Where messages are written.
+enum Output {
+    Stdout,
+    Stderr,
+    File(Mutex<File>),
+}
+
+/// A message as written, one per line.
+#[derive(Serialize)]
+struct Record<'a> {
+    at: DateTime<Utc>,
+    source: Source,
+    /// The app the message is about, if known. See [crate::heroku].
+    app: Option<&'a str>,
+    message: &'a Message,
+}
+
+/// Writes messages, safe to share across requests.
+pub struct Console {
+    output: Output,
+    tee: bool,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Console {
+            output: Output::Stdout,
+            tee: false,
+        }
+    }
+}
+
+impl Console {
+    /// Open an output from its environment variable representation, creating
+    /// the file if need be.
+    ///
+    /// ```
+    /// let x = Console::open("stderr").unwrap();
+    /// ```
+    pub fn open(x: &str) -> io::Result<Self> {
+        let output = match x {
+            "stdout" => Output::Stdout,
+            "stderr" => Output::Stderr,
+            path => Output::File(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+        };
+
+        Ok(Console { output, tee: false })
+    }
+
+    /// Additionally write every message sent. See [crate::delivery].
+    pub fn with_tee(mut self, x: bool) -> Self {
+        self.tee = x;
+        self
+    }
+
+    pub fn is_tee(&self) -> bool {
+        self.tee
+    }
+
+    /// Write a message, logging rather than failing if it can't be.
+    pub fn write(&self, msg: &Message, source: Source, app: Option<&str>) {
+        let x = Record {
+            at: Utc::now(),
+            source,
+            app,
+            message: msg,
+        };
+
+        if let Err(e) = self.write_(&x) {
+            warn!(
+                "Failed to write message to {} to console: {}",
+                msg.channel, e
+            );
+        }
+    }
+
+    fn write_(&self, x: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(x)?;
+        line.push(b'\n');
+
+        match &self.output {
+            Output::Stdout => io::stdout().lock().write_all(&line),
+            Output::Stderr => io::stderr().lock().write_all(&line),
+            Output::File(f) => f.lock().unwrap().write_all(&line),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::channel::ChannelName;
+    use std::fs;
+
+    #[test]
+    fn test_write_file() {
+        let path = std::env::temp_dir().join(format!("mercury-console-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let x = Console::open(path.to_str().unwrap()).unwrap();
+        let msg = Message::new(ChannelName("deploys".into()), "api", "Deployed");
+        x.write(&msg, Source::Heroku, Some("api"));
+        x.write(&msg, Source::Api, None);
+
+        let lines: Vec<serde_json::Value> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|x| serde_json::from_str(x).unwrap())
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["source"], "heroku");
+        assert_eq!(lines[0]["app"], "api");
+        assert_eq!(lines[0]["message"]["channel"], "deploys");
+        assert_eq!(lines[1]["app"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,175 @@
+//! Record deploys and rollbacks as [Datadog](https://www.datadoghq.com)
+//! events, tagged by app and version, so that they can be overlaid on graphs
+//! and correlated with regressions.
+//!
+//! Enabled by configuring an API key at `$DATADOG_API_KEY`. Accounts outside
+//! Datadog's default US1 site must also set `$DATADOG_SITE`, for example
+//! `datadoghq.eu`.
+//!
+//! Events are tagged `source:mercury`, `kind:deploy` or `kind:rollback`,
+//! `app:<app>`, and `version:<version>` where the version's known, and
+//! aggregated by app. Recording is best effort, in addition to wherever the
+//! event's headed, and suppressed in read-only mode.
+
+use crate::event::{Event, EventKind};
+use chrono::Utc;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The site of accounts which don't specify one.
+pub const DEFAULT_SITE: &str = "datadoghq.com";
+
+/// How long to wait for Datadog to accept each event.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// <https://docs.datadoghq.com/api/latest/events/#post-an-event>
+#[derive(Serialize)]
+struct PostEventRequest {
+    title: String,
+    text: String,
+    /// Unix seconds.
+    date_happened: i64,
+    tags: Vec<String>,
+    alert_type: &'static str,
+    aggregation_key: Option<String>,
+    source_type_name: &'static str,
+}
+
+/// Records events, safe to share across requests.
+pub struct Datadog {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl Datadog {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [api_url].
+    pub fn new(base_url: String, api_key: String) -> Self {
+        Datadog {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            base_url,
+            api_key,
+        }
+    }
+
+    /// Best effort record a deploy or rollback, ignoring other events.
+    pub async fn record(&self, evt: &Event) {
+        let Some(req) = to_request(evt) else {
+            return;
+        };
+
+        let res = self
+            .client
+            .post(format!("{}/api/v1/events", self.base_url))
+            .header("DD-API-KEY", &self.api_key)
+            .json(&req)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status());
+
+        match res {
+            Ok(_) => info!("Recorded {:?} event in Datadog", evt.kind),
+            Err(e) => warn!("Failed to record event in Datadog: {}", e),
+        }
+    }
+}
+
+/// The API's URL for a site, for example `datadoghq.eu`.
+///
+/// ```
+/// assert_eq!(api_url("datadoghq.eu"), "https://api.datadoghq.eu");
+/// ```
+pub fn api_url(site: &str) -> String {
+    format!("https://api.{}", site)
+}
+
+fn to_request(evt: &Event) -> Option<PostEventRequest> {
+    let alert_type = match evt.kind {
+        EventKind::Deploy => "info",
+        EventKind::Rollback => "warning",
+        EventKind::ConfigChange | EventKind::Crash => return None,
+    };
+
+    let mut tags = vec![
+        String::from("source:mercury"),
+        format!("kind:{}", evt.kind.as_str()),
+    ];
+    if let Some(x) = &evt.app {
+        tags.push(format!("app:{}", x));
+    }
+    if let Some(x) = &evt.version {
+        tags.push(format!("version:{}", x));
+    }
+
+    let title = match &evt.version {
+        Some(v) => format!("{} {}", evt.title, v),
+        None => evt.title.clone(),
+    };
+
+    let mut text = evt.summary.clone();
+    if let Some(x) = evt.links.first() {
+        text += &format!("\n{}", x);
+    }
+
+    Some(PostEventRequest {
+        title,
+        text,
+        date_happened: evt.occurred_at.unwrap_or_else(Utc::now).timestamp(),
+        tags,
+        alert_type,
+        aggregation_key: evt.app.clone(),
+        source_type_name: "mercury",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Source;
+    use chrono::TimeZone;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_record() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/api/v1/events")
+            .match_header("DD-API-KEY", "key")
+            .match_body(Matcher::Json(json!({
+                "title": "api v42",
+                "text": "Rolled back to v41",
+                "date_happened": 1704110400,
+                "tags": ["source:mercury", "kind:rollback", "app:api", "version:v42"],
+                "alert_type": "warning",
+                "aggregation_key": "api",
+                "source_type_name": "mercury",
+            })))
+            .with_status(202)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Datadog::new(srv.url(), "key".into());
+        let evt = |kind| Event {
+            source: Source::Heroku,
+            kind,
+            app: Some(String::from("api")),
+            version: Some(String::from("v42")),
+            severity: None,
+            occurred_at: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            title: String::from("api"),
+            summary: String::from("Rolled back to v41"),
+            fields: Vec::new(),
+            links: Vec::new(),
+        };
+
+        x.record(&evt(EventKind::Rollback)).await;
+        x.record(&evt(EventKind::Crash)).await;
+
+        mock.assert_async().await;
+    }
+}
@@ -1,6 +1,10 @@
 //! Custom Serde deserialisers.
 
-use serde::de::{Deserialize, Deserializer, Error};
+use schemars::{json_schema, Schema, SchemaGenerator};
+use serde::{
+    de::{DeserializeOwned, Deserializer, Error},
+    Deserialize,
+};
 
 /// Deserialise a `bool`, accepting only `true` and rejecting `false`. The dual
 /// to [only_false].
@@ -48,6 +52,171 @@ where
     })
 }
 
+/// Deserialise key-value pairs from either a sequence of pairs, as they're
+/// serialised, or newline-separated `key: value` lines, as is convenient in
+/// forms. Blank lines are ignored.
+///
+/// ```
+/// struct T {
+///     #[serde(deserialize_with = "fields")]
+///     val: Vec<(String, String)>,
+/// }
+///
+/// ```
+pub fn fields<'a, D>(deserializer: D) -> Result<Vec<(String, String)>, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Pairs(Vec<(String, String)>),
+        Lines(String),
+    }
+
+    match Repr::deserialize(deserializer)? {
+        Repr::Pairs(xs) => Ok(xs),
+        Repr::Lines(x) => x
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| match l.split_once(':') {
+                Some((k, v)) => Ok((k.trim().to_owned(), v.trim().to_owned())),
+                None => Err(Error::custom(format!(
+                    "invalid field, expected key: value: {}",
+                    l
+                ))),
+            })
+            .collect(),
+    }
+}
+
+/// Deserialise an optional value, treating one which is present but invalid as
+/// absent, for fields we can do without. Should be paired with
+/// `#[serde(default)]`.
+///
+/// ```
+/// struct T {
+///     #[serde(default, deserialize_with = "lenient")]
+///     val: Option<u8>,
+/// }
+///
+/// ```
+pub fn lenient<'a, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'a>,
+    T: DeserializeOwned,
+{
+    let x = serde_json::Value::deserialize(deserializer)?;
+
+    Ok(T::deserialize(x).ok())
+}
+
+/// Deserialise a string, additionally accepting numbers as their string
+/// representation, for values which aren't consistently typed.
+///
+/// ```
+/// struct T {
+///     #[serde(deserialize_with = "string_or_number")]
+///     val: String,
+/// }
+///
+/// ```
+pub fn string_or_number<'a, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'a>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    match Repr::deserialize(deserializer)? {
+        Repr::String(x) => Ok(x),
+        Repr::Number(x) => Ok(x.to_string()),
+    }
+}
+
+/// Deserialise an enum case-insensitively, for enums whose variants are
+/// renamed to lowercase. Anything other than a string, such as a `null` for
+/// an optional enum, is deserialised as usual. Unrecognised values are still
+/// rejected, unless the enum has a `#[serde(other)]` variant, in which case
+/// the raw value is lost; see [Tolerant] for preserving it.
+///
+/// ```
+/// struct T {
+///     #[serde(default, deserialize_with = "case_insensitive")]
+///     val: Option<E>,
+/// }
+///
+/// ```
+pub fn case_insensitive<'a, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'a>,
+    T: DeserializeOwned,
+{
+    let x = match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::String(x) => serde_json::Value::String(x.to_lowercase()),
+        x => x,
+    };
+
+    T::deserialize(x).map_err(Error::custom)
+}
+
+/// An enum of string values which can't be exhaustively known, such as those
+/// defined by third parties, and which has a catch-all variant for the rest.
+/// Implementors should deserialise via [tolerant].
+pub trait Tolerant: Sized {
+    /// The variant for a known value, which is always lowercase.
+    fn known(x: &str) -> Option<Self>;
+
+    /// The catch-all variant, preserving the raw value.
+    fn other(x: String) -> Self;
+}
+
+/// Deserialise a [Tolerant] enum, matching known values case-insensitively,
+/// accepting numbers as well as strings, and falling back to the catch-all
+/// variant for anything else rather than failing.
+///
+/// ```
+/// struct T {
+///     #[serde(deserialize_with = "tolerant")]
+///     val: E,
+/// }
+///
+/// ```
+pub fn tolerant<'a, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'a>,
+    T: Tolerant,
+{
+    let x = string_or_number(deserializer)?;
+
+    Ok(T::known(&x.to_lowercase()).unwrap_or_else(|| T::other(x)))
+}
+
+/// The JSON Schema of what's accepted by [fields].
+pub fn fields_schema(_: &mut SchemaGenerator) -> Schema {
+    json_schema!({
+        "anyOf": [
+            {
+                "type": "array",
+                "items": {
+                    "type": "array",
+                    "prefixItems": [{ "type": "string" }, { "type": "string" }],
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+            },
+            {
+                "type": "string",
+                "description": "Newline-separated `key: value` lines.",
+            },
+        ],
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,4 +252,131 @@ mod tests {
 
         assert!(serde_json::from_str::<T>(r#"{"val": true}"#).is_err());
     }
+
+    #[test]
+    fn test_lenient() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct T {
+            #[serde(default, deserialize_with = "lenient")]
+            val: Option<u8>,
+        }
+
+        let parse = |x| serde_json::from_str::<T>(x).unwrap().val;
+
+        assert_eq!(parse(r#"{"val": 1}"#), Some(1));
+        assert_eq!(parse(r#"{"val": null}"#), None);
+        assert_eq!(parse(r#"{"val": "nope"}"#), None);
+        assert_eq!(parse(r#"{"val": 256}"#), None);
+        assert_eq!(parse(r#"{}"#), None);
+    }
+
+    #[test]
+    fn test_string_or_number() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct T {
+            #[serde(deserialize_with = "string_or_number")]
+            val: String,
+        }
+
+        let parse = |x| serde_json::from_str::<T>(x).map(|x| x.val);
+
+        assert_eq!(parse(r#"{"val": "abc"}"#).unwrap(), "abc");
+        assert_eq!(parse(r#"{"val": 42}"#).unwrap(), "42");
+        assert_eq!(parse(r#"{"val": -1.5}"#).unwrap(), "-1.5");
+        assert!(parse(r#"{"val": null}"#).is_err());
+        assert!(parse(r#"{"val": true}"#).is_err());
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        #[serde(rename_all = "lowercase")]
+        enum E {
+            Foo,
+            Bar,
+        }
+
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct T {
+            #[serde(default, deserialize_with = "case_insensitive")]
+            val: Option<E>,
+        }
+
+        let parse = |x| serde_json::from_str::<T>(x).map(|x| x.val);
+
+        assert_eq!(parse(r#"{"val": "foo"}"#).unwrap(), Some(E::Foo));
+        assert_eq!(parse(r#"{"val": "BaR"}"#).unwrap(), Some(E::Bar));
+        assert_eq!(parse(r#"{"val": null}"#).unwrap(), None);
+        assert_eq!(parse(r#"{}"#).unwrap(), None);
+        assert!(parse(r#"{"val": "baz"}"#).is_err());
+
+        assert_eq!(
+            serde_urlencoded::from_str::<T>("val=FOO").unwrap().val,
+            Some(E::Foo)
+        );
+    }
+
+    #[test]
+    fn test_tolerant() {
+        #[derive(Debug, PartialEq, Eq)]
+        enum E {
+            Foo,
+            Other(String),
+        }
+
+        impl Tolerant for E {
+            fn known(x: &str) -> Option<Self> {
+                match x {
+                    "foo" => Some(E::Foo),
+                    _ => None,
+                }
+            }
+
+            fn other(x: String) -> Self {
+                E::Other(x)
+            }
+        }
+
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct T {
+            #[serde(deserialize_with = "tolerant")]
+            val: E,
+        }
+
+        let parse = |x| serde_json::from_str::<T>(x).map(|x| x.val);
+
+        assert_eq!(parse(r#"{"val": "foo"}"#).unwrap(), E::Foo);
+        assert_eq!(parse(r#"{"val": "FOO"}"#).unwrap(), E::Foo);
+        assert_eq!(parse(r#"{"val": "Baz"}"#).unwrap(), E::Other("Baz".into()));
+        assert_eq!(parse(r#"{"val": 7}"#).unwrap(), E::Other("7".into()));
+        assert!(parse(r#"{"val": null}"#).is_err());
+    }
+
+    #[test]
+    fn test_fields() {
+        #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+        struct T {
+            #[serde(deserialize_with = "fields")]
+            val: Vec<(String, String)>,
+        }
+
+        let expected = T {
+            val: vec![
+                ("App".into(), "web".into()),
+                ("Region".into(), "eu: west".into()),
+            ],
+        };
+
+        assert_eq!(
+            serde_urlencoded::from_str::<T>("val=App%3A+web%0A%0ARegion%3Aeu%3A+west").unwrap(),
+            expected,
+        );
+        assert_eq!(
+            serde_json::from_str::<T>(r#"{"val": [["App", "web"], ["Region", "eu: west"]]}"#)
+                .unwrap(),
+            expected,
+        );
+
+        assert!(serde_urlencoded::from_str::<T>("val=nope").is_err());
+    }
 }
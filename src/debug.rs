@@ -0,0 +1,40 @@
+//! Optional logging of payloads, enabled with `DEBUG_PAYLOADS=true`, to help
+//! diagnose reports of messages not looking as expected.
+//!
+//! Both inbound request payloads and outbound Slack payloads are logged, the
+//! latter by [crate::slack::SlackClient]. Everything is passed through
+//! [crate::redact] first.
+
+use crate::redact::redact;
+use axum::{
+    body::{self, Body},
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+/// The largest request body we'll buffer in order to log it, matching Axum's
+/// default limit for extractors.
+const MAX_LOGGED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Middleware logging each inbound request's redacted payload, including its
+/// query string.
+pub async fn log_inbound(req: Request, next: Next) -> Response {
+    let (parts, body) = req.into_parts();
+
+    let Ok(bytes) = body::to_bytes(body, MAX_LOGGED_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    info!(
+        "Inbound payload: {} {} {}",
+        parts.method,
+        redact(&parts.uri.to_string()),
+        redact(&String::from_utf8_lossy(&bytes))
+    );
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
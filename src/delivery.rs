@@ -0,0 +1,341 @@
+//! The single path by which outbound messages are delivered, allowing
+//! cross-cutting behaviour such as read-only mode to apply uniformly regardless
+//! of where a message originated.
+//!
+//! Messages can additionally be mirrored to a [Shadow] channel, making it easy
+//! to validate formatting changes against production traffic without touching
+//! user-facing channels.
+//!
+//! Each channel's [crate::budget], if any, is enforced here too, as are
+//! [crate::threading] and [crate::priority].
+//!
+//! Failures which need an operator's attention, such as posting to an archived
+//! channel, are reported to the ops channel if there is one. Repeated failures
+//! raise a [crate::meta] alert.
+//!
+//! Every delivery attempt is recorded in the [crate::audit] history, and every
+//! message sent is counted towards the [crate::stats] and broadcast to any
+//! [crate::stream] consumers.
+
+use crate::{
+    budget::{summary_message, Admission},
+    event::Event,
+    priority::Priority,
+    router::Deps,
+    slack::{
+        channel::ChannelName, error::APIError, message::PostOptions, Message, Severity, SlackError,
+    },
+    stream::StreamEvent,
+    telemetry::record_channel,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tracing::{info, warn};
+
+/// The response header present when a request was accepted but its onward
+/// delivery was deliberately suppressed. The value describes why.
+pub const SUPPRESSED_HEADER: &str = "Mercury-Suppressed";
+
+/// The outcome of a delivery which didn't fail.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Sent,
+    /// Delivery was deliberately skipped, for the given reason.
+    Suppressed(&'static str),
+}
+
+/// Where a message originated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// Mercury's own API. See [crate::slack::router].
+    Api,
+    /// See [crate::heroku].
+    Heroku,
+    /// See [crate::admin].
+    Selftest,
+    /// See [crate::audit].
+    Replay,
+}
+
+impl Source {
+    /// The name of the source as it's serialised, for example in metric
+    /// labels.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Source::Api => "api",
+            Source::Heroku => "heroku",
+            Source::Selftest => "selftest",
+            Source::Replay => "replay",
+        }
+    }
+}
+
+/// A channel to which outbound messages are mirrored.
+#[derive(Clone)]
+pub struct Shadow {
+    pub channel: ChannelName,
+    /// Mirror one in every so many messages. A value of 1 mirrors everything.
+    sample_every: u64,
+    /// The number of messages seen so far.
+    count: Arc<AtomicU64>,
+}
+
+impl Shadow {
+    pub fn new(channel: ChannelName, sample_every: u64) -> Self {
+        Shadow {
+            channel,
+            // Guard against division by zero.
+            sample_every: sample_every.max(1),
+            count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Whether the next message should be mirrored. Sampling is deterministic,
+    /// starting with the first message.
+    fn sample(&self) -> bool {
+        self.count.fetch_add(1, Ordering::Relaxed) % self.sample_every == 0
+    }
+}
+
+/// Deliver a message to Slack, unless delivery is currently suppressed.
+///
+/// Mirroring to the [Shadow] channel, if any, is best effort and happens
+/// irrespective of whether the primary delivery succeeded.
+pub async fn deliver(deps: &Deps, msg: &Message, source: Source) -> Result<Delivery, SlackError> {
+    deliver_(deps, msg, source, None).await
+}
+
+/// Deliver a message rendered from an [Event] as per [deliver]. If the event
+/// is about an app, the message is grouped in a thread with other messages
+/// about the same app if [crate::threading] is enabled, and the app's status
+/// is updated if [crate::status] is.
+pub async fn deliver_event(
+    deps: &Deps,
+    evt: &Event,
+    msg: &Message,
+) -> Result<Delivery, SlackError> {
+    let res = deliver_(deps, msg, evt.source, evt.app.as_deref()).await;
+
+    if let (Ok(Delivery::Sent), Some(x)) = (&res, &deps.status_boards) {
+        x.record(&msg.channel, evt);
+    }
+
+    res
+}
+
+async fn deliver_(
+    deps: &Deps,
+    msg: &Message,
+    source: Source,
+    app: Option<&str>,
+) -> Result<Delivery, SlackError> {
+    record_channel(&msg.channel);
+    let res = try_deliver(deps, msg, Priority::of(msg, source), app).await;
+
+    if let Err(e) = &res {
+        notify_ops(deps, msg, e).await;
+
+        if let Some(x) = &deps.sms {
+            x.fallback(msg, e).await;
+        }
+    }
+
+    if let Some(x) = &deps.meta_alerts {
+        match &res {
+            Ok(Delivery::Sent) => x.record_success().await,
+            Ok(Delivery::Suppressed(_)) => {}
+            Err(e) => x.record_failure(e).await,
+        }
+    }
+
+    let id = deps.audit.record(msg, source, &res);
+    info!("Recorded delivery to {} as audit entry {}", msg.channel, id);
+
+    if let Ok(Delivery::Sent) = res {
+        let now = Utc::now();
+
+        if deps.console.is_tee() {
+            deps.console.write(msg, source, app);
+        }
+
+        deps.stats.record(now, &msg.channel, source);
+        deps.events
+            .publish(StreamEvent::new(id, now, source, app, msg));
+    }
+
+    res
+}
+
+/// Best effort notify the ops channel of a failure, if it's one an operator
+/// can remedy.
+async fn notify_ops(deps: &Deps, msg: &Message, e: &SlackError) {
+    let (Some(channel), Some(hint)) = (&deps.ops_channel, e.remediation()) else {
+        return;
+    };
+
+    // Don't compound the failure by retrying the same channel.
+    if channel.0.trim_start_matches('#') == msg.channel.0.trim_start_matches('#') {
+        return;
+    }
+
+    let desc = format!(
+        "Failed to deliver \"{}\" to {}: {}\n{}",
+        msg.title, msg.channel, e, hint
+    );
+
+    notify_ops_channel(deps, channel, desc).await;
+}
+
+/// Best effort post a warning to the ops channel, bypassing delivery so as
+/// not to be subject to suppression.
+pub async fn notify_ops_channel(deps: &Deps, channel: &ChannelName, desc: String) {
+    let notice = Message {
+        severity: Some(Severity::Warning),
+        timestamp: Some(Utc::now()),
+        ..Message::new(channel.clone(), "Mercury", desc)
+    };
+
+    let token = deps.slack_token.load_full();
+    let res = deps
+        .slack_client
+        .lock()
+        .await
+        .post_message(&notice, &token)
+        .await;
+
+    if let Err(e) = res {
+        warn!("Failed to notify ops channel {}: {}", channel, e);
+    }
+}
+
+async fn try_deliver(
+    deps: &Deps,
+    msg: &Message,
+    priority: Priority,
+    key: Option<&str>,
+) -> Result<Delivery, SlackError> {
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode, suppressing message to {}", msg.channel);
+
+        return Ok(Delivery::Suppressed("read-only"));
+    }
+
+    let admission = deps.noise_budgets.as_ref().map(|x| x.admit(&msg.channel));
+    if admission == Some(Admission::Exceeded) {
+        info!(
+            "Noise budget exceeded, suppressing message to {}",
+            msg.channel
+        );
+
+        return Ok(Delivery::Suppressed("noise-budget"));
+    }
+
+    let _turn = deps.lanes.turn(priority).await;
+    let token = deps.slack_token.load_full();
+    let mut client = deps.slack_client.lock().await;
+
+    // Best effort, the message itself matters more.
+    if let Some(Admission::Allowed { summary: Some(n) }) = admission {
+        let summary = summary_message(msg.channel.clone(), n);
+
+        if let Err(e) = client.post_message(&summary, &token).await {
+            warn!(
+                "Failed to post noise budget summary to {}: {}",
+                msg.channel, e
+            );
+        }
+    }
+
+    // Only critical messages are worth escalating. See [crate::escalation].
+    let escalations = deps
+        .escalations
+        .as_ref()
+        .filter(|_| msg.severity == Some(Severity::Critical));
+
+    let thread = key.zip(deps.threads.as_ref());
+    // Threads requested explicitly take precedence over our own.
+    let parent_ts = match &msg.thread_ts {
+        Some(x) => Some(x.clone()),
+        None => thread.and_then(|(k, x)| x.get_parent_ts(&msg.channel, k)),
+    };
+
+    let opts = PostOptions {
+        acknowledgeable: escalations.is_some(),
+        thread_ts: parent_ts.as_deref(),
+    };
+
+    let res = client.post_message_with(msg, &opts, &token).await;
+
+    if let Err(SlackError::APIResponseError(APIError::RateLimited {
+        retry_after: Some(x),
+    })) = &res
+    {
+        warn!("Rate limited, pausing deliveries for {}s", x.as_secs());
+        deps.lanes.pause(*x);
+    }
+
+    if let Ok(m) = &res {
+        match (m, escalations) {
+            (Some(m), Some(x)) => x.track(m.clone()),
+            (None, Some(_)) => warn!(
+                "No timestamp for message to {}, can't escalate",
+                msg.channel
+            ),
+            (_, None) => {}
+        }
+
+        if let (Some(m), Some((k, x)), None) = (m, thread, &parent_ts) {
+            x.start(&msg.channel, k, m.clone());
+        }
+    }
+
+    if let Some(shadow) = deps.shadow.as_ref().filter(|x| x.sample()) {
+        let mirror = Message {
+            channel: shadow.channel.clone(),
+            ..msg.clone()
+        };
+
+        if let Err(e) = client.post_message(&mirror, &token).await {
+            warn!("Failed to mirror message to {}: {}", shadow.channel, e);
+        }
+    }
+
+    res.map(|_| Delivery::Sent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_as_str() {
+        for x in [
+            Source::Api,
+            Source::Heroku,
+            Source::Selftest,
+            Source::Replay,
+        ] {
+            assert_eq!(serde_json::to_value(x).unwrap(), x.as_str());
+        }
+    }
+
+    #[test]
+    fn test_shadow_sample() {
+        let every = Shadow::new(ChannelName("any".into()), 1);
+        assert!((0..5).all(|_| every.sample()));
+
+        let third = Shadow::new(ChannelName("any".into()), 3);
+        assert_eq!(
+            (0..7).map(|_| third.sample()).collect::<Vec<_>>(),
+            vec![true, false, false, true, false, false, true]
+        );
+
+        let zero = Shadow::new(ChannelName("any".into()), 0);
+        assert!((0..5).all(|_| zero.sample()));
+    }
+}
@@ -0,0 +1,232 @@
+//! Escalate critical messages which nobody acknowledges in time.
+//!
+//! An escalation policy is configured via `$ESCALATION_POLICY` as a
+//! comma-separated list of `minutes:@handle` stages, for example
+//! `15:@sre,60:@eng-leads`. Critical messages are then posted with a button
+//! with which to acknowledge them. If by the time a stage is due nobody has
+//! clicked the button or reacted to the message, the stage's user group is
+//! mentioned in the message's thread.
+//!
+//! Clicking the button requires Slack's interactivity to be configured. See
+//! [crate::slack::interactivity].
+
+use crate::{
+    bounded::{BoundedMap, StateMetrics},
+    router::Deps,
+    slack::{
+        mention::Mention,
+        message::{fmt_mention, MessageRef},
+    },
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// How often to check for escalations which are due.
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// The most messages awaiting acknowledgement at once, beyond which those
+/// posted longest ago are forgotten.
+const MAX_PENDING: usize = 10_000;
+
+/// A single step of an [EscalationPolicy].
+pub struct Stage {
+    /// How long after the message was posted to escalate.
+    pub after: Duration,
+    pub cc: Mention,
+}
+
+/// Stages in the order in which they're due.
+pub type EscalationPolicy = Vec<Stage>;
+
+/// Parse an escalation policy from its environment variable representation.
+/// Any invalid stage invalidates the whole policy.
+///
+/// ```
+/// let x = parse_policy("60:@eng-leads, 15:@sre").unwrap();
+/// assert_eq!(x[0].after, Duration::from_secs(60 * 15));
+/// ```
+pub fn parse_policy(x: &str) -> Option<EscalationPolicy> {
+    let mut xs = x
+        .split(',')
+        .map(|stage| {
+            let (mins, handle) = stage.trim().split_once(':')?;
+            let mins: u64 = mins.parse().ok()?;
+            let handle = handle.trim_start_matches('@');
+
+            (!handle.is_empty()).then(|| Stage {
+                after: Duration::from_secs(60 * mins),
+                cc: Mention::from(handle.to_owned()),
+            })
+        })
+        .collect::<Option<EscalationPolicy>>()?;
+
+    xs.sort_by_key(|s| s.after);
+
+    Some(xs)
+}
+
+/// A message awaiting acknowledgement.
+struct Pending {
+    posted_at: Instant,
+    /// An index into the [EscalationPolicy].
+    next_stage: usize,
+}
+
+/// Tracks messages awaiting acknowledgement against a policy.
+pub struct Escalations {
+    policy: EscalationPolicy,
+    pending: Mutex<BoundedMap<MessageRef, Pending>>,
+}
+
+impl Escalations {
+    pub fn new(policy: EscalationPolicy, metrics: StateMetrics) -> Self {
+        Escalations {
+            policy,
+            pending: Mutex::new(BoundedMap::new(MAX_PENDING, metrics)),
+        }
+    }
+
+    pub fn policy(&self) -> &EscalationPolicy {
+        &self.policy
+    }
+
+    /// Start tracking a newly posted message.
+    pub fn track(&self, m: MessageRef) {
+        let x = Pending {
+            posted_at: Instant::now(),
+            next_stage: 0,
+        };
+
+        self.pending.lock().unwrap().insert(m, x);
+    }
+
+    /// Stop tracking a message, returning whether it was being tracked.
+    pub fn acknowledge(&self, m: &MessageRef) -> bool {
+        self.pending.lock().unwrap().remove(m).is_some()
+    }
+
+    /// Get the messages which are due to escalate, each with the stage now
+    /// due, advancing past it. Messages with no further stages are forgotten.
+    fn take_due(&self) -> Vec<(MessageRef, &Stage)> {
+        let mut pending = self.pending.lock().unwrap();
+        let mut due = Vec::new();
+
+        pending.retain(|m, x| {
+            let Some(stage) = self.policy.get(x.next_stage) else {
+                return false;
+            };
+
+            if x.posted_at.elapsed() < stage.after {
+                return true;
+            }
+
+            due.push((m.clone(), stage));
+            x.next_stage += 1;
+
+            x.next_stage < self.policy.len()
+        });
+
+        due
+    }
+}
+
+/// Indefinitely escalate messages as they fall due. Reactions are checked
+/// lazily just before escalating, sparing us from subscribing to Slack's
+/// events.
+pub async fn watch_escalations(deps: Deps, escalations: Arc<Escalations>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for (m, stage) in escalations.take_due() {
+            let token = deps.slack_token.load_full();
+            let mut client = deps.slack_client.lock().await;
+
+            match client.has_reactions(&m, &token).await {
+                Ok(true) => {
+                    escalations.acknowledge(&m);
+                    info!("Message {} acknowledged by reaction", m.ts);
+                    continue;
+                }
+                Ok(false) => {}
+                // Better to escalate needlessly than not at all.
+                Err(e) => warn!("Failed to get reactions to message {}: {}", m.ts, e),
+            }
+
+            let cc = client.resolve_mention(&stage.cc, &token).await;
+            let text = format!(
+                "Unacknowledged after {} minutes, escalating. {}",
+                stage.after.as_secs() / 60,
+                fmt_mention(&cc)
+            );
+
+            match client.post_reply(&m, text, &token).await {
+                Ok(_) => info!("Escalated message {}", m.ts),
+                Err(e) => warn!("Failed to escalate message {}: {}", m.ts, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::Metrics, slack::channel::ChannelId};
+    use mock_instant::MockClock;
+
+    fn msg(ts: &str) -> MessageRef {
+        MessageRef {
+            channel_id: ChannelId("C123".into()),
+            ts: ts.into(),
+        }
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let xs = parse_policy("60:@eng-leads, 15:sre").unwrap();
+        assert_eq!(
+            xs.iter().map(|s| s.after.as_secs()).collect::<Vec<_>>(),
+            vec![900, 3600]
+        );
+        assert_eq!(String::from(xs[0].cc.clone()), "@sre");
+
+        assert!(parse_policy("15:@sre,soon:@eng-leads").is_none());
+        assert!(parse_policy("15:@").is_none());
+        assert!(parse_policy("").is_none());
+    }
+
+    #[test]
+    fn test_take_due() {
+        let x = Escalations::new(
+            parse_policy("1:@sre,5:@eng-leads").unwrap(),
+            StateMetrics::new(&Metrics::new(), "escalations"),
+        );
+
+        x.track(msg("1"));
+        x.track(msg("2"));
+        assert!(x.take_due().is_empty());
+
+        MockClock::advance(Duration::from_secs(60));
+        assert!(x.acknowledge(&msg("2")));
+        let due = x.take_due();
+        assert_eq!(due.len(), 1);
+        assert_eq!(String::from(due[0].1.cc.clone()), "@sre");
+        assert!(x.take_due().is_empty());
+
+        MockClock::advance(Duration::from_secs(60 * 4));
+        let due = x.take_due();
+        assert_eq!(String::from(due[0].1.cc.clone()), "@eng-leads");
+
+        // Every stage has been exhausted.
+        assert!(!x.acknowledge(&msg("1")));
+    }
+}
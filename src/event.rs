@@ -0,0 +1,57 @@
+//! A normalized event which inbound sources decode into and outbound
+//! platforms render from, such that neither need know about the other.
+//!
+//! Adding a source requires only decoding into an [Event], and adding a
+//! platform requires only rendering from one. See for example
+//! [crate::heroku::webhook] and [crate::slack::Message::from_event].
+//!
+//! Messages sent directly via the API are already in the shape of a message
+//! and so bypass this.
+
+use crate::{delivery::Source, slack::Severity};
+use chrono::{DateTime, Utc};
+use url::Url;
+
+/// Something that happened, described independently of how it's presented.
+#[derive(Clone)]
+pub struct Event {
+    pub source: Source,
+    pub kind: EventKind,
+    /// The app the event is about, if any.
+    pub app: Option<String>,
+    /// The version of the app the event is about, if known, for example a
+    /// Heroku release's `v42`.
+    pub version: Option<String>,
+    pub severity: Option<Severity>,
+    pub occurred_at: Option<DateTime<Utc>>,
+    /// A short heading, typically the subject of the event.
+    pub title: String,
+    /// A human-readable description of what happened, which may span lines.
+    pub summary: String,
+    /// Structured metadata, in order of presentation.
+    pub fields: Vec<(String, String)>,
+    /// Related pages, the most relevant first.
+    pub links: Vec<Url>,
+}
+
+/// The category of an [Event], which platforms may use to decorate it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Deploy,
+    Rollback,
+    ConfigChange,
+    Crash,
+}
+
+impl EventKind {
+    /// The name of the kind as it's configured, for example in
+    /// [crate::heroku::emoji] rules, and serialised.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Deploy => "deploy",
+            EventKind::Rollback => "rollback",
+            EventKind::ConfigChange => "config",
+            EventKind::Crash => "crash",
+        }
+    }
+}
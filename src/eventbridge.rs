@@ -0,0 +1,455 @@
+//! Publish normalized [events](Event) to an
+//! [Amazon EventBridge](https://aws.amazon.com/eventbridge/) bus, so that
+//! serverless consumers such as Lambda functions can react to deploys and
+//! crashes without another webhook integration.
+//!
+//! Enabled by configuring the bus's name or ARN at `$EVENTBRIDGE_BUS`, along
+//! with `$AWS_REGION` and credentials at `$AWS_ACCESS_KEY_ID` and
+//! `$AWS_SECRET_ACCESS_KEY`, and `$AWS_SESSION_TOKEN` if they're temporary.
+//! The credentials require `events:PutEvents` on the bus.
+//!
+//! Events are published with the source `mercury` and their kind, for example
+//! `deploy` or `crash`, as the detail type, so that rules can match on either.
+//! Publishing is best effort, in addition to wherever the event's headed, and
+//! suppressed in read-only mode.
+
+use crate::{delivery::Source, event::Event, slack::Severity};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tracing::{info, warn};
+use url::Url;
+
+/// The source events are published with.
+const SOURCE: &str = "mercury";
+
+/// The AWS service name requests are signed for.
+const SERVICE: &str = "events";
+
+/// How long to wait for EventBridge to accept each event.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Credentials for signing requests. See
+/// <https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv.html>.
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Only present for temporary credentials.
+    pub session_token: Option<String>,
+}
+
+/// <https://docs.aws.amazon.com/eventbridge/latest/APIReference/API_PutEvents.html>
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PutEventsRequest<'a> {
+    entries: [PutEventsEntry<'a>; 1],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PutEventsEntry<'a> {
+    source: &'static str,
+    detail_type: &'static str,
+    /// A JSON-encoded [Detail].
+    detail: String,
+    event_bus_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<i64>,
+}
+
+/// The event as consumers receive it.
+#[derive(Serialize)]
+struct Detail<'a> {
+    source: Source,
+    kind: &'static str,
+    app: Option<&'a str>,
+    severity: Option<Severity>,
+    occurred_at: Option<DateTime<Utc>>,
+    title: &'a str,
+    summary: &'a str,
+    fields: Vec<(&'a str, &'a str)>,
+    links: Vec<&'a str>,
+}
+
+/// Publishes events to a bus, safe to share across requests.
+pub struct EventBridge {
+    client: reqwest::Client,
+    endpoint: Url,
+    region: String,
+    credentials: AwsCredentials,
+    bus: String,
+}
+
+impl EventBridge {
+    pub fn new(region: String, credentials: AwsCredentials, bus: String) -> Self {
+        let endpoint = format!("https://events.{}.amazonaws.com/", region)
+            .parse()
+            .expect("Could not parse EventBridge endpoint from region");
+
+        EventBridge {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            endpoint,
+            region,
+            credentials,
+            bus,
+        }
+    }
+
+    /// Publish to an endpoint other than the region's, for example a VPC
+    /// endpoint.
+    pub fn with_endpoint(mut self, x: Url) -> Self {
+        self.endpoint = x;
+        self
+    }
+
+    /// Best effort publish an event.
+    pub async fn publish(&self, evt: &Event) {
+        match self.publish_(evt).await {
+            Ok(()) => info!("Published {:?} event to EventBridge", evt.kind),
+            Err(e) => warn!("Failed to publish event to EventBridge: {}", e),
+        }
+    }
+
+    async fn publish_(&self, evt: &Event) -> Result<(), String> {
+        let body = serde_json::to_vec(&PutEventsRequest {
+            entries: [PutEventsEntry {
+                source: SOURCE,
+                detail_type: evt.kind.as_str(),
+                detail: serde_json::to_string(&to_detail(evt)).map_err(|e| e.to_string())?,
+                event_bus_name: &self.bus,
+                time: evt.occurred_at.map(|x| x.timestamp()),
+            }],
+        })
+        .map_err(|e| e.to_string())?;
+
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or_default(), port),
+            None => self.endpoint.host_str().unwrap_or_default().to_owned(),
+        };
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("content-type", "application/x-amz-json-1.1"),
+            ("host", host.as_str()),
+            ("x-amz-date", amz_date.as_str()),
+            ("x-amz-target", "AWSEvents.PutEvents"),
+        ];
+        if let Some(x) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", x));
+        }
+        headers.sort();
+
+        let auth = authorization(
+            &self.credentials,
+            &Scope {
+                region: &self.region,
+                service: SERVICE,
+            },
+            &now,
+            "POST",
+            self.endpoint.path(),
+            &headers,
+            &body,
+        );
+
+        let mut req = self.client.post(self.endpoint.clone());
+        for (k, v) in headers.iter().filter(|(k, _)| *k != "host") {
+            req = req.header(*k, *v);
+        }
+
+        let res = req
+            .header("authorization", auth)
+            .body(body)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map_err(|e| e.to_string())?;
+
+        // Entries can fail individually despite a successful response.
+        let x: serde_json::Value = res.json().await.map_err(|e| e.to_string())?;
+        match x["FailedEntryCount"].as_u64() {
+            Some(0) | None => Ok(()),
+            Some(_) => Err(format!(
+                "{}: {}",
+                x["Entries"][0]["ErrorCode"].as_str().unwrap_or_default(),
+                x["Entries"][0]["ErrorMessage"].as_str().unwrap_or_default()
+            )),
+        }
+    }
+}
+
+fn to_detail(evt: &Event) -> Detail<'_> {
+    Detail {
+        source: evt.source,
+        kind: evt.kind.as_str(),
+        app: evt.app.as_deref(),
+        severity: evt.severity,
+        occurred_at: evt.occurred_at,
+        title: &evt.title,
+        summary: &evt.summary,
+        fields: evt
+            .fields
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect(),
+        links: evt.links.iter().map(|x| x.as_str()).collect(),
+    }
+}
+
+/// The region and service for which a request is signed.
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    region: &'a str,
+    service: &'a str,
+}
+
+/// Sign a request with AWS Signature Version 4, returning the `Authorization`
+/// header. Headers must be lowercase and sorted, and include `host` and
+/// `x-amz-date`. The path mustn't need further encoding, and there mustn't be a
+/// query string.
+fn authorization(
+    credentials: &AwsCredentials,
+    scope: &Scope,
+    now: &DateTime<Utc>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> String {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+
+    let signed_headers = headers
+        .iter()
+        .map(|(k, _)| *k)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(k, v)| format!("{}:{}\n", k, v.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n\n{}\n{}\n{}",
+        method,
+        path,
+        canonical_headers,
+        signed_headers,
+        hex::encode(Sha256::digest(body))
+    );
+
+    let Scope { region, service } = *scope;
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = signing_key(&credentials.secret_access_key, &date, region, service);
+    let signature = hex::encode(hmac(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+/// <https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html#derive-signing-key>
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let x = hmac(format!("AWS4{}", secret).as_bytes(), date.as_bytes());
+    let x = hmac(&x, region.as_bytes());
+    let x = hmac(&x, service.as_bytes());
+
+    hmac(&x, b"aws4_request")
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventKind;
+    use chrono::TimeZone;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    #[test]
+    fn test_signing_key() {
+        assert_eq!(
+            hex::encode(signing_key(SECRET, "20120215", "us-east-1", "iam")),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    /// The `get-vanilla` and `post-vanilla` cases from AWS's Signature Version
+    /// 4 test suite.
+    #[test]
+    fn test_authorization_known_answer() {
+        let credentials = AwsCredentials {
+            access_key_id: String::from("AKIDEXAMPLE"),
+            secret_access_key: SECRET.into(),
+            session_token: None,
+        };
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        let headers = [
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ];
+        let sign = |method| {
+            authorization(
+                &credentials,
+                &Scope {
+                    region: "us-east-1",
+                    service: "service",
+                },
+                &now,
+                method,
+                "/",
+                &headers,
+                b"",
+            )
+        };
+
+        assert_eq!(
+            sign("GET"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+        assert_eq!(
+            sign("POST"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let credentials = AwsCredentials {
+            access_key_id: String::from("AKIDEXAMPLE"),
+            secret_access_key: SECRET.into(),
+            session_token: None,
+        };
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        let headers = [
+            ("content-type", "application/x-amz-json-1.1"),
+            ("host", "events.us-east-1.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ];
+
+        let x = authorization(
+            &credentials,
+            &Scope {
+                region: "us-east-1",
+                service: "events",
+            },
+            &now,
+            "POST",
+            "/",
+            &headers,
+            b"{}",
+        );
+
+        assert!(x.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/events/aws4_request, SignedHeaders=content-type;host;x-amz-date, Signature="
+        ));
+        // Deterministic, yet sensitive to the body.
+        assert_eq!(
+            x,
+            authorization(
+                &credentials,
+                &Scope {
+                    region: "us-east-1",
+                    service: "events"
+                },
+                &now,
+                "POST",
+                "/",
+                &headers,
+                b"{}"
+            )
+        );
+        assert_ne!(
+            x,
+            authorization(
+                &credentials,
+                &Scope {
+                    region: "us-east-1",
+                    service: "events"
+                },
+                &now,
+                "POST",
+                "/",
+                &headers,
+                b"[]"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_publish() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/")
+            .match_header("x-amz-target", "AWSEvents.PutEvents")
+            .match_header("x-amz-security-token", "session")
+            .match_header(
+                "authorization",
+                Matcher::Regex("^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/".into()),
+            )
+            .match_body(Matcher::PartialJson(json!({
+                "Entries": [{
+                    "Source": "mercury",
+                    "DetailType": "crash",
+                    "EventBusName": "deploys",
+                }],
+            })))
+            .with_body(r#"{"FailedEntryCount": 0, "Entries": [{"EventId": "1"}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = EventBridge::new(
+            String::from("us-east-1"),
+            AwsCredentials {
+                access_key_id: String::from("AKIDEXAMPLE"),
+                secret_access_key: SECRET.into(),
+                session_token: Some(String::from("session")),
+            },
+            String::from("deploys"),
+        )
+        .with_endpoint((srv.url() + "/").parse().unwrap());
+
+        let evt = Event {
+            source: Source::Heroku,
+            kind: EventKind::Crash,
+            app: Some(String::from("api")),
+            version: None,
+            severity: Some(Severity::Critical),
+            occurred_at: None,
+            title: String::from("api"),
+            summary: String::from("Dyno web.1 crashed"),
+            fields: Vec::new(),
+            links: Vec::new(),
+        };
+
+        assert!(x.publish_(&evt).await.is_ok());
+
+        let detail: serde_json::Value =
+            serde_json::from_str(&serde_json::to_string(&to_detail(&evt)).unwrap()).unwrap();
+        assert_eq!(detail["app"], "api");
+        assert_eq!(detail["severity"], "critical");
+
+        mock.assert_async().await;
+    }
+}
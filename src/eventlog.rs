@@ -0,0 +1,300 @@
+//! Write normalized [events](Event) to syslog or journald with structured
+//! fields, so that they're captured by a central log pipeline irrespective of
+//! whether they reach chat.
+//!
+//! Enabled by setting `$EVENT_LOG` to `journald`, to `syslog` for the local
+//! syslog socket at `/dev/log`, or to `udp://host:port` for a remote syslog
+//! server. Syslog messages are formatted as per
+//! [RFC 5424](https://datatracker.ietf.org/doc/html/rfc5424), with the event's
+//! metadata as structured data.
+//!
+//! Events are written whenever they're forwarded, before and regardless of
+//! delivery, including in read-only mode, as they're a record rather than a
+//! notification.
+
+use crate::{event::Event, slack::Severity};
+use chrono::{SecondsFormat, Utc};
+use std::{io, path::PathBuf};
+use tokio::net::{UdpSocket, UnixDatagram};
+use tracing::warn;
+
+/// The path of journald's native protocol socket.
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// The path of the local syslog socket.
+const SYSLOG_SOCKET: &str = "/dev/log";
+
+/// How we identify ourselves to syslog and journald.
+const IDENTIFIER: &str = "mercury";
+
+/// The structured data ID under which metadata is written to syslog. 32473 is
+/// the private enterprise number reserved for documentation, as we have none.
+const SD_ID: &str = "mercury@32473";
+
+/// Where events are written, as configured.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    Journald,
+    Syslog(PathBuf),
+    /// A remote syslog server's `host:port`.
+    SyslogUdp(String),
+}
+
+/// Parse a target from its environment variable representation.
+///
+/// ```
+/// let x = parse_target("udp://logs.example.com:514").unwrap();
+/// ```
+pub fn parse_target(x: &str) -> Result<Target, String> {
+    match x.trim() {
+        "journald" => Ok(Target::Journald),
+        "syslog" => Ok(Target::Syslog(SYSLOG_SOCKET.into())),
+        x => match x.strip_prefix("udp://") {
+            Some(addr)
+                if addr
+                    .rsplit_once(':')
+                    .is_some_and(|(_, p)| p.parse::<u16>().is_ok()) =>
+            {
+                Ok(Target::SyslogUdp(addr.to_owned()))
+            }
+            _ => Err(format!(
+                "expected journald, syslog, or udp://host:port, found: {}",
+                x
+            )),
+        },
+    }
+}
+
+enum Socket {
+    Unix(UnixDatagram, PathBuf),
+    Udp(UdpSocket),
+}
+
+/// Writes events to syslog or journald, safe to share across requests.
+pub struct EventLog {
+    socket: Socket,
+    format: Format,
+}
+
+enum Format {
+    Journald,
+    Syslog,
+}
+
+impl EventLog {
+    pub async fn open(target: Target) -> io::Result<Self> {
+        let (socket, format) = match target {
+            Target::Journald => (
+                Socket::Unix(UnixDatagram::unbound()?, JOURNALD_SOCKET.into()),
+                Format::Journald,
+            ),
+            Target::Syslog(path) => (Socket::Unix(UnixDatagram::unbound()?, path), Format::Syslog),
+            Target::SyslogUdp(addr) => {
+                let x = UdpSocket::bind("0.0.0.0:0").await?;
+                x.connect(addr).await?;
+
+                (Socket::Udp(x), Format::Syslog)
+            }
+        };
+
+        Ok(EventLog { socket, format })
+    }
+
+    /// Write an event, logging rather than failing if it can't be.
+    pub async fn write(&self, evt: &Event) {
+        let buf = match self.format {
+            Format::Journald => to_journald(evt),
+            Format::Syslog => to_syslog(evt).into_bytes(),
+        };
+
+        let res = match &self.socket {
+            Socket::Unix(x, path) => x.send_to(&buf, path).await,
+            Socket::Udp(x) => x.send(&buf).await,
+        };
+
+        if let Err(e) = res {
+            warn!("Failed to write event to event log: {}", e);
+        }
+    }
+}
+
+/// The syslog severity of an event, from 0 (emergency) to 7 (debug).
+fn to_priority(x: Option<Severity>) -> u8 {
+    match x {
+        Some(Severity::Critical) => 2,
+        Some(Severity::Warning) => 4,
+        Some(Severity::Debug) => 7,
+        Some(Severity::Info | Severity::Success) | None => 6,
+    }
+}
+
+/// The message as read by humans.
+fn to_message(evt: &Event) -> String {
+    format!("{}: {}", evt.title, evt.summary)
+}
+
+/// Encode an event as per journald's native protocol, in which values
+/// containing newlines are length-prefixed.
+///
+/// <https://systemd.io/JOURNAL_NATIVE_PROTOCOL/>
+fn to_journald(evt: &Event) -> Vec<u8> {
+    let mut fields = vec![
+        ("MESSAGE", to_message(evt)),
+        ("PRIORITY", to_priority(evt.severity).to_string()),
+        ("SYSLOG_IDENTIFIER", IDENTIFIER.to_owned()),
+        ("MERCURY_SOURCE", evt.source.as_str().to_owned()),
+        ("MERCURY_KIND", evt.kind.as_str().to_owned()),
+        ("MERCURY_TITLE", evt.title.clone()),
+    ];
+    if let Some(x) = &evt.app {
+        fields.push(("MERCURY_APP", x.clone()));
+    }
+    if let Some(x) = evt.links.first() {
+        fields.push(("MERCURY_LINK", x.to_string()));
+    }
+    if !evt.fields.is_empty() {
+        // Serializing strings can't fail.
+        fields.push((
+            "MERCURY_FIELDS",
+            serde_json::to_string(&evt.fields).unwrap(),
+        ));
+    }
+
+    let mut out = Vec::new();
+    for (k, v) in fields {
+        out.extend_from_slice(k.as_bytes());
+
+        match v.contains('\n') {
+            false => {
+                out.push(b'=');
+                out.extend_from_slice(v.as_bytes());
+            }
+            true => {
+                out.push(b'\n');
+                out.extend_from_slice(&(v.len() as u64).to_le_bytes());
+                out.extend_from_slice(v.as_bytes());
+            }
+        }
+
+        out.push(b'\n');
+    }
+
+    out
+}
+
+/// Format an event as per RFC 5424, with the user-level facility.
+fn to_syslog(evt: &Event) -> String {
+    let pri = 8 + to_priority(evt.severity);
+    let at = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+
+    let mut params = vec![
+        ("source", evt.source.as_str().to_owned()),
+        ("kind", evt.kind.as_str().to_owned()),
+    ];
+    if let Some(x) = &evt.app {
+        params.push(("app", x.clone()));
+    }
+    if let Some(x) = evt.links.first() {
+        params.push(("link", x.to_string()));
+    }
+
+    let sd: String = params
+        .iter()
+        .map(|(k, v)| format!(" {}=\"{}\"", k, escape_param(v)))
+        .collect();
+
+    format!(
+        "<{}>1 {} - {} - {} [{}{}] {}",
+        pri,
+        at,
+        IDENTIFIER,
+        evt.kind.as_str(),
+        SD_ID,
+        sd,
+        to_message(evt).replace('\n', " ")
+    )
+}
+
+/// Escape a structured data param value, in which `"`, `\`, and `]` are
+/// special.
+fn escape_param(x: &str) -> String {
+    let mut out = String::with_capacity(x.len());
+
+    for c in x.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, event::EventKind};
+
+    fn crash() -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Crash,
+            app: Some(String::from("api")),
+            version: None,
+            severity: Some(Severity::Critical),
+            occurred_at: None,
+            title: String::from("api"),
+            summary: String::from("Dyno web.1 crashed\nwith \"137\""),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("journald"), Ok(Target::Journald));
+        assert_eq!(
+            parse_target("syslog"),
+            Ok(Target::Syslog(PathBuf::from("/dev/log")))
+        );
+        assert_eq!(
+            parse_target("udp://logs.example.com:514"),
+            Ok(Target::SyslogUdp(String::from("logs.example.com:514")))
+        );
+        assert!(parse_target("udp://logs.example.com").is_err());
+        assert!(parse_target("stdout").is_err());
+    }
+
+    #[test]
+    fn test_to_journald() {
+        let x = to_journald(&crash());
+        let msg = "api: Dyno web.1 crashed\nwith \"137\"";
+
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&(msg.len() as u64).to_le_bytes());
+        expected.extend_from_slice(msg.as_bytes());
+        expected.extend_from_slice(b"\nPRIORITY=2\nSYSLOG_IDENTIFIER=mercury\nMERCURY_SOURCE=heroku\nMERCURY_KIND=crash\nMERCURY_TITLE=api\nMERCURY_APP=api\n");
+
+        assert_eq!(x, expected);
+    }
+
+    #[tokio::test]
+    async fn test_write_udp() {
+        let srv = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = srv.local_addr().unwrap();
+
+        let x = EventLog::open(Target::SyslogUdp(addr.to_string()))
+            .await
+            .unwrap();
+        x.write(&crash()).await;
+
+        let mut buf = [0; 1024];
+        let n = srv.recv(&mut buf).await.unwrap();
+        let msg = std::str::from_utf8(&buf[..n]).unwrap();
+
+        assert!(msg.starts_with("<10>1 "));
+        assert!(msg.ends_with(
+            " - mercury - crash [mercury@32473 source=\"heroku\" kind=\"crash\" app=\"api\"] api: Dyno web.1 crashed with \"137\""
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+//! Explain how a request would be handled without delivering anything, as a
+//! step-by-step [Trace] of the decisions made along the way, to help answer
+//! why a request did or didn't produce the message expected.
+//!
+//! Each source's explain endpoint runs as much of its usual pipeline as can be
+//! run without side effects, for example [crate::heroku::explain]. Changelogs
+//! aren't fetched, and nothing is recorded, counted, or sent. Channels are
+//! however looked up in Slack.
+
+use crate::{
+    delivery::Source,
+    priority::Priority,
+    router::Deps,
+    slack::{channel::ChannelName, Message},
+};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+
+/// A decision made whilst handling a request.
+#[derive(Serialize)]
+pub struct Step {
+    pub step: &'static str,
+    /// Whether handling would proceed past this step.
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// How a request would be handled.
+#[derive(Default, Serialize)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+    /// Whether the request would ultimately be delivered, which is to say
+    /// whether every step would be passed.
+    pub would_deliver: bool,
+    /// The message which would be delivered, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<Message>,
+}
+
+impl Trace {
+    /// Record a step which handling would proceed past.
+    pub fn pass(&mut self, step: &'static str, detail: impl Into<String>) {
+        self.steps.push(Step {
+            step,
+            ok: true,
+            detail: detail.into(),
+        });
+    }
+
+    /// Record a step at which handling would stop.
+    pub fn fail(&mut self, step: &'static str, detail: impl Into<String>) {
+        self.steps.push(Step {
+            step,
+            ok: false,
+            detail: detail.into(),
+        });
+    }
+}
+
+/// Explain whether a request from a source would be accepted for delivery,
+/// returning whether it would. See [crate::ingestion] and [crate::priority].
+pub fn explain_admission(
+    deps: &Deps,
+    trace: &mut Trace,
+    source: Source,
+    priority: Priority,
+) -> bool {
+    if deps.ingestion.held(source).is_some() {
+        trace.fail(
+            "ingestion",
+            format!(
+                "Ingestion from {} is paused, so the request would be held",
+                source.as_str()
+            ),
+        );
+
+        return false;
+    }
+    trace.pass("ingestion", "Not paused");
+
+    if let Some(x) = deps.lanes.backpressure(priority) {
+        trace.fail(
+            "backpressure",
+            format!(
+                "Too many messages are awaiting delivery, so the request would be turned away for {}s",
+                x.as_secs()
+            ),
+        );
+
+        return false;
+    }
+    trace.pass(
+        "backpressure",
+        format!("Admitted at {:?} priority", priority),
+    );
+
+    true
+}
+
+/// Explain whether a message would be delivered, recording it in the trace if
+/// so. See [crate::delivery].
+pub async fn explain_delivery(deps: &Deps, trace: &mut Trace, msg: Message) {
+    if deps.read_only.load(Ordering::Relaxed) {
+        trace.fail("read-only", "Read-only mode is enabled");
+
+        return;
+    }
+
+    if let Some(x) = deps.noise_budgets.as_ref() {
+        if x.is_exceeded(&msg.channel) {
+            trace.fail(
+                "noise-budget",
+                format!("The noise budget of {} is spent", msg.channel),
+            );
+
+            return;
+        }
+    }
+
+    if !explain_channel(deps, trace, &msg.channel).await {
+        return;
+    }
+
+    // Some steps can fail without stopping the trace, such as a bad signature.
+    trace.would_deliver = trace.steps.iter().all(|x| x.ok);
+    trace.message = Some(msg);
+}
+
+/// Explain whether a channel can be found in Slack, returning whether it can.
+async fn explain_channel(deps: &Deps, trace: &mut Trace, channel: &ChannelName) -> bool {
+    let res = deps
+        .slack_client
+        .lock()
+        .await
+        .get_channel_id(channel, &deps.slack_token.load_full())
+        .await;
+
+    match res {
+        Ok(_) => {
+            trace.pass("channel", format!("Found {} in Slack", channel));
+
+            true
+        }
+        Err(e) => {
+            trace.fail("channel", format!("Failed to find {}: {}", channel, e));
+
+            false
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Expose recently sent messages as an Atom feed per [Source], optionally
+//! narrowed to a single channel, for those who'd rather follow along in a feed
+//! reader than in Slack.
+//!
+//! Feeds are built from the in-memory [crate::audit] history, so they reset on
+//! restart and include at most [FEED_LIMIT] entries.
+
+pub mod router;
+
+use crate::{audit::AuditEntry, delivery::Source};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// The most entries included in any one feed.
+pub const FEED_LIMIT: usize = 50;
+
+/// Render a feed of entries, which are expected to be ordered newest first.
+/// The feed is considered updated as of its newest entry, or else `now`.
+pub fn to_atom(
+    source: Source,
+    channel: Option<&str>,
+    entries: &[AuditEntry],
+    now: DateTime<Utc>,
+) -> String {
+    let name = source_name(source);
+    let (id, title) = match channel {
+        Some(c) => (
+            format!("urn:mercury:feed:{}:{}", name, c),
+            format!("Mercury: {} in #{}", name, c),
+        ),
+        None => (
+            format!("urn:mercury:feed:{}", name),
+            format!("Mercury: {}", name),
+        ),
+    };
+    let updated = entries.first().map_or(now, |x| x.at);
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    out.push_str(&format!("  <id>{}</id>\n", escape_xml(&id)));
+    out.push_str(&format!("  <title>{}</title>\n", escape_xml(&title)));
+    out.push_str(&format!("  <updated>{}</updated>\n", fmt_date(&updated)));
+    out.push_str("  <author><name>Mercury</name></author>\n");
+
+    for x in entries {
+        out.push_str(&fmt_entry(x));
+    }
+
+    out.push_str("</feed>\n");
+    out
+}
+
+fn fmt_entry(x: &AuditEntry) -> String {
+    let msg = &x.message;
+    let channel = msg.channel.0.trim_start_matches('#');
+
+    let mut out = String::from("  <entry>\n");
+    out.push_str(&format!("    <id>urn:mercury:audit:{}</id>\n", x.id));
+    out.push_str(&format!("    <title>{}</title>\n", escape_xml(&msg.title)));
+    out.push_str(&format!("    <updated>{}</updated>\n", fmt_date(&x.at)));
+    out.push_str(&format!(
+        "    <category term=\"{}\"/>\n",
+        escape_xml(channel)
+    ));
+    if let Some(link) = &msg.link {
+        out.push_str(&format!(
+            "    <link href=\"{}\"/>\n",
+            escape_xml(link.as_str())
+        ));
+    }
+    out.push_str(&format!(
+        "    <summary>{}</summary>\n",
+        escape_xml(&msg.desc)
+    ));
+    out.push_str("  </entry>\n");
+    out
+}
+
+/// The name by which a source is referred to in feed paths, matching its
+/// serialized form.
+pub fn source_name(x: Source) -> &'static str {
+    match x {
+        Source::Api => "api",
+        Source::Heroku => "heroku",
+        Source::Selftest => "selftest",
+        Source::Replay => "replay",
+    }
+}
+
+/// Format a date as Atom expects, per RFC 3339.
+fn fmt_date(x: &DateTime<Utc>) -> String {
+    x.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Escape text for inclusion in XML, whether as content or as an attribute
+/// value.
+///
+/// ```
+/// assert_eq!(escape_xml("<b>&</b>"), "&lt;b&gt;&amp;&lt;/b&gt;");
+/// ```
+pub fn escape_xml(x: &str) -> String {
+    let mut out = String::with_capacity(x.len());
+
+    for c in x.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audit::Outcome,
+        slack::{channel::ChannelName, Message},
+    };
+    use url::Url;
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(escape_xml("plain"), "plain");
+        assert_eq!(
+            escape_xml("<a href=\"#\">'&'</a>"),
+            "&lt;a href=&quot;#&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_to_atom() {
+        let at = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .into();
+        let entry = AuditEntry {
+            id: 42,
+            at,
+            message: Message {
+                link: Some(Url::parse("https://unsplash.com/?a=b&c=d").unwrap()),
+                ..Message::new(ChannelName("any".into()), "Deploy <mercury>", "any")
+            },
+            outcome: Outcome::Sent,
+            source: Some(Source::Heroku),
+        };
+
+        let out = to_atom(Source::Heroku, Some("deploys"), &[entry], Utc::now());
+
+        assert!(out.contains("<id>urn:mercury:feed:heroku:deploys</id>"));
+        assert!(out.contains("<title>Mercury: heroku in #deploys</title>"));
+        assert!(out.contains("<updated>2024-01-02T03:04:05Z</updated>"));
+        assert!(out.contains("<id>urn:mercury:audit:42</id>"));
+        assert!(out.contains("<title>Deploy &lt;mercury&gt;</title>"));
+        assert!(out.contains("<category term=\"any\"/>"));
+        assert!(out.contains("<link href=\"https://unsplash.com/?a=b&amp;c=d\"/>"));
+        assert!(out.ends_with("</feed>\n"));
+    }
+
+    #[test]
+    fn test_to_atom_empty() {
+        let now = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .into();
+
+        let out = to_atom(Source::Api, None, &[], now);
+
+        assert!(out.contains("<id>urn:mercury:feed:api</id>"));
+        assert!(out.contains("<updated>2024-01-02T03:04:05Z</updated>"));
+        assert!(!out.contains("<entry>"));
+    }
+}
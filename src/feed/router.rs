@@ -0,0 +1,88 @@
+//! Feed subrouter definition.
+//!
+//! The following subroutes are supported:
+//!
+//! - GET: `/:source.atom`
+
+use super::{source_name, to_atom, FEED_LIMIT};
+use crate::{
+    audit::Outcome,
+    cache,
+    delivery::Source,
+    router::Deps,
+    slack::{channel::ChannelName, router::authenticate},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+/// The content type of Atom feeds.
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+/// Instantiate a new feed subrouter, authenticated as per the Slack
+/// subrouter, and caching responses as per [crate::cache].
+pub fn feed_router(deps: &Deps) -> Router<Deps> {
+    Router::new()
+        // Path params must span a whole segment, so the extension is parsed
+        // in the handler.
+        .route("/:feed", get(feed_handler))
+        .layer(middleware::from_fn_with_state(
+            deps.response_cache.clone(),
+            cache::cache,
+        ))
+        .layer(middleware::from_fn_with_state(deps.clone(), authenticate))
+}
+
+/// Optional query params for the GET subroute `/:source.atom`.
+#[derive(Deserialize)]
+struct FeedOptions {
+    channel: Option<ChannelName>,
+}
+
+/// Handler for the GET subroute `/:source.atom`.
+///
+/// Responds with an Atom feed of the messages most recently sent from the
+/// given [Source], optionally only those sent to a given `channel`.
+async fn feed_handler(
+    State(deps): State<Deps>,
+    Path(feed): Path<String>,
+    Query(opts): Query<FeedOptions>,
+) -> Response {
+    let Some(source) = feed.strip_suffix(".atom").and_then(parse_source) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // Channels may be written with or without the leading hash.
+    let channel = opts.channel.as_ref().map(|x| x.0.trim_start_matches('#'));
+
+    let entries = deps.audit.recent(FEED_LIMIT, |x| {
+        x.source == Some(source)
+            && matches!(x.outcome, Outcome::Sent)
+            && channel.map_or(true, |c| x.message.channel.0.trim_start_matches('#') == c)
+    });
+
+    (
+        [(CONTENT_TYPE, ATOM_CONTENT_TYPE)],
+        to_atom(source, channel, &entries, Utc::now()),
+    )
+        .into_response()
+}
+
+/// Parse a source from its name in feed paths.
+fn parse_source(x: &str) -> Option<Source> {
+    [
+        Source::Api,
+        Source::Heroku,
+        Source::Selftest,
+        Source::Replay,
+    ]
+    .into_iter()
+    .find(|s| source_name(*s) == x)
+}
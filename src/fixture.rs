@@ -0,0 +1,305 @@
+//! Optionally record inbound payloads as test fixtures, so that supporting an
+//! event variant first seen in production starts from exactly what arrived.
+//!
+//! Enabled by setting `$FIXTURE_DIR` to a directory to write to, for example
+//! `FIXTURE_DIR=/tmp/fixtures`, after which recording is switched on and off
+//! via the admin API. See [crate::admin]. Recording starts off, as every
+//! authenticated payload is written whilst it's on, and switches itself off
+//! again after [MAX_FIXTURES].
+//!
+//! Each payload is written to its own file beneath a subdirectory named after
+//! its source, for example `heroku/20240101T120000Z-0.json`, in the form of an
+//! end-to-end test case as per `tests/e2e.rs`, lacking only the expected Slack
+//! messages. Payloads, query strings, and headers are passed through
+//! [anonymise] first, and headers which authenticate or identify the sender are
+//! omitted entirely, however fixtures should still be reviewed before they're
+//! committed.
+
+use crate::{auth::Authenticated, delivery::Source, redact::anonymise, router::Deps};
+use axum::{
+    body::{self, Body, Bytes},
+    extract::{OriginalUri, Request, State},
+    http::{request::Parts, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::fs;
+use tracing::warn;
+
+/// The largest request body we'll buffer in order to record it, matching
+/// Axum's default limit for extractors.
+const MAX_BUFFERED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// The most fixtures written each time recording is switched on, bounding disk
+/// usage.
+pub const MAX_FIXTURES: u64 = 100;
+
+/// Header name fragments identifying headers which are never recorded.
+const OMITTED_HEADERS: &[&str] = &[
+    "authorization",
+    "cookie",
+    "forwarded",
+    "host",
+    "-ip",
+    "signature",
+    "hmac",
+    "token",
+    "secret",
+];
+
+/// A recorded payload, as written.
+#[derive(Serialize)]
+struct Fixture {
+    /// The path it was sent to, excluding the query string.
+    path: String,
+    /// The query string, if any.
+    query: String,
+    headers: BTreeMap<String, String>,
+    /// The payload, as JSON if it's valid JSON, else as a string. A list for
+    /// the sake of `tests/e2e.rs`, which replays several in order.
+    requests: Vec<Value>,
+    /// The expected Slack messages, left to be filled in.
+    slack: Vec<Value>,
+}
+
+/// Where fixtures are written, and whether they're being recorded, safe to
+/// share across requests.
+pub struct Fixtures {
+    dir: PathBuf,
+    recording: AtomicBool,
+    /// Disambiguates fixtures recorded within the same second.
+    written: AtomicU64,
+    /// How many have been written since recording was switched on, including
+    /// any being written.
+    recorded: AtomicU64,
+}
+
+impl Fixtures {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Fixtures {
+            dir: dir.into(),
+            recording: AtomicBool::new(false),
+            written: AtomicU64::new(0),
+            recorded: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    pub fn set_recording(&self, x: bool) {
+        if x {
+            self.recorded.store(0, Ordering::Relaxed);
+        }
+        self.recording.store(x, Ordering::Relaxed);
+    }
+
+    /// Claim one of the fixtures remaining before [MAX_FIXTURES], switching
+    /// recording off once they've all been claimed.
+    fn claim(&self) -> bool {
+        let n = self.recorded.fetch_add(1, Ordering::Relaxed) + 1;
+        if n >= MAX_FIXTURES {
+            self.recording.store(false, Ordering::Relaxed);
+        }
+        if n == MAX_FIXTURES {
+            warn!("Recorded {} fixtures, stopped recording", MAX_FIXTURES);
+        }
+
+        n <= MAX_FIXTURES
+    }
+
+    async fn write(&self, source: Source, x: &Fixture) -> Result<PathBuf, String> {
+        let dir = self.dir.join(source.as_str());
+        fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+
+        let n = self.written.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(
+            "{}-{}.json",
+            Utc::now().format("%Y%m%dT%H%M%SZ"),
+            n
+        ));
+
+        // Serialising a map of strings and JSON values is infallible.
+        let mut json = serde_json::to_vec_pretty(x).unwrap();
+        json.push(b'\n');
+
+        fs::write(&path, json)
+            .await
+            .map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+
+        Ok(path)
+    }
+}
+
+/// Record requests to a subrouter as being from a source, if enabled.
+pub fn record_from(router: Router<Deps>, deps: &Deps, source: Source) -> Router<Deps> {
+    match &deps.fixtures {
+        None => router,
+        Some(x) => router.layer(middleware::from_fn_with_state(
+            (x.clone(), source),
+            record_inbound,
+        )),
+    }
+}
+
+/// Middleware writing each authenticated inbound request's anonymised payload
+/// as a fixture whilst recording, once it's been handled.
+async fn record_inbound(
+    State((fixtures, source)): State<(Arc<Fixtures>, Source)>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !fixtures.is_recording() {
+        return next.run(req).await;
+    }
+
+    let (mut parts, body) = req.into_parts();
+    let authenticated = Authenticated::track(&mut parts.extensions);
+
+    let Ok(bytes) = body::to_bytes(body, MAX_BUFFERED_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+
+    let fixture = to_fixture(&parts, &bytes);
+    let res = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+
+    if authenticated.is_marked() && fixtures.claim() {
+        if let Err(e) = fixtures.write(source, &fixture).await {
+            warn!("Failed to record fixture: {}", e);
+        }
+    }
+
+    res
+}
+
+fn to_fixture(parts: &Parts, body: &Bytes) -> Fixture {
+    // Nested routers only see the remainder of the path.
+    let uri = parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(&parts.uri, |x| &x.0);
+
+    let headers = parts
+        .headers
+        .iter()
+        .filter(|(k, _)| !is_omitted_header(k.as_str()))
+        .filter_map(|(k, v)| Some((k.to_string(), anonymise(v.to_str().ok()?))))
+        .collect();
+
+    let body = anonymise(&String::from_utf8_lossy(body));
+    let body = serde_json::from_str(&body).unwrap_or(Value::String(body));
+
+    Fixture {
+        path: uri.path().to_owned(),
+        query: anonymise(uri.query().unwrap_or_default()),
+        headers,
+        requests: vec![body],
+        slack: Vec::new(),
+    }
+}
+
+fn is_omitted_header(name: &str) -> bool {
+    OMITTED_HEADERS.iter().any(|x| name.contains(x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(uri: &str, headers: &[(&str, &str)]) -> Parts {
+        let mut req = Request::builder().method("POST").uri(uri);
+        for (k, v) in headers {
+            req = req.header(*k, *v);
+        }
+
+        req.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn test_to_fixture() {
+        let x = to_fixture(
+            &parts(
+                "/api/v1/heroku/hook?platform=slack&channel=deploys",
+                &[
+                    ("content-type", "application/json"),
+                    ("heroku-webhook-hmac-sha256", "abc123="),
+                    ("x-forwarded-for", "10.0.0.1"),
+                    ("authorization", "Bearer foobar"),
+                ],
+            ),
+            &Bytes::from(r#"{"action":"update","actor":{"email":"hodor@unsplash.com"}}"#),
+        );
+
+        assert_eq!(x.path, "/api/v1/heroku/hook");
+        assert_eq!(x.query, "platform=slack&channel=deploys");
+        assert_eq!(
+            x.headers.into_iter().collect::<Vec<_>>(),
+            vec![(
+                String::from("content-type"),
+                String::from("application/json")
+            )]
+        );
+        assert_eq!(
+            x.requests,
+            vec![serde_json::json!({
+                "action": "update",
+                "actor": { "email": "user@example.com" },
+            })]
+        );
+
+        let x = to_fixture(&parts("/", &[]), &Bytes::from("channel=fp&title=x"));
+        assert_eq!(x.requests, vec![Value::from("channel=fp&title=x")]);
+    }
+
+    #[test]
+    fn test_claim() {
+        let x = Fixtures::new("any");
+        x.set_recording(true);
+
+        for _ in 0..MAX_FIXTURES {
+            assert!(x.claim());
+        }
+        assert!(!x.is_recording());
+        assert!(!x.claim());
+
+        x.set_recording(true);
+        assert!(x.claim());
+    }
+
+    #[tokio::test]
+    async fn test_write() {
+        let dir = std::env::temp_dir().join(format!("mercury-fixture-test-{}", std::process::id()));
+        let x = Fixtures::new(&dir);
+
+        let path = x
+            .write(
+                Source::Heroku,
+                &to_fixture(&parts("/", &[]), &Bytes::from("{}")),
+            )
+            .await
+            .unwrap();
+        assert!(path.starts_with(dir.join("heroku")));
+
+        let written: Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written["requests"], serde_json::json!([{}]));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
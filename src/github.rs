@@ -0,0 +1,20 @@
+//! Enrich notifications with context from GitHub, such as the commits which
+//! make up a release, and report events back to the repository as commit
+//! statuses and issues.
+//!
+//! An access token is required, sourced from `$GITHUB_TOKEN`. A fine-grained
+//! token with read-only access to the contents of the relevant repositories is
+//! sufficient for enrichment. Reporting additionally requires read and write
+//! access to commit statuses and issues.
+
+pub mod api;
+pub mod auth;
+pub mod compare;
+pub mod error;
+pub mod issue;
+pub mod status;
+
+pub use api::GitHubClient;
+pub use auth::GitHubToken;
+pub use compare::GitHubRepo;
+pub use error::GitHubError;
@@ -0,0 +1,48 @@
+//! Type definitions and helpers for the GitHub REST API.
+
+use super::auth::*;
+
+/// The base URL of the GitHub REST API.
+pub const API_BASE: &str = "https://api.github.com";
+
+/// Holds a client request pool against a base URL.
+pub struct GitHubClient {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl GitHubClient {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [API_BASE].
+    pub fn new(base_url: String) -> Self {
+        GitHubClient {
+            client: reqwest::Client::new(),
+            base_url,
+        }
+    }
+
+    /// Create a GET request to any GitHub API endpoint, handling
+    /// authentication and the headers GitHub insists upon.
+    pub fn get<T: ToString>(&self, path: T, token: &GitHubToken) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::GET, path, token)
+    }
+
+    /// Create a POST request to any GitHub API endpoint, as per [Self::get].
+    pub fn post<T: ToString>(&self, path: T, token: &GitHubToken) -> reqwest::RequestBuilder {
+        self.request(reqwest::Method::POST, path, token)
+    }
+
+    fn request<T: ToString>(
+        &self,
+        method: reqwest::Method,
+        path: T,
+        token: &GitHubToken,
+    ) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, self.base_url.clone() + &path.to_string())
+            .header(reqwest::header::AUTHORIZATION, to_auth_header_val(token))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            // Requests without a user agent are rejected.
+            .header(reqwest::header::USER_AGENT, "mercury")
+    }
+}
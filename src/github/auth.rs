@@ -0,0 +1,15 @@
+//! Helpers around GitHub's use of Bearer Authentication.
+
+/// A newtype wrapper around GitHub access tokens.
+#[derive(Clone)]
+pub struct GitHubToken(pub String);
+
+/// Convert a GitHub access token to a `Bearer` `Authorization` header value.
+///
+/// ```
+/// let token = GitHubToken("ghp_foo".into());
+/// assert_eq!(to_auth_header_val(&token), "Bearer ghp_foo");
+/// ```
+pub fn to_auth_header_val(t: &GitHubToken) -> String {
+    format!("Bearer {}", t.0)
+}
@@ -0,0 +1,146 @@
+//! Summarise the commits between two points in a repository's history.
+
+use super::{GitHubClient, GitHubError, GitHubToken};
+use serde::Deserialize;
+use std::fmt;
+
+/// The maximum number of commit subjects retained in a [Changelog].
+const MAX_SUBJECTS: usize = 5;
+
+/// A repository identified by its owner and name.
+///
+/// ```
+/// let repo = GitHubRepo("unsplash/mercury".into());
+/// ```
+#[derive(Clone, Deserialize)]
+pub struct GitHubRepo(pub String);
+
+/// Format without the surrounding newtype wrapper.
+///
+/// ```
+/// let x = GitHubRepo("unsplash/mercury".into());
+/// assert_eq!(format!("{}", x), "unsplash/mercury");
+/// ```
+impl fmt::Display for GitHubRepo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A summary of the commits between two points in a repository's history.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Changelog {
+    /// The total number of commits, which may exceed the number of subjects.
+    pub total_commits: usize,
+    /// The subjects of the most recent commits, most recent first.
+    pub subjects: Vec<String>,
+}
+
+/// <https://docs.github.com/en/rest/commits/commits#compare-two-commits>
+#[derive(Deserialize)]
+struct CompareResponse {
+    total_commits: usize,
+    /// Ordered oldest first.
+    commits: Vec<CommitMeta>,
+}
+
+/// The metadata we care about per-commit within [CompareResponse].
+#[derive(Deserialize)]
+struct CommitMeta {
+    commit: CommitData,
+}
+
+#[derive(Deserialize)]
+struct CommitData {
+    message: String,
+}
+
+impl GitHubClient {
+    /// Get a [Changelog] of the commits reachable from `head` but not from
+    /// `base`.
+    pub async fn compare(
+        &self,
+        repo: &GitHubRepo,
+        base: &str,
+        head: &str,
+        token: &GitHubToken,
+    ) -> Result<Changelog, GitHubError> {
+        let res: CompareResponse = self
+            .get(
+                format!("/repos/{}/compare/{}...{}", repo, base, head),
+                token,
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(to_changelog(res))
+    }
+}
+
+/// Summarise a [CompareResponse], retaining only the most recent commit
+/// subjects.
+fn to_changelog(res: CompareResponse) -> Changelog {
+    Changelog {
+        total_commits: res.total_commits,
+        subjects: res
+            .commits
+            .iter()
+            .rev()
+            .take(MAX_SUBJECTS)
+            .map(|x| to_subject(&x.commit.message))
+            .collect(),
+    }
+}
+
+/// Get the subject, the first line, of a commit message.
+fn to_subject(msg: &str) -> String {
+    msg.lines().next().unwrap_or_default().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_changelog() {
+        let res = r#"{
+            "status": "ahead",
+            "ahead_by": 7,
+            "behind_by": 0,
+            "total_commits": 7,
+            "commits": [
+                { "sha": "a", "commit": { "message": "One" } },
+                { "sha": "b", "commit": { "message": "Two\n\nWith a body." } },
+                { "sha": "c", "commit": { "message": "Three" } },
+                { "sha": "d", "commit": { "message": "Four" } },
+                { "sha": "e", "commit": { "message": "Five" } },
+                { "sha": "f", "commit": { "message": "Six" } },
+                { "sha": "g", "commit": { "message": "Seven" } }
+            ]
+        }"#;
+
+        assert_eq!(
+            to_changelog(serde_json::from_str(res).unwrap()),
+            Changelog {
+                total_commits: 7,
+                subjects: vec![
+                    "Seven".to_string(),
+                    "Six".to_string(),
+                    "Five".to_string(),
+                    "Four".to_string(),
+                    "Three".to_string(),
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_subject() {
+        assert_eq!(to_subject("Subject\n\nBody"), "Subject");
+        assert_eq!(to_subject("Subject"), "Subject");
+        assert_eq!(to_subject(""), "");
+    }
+}
@@ -0,0 +1,30 @@
+//! Captures what failure can look like when making requests to the GitHub API.
+
+use std::fmt;
+
+/// Every possible unexceptional fail case when making requests to the GitHub
+/// API.
+#[derive(Debug)]
+pub enum GitHubError {
+    /// GitHub was asked for however `$GITHUB_TOKEN` isn't configured.
+    Unconfigured,
+    /// General request failure, including unsuccessful status codes.
+    APIRequestFailed(reqwest::Error),
+}
+
+impl From<reqwest::Error> for GitHubError {
+    fn from(e: reqwest::Error) -> Self {
+        GitHubError::APIRequestFailed(e)
+    }
+}
+
+impl fmt::Display for GitHubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = match self {
+            GitHubError::Unconfigured => String::from("GitHub is not configured"),
+            GitHubError::APIRequestFailed(e) => format!("GitHub API request failed: {:?}", e),
+        };
+
+        write!(f, "{}", x)
+    }
+}
@@ -0,0 +1,111 @@
+//! Open issues about events, or comment on the one already open about the same
+//! thing.
+
+use super::{GitHubClient, GitHubError, GitHubRepo, GitHubToken};
+use serde::Deserialize;
+use serde_json::json;
+
+/// <https://docs.github.com/en/rest/issues/issues#list-repository-issues>
+#[derive(Deserialize)]
+struct Issue {
+    number: u64,
+}
+
+impl GitHubClient {
+    /// Comment on the most recent open issue with a label, otherwise open one
+    /// with it. Returns the issue's number.
+    pub async fn file_issue(
+        &self,
+        repo: &GitHubRepo,
+        label: &str,
+        title: &str,
+        body: &str,
+        token: &GitHubToken,
+    ) -> Result<u64, GitHubError> {
+        let open: Vec<Issue> = self
+            .get(format!("/repos/{}/issues", repo), token)
+            .query(&[("labels", label), ("state", "open"), ("per_page", "1")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match open.first() {
+            Some(x) => {
+                self.post(
+                    format!("/repos/{}/issues/{}/comments", repo, x.number),
+                    token,
+                )
+                .json(&json!({ "body": body }))
+                .send()
+                .await?
+                .error_for_status()?;
+
+                Ok(x.number)
+            }
+            None => {
+                let x: Issue = self
+                    .post(format!("/repos/{}/issues", repo), token)
+                    .json(&json!({ "title": title, "body": body, "labels": [label] }))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                Ok(x.number)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn test_file_issue() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let list = srv
+            .mock("GET", "/repos/unsplash/mercury/issues")
+            .match_query(Matcher::UrlEncoded(
+                "labels".into(),
+                "mercury-crash-api".into(),
+            ))
+            .with_body("[]")
+            .expect(1)
+            .create_async()
+            .await;
+        let create = srv
+            .mock("POST", "/repos/unsplash/mercury/issues")
+            .match_body(Matcher::Json(json!({
+                "title": "api: Dyno web.1 crashed",
+                "body": "Dyno web.1 crashed",
+                "labels": ["mercury-crash-api"],
+            })))
+            .with_status(201)
+            .with_body(r#"{"number": 42}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = GitHubClient::new(srv.url());
+        let res = x
+            .file_issue(
+                &GitHubRepo("unsplash/mercury".into()),
+                "mercury-crash-api",
+                "api: Dyno web.1 crashed",
+                "Dyno web.1 crashed",
+                &GitHubToken("token".into()),
+            )
+            .await;
+
+        assert!(matches!(res, Ok(42)));
+
+        list.assert_async().await;
+        create.assert_async().await;
+    }
+}
@@ -0,0 +1,106 @@
+//! Mark commits with the outcome of what happened to them, for example that
+//! they were deployed or rolled back.
+
+use super::{GitHubClient, GitHubError, GitHubRepo, GitHubToken};
+use serde::Serialize;
+
+/// GitHub truncates nothing, instead rejecting descriptions beyond this many
+/// characters.
+const MAX_DESCRIPTION_CHARS: usize = 140;
+
+/// <https://docs.github.com/en/rest/commits/statuses#create-a-commit-status>
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitState {
+    Success,
+    Failure,
+}
+
+/// A status to set on a commit.
+#[derive(Serialize)]
+pub struct CommitStatus {
+    pub state: CommitState,
+    /// Statuses are distinguished by context, newer replacing older.
+    pub context: String,
+    pub description: String,
+    pub target_url: Option<String>,
+}
+
+impl GitHubClient {
+    /// Set a status on a commit, replacing any with the same context.
+    pub async fn set_status(
+        &self,
+        repo: &GitHubRepo,
+        sha: &str,
+        mut status: CommitStatus,
+        token: &GitHubToken,
+    ) -> Result<(), GitHubError> {
+        status.description = truncate(&status.description);
+
+        self.post(format!("/repos/{}/statuses/{}", repo, sha), token)
+            .json(&status)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+fn truncate(x: &str) -> String {
+    match x.chars().count() > MAX_DESCRIPTION_CHARS {
+        false => x.to_owned(),
+        true => {
+            x.chars()
+                .take(MAX_DESCRIPTION_CHARS - 1)
+                .collect::<String>()
+                + "…"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/repos/unsplash/mercury/statuses/69eec518")
+            .match_header("Authorization", "Bearer token")
+            .match_body(Matcher::Json(json!({
+                "state": "failure",
+                "context": "heroku/api",
+                "description": "x".repeat(139) + "…",
+                "target_url": null,
+            })))
+            .with_status(201)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = GitHubClient::new(srv.url());
+        let status = CommitStatus {
+            state: CommitState::Failure,
+            context: String::from("heroku/api"),
+            description: "x".repeat(200),
+            target_url: None,
+        };
+
+        assert!(x
+            .set_status(
+                &GitHubRepo("unsplash/mercury".into()),
+                "69eec518",
+                status,
+                &GitHubToken("token".into())
+            )
+            .await
+            .is_ok());
+
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,150 @@
+//! Annotate [Grafana](https://grafana.com) dashboards with deploys and
+//! rollbacks, so that they show up as vertical markers alongside whatever
+//! metrics they might explain.
+//!
+//! Enabled by configuring Grafana's URL at `$GRAFANA_URL`, for example
+//! `https://grafana.example.com`, and a service account token with permission
+//! to write annotations at `$GRAFANA_API_KEY`. A self-hosted Grafana's TLS can
+//! be configured as per [crate::tls].
+//!
+//! Annotations are organization-wide rather than tied to a dashboard, and
+//! tagged `mercury`, the event's kind, and the app's name, so that dashboards
+//! can pick out the relevant ones with an annotation query filtering by tags.
+//! Annotating is best effort, in addition to wherever the event's headed, and
+//! suppressed in read-only mode.
+
+use crate::{
+    event::{Event, EventKind},
+    tls::ClientTls,
+};
+use chrono::Utc;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The tag every annotation has.
+const TAG: &str = "mercury";
+
+/// How long to wait for Grafana to accept each annotation.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// <https://grafana.com/docs/grafana/latest/developers/http_api/annotations/#create-annotation>
+#[derive(Serialize)]
+struct CreateAnnotationRequest<'a> {
+    /// Epoch milliseconds.
+    time: i64,
+    tags: Vec<&'a str>,
+    text: String,
+}
+
+/// Annotates dashboards, safe to share across requests.
+pub struct Grafana {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+impl Grafana {
+    pub fn new(base_url: String, api_key: String, tls: ClientTls) -> Self {
+        Grafana {
+            client: tls
+                .apply(reqwest::Client::builder().timeout(TIMEOUT))
+                .build()
+                .unwrap(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            api_key,
+        }
+    }
+
+    /// Best effort annotate a deploy or rollback, ignoring other events.
+    pub async fn annotate(&self, evt: &Event) {
+        let Some(req) = to_request(evt) else {
+            return;
+        };
+
+        let res = self
+            .client
+            .post(format!("{}/api/annotations", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&req)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status());
+
+        match res {
+            Ok(_) => info!("Annotated {:?} event in Grafana", evt.kind),
+            Err(e) => warn!("Failed to annotate event in Grafana: {}", e),
+        }
+    }
+}
+
+fn to_request(evt: &Event) -> Option<CreateAnnotationRequest<'_>> {
+    if !matches!(evt.kind, EventKind::Deploy | EventKind::Rollback) {
+        return None;
+    }
+
+    let mut tags = vec![TAG, evt.kind.as_str()];
+    if let Some(x) = &evt.app {
+        tags.push(x);
+    }
+
+    let mut text = format!("{}: {}", evt.title, evt.summary.replace('\n', "<br>"));
+    if let Some(x) = evt.links.first() {
+        text += &format!(" <a href=\"{}\">View</a>", x);
+    }
+
+    Some(CreateAnnotationRequest {
+        time: evt.occurred_at.unwrap_or_else(Utc::now).timestamp_millis(),
+        tags,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Source;
+    use chrono::TimeZone;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    fn event(kind: EventKind) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind,
+            app: Some(String::from("api")),
+            version: None,
+            severity: None,
+            occurred_at: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            title: String::from("api"),
+            summary: String::from("Deployed abc123"),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_annotate() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/api/annotations")
+            .match_header("Authorization", "Bearer key")
+            .match_body(Matcher::Json(json!({
+                "time": 1704110400000_i64,
+                "tags": ["mercury", "deploy", "api"],
+                "text": "api: Deployed abc123",
+            })))
+            .with_body(r#"{"id": 1, "message": "Annotation added"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Grafana::new(srv.url() + "/", "key".into(), ClientTls::default());
+
+        x.annotate(&event(EventKind::Deploy)).await;
+        x.annotate(&event(EventKind::Crash)).await;
+
+        mock.assert_async().await;
+    }
+}
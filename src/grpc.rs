@@ -0,0 +1,306 @@
+//! An optional gRPC service equivalent to the HTTP API, for internal services
+//! which prefer protobuf contracts and connection reuse over form posts. See
+//! `proto/mercury.proto`.
+//!
+//! The service listens on `$GRPC_PORT` if set, on the same address as the HTTP
+//! API. Requests are authenticated with
+//! a `Bearer` token in the `authorization` metadata, as per the HTTP API.
+//! Unlike the HTTP API, Heroku events needn't be signed by Heroku.
+
+// Tonic's `Status` is large, however it's the error type RPCs must return.
+#![allow(clippy::result_large_err)]
+
+use crate::{
+    delivery::{deliver, Delivery, Source},
+    github::{GitHubError, GitHubRepo},
+    heartbeat::beat,
+    heroku::{
+        otto::OttoError,
+        payload::HookPayload,
+        platform::slack::SlackPlatform,
+        webhook::{forward, ForwardFailure, ForwardResult, HookOptions, Job},
+        Platform,
+    },
+    ingestion::Held,
+    matrix::MatrixError,
+    router::Deps,
+    slack::{
+        channel::ChannelName,
+        router::{handle_slack_err, is_accepted_bearer},
+        Message, Severity, SlackError,
+    },
+    statuspage::StatuspageError,
+    zulip::ZulipError,
+};
+use axum::http::StatusCode;
+use chrono::DateTime;
+use serde::{de::IntoDeserializer, Deserialize};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{transport::Server, Code, Request, Response, Status};
+use tracing::{info, warn};
+use url::Url;
+
+pub mod proto {
+    tonic::include_proto!("mercury.v1");
+}
+
+use proto::{
+    mercury_server::{Mercury, MercuryServer},
+    ForwardEventRequest, ForwardEventResponse, SendMessageRequest, SendMessageResponse,
+};
+
+/// The service itself, sharing dependencies with the HTTP API.
+pub struct GrpcService {
+    deps: Deps,
+}
+
+/// Serve the gRPC service indefinitely.
+pub async fn serve(listener: TcpListener, deps: Deps) {
+    if let Ok(addr) = listener.local_addr() {
+        info!("gRPC listening on {}", addr);
+    }
+
+    let res = Server::builder()
+        .add_service(MercuryServer::new(GrpcService { deps }))
+        .serve_with_incoming(TcpListenerStream::new(listener))
+        .await;
+
+    if let Err(e) = res {
+        warn!("gRPC server failed: {}", e);
+    }
+}
+
+impl GrpcService {
+    fn authenticate<T>(&self, req: &Request<T>) -> Result<(), Status> {
+        let headers = req.metadata().clone().into_headers();
+
+        match headers.get("authorization") {
+            Some(x) if is_accepted_bearer(&self.deps, x) => Ok(()),
+            _ => Err(Status::unauthenticated("Invalid bearer token")),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Mercury for GrpcService {
+    async fn send_message(
+        &self,
+        req: Request<SendMessageRequest>,
+    ) -> Result<Response<SendMessageResponse>, Status> {
+        self.authenticate(&req)?;
+
+        let msg = to_message(req.into_inner())?;
+        beat(&self.deps, Source::Api, None);
+
+        let msg = match self.deps.ingestion.hold(Source::Api, msg, Held::Message) {
+            Ok(()) => {
+                return Ok(Response::new(SendMessageResponse {
+                    suppressed: Some(String::from("paused")),
+                }))
+            }
+            Err(msg) => msg,
+        };
+
+        match deliver(&self.deps, &msg, Source::Api).await {
+            Ok(x) => Ok(Response::new(SendMessageResponse {
+                suppressed: to_suppressed(x),
+            })),
+            Err(e) => Err(to_status(&e)),
+        }
+    }
+
+    async fn forward_event(
+        &self,
+        req: Request<ForwardEventRequest>,
+    ) -> Result<Response<ForwardEventResponse>, Status> {
+        self.authenticate(&req)?;
+
+        let req = req.into_inner();
+        let payload = serde_json::from_str::<HookPayload>(&req.payload).map_err(|e| {
+            Status::invalid_argument(format!("Failed to deserialize payload: {}", e))
+        })?;
+        let platform = Platform::Slack(SlackPlatform {
+            channel: req.channel.map(ChannelName),
+            route: None,
+        });
+        let opts = HookOptions {
+            repo: req.repo.map(GitHubRepo),
+        };
+
+        let job = Job {
+            platform,
+            opts,
+            payload,
+        };
+        let job = match self
+            .deps
+            .ingestion
+            .hold(Source::Heroku, job, Held::HerokuHook)
+        {
+            Ok(()) => {
+                return Ok(Response::new(ForwardEventResponse {
+                    suppressed: Some(String::from("paused")),
+                }))
+            }
+            Err(job) => job,
+        };
+
+        let suppressed = match forward(&self.deps, &job.platform, &job.opts, &job.payload).await {
+            ForwardResult::Failure(ForwardFailure::ToSlack(e)) => return Err(to_status(&e)),
+            ForwardResult::Failure(ForwardFailure::ToMatrix(e)) => {
+                return Err(match e {
+                    MatrixError::Unconfigured => Status::failed_precondition(e.to_string()),
+                    _ => Status::unavailable(e.to_string()),
+                })
+            }
+            ForwardResult::Failure(ForwardFailure::ToZulip(e)) => {
+                return Err(match e {
+                    ZulipError::Unconfigured => Status::failed_precondition(e.to_string()),
+                    _ => Status::unavailable(e.to_string()),
+                })
+            }
+            ForwardResult::Failure(ForwardFailure::ToGitHub(e)) => {
+                return Err(match e {
+                    GitHubError::Unconfigured => Status::failed_precondition(e.to_string()),
+                    _ => Status::unavailable(e.to_string()),
+                })
+            }
+            ForwardResult::Failure(ForwardFailure::ToStatuspage(e)) => {
+                return Err(match e {
+                    StatuspageError::Unconfigured => Status::failed_precondition(e.to_string()),
+                    _ => Status::unavailable(e.to_string()),
+                })
+            }
+            ForwardResult::Failure(ForwardFailure::ToOtto(e)) => {
+                return Err(match e {
+                    OttoError::Unconfigured => Status::failed_precondition(e.to_string()),
+                    _ => Status::unavailable(e.to_string()),
+                })
+            }
+            ForwardResult::Unroutable(app) => {
+                return Err(Status::invalid_argument(format!(
+                    "No channel supplied or routed for app: {}",
+                    app
+                )))
+            }
+            ForwardResult::UnknownRoute(name) => {
+                return Err(Status::invalid_argument(format!(
+                    "No route named: {}",
+                    name
+                )))
+            }
+            ForwardResult::Suppressed(reason) => Some(reason.to_owned()),
+            ForwardResult::UnsupportedEvent(evt) => {
+                info!(
+                    "Could not decode payload to a supported event, found: {}",
+                    evt
+                );
+
+                None
+            }
+            ForwardResult::Success | ForwardResult::IgnoredAction => None,
+        };
+
+        Ok(Response::new(ForwardEventResponse { suppressed }))
+    }
+}
+
+/// Map a request to a [Message], validating it as the HTTP API would.
+fn to_message(x: SendMessageRequest) -> Result<Message, Status> {
+    let parse_url = |x: String| {
+        Url::parse(&x).map_err(|e| Status::invalid_argument(format!("Invalid URL {}: {}", x, e)))
+    };
+    let parse_severity = |x: String| {
+        Severity::deserialize(x.as_str().into_deserializer())
+            .map_err(|e: serde::de::value::Error| Status::invalid_argument(e.to_string()))
+    };
+    let parse_timestamp = |x: i64| {
+        DateTime::from_timestamp(x, 0)
+            .ok_or_else(|| Status::invalid_argument(format!("Invalid timestamp: {}", x)))
+    };
+
+    Ok(Message {
+        channel: ChannelName(x.channel),
+        title: x.title,
+        desc: x.desc,
+        link: x.link.map(parse_url).transpose()?,
+        cc: x.cc.map(Into::into),
+        avatar: x.avatar.map(parse_url).transpose()?,
+        severity: x.severity.map(parse_severity).transpose()?,
+        timestamp: x.timestamp.map(parse_timestamp).transpose()?,
+        fields: x.fields.into_iter().map(|f| (f.key, f.value)).collect(),
+        title_as_header: x.title_as_header,
+        thread_ts: x.thread_ts,
+    })
+}
+
+fn to_suppressed(x: Delivery) -> Option<String> {
+    match x {
+        Delivery::Sent => None,
+        Delivery::Suppressed(reason) => Some(reason.to_owned()),
+    }
+}
+
+/// Map a Slack error to a status as the HTTP API would.
+fn to_status(e: &SlackError) -> Status {
+    let (code, msg) = handle_slack_err(e);
+
+    let code = match code {
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::BAD_REQUEST => Code::InvalidArgument,
+        StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+        StatusCode::CONFLICT | StatusCode::FORBIDDEN => Code::FailedPrecondition,
+        _ => Code::Internal,
+    };
+
+    match e.remediation() {
+        Some(hint) => Status::new(code, format!("{} {}", msg, hint)),
+        None => Status::new(code, msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req() -> SendMessageRequest {
+        SendMessageRequest {
+            channel: "playground".into(),
+            title: "a title".into(),
+            desc: "a description".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_to_message() {
+        let x = to_message(SendMessageRequest {
+            link: Some("https://unsplash.com".into()),
+            severity: Some("critical".into()),
+            timestamp: Some(1691056830),
+            ..req()
+        })
+        .unwrap();
+
+        assert_eq!(x.channel.0, "playground");
+        assert_eq!(x.link.unwrap().as_str(), "https://unsplash.com/");
+        assert_eq!(x.severity, Some(Severity::Critical));
+        assert_eq!(x.timestamp.unwrap().timestamp(), 1691056830);
+    }
+
+    #[test]
+    fn test_to_message_invalid() {
+        let link = SendMessageRequest {
+            link: Some("not a url".into()),
+            ..req()
+        };
+        assert!(matches!(to_message(link), Err(e) if e.code() == Code::InvalidArgument));
+
+        let severity = SendMessageRequest {
+            severity: Some("apocalyptic".into()),
+            ..req()
+        };
+        assert!(matches!(to_message(severity), Err(e) if e.code() == Code::InvalidArgument));
+    }
+}
@@ -0,0 +1,24 @@
+//! Report on Mercury's health beyond it merely being up.
+
+use crate::{router::Deps, slack::history::CallReport};
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+
+/// The response of the deep health check.
+#[derive(Serialize)]
+pub struct DeepHealth {
+    read_only: bool,
+    slack: CallReport,
+}
+
+/// Handler for the GET route `/api/v1/health/deep`.
+///
+/// Responds with [DeepHealth] in `application/json` format. Unlike the shallow
+/// health check this always succeeds, leaving interpretation to the caller.
+pub async fn deep_health_handler(State(deps): State<Deps>) -> Json<DeepHealth> {
+    Json(DeepHealth {
+        read_only: deps.read_only.load(Ordering::Relaxed),
+        slack: deps.slack_history.report(),
+    })
+}
@@ -0,0 +1,260 @@
+//! Notice when webhooks we expect regularly stop arriving, for example those
+//! of a daily scheduler dyno, which is otherwise indistinguishable from there
+//! being nothing to report. Catches webhooks being quietly deleted, or their
+//! secrets rotated on only one side.
+//!
+//! Expectations are configured via `$HEARTBEATS` as a comma-separated list of
+//! `source:mins` or `source/app:mins` entries, for example
+//! `heroku/daily-reports:1500,api:60`. The source is one of `api` or `heroku`,
+//! and an app narrows the latter to requests about that Heroku app. Any
+//! authenticated request counts, whether or not it's ultimately delivered.
+//!
+//! Once an expectation has gone unmet for its number of minutes, a warning is
+//! posted to `$OPS_CHANNEL`, if configured, and logged. Another follows when
+//! requests resume. Startup counts as a request, so restarts don't alert
+//! prematurely.
+
+use crate::{delivery::notify_ops_channel, delivery::Source, router::Deps};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// How often to check for expectations which have gone unmet.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Requests which are expected at least so often.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Expectation {
+    source: Source,
+    /// Only applicable to [Source::Heroku].
+    app: Option<String>,
+    within: Duration,
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.app {
+            None => write!(f, "{}", self.source.as_str()),
+            Some(app) => write!(f, "{}/{}", self.source.as_str(), app),
+        }
+    }
+}
+
+/// Parse expectations from their environment variable representation.
+///
+/// ```
+/// let xs = parse_heartbeats("heroku/daily-reports:1500, api:60").unwrap();
+/// assert_eq!(xs.len(), 2);
+/// ```
+pub fn parse_heartbeats(x: &str) -> Result<Vec<Expectation>, String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|entry| {
+            let invalid = || format!("invalid entry: {}", entry);
+
+            let (key, mins) = entry.rsplit_once(':').ok_or_else(invalid)?;
+            let mins: u64 = mins.parse().map_err(|_| invalid())?;
+            let (source, app) = match key.split_once('/') {
+                Some((source, app)) => (source, Some(app.to_owned())),
+                None => (key, None),
+            };
+            let source = match (source, &app) {
+                ("api", None) => Source::Api,
+                ("heroku", _) => Source::Heroku,
+                _ => return Err(invalid()),
+            };
+
+            Ok(Expectation {
+                source,
+                app,
+                within: Duration::from_secs(60 * mins),
+            })
+        })
+        .collect()
+}
+
+/// A change in whether an expectation is being met.
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    Silent,
+    Resumed,
+}
+
+/// How an expectation has fared.
+struct Pulse {
+    last_seen: Instant,
+    /// Whether we've alerted that it's gone unmet.
+    silent: bool,
+}
+
+/// Tracks when each expectation was last met, safe to share across requests.
+pub struct Heartbeats {
+    expectations: Vec<Expectation>,
+    pulses: Mutex<Vec<Pulse>>,
+}
+
+impl Heartbeats {
+    pub fn new(expectations: Vec<Expectation>) -> Self {
+        let pulses = expectations
+            .iter()
+            .map(|_| Pulse {
+                last_seen: Instant::now(),
+                silent: false,
+            })
+            .collect();
+
+        Heartbeats {
+            expectations,
+            pulses: Mutex::new(pulses),
+        }
+    }
+
+    /// Record a request from a source, about an app if applicable.
+    pub fn record(&self, source: Source, app: Option<&str>) {
+        let mut pulses = self.pulses.lock().unwrap();
+
+        for (x, p) in self.expectations.iter().zip(pulses.iter_mut()) {
+            if x.source == source && x.app.as_deref().map_or(true, |a| Some(a) == app) {
+                p.last_seen = Instant::now();
+            }
+        }
+    }
+
+    /// Take the expectations which have since gone unmet or been met again.
+    fn take_changes(&self) -> Vec<(&Expectation, Change)> {
+        let mut pulses = self.pulses.lock().unwrap();
+
+        self.expectations
+            .iter()
+            .zip(pulses.iter_mut())
+            .filter_map(|(x, p)| {
+                let overdue = p.last_seen.elapsed() >= x.within;
+                let change = match (overdue, p.silent) {
+                    (true, false) => Change::Silent,
+                    (false, true) => Change::Resumed,
+                    _ => return None,
+                };
+                p.silent = overdue;
+
+                Some((x, change))
+            })
+            .collect()
+    }
+}
+
+/// Record a request against [Deps::heartbeats], if enabled.
+pub fn beat(deps: &Deps, source: Source, app: Option<&str>) {
+    if let Some(x) = &deps.heartbeats {
+        x.record(source, app);
+    }
+}
+
+/// Describe a change for operators.
+fn describe(x: &Expectation, change: &Change) -> String {
+    match change {
+        Change::Silent => format!(
+            "No requests received from {} in over {} minutes. Has its webhook been deleted, or its secret changed?",
+            x,
+            x.within.as_secs() / 60
+        ),
+        Change::Resumed => format!("Requests from {} have resumed", x),
+    }
+}
+
+/// Indefinitely check for expectations which have gone unmet or been met again.
+pub async fn watch_heartbeats(deps: Deps, heartbeats: Arc<Heartbeats>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        for (x, change) in heartbeats.take_changes() {
+            let desc = describe(x, &change);
+
+            match change {
+                Change::Silent => warn!(desc),
+                Change::Resumed => info!(desc),
+            }
+
+            if let Some(channel) = &deps.ops_channel {
+                notify_ops_channel(&deps, channel, desc).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_instant::MockClock;
+
+    #[test]
+    fn test_parse_heartbeats() {
+        assert_eq!(
+            parse_heartbeats(" heroku/daily-reports:1500,, api:60 ,heroku:5"),
+            Ok(vec![
+                Expectation {
+                    source: Source::Heroku,
+                    app: Some("daily-reports".into()),
+                    within: Duration::from_secs(90_000),
+                },
+                Expectation {
+                    source: Source::Api,
+                    app: None,
+                    within: Duration::from_secs(3600),
+                },
+                Expectation {
+                    source: Source::Heroku,
+                    app: None,
+                    within: Duration::from_secs(300),
+                },
+            ])
+        );
+
+        assert!(parse_heartbeats("heroku").is_err());
+        assert!(parse_heartbeats("heroku:x").is_err());
+        assert!(parse_heartbeats("api/app:5").is_err());
+        assert!(parse_heartbeats("selftest:5").is_err());
+    }
+
+    #[test]
+    fn test_take_changes() {
+        let x = Heartbeats::new(parse_heartbeats("heroku/reports:10,heroku:20").unwrap());
+        let changes = |x: &Heartbeats| {
+            x.take_changes()
+                .into_iter()
+                .map(|(x, c)| (x.to_string(), c))
+                .collect::<Vec<_>>()
+        };
+
+        MockClock::advance(Duration::from_secs(5 * 60));
+        assert!(changes(&x).is_empty());
+
+        // Other apps only satisfy the wider expectation.
+        x.record(Source::Heroku, Some("web"));
+        x.record(Source::Api, None);
+        MockClock::advance(Duration::from_secs(5 * 60));
+        assert_eq!(
+            changes(&x),
+            vec![("heroku/reports".to_owned(), Change::Silent)]
+        );
+        // Each change is only taken once.
+        assert!(changes(&x).is_empty());
+
+        x.record(Source::Heroku, Some("reports"));
+        assert_eq!(
+            changes(&x),
+            vec![("heroku/reports".to_owned(), Change::Resumed)]
+        );
+        assert!(changes(&x).is_empty());
+    }
+}
@@ -1,11 +1,25 @@
-//! Receive webhooks for dyno crashes, rollbacks, and environment variable
-//! changes from Heroku.
+//! Receive webhooks for deploys, dyno crashes, rollbacks, and environment
+//! variable changes from Heroku.
 
 pub mod auth;
+pub mod calendar;
 mod dashboard;
-mod platform;
+pub mod description;
+pub mod emoji;
+pub mod explain;
+pub mod jira;
+pub mod linear;
+pub mod otto;
+pub mod payload;
+pub mod platform;
+pub mod poll;
+pub mod push;
 pub mod router;
-mod webhook;
+pub mod routing;
+pub mod runbook;
+pub mod webhook;
 
 pub use auth::HerokuSecret;
 pub use platform::Platform;
+pub use routing::AppRoutes;
+pub use webhook::ReleaseCommitMap;
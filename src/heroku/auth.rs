@@ -10,13 +10,28 @@
 //! header. We compare our own signature against it to know if the request
 //! really came from Heroku.
 //!
+//! Multiple secrets may be valid at once, allowing a secret to be rotated at
+//! runtime without rejecting requests from webhooks not yet updated.
+//!
+//! Handlers verify requests by extracting a
+//! [SignedBody](crate::signed::SignedBody) with [SchemeHeroku].
+//!
 //! <https://devcenter.heroku.com/articles/app-webhooks#using-the-shared-secret>
 
-use axum::http::header::HeaderMap;
+use super::payload::HookPayload;
+use crate::{
+    auth::constant_time_eq,
+    delivery::Source,
+    router::Deps,
+    signed::{Scheme, SignedPayload},
+    validation::ValidationError,
+};
+use axum::http::{header::HeaderMap, StatusCode};
 use base64::{engine::general_purpose::STANDARD as b64, Engine};
 use hmac::{Hmac, Mac};
 use hyper::body::Bytes;
 use sha2::Sha256;
+use tracing::warn;
 
 /// A newtype wrapper around the Heroku secret.
 #[derive(Clone)]
@@ -28,14 +43,15 @@ pub enum SecretError {
     Invalid,
 }
 
-/// Test a request's headers for a valid secret-based signature.
+/// Test a request's headers for a valid secret-based signature from any of the
+/// currently valid secrets.
 ///
 /// The payload body should be supplied entirely unmodified from the request.
 ///
 /// Requests which fail this predicate, or which don't have a signature at all,
 /// should be considered unauthenticated.
-pub async fn validate_request_signature(
-    secret: &HerokuSecret,
+pub fn validate_request_signature(
+    secrets: &[HerokuSecret],
     body: &Bytes,
     headers: &HeaderMap,
 ) -> Result<(), SecretError> {
@@ -43,7 +59,11 @@ pub async fn validate_request_signature(
         None => Err(SecretError::Missing),
         Some(h) => match h.to_str() {
             Err(_) => Err(SecretError::Invalid),
-            Ok(v) => match is_valid_signature(secret, body, &v.to_owned()) {
+            // Every secret is tested so as not to leak which matched.
+            Ok(v) => match secrets
+                .iter()
+                .fold(false, |acc, s| acc | is_valid_signature(s, body, v))
+            {
                 false => Err(SecretError::Invalid),
                 true => Ok(()),
             },
@@ -51,14 +71,43 @@ pub async fn validate_request_signature(
     }
 }
 
+/// The [Scheme] by which Heroku signs webhook requests.
+pub struct SchemeHeroku;
+
+impl Scheme for SchemeHeroku {
+    const SOURCE: Source = Source::Heroku;
+
+    fn is_configured(deps: &Deps) -> bool {
+        !deps.heroku_secrets.load().is_empty()
+    }
+
+    fn verify(deps: &Deps, headers: &HeaderMap, body: &Bytes) -> Result<(), StatusCode> {
+        validate_request_signature(&deps.heroku_secrets.load(), body, headers).map_err(|e| {
+            let msg = match e {
+                SecretError::Missing => "Missing Heroku secret",
+                SecretError::Invalid => "Invalid Heroku secret",
+            };
+            warn!(msg);
+
+            StatusCode::UNAUTHORIZED
+        })
+    }
+}
+
+impl SignedPayload for HookPayload {
+    fn from_json(bytes: &[u8]) -> Result<(Self, Vec<String>), ValidationError> {
+        HookPayload::from_json_with_unknown(bytes)
+    }
+}
+
 /// Compare a valid signature for a payload against that offered alongside it
-/// in a request.
-fn is_valid_signature(secret: &HerokuSecret, payload: &Bytes, sig: &String) -> bool {
-    gen_signature(secret, payload).as_ref() == Some(sig)
+/// in a request, in constant time.
+fn is_valid_signature(secret: &HerokuSecret, payload: &Bytes, sig: &str) -> bool {
+    gen_signature(secret, payload).is_some_and(|x| constant_time_eq(&x, sig))
 }
 
 /// Generate a valid signature with our secret for a payload.
-fn gen_signature(secret: &HerokuSecret, payload: &Bytes) -> Option<String> {
+pub fn gen_signature(secret: &HerokuSecret, payload: &Bytes) -> Option<String> {
     type HmacSha256 = Hmac<Sha256>;
 
     HmacSha256::new_from_slice(secret.0.as_bytes())
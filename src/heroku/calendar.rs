@@ -0,0 +1,285 @@
+//! Log production releases and rollbacks as events in a shared
+//! [Google Calendar](https://calendar.google.com), for those who'd rather
+//! glance at a calendar than scroll through Slack.
+//!
+//! Enabled by configuring the calendar's ID at `$GOOGLE_CALENDAR_ID`, for
+//! example `c_abc123@group.calendar.google.com`. Only apps matching one of the
+//! comma-separated patterns at `$GOOGLE_CALENDAR_APPS` are logged, `*-production`
+//! by default, with patterns as per [super::routing].
+//!
+//! Requests are authorized as a user who can edit the calendar via an OAuth
+//! client's ID and secret at `$GOOGLE_CLIENT_ID` and `$GOOGLE_CLIENT_SECRET`,
+//! and a refresh token with the `https://www.googleapis.com/auth/calendar.events`
+//! scope at `$GOOGLE_REFRESH_TOKEN`. Access tokens are refreshed as needed.
+//!
+//! Logging is best effort, in addition to whatever's posted to Slack, and
+//! suppressed in read-only mode.
+
+use super::routing::matches_pattern;
+use crate::event::{Event, EventKind};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use url::Url;
+
+/// The base URL of the Google Calendar API.
+pub const API_BASE: &str = "https://www.googleapis.com/calendar/v3";
+
+/// Where access tokens are refreshed.
+pub const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Which apps are logged unless configured otherwise.
+pub const DEFAULT_APPS: &str = "*-production";
+
+/// Releases are instantaneous, however calendar events need a duration to be
+/// visible.
+const DURATION: ChronoDuration = ChronoDuration::minutes(15);
+
+/// Refresh access tokens this long before they expire, so they don't expire
+/// mid-request.
+const EXPIRY_MARGIN: ChronoDuration = ChronoDuration::minutes(1);
+
+/// How long to wait for Google to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An OAuth client and a user's refresh token for it.
+pub struct GoogleCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+}
+
+/// <https://developers.google.com/identity/protocols/oauth2/web-server#offline>
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: i64,
+}
+
+struct AccessToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Logs releases to a calendar, safe to share across requests.
+pub struct Calendar {
+    client: reqwest::Client,
+    api_base: String,
+    token_url: String,
+    calendar_id: String,
+    apps: Vec<String>,
+    credentials: GoogleCredentials,
+    token: Mutex<Option<AccessToken>>,
+}
+
+impl Calendar {
+    /// Instantiate against the given URLs, enabling easy mocking. For
+    /// real-world usage see [API_BASE] and [TOKEN_URL].
+    pub fn new(
+        api_base: String,
+        token_url: String,
+        calendar_id: String,
+        apps: &str,
+        credentials: GoogleCredentials,
+    ) -> Self {
+        Calendar {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            api_base,
+            token_url,
+            calendar_id,
+            apps: parse_apps(apps),
+            credentials,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Best effort log a release or rollback of a matching app, ignoring
+    /// other events.
+    pub async fn log(&self, evt: &Event) {
+        if !matches!(evt.kind, EventKind::Deploy | EventKind::Rollback) {
+            return;
+        }
+
+        let Some(app_name) = &evt.app else {
+            return;
+        };
+
+        if !self.apps.iter().any(|x| matches_pattern(x, app_name)) {
+            return;
+        }
+
+        match self.log_(evt).await {
+            Ok(()) => info!("Logged {:?} of {} to calendar", evt.kind, app_name),
+            Err(e) => warn!("Failed to log {} release to calendar: {}", app_name, e),
+        }
+    }
+
+    async fn log_(&self, evt: &Event) -> Result<(), reqwest::Error> {
+        let token = self.access_token().await?;
+
+        let mut url: Url = self
+            .api_base
+            .parse()
+            .expect("Could not parse Google Calendar API base URL");
+        url.path_segments_mut()
+            .expect("Google Calendar API base URL cannot be a base")
+            .extend(["calendars", &self.calendar_id, "events"]);
+
+        let start = evt.occurred_at.unwrap_or_else(Utc::now);
+
+        self.client
+            .post(url)
+            .bearer_auth(token)
+            .json(&json!({
+                "summary": to_summary(evt),
+                "description": to_description(evt),
+                "start": { "dateTime": start.to_rfc3339() },
+                "end": { "dateTime": (start + DURATION).to_rfc3339() },
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Get an access token, refreshing it if it's absent or about to expire.
+    async fn access_token(&self) -> Result<String, reqwest::Error> {
+        let mut token = self.token.lock().await;
+
+        if let Some(x) = &*token {
+            if x.expires_at - EXPIRY_MARGIN > Utc::now() {
+                return Ok(x.token.clone());
+            }
+        }
+
+        let res: TokenResponse = self
+            .client
+            .post(&self.token_url)
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.credentials.client_id),
+                ("client_secret", &self.credentials.client_secret),
+                ("refresh_token", &self.credentials.refresh_token),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        *token = Some(AccessToken {
+            token: res.access_token.clone(),
+            expires_at: Utc::now() + ChronoDuration::seconds(res.expires_in),
+        });
+
+        Ok(res.access_token)
+    }
+}
+
+/// Parse comma-separated app patterns.
+fn parse_apps(x: &str) -> Vec<String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// For example `api-production v42: Deploy 69eec518`.
+fn to_summary(evt: &Event) -> String {
+    let subject = match &evt.version {
+        Some(v) => format!("{} {}", evt.title, v),
+        None => evt.title.clone(),
+    };
+
+    format!(
+        "{}: {}",
+        subject,
+        evt.summary.lines().next().unwrap_or_default()
+    )
+}
+
+fn to_description(evt: &Event) -> String {
+    match evt.links.first() {
+        Some(x) => format!("{}\n\n{}", evt.summary, x),
+        None => evt.summary.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Source;
+    use chrono::TimeZone;
+    use mockito::Matcher;
+
+    fn release(kind: EventKind, app: &str) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind,
+            app: Some(app.to_owned()),
+            version: Some(String::from("v42")),
+            severity: None,
+            occurred_at: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            title: app.to_owned(),
+            summary: String::from("Deploy 69eec518\nIncludes 1 commit"),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let token = srv
+            .mock("POST", "/token")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("grant_type".into(), "refresh_token".into()),
+                Matcher::UrlEncoded("refresh_token".into(), "refresh".into()),
+            ]))
+            .with_body(r#"{"access_token": "access", "expires_in": 3599, "token_type": "Bearer"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let create = srv
+            .mock(
+                "POST",
+                "/calendar/v3/calendars/deploys@group.calendar.google.com/events",
+            )
+            .match_header("Authorization", "Bearer access")
+            .match_body(Matcher::Json(json!({
+                "summary": "api-production v42: Deploy 69eec518",
+                "description": "Deploy 69eec518\nIncludes 1 commit",
+                "start": { "dateTime": "2024-01-01T12:00:00+00:00" },
+                "end": { "dateTime": "2024-01-01T12:15:00+00:00" },
+            })))
+            .expect(2)
+            .create_async()
+            .await;
+
+        let x = Calendar::new(
+            srv.url() + "/calendar/v3",
+            srv.url() + "/token",
+            String::from("deploys@group.calendar.google.com"),
+            DEFAULT_APPS,
+            GoogleCredentials {
+                client_id: String::from("id"),
+                client_secret: String::from("secret"),
+                refresh_token: String::from("refresh"),
+            },
+        );
+
+        x.log(&release(EventKind::Deploy, "api-production")).await;
+        x.log(&release(EventKind::Deploy, "api-production")).await;
+        x.log(&release(EventKind::Deploy, "api-staging")).await;
+        x.log(&release(EventKind::Crash, "api-production")).await;
+
+        token.assert_async().await;
+        create.assert_async().await;
+    }
+}
@@ -0,0 +1,262 @@
+//! Recognise release events by their descriptions, which is all Heroku gives
+//! us to go on.
+//!
+//! There's no indication that these descriptions are stable on Heroku's side,
+//! so further rules can be configured via `$HEROKU_DESCRIPTION_PATTERNS`
+//! without requiring a release. Entries are newline-separated, as patterns may
+//! themselves contain commas, and take the form `kind:pattern`, for example
+//! `deploy:^Deployed (?P<commit>[0-9a-f]+)$`. Each kind requires a named
+//! capture group:
+//!
+//! - `deploy`: `commit`
+//! - `rollback`: `version`
+//! - `config`: `change`
+//!
+//! A rule may additionally template the event's summary, following the
+//! pattern and ` => `, for example
+//! `rollback:^Reverted to (?P<version>v\d+) by (?P<who>.+)$ => {who} rolled back to {version}`.
+//! Templates may refer to any named capture group and to `{author}`. Without
+//! one, the summary is as it would be for the built-in rules.
+//!
+//! Configured rules are tried before the built-in ones, in order, the first
+//! match winning.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// The kinds of release event which can be recognised by description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DescriptionKind {
+    Deploy,
+    Rollback,
+    EnvVarsChange,
+}
+
+impl DescriptionKind {
+    /// The capture group a pattern for this kind must define.
+    fn group(&self) -> &'static str {
+        match self {
+            DescriptionKind::Deploy => "commit",
+            DescriptionKind::Rollback => "version",
+            DescriptionKind::EnvVarsChange => "change",
+        }
+    }
+}
+
+/// A rule recognising a kind of release event.
+#[derive(Debug)]
+pub struct DescriptionPattern {
+    pub kind: DescriptionKind,
+    re: Regex,
+    /// Overrides the event's summary if present.
+    template: Option<String>,
+}
+
+/// Placeholders within a summary template, for example `{version}`.
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(\w+)\}").unwrap());
+
+impl DescriptionPattern {
+    fn new(kind: DescriptionKind, re: &str, template: Option<&str>) -> Result<Self, String> {
+        let re = Regex::new(re).map_err(|e| format!("invalid pattern {}: {}", re, e))?;
+        let has_group = |x: &str| re.capture_names().flatten().any(|y| y == x);
+
+        if !has_group(kind.group()) {
+            return Err(format!(
+                "pattern {} lacks a capture group named {}",
+                re,
+                kind.group()
+            ));
+        }
+
+        if let Some(x) = template {
+            let unknown = PLACEHOLDER
+                .captures_iter(x)
+                .map(|cs| cs.get(1).unwrap().as_str())
+                .find(|x| *x != "author" && !has_group(x));
+
+            if let Some(name) = unknown {
+                return Err(format!("template {} refers to unknown {}", x, name));
+            }
+        }
+
+        Ok(DescriptionPattern {
+            kind,
+            re,
+            template: template.map(str::to_owned),
+        })
+    }
+}
+
+/// A description recognised by a [DescriptionPattern].
+#[derive(Debug, PartialEq, Eq)]
+pub struct DescriptionMatch {
+    pub kind: DescriptionKind,
+    /// The contents of the kind's capture group.
+    pub value: String,
+    /// The templated summary, if the rule has a template.
+    pub summary: Option<String>,
+}
+
+static BUILT_IN: Lazy<Vec<DescriptionPattern>> = Lazy::new(|| {
+    [
+        (DescriptionKind::Deploy, r"^Deploy (?P<commit>[0-9a-f]+)$"),
+        (DescriptionKind::Rollback, r"^Rollback to (?P<version>.+)$"),
+        (
+            DescriptionKind::EnvVarsChange,
+            r"^(?P<change>.+) config vars$",
+        ),
+    ]
+    .into_iter()
+    .map(|(kind, re)| DescriptionPattern::new(kind, re, None).unwrap())
+    .collect()
+});
+
+/// Configured patterns, which take precedence over the built-in ones.
+#[derive(Debug, Default)]
+pub struct DescriptionPatterns(Vec<DescriptionPattern>);
+
+impl DescriptionPatterns {
+    /// Parse patterns from their environment variable representation.
+    ///
+    /// ```
+    /// let xs = DescriptionPatterns::parse("deploy:^Deployed (?P<commit>[0-9a-f]+)$");
+    /// ```
+    pub fn parse(x: &str) -> Result<Self, String> {
+        x.lines()
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|entry| {
+                let (kind, rest) = entry
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid entry: {}", entry))?;
+                let (re, template) = match rest.split_once(" => ") {
+                    Some((re, x)) => (re, Some(x.trim())),
+                    None => (rest, None),
+                };
+                let kind = match kind.trim() {
+                    "deploy" => DescriptionKind::Deploy,
+                    "rollback" => DescriptionKind::Rollback,
+                    "config" => DescriptionKind::EnvVarsChange,
+                    x => return Err(format!("unknown kind: {}", x)),
+                };
+
+                DescriptionPattern::new(kind, re.trim(), template)
+            })
+            .collect::<Result<_, _>>()
+            .map(DescriptionPatterns)
+    }
+
+    /// Find the first rule matching a description, rendering its template, if
+    /// any, with the release's author.
+    pub fn find(&self, desc: &str, author: &str) -> Option<DescriptionMatch> {
+        self.0.iter().chain(BUILT_IN.iter()).find_map(|x| {
+            let cs = x.re.captures(desc)?;
+            let value = cs.name(x.kind.group())?.as_str().to_owned();
+
+            let summary = x.template.as_ref().map(|t| {
+                PLACEHOLDER
+                    .replace_all(t, |ps: &regex::Captures| match &ps[1] {
+                        "author" => author.to_owned(),
+                        name => cs.name(name).map_or("", |m| m.as_str()).to_owned(),
+                    })
+                    .into_owned()
+            });
+
+            Some(DescriptionMatch {
+                kind: x.kind,
+                value,
+                summary,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(xs: &DescriptionPatterns, desc: &str) -> Option<(DescriptionKind, String)> {
+        xs.find(desc, "hodor@unsplash.com")
+            .map(|x| (x.kind, x.value))
+    }
+
+    #[test]
+    fn test_parse() {
+        let xs = DescriptionPatterns::parse(
+            "deploy:^Deployed (?P<commit>[0-9a-f]{7,40})$\n\n  rollback: ^Reverted to (?P<version>v\\d+)$ => Back to {version}\n",
+        )
+        .unwrap();
+        assert_eq!(xs.0.len(), 2);
+        assert_eq!(xs.0[1].kind, DescriptionKind::Rollback);
+        assert_eq!(xs.0[1].template.as_deref(), Some("Back to {version}"));
+        assert_eq!(
+            find(&xs, "Reverted to v12"),
+            Some((DescriptionKind::Rollback, "v12".into()))
+        );
+
+        assert!(DescriptionPatterns::parse("").unwrap().0.is_empty());
+        assert!(DescriptionPatterns::parse("deploy").is_err());
+        assert!(DescriptionPatterns::parse("nope:^(?P<commit>.+)$").is_err());
+        assert!(DescriptionPatterns::parse("deploy:^(?P<commit>.+$").is_err());
+        assert!(DescriptionPatterns::parse("deploy:^(?P<version>.+)$").is_err());
+        assert!(DescriptionPatterns::parse("deploy:^(?P<commit>.+)$ => {nope}").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let xs = DescriptionPatterns::parse(
+            "deploy:^Deployed (?P<commit>[0-9a-f]+)$\nconfig:^Deploy (?P<change>oops)$",
+        )
+        .unwrap();
+
+        assert_eq!(
+            find(&xs, "Deployed 69eec518"),
+            Some((DescriptionKind::Deploy, "69eec518".into()))
+        );
+        assert_eq!(
+            find(&xs, "Deploy 69eec518"),
+            Some((DescriptionKind::Deploy, "69eec518".into()))
+        );
+        // Configured rules take precedence.
+        assert_eq!(
+            find(&xs, "Deploy oops"),
+            Some((DescriptionKind::EnvVarsChange, "oops".into()))
+        );
+        assert_eq!(
+            find(&DescriptionPatterns::default(), "Rollback to v1"),
+            Some((DescriptionKind::Rollback, "v1".into()))
+        );
+        assert_eq!(
+            find(&DescriptionPatterns::default(), "Something else"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_template() {
+        let xs = DescriptionPatterns::parse(
+            "rollback:^Reverted to (?P<version>v\\d+)(?: because (?P<why>.+))?$ => {version} by {author}: {why}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            xs.find("Reverted to v12 because oops", "hodor@unsplash.com"),
+            Some(DescriptionMatch {
+                kind: DescriptionKind::Rollback,
+                value: "v12".into(),
+                summary: Some("v12 by hodor@unsplash.com: oops".into()),
+            })
+        );
+        // Groups which didn't participate are empty.
+        assert_eq!(
+            xs.find("Reverted to v12", "hodor@unsplash.com")
+                .and_then(|x| x.summary),
+            Some("v12 by hodor@unsplash.com: ".into())
+        );
+        assert_eq!(
+            xs.find("Rollback to v1", "hodor@unsplash.com")
+                .and_then(|x| x.summary),
+            None
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Configure the emoji prefixing Heroku events' titles per kind of event and
+//! per app, for example to use a workspace's custom emoji.
+//!
+//! Rules are configured via `$HEROKU_EMOJI` as a comma-separated list of
+//! `kind=emoji` or `kind/pattern=emoji` entries, for example
+//! `crash/api-*=:api-on-fire:,crash=:fire:,deploy=`. The kind is one of
+//! `deploy`, `rollback`, `config`, or `crash`, and patterns may include `*`
+//! wildcards as per [super::routing]. Rules are consulted in order, the first
+//! match winning, falling back to the built-in emoji. An empty emoji omits the
+//! prefix.
+//!
+//! Shortcodes such as `:fire:` are only rendered as emoji where Slack renders
+//! them, which excludes the username, so custom emoji are best paired with
+//! [title_as_header](crate::slack::Message::title_as_header).
+
+use super::routing::{matches_pattern, parse_event_kind};
+use crate::event::EventKind;
+
+/// An emoji for events of a kind, optionally only for apps matching a
+/// pattern.
+pub struct EmojiRule {
+    kind: EventKind,
+    pattern: Option<String>,
+    emoji: String,
+}
+
+/// Rules in order of precedence.
+#[derive(Default)]
+pub struct EmojiRules(Vec<EmojiRule>);
+
+impl EmojiRules {
+    /// Parse rules from their environment variable representation.
+    ///
+    /// ```
+    /// let xs = EmojiRules::parse("crash/api-*=:api-on-fire:, crash=:fire:").unwrap();
+    /// ```
+    pub fn parse(x: &str) -> Result<Self, String> {
+        x.split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|entry| {
+                let (key, emoji) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("invalid entry: {}", entry))?;
+                let (kind, pattern) = match key.split_once('/') {
+                    Some((kind, pattern)) => (kind, Some(pattern.trim().to_owned())),
+                    None => (key, None),
+                };
+                Ok(EmojiRule {
+                    kind: parse_event_kind(kind)?,
+                    pattern,
+                    emoji: emoji.trim().to_owned(),
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(EmojiRules)
+    }
+
+    /// Find the configured emoji for an event about an app, if any.
+    pub fn find(&self, kind: EventKind, app_name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|x| {
+                x.kind == kind
+                    && x.pattern
+                        .as_ref()
+                        .map_or(true, |p| matches_pattern(p, app_name))
+            })
+            .map(|x| x.emoji.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xs = EmojiRules::parse(" crash/api-*=:api-on-fire:,, crash = :fire: ,deploy=").unwrap();
+        assert_eq!(xs.0.len(), 3);
+
+        assert!(EmojiRules::parse("").unwrap().0.is_empty());
+        assert!(EmojiRules::parse("crash").is_err());
+        assert!(EmojiRules::parse("nope=:fire:").is_err());
+    }
+
+    #[test]
+    fn test_find() {
+        let xs = EmojiRules::parse("crash/api-*=:api-on-fire:,crash=:fire:,deploy=").unwrap();
+
+        assert_eq!(
+            xs.find(EventKind::Crash, "api-staging"),
+            Some(":api-on-fire:")
+        );
+        assert_eq!(xs.find(EventKind::Crash, "web"), Some(":fire:"));
+        assert_eq!(xs.find(EventKind::Deploy, "web"), Some(""));
+        assert_eq!(xs.find(EventKind::Rollback, "web"), None);
+        assert_eq!(EmojiRules::default().find(EventKind::Crash, "web"), None);
+    }
+}
@@ -0,0 +1,215 @@
+//! Explain how a Heroku webhook would be handled, as per [crate::explain],
+//! decoding and routing it as [super::webhook::forward] does.
+//!
+//! The signature is checked if supplied, however the trace continues beyond an
+//! invalid one, so that payloads can be explained without signing them.
+
+use super::{
+    auth::{validate_request_signature, SecretError},
+    payload::*,
+    routing::{route, Channel, Destination, Unrouted, Via},
+    webhook::*,
+    Platform,
+};
+use crate::{
+    delivery::Source,
+    explain::{explain_admission, explain_delivery, Trace},
+    router::Deps,
+    slack::{Message, Severity},
+};
+use axum::http::HeaderMap;
+use hyper::body::Bytes;
+use std::sync::atomic::Ordering;
+
+/// Explain how a webhook would be handled, without delivering it.
+pub async fn explain(deps: &Deps, plat: &Platform, headers: &HeaderMap, body: &Bytes) -> Trace {
+    let mut trace = Trace::default();
+
+    let secrets = deps.heroku_secrets.load();
+    match validate_request_signature(&secrets, body, headers) {
+        _ if secrets.is_empty() => trace.fail("signature", "No Heroku secrets are configured"),
+        Ok(()) => trace.pass("signature", "Valid"),
+        Err(SecretError::Missing) => trace.fail("signature", "Not supplied"),
+        Err(SecretError::Invalid) => trace.fail("signature", "Invalid"),
+    }
+
+    let (payload, unknown) = match HookPayload::from_json_with_unknown(body) {
+        Ok(x) => x,
+        Err(e) => {
+            let detail = match e.field {
+                Some(field) => format!("{}: {}", e.error, field),
+                None => e.error,
+            };
+            trace.fail("payload", detail);
+
+            return trace;
+        }
+    };
+
+    let app_name = &get_app_data(&payload).name;
+    let resource = match payload {
+        HookPayload::Release(_) => "release",
+        HookPayload::Dyno(_) => "dyno",
+    };
+    let decoded = format!("Decoded a {} webhook about {}", resource, app_name);
+
+    match unknown.is_empty() {
+        true => trace.pass("payload", decoded),
+        false if deps.strict_sources.contains(&Source::Heroku) => {
+            trace.fail(
+                "payload",
+                format!(
+                    "Unrecognised fields, rejected in strict mode: {}",
+                    unknown.join(", ")
+                ),
+            );
+
+            return trace;
+        }
+        false => trace.pass(
+            "payload",
+            format!(
+                "{}, ignoring unrecognised fields: {}",
+                decoded,
+                unknown.join(", ")
+            ),
+        ),
+    }
+
+    if !explain_admission(deps, &mut trace, Source::Heroku, get_priority(&payload)) {
+        return trace;
+    }
+
+    let (event, summary) = match decode_event(&deps.heroku_description_patterns, &payload) {
+        Ok(x) => x,
+        Err(e) => {
+            let detail = match e {
+                Undecoded::IgnoredAction(action) => {
+                    format!("Ignored the {:?} action, only updates are sent", action)
+                }
+                Undecoded::NoCrash => "Ignored as the dyno didn't crash".to_owned(),
+                Undecoded::UnsupportedEvent(desc) => {
+                    format!("No description pattern matched: {}", desc)
+                }
+            };
+            trace.fail("event", detail);
+
+            return trace;
+        }
+    };
+    trace.pass("event", format!("Decoded as {:?}", event));
+
+    let dest = match route(deps, plat, app_name) {
+        Ok(x) => x,
+        Err(Unrouted::UnknownRoute(name)) => {
+            trace.fail("route", format!("No route named {}", name));
+
+            return trace;
+        }
+        Err(Unrouted::NoRoute) => {
+            trace.fail("route", "No channel supplied or routed");
+
+            return trace;
+        }
+    };
+
+    match dest {
+        Destination::Slack(x) => {
+            let msg = explain_channel(deps, &mut trace, &event, summary, &payload, x);
+            explain_delivery(deps, &mut trace, msg).await;
+
+            return trace;
+        }
+        Destination::Stdout(x) => {
+            let msg = explain_channel(deps, &mut trace, &event, summary, &payload, x);
+            trace.pass("console", "Written to the console rather than delivered");
+            trace.would_deliver = trace.steps.iter().all(|x| x.ok);
+            trace.message = Some(msg);
+
+            return trace;
+        }
+        Destination::Matrix(x) => {
+            trace.pass("route", format!("Room {} supplied", x.room));
+            match deps.matrix {
+                Some(_) => trace.pass("matrix", "Configured"),
+                None => trace.fail("matrix", "$MATRIX_HOMESERVER is not configured"),
+            }
+        }
+        Destination::Zulip(x) => {
+            trace.pass("route", format!("Stream {} supplied", x.stream));
+            match deps.zulip {
+                Some(_) => trace.pass("zulip", "Configured"),
+                None => trace.fail("zulip", "$ZULIP_SITE is not configured"),
+            }
+        }
+        Destination::GitHub(x) => {
+            trace.pass("route", format!("Repository {} supplied", x.repo));
+            match deps.github_token {
+                Some(_) => trace.pass("github", "Configured"),
+                None => trace.fail("github", "$GITHUB_TOKEN is not configured"),
+            }
+            if let HookEvent::EnvVarsChange { .. } = event {
+                trace.fail("github", "Config changes aren't reported to GitHub");
+            }
+        }
+        Destination::Statuspage(x) => {
+            match &x.component {
+                Some(c) => trace.pass("route", format!("Component {} supplied", c)),
+                None => trace.pass("route", "No component supplied"),
+            }
+            match deps.statuspage {
+                Some(_) => trace.pass("statuspage", "Configured"),
+                None => trace.fail("statuspage", "$STATUSPAGE_PAGE_ID is not configured"),
+            }
+            match event.severity() {
+                Severity::Critical | Severity::Success => {}
+                _ => trace.fail(
+                    "statuspage",
+                    "Only critical and successful events update Statuspage",
+                ),
+            }
+        }
+        Destination::Otto => {
+            trace.pass("route", "Relayed to Otto");
+            match deps.heroku_otto {
+                Some(_) => trace.pass("otto", "Configured"),
+                None => trace.fail("otto", "$OTTO_URL is not configured"),
+            }
+            if let HookEvent::EnvVarsChange { .. } = event {
+                trace.fail("otto", "Config changes were never sent to Otto");
+            }
+        }
+    }
+
+    // Sent directly rather than via crate::delivery, so checked here instead.
+    if deps.read_only.load(Ordering::Relaxed) {
+        trace.fail("read-only", "Read-only mode is enabled");
+    }
+    trace.would_deliver = trace.steps.iter().all(|x| x.ok);
+
+    trace
+}
+
+/// Explain how a channel was found for an event, and render the message that
+/// would be sent there.
+fn explain_channel(
+    deps: &Deps,
+    trace: &mut Trace,
+    event: &HookEvent,
+    summary: Option<String>,
+    payload: &HookPayload,
+    channel: Channel,
+) -> Message {
+    let detail = match channel.via {
+        Via::Supplied => format!("Channel {} supplied", channel.name),
+        Via::Name(name) => format!("Routed by name {} to {}", name, channel.name),
+        Via::Pattern(pattern) => format!("Routed by pattern {} to {}", pattern, channel.name),
+    };
+    trace.pass("route", detail);
+
+    let app_name = &get_app_data(payload).name;
+    let locale = deps.channel_locales.get(&channel.name.0);
+    let evt = to_event(event, summary, None, payload, locale);
+
+    to_message(deps, &evt, channel, app_name)
+}
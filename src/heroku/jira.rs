@@ -0,0 +1,346 @@
+//! Open [Jira](https://www.atlassian.com/software/jira) issues for selected
+//! Heroku events, for example a ticket for every production rollback, or
+//! comment on the issue already open for the app and kind of event.
+//!
+//! Routes are configured via `$JIRA_ROUTES` as a comma-separated list of
+//! `kind=PROJECT:issue type` or `kind/pattern=PROJECT:issue type` entries, for
+//! example `rollback/api-production=OPS:Incident`. Kinds and patterns are as
+//! per [super::emoji], and every matching route is acted upon.
+//!
+//! Issues are labelled with `mercury-<kind>-<app>`, by which an unresolved
+//! issue is found and commented on rather than another opened.
+//!
+//! Requires the site's URL at `$JIRA_BASE`, for example
+//! `https://example.atlassian.net`, and an account's email and API token at
+//! `$JIRA_EMAIL` and `$JIRA_API_TOKEN`. Best effort, in addition to whatever's
+//! posted to Slack, and suppressed in read-only mode.
+
+use super::routing::{matches_pattern, parse_event_kind};
+use crate::event::{Event, EventKind};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// How long to wait for Jira to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Open issues of a type in a project for events of a kind, optionally only
+/// for apps matching a pattern.
+pub struct JiraRoute {
+    kind: EventKind,
+    pattern: Option<String>,
+    project: String,
+    issue_type: String,
+}
+
+/// Parse routes from their environment variable representation.
+///
+/// ```
+/// let xs = parse_jira_routes("rollback/api-*=OPS:Incident").unwrap();
+/// ```
+pub fn parse_jira_routes(x: &str) -> Result<Vec<JiraRoute>, String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|entry| {
+            let invalid = || format!("invalid entry: {}", entry);
+
+            let (key, target) = entry.split_once('=').ok_or_else(invalid)?;
+            let (kind, pattern) = match key.split_once('/') {
+                Some((kind, pattern)) => (kind, Some(pattern.trim().to_owned())),
+                None => (key, None),
+            };
+            let (project, issue_type) = match target.trim().split_once(':') {
+                Some((p, t)) if !p.is_empty() && !t.trim().is_empty() => (p, t.trim()),
+                _ => return Err(invalid()),
+            };
+
+            Ok(JiraRoute {
+                kind: parse_event_kind(kind)?,
+                pattern,
+                project: project.to_owned(),
+                issue_type: issue_type.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// <https://developer.atlassian.com/cloud/jira/platform/rest/v2/api-group-issue-search/#api-rest-api-2-search-get>
+#[derive(Deserialize)]
+struct SearchResponse {
+    issues: Vec<Issue>,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    key: String,
+}
+
+/// <https://developer.atlassian.com/cloud/jira/platform/rest/v2/api-group-issues/#api-rest-api-2-issue-post>
+#[derive(Serialize)]
+struct CreateIssueRequest<'a> {
+    fields: IssueFields<'a>,
+}
+
+#[derive(Serialize)]
+struct IssueFields<'a> {
+    project: serde_json::Value,
+    issuetype: serde_json::Value,
+    summary: String,
+    description: String,
+    labels: [&'a str; 1],
+}
+
+/// Opens and comments on issues for the events routed to it.
+pub struct Jira {
+    client: reqwest::Client,
+    routes: Vec<JiraRoute>,
+    base_url: String,
+    email: String,
+    api_token: String,
+}
+
+impl Jira {
+    pub fn new(routes: Vec<JiraRoute>, base_url: String, email: String, api_token: String) -> Self {
+        Jira {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            routes,
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            email,
+            api_token,
+        }
+    }
+
+    /// The routes matching an event of a kind about an app.
+    fn routes<'a>(
+        &'a self,
+        kind: EventKind,
+        app_name: &'a str,
+    ) -> impl Iterator<Item = &'a JiraRoute> {
+        self.routes.iter().filter(move |x| {
+            x.kind == kind
+                && x.pattern
+                    .as_ref()
+                    .map_or(true, |p| matches_pattern(p, app_name))
+        })
+    }
+
+    /// Best effort open or comment on an issue for an event per route.
+    pub async fn file(&self, evt: &Event) {
+        let Some(app_name) = &evt.app else {
+            return;
+        };
+
+        for route in self.routes(evt.kind, app_name) {
+            match self.file_(route, evt, app_name).await {
+                Ok(x) => info!("Filed {:?} event about {} as {}", evt.kind, app_name, x),
+                Err(e) => warn!("Failed to file event about {} in Jira: {}", app_name, e),
+            }
+        }
+    }
+
+    /// Returns the key of the issue opened or commented on.
+    async fn file_(
+        &self,
+        route: &JiraRoute,
+        evt: &Event,
+        app_name: &str,
+    ) -> Result<String, reqwest::Error> {
+        let label = to_label(evt.kind, app_name);
+        let desc = to_description(evt);
+
+        match self.find_open(&route.project, &label).await? {
+            Some(key) => {
+                self.client
+                    .post(format!(
+                        "{}/rest/api/2/issue/{}/comment",
+                        self.base_url, key
+                    ))
+                    .basic_auth(&self.email, Some(&self.api_token))
+                    .json(&json!({ "body": desc }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+
+                Ok(key)
+            }
+            None => {
+                let x: Issue = self
+                    .client
+                    .post(format!("{}/rest/api/2/issue", self.base_url))
+                    .basic_auth(&self.email, Some(&self.api_token))
+                    .json(&CreateIssueRequest {
+                        fields: IssueFields {
+                            project: json!({ "key": route.project }),
+                            issuetype: json!({ "name": route.issue_type }),
+                            summary: format!("{}: {}", evt.title, first_line(&evt.summary)),
+                            description: desc,
+                            labels: [&label],
+                        },
+                    })
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                Ok(x.key)
+            }
+        }
+    }
+
+    /// Find the most recent unresolved issue in a project with a label.
+    async fn find_open(
+        &self,
+        project: &str,
+        label: &str,
+    ) -> Result<Option<String>, reqwest::Error> {
+        let jql = format!(
+            "project = \"{}\" AND labels = \"{}\" AND statusCategory != Done ORDER BY created DESC",
+            project, label
+        );
+
+        let x: SearchResponse = self
+            .client
+            .get(format!("{}/rest/api/2/search", self.base_url))
+            .basic_auth(&self.email, Some(&self.api_token))
+            .query(&[
+                ("jql", jql.as_str()),
+                ("maxResults", "1"),
+                ("fields", "key"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(x.issues.into_iter().next().map(|x| x.key))
+    }
+}
+
+/// Labels can't contain spaces, which app names can't either.
+fn to_label(kind: EventKind, app_name: &str) -> String {
+    format!("mercury-{}-{}", kind.as_str(), app_name)
+}
+
+fn first_line(x: &str) -> &str {
+    x.lines().next().unwrap_or_default()
+}
+
+/// Render an event as Jira wiki markup.
+fn to_description(evt: &Event) -> String {
+    let mut xs = vec![evt.summary.clone()];
+
+    for (k, v) in &evt.fields {
+        xs.push(format!("*{}*: {}", k, v));
+    }
+
+    if let Some(x) = evt.links.first() {
+        xs.push(format!("[View|{}]", x));
+    }
+
+    xs.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, slack::Severity};
+    use mockito::Matcher;
+
+    fn rollback() -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Rollback,
+            app: Some(String::from("api")),
+            version: None,
+            severity: Some(Severity::Warning),
+            occurred_at: None,
+            title: String::from("api"),
+            summary: String::from("Rolled back to v41"),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_jira_routes() {
+        let xs =
+            parse_jira_routes(" rollback/api-*=OPS:Incident ,, crash=ENG:Service Request").unwrap();
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].project, "OPS");
+        assert_eq!(xs[1].issue_type, "Service Request");
+
+        assert!(parse_jira_routes("").unwrap().is_empty());
+        assert!(parse_jira_routes("rollback").is_err());
+        assert!(parse_jira_routes("rollback=OPS").is_err());
+        assert!(parse_jira_routes("rollback=OPS:").is_err());
+        assert!(parse_jira_routes("nope=OPS:Incident").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let search = srv
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded(
+                "jql".into(),
+                "project = \"OPS\" AND labels = \"mercury-rollback-api\" AND statusCategory != Done ORDER BY created DESC".into(),
+            ))
+            .with_body(r#"{"issues": []}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let search_eng = srv
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded(
+                "jql".into(),
+                "project = \"ENG\" AND labels = \"mercury-rollback-api\" AND statusCategory != Done ORDER BY created DESC".into(),
+            ))
+            .with_body(r#"{"issues": [{"key": "ENG-7"}]}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let create = srv
+            .mock("POST", "/rest/api/2/issue")
+            .match_header("Authorization", Matcher::Regex("^Basic ".into()))
+            .match_body(Matcher::PartialJson(json!({
+                "fields": {
+                    "project": { "key": "OPS" },
+                    "issuetype": { "name": "Incident" },
+                    "summary": "api: Rolled back to v41",
+                    "labels": ["mercury-rollback-api"],
+                },
+            })))
+            .with_status(201)
+            .with_body(r#"{"id": "1", "key": "OPS-1"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let comment = srv
+            .mock("POST", "/rest/api/2/issue/ENG-7/comment")
+            .match_body(Matcher::Json(json!({ "body": "Rolled back to v41" })))
+            .with_status(201)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Jira::new(
+            parse_jira_routes("rollback/api=OPS:Incident,rollback=ENG:Bug,crash=OPS:Incident")
+                .unwrap(),
+            srv.url(),
+            String::from("bot@example.com"),
+            String::from("token"),
+        );
+
+        x.file(&rollback()).await;
+
+        search.assert_async().await;
+        search_eng.assert_async().await;
+        create.assert_async().await;
+        comment.assert_async().await;
+    }
+}
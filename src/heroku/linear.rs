@@ -0,0 +1,349 @@
+//! Open a [Linear](https://linear.app) triage issue when an app's dynos crash
+//! repeatedly in quick succession, aggregating the crashes and linking back to
+//! their Slack thread, so that crash storms are tracked rather than scrolled
+//! past.
+//!
+//! Enabled by configuring an API key at `$LINEAR_API_KEY` and the ID of the team
+//! to file issues with at `$LINEAR_TEAM_ID`. A storm is
+//! `$LINEAR_CRASH_THRESHOLD` crashes (5 by default) of an app within
+//! `$LINEAR_CRASH_WINDOW_MINS` of one another (10 by default). One issue is
+//! opened per storm, which ends once the app's gone a window without crashing.
+//!
+//! The Slack thread is only known if [crate::threading] is enabled.
+
+use crate::{event::Event, router::Deps, slack::channel::ChannelName};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use tracing::{info, warn};
+use url::Url;
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// The URL of Linear's GraphQL API.
+pub const API_URL: &str = "https://api.linear.app/graphql";
+
+/// The default number of crashes within a window which constitute a storm.
+pub const DEFAULT_THRESHOLD: usize = 5;
+
+/// The default window within which crashes constitute a storm.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(60 * 10);
+
+/// How long to wait for Linear to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// <https://developers.linear.app/docs/graphql/working-with-the-graphql-api#creating-and-editing-issues>
+const ISSUE_CREATE: &str = "mutation IssueCreate($input: IssueCreateInput!) { issueCreate(input: $input) { success issue { identifier url } } }";
+
+/// A crash within an ongoing storm.
+#[derive(Clone)]
+struct Crash {
+    at: Instant,
+    occurred_at: DateTime<Utc>,
+    summary: String,
+}
+
+/// An app's recent crashes.
+#[derive(Default)]
+struct Storm {
+    crashes: Vec<Crash>,
+    /// Whether an issue's been opened for this storm.
+    filed: bool,
+}
+
+#[derive(Deserialize)]
+struct IssueCreateResponse {
+    data: Option<IssueCreateData>,
+    #[serde(default)]
+    errors: Vec<GraphQLError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IssueCreateData {
+    issue_create: IssueCreatePayload,
+}
+
+#[derive(Deserialize)]
+struct IssueCreatePayload {
+    issue: Option<CreatedIssue>,
+}
+
+#[derive(Deserialize)]
+struct CreatedIssue {
+    identifier: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQLError {
+    message: String,
+}
+
+/// Tracks crashes per app and files issues for storms, safe to share across
+/// requests.
+pub struct Linear {
+    client: reqwest::Client,
+    api_url: String,
+    api_key: String,
+    team_id: String,
+    threshold: usize,
+    window: Duration,
+    storms: Mutex<HashMap<String, Storm>>,
+}
+
+impl Linear {
+    pub fn new(
+        api_url: String,
+        api_key: String,
+        team_id: String,
+        threshold: usize,
+        window: Duration,
+    ) -> Self {
+        Linear {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            api_url,
+            api_key,
+            team_id,
+            threshold,
+            window,
+            storms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record a crash of an app, returning the storm's crashes if it's just
+    /// become one.
+    fn record(&self, app_name: &str, evt: &Event) -> Option<Vec<Crash>> {
+        let now = Instant::now();
+        let mut storms = self.storms.lock().unwrap();
+
+        // A storm ends once an app's gone a window without crashing.
+        storms.retain(|_, x| {
+            x.crashes
+                .last()
+                .is_some_and(|c| now.duration_since(c.at) < self.window)
+        });
+
+        let storm = storms.entry(app_name.to_owned()).or_default();
+        storm.crashes.push(Crash {
+            at: now,
+            occurred_at: evt.occurred_at.unwrap_or_else(Utc::now),
+            summary: evt.summary.clone(),
+        });
+
+        if storm.filed || storm.crashes.len() < self.threshold {
+            return None;
+        }
+        storm.filed = true;
+
+        Some(storm.crashes.clone())
+    }
+
+    /// Open an issue, returning its identifier, for example `ENG-123`.
+    async fn create_issue(&self, title: String, description: String) -> Result<String, String> {
+        let res: IssueCreateResponse = self
+            .client
+            .post(&self.api_url)
+            .header(reqwest::header::AUTHORIZATION, &self.api_key)
+            .json(&json!({
+                "query": ISSUE_CREATE,
+                "variables": {
+                    "input": {
+                        "teamId": self.team_id,
+                        "title": title,
+                        "description": description,
+                    },
+                },
+            }))
+            .send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map_err(|e| e.to_string())?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if let Some(e) = res.errors.first() {
+            return Err(e.message.clone());
+        }
+
+        res.data
+            .and_then(|x| x.issue_create.issue)
+            .map(|x| x.identifier)
+            .ok_or_else(|| String::from("no issue was created"))
+    }
+}
+
+/// Record a crash of an app, best effort opening an issue if it's part of a
+/// storm. The crash must already have been posted to the channel, and so not
+/// in read-only mode.
+pub async fn triage_crash(deps: &Deps, channel: &ChannelName, app_name: &str, evt: &Event) {
+    let Some(linear) = &deps.heroku_linear else {
+        return;
+    };
+
+    let Some(crashes) = linear.record(app_name, evt) else {
+        return;
+    };
+
+    let thread = match deps
+        .threads
+        .as_ref()
+        .and_then(|x| x.parent(channel, app_name))
+    {
+        None => None,
+        Some(m) => {
+            let token = deps.slack_token.load_full();
+            let res = deps
+                .slack_client
+                .lock()
+                .await
+                .get_permalink(&m, &token)
+                .await;
+
+            res.map_err(|e| warn!("Failed to link to crash thread in {}: {}", channel, e))
+                .ok()
+        }
+    };
+
+    let title = format!("{} is crashing repeatedly", app_name);
+    let description = to_description(app_name, &crashes, linear.window, thread.as_ref());
+
+    match linear.create_issue(title, description).await {
+        Ok(x) => info!("Opened {} for {} crash storm", x, app_name),
+        Err(e) => warn!("Failed to open issue for {} crash storm: {}", app_name, e),
+    }
+}
+
+/// Render a storm as Markdown.
+fn to_description(
+    app_name: &str,
+    crashes: &[Crash],
+    window: Duration,
+    thread: Option<&Url>,
+) -> String {
+    let mut x = format!(
+        "{} crashed {} times, each within {} minutes of the last:\n\n",
+        app_name,
+        crashes.len(),
+        window.as_secs() / 60
+    );
+
+    for c in crashes {
+        x += &format!(
+            "- {}: {}\n",
+            c.occurred_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+            c.summary.replace('\n', " ")
+        );
+    }
+
+    if let Some(url) = thread {
+        x += &format!("\n[Slack thread]({})\n", url);
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, event::EventKind, slack::Severity};
+    use chrono::TimeZone;
+    use mock_instant::MockClock;
+    use mockito::Matcher;
+
+    fn crash(n: u32) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Crash,
+            app: Some(String::from("api")),
+            version: None,
+            severity: Some(Severity::Critical),
+            occurred_at: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, n, 0).unwrap()),
+            title: String::from("api"),
+            summary: format!("Dyno web.{} crashed", n),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let x = Linear::new(
+            API_URL.into(),
+            "key".into(),
+            "team".into(),
+            3,
+            Duration::from_secs(60),
+        );
+
+        assert!(x.record("api", &crash(1)).is_none());
+        assert!(x.record("web", &crash(1)).is_none());
+        assert!(x.record("api", &crash(2)).is_none());
+        assert_eq!(x.record("api", &crash(3)).map(|x| x.len()), Some(3));
+
+        // Only one issue per storm.
+        assert!(x.record("api", &crash(4)).is_none());
+
+        // Each crash prolongs the storm.
+        MockClock::advance(Duration::from_secs(59));
+        assert!(x.record("api", &crash(5)).is_none());
+
+        MockClock::advance(Duration::from_secs(60));
+        assert!(x.record("api", &crash(6)).is_none());
+        assert!(x.record("api", &crash(7)).is_none());
+        assert!(x.record("api", &crash(8)).is_some());
+    }
+
+    #[test]
+    fn test_to_description() {
+        let crashes = vec![Crash {
+            at: Instant::now(),
+            occurred_at: Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap(),
+            summary: String::from("Dyno web.1 crashed"),
+        }];
+        let url = "https://example.slack.com/archives/C123/p1"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            to_description("api", &crashes, DEFAULT_WINDOW, Some(&url)),
+            "api crashed 1 times, each within 10 minutes of the last:\n\n- 2024-01-01T12:00:00Z: Dyno web.1 crashed\n\n[Slack thread](https://example.slack.com/archives/C123/p1)\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_issue() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/graphql")
+            .match_header("Authorization", "key")
+            .match_body(Matcher::PartialJson(json!({
+                "variables": { "input": { "teamId": "team", "title": "api is crashing repeatedly" } },
+            })))
+            .with_body(r#"{"data": {"issueCreate": {"success": true, "issue": {"identifier": "ENG-123", "url": "https://linear.app/x/issue/ENG-123"}}}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Linear::new(
+            srv.url() + "/graphql",
+            "key".into(),
+            "team".into(),
+            1,
+            DEFAULT_WINDOW,
+        );
+
+        assert_eq!(
+            x.create_issue("api is crashing repeatedly".into(), "".into())
+                .await,
+            Ok(String::from("ENG-123"))
+        );
+
+        mock.assert_async().await;
+    }
+}
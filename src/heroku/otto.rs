@@ -0,0 +1,331 @@
+//! Relay Heroku events to our legacy [Otto](https://github.com/unsplash/otto)
+//! bot in the formats it's always received them in, so that Mercury and Otto
+//! can run in parallel whilst Otto's retired piecemeal.
+//!
+//! Deploys and rollbacks are relayed to Otto's `/hooks/deploy` route as per
+//! Heroku's retired HTTP deploy hooks, a form-encoded body with the app, user,
+//! and commits. Crashes are relayed to its `/hooks/monitor` route as per a
+//! Heroku `dyno` webhook, restricted to the fields Otto reads. Config changes
+//! were never sent to Otto, so aren't relayed.
+//!
+//! Enabled by configuring Otto's base URL at `$OTTO_URL`. If Otto verifies
+//! webhook signatures then its secret can be configured at `$OTTO_SECRET`,
+//! with which relayed crashes are signed as Heroku would. Otto's TLS can be
+//! configured as per [crate::tls].
+//!
+//! <https://devcenter.heroku.com/articles/deploy-hooks#http-post-hook>
+
+use super::{payload::HookPayload, webhook::HookEvent};
+use crate::{github::compare::Changelog, tls::ClientTls};
+use base64::{engine::general_purpose::STANDARD as b64, Engine};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::{fmt, time::Duration};
+
+/// How long to wait for Otto to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The length of the abbreviated commits Heroku's deploy hooks supplied.
+const SHORT_COMMIT_LEN: usize = 7;
+
+/// What went wrong communicating with Otto.
+#[derive(Debug)]
+pub enum OttoError {
+    /// Otto was asked for however `$OTTO_URL` isn't configured.
+    Unconfigured,
+    /// General request failure, including unsuccessful status codes.
+    RequestFailed(reqwest::Error),
+}
+
+impl fmt::Display for OttoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OttoError::Unconfigured => write!(f, "Otto is not configured"),
+            OttoError::RequestFailed(e) => write!(f, "Otto request failed: {:?}", e),
+        }
+    }
+}
+
+/// An event as Otto expects to receive it.
+#[derive(Debug, PartialEq)]
+pub enum Relay {
+    /// A form-encoded deploy hook.
+    Deploy(Vec<(&'static str, String)>),
+    /// A JSON `dyno` webhook.
+    Monitor(Value),
+}
+
+/// Relays events to a single Otto instance, safe to share across requests.
+pub struct Otto {
+    client: reqwest::Client,
+    base_url: String,
+    secret: Option<String>,
+}
+
+impl Otto {
+    pub fn new(base_url: String, secret: Option<String>, tls: ClientTls) -> Self {
+        Otto {
+            client: tls
+                .apply(reqwest::Client::builder().timeout(TIMEOUT))
+                .build()
+                .unwrap(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            secret,
+        }
+    }
+
+    pub async fn relay(&self, x: &Relay) -> Result<(), OttoError> {
+        let req = match x {
+            Relay::Deploy(form) => self
+                .client
+                .post(format!("{}/hooks/deploy", self.base_url))
+                .form(form),
+            Relay::Monitor(payload) => {
+                // Serializing a JSON value can't fail.
+                let body = serde_json::to_vec(payload).unwrap();
+                let req = self
+                    .client
+                    .post(format!("{}/hooks/monitor", self.base_url))
+                    .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+                match &self.secret {
+                    Some(s) => req.header("Heroku-Webhook-Hmac-SHA256", sign(s, &body)),
+                    None => req,
+                }
+                .body(body)
+            }
+        };
+
+        req.send()
+            .await
+            .and_then(|x| x.error_for_status())
+            .map(|_| ())
+            .map_err(OttoError::RequestFailed)
+    }
+}
+
+/// Sign a request body as Heroku signs its webhooks.
+fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+
+    b64.encode(mac.finalize().into_bytes())
+}
+
+/// Transform an event into what Otto would have received for it, if
+/// anything. The commit of the app's previous release and the changelog are
+/// optional, as they were for Heroku.
+pub fn to_relay(
+    event: &HookEvent,
+    payload: &HookPayload,
+    prev_commit: Option<&str>,
+    changelog: Option<&Changelog>,
+) -> Option<Relay> {
+    match (event, payload) {
+        (HookEvent::Deploy { .. } | HookEvent::Rollback { .. }, HookPayload::Release(x)) => {
+            // Deploy hooks only ever fired for releases with code.
+            let commit = &x.data.slug.as_ref()?.commit;
+            let app_name = &x.data.app.name;
+            let git_log = changelog
+                .map(|x| {
+                    x.subjects
+                        .iter()
+                        .map(|s| format!("  * {}\n", s))
+                        .collect::<String>()
+                })
+                .unwrap_or_default();
+
+            Some(Relay::Deploy(vec![
+                ("app", app_name.clone()),
+                ("user", x.data.user.email.clone()),
+                ("url", format!("https://{}.herokuapp.com", app_name)),
+                ("head", short_commit(commit).to_owned()),
+                ("head_long", commit.clone()),
+                ("prev_head", prev_commit.unwrap_or_default().to_owned()),
+                ("git_log", git_log),
+                (
+                    "release",
+                    x.data
+                        .version
+                        .map(|v| format!("v{}", v))
+                        .unwrap_or_default(),
+                ),
+            ]))
+        }
+        (
+            HookEvent::DynoCrash {
+                name,
+                status_code: Some(code),
+            },
+            HookPayload::Dyno(x),
+        ) => Some(Relay::Monitor(json!({
+            "resource": "dyno",
+            "action": "update",
+            "created_at": x.created_at,
+            "data": {
+                "app": { "name": x.data.app.name },
+                "name": name,
+                "type": x.data.typ,
+                "state": "crashed",
+                "exit_status": code,
+            },
+        }))),
+        // Otto ignores crashes without a status code, such as those found by
+        // polling.
+        _ => None,
+    }
+}
+
+fn short_commit(x: &str) -> &str {
+    x.get(..SHORT_COMMIT_LEN).unwrap_or(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heroku::payload::{
+        AppData, DynoHookData, DynoHookPayload, DynoState, ReleaseHookAction, ReleaseHookData,
+        ReleaseHookPayload, SlugData, UserData,
+    };
+    use mockito::Matcher;
+
+    fn release() -> HookPayload {
+        HookPayload::Release(ReleaseHookPayload {
+            data: ReleaseHookData {
+                app: AppData {
+                    name: String::from("api"),
+                },
+                description: String::from("Deploy 1234567890ab"),
+                user: UserData {
+                    email: String::from("sam@example.com"),
+                },
+                slug: Some(SlugData {
+                    commit: String::from("1234567890abcdef"),
+                }),
+                version: Some(42),
+            },
+            action: ReleaseHookAction::Update,
+            created_at: None,
+        })
+    }
+
+    fn crash() -> HookPayload {
+        HookPayload::Dyno(DynoHookPayload {
+            data: DynoHookData {
+                app: AppData {
+                    name: String::from("api"),
+                },
+                name: String::from("web.1"),
+                typ: String::from("web"),
+                state: DynoState::Crashed,
+                exit_status: Some(137),
+            },
+            created_at: None,
+        })
+    }
+
+    #[test]
+    fn test_to_relay_deploy() {
+        let evt = HookEvent::Deploy {
+            author: String::from("sam@example.com"),
+            commit: String::from("1234567890abcdef"),
+        };
+        let changelog = Changelog {
+            total_commits: 2,
+            subjects: vec![String::from("Fix"), String::from("Add")],
+        };
+
+        assert_eq!(
+            to_relay(&evt, &release(), Some("fedcba"), Some(&changelog)),
+            Some(Relay::Deploy(vec![
+                ("app", String::from("api")),
+                ("user", String::from("sam@example.com")),
+                ("url", String::from("https://api.herokuapp.com")),
+                ("head", String::from("1234567")),
+                ("head_long", String::from("1234567890abcdef")),
+                ("prev_head", String::from("fedcba")),
+                ("git_log", String::from("  * Fix\n  * Add\n")),
+                ("release", String::from("v42")),
+            ]))
+        );
+
+        let evt = HookEvent::EnvVarsChange {
+            author: String::from("sam@example.com"),
+            raw_change: String::from("FOO"),
+        };
+        assert_eq!(to_relay(&evt, &release(), None, None), None);
+    }
+
+    #[test]
+    fn test_to_relay_crash() {
+        let evt = |status_code| HookEvent::DynoCrash {
+            name: String::from("web.1"),
+            status_code,
+        };
+
+        assert_eq!(
+            to_relay(&evt(Some(137)), &crash(), None, None),
+            Some(Relay::Monitor(json!({
+                "resource": "dyno",
+                "action": "update",
+                "created_at": null,
+                "data": {
+                    "app": { "name": "api" },
+                    "name": "web.1",
+                    "type": "web",
+                    "state": "crashed",
+                    "exit_status": 137,
+                },
+            })))
+        );
+        assert_eq!(to_relay(&evt(None), &crash(), None, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let deploy = srv
+            .mock("POST", "/hooks/deploy")
+            .match_header("Heroku-Webhook-Hmac-SHA256", Matcher::Missing)
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("app".into(), "api".into()),
+                Matcher::UrlEncoded("head".into(), "1234567".into()),
+            ]))
+            .expect(1)
+            .create_async()
+            .await;
+        let monitor = srv
+            .mock("POST", "/hooks/monitor")
+            .match_header("Content-Type", "application/json")
+            .match_header(
+                "Heroku-Webhook-Hmac-SHA256",
+                sign("secret", br#"{"resource":"dyno"}"#).as_str(),
+            )
+            .match_body(r#"{"resource":"dyno"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Otto::new(
+            srv.url() + "/",
+            Some(String::from("secret")),
+            ClientTls::default(),
+        );
+
+        x.relay(&Relay::Deploy(vec![
+            ("app", String::from("api")),
+            ("head", String::from("1234567")),
+        ]))
+        .await
+        .unwrap();
+        x.relay(&Relay::Monitor(json!({ "resource": "dyno" })))
+            .await
+            .unwrap();
+
+        deploy.assert_async().await;
+        monitor.assert_async().await;
+    }
+}
@@ -0,0 +1,686 @@
+//! The payloads Heroku sends, deserialised as leniently as is safe. Real
+//! payloads have surprised us before, for example with `exit_status` being
+//! `null` rather than absent, so this is fuzzed. See `fuzz/` and the property
+//! tests below.
+//!
+//! This module depends on nothing else in the crate besides [crate::de] and
+//! [crate::validation], allowing it to be exposed to the fuzzer via the
+//! library.
+
+use crate::de::Tolerant;
+use crate::validation::{from_value, from_value_with_ignored, parse_json, ValidationError};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// The anticipated payload supplied by Heroku in webhook requests.
+///
+/// This isn't very well documented. An example request is provided here:
+///
+/// <https://devcenter.heroku.com/articles/app-webhooks#receiving-webhooks>
+///
+/// Real payloads from a given Heroku app's webhooks can be found here:
+///
+/// <https://dashboard.heroku.com/apps/HEROKU_APP/webhooks/>
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+#[serde(tag = "resource")]
+pub enum HookPayload {
+    #[serde(rename = "release")]
+    Release(ReleaseHookPayload),
+    #[serde(rename = "dyno")]
+    Dyno(DynoHookPayload),
+}
+
+impl HookPayload {
+    /// Deserialise as per [crate::validation], dispatching on the tag ourselves
+    /// so that the path to any offending field is retained. The paths of any
+    /// fields we don't recognise, or which have values we don't recognise or
+    /// have leniently discarded, are returned too, so that we can learn when
+    /// Heroku changes its payloads.
+    pub fn from_json_with_unknown(bytes: &[u8]) -> Result<(Self, Vec<String>), ValidationError> {
+        let value = parse_json(bytes)?;
+
+        let (x, mut unknown) = match value.get("resource").and_then(Value::as_str) {
+            Some("release") => from_value_with_ignored(&value)
+                .map(|(x, unknown)| (HookPayload::Release(x), unknown))?,
+            Some("dyno") => from_value_with_ignored(&value)
+                .map(|(x, unknown)| (HookPayload::Dyno(x), unknown))?,
+            _ => (from_value(&value)?, Vec::new()),
+        };
+        // The tag's consumed by us rather than the variant.
+        unknown.retain(|x| x != "resource");
+
+        // Values we've tolerated or leniently discarded weren't recognised
+        // either.
+        let present = |ptr| value.pointer(ptr).is_some_and(|x| !x.is_null());
+        let mut check = |path: &str, ok: bool| {
+            if !ok {
+                unknown.push(path.to_owned());
+            }
+        };
+
+        match &x {
+            HookPayload::Release(x) => {
+                check("action", !matches!(x.action, ReleaseHookAction::Other(_)));
+                check("data.slug", x.data.slug.is_some() || !present("/data/slug"));
+                check(
+                    "data.version",
+                    x.data.version.is_some() || !present("/data/version"),
+                );
+                check(
+                    "created_at",
+                    x.created_at.is_some() || !present("/created_at"),
+                );
+            }
+            HookPayload::Dyno(x) => {
+                check("data.state", !matches!(x.data.state, DynoState::Other(_)));
+                check(
+                    "created_at",
+                    x.created_at.is_some() || !present("/created_at"),
+                );
+            }
+        }
+
+        Ok((x, unknown))
+    }
+}
+
+/// The payload supplied by Heroku for the `api:release` entity type.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct ReleaseHookPayload {
+    pub(crate) data: ReleaseHookData,
+    #[serde(deserialize_with = "crate::de::tolerant")]
+    #[schemars(with = "String")]
+    pub action: ReleaseHookAction,
+    /// Only informational, so not worth rejecting the payload over.
+    #[serde(default, deserialize_with = "crate::de::lenient")]
+    #[schemars(with = "Option<DateTime<Utc>>")]
+    pub(crate) created_at: Option<DateTime<Utc>>,
+}
+
+/// The payload supplied by Heroku for the `dyno` entity type.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct DynoHookPayload {
+    pub(crate) data: DynoHookData,
+    /// Only informational, so not worth rejecting the payload over.
+    #[serde(default, deserialize_with = "crate::de::lenient")]
+    #[schemars(with = "Option<DateTime<Utc>>")]
+    pub(crate) created_at: Option<DateTime<Utc>>,
+}
+
+/// The action within an `api:release` webhook event lifecycle.
+///
+/// Multiple payloads can be sent for the same wider event, for example "create"
+/// followed by "update".
+///
+/// <https://help.heroku.com/JP3QR5I5/why-am-i-receiving-2-web-hook-events-for-a-single-release>
+#[derive(Debug, PartialEq)]
+pub enum ReleaseHookAction {
+    Create,
+    Update,
+    Destroy,
+    /// An action we don't recognise.
+    Other(String),
+}
+
+impl Tolerant for ReleaseHookAction {
+    fn known(x: &str) -> Option<Self> {
+        match x {
+            "create" => Some(ReleaseHookAction::Create),
+            "update" => Some(ReleaseHookAction::Update),
+            "destroy" => Some(ReleaseHookAction::Destroy),
+            _ => None,
+        }
+    }
+
+    fn other(x: String) -> Self {
+        ReleaseHookAction::Other(x)
+    }
+}
+
+/// The state of a dyno, as of a `dyno` webhook event.
+///
+/// <https://devcenter.heroku.com/articles/dyno-runtime#dyno-states>
+#[derive(Debug, PartialEq)]
+pub enum DynoState {
+    Starting,
+    Up,
+    Idle,
+    Down,
+    Crashed,
+    /// A state we don't recognise.
+    Other(String),
+}
+
+impl Tolerant for DynoState {
+    fn known(x: &str) -> Option<Self> {
+        match x {
+            "starting" => Some(DynoState::Starting),
+            "up" => Some(DynoState::Up),
+            "idle" => Some(DynoState::Idle),
+            "down" => Some(DynoState::Down),
+            "crashed" => Some(DynoState::Crashed),
+            _ => None,
+        }
+    }
+
+    fn other(x: String) -> Self {
+        DynoState::Other(x)
+    }
+}
+
+/// General information about an `api:release` entity type.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct ReleaseHookData {
+    pub(crate) app: AppData,
+    pub(crate) description: String,
+    pub(crate) user: UserData,
+    /// Absent for releases which don't yet have a slug, for example an app's
+    /// very first release. Treated as absent if it's malformed, for example
+    /// if its commit is `null`, which only costs us the changelog.
+    #[serde(default, deserialize_with = "crate::de::lenient")]
+    #[schemars(with = "Option<SlugData>")]
+    pub(crate) slug: Option<SlugData>,
+    /// The release's number, for example `42` for v42. Treated as absent if
+    /// it's malformed.
+    #[serde(default, deserialize_with = "crate::de::lenient")]
+    #[schemars(with = "Option<u64>")]
+    pub(crate) version: Option<u64>,
+}
+
+/// General information about an `dyno` entity type.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct DynoHookData {
+    pub(crate) app: AppData,
+    pub(crate) name: String,
+    #[serde(rename = "type")]
+    pub(crate) typ: String,
+    #[serde(deserialize_with = "crate::de::tolerant")]
+    #[schemars(with = "String")]
+    pub(crate) state: DynoState,
+    /// We need this for `DynoCrash`, however for other types of dyno events it
+    /// can be absent or `null`, and we should still serialise those and return
+    /// 200.
+    pub(crate) exit_status: Option<u8>,
+}
+
+/// Common metadata about the app for which a webhook event fired.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct AppData {
+    pub(crate) name: String,
+}
+
+/// The slug, or build, which a release is running.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct SlugData {
+    pub(crate) commit: String,
+}
+
+/// Information about the user who enacted the change.
+#[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+pub struct UserData {
+    pub(crate) email: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod deserialization {
+        use super::*;
+
+        #[test]
+        fn test_root_payload_release() {
+            let real_redacted_example = r#"{
+                "id": "66a9e685-e1f3-4f9f-9177-a024fb5f0902",
+                "data": {
+                    "id": "38821f7c-e1a1-41d9-a34b-c41e2fa6d82d",
+                    "app": {
+                        "id": "59d151db-c38e-4e9c-a854-faead7e8d6cc",
+                        "name": "my-app",
+                        "process_tier": "production"
+                    },
+                    "slug": {
+                        "id": "507af0a6-a83b-4a16-9a9f-bf55b5864848",
+                        "commit": "69eec518969cc409e116940aa5304ab6ab237a4d",
+                        "commit_description": ""
+                    },
+                    "user": {
+                        "id": "71def50e-da83-453a-bba3-46b4e26911b0",
+                        "email": "hodor@unsplash.com"
+                    },
+                    "stack": "heroku-20",
+                    "status": "succeeded",
+                    "current": true,
+                    "pstable": {
+                        "my-process": {
+                            "slug": {
+                                "id": "fefa649a-845f-4c35-ad9a-2819633b4884"
+                            },
+                            "command": "/bin/cowsay moo"
+                        }
+                    },
+                    "version": 6644,
+                    "created_at": "2023-08-03T10:00:30Z",
+                    "updated_at": "2023-08-03T10:00:30Z",
+                    "description": "Deploy 69eec518",
+                    "addon_plan_names": [],
+                    "output_stream_url": null
+                },
+                "actor": {
+                    "id": "71def50e-da83-453a-bba3-46b4e26911b0",
+                    "email": "hello@example.com"
+                },
+                "action": "update",
+                "version": "application/vnd.heroku+json; version=3",
+                "resource": "release",
+                "sequence": null,
+                "created_at": "2023-08-03T10:00:30.693808Z",
+                "updated_at": "2023-08-03T10:00:30.693817Z",
+                "published_at": "2023-08-03T10:00:31Z",
+                "previous_data": {},
+                "webhook_metadata": {
+                    "attempt": {
+                        "id": "6ddac576-005d-456c-8153-8fba3f5702de"
+                    },
+                    "delivery": {
+                        "id": "ecb8532f-3b9b-4f8a-8eee-14296e0a6784"
+                    },
+                    "event": {
+                        "id": "66a9e685-e1f3-4f9f-9177-a024fb5f0902",
+                        "include": "api:release"
+                    },
+                    "webhook": {
+                        "id": "af83d062-fdfe-4fc0-88ad-b91bf58f0656"
+                    }
+                }
+            }"#;
+
+            let expected = HookPayload::Release(ReleaseHookPayload {
+                data: ReleaseHookData {
+                    app: AppData {
+                        name: "my-app".to_string(),
+                    },
+                    description: "Deploy 69eec518".to_string(),
+                    user: UserData {
+                        email: "hodor@unsplash.com".to_string(),
+                    },
+                    slug: Some(SlugData {
+                        commit: "69eec518969cc409e116940aa5304ab6ab237a4d".to_string(),
+                    }),
+                    version: Some(6644),
+                },
+                action: ReleaseHookAction::Update,
+                created_at: Some("2023-08-03T10:00:30.693808Z".parse().unwrap()),
+            });
+
+            assert_eq!(
+                expected,
+                serde_json::from_str(real_redacted_example).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_root_payload_dyno() {
+            let real_redacted_example = r#"{
+                "id": "292a769d-53d8-4edd-ace4-017a967653e1",
+                "created_at": "2023-08-03T14:19:07Z",
+                "data": {
+                    "id": "ab9fece7-41cc-4f22-8b73-de8c1a7a52b5",
+                    "app": {
+                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
+                        "name": "my-app"
+                    },
+                    "release": {
+                        "id": "3a7a5c18-b1ac-4830-9efb-5b68551e85f0",
+                        "version": 7634
+                    },
+                    "command": "/bin/cowsay moo",
+                    "size": "Standard-1X",
+                    "exit_status": 137,
+                    "management": "run:detached",
+                    "state": "crashed",
+                    "type": "scheduler",
+                    "name": "scheduler.8375"
+                },
+                "actor": {
+                  "id": "1030c06a-bcbe-4738-9134-89af5c717fb1",
+                  "email": "noreply+webhooks@heroku.com"
+                },
+                "previous_data": {},
+                "published_at": null,
+                "resource": "dyno",
+                "action": "destroy",
+                "version": "application/vnd.heroku+json; version=3"
+            }"#;
+
+            let expected = HookPayload::Dyno(DynoHookPayload {
+                data: DynoHookData {
+                    app: AppData {
+                        name: "my-app".to_string(),
+                    },
+                    name: "scheduler.8375".to_string(),
+                    typ: "scheduler".to_string(),
+                    state: DynoState::Crashed,
+                    exit_status: Some(137),
+                },
+                created_at: Some("2023-08-03T14:19:07Z".parse().unwrap()),
+            });
+
+            assert_eq!(
+                expected,
+                serde_json::from_str(real_redacted_example).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_root_payload_dyno_no_status_code() {
+            let real_redacted_example = r#"{
+                "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
+                "data": {
+                    "id": "d0eb9c96-0529-4f9d-bb4c-cc3382a55180",
+                    "app": {
+                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
+                        "name": "my-app"
+                    },
+                    "name": "scheduler.3540",
+                    "size": "Standard-1X",
+                    "type": "scheduler",
+                    "state": "starting",
+                    "command": "/bin/cowsay moo",
+                    "release": {
+                        "id": "9e67f453-d990-4c5f-89d3-c9ca2a70830f",
+                        "version": 7636
+                    },
+                    "attach_url": null,
+                    "created_at": "2023-08-03T17:40:49Z",
+                    "updated_at": "2023-08-03T17:40:49Z"
+                },
+                "actor": {
+                    "id": "6a65fcb0-507d-45ae-85d9-9bb29fe8d234",
+                    "email": "scheduler@addons.heroku.com"
+                },
+                "action": "create",
+                "version": "application/vnd.heroku+json; version=3",
+                "resource": "dyno",
+                "sequence": null,
+                "created_at": "2023-08-03T17:40:49.504132Z",
+                "updated_at": "2023-08-03T17:40:49.504139Z",
+                "published_at": "2023-08-03T17:40:50Z",
+                "previous_data": {},
+                "webhook_metadata": {
+                    "attempt": {
+                        "id": "9c522e4b-46aa-4233-9831-412a69c5357b"
+                    },
+                    "delivery": {
+                        "id": "4505f0a6-9397-4195-8640-33159b48cfb7"
+                    },
+                    "event": {
+                        "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
+                        "include": "api:dyno"
+                    },
+                    "webhook": {
+                        "id": "f7491c4b-2212-46d5-826f-064489daf9c4"
+                    }
+                }
+            }"#;
+
+            let expected = HookPayload::Dyno(DynoHookPayload {
+                data: DynoHookData {
+                    app: AppData {
+                        name: "my-app".to_string(),
+                    },
+                    name: "scheduler.3540".to_string(),
+                    typ: "scheduler".to_string(),
+                    state: DynoState::Starting,
+                    exit_status: None,
+                },
+                created_at: Some("2023-08-03T17:40:49.504132Z".parse().unwrap()),
+            });
+
+            assert_eq!(
+                expected,
+                serde_json::from_str(real_redacted_example).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_root_payload_dyno_null_status_code() {
+            let synthetic_example = r#"{
+                "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
+                "data": {
+                    "id": "d0eb9c96-0529-4f9d-bb4c-cc3382a55180",
+                    "app": {
+                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
+                        "name": "my-app"
+                    },
+                    "name": "scheduler.3540",
+                    "size": "Standard-1X",
+                    "type": "scheduler",
+                    "state": "starting",
+                    "exit_status": null,
+                    "command": "/bin/cowsay moo",
+                    "release": {
+                        "id": "9e67f453-d990-4c5f-89d3-c9ca2a70830f",
+                        "version": 7636
+                    },
+                    "attach_url": null,
+                    "created_at": "2023-08-03T17:40:49Z",
+                    "updated_at": "2023-08-03T17:40:49Z"
+                },
+                "actor": {
+                    "id": "6a65fcb0-507d-45ae-85d9-9bb29fe8d234",
+                    "email": "scheduler@addons.heroku.com"
+                },
+                "action": "create",
+                "version": "application/vnd.heroku+json; version=3",
+                "resource": "dyno",
+                "sequence": null,
+                "created_at": "2023-08-03T17:40:49.504132Z",
+                "updated_at": "2023-08-03T17:40:49.504139Z",
+                "published_at": "2023-08-03T17:40:50Z",
+                "previous_data": {},
+                "webhook_metadata": {
+                    "attempt": {
+                        "id": "9c522e4b-46aa-4233-9831-412a69c5357b"
+                    },
+                    "delivery": {
+                        "id": "4505f0a6-9397-4195-8640-33159b48cfb7"
+                    },
+                    "event": {
+                        "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
+                        "include": "api:dyno"
+                    },
+                    "webhook": {
+                        "id": "f7491c4b-2212-46d5-826f-064489daf9c4"
+                    }
+                }
+            }"#;
+
+            let expected = HookPayload::Dyno(DynoHookPayload {
+                data: DynoHookData {
+                    app: AppData {
+                        name: "my-app".to_string(),
+                    },
+                    name: "scheduler.3540".to_string(),
+                    typ: "scheduler".to_string(),
+                    state: DynoState::Starting,
+                    exit_status: None,
+                },
+                created_at: Some("2023-08-03T17:40:49.504132Z".parse().unwrap()),
+            });
+
+            assert_eq!(expected, serde_json::from_str(synthetic_example).unwrap());
+        }
+
+        #[test]
+        fn test_tolerant_enums() {
+            let example = r#"{
+                "resource": "dyno",
+                "data": {
+                    "app": { "name": "my-app" },
+                    "name": "web.1",
+                    "type": "web",
+                    "state": "Crashed",
+                    "exit_status": 1
+                }
+            }"#;
+
+            match serde_json::from_str(example).unwrap() {
+                HookPayload::Dyno(x) => assert_eq!(x.data.state, DynoState::Crashed),
+                x => panic!("unexpected payload: {:?}", x),
+            }
+
+            let example = r#"{
+                "resource": "release",
+                "action": "Promote",
+                "data": {
+                    "app": { "name": "my-app" },
+                    "description": "Promote v1",
+                    "user": { "email": "hodor@unsplash.com" }
+                }
+            }"#;
+
+            match serde_json::from_str(example).unwrap() {
+                HookPayload::Release(x) => {
+                    assert_eq!(x.action, ReleaseHookAction::Other("Promote".into()))
+                }
+                x => panic!("unexpected payload: {:?}", x),
+            }
+        }
+
+        #[test]
+        fn test_unknown_fields() {
+            let example = r#"{
+                "resource": "release",
+                "action": "rollback",
+                "data": {
+                    "app": { "name": "my-app", "process_tier": "production" },
+                    "description": "Rollback to v1",
+                    "user": { "email": "hodor@unsplash.com" },
+                    "version": 6644
+                },
+                "created_at": "yesterday",
+                "sequence": null
+            }"#;
+
+            let (x, mut unknown) = HookPayload::from_json_with_unknown(example.as_bytes()).unwrap();
+            unknown.sort();
+
+            assert!(matches!(x, HookPayload::Release(_)));
+            assert_eq!(
+                unknown,
+                vec!["action", "created_at", "data.app.process_tier", "sequence"]
+            );
+        }
+    }
+
+    mod properties {
+        use super::*;
+        use quickcheck::{Arbitrary, Gen};
+        use serde_json::json;
+
+        /// The ways in which a field might surprise us.
+        #[derive(Clone, Debug)]
+        enum Field {
+            Missing,
+            Null,
+            Junk(JsonValue),
+            Valid,
+        }
+
+        impl Arbitrary for Field {
+            fn arbitrary(g: &mut Gen) -> Self {
+                match g.choose(&[0, 1, 2, 3]).unwrap() {
+                    0 => Field::Missing,
+                    1 => Field::Null,
+                    2 => Field::Junk(JsonValue::arbitrary(g)),
+                    _ => Field::Valid,
+                }
+            }
+        }
+
+        /// Arbitrary, shallow JSON.
+        #[derive(Clone, Debug)]
+        struct JsonValue(Value);
+
+        impl Arbitrary for JsonValue {
+            fn arbitrary(g: &mut Gen) -> Self {
+                JsonValue(match g.choose(&[0, 1, 2, 3, 4, 5]).unwrap() {
+                    0 => Value::Null,
+                    1 => json!(bool::arbitrary(g)),
+                    2 => json!(i64::arbitrary(g)),
+                    3 => json!(String::arbitrary(g)),
+                    4 => json!(Vec::<String>::arbitrary(g)),
+                    _ => json!({ String::arbitrary(g): String::arbitrary(g) }),
+                })
+            }
+        }
+
+        /// Set a field on an object as per [Field].
+        fn set(obj: &mut Value, k: &str, field: Field, valid: Value) {
+            let v = match field {
+                Field::Missing => return,
+                Field::Null => Value::Null,
+                Field::Junk(x) => x.0,
+                Field::Valid => valid,
+            };
+
+            obj[k] = v;
+        }
+
+        quickcheck! {
+          fn test_from_json_never_panics(x: Vec<u8>) -> () {
+              let _ = HookPayload::from_json_with_unknown(&x);
+          }
+
+          fn test_from_arbitrary_json_never_panics(x: JsonValue) -> () {
+              let _ = HookPayload::from_json_with_unknown(x.0.to_string().as_bytes());
+          }
+
+          fn test_release_optional_fields(slug: Field, slug_commit: Field, created_at: Field, extra: String) -> bool {
+              let mut data = json!({
+                  "app": { "name": "my-app" },
+                  "description": "Deploy 69eec518",
+                  "user": { "email": "x@example.com" },
+              });
+              let mut slug_data = json!({});
+              set(&mut slug_data, "commit", slug_commit, json!("69eec518"));
+              set(&mut data, "slug", slug, slug_data);
+
+              let mut x = json!({
+                  "resource": "release",
+                  "action": "update",
+                  "data": data,
+              });
+              set(&mut x, "created_at", created_at, json!("2023-08-03T10:00:30Z"));
+              x[format!("x-{}", extra)] = json!("unexpected");
+
+              HookPayload::from_json_with_unknown(x.to_string().as_bytes()).is_ok()
+          }
+
+          fn test_dyno_optional_fields(exit_status: Option<u8>, null: bool, created_at: Field) -> bool {
+              let mut data = json!({
+                  "app": { "name": "my-app" },
+                  "name": "web.1",
+                  "type": "web",
+                  "state": "crashed",
+              });
+              match exit_status {
+                  Some(n) => data["exit_status"] = json!(n),
+                  None if null => data["exit_status"] = Value::Null,
+                  None => {}
+              }
+
+              let mut x = json!({ "resource": "dyno", "data": data });
+              set(&mut x, "created_at", created_at, json!("2023-08-03T10:00:30Z"));
+
+              match HookPayload::from_json_with_unknown(x.to_string().as_bytes()) {
+                  Ok((HookPayload::Dyno(x), _)) => x.data.exit_status == exit_status,
+                  _ => false,
+              }
+          }
+        }
+    }
+}
@@ -1,9 +1,17 @@
 //! Messaging platforms for successful Heroku webhook requests.
 
-use self::slack::SlackPlatform;
+use self::{
+    github::GitHubPlatform, matrix::MatrixPlatform, slack::SlackPlatform,
+    statuspage::StatuspagePlatform, stdout::StdoutPlatform, zulip::ZulipPlatform,
+};
 use serde::Deserialize;
 
-pub(super) mod slack;
+pub mod github;
+pub mod matrix;
+pub mod slack;
+pub mod statuspage;
+pub mod stdout;
+pub mod zulip;
 
 /// Supported onward platforms.
 #[derive(Deserialize)]
@@ -12,4 +20,24 @@ pub enum Platform {
     /// Post a fixed message to the specified Slack channel.
     #[serde(rename = "slack")]
     Slack(SlackPlatform),
+    /// Write the message that would have been posted to the console.
+    #[serde(rename = "stdout")]
+    Stdout(StdoutPlatform),
+    /// Post to the specified Matrix room.
+    #[serde(rename = "matrix")]
+    Matrix(MatrixPlatform),
+    /// Post to the specified Zulip stream, under a topic per app.
+    #[serde(rename = "zulip")]
+    Zulip(ZulipPlatform),
+    /// Report to the specified GitHub repository as commit statuses and
+    /// issues.
+    #[serde(rename = "github")]
+    GitHub(GitHubPlatform),
+    /// Open incidents on the configured Statuspage page for critical events,
+    /// resolving them upon recovery.
+    #[serde(rename = "statuspage")]
+    Statuspage(StatuspagePlatform),
+    /// Relay to the configured Otto instance in its legacy formats.
+    #[serde(rename = "otto")]
+    Otto,
 }
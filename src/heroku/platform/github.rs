@@ -0,0 +1,14 @@
+//! Report events to a specified GitHub repository on receipt of a Heroku
+//! webhook, as commit statuses and issues. See [crate::github].
+
+use crate::github::GitHubRepo;
+use serde::Deserialize;
+
+/// Metadata for the GitHub platform which the webhook request must supply.
+#[derive(Deserialize)]
+pub struct GitHubPlatform {
+    /// Shared with [HookOptions][crate::heroku::webhook::HookOptions], so
+    /// deploys and rollbacks reported here also include changelogs elsewhere
+    /// in the event.
+    pub repo: GitHubRepo,
+}
@@ -0,0 +1,12 @@
+//! Post events to a specified Matrix room on receipt of a Heroku webhook, as
+//! per [crate::matrix].
+
+use crate::matrix::RoomAlias;
+use serde::Deserialize;
+
+/// Metadata for the Matrix platform which the webhook request must supply.
+#[derive(Deserialize)]
+pub struct MatrixPlatform {
+    /// Unlike Slack channels this is neither templated nor routed.
+    pub room: RoomAlias,
+}
@@ -3,8 +3,77 @@
 use crate::slack::channel::ChannelName;
 use serde::Deserialize;
 
+/// The placeholder in a channel name substituted for the app's name.
+pub const APP_PLACEHOLDER: &str = "{app}";
+
+/// Slack's limit on the length of channel names.
+const MAX_CHANNEL_NAME_LEN: usize = 80;
+
 /// Metadata for the Slack platform which the webhook request must supply.
 #[derive(Deserialize)]
 pub struct SlackPlatform {
-    pub channel: ChannelName,
+    /// May be templated with the app's name. See [expand_channel]. If omitted
+    /// the channel is found via [crate::heroku::routing].
+    pub channel: Option<ChannelName>,
+    /// The name of a route to find the channel via, if the channel's omitted.
+    /// See [crate::heroku::routing].
+    pub route: Option<String>,
+}
+
+/// Expand a templated channel name such as `deploys-{app}` for an app,
+/// enabling per-app channels with a single webhook configuration, which is
+/// especially useful for review apps. Channel names without a placeholder are
+/// returned as-is.
+///
+/// ```
+/// let x = expand_channel(&ChannelName("deploys-{app}".into()), "mercury-pr-12");
+/// assert_eq!(x.0, "deploys-mercury-pr-12");
+/// ```
+pub fn expand_channel(template: &ChannelName, app_name: &str) -> ChannelName {
+    if !template.0.contains(APP_PLACEHOLDER) {
+        return template.clone();
+    }
+
+    let x = template
+        .0
+        .replace(APP_PLACEHOLDER, &sanitise_channel_name(app_name));
+
+    ChannelName(x.chars().take(MAX_CHANNEL_NAME_LEN).collect())
+}
+
+/// Coerce arbitrary text into the characters Slack permits in channel names:
+/// lowercase letters, numbers, hyphens, and underscores.
+fn sanitise_channel_name(x: &str) -> String {
+    x.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' | '-' | '_' => c,
+            _ => '-',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_channel() {
+        let expand = |t: &str, app| expand_channel(&ChannelName(t.into()), app).0;
+
+        assert_eq!(expand("deploys", "any"), "deploys");
+        assert_eq!(
+            expand("deploys-{app}", "mercury-pr-12"),
+            "deploys-mercury-pr-12"
+        );
+        assert_eq!(expand("#{app}-deploys", "mercury"), "#mercury-deploys");
+        assert_eq!(
+            expand("deploys-{app}", "Weird App.Name"),
+            "deploys-weird-app-name"
+        );
+        assert_eq!(
+            expand("{app}", &"x".repeat(100)).len(),
+            MAX_CHANNEL_NAME_LEN
+        );
+    }
 }
@@ -0,0 +1,12 @@
+//! Open and resolve incidents on a Statuspage page on receipt of a Heroku
+//! webhook, as per [crate::statuspage].
+
+use serde::Deserialize;
+
+/// Metadata for the Statuspage platform which the webhook request may supply.
+#[derive(Deserialize)]
+pub struct StatuspagePlatform {
+    /// The ID of the component representing the app, if any, whose status
+    /// follows the incident's.
+    pub component: Option<String>,
+}
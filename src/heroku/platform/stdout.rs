@@ -0,0 +1,15 @@
+//! Write messages to the console on receipt of a Heroku webhook rather than
+//! sending them anywhere, as per [crate::console].
+
+use crate::slack::channel::ChannelName;
+use serde::Deserialize;
+
+/// Metadata for the stdout platform, which is resolved into a message exactly
+/// as per the Slack platform, however the channel is only recorded.
+#[derive(Deserialize)]
+pub struct StdoutPlatform {
+    /// See [super::slack::SlackPlatform].
+    pub channel: Option<ChannelName>,
+    /// See [super::slack::SlackPlatform].
+    pub route: Option<String>,
+}
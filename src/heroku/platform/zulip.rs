@@ -0,0 +1,11 @@
+//! Post events to a specified Zulip stream on receipt of a Heroku webhook, as
+//! per [crate::zulip].
+
+use serde::Deserialize;
+
+/// Metadata for the Zulip platform which the webhook request must supply.
+#[derive(Deserialize)]
+pub struct ZulipPlatform {
+    /// The topic is the app's name, so neither this nor it is templated.
+    pub stream: String,
+}
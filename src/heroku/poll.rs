@@ -0,0 +1,465 @@
+//! Optionally poll the Heroku Platform API for apps' recent releases and dyno
+//! states, synthesising the events their webhooks would otherwise have sent.
+//! This is a fallback for apps on which webhooks can't be configured, or whose
+//! webhooks have been silently deleted.
+//!
+//! Apps are configured via `$HEROKU_POLL_APPS` as a comma-separated list of
+//! `app` or `app:channel` entries, for example `api:api-deploys,web`. Apps
+//! without a channel are routed as per [super::routing]. Polling requires an
+//! API token with read access to the apps at `$HEROKU_API_TOKEN`, and happens
+//! every `$HEROKU_POLL_INTERVAL_SECS`, a minute by default.
+//!
+//! The first poll of each app only records where things stand, so restarts
+//! don't replay history. Thereafter each newly succeeded release is forwarded
+//! as its webhook would have been, as is each dyno which has newly crashed. The
+//! API doesn't report crashed dynos' exit statuses, so they're reported
+//! without, and as with webhooks `run` dynos are ignored.
+//!
+//! Events aren't deduplicated against webhooks, so apps shouldn't be
+//! configured for both.
+//!
+//! <https://devcenter.heroku.com/articles/platform-api-reference>
+
+use super::{
+    payload::*,
+    platform::slack::SlackPlatform,
+    webhook::{forward, send, HookEvent, HookOptions},
+    Platform,
+};
+use crate::{router::Deps, slack::channel::ChannelName};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+use tracing::warn;
+
+/// The base URL of the Heroku Platform API.
+pub const API_BASE: &str = "https://api.heroku.com";
+
+/// How often to poll by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many of an app's most recent releases to fetch per poll. Any more
+/// released between polls are missed.
+const MAX_RELEASES: usize = 20;
+
+/// A newtype wrapper around a Heroku Platform API token.
+#[derive(Clone)]
+pub struct HerokuApiToken(pub String);
+
+/// An app to poll, and where to send its events.
+pub struct PolledApp {
+    pub name: String,
+    /// If absent the channel is found via [super::routing].
+    pub channel: Option<ChannelName>,
+}
+
+/// Parse polled apps from their environment variable representation, ignoring
+/// invalid entries.
+///
+/// ```
+/// let xs = parse_poll_apps("api:api-deploys, web");
+/// assert_eq!(xs.len(), 2);
+/// ```
+pub fn parse_poll_apps(x: &str) -> Vec<PolledApp> {
+    x.split(',')
+        .filter_map(|entry| {
+            let mut xs = entry.trim().splitn(2, ':');
+            let name = xs.next().filter(|x| !x.is_empty())?;
+
+            Some(PolledApp {
+                name: name.to_owned(),
+                channel: xs
+                    .next()
+                    .filter(|x| !x.is_empty())
+                    .map(|x| ChannelName(x.to_owned())),
+            })
+        })
+        .collect()
+}
+
+/// <https://devcenter.heroku.com/articles/platform-api-reference#release>
+#[derive(Debug, Deserialize)]
+struct Release {
+    version: u64,
+    description: String,
+    /// One of `pending`, `succeeded`, or `failed`.
+    status: String,
+    user: UserData,
+    created_at: Option<DateTime<Utc>>,
+}
+
+/// <https://devcenter.heroku.com/articles/platform-api-reference#dyno>
+#[derive(Debug, Deserialize)]
+struct Dyno {
+    name: String,
+    #[serde(rename = "type")]
+    typ: String,
+    state: String,
+}
+
+/// What we knew of an app as of the previous poll.
+#[derive(Debug, Default)]
+struct AppState {
+    /// The most recent release we've dealt with, if we've polled before.
+    last_version: Option<u64>,
+    /// The names of the dynos which were crashed.
+    crashed: HashSet<String>,
+}
+
+impl AppState {
+    /// Take the releases which are new since the previous poll, oldest first,
+    /// given the most recent releases in any order.
+    ///
+    /// Pending releases and any after them are left for a future poll, so that
+    /// they're reported once they've succeeded.
+    fn new_releases(&mut self, mut xs: Vec<Release>) -> Vec<Release> {
+        xs.sort_by_key(|x| x.version);
+        let first_poll = self.last_version.is_none();
+        let last = self.last_version.unwrap_or_default();
+
+        let pending = xs.iter().position(|x| x.status == "pending");
+        let done: Vec<_> = xs
+            .into_iter()
+            .take(pending.unwrap_or(usize::MAX))
+            .filter(|x| x.version > last)
+            .collect();
+
+        if let Some(x) = done.last() {
+            self.last_version = Some(x.version);
+        } else if first_poll {
+            self.last_version = Some(0);
+        }
+
+        match first_poll {
+            true => Vec::new(),
+            false => done
+                .into_iter()
+                .filter(|x| x.status == "succeeded")
+                .collect(),
+        }
+    }
+
+    /// Take the dynos which have crashed since the previous poll.
+    fn new_crashes(&mut self, xs: Vec<Dyno>) -> Vec<Dyno> {
+        let first_poll = self.last_version.is_none();
+        let crashed: Vec<_> = xs
+            .into_iter()
+            .filter(|x| x.state == "crashed" && x.typ != "run")
+            .collect();
+        let prev = std::mem::replace(
+            &mut self.crashed,
+            crashed.iter().map(|x| x.name.clone()).collect(),
+        );
+
+        match first_poll {
+            true => Vec::new(),
+            false => crashed
+                .into_iter()
+                .filter(|x| !prev.contains(&x.name))
+                .collect(),
+        }
+    }
+}
+
+/// Polls the configured apps, remembering what it's seen of each.
+pub struct Poller {
+    client: reqwest::Client,
+    base_url: String,
+    token: HerokuApiToken,
+    apps: Vec<PolledApp>,
+    state: HashMap<String, AppState>,
+}
+
+impl Poller {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [API_BASE].
+    pub fn new(base_url: String, token: HerokuApiToken, apps: Vec<PolledApp>) -> Self {
+        Poller {
+            client: reqwest::Client::new(),
+            base_url,
+            token,
+            apps,
+            state: HashMap::new(),
+        }
+    }
+
+    fn get(&self, path: String) -> reqwest::RequestBuilder {
+        self.client
+            .get(self.base_url.clone() + &path)
+            .bearer_auth(&self.token.0)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/vnd.heroku+json; version=3",
+            )
+    }
+
+    async fn releases(&self, app: &str) -> Result<Vec<Release>, reqwest::Error> {
+        self.get(format!("/apps/{}/releases", app))
+            .header(
+                reqwest::header::RANGE,
+                format!("version ..; order=desc, max={}", MAX_RELEASES),
+            )
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    async fn dynos(&self, app: &str) -> Result<Vec<Dyno>, reqwest::Error> {
+        self.get(format!("/apps/{}/dynos", app))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// Poll each app once, forwarding anything new.
+    pub async fn poll(&mut self, deps: &Deps) {
+        for i in 0..self.apps.len() {
+            let app = &self.apps[i];
+
+            let (releases, dynos) =
+                match tokio::try_join!(self.releases(&app.name), self.dynos(&app.name)) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        warn!("Failed to poll Heroku app {}: {:?}", app.name, e);
+                        continue;
+                    }
+                };
+
+            let state = self.state.entry(app.name.clone()).or_default();
+            let crashes = state.new_crashes(dynos);
+            let releases = state.new_releases(releases);
+
+            let app = &self.apps[i];
+            let plat = Platform::Slack(SlackPlatform {
+                channel: app.channel.clone(),
+                route: None,
+            });
+            let app_data = || AppData {
+                name: app.name.clone(),
+            };
+
+            for x in releases {
+                let payload = HookPayload::Release(ReleaseHookPayload {
+                    data: ReleaseHookData {
+                        app: app_data(),
+                        description: x.description,
+                        user: x.user,
+                        slug: None,
+                        version: Some(x.version),
+                    },
+                    action: ReleaseHookAction::Update,
+                    created_at: x.created_at,
+                });
+
+                forward(deps, &plat, &HookOptions { repo: None }, &payload)
+                    .await
+                    .log("polled release");
+            }
+
+            for x in crashes {
+                let event = HookEvent::DynoCrash {
+                    name: x.name.clone(),
+                    status_code: None,
+                };
+                let payload = HookPayload::Dyno(DynoHookPayload {
+                    data: DynoHookData {
+                        app: app_data(),
+                        name: x.name,
+                        typ: x.typ,
+                        state: DynoState::Crashed,
+                        exit_status: None,
+                    },
+                    created_at: Some(Utc::now()),
+                });
+
+                send(deps, &plat, &event, None, None, None, &payload)
+                    .await
+                    .log("polled dyno crash");
+            }
+        }
+    }
+}
+
+/// Indefinitely poll at the given interval.
+pub async fn watch(deps: Deps, mut poller: Poller, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+        poller.poll(&deps).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    fn release(version: u64, status: &str) -> Release {
+        Release {
+            version,
+            description: format!("Deploy {}", version),
+            status: status.to_owned(),
+            user: UserData {
+                email: "hodor@unsplash.com".to_owned(),
+            },
+            created_at: None,
+        }
+    }
+
+    fn dyno(name: &str, state: &str) -> Dyno {
+        Dyno {
+            name: name.to_owned(),
+            typ: name.split('.').next().unwrap().to_owned(),
+            state: state.to_owned(),
+        }
+    }
+
+    fn versions(xs: Vec<Release>) -> Vec<u64> {
+        xs.into_iter().map(|x| x.version).collect()
+    }
+
+    #[test]
+    fn test_parse_poll_apps() {
+        let xs: Vec<_> = parse_poll_apps(" api:api-deploys,, web ,:nope")
+            .into_iter()
+            .map(|x| (x.name, x.channel.map(|c| c.0)))
+            .collect();
+
+        assert_eq!(
+            xs,
+            vec![
+                ("api".into(), Some("api-deploys".into())),
+                ("web".into(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_new_releases() {
+        let mut x = AppState::default();
+
+        // The first poll only seeds the state.
+        assert!(x
+            .new_releases(vec![release(2, "succeeded"), release(1, "succeeded")])
+            .is_empty());
+        assert_eq!(x.last_version, Some(2));
+
+        assert_eq!(
+            versions(x.new_releases(vec![
+                release(5, "succeeded"),
+                release(4, "failed"),
+                release(3, "succeeded"),
+                release(2, "succeeded"),
+            ])),
+            vec![3, 5]
+        );
+        assert_eq!(x.last_version, Some(5));
+
+        // Pending releases hold back those after them until they're done.
+        assert_eq!(
+            versions(x.new_releases(vec![
+                release(8, "succeeded"),
+                release(7, "pending"),
+                release(6, "succeeded"),
+            ])),
+            vec![6]
+        );
+        assert_eq!(
+            versions(x.new_releases(vec![
+                release(8, "succeeded"),
+                release(7, "succeeded"),
+                release(6, "succeeded"),
+            ])),
+            vec![7, 8]
+        );
+        assert!(x.new_releases(vec![release(8, "succeeded")]).is_empty());
+    }
+
+    #[test]
+    fn test_new_releases_empty() {
+        let mut x = AppState::default();
+
+        assert!(x.new_releases(Vec::new()).is_empty());
+        assert_eq!(
+            versions(x.new_releases(vec![release(1, "succeeded")])),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn test_new_crashes() {
+        let mut x = AppState::default();
+
+        assert!(x.new_crashes(vec![dyno("web.1", "crashed")]).is_empty());
+        x.new_releases(Vec::new());
+
+        let crashes = x.new_crashes(vec![
+            dyno("web.1", "crashed"),
+            dyno("web.2", "crashed"),
+            dyno("run.1", "crashed"),
+            dyno("worker.1", "up"),
+        ]);
+        assert_eq!(
+            crashes.into_iter().map(|x| x.name).collect::<Vec<_>>(),
+            vec!["web.2"]
+        );
+
+        // Recovering and crashing again is a new crash.
+        assert!(x.new_crashes(vec![dyno("web.2", "up")]).is_empty());
+        assert_eq!(x.new_crashes(vec![dyno("web.2", "crashed")]).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let releases_mock = srv
+            .mock("GET", "/apps/my-app/releases")
+            .match_header("Authorization", "Bearer foobar")
+            .match_header("Accept", "application/vnd.heroku+json; version=3")
+            .match_header("Range", Matcher::Regex("order=desc".into()))
+            .with_status(206)
+            .with_body(
+                r#"[{
+                    "version": 6644,
+                    "description": "Deploy 69eec518",
+                    "status": "succeeded",
+                    "user": { "id": "x", "email": "hodor@unsplash.com" },
+                    "created_at": "2023-08-03T10:00:30Z",
+                    "current": true
+                }]"#,
+            )
+            .create_async()
+            .await;
+
+        let dynos_mock = srv
+            .mock("GET", "/apps/my-app/dynos")
+            .with_body(
+                r#"[{ "name": "web.1", "type": "web", "state": "crashed", "size": "basic" }]"#,
+            )
+            .create_async()
+            .await;
+
+        let poller = Poller::new(
+            srv.url(),
+            HerokuApiToken("foobar".into()),
+            parse_poll_apps("my-app"),
+        );
+
+        let releases = poller.releases("my-app").await.unwrap();
+        assert_eq!(versions(releases), vec![6644]);
+        releases_mock.assert_async().await;
+
+        let dynos = poller.dynos("my-app").await.unwrap();
+        assert_eq!(dynos[0].state, "crashed");
+        dynos_mock.assert_async().await;
+    }
+}
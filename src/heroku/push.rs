@@ -0,0 +1,326 @@
+//! Push Heroku events about particular apps to individual engineers' phones
+//! via [ntfy](https://ntfy.sh) or [Pushover](https://pushover.net), without
+//! the noise of Slack's mobile notifications.
+//!
+//! Routes are configured via `$HEROKU_PUSH_ROUTES` as a comma-separated list
+//! of `kind=target` or `kind/pattern=target` entries, for example
+//! `crash/api-*=ntfy:alice-api-crashes,crash=pushover:<user key>`. Kinds and
+//! patterns are as per [super::emoji], and the target is either an ntfy topic
+//! or a Pushover user or group key. Unlike emoji, every matching route is
+//! pushed to.
+//!
+//! ntfy topics are published to at `$NTFY_BASE`, ntfy.sh by default,
+//! authenticated with `$NTFY_TOKEN` if it's set. Pushover requires an
+//! application token at `$PUSHOVER_TOKEN`.
+//!
+//! Pushes are best effort, in addition to whatever's posted to Slack, and
+//! suppressed in read-only mode.
+
+use super::routing::{matches_pattern, parse_event_kind};
+use crate::{
+    event::{Event, EventKind},
+    slack::Severity,
+};
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The default base URL of the ntfy server to publish to.
+pub const NTFY_BASE: &str = "https://ntfy.sh";
+
+/// The base URL of Pushover's API.
+pub const PUSHOVER_BASE: &str = "https://api.pushover.net";
+
+/// How long to wait for each push to be accepted.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where to push to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Target {
+    /// An ntfy topic.
+    Ntfy(String),
+    /// A Pushover user or group key.
+    Pushover(String),
+}
+
+/// Push events of a kind to a target, optionally only for apps matching a
+/// pattern.
+pub struct PushRoute {
+    kind: EventKind,
+    pattern: Option<String>,
+    target: Target,
+}
+
+/// Parse routes from their environment variable representation.
+///
+/// ```
+/// let xs = parse_push_routes("crash/api-*=ntfy:alice-api-crashes").unwrap();
+/// ```
+pub fn parse_push_routes(x: &str) -> Result<Vec<PushRoute>, String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|entry| {
+            let invalid = || format!("invalid entry: {}", entry);
+
+            let (key, target) = entry.split_once('=').ok_or_else(invalid)?;
+            let (kind, pattern) = match key.split_once('/') {
+                Some((kind, pattern)) => (kind, Some(pattern.trim().to_owned())),
+                None => (key, None),
+            };
+            let target = match target.trim().split_once(':') {
+                Some(("ntfy", x)) if !x.is_empty() => Target::Ntfy(x.to_owned()),
+                Some(("pushover", x)) if !x.is_empty() => Target::Pushover(x.to_owned()),
+                _ => return Err(invalid()),
+            };
+
+            Ok(PushRoute {
+                kind: parse_event_kind(kind)?,
+                pattern,
+                target,
+            })
+        })
+        .collect()
+}
+
+/// <https://docs.ntfy.sh/publish/#publish-as-json>
+#[derive(Serialize)]
+struct NtfyMessage<'a> {
+    topic: &'a str,
+    title: &'a str,
+    message: &'a str,
+    /// From 1 to 5, 3 being the default.
+    priority: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    click: Option<&'a str>,
+}
+
+/// <https://pushover.net/api#messages>
+#[derive(Serialize)]
+struct PushoverMessage<'a> {
+    token: &'a str,
+    user: &'a str,
+    title: &'a str,
+    message: &'a str,
+    /// From -2 to 2, 0 being the default.
+    priority: i8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<&'a str>,
+}
+
+/// Pushes events to the targets routed to.
+pub struct Pusher {
+    client: reqwest::Client,
+    routes: Vec<PushRoute>,
+    ntfy_base: String,
+    ntfy_token: Option<String>,
+    pushover_base: String,
+    pushover_token: Option<String>,
+}
+
+impl Pusher {
+    pub fn new(routes: Vec<PushRoute>) -> Self {
+        Pusher {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            routes,
+            ntfy_base: NTFY_BASE.into(),
+            ntfy_token: None,
+            pushover_base: PUSHOVER_BASE.into(),
+            pushover_token: None,
+        }
+    }
+
+    /// Publish to an ntfy server other than [NTFY_BASE], optionally
+    /// authenticated.
+    pub fn with_ntfy(mut self, base_url: String, token: Option<String>) -> Self {
+        self.ntfy_base = base_url;
+        self.ntfy_token = token;
+        self
+    }
+
+    /// Push via Pushover with an application token, at a base URL other than
+    /// [PUSHOVER_BASE] if need be.
+    pub fn with_pushover(mut self, base_url: String, token: String) -> Self {
+        self.pushover_base = base_url;
+        self.pushover_token = Some(token);
+        self
+    }
+
+    /// Whether any route pushes via Pushover, and so requires a token.
+    pub fn needs_pushover(&self) -> bool {
+        self.routes
+            .iter()
+            .any(|x| matches!(x.target, Target::Pushover(_)))
+    }
+
+    /// The targets routed to for an event of a kind about an app.
+    fn targets<'a>(
+        &'a self,
+        kind: EventKind,
+        app_name: &'a str,
+    ) -> impl Iterator<Item = &'a Target> {
+        self.routes
+            .iter()
+            .filter(move |x| {
+                x.kind == kind
+                    && x.pattern
+                        .as_ref()
+                        .map_or(true, |p| matches_pattern(p, app_name))
+            })
+            .map(|x| &x.target)
+    }
+
+    /// Best effort push an event to every target routed to.
+    pub async fn push(&self, evt: &Event) {
+        let Some(app_name) = &evt.app else {
+            return;
+        };
+        let click = evt.links.first().map(|x| x.as_str());
+
+        for target in self.targets(evt.kind, app_name) {
+            let req = match target {
+                Target::Ntfy(topic) => {
+                    let req = self.client.post(&self.ntfy_base).json(&NtfyMessage {
+                        topic,
+                        title: &evt.title,
+                        message: &evt.summary,
+                        priority: match evt.severity {
+                            Some(Severity::Critical) => 5,
+                            Some(Severity::Warning) => 4,
+                            _ => 3,
+                        },
+                        click,
+                    });
+
+                    match &self.ntfy_token {
+                        Some(x) => req.bearer_auth(x),
+                        None => req,
+                    }
+                }
+                Target::Pushover(user) => {
+                    let Some(token) = &self.pushover_token else {
+                        warn!("Cannot push to Pushover without $PUSHOVER_TOKEN");
+                        continue;
+                    };
+
+                    self.client
+                        .post(self.pushover_base.clone() + "/1/messages.json")
+                        .json(&PushoverMessage {
+                            token,
+                            user,
+                            title: &evt.title,
+                            message: &evt.summary,
+                            priority: match evt.severity {
+                                Some(Severity::Critical) => 1,
+                                _ => 0,
+                            },
+                            url: click,
+                        })
+                }
+            };
+
+            match req.send().await.and_then(|x| x.error_for_status()) {
+                Ok(_) => info!("Pushed {:?} event about {}", evt.kind, app_name),
+                Err(e) => warn!("Failed to push event about {}: {}", app_name, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Source;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    fn crash(app: &str) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Crash,
+            app: Some(app.to_owned()),
+            version: None,
+            severity: Some(Severity::Critical),
+            occurred_at: None,
+            title: app.to_owned(),
+            summary: String::from("Dyno web.1 crashed"),
+            fields: Vec::new(),
+            links: vec!["https://dashboard.heroku.com/apps/api/activity"
+                .parse()
+                .unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_parse_push_routes() {
+        let xs = parse_push_routes(" crash/api-*=ntfy:alice ,, deploy=pushover:ukey").unwrap();
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].target, Target::Ntfy("alice".into()));
+        assert_eq!(xs[1].target, Target::Pushover("ukey".into()));
+
+        assert!(parse_push_routes("").unwrap().is_empty());
+        assert!(parse_push_routes("crash").is_err());
+        assert!(parse_push_routes("crash=ntfy:").is_err());
+        assert!(parse_push_routes("crash=email:x").is_err());
+        assert!(parse_push_routes("nope=ntfy:x").is_err());
+    }
+
+    #[test]
+    fn test_targets() {
+        let x = Pusher::new(
+            parse_push_routes("crash/api-*=ntfy:alice,crash=ntfy:bob,deploy/api=ntfy:carol")
+                .unwrap(),
+        );
+        let targets = |kind, app| x.targets(kind, app).collect::<Vec<_>>();
+
+        assert_eq!(
+            targets(EventKind::Crash, "api-staging"),
+            vec![&Target::Ntfy("alice".into()), &Target::Ntfy("bob".into())]
+        );
+        assert_eq!(
+            targets(EventKind::Crash, "web"),
+            vec![&Target::Ntfy("bob".into())]
+        );
+        assert!(targets(EventKind::Deploy, "web").is_empty());
+        assert!(!x.needs_pushover());
+    }
+
+    #[tokio::test]
+    async fn test_push() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let ntfy = srv
+            .mock("POST", "/")
+            .match_header("Authorization", "Bearer tk")
+            .match_body(Matcher::PartialJson(json!({
+                "topic": "alice",
+                "title": "api",
+                "message": "Dyno web.1 crashed",
+                "priority": 5,
+                "click": "https://dashboard.heroku.com/apps/api/activity",
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+        let pushover = srv
+            .mock("POST", "/1/messages.json")
+            .match_body(Matcher::PartialJson(json!({
+                "token": "app",
+                "user": "ukey",
+                "priority": 1,
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Pusher::new(parse_push_routes("crash/api=ntfy:alice,crash=pushover:ukey").unwrap())
+            .with_ntfy(srv.url() + "/", Some("tk".into()))
+            .with_pushover(srv.url(), "app".into());
+        assert!(x.needs_pushover());
+
+        x.push(&crash("api")).await;
+
+        ntfy.assert_async().await;
+        pushover.assert_async().await;
+    }
+}
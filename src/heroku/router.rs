@@ -1,25 +1,70 @@
 //! Heroku subrouter definition.
 //!
-//! The following subroute is supported:
+//! The following subroutes are supported:
 //!
 //! - POST: `/hook`
+//! - POST: `/hook/explain`
 
-use super::{auth::*, webhook::*, Platform};
-use crate::{router::Deps, slack::router::handle_slack_err};
+use super::{
+    auth::SchemeHeroku, explain::explain, otto::OttoError, payload::HookPayload, webhook::*,
+    Platform,
+};
+use crate::{
+    auth::Authenticated,
+    delivery::{Source, SUPPRESSED_HEADER},
+    github::GitHubError,
+    heartbeat::beat,
+    ingestion::Held,
+    matrix::MatrixError,
+    priority::too_many_requests,
+    router::Deps,
+    signed::SignedBody,
+    slack::router::{handle_slack_err, is_accepted_bearer},
+    statuspage::StatuspageError,
+    zulip::ZulipError,
+};
 use axum::{
     extract::{self, State},
-    http::{header::HeaderMap, StatusCode},
-    response::IntoResponse,
+    http::{
+        header::{HeaderMap, AUTHORIZATION},
+        Extensions, StatusCode,
+    },
+    response::{IntoResponse, Response},
     routing::post,
-    Router,
+    Json, Router,
 };
-use axum_extra::{headers, TypedHeader};
 use hyper::body::Bytes;
 use tracing::{info, warn};
 
 /// Instantiate a new Heroku subrouter.
 pub fn heroku_router() -> Router<Deps> {
-    Router::new().route("/hook", post(webhook_handler))
+    Router::new()
+        .route("/hook", post(webhook_handler))
+        .route("/hook/explain", post(explain_handler))
+}
+
+/// Handler for the POST subroute `/hook/explain`.
+///
+/// Requests must be authenticated with a `Bearer` token as per the Slack API.
+/// See [crate::slack::router].
+///
+/// Accepts the same query params and payload as [webhook_handler], responding
+/// with a [Trace][crate::explain::Trace] of how it would be handled in
+/// `application/json` format, without delivering it. The signature is checked
+/// only if supplied.
+async fn explain_handler(
+    State(deps): State<Deps>,
+    extract::Query(platform): extract::Query<Platform>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    match headers.get(AUTHORIZATION) {
+        Some(x) if is_accepted_bearer(&deps, x) => Authenticated::mark(&extensions),
+        _ => return StatusCode::UNAUTHORIZED.into_response(),
+    }
+
+    Json(explain(&deps, &platform, &headers, &body).await).into_response()
 }
 
 /// Handler for the POST subroute `/hook`.
@@ -28,62 +73,121 @@ pub fn heroku_router() -> Router<Deps> {
 /// of the request body, signed with the shared secret, must be present.
 ///
 /// Accepts a `platform` query param indicating the supported [Platform], along
-/// with that platform's respective query params.
+/// with that platform's respective query params. Further optional query params
+/// are described by [HookOptions].
 ///
 /// Accepts a [HookPayload] in `application/json` format. Valid events are
-/// forwarded to the specified platform. This feature is potentially
-/// temperamental; see [decode_release_payload].
+/// forwarded to the specified platform, subject to read-only mode. This
+/// feature is potentially temperamental; see [decode_release_payload].
+///
+/// Requests are held whilst ingestion from Heroku is paused, as per
+/// [crate::ingestion]. Requests other than crashes are turned away with a `429` status should
+/// there be too many messages awaiting delivery. See [crate::priority].
 async fn webhook_handler(
     State(deps): State<Deps>,
-    TypedHeader(content_type): TypedHeader<headers::ContentType>,
-    headers: HeaderMap,
     extract::Query(platform): extract::Query<Platform>,
-    // We can't parse this at all yet as we need to compare signatures.
-    body_bytes: Bytes,
+    extract::Query(opts): extract::Query<HookOptions>,
+    SignedBody(payload, _): SignedBody<HookPayload, SchemeHeroku>,
 ) -> impl IntoResponse {
-    let heroku_secret = deps
-        .heroku_secret
-        .as_ref()
-        .ok_or_else(|| (StatusCode::PRECONDITION_FAILED, String::new()))?;
-
-    if content_type != headers::ContentType::json() {
-        return Err((
-            StatusCode::UNSUPPORTED_MEDIA_TYPE,
-            String::from("Requests must have `Content-Type: application/json`"),
-        ));
+    beat(&deps, Source::Heroku, Some(&get_app_data(&payload).name));
+
+    let job = Job {
+        platform,
+        opts,
+        payload,
+    };
+    let job = match deps.ingestion.hold(Source::Heroku, job, Held::HerokuHook) {
+        Ok(()) => {
+            info!("Ingestion from Heroku paused, holding webhook");
+
+            return Ok([(SUPPRESSED_HEADER, "paused")].into_response());
+        }
+        Err(job) => job,
+    };
+
+    if let Some(x) = deps.lanes.backpressure(get_priority(&job.payload)) {
+        warn!("Backlogged, asking Heroku to retry in {}s", x.as_secs());
+
+        return Err(too_many_requests(x));
     }
 
-    validate_request_signature(heroku_secret, &body_bytes, &headers)
-        .await
-        .map_err(|e| {
-            let msg = match e {
-                SecretError::Missing => "Missing Heroku secret",
-                SecretError::Invalid => "Invalid Heroku secret",
+    let res = forward(&deps, &job.platform, &job.opts, &job.payload).await;
+
+    match res {
+        ForwardResult::Failure(ForwardFailure::ToSlack(e)) => {
+            Err(handle_slack_err(&e).into_response())
+        }
+        ForwardResult::Failure(ForwardFailure::ToMatrix(e)) => {
+            warn!("{}", e);
+
+            let status = match e {
+                MatrixError::Unconfigured => StatusCode::BAD_REQUEST,
+                _ => StatusCode::BAD_GATEWAY,
             };
-            warn!(msg);
 
-            (StatusCode::UNAUTHORIZED, String::new())
-        })?;
+            Err((status, e.to_string()).into_response())
+        }
+        ForwardResult::Failure(ForwardFailure::ToZulip(e)) => {
+            warn!("{}", e);
 
-    let payload = serde_json::from_slice::<HookPayload>(&body_bytes).map_err(|e| {
-        let msg = format!("Failed to deserialize payload: {}", e);
-        warn!(msg);
+            let status = match e {
+                ZulipError::Unconfigured => StatusCode::BAD_REQUEST,
+                _ => StatusCode::BAD_GATEWAY,
+            };
 
-        (StatusCode::UNPROCESSABLE_ENTITY, msg)
-    })?;
+            Err((status, e.to_string()).into_response())
+        }
+        ForwardResult::Failure(ForwardFailure::ToGitHub(e)) => {
+            warn!("{}", e);
 
-    let res = forward(&deps, &platform, &payload).await;
+            let status = match e {
+                GitHubError::Unconfigured => StatusCode::BAD_REQUEST,
+                _ => StatusCode::BAD_GATEWAY,
+            };
 
-    match res {
-        ForwardResult::Failure(ForwardFailure::ToSlack(e)) => Err(handle_slack_err(&e)),
+            Err((status, e.to_string()).into_response())
+        }
+        ForwardResult::Failure(ForwardFailure::ToStatuspage(e)) => {
+            warn!("{}", e);
+
+            let status = match e {
+                StatuspageError::Unconfigured => StatusCode::BAD_REQUEST,
+                _ => StatusCode::BAD_GATEWAY,
+            };
+
+            Err((status, e.to_string()).into_response())
+        }
+        ForwardResult::Failure(ForwardFailure::ToOtto(e)) => {
+            warn!("{}", e);
+
+            let status = match e {
+                OttoError::Unconfigured => StatusCode::BAD_REQUEST,
+                _ => StatusCode::BAD_GATEWAY,
+            };
+
+            Err((status, e.to_string()).into_response())
+        }
         ForwardResult::UnsupportedEvent(evt) => {
             info!(
                 "Could not decode payload to a supported event, found: {}",
                 evt
             );
 
-            Ok(())
+            Ok(().into_response())
+        }
+        ForwardResult::Suppressed(reason) => Ok([(SUPPRESSED_HEADER, reason)].into_response()),
+        ForwardResult::Unroutable(app) => {
+            let msg = format!("No channel supplied or routed for app: {}", app);
+            warn!(msg);
+
+            Err((StatusCode::UNPROCESSABLE_ENTITY, msg).into_response())
+        }
+        ForwardResult::UnknownRoute(name) => {
+            let msg = format!("No route named: {}", name);
+            warn!(msg);
+
+            Err((StatusCode::UNPROCESSABLE_ENTITY, msg).into_response())
         }
-        ForwardResult::Success | ForwardResult::IgnoredAction => Ok(()),
+        ForwardResult::Success | ForwardResult::IgnoredAction => Ok(().into_response()),
     }
 }
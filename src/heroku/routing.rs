@@ -0,0 +1,263 @@
+//! Centralise knowledge of which team owns which Heroku app, so that webhooks
+//! needn't each specify a channel.
+//!
+//! Routes are configured via `$HEROKU_APP_ROUTES` as a comma-separated list of
+//! `pattern:channel` or `pattern:channel:mention` entries, for example
+//! `api-*:api-deploys:@api-team,web:web-deploys`. Patterns may include `*`
+//! wildcards. They're consulted in order when a webhook omits its channel, the
+//! first match winning.
+//!
+//! Webhooks can instead reference a named route with `route=name`, so that
+//! moving or renaming a channel needn't involve editing every webhook. Named
+//! routes are configured via `$HEROKU_NAMED_ROUTES` as per app routes, however
+//! with a name in place of each pattern, for example
+//! `prod-alerts:alerts-production:@sre`. Their channels may likewise be
+//! templated.
+//!
+//! Where an event is sent is decided by [route], both when it's forwarded and
+//! when it's explained.
+
+use super::platform::{
+    github::GitHubPlatform, matrix::MatrixPlatform, slack::expand_channel,
+    statuspage::StatuspagePlatform, zulip::ZulipPlatform, Platform,
+};
+use crate::{
+    event::EventKind,
+    router::Deps,
+    slack::{channel::ChannelName, mention::Mention},
+};
+
+/// Where to send messages for the Heroku apps matching a pattern.
+pub struct AppRoute {
+    pub pattern: String,
+    /// May be templated as per
+    /// [expand_channel][super::platform::slack::expand_channel].
+    pub channel: ChannelName,
+    pub cc: Option<Mention>,
+}
+
+/// Routes in order of precedence.
+pub type AppRoutes = Vec<AppRoute>;
+
+/// Parse app routes from their environment variable representation, ignoring
+/// invalid entries.
+///
+/// ```
+/// let xs = parse_app_routes("api-*:api-deploys:@api-team, web:web-deploys");
+/// assert_eq!(xs.len(), 2);
+/// ```
+pub fn parse_app_routes(x: &str) -> AppRoutes {
+    x.split(',')
+        .filter_map(|entry| {
+            // Mentions can themselves contain colons, as per on-call schedules.
+            let mut xs = entry.trim().splitn(3, ':');
+            let pattern = xs.next().filter(|x| !x.is_empty())?;
+            let channel = xs.next().filter(|x| !x.is_empty())?;
+
+            Some(AppRoute {
+                pattern: pattern.to_owned(),
+                channel: ChannelName(channel.to_owned()),
+                cc: xs.next().map(|x| Mention::from(x.to_owned())),
+            })
+        })
+        .collect()
+}
+
+/// Find the first route whose pattern matches an app's name.
+pub fn find_app_route<'a>(routes: &'a [AppRoute], app_name: &str) -> Option<&'a AppRoute> {
+    routes
+        .iter()
+        .find(|r| matches_pattern(&r.pattern, app_name))
+}
+
+/// Find the route with the given name, as per [parse_app_routes] with names in
+/// place of patterns.
+pub fn find_named_route<'a>(routes: &'a [AppRoute], name: &str) -> Option<&'a AppRoute> {
+    routes.iter().find(|r| r.pattern == name)
+}
+
+/// Where an event is sent, as decided by [route].
+pub enum Destination<'a> {
+    Slack(Channel<'a>),
+    Stdout(Channel<'a>),
+    Matrix(&'a MatrixPlatform),
+    Zulip(&'a ZulipPlatform),
+    GitHub(&'a GitHubPlatform),
+    Statuspage(&'a StatuspagePlatform),
+    Otto,
+}
+
+/// A channel to send an event to.
+pub struct Channel<'a> {
+    /// Expanded for the app as per [expand_channel].
+    pub name: ChannelName,
+    pub cc: Option<Mention>,
+    pub via: Via<'a>,
+}
+
+/// How a [Channel] was found.
+pub enum Via<'a> {
+    /// The webhook supplied it.
+    Supplied,
+    /// By the name of the route the webhook supplied.
+    Name(&'a str),
+    /// By the pattern of the first route matching the app's name.
+    Pattern(&'a str),
+}
+
+/// Why an event couldn't be routed.
+pub enum Unrouted {
+    /// The named route supplied isn't configured.
+    UnknownRoute(String),
+    /// No channel was supplied, and none is routed for the app.
+    NoRoute,
+}
+
+/// Decide where to send an event about an app. Rooms, streams, repositories,
+/// pages, and Otto are used as supplied or configured, whereas channels are
+/// supplied or else found by the route supplied or by the app's name.
+pub fn route<'a>(
+    deps: &'a Deps,
+    plat: &'a Platform,
+    app_name: &str,
+) -> Result<Destination<'a>, Unrouted> {
+    match plat {
+        Platform::Slack(x) => find_channel(deps, x.channel.as_ref(), x.route.as_deref(), app_name)
+            .map(Destination::Slack),
+        Platform::Stdout(x) => find_channel(deps, x.channel.as_ref(), x.route.as_deref(), app_name)
+            .map(Destination::Stdout),
+        Platform::Matrix(x) => Ok(Destination::Matrix(x)),
+        Platform::Zulip(x) => Ok(Destination::Zulip(x)),
+        Platform::GitHub(x) => Ok(Destination::GitHub(x)),
+        Platform::Statuspage(x) => Ok(Destination::Statuspage(x)),
+        Platform::Otto => Ok(Destination::Otto),
+    }
+}
+
+fn find_channel<'a>(
+    deps: &'a Deps,
+    channel: Option<&'a ChannelName>,
+    route: Option<&'a str>,
+    app_name: &str,
+) -> Result<Channel<'a>, Unrouted> {
+    let (template, cc, via) = match (channel, route) {
+        (Some(x), _) => (x, None, Via::Supplied),
+        (None, Some(name)) => {
+            let r = find_named_route(&deps.heroku_named_routes, name)
+                .ok_or_else(|| Unrouted::UnknownRoute(name.to_owned()))?;
+
+            (&r.channel, r.cc.clone(), Via::Name(name))
+        }
+        (None, None) => {
+            let r = find_app_route(&deps.heroku_app_routes, app_name).ok_or(Unrouted::NoRoute)?;
+
+            (&r.channel, r.cc.clone(), Via::Pattern(&r.pattern))
+        }
+    };
+
+    Ok(Channel {
+        name: expand_channel(template, app_name),
+        cc,
+        via,
+    })
+}
+
+/// Parse a kind of event as it's configured, one of `deploy`, `rollback`,
+/// `config`, or `crash`.
+pub(super) fn parse_event_kind(x: &str) -> Result<EventKind, String> {
+    match x.trim() {
+        "deploy" => Ok(EventKind::Deploy),
+        "rollback" => Ok(EventKind::Rollback),
+        "config" => Ok(EventKind::ConfigChange),
+        "crash" => Ok(EventKind::Crash),
+        x => Err(format!("unknown kind: {}", x)),
+    }
+}
+
+/// Test whether a name matches a pattern in which `*` matches any sequence of
+/// characters, including none.
+pub(super) fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There's always at least one part, even for an empty pattern.
+    let first = parts.next().unwrap_or_default();
+
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts = parts.peekable();
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            // The final part must anchor to the end of the name.
+            return rest.ends_with(part);
+        }
+
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    // No wildcards, so the prefix must have been the whole name.
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_app_routes() {
+        let xs = parse_app_routes("api-*:api-deploys:@api-team, web:web-deploys,invalid,:x");
+
+        assert_eq!(xs.len(), 2);
+        assert_eq!(xs[0].pattern, "api-*");
+        assert_eq!(xs[0].channel.0, "api-deploys");
+        assert_eq!(
+            xs[0].cc.clone().map(String::from),
+            Some("@api-team".to_owned())
+        );
+        assert!(xs[1].cc.is_none());
+
+        let xs = parse_app_routes("*:alerts:oncall:P1ABCDE");
+        assert_eq!(
+            xs[0].cc.clone().map(String::from),
+            Some("oncall:P1ABCDE".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("web", "web"));
+        assert!(!matches_pattern("web", "web-staging"));
+        assert!(matches_pattern("web-*", "web-staging"));
+        assert!(matches_pattern("web-*", "web-"));
+        assert!(!matches_pattern("web-*", "api-staging"));
+        assert!(matches_pattern("*-staging", "web-staging"));
+        assert!(matches_pattern("*-pr-*", "web-pr-12"));
+        assert!(!matches_pattern("*-pr-*", "web-prod"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("a*a", "aa"));
+        assert!(!matches_pattern("a*a", "a"));
+    }
+
+    #[test]
+    fn test_find_app_route() {
+        let xs = parse_app_routes("web-*:web,*:catch-all");
+
+        assert_eq!(find_app_route(&xs, "web-pr-1").unwrap().channel.0, "web");
+        assert_eq!(find_app_route(&xs, "api").unwrap().channel.0, "catch-all");
+        assert!(find_app_route(&[], "api").is_none());
+    }
+
+    #[test]
+    fn test_find_named_route() {
+        let xs = parse_app_routes("prod-alerts:alerts:@sre,prod-*:other");
+
+        assert_eq!(
+            find_named_route(&xs, "prod-alerts").unwrap().channel.0,
+            "alerts"
+        );
+        assert!(find_named_route(&xs, "prod-deploys").is_none());
+    }
+}
@@ -0,0 +1,161 @@
+//! Bookmark apps' runbooks in the channels their crashes are posted to, so
+//! that responders always have them one click away.
+//!
+//! Runbooks are configured via `$HEROKU_RUNBOOKS` as a comma-separated list of
+//! `pattern:url` entries, for example
+//! `api-*:https://wiki.example.com/api-runbook`. Patterns may include `*`
+//! wildcards as per [super::routing], the first match winning.
+//!
+//! Bookmarking is best effort, and happens at most once per channel and
+//! runbook per process, or not at all if the runbook's already bookmarked.
+
+use super::routing::matches_pattern;
+use crate::{
+    router::Deps,
+    slack::channel::{ChannelId, ChannelName},
+};
+use std::{collections::HashSet, sync::Mutex};
+use tracing::{info, warn};
+use url::Url;
+
+/// The runbook for the Heroku apps matching a pattern.
+pub struct Runbook {
+    pub pattern: String,
+    pub url: Url,
+}
+
+/// Runbooks in order of precedence, and where they've been bookmarked.
+#[derive(Default)]
+pub struct Runbooks {
+    runbooks: Vec<Runbook>,
+    bookmarked: Mutex<HashSet<(ChannelId, Url)>>,
+}
+
+impl Runbooks {
+    /// Parse runbooks from their environment variable representation,
+    /// ignoring invalid entries.
+    ///
+    /// ```
+    /// let xs = Runbooks::parse("api-*:https://wiki.example.com/api-runbook");
+    /// ```
+    pub fn parse(x: &str) -> Self {
+        let runbooks = x
+            .split(',')
+            .filter_map(|entry| {
+                // URLs contain colons of their own.
+                let (pattern, url) = entry.trim().split_once(':')?;
+
+                Some(Runbook {
+                    pattern: pattern.to_owned(),
+                    url: Url::parse(url).ok()?,
+                })
+            })
+            .collect();
+
+        Runbooks {
+            runbooks,
+            bookmarked: Mutex::new(HashSet::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runbooks.is_empty()
+    }
+
+    /// Find the runbook for an app, if any.
+    pub fn find(&self, app_name: &str) -> Option<&Url> {
+        self.runbooks
+            .iter()
+            .find(|x| matches_pattern(&x.pattern, app_name))
+            .map(|x| &x.url)
+    }
+
+    /// Mark a runbook as bookmarked in a channel, returning whether it wasn't
+    /// already.
+    fn mark(&self, channel: &ChannelId, url: &Url) -> bool {
+        self.bookmarked
+            .lock()
+            .unwrap()
+            .insert((channel.clone(), url.clone()))
+    }
+
+    /// Forget that a runbook was bookmarked, so that it's tried again.
+    fn unmark(&self, channel: &ChannelId, url: &Url) {
+        self.bookmarked
+            .lock()
+            .unwrap()
+            .remove(&(channel.clone(), url.clone()));
+    }
+}
+
+/// Best effort bookmark an app's runbook, if it has one, in a channel.
+pub async fn bookmark_runbook(deps: &Deps, channel: &ChannelName, app_name: &str) {
+    let Some(url) = deps.heroku_runbooks.find(app_name) else {
+        return;
+    };
+
+    let token = deps.slack_token.load_full();
+    let mut client = deps.slack_client.lock().await;
+
+    let channel_id = match client.get_channel_id(channel, &token).await {
+        Ok(x) => x,
+        Err(e) => {
+            warn!("Failed to bookmark runbook in {}: {}", channel, e);
+            return;
+        }
+    };
+
+    if !deps.heroku_runbooks.mark(&channel_id, url) {
+        return;
+    }
+
+    let title = format!("{} runbook", app_name);
+    match client
+        .ensure_bookmark(&channel_id, &title, url, &token)
+        .await
+    {
+        Ok(true) => info!("Bookmarked {} runbook in {}", app_name, channel),
+        Ok(false) => {}
+        Err(e) => {
+            warn!("Failed to bookmark runbook in {}: {}", channel, e);
+            deps.heroku_runbooks.unmark(&channel_id, url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xs = Runbooks::parse(
+            " api-*:https://wiki.example.com/api-runbook, invalid,web:nope, *:https://wiki.example.com/runbook",
+        );
+
+        assert_eq!(xs.runbooks.len(), 2);
+        assert_eq!(
+            xs.find("api-staging").map(Url::as_str),
+            Some("https://wiki.example.com/api-runbook")
+        );
+        assert_eq!(
+            xs.find("web").map(Url::as_str),
+            Some("https://wiki.example.com/runbook")
+        );
+        assert!(Runbooks::default().find("web").is_none());
+    }
+
+    #[test]
+    fn test_mark() {
+        let xs = Runbooks::default();
+        let channel = ChannelId("C123".into());
+        let url = Url::parse("https://wiki.example.com/runbook").unwrap();
+
+        assert!(xs.mark(&channel, &url));
+        assert!(!xs.mark(&channel, &url));
+        assert!(xs.mark(&ChannelId("C456".into()), &url));
+
+        xs.unmark(&channel, &url);
+        assert!(xs.mark(&channel, &url));
+    }
+}
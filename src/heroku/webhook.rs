@@ -9,162 +9,815 @@
 //! Events can be filtered by specifying Heroku entity types during webhook
 //! creation.
 //!
-//! Currently the only supported platform is [Slack][slack], which takes
+//! The principal platform is [Slack][slack], which takes
 //! an additional `channel` query param (as per
 //! [SlackPlatform][super::platform::slack::SlackPlatform]), for example
 //! `/api/v1/heroku/hook?platform=slack&channel=playground`. The message
-//! structure is fixed.
+//! structure is fixed. The channel may be templated with the app's name, for
+//! example `channel=deploys-{app}`, as per
+//! [expand_channel][super::platform::slack::expand_channel]. If the channel is
+//! omitted it's found by app name instead, as per [super::routing].
+//!
+//! Alternatively messages can be posted to a [Matrix][crate::matrix] room,
+//! which takes a `room` alias query param instead (as per
+//! [MatrixPlatform][super::platform::matrix::MatrixPlatform]), for example
+//! `/api/v1/heroku/hook?platform=matrix&room=%23deploys:example.org`,
+//! to a [Zulip][crate::zulip] stream with a `stream` query param (as per
+//! [ZulipPlatform][super::platform::zulip::ZulipPlatform]), for example
+//! `/api/v1/heroku/hook?platform=zulip&stream=deploys`, or written to the
+//! console with `platform=stdout`.
+//!
+//! Events can also be reported back to the [GitHub][crate::github] repository
+//! the app's deployed from with a `repo` query param (as per
+//! [GitHubPlatform][super::platform::github::GitHubPlatform]), for example
+//! `/api/v1/heroku/hook?platform=github&repo=unsplash/mercury`. Deploys mark
+//! the deployed commit with a successful status, and rollbacks additionally
+//! mark the commit rolled back from as failed, if we've seen it. Crashes open
+//! an issue, or comment on the one already open for the app. Config changes
+//! aren't reported.
+//!
+//! Similarly, critical events such as crashes can open an incident on the
+//! [Statuspage][crate::statuspage] page with `platform=statuspage`, optionally
+//! with a `component` ID (as per
+//! [StatuspagePlatform][super::platform::statuspage::StatuspagePlatform]).
+//! Further critical events update the incident, and the next deploy resolves
+//! it.
+//!
+//! Whilst our legacy Otto bot's being retired, events can be relayed to it in
+//! the formats it expects with `platform=otto`, as per [super::otto].
+//!
+//! Messages are grouped into threads by app if [crate::threading] is enabled.
+//! Crashes bookmark the app's runbook in their channel if it has one, as per
+//! [super::runbook].
+//!
+//! Deploys and rollbacks can optionally be enriched with a changelog from
+//! GitHub by supplying a `repo` query param (as per [HookOptions]), for example
+//! `/api/v1/heroku/hook?platform=slack&channel=playground&repo=unsplash/mercury`.
+//! This requires `$GITHUB_TOKEN`. The changelog is derived from the previous
+//! release we've seen for the app, so the first release after startup won't
+//! include one.
 
-use super::{dashboard::activity_page_url, Platform};
+use super::{
+    dashboard::activity_page_url,
+    description::{DescriptionKind, DescriptionMatch, DescriptionPatterns},
+    linear::triage_crash,
+    otto::{to_relay, OttoError},
+    payload::*,
+    routing::{route, Channel, Destination, Unrouted},
+    runbook::bookmark_runbook,
+    Platform,
+};
 use crate::{
+    bounded::BoundedMap,
+    delivery::{deliver_event, Delivery, Source},
+    event::{Event, EventKind},
+    github::{
+        compare::Changelog,
+        status::{CommitState, CommitStatus},
+        GitHubError, GitHubRepo,
+    },
+    locale::{self, tr},
+    matrix::{MatrixError, RoomAlias},
+    priority::Priority,
     router::Deps,
-    slack::{self, SlackError},
+    slack::{self, Severity, SlackError},
+    statuspage::{Outcome, StatuspageError},
+    zulip::ZulipError,
 };
-use regex::Regex;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use tracing::{info, warn};
+use unic_langid::LanguageIdentifier;
 
 /// Supported Heroku webhook events.
 #[derive(Debug, PartialEq, Eq)]
 pub enum HookEvent {
+    /// From the entity `api:release`.
+    Deploy { author: String, commit: String },
     /// From the entity `api:release`.
     Rollback { author: String, version: String },
     /// From the entity `api:release`.
     EnvVarsChange { author: String, raw_change: String },
-    /// From the entity `dyno` (NB *not* `api:dyno`).
-    DynoCrash { name: String, status_code: u8 },
+    /// From the entity `dyno` (NB *not* `api:dyno`), or from [super::poll],
+    /// which can't know the status code.
+    DynoCrash {
+        name: String,
+        status_code: Option<u8>,
+    },
+}
+
+impl HookEvent {
+    /// How severe the event is, independent of how it's rendered.
+    pub fn severity(&self) -> Severity {
+        match self {
+            HookEvent::Deploy { .. } => Severity::Success,
+            HookEvent::Rollback { .. } => Severity::Warning,
+            HookEvent::EnvVarsChange { .. } => Severity::Info,
+            HookEvent::DynoCrash { .. } => Severity::Critical,
+        }
+    }
 }
 
+/// Optional query params applicable to any [Platform].
+#[derive(Deserialize)]
+pub struct HookOptions {
+    /// The GitHub repository from which the app is deployed, enabling
+    /// changelogs for deploys and rollbacks.
+    pub repo: Option<GitHubRepo>,
+}
+
+/// A validated webhook awaiting forwarding.
+pub struct Job {
+    pub platform: Platform,
+    pub opts: HookOptions,
+    pub payload: HookPayload,
+}
+
+/// Maps Heroku app names to the commit of their most recent release, for at
+/// most [MAX_RELEASE_COMMITS] apps, forgetting those released least recently.
+pub type ReleaseCommitMap = BoundedMap<String, String>;
+
+pub const MAX_RELEASE_COMMITS: usize = 10_000;
+
 /// The result of attempting to forward a valid webhook.
 pub enum ForwardResult {
     IgnoredAction,
     UnsupportedEvent(String),
     Failure(ForwardFailure),
+    /// Delivery was deliberately skipped, for the given reason.
+    Suppressed(&'static str),
+    /// No channel was supplied, and none is routed for the given app.
+    Unroutable(String),
+    /// The named route supplied isn't configured.
+    UnknownRoute(String),
     Success,
 }
 
+impl ForwardResult {
+    /// Log the result of forwarding in the background, where there's nobody to
+    /// respond to, describing what was forwarded.
+    pub fn log(&self, what: &str) {
+        match self {
+            ForwardResult::Failure(ForwardFailure::ToSlack(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Failure(ForwardFailure::ToMatrix(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Failure(ForwardFailure::ToZulip(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Failure(ForwardFailure::ToGitHub(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Failure(ForwardFailure::ToStatuspage(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Failure(ForwardFailure::ToOtto(e)) => {
+                warn!("Failed to forward {}: {}", what, e)
+            }
+            ForwardResult::Unroutable(app) => {
+                warn!("No channel supplied or routed for app: {}", app)
+            }
+            ForwardResult::UnknownRoute(name) => warn!("No route named: {}", name),
+            ForwardResult::UnsupportedEvent(evt) => info!(
+                "Could not decode payload to a supported event, found: {}",
+                evt
+            ),
+            ForwardResult::Suppressed(_)
+            | ForwardResult::Success
+            | ForwardResult::IgnoredAction => {}
+        }
+    }
+}
+
 /// What went wrong during forwarding, specifically in communication with the
 /// onward platform.
+// Named for where forwarding failed, which reads naturally as `ToSlack` etc.
+#[allow(clippy::enum_variant_names)]
 pub enum ForwardFailure {
     ToSlack(SlackError),
+    ToMatrix(MatrixError),
+    ToZulip(ZulipError),
+    ToGitHub(GitHubError),
+    ToStatuspage(StatuspageError),
+    ToOtto(OttoError),
 }
 
 /// Validate, filter, and ultimately forward a webhook event to the given
 /// [Platform].
-pub async fn forward(deps: &Deps, plat: &Platform, payload: &HookPayload) -> ForwardResult {
+pub async fn forward(
+    deps: &Deps,
+    plat: &Platform,
+    opts: &HookOptions,
+    payload: &HookPayload,
+) -> ForwardResult {
+    // Tracked even for releases we don't send, so that the next changelog is
+    // complete.
+    let prev_commit = match payload {
+        HookPayload::Release(x) if x.action == ReleaseHookAction::Update => {
+            swap_release_commit(deps, x).await
+        }
+        _ => None,
+    };
+
+    let (evt, summary) = match decode_event(&deps.heroku_description_patterns, payload) {
+        Ok(x) => x,
+        Err(Undecoded::UnsupportedEvent(desc)) => return ForwardResult::UnsupportedEvent(desc),
+        Err(Undecoded::IgnoredAction(_) | Undecoded::NoCrash) => {
+            return ForwardResult::IgnoredAction
+        }
+    };
+
+    let changelog = match payload {
+        HookPayload::Release(x) => get_changelog(deps, opts, &evt, x, prev_commit.clone()).await,
+        HookPayload::Dyno(_) => None,
+    };
+
+    send(
+        deps,
+        plat,
+        &evt,
+        summary,
+        changelog.as_ref(),
+        prev_commit.as_deref(),
+        payload,
+    )
+    .await
+}
+
+/// Why a valid webhook isn't a supported [HookEvent], as per [decode_event].
+pub enum Undecoded<'a> {
+    /// Any release action besides the update, as we only want to send one
+    /// notification per release.
+    IgnoredAction(&'a ReleaseHookAction),
+    /// A dyno event other than a crash.
+    NoCrash,
+    /// A release whose description couldn't be decoded, as per
+    /// [decode_release_payload].
+    UnsupportedEvent(String),
+}
+
+/// Decode a valid webhook into the [HookEvent] it represents, along with its
+/// templated summary if any.
+pub fn decode_event<'a>(
+    patterns: &DescriptionPatterns,
+    payload: &'a HookPayload,
+) -> Result<(HookEvent, Option<String>), Undecoded<'a>> {
     match payload {
-        HookPayload::Release(x) => match x.action {
-            // We only want to send one notification, so we'll
-            // ignore anything other than the hopefully lone
-            // update action.
-            ReleaseHookAction::Other => ForwardResult::IgnoredAction,
-            ReleaseHookAction::Update => match decode_release_payload(x) {
-                Err(desc) => ForwardResult::UnsupportedEvent(desc),
-                Ok(evt) => send(deps, plat, &evt, payload).await,
-            },
+        HookPayload::Release(x) => match &x.action {
+            ReleaseHookAction::Update => {
+                decode_release_payload(patterns, x).map_err(Undecoded::UnsupportedEvent)
+            }
+            action => Err(Undecoded::IgnoredAction(action)),
         },
         HookPayload::Dyno(x) => match is_dyno_crash(x) {
-            None => ForwardResult::IgnoredAction,
-            Some(status_code) => {
-                send(
-                    deps,
-                    plat,
-                    &HookEvent::DynoCrash {
-                        name: x.data.name.to_owned(),
-                        status_code,
-                    },
-                    payload,
-                )
-                .await
-            }
+            Some(status_code) => Ok((
+                HookEvent::DynoCrash {
+                    name: x.data.name.to_owned(),
+                    status_code: Some(status_code),
+                },
+                None,
+            )),
+            None => Err(Undecoded::NoCrash),
         },
     }
 }
 
-/// Send a valid webhook event to the given [Platform].
-async fn send(
+/// Send a valid webhook event to wherever it's routed, as per [route],
+/// optionally overriding its summary. The commit of the app's previous release
+/// is only needed for rollbacks reported to GitHub and for Otto.
+pub(super) async fn send(
     deps: &Deps,
     plat: &Platform,
     event: &HookEvent,
+    summary: Option<String>,
+    changelog: Option<&Changelog>,
+    prev_commit: Option<&str>,
     payload: &HookPayload,
 ) -> ForwardResult {
     let app_name = &get_app_data(payload).name;
 
-    let title = match event {
-        HookEvent::Rollback { .. } => format!("🏳️ {}", app_name),
-        HookEvent::EnvVarsChange { .. } => format!("⚙️  {}", app_name),
-        HookEvent::DynoCrash { .. } => format!("☢️  {}", app_name),
+    let dest = match route(deps, plat, app_name) {
+        Ok(x) => x,
+        Err(Unrouted::UnknownRoute(name)) => return ForwardResult::UnknownRoute(name),
+        Err(Unrouted::NoRoute) => return ForwardResult::Unroutable(app_name.to_owned()),
     };
 
-    let desc = match event {
-        HookEvent::Rollback { version, author } => format!("Rollback to {} ({})", version, author),
-        HookEvent::EnvVarsChange { raw_change, author } => {
-            format!("Environment variables changed: {} ({})", raw_change, author)
+    // Rooms, streams, and repositories have no configured locale.
+    let event_in = |locale| to_event(event, summary.clone(), changelog, payload, locale);
+
+    match dest {
+        Destination::Slack(x) => {
+            let evt = event_in(deps.channel_locales.get(&x.name.0));
+            let msg = to_message(deps, &evt, x, app_name);
+
+            fan_out(deps, &evt);
+
+            let res = deliver_event(deps, &evt, &msg).await;
+
+            if let (Ok(Delivery::Sent), HookEvent::DynoCrash { .. }) = (&res, event) {
+                bookmark_runbook(deps, &msg.channel, app_name).await;
+                triage_crash(deps, &msg.channel, app_name, &evt).await;
+            }
+
+            match res {
+                Err(e) => ForwardResult::Failure(ForwardFailure::ToSlack(e)),
+                Ok(Delivery::Sent) => ForwardResult::Success,
+                Ok(Delivery::Suppressed(reason)) => ForwardResult::Suppressed(reason),
+            }
+        }
+        Destination::Stdout(x) => {
+            let evt = event_in(deps.channel_locales.get(&x.name.0));
+            let msg = to_message(deps, &evt, x, app_name);
+
+            deps.console.write(&msg, Source::Heroku, Some(app_name));
+
+            ForwardResult::Success
         }
-        HookEvent::DynoCrash { name, status_code } => {
-            format!("Dyno {} crashed with status code {}", name, status_code)
+        Destination::Matrix(x) => send_matrix(deps, &x.room, &event_in(&locale::DEFAULT)).await,
+        Destination::Zulip(x) => send_zulip(deps, &x.stream, &event_in(&locale::DEFAULT)).await,
+        Destination::GitHub(x) => {
+            let evt = event_in(&locale::DEFAULT);
+
+            send_github(deps, &x.repo, event, &evt, prev_commit, payload).await
+        }
+        Destination::Statuspage(x) => {
+            let evt = event_in(&locale::DEFAULT);
+
+            send_statuspage(deps, x.component.as_deref(), app_name, &evt).await
+        }
+        Destination::Otto => {
+            let evt = event_in(&locale::DEFAULT);
+
+            send_otto(deps, event, &evt, prev_commit, changelog, payload).await
         }
+    }
+}
+
+/// Render an event for the channel it's routed to, as per [route].
+pub(super) fn to_message(
+    deps: &Deps,
+    evt: &Event,
+    channel: Channel,
+    app_name: &str,
+) -> slack::Message {
+    let emoji = deps.heroku_emoji.find(evt.kind, app_name);
+
+    slack::Message::from_event(evt, channel.name, channel.cc, emoji)
+}
+
+/// Post an event to a Matrix room, unless in read-only mode.
+async fn send_matrix(deps: &Deps, room: &RoomAlias, evt: &Event) -> ForwardResult {
+    let Some(x) = &deps.matrix else {
+        return ForwardResult::Failure(ForwardFailure::ToMatrix(MatrixError::Unconfigured));
+    };
+
+    fan_out(deps, evt);
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not posting to {}", room);
+        return ForwardResult::Suppressed("read-only");
+    }
+
+    match x.send(room, evt).await {
+        Ok(()) => ForwardResult::Success,
+        Err(e) => ForwardResult::Failure(ForwardFailure::ToMatrix(e)),
+    }
+}
+
+/// Post an event to a Zulip stream, unless in read-only mode.
+async fn send_zulip(deps: &Deps, stream: &str, evt: &Event) -> ForwardResult {
+    let Some(x) = &deps.zulip else {
+        return ForwardResult::Failure(ForwardFailure::ToZulip(ZulipError::Unconfigured));
+    };
+
+    fan_out(deps, evt);
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not posting to {}", stream);
+        return ForwardResult::Suppressed("read-only");
+    }
+
+    match x.send(stream, evt).await {
+        Ok(()) => ForwardResult::Success,
+        Err(e) => ForwardResult::Failure(ForwardFailure::ToZulip(e)),
+    }
+}
+
+/// Report an event to a GitHub repository, unless in read-only mode.
+async fn send_github(
+    deps: &Deps,
+    repo: &GitHubRepo,
+    event: &HookEvent,
+    evt: &Event,
+    prev_commit: Option<&str>,
+    payload: &HookPayload,
+) -> ForwardResult {
+    let Some(token) = &deps.github_token else {
+        return ForwardResult::Failure(ForwardFailure::ToGitHub(GitHubError::Unconfigured));
     };
 
-    match plat {
-        Platform::Slack(x) => {
-            let res = deps
-                .slack_client
-                .lock()
+    fan_out(deps, evt);
+
+    if let HookEvent::EnvVarsChange { .. } = event {
+        return ForwardResult::IgnoredAction;
+    }
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not reporting to {}", repo);
+        return ForwardResult::Suppressed("read-only");
+    }
+
+    let app_name = &get_app_data(payload).name;
+    let client = &deps.github_client;
+
+    let res = match event {
+        HookEvent::EnvVarsChange { .. } => unreachable!(),
+        HookEvent::DynoCrash { .. } => {
+            let title = format!(
+                "{}: {}",
+                app_name,
+                evt.summary.lines().next().unwrap_or_default()
+            );
+            let body = match evt.links.first() {
+                Some(x) => format!("{}\n\n[View]({})", evt.summary, x),
+                None => evt.summary.clone(),
+            };
+            let label = format!("mercury-crash-{}", app_name);
+
+            client
+                .file_issue(repo, &label, &title, &body, token)
                 .await
-                .post_message(
-                    &slack::Message {
-                        channel: x.channel.clone(),
-                        title,
-                        desc,
-                        link: Some(activity_page_url(app_name)),
-                        cc: None,
-                        avatar: None,
-                    },
-                    &deps.slack_token,
+                .map(|_| ())
+        }
+        HookEvent::Deploy { .. } | HookEvent::Rollback { .. } => {
+            let commit = match payload {
+                HookPayload::Release(x) => x.data.slug.as_ref().map(|x| x.commit.as_str()),
+                HookPayload::Dyno(_) => None,
+            };
+            // Releases without a slug have nothing to mark.
+            let Some(commit) = commit else {
+                return ForwardResult::IgnoredAction;
+            };
+
+            let status = |state, description| CommitStatus {
+                state,
+                context: format!("heroku/{}", app_name),
+                description,
+                target_url: evt.links.first().map(|x| x.to_string()),
+            };
+
+            let mut res = client
+                .set_status(
+                    repo,
+                    commit,
+                    status(CommitState::Success, format!("Deployed to {}", app_name)),
+                    token,
                 )
                 .await;
 
-            match res {
-                Err(e) => ForwardResult::Failure(ForwardFailure::ToSlack(e)),
-                Ok(_) => ForwardResult::Success,
+            if let (HookEvent::Rollback { .. }, Some(prev), Ok(())) = (event, prev_commit, &res) {
+                if prev != commit {
+                    res = client
+                        .set_status(
+                            repo,
+                            prev,
+                            status(CommitState::Failure, format!("Rolled back on {}", app_name)),
+                            token,
+                        )
+                        .await;
+                }
             }
+
+            res
         }
+    };
+
+    match res {
+        Ok(()) => ForwardResult::Success,
+        Err(e) => ForwardResult::Failure(ForwardFailure::ToGitHub(e)),
     }
 }
 
-/// Attempt to decode a valid webhook payload into a supported [HookEvent].
-/// Returns the description that failed decoding upon failure.
-///
-/// There's no indication that these descriptions are stable on Heroku's side.
-pub fn decode_release_payload(payload: &ReleaseHookPayload) -> Result<HookEvent, String> {
-    decode_rollback(payload)
-        .or_else(|| decode_env_vars_change(payload))
-        .ok_or_else(|| payload.data.description.clone())
+/// Open or update an incident on Statuspage for a critical event, or resolve
+/// it upon a successful one, unless in read-only mode.
+async fn send_statuspage(
+    deps: &Deps,
+    component: Option<&str>,
+    app_name: &str,
+    evt: &Event,
+) -> ForwardResult {
+    let Some(x) = &deps.statuspage else {
+        return ForwardResult::Failure(ForwardFailure::ToStatuspage(StatuspageError::Unconfigured));
+    };
+
+    fan_out(deps, evt);
+
+    let open = match evt.severity {
+        Some(Severity::Critical) => true,
+        Some(Severity::Success) => false,
+        _ => return ForwardResult::IgnoredAction,
+    };
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not updating Statuspage");
+        return ForwardResult::Suppressed("read-only");
+    }
+
+    let res = match open {
+        true => x.open(app_name, component, &evt.summary).await,
+        false => x.resolve(app_name, component, &evt.summary).await,
+    };
+
+    match res {
+        Ok(Outcome::Unchanged) => ForwardResult::IgnoredAction,
+        Ok(_) => ForwardResult::Success,
+        Err(e) => ForwardResult::Failure(ForwardFailure::ToStatuspage(e)),
+    }
 }
 
-/// Attempt to decode a rollback webhook event from a payload.
-fn decode_rollback(payload: &ReleaseHookPayload) -> Option<HookEvent> {
-    Regex::new(r"^Rollback to (?P<version>.+)$")
-        .ok()
-        .and_then(|re| re.captures(&payload.data.description))
-        .and_then(|cs| cs.name("version"))
-        .map(|m| HookEvent::Rollback {
-            author: payload.data.user.email.to_owned(),
-            version: m.as_str().to_owned(),
-        })
+/// Relay an event to Otto in its legacy format, unless in read-only mode.
+async fn send_otto(
+    deps: &Deps,
+    event: &HookEvent,
+    evt: &Event,
+    prev_commit: Option<&str>,
+    changelog: Option<&Changelog>,
+    payload: &HookPayload,
+) -> ForwardResult {
+    let Some(x) = &deps.heroku_otto else {
+        return ForwardResult::Failure(ForwardFailure::ToOtto(OttoError::Unconfigured));
+    };
+
+    fan_out(deps, evt);
+
+    let Some(relay) = to_relay(event, payload, prev_commit, changelog) else {
+        return ForwardResult::IgnoredAction;
+    };
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not relaying to Otto");
+        return ForwardResult::Suppressed("read-only");
+    }
+
+    match x.relay(&relay).await {
+        Ok(()) => ForwardResult::Success,
+        Err(e) => ForwardResult::Failure(ForwardFailure::ToOtto(e)),
+    }
+}
+
+/// Push an event in the background to whoever it's routed to, if anyone, file
+/// it in Jira, publish it to EventBridge, mark it in Grafana, Datadog, and
+/// Honeycomb, log it to a calendar, and write it to the event log if
+/// configured. See [super::push], [super::jira], [crate::eventbridge],
+/// [crate::grafana], [crate::datadog], [crate::honeycomb], [super::calendar],
+/// and [crate::eventlog].
+fn fan_out(deps: &Deps, evt: &Event) {
+    // A record rather than a notification, so written even in read-only mode.
+    if let Some(x) = &deps.event_log {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.write(&evt).await });
+    }
+
+    if deps.heroku_push.is_none()
+        && deps.heroku_jira.is_none()
+        && deps.eventbridge.is_none()
+        && deps.grafana.is_none()
+        && deps.datadog.is_none()
+        && deps.honeycomb.is_none()
+        && deps.heroku_calendar.is_none()
+    {
+        return;
+    }
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode enabled, not pushing, filing, publishing, or marking");
+        return;
+    }
+
+    if let Some(x) = &deps.heroku_push {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.push(&evt).await });
+    }
+
+    if let Some(x) = &deps.heroku_jira {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.file(&evt).await });
+    }
+
+    if let Some(x) = &deps.eventbridge {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.publish(&evt).await });
+    }
+
+    if let Some(x) = &deps.grafana {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.annotate(&evt).await });
+    }
+
+    if let Some(x) = &deps.datadog {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.record(&evt).await });
+    }
+
+    if let Some(x) = &deps.honeycomb {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.record(&evt).await });
+    }
+
+    if let Some(x) = &deps.heroku_calendar {
+        let x = x.clone();
+        let evt = evt.clone();
+        tokio::spawn(async move { x.log(&evt).await });
+    }
+}
+
+/// Normalize a webhook event, irrespective of where it's headed besides its
+/// locale. The summary is derived from the event unless supplied.
+pub(super) fn to_event(
+    event: &HookEvent,
+    summary: Option<String>,
+    changelog: Option<&Changelog>,
+    payload: &HookPayload,
+    locale: &LanguageIdentifier,
+) -> Event {
+    let app_name = &get_app_data(payload).name;
+
+    let kind = match event {
+        HookEvent::Deploy { .. } => EventKind::Deploy,
+        HookEvent::Rollback { .. } => EventKind::Rollback,
+        HookEvent::EnvVarsChange { .. } => EventKind::ConfigChange,
+        HookEvent::DynoCrash { .. } => EventKind::Crash,
+    };
+
+    let summary = summary.unwrap_or_else(|| match event {
+        HookEvent::Deploy { commit, author } => tr(
+            locale,
+            "heroku-deploy",
+            &[("commit", commit.into()), ("author", author.into())],
+        ),
+        HookEvent::Rollback { version, author } => tr(
+            locale,
+            "heroku-rollback",
+            &[("version", version.into()), ("author", author.into())],
+        ),
+        HookEvent::EnvVarsChange { raw_change, author } => tr(
+            locale,
+            "heroku-config-change",
+            &[("change", raw_change.into()), ("author", author.into())],
+        ),
+        HookEvent::DynoCrash {
+            name,
+            status_code: Some(x),
+        } => tr(
+            locale,
+            "heroku-dyno-crash",
+            &[("name", name.into()), ("code", x.to_string().into())],
+        ),
+        HookEvent::DynoCrash {
+            name,
+            status_code: None,
+        } => tr(
+            locale,
+            "heroku-dyno-crash-unknown",
+            &[("name", name.into())],
+        ),
+    });
+
+    let summary = match changelog {
+        None => summary,
+        Some(x) => format!("{}\n{}", summary, fmt_changelog(event, x, locale)),
+    };
+
+    Event {
+        source: Source::Heroku,
+        kind,
+        app: Some(app_name.to_owned()),
+        version: get_version(payload),
+        severity: Some(event.severity()),
+        occurred_at: get_created_at(payload),
+        title: app_name.to_owned(),
+        summary,
+        fields: Vec::new(),
+        links: vec![activity_page_url(app_name)],
+    }
+}
+
+/// Format a [Changelog] as a list of commit subjects, noting how many more
+/// commits there are beyond those listed.
+fn fmt_changelog(event: &HookEvent, changelog: &Changelog, locale: &LanguageIdentifier) -> String {
+    let key = match event {
+        HookEvent::Rollback { .. } => "changelog-reverts",
+        _ => "changelog-includes",
+    };
+
+    let mut xs = vec![tr(
+        locale,
+        key,
+        &[("count", changelog.total_commits.into())],
+    )];
+
+    xs.extend(changelog.subjects.iter().map(|x| format!("• {}", x)));
+
+    let remaining = changelog
+        .total_commits
+        .saturating_sub(changelog.subjects.len());
+    if remaining > 0 {
+        xs.push(format!(
+            "• {}",
+            tr(locale, "changelog-more", &[("count", remaining.into())])
+        ));
+    }
+
+    xs.join("\n")
+}
+
+/// Record the commit of a release as the latest for its app, returning the
+/// commit of the release before it if we've seen one.
+async fn swap_release_commit(deps: &Deps, payload: &ReleaseHookPayload) -> Option<String> {
+    let commit = payload.data.slug.as_ref()?.commit.to_owned();
+
+    deps.release_commits
+        .lock()
+        .await
+        .insert(payload.data.app.name.to_owned(), commit)
 }
 
-/// Attempt to decode an environment variable-related webhook event from a
-/// payload.
-fn decode_env_vars_change(payload: &ReleaseHookPayload) -> Option<HookEvent> {
-    Regex::new(r"^(?P<change>.+) config vars$")
+/// Fetch a changelog from GitHub for deploys and rollbacks, provided GitHub is
+/// configured and we know of a previous release to compare against.
+///
+/// Enrichment is best effort; failures are logged and otherwise ignored.
+async fn get_changelog(
+    deps: &Deps,
+    opts: &HookOptions,
+    event: &HookEvent,
+    payload: &ReleaseHookPayload,
+    prev_commit: Option<String>,
+) -> Option<Changelog> {
+    let token = deps.github_token.as_ref()?;
+    let repo = opts.repo.as_ref()?;
+    let prev = prev_commit?;
+    let curr = &payload.data.slug.as_ref()?.commit;
+
+    let (base, head) = match event {
+        HookEvent::Deploy { .. } => (&prev, curr),
+        // Rollbacks go back in time, so to see what's being reverted we need
+        // to compare in the opposite direction.
+        HookEvent::Rollback { .. } => (curr, &prev),
+        _ => return None,
+    };
+
+    deps.github_client
+        .compare(repo, base, head, token)
+        .await
+        .map_err(|e| warn!("Failed to fetch changelog: {}", e))
         .ok()
-        .and_then(|re| re.captures(&payload.data.description))
-        .and_then(|cs| cs.name("change"))
-        .map(|m| HookEvent::EnvVarsChange {
-            author: payload.data.user.email.to_owned(),
-            raw_change: m.as_str().to_owned(),
-        })
+}
+
+/// Attempt to decode a valid webhook payload into a supported [HookEvent] by
+/// its description, as per [super::description], along with its templated
+/// summary if any. Returns the description that failed decoding upon failure.
+pub fn decode_release_payload(
+    patterns: &DescriptionPatterns,
+    payload: &ReleaseHookPayload,
+) -> Result<(HookEvent, Option<String>), String> {
+    let desc = &payload.data.description;
+    let author = payload.data.user.email.to_owned();
+
+    let Some(DescriptionMatch {
+        kind,
+        value,
+        summary,
+    }) = patterns.find(desc, &author)
+    else {
+        return Err(desc.clone());
+    };
+
+    let event = match kind {
+        DescriptionKind::Deploy => HookEvent::Deploy {
+            author,
+            commit: value,
+        },
+        DescriptionKind::Rollback => HookEvent::Rollback {
+            author,
+            version: value,
+        },
+        DescriptionKind::EnvVarsChange => HookEvent::EnvVarsChange {
+            author,
+            raw_change: value,
+        },
+    };
+
+    Ok((event, summary))
 }
 
 /// Determines if a dyno event payload corresponds to a relevant crash, and if
@@ -172,7 +825,7 @@ fn decode_env_vars_change(payload: &ReleaseHookPayload) -> Option<HookEvent> {
 ///
 /// This logic is copied from Otto:
 /// <https://github.com/unsplash/otto/blob/38c0fc5cf9a0ea5f1443a2fa5f45c0d837ba83a3/app/routes/hooks/monitor.rb#L17>
-fn is_dyno_crash(payload: &DynoHookPayload) -> Option<u8> {
+pub(super) fn is_dyno_crash(payload: &DynoHookPayload) -> Option<u8> {
     let DynoHookData {
         typ,
         state,
@@ -180,401 +833,92 @@ fn is_dyno_crash(payload: &DynoHookPayload) -> Option<u8> {
         ..
     } = &payload.data;
 
-    exit_status.filter(|code| typ != "run" && state == "crashed" && code > &0)
-}
-
-/// The anticipated payload supplied by Heroku in webhook requests.
-///
-/// This isn't very well documented. An example request is provided here:
-///
-/// <https://devcenter.heroku.com/articles/app-webhooks#receiving-webhooks>
-///
-/// Real payloads from a given Heroku app's webhooks can be found here:
-///
-/// <https://dashboard.heroku.com/apps/HEROKU_APP/webhooks/>
-#[derive(Debug, PartialEq, Deserialize)]
-#[serde(tag = "resource")]
-pub enum HookPayload {
-    #[serde(rename = "release")]
-    Release(ReleaseHookPayload),
-    #[serde(rename = "dyno")]
-    Dyno(DynoHookPayload),
-}
-
-/// The payload supplied by Heroku for the `api:release` entity type.
-#[derive(Debug, PartialEq, Deserialize)]
-pub struct ReleaseHookPayload {
-    data: ReleaseHookData,
-    pub action: ReleaseHookAction,
-}
-
-/// The payload supplied by Heroku for the `dyno` entity type.
-#[derive(Debug, PartialEq, Deserialize)]
-pub struct DynoHookPayload {
-    data: DynoHookData,
-}
-
-/// The action within an `api:release` webhook event lifecycle.
-///
-/// Multiple payloads can be sent for the same wider event, for example "create"
-/// followed by "update".
-///
-/// <https://help.heroku.com/JP3QR5I5/why-am-i-receiving-2-web-hook-events-for-a-single-release>
-#[derive(Debug, PartialEq, Deserialize)]
-pub enum ReleaseHookAction {
-    #[serde(rename = "update")]
-    Update,
-    #[serde(other)]
-    Other,
-}
-
-/// General information about an `api:release` entity type.
-#[derive(Debug, PartialEq, Deserialize)]
-struct ReleaseHookData {
-    app: AppData,
-    description: String,
-    user: UserData,
+    exit_status.filter(|code| typ != "run" && *state == DynoState::Crashed && code > &0)
 }
 
-/// General information about an `dyno` entity type.
-#[derive(Debug, PartialEq, Deserialize)]
-struct DynoHookData {
-    app: AppData,
-    name: String,
-    #[serde(rename = "type")]
-    typ: String,
-    state: String,
-    /// We need this for `DynoCrash`, however for other types of dyno events it
-    /// can be absent or `null`, and we should still serialise those and return
-    /// 200.
-    exit_status: Option<u8>,
+/// The priority with which a payload's message would be delivered, as far as
+/// can be told before decoding it. See [crate::priority].
+pub(super) fn get_priority(payload: &HookPayload) -> Priority {
+    match payload {
+        HookPayload::Dyno(x) if is_dyno_crash(x).is_some() => Priority::Critical,
+        _ => Priority::Normal,
+    }
 }
 
-/// Common metadata about the app for which a webhook event fired.
-#[derive(Debug, PartialEq, Deserialize)]
-struct AppData {
-    name: String,
+pub(crate) fn get_app_data(payload: &HookPayload) -> &AppData {
+    match payload {
+        HookPayload::Release(x) => &x.data.app,
+        HookPayload::Dyno(x) => &x.data.app,
+    }
 }
 
-/// Information about the user who enacted the change.
-#[derive(Debug, PartialEq, Deserialize)]
-struct UserData {
-    email: String,
+/// The release's version, for example `v42`. Dynos don't say which release
+/// they're running.
+fn get_version(payload: &HookPayload) -> Option<String> {
+    match payload {
+        HookPayload::Release(x) => x.data.version.map(|x| format!("v{}", x)),
+        HookPayload::Dyno(_) => None,
+    }
 }
 
-fn get_app_data(payload: &HookPayload) -> &AppData {
+/// When the webhook event occurred, if Heroku told us.
+fn get_created_at(payload: &HookPayload) -> Option<DateTime<Utc>> {
     match payload {
-        HookPayload::Release(x) => &x.data.app,
-        HookPayload::Dyno(x) => &x.data.app,
+        HookPayload::Release(x) => x.created_at,
+        HookPayload::Dyno(x) => x.created_at,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::locale::DEFAULT;
 
-    mod deserialization {
+    mod decode_payload {
         use super::*;
 
-        #[test]
-        fn test_root_payload_release() {
-            let real_redacted_example = r#"{
-                "id": "66a9e685-e1f3-4f9f-9177-a024fb5f0902",
-                "data": {
-                    "id": "38821f7c-e1a1-41d9-a34b-c41e2fa6d82d",
-                    "app": {
-                        "id": "59d151db-c38e-4e9c-a854-faead7e8d6cc",
-                        "name": "my-app",
-                        "process_tier": "production"
-                    },
-                    "slug": {
-                        "id": "507af0a6-a83b-4a16-9a9f-bf55b5864848",
-                        "commit": "69eec518969cc409e116940aa5304ab6ab237a4d",
-                        "commit_description": ""
-                    },
-                    "user": {
-                        "id": "71def50e-da83-453a-bba3-46b4e26911b0",
-                        "email": "hodor@unsplash.com"
-                    },
-                    "stack": "heroku-20",
-                    "status": "succeeded",
-                    "current": true,
-                    "pstable": {
-                        "my-process": {
-                            "slug": {
-                                "id": "fefa649a-845f-4c35-ad9a-2819633b4884"
-                            },
-                            "command": "/bin/cowsay moo"
-                        }
-                    },
-                    "version": 6644,
-                    "created_at": "2023-08-03T10:00:30Z",
-                    "updated_at": "2023-08-03T10:00:30Z",
-                    "description": "Deploy 69eec518",
-                    "addon_plan_names": [],
-                    "output_stream_url": null
-                },
-                "actor": {
-                    "id": "71def50e-da83-453a-bba3-46b4e26911b0",
-                    "email": "hello@example.com"
-                },
-                "action": "update",
-                "version": "application/vnd.heroku+json; version=3",
-                "resource": "release",
-                "sequence": null,
-                "created_at": "2023-08-03T10:00:30.693808Z",
-                "updated_at": "2023-08-03T10:00:30.693817Z",
-                "published_at": "2023-08-03T10:00:31Z",
-                "previous_data": {},
-                "webhook_metadata": {
-                    "attempt": {
-                        "id": "6ddac576-005d-456c-8153-8fba3f5702de"
-                    },
-                    "delivery": {
-                        "id": "ecb8532f-3b9b-4f8a-8eee-14296e0a6784"
-                    },
-                    "event": {
-                        "id": "66a9e685-e1f3-4f9f-9177-a024fb5f0902",
-                        "include": "api:release"
-                    },
-                    "webhook": {
-                        "id": "af83d062-fdfe-4fc0-88ad-b91bf58f0656"
-                    }
-                }
-            }"#;
+        fn decode(payload: &ReleaseHookPayload) -> Result<HookEvent, String> {
+            decode_release_payload(&DescriptionPatterns::default(), payload).map(|(x, _)| x)
+        }
 
-            let expected = HookPayload::Release(ReleaseHookPayload {
+        fn payload_from_desc<T: ToString>(desc: T) -> ReleaseHookPayload {
+            ReleaseHookPayload {
                 data: ReleaseHookData {
                     app: AppData {
-                        name: "my-app".to_string(),
+                        name: "any".to_string(),
                     },
-                    description: "Deploy 69eec518".to_string(),
+                    description: desc.to_string(),
                     user: UserData {
                         email: "hodor@unsplash.com".to_string(),
                     },
+                    slug: None,
+                    version: None,
                 },
                 action: ReleaseHookAction::Update,
-            });
-
-            assert_eq!(
-                expected,
-                serde_json::from_str(real_redacted_example).unwrap()
-            );
+                created_at: None,
+            }
         }
 
         #[test]
-        fn test_root_payload_dyno() {
-            let real_redacted_example = r#"{
-                "id": "292a769d-53d8-4edd-ace4-017a967653e1",
-                "created_at": "2023-08-03T14:19:07Z",
-                "data": {
-                    "id": "ab9fece7-41cc-4f22-8b73-de8c1a7a52b5",
-                    "app": {
-                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
-                        "name": "my-app"
-                    },
-                    "release": {
-                        "id": "3a7a5c18-b1ac-4830-9efb-5b68551e85f0",
-                        "version": 7634
-                    },
-                    "command": "/bin/cowsay moo",
-                    "size": "Standard-1X",
-                    "exit_status": 137,
-                    "management": "run:detached",
-                    "state": "crashed",
-                    "type": "scheduler",
-                    "name": "scheduler.8375"
-                },
-                "actor": {
-                  "id": "1030c06a-bcbe-4738-9134-89af5c717fb1",
-                  "email": "noreply+webhooks@heroku.com"
-                },
-                "previous_data": {},
-                "published_at": null,
-                "resource": "dyno",
-                "action": "destroy",
-                "version": "application/vnd.heroku+json; version=3"
-            }"#;
-
-            let expected = HookPayload::Dyno(DynoHookPayload {
-                data: DynoHookData {
-                    app: AppData {
-                        name: "my-app".to_string(),
-                    },
-                    name: "scheduler.8375".to_string(),
-                    typ: "scheduler".to_string(),
-                    state: "crashed".to_string(),
-                    exit_status: Some(137),
-                },
-            });
-
+        fn test_deploy() {
             assert_eq!(
-                expected,
-                serde_json::from_str(real_redacted_example).unwrap()
+                decode(&payload_from_desc("Deploy 69eec518")),
+                Ok(HookEvent::Deploy {
+                    author: "hodor@unsplash.com".to_string(),
+                    commit: "69eec518".to_string()
+                }),
             );
-        }
-
-        #[test]
-        fn test_root_payload_dyno_no_status_code() {
-            let real_redacted_example = r#"{
-                "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
-                "data": {
-                    "id": "d0eb9c96-0529-4f9d-bb4c-cc3382a55180",
-                    "app": {
-                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
-                        "name": "my-app"
-                    },
-                    "name": "scheduler.3540",
-                    "size": "Standard-1X",
-                    "type": "scheduler",
-                    "state": "starting",
-                    "command": "/bin/cowsay moo",
-                    "release": {
-                        "id": "9e67f453-d990-4c5f-89d3-c9ca2a70830f",
-                        "version": 7636
-                    },
-                    "attach_url": null,
-                    "created_at": "2023-08-03T17:40:49Z",
-                    "updated_at": "2023-08-03T17:40:49Z"
-                },
-                "actor": {
-                    "id": "6a65fcb0-507d-45ae-85d9-9bb29fe8d234",
-                    "email": "scheduler@addons.heroku.com"
-                },
-                "action": "create",
-                "version": "application/vnd.heroku+json; version=3",
-                "resource": "dyno",
-                "sequence": null,
-                "created_at": "2023-08-03T17:40:49.504132Z",
-                "updated_at": "2023-08-03T17:40:49.504139Z",
-                "published_at": "2023-08-03T17:40:50Z",
-                "previous_data": {},
-                "webhook_metadata": {
-                    "attempt": {
-                        "id": "9c522e4b-46aa-4233-9831-412a69c5357b"
-                    },
-                    "delivery": {
-                        "id": "4505f0a6-9397-4195-8640-33159b48cfb7"
-                    },
-                    "event": {
-                        "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
-                        "include": "api:dyno"
-                    },
-                    "webhook": {
-                        "id": "f7491c4b-2212-46d5-826f-064489daf9c4"
-                    }
-                }
-            }"#;
-
-            let expected = HookPayload::Dyno(DynoHookPayload {
-                data: DynoHookData {
-                    app: AppData {
-                        name: "my-app".to_string(),
-                    },
-                    name: "scheduler.3540".to_string(),
-                    typ: "scheduler".to_string(),
-                    state: "starting".to_string(),
-                    exit_status: None,
-                },
-            });
 
             assert_eq!(
-                expected,
-                serde_json::from_str(real_redacted_example).unwrap()
+                decode(&payload_from_desc("Deploy something else")),
+                Err("Deploy something else".to_string()),
             );
         }
 
-        #[test]
-        fn test_root_payload_dyno_null_status_code() {
-            let synthetic_example = r#"{
-                "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
-                "data": {
-                    "id": "d0eb9c96-0529-4f9d-bb4c-cc3382a55180",
-                    "app": {
-                        "id": "b3e4c9d6-3d05-4f2d-98d1-458c358269df",
-                        "name": "my-app"
-                    },
-                    "name": "scheduler.3540",
-                    "size": "Standard-1X",
-                    "type": "scheduler",
-                    "state": "starting",
-                    "exit_status": null,
-                    "command": "/bin/cowsay moo",
-                    "release": {
-                        "id": "9e67f453-d990-4c5f-89d3-c9ca2a70830f",
-                        "version": 7636
-                    },
-                    "attach_url": null,
-                    "created_at": "2023-08-03T17:40:49Z",
-                    "updated_at": "2023-08-03T17:40:49Z"
-                },
-                "actor": {
-                    "id": "6a65fcb0-507d-45ae-85d9-9bb29fe8d234",
-                    "email": "scheduler@addons.heroku.com"
-                },
-                "action": "create",
-                "version": "application/vnd.heroku+json; version=3",
-                "resource": "dyno",
-                "sequence": null,
-                "created_at": "2023-08-03T17:40:49.504132Z",
-                "updated_at": "2023-08-03T17:40:49.504139Z",
-                "published_at": "2023-08-03T17:40:50Z",
-                "previous_data": {},
-                "webhook_metadata": {
-                    "attempt": {
-                        "id": "9c522e4b-46aa-4233-9831-412a69c5357b"
-                    },
-                    "delivery": {
-                        "id": "4505f0a6-9397-4195-8640-33159b48cfb7"
-                    },
-                    "event": {
-                        "id": "5c930235-d947-4a8d-aefe-2692604d0c9a",
-                        "include": "api:dyno"
-                    },
-                    "webhook": {
-                        "id": "f7491c4b-2212-46d5-826f-064489daf9c4"
-                    }
-                }
-            }"#;
-
-            let expected = HookPayload::Dyno(DynoHookPayload {
-                data: DynoHookData {
-                    app: AppData {
-                        name: "my-app".to_string(),
-                    },
-                    name: "scheduler.3540".to_string(),
-                    typ: "scheduler".to_string(),
-                    state: "starting".to_string(),
-                    exit_status: None,
-                },
-            });
-
-            assert_eq!(expected, serde_json::from_str(synthetic_example).unwrap());
-        }
-    }
-
-    mod decode_payload {
-        use super::*;
-
-        fn payload_from_desc<T: ToString>(desc: T) -> ReleaseHookPayload {
-            ReleaseHookPayload {
-                data: ReleaseHookData {
-                    app: AppData {
-                        name: "any".to_string(),
-                    },
-                    description: desc.to_string(),
-                    user: UserData {
-                        email: "hodor@unsplash.com".to_string(),
-                    },
-                },
-                action: ReleaseHookAction::Update,
-            }
-        }
-
         #[test]
         fn test_rollback() {
             assert_eq!(
-                decode_release_payload(&payload_from_desc("Rollback to v1234")),
+                decode(&payload_from_desc("Rollback to v1234")),
                 Ok(HookEvent::Rollback {
                     author: "hodor@unsplash.com".to_string(),
                     version: "v1234".to_string()
@@ -582,7 +926,7 @@ mod tests {
             );
 
             assert_eq!(
-                decode_release_payload(&payload_from_desc("Rollback to some new format")),
+                decode(&payload_from_desc("Rollback to some new format")),
                 Ok(HookEvent::Rollback {
                     author: "hodor@unsplash.com".to_string(),
                     version: "some new format".to_string()
@@ -590,15 +934,45 @@ mod tests {
             );
 
             assert_eq!(
-                decode_release_payload(&payload_from_desc("rolled back to v1234")),
+                decode(&payload_from_desc("rolled back to v1234")),
                 Err("rolled back to v1234".to_string()),
             );
         }
 
+        #[test]
+        fn test_configured() {
+            let patterns = DescriptionPatterns::parse(
+                "rollback:^Reverted to (?P<version>v\\d+)$ => {author} reverted to {version}",
+            )
+            .unwrap();
+
+            assert_eq!(
+                decode_release_payload(&patterns, &payload_from_desc("Reverted to v12")),
+                Ok((
+                    HookEvent::Rollback {
+                        author: "hodor@unsplash.com".to_string(),
+                        version: "v12".to_string()
+                    },
+                    Some("hodor@unsplash.com reverted to v12".to_string())
+                )),
+            );
+
+            assert_eq!(
+                decode_release_payload(&patterns, &payload_from_desc("Rollback to v12")),
+                Ok((
+                    HookEvent::Rollback {
+                        author: "hodor@unsplash.com".to_string(),
+                        version: "v12".to_string()
+                    },
+                    None
+                )),
+            );
+        }
+
         #[test]
         fn test_env_vars_change() {
             assert_eq!(
-                decode_release_payload(&payload_from_desc("Set FOO, BAR config vars")),
+                decode(&payload_from_desc("Set FOO, BAR config vars")),
                 Ok(HookEvent::EnvVarsChange {
                     author: "hodor@unsplash.com".to_string(),
                     raw_change: "Set FOO, BAR".to_string()
@@ -606,7 +980,7 @@ mod tests {
             );
 
             assert_eq!(
-                decode_release_payload(&payload_from_desc("Some new format config vars")),
+                decode(&payload_from_desc("Some new format config vars")),
                 Ok(HookEvent::EnvVarsChange {
                     author: "hodor@unsplash.com".to_string(),
                     raw_change: "Some new format".to_string()
@@ -614,9 +988,131 @@ mod tests {
             );
 
             assert_eq!(
-                decode_release_payload(&payload_from_desc("Config vars changed")),
+                decode(&payload_from_desc("Config vars changed")),
                 Err("Config vars changed".to_string()),
             );
         }
     }
+
+    mod changelog {
+        use super::*;
+
+        #[test]
+        fn test_fmt_changelog() {
+            let deploy = HookEvent::Deploy {
+                author: "hodor@unsplash.com".to_string(),
+                commit: "69eec518".to_string(),
+            };
+
+            let rollback = HookEvent::Rollback {
+                author: "hodor@unsplash.com".to_string(),
+                version: "v1234".to_string(),
+            };
+
+            assert_eq!(
+                fmt_changelog(
+                    &deploy,
+                    &Changelog {
+                        total_commits: 1,
+                        subjects: vec!["Fix typo".to_string()],
+                    },
+                    &DEFAULT,
+                ),
+                "Includes 1 commit:\n• Fix typo",
+            );
+
+            assert_eq!(
+                fmt_changelog(
+                    &rollback,
+                    &Changelog {
+                        total_commits: 8,
+                        subjects: vec!["Break things".to_string(), "Fix typo".to_string()],
+                    },
+                    &DEFAULT,
+                ),
+                "Reverts 8 commits:\n• Break things\n• Fix typo\n• …and 6 more",
+            );
+
+            assert_eq!(
+                fmt_changelog(
+                    &rollback,
+                    &Changelog {
+                        total_commits: 2,
+                        subjects: vec!["Fix typo".to_string()],
+                    },
+                    &"de".parse().unwrap(),
+                ),
+                "Macht 2 Commits rückgängig:\n• Fix typo\n• …und 1 weitere",
+            );
+        }
+    }
+
+    mod snapshots {
+        use super::*;
+        use crate::{
+            slack::{channel::ChannelName, Message},
+            snapshot::assert_snapshot,
+        };
+        use serde_json::json;
+
+        fn assert_rendered(name: &str, event: HookEvent, changelog: Option<&Changelog>) {
+            let payload: HookPayload = serde_json::from_value(json!({
+                "resource": "release",
+                "action": "update",
+                "created_at": "2023-08-03T10:00:30Z",
+                "data": {
+                    "app": { "name": "my-app" },
+                    "description": "Unused",
+                    "user": { "email": "hodor@unsplash.com" },
+                },
+            }))
+            .unwrap();
+
+            let evt = to_event(&event, None, changelog, &payload, &DEFAULT);
+            let msg = Message::from_event(&evt, ChannelName("any".to_string()), None, None);
+
+            assert_snapshot(&format!("heroku_webhook__{}", name), &msg);
+        }
+
+        #[test]
+        fn test_snapshot_events() {
+            let author = || "hodor@unsplash.com".to_string();
+            let changelog = Changelog {
+                total_commits: 3,
+                subjects: vec!["Break things".to_string(), "Fix typo".to_string()],
+            };
+
+            let deploy = || HookEvent::Deploy {
+                author: author(),
+                commit: "69eec518".to_string(),
+            };
+            assert_rendered("deploy", deploy(), None);
+            assert_rendered("deploy_changelog", deploy(), Some(&changelog));
+
+            let rollback = || HookEvent::Rollback {
+                author: author(),
+                version: "v1234".to_string(),
+            };
+            assert_rendered("rollback", rollback(), None);
+            assert_rendered("rollback_changelog", rollback(), Some(&changelog));
+
+            assert_rendered(
+                "env_vars_change",
+                HookEvent::EnvVarsChange {
+                    author: author(),
+                    raw_change: "Set FOO, BAR".to_string(),
+                },
+                None,
+            );
+
+            assert_rendered(
+                "dyno_crash",
+                HookEvent::DynoCrash {
+                    name: "scheduler.8375".to_string(),
+                    status_code: Some(137),
+                },
+                None,
+            );
+        }
+    }
 }
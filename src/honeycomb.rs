@@ -0,0 +1,146 @@
+//! Record deploys and rollbacks as [Honeycomb](https://www.honeycomb.io)
+//! markers, labelled by app and version, so that they show up on queries and
+//! can be correlated with regressions.
+//!
+//! Enabled by configuring an API key with permission to manage markers at
+//! `$HONEYCOMB_API_KEY`. Markers are environment-wide unless a dataset is
+//! configured at `$HONEYCOMB_DATASET`. Accounts in Honeycomb's EU region must
+//! also set `$HONEYCOMB_API` to `https://api.eu1.honeycomb.io`.
+//!
+//! Marker types are the event's kind, `deploy` or `rollback`, and messages are
+//! the app and version, for example `api v42`. Recording is best effort, in
+//! addition to wherever the event's headed, and suppressed in read-only mode.
+
+use crate::event::{Event, EventKind};
+use chrono::Utc;
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// The API of accounts in Honeycomb's US region.
+pub const API_BASE: &str = "https://api.honeycomb.io";
+
+/// The pseudo-dataset for markers which apply to the whole environment.
+pub const ALL_DATASETS: &str = "__all__";
+
+/// How long to wait for Honeycomb to accept each marker.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// <https://docs.honeycomb.io/api/tag/Markers#operation/createMarker>
+#[derive(Serialize)]
+struct CreateMarkerRequest<'a> {
+    message: String,
+    #[serde(rename = "type")]
+    typ: &'static str,
+    /// Unix seconds.
+    start_time: i64,
+    url: Option<&'a str>,
+}
+
+/// Records markers in a dataset, safe to share across requests.
+pub struct Honeycomb {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    dataset: String,
+}
+
+impl Honeycomb {
+    pub fn new(base_url: String, api_key: String, dataset: String) -> Self {
+        Honeycomb {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            api_key,
+            dataset,
+        }
+    }
+
+    /// Best effort record a deploy or rollback, ignoring other events.
+    pub async fn record(&self, evt: &Event) {
+        let Some(req) = to_request(evt) else {
+            return;
+        };
+
+        let res = self
+            .client
+            .post(format!("{}/1/markers/{}", self.base_url, self.dataset))
+            .header("X-Honeycomb-Team", &self.api_key)
+            .json(&req)
+            .send()
+            .await
+            .and_then(|x| x.error_for_status());
+
+        match res {
+            Ok(_) => info!("Recorded {:?} event in Honeycomb", evt.kind),
+            Err(e) => warn!("Failed to record event in Honeycomb: {}", e),
+        }
+    }
+}
+
+fn to_request(evt: &Event) -> Option<CreateMarkerRequest<'_>> {
+    if !matches!(evt.kind, EventKind::Deploy | EventKind::Rollback) {
+        return None;
+    }
+
+    let app = evt.app.as_ref().unwrap_or(&evt.title);
+    let message = match &evt.version {
+        Some(v) => format!("{} {}", app, v),
+        None => app.clone(),
+    };
+
+    Some(CreateMarkerRequest {
+        message,
+        typ: evt.kind.as_str(),
+        start_time: evt.occurred_at.unwrap_or_else(Utc::now).timestamp(),
+        url: evt.links.first().map(|x| x.as_str()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Source;
+    use chrono::TimeZone;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_record() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/1/markers/__all__")
+            .match_header("X-Honeycomb-Team", "key")
+            .match_body(Matcher::Json(json!({
+                "message": "api v42",
+                "type": "deploy",
+                "start_time": 1704110400,
+                "url": "https://dashboard.heroku.com/apps/api/activity",
+            })))
+            .with_status(201)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = Honeycomb::new(srv.url(), "key".into(), ALL_DATASETS.into());
+        let evt = |kind| Event {
+            source: Source::Heroku,
+            kind,
+            app: Some(String::from("api")),
+            version: Some(String::from("v42")),
+            severity: None,
+            occurred_at: Some(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()),
+            title: String::from("api"),
+            summary: String::from("Deployed abc123"),
+            fields: Vec::new(),
+            links: vec!["https://dashboard.heroku.com/apps/api/activity"
+                .parse()
+                .unwrap()],
+        };
+
+        x.record(&evt(EventKind::Deploy)).await;
+        x.record(&evt(EventKind::ConfigChange)).await;
+
+        mock.assert_async().await;
+    }
+}
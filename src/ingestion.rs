@@ -0,0 +1,164 @@
+//! Pause ingestion from a source, for example Heroku during a known flapping
+//! incident, whilst continuing to accept its requests so that senders neither
+//! retry nor give up on their webhooks.
+//!
+//! Requests received whilst paused are responded to as per read-only mode,
+//! with a `Mercury-Suppressed: paused` header, and held in memory up to a
+//! limit beyond which the oldest are dropped. Upon resuming they can be caught
+//! up on, delivered in the order they arrived, or else discarded. Held requests
+//! are lost at shutdown.
+//!
+//! Controlled via the admin API. See [crate::admin].
+
+use crate::{
+    delivery::{deliver, Source},
+    heroku::webhook::{forward, Job},
+    router::Deps,
+    slack::Message,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+use tracing::{info, warn};
+
+/// How many requests are held per source before the oldest are dropped.
+pub const CAPACITY: usize = 1000;
+
+/// The sources whose ingestion can be paused. Others originate within Mercury.
+pub const PAUSABLE: [Source; 2] = [Source::Api, Source::Heroku];
+
+/// A request received whilst its source was paused.
+pub enum Held {
+    Message(Message),
+    HerokuHook(Job),
+}
+
+/// Tracks which sources are paused and what they've sent since, safe to share
+/// across requests.
+#[derive(Default)]
+pub struct Ingestion {
+    paused: Mutex<HashMap<Source, VecDeque<Held>>>,
+}
+
+impl Ingestion {
+    /// Pause a source, returning whether it wasn't already.
+    pub fn pause(&self, source: Source) -> bool {
+        let mut paused = self.paused.lock().unwrap();
+
+        match paused.contains_key(&source) {
+            true => false,
+            false => {
+                paused.insert(source, VecDeque::new());
+                true
+            }
+        }
+    }
+
+    /// Resume a source, returning what it sent whilst paused, if it was.
+    pub fn resume(&self, source: Source) -> Option<Vec<Held>> {
+        self.paused.lock().unwrap().remove(&source).map(Vec::from)
+    }
+
+    /// How many requests are held for a source, if it's paused.
+    pub fn held(&self, source: Source) -> Option<usize> {
+        self.paused.lock().unwrap().get(&source).map(VecDeque::len)
+    }
+
+    /// Hold a request if its source is paused, handing it back otherwise.
+    pub fn hold<T>(&self, source: Source, x: T, into: impl FnOnce(T) -> Held) -> Result<(), T> {
+        let mut paused = self.paused.lock().unwrap();
+        let Some(xs) = paused.get_mut(&source) else {
+            return Err(x);
+        };
+
+        if xs.len() >= CAPACITY {
+            warn!(
+                "Too many requests held from {}, dropping the oldest",
+                source.as_str()
+            );
+            xs.pop_front();
+        }
+        xs.push_back(into(x));
+
+        Ok(())
+    }
+}
+
+/// Deliver requests held from a source, one at a time in the order they
+/// arrived.
+pub async fn catch_up(deps: Deps, source: Source, xs: Vec<Held>) {
+    let n = xs.len();
+    info!("Catching up on {} requests from {}", n, source.as_str());
+
+    for x in xs {
+        match x {
+            Held::Message(m) => {
+                if let Err(e) = deliver(&deps, &m, source).await {
+                    warn!("Failed to deliver held message to {}: {}", m.channel, e);
+                }
+            }
+            Held::HerokuHook(job) => forward(&deps, &job.platform, &job.opts, &job.payload)
+                .await
+                .log("held webhook"),
+        }
+    }
+
+    info!("Caught up on {} requests from {}", n, source.as_str());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::channel::ChannelName;
+
+    fn message(title: &str) -> Message {
+        Message::new(ChannelName("any".into()), title, "desc")
+    }
+
+    fn titles(xs: Vec<Held>) -> Vec<String> {
+        xs.into_iter()
+            .map(|x| match x {
+                Held::Message(m) => m.title,
+                Held::HerokuHook(_) => String::from("hook"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hold() {
+        let x = Ingestion::default();
+
+        assert!(x.hold(Source::Api, message("a"), Held::Message).is_err());
+        assert_eq!(x.held(Source::Api), None);
+        assert!(x.resume(Source::Api).is_none());
+
+        assert!(x.pause(Source::Api));
+        assert!(!x.pause(Source::Api));
+        assert!(x.hold(Source::Api, message("b"), Held::Message).is_ok());
+        assert!(x.hold(Source::Api, message("c"), Held::Message).is_ok());
+        // Other sources are unaffected.
+        assert!(x.hold(Source::Heroku, message("d"), Held::Message).is_err());
+        assert_eq!(x.held(Source::Api), Some(2));
+
+        assert_eq!(titles(x.resume(Source::Api).unwrap()), vec!["b", "c"]);
+        assert_eq!(x.held(Source::Api), None);
+        assert!(x.hold(Source::Api, message("e"), Held::Message).is_err());
+    }
+
+    #[test]
+    fn test_hold_capacity() {
+        let x = Ingestion::default();
+        x.pause(Source::Api);
+
+        for i in 0..=CAPACITY {
+            assert!(x
+                .hold(Source::Api, message(&i.to_string()), Held::Message)
+                .is_ok());
+        }
+
+        let xs = titles(x.resume(Source::Api).unwrap());
+        assert_eq!(xs.len(), CAPACITY);
+        assert_eq!(xs[0], "1");
+    }
+}
@@ -0,0 +1,1157 @@
+//! The guide of souls to the underworld.
+//!
+//! For a high-level introduction see the project README.
+//!
+//! The only communication mechanism currently supported is [Slack][slack].
+//!
+//! Mercury is principally a server, whose binary merely calls [run]. Beyond
+//! that this library exposes only what's useful to its consumers, behind
+//! feature flags:
+//!
+//! - `client`: A typed [client::MercuryClient] for Mercury's HTTP API.
+//! - `fuzz`: Inbound payload types, for the fuzz targets in `fuzz/`.
+//! - `bench`: What the benchmarks in `benches/` exercise.
+
+use admin::AdminToken;
+use arc_swap::{ArcSwap, ArcSwapOption};
+use audit::AuditLog;
+use bounded::StateMetrics;
+use budget::NoiseBudgets;
+use capture::Captures;
+use chrono::DateTime;
+use console::Console;
+use delivery::{Shadow, Source};
+use dotenvy::dotenv;
+use escalation::Escalations;
+use fixture::Fixtures;
+use github::{GitHubClient, GitHubToken};
+use heartbeat::Heartbeats;
+use heroku::{HerokuSecret, ReleaseCommitMap};
+use ingestion::Ingestion;
+use locale::ChannelLocales;
+use meta::MetaAlerts;
+use metrics::Metrics;
+use oncall::{OnCallProvider, OpsgenieApiKey, OpsgenieClient, PagerDutyClient, PagerDutyToken};
+use priority::Lanes;
+use proxy::TrustedProxies;
+use router::{Deps, Routes};
+use slack::{
+    api::API_BASE, channel::ChannelName, interactivity::SlackSigningSecret, SlackAccessToken,
+    SlackClient,
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use stats::Stats;
+use status::StatusBoards;
+use std::{
+    env, io,
+    net::SocketAddr,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+use stream::{parse_list, EventStream};
+use telemetry::TraceFilter;
+use tenant::{TenantConfig, Tenants};
+use threading::Threads;
+use tokio::{
+    net::TcpListener,
+    sync::{oneshot, Mutex},
+};
+use tracing::{info, warn};
+
+mod admin;
+mod audit;
+mod auth;
+mod bounded;
+mod budget;
+mod cache;
+mod capture;
+mod check;
+#[cfg(feature = "client")]
+pub mod client;
+mod console;
+mod datadog;
+mod de;
+mod debug;
+mod delivery;
+mod escalation;
+mod event;
+mod eventbridge;
+mod eventlog;
+mod explain;
+mod feed;
+mod fixture;
+mod github;
+mod grafana;
+mod grpc;
+mod health;
+mod heartbeat;
+mod heroku;
+mod honeycomb;
+mod ingestion;
+mod locale;
+mod matrix;
+mod meta;
+mod metrics;
+mod oncall;
+mod panic;
+mod priority;
+mod proxy;
+mod redact;
+mod router;
+mod schema;
+mod secrets;
+mod sign;
+mod signed;
+mod signing;
+mod slack;
+mod sms;
+#[cfg(test)]
+mod snapshot;
+mod stats;
+mod status;
+mod statuspage;
+mod stream;
+mod telemetry;
+mod tenant;
+mod threading;
+mod tls;
+mod validation;
+mod zulip;
+
+/// What the fuzz targets in `fuzz/` exercise.
+#[cfg(feature = "fuzz")]
+pub mod fuzz {
+    pub use crate::heroku::payload::HookPayload;
+}
+
+/// What the benchmarks in `benches/` exercise.
+#[cfg(feature = "bench")]
+pub mod bench {
+    pub use crate::slack::{channel::ChannelName, SlackAccessToken, SlackClient};
+}
+
+#[cfg(test)]
+#[macro_use]
+extern crate quickcheck;
+
+/// Application entrypoint. Initialises tracing, checks for environment
+/// variables, binds to `$BIND_ADDR` (0.0.0.0 by default), and starts the
+/// server. Alternatively `mercury check-config` lints the configuration, see
+/// [check], `mercury sign` prints the headers with which to sign a request,
+/// see [sign], and `mercury dev --fake-slack` serves against a fake Slack, see
+/// [slack::fake].
+pub async fn run() {
+    tracing_subscriber::fmt()
+        .with_target(false)
+        .with_ansi(print_in_color())
+        .compact()
+        .init();
+
+    let has_dotenv = dotenv().is_ok();
+    if !has_dotenv {
+        warn!("No .env found");
+    }
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().is_some_and(|x| x == "check-config") {
+        let live = args.iter().any(|x| x == "--live");
+
+        std::process::exit(check::run(live).await);
+    }
+
+    if args.first().is_some_and(|x| x == "sign") {
+        std::process::exit(sign::run(&args[1..]));
+    }
+
+    let addr: SocketAddr = match env::var("BIND_ADDR") {
+        Ok(x) => x
+            .parse()
+            .expect("Could not parse BIND_ADDR to a socket address"),
+        Err(_) => {
+            let port: u16 = env::var("PORT")
+                .map(|x| x.parse().expect("Could not parse PORT to u16"))
+                .unwrap_or(80);
+
+            SocketAddr::from(([0, 0, 0, 0], port))
+        }
+    };
+
+    let fake_slack =
+        args.first().is_some_and(|x| x == "dev") && args.iter().any(|x| x == "--fake-slack");
+
+    let (slack_token, slack_api_base) = match fake_slack {
+        true => {
+            let base = slack::fake::serve(fake_slack_channels())
+                .await
+                .expect("Failed to serve fake Slack");
+            let token = SlackAccessToken(FAKE_SLACK_TOKEN.into());
+
+            warn!(
+                "Serving fake Slack at {}, messages will be printed rather than sent",
+                base
+            );
+
+            (token, base)
+        }
+        false => {
+            let token = load_secret("SLACK_TOKEN")
+                .await
+                .map(SlackAccessToken)
+                .expect("No $SLACK_TOKEN secret found");
+            // Overridable for end-to-end tests against mocks. See `tests/`.
+            let base = env::var("SLACK_API_BASE").unwrap_or_else(|_| API_BASE.into());
+
+            (token, base)
+        }
+    };
+
+    server_(addr, slack_token, slack_api_base).await;
+}
+
+/// The Slack access token used with a fake Slack, which in the absence of
+/// `$MERCURY_API_TOKEN` is also accepted for inbound requests.
+const FAKE_SLACK_TOKEN: &str = "fake";
+
+/// The channels in a fake Slack: `dev`, any listed in `$FAKE_SLACK_CHANNELS`,
+/// and any referenced by the configuration.
+fn fake_slack_channels() -> Vec<ChannelName> {
+    let get = |x: &str| env::var(x).ok();
+
+    let mut xs = vec![ChannelName("dev".into())];
+    if let Some(x) = get("FAKE_SLACK_CHANNELS") {
+        xs.extend(
+            x.split(',')
+                .map(|x| x.trim().trim_start_matches('#'))
+                .filter(|x| !x.is_empty())
+                .map(|x| ChannelName(x.to_owned())),
+        );
+    }
+    xs.extend(check::channels(get).into_iter().map(|(_, x)| x));
+
+    xs
+}
+
+/// Initialise a server without graceful shutdown.
+async fn server_(addr: SocketAddr, slack_token: SlackAccessToken, slack_api_base: String) {
+    // Giving a receiver that will never resolve.
+    server(
+        addr,
+        slack_token,
+        slack_api_base,
+        oneshot::channel::<()>().1,
+    )
+    .await;
+}
+
+/// Initialise a server with graceful shutdown via `rx`.
+async fn server(
+    addr: SocketAddr,
+    slack_token: SlackAccessToken,
+    slack_api_base: String,
+    rx: oneshot::Receiver<()>,
+) {
+    let metrics = Metrics::new();
+
+    let heroku_secret = load_secret("HEROKU_SECRET").await.map(HerokuSecret);
+    if heroku_secret.is_none() {
+        warn!("No $HEROKU_SECRET secret found");
+    }
+
+    let github_token = load_secret("GITHUB_TOKEN").await.map(GitHubToken);
+    if github_token.is_none() {
+        warn!("No $GITHUB_TOKEN secret found");
+    }
+
+    let admin_token = load_secret("ADMIN_TOKEN").await.map(AdminToken);
+    if admin_token.is_none() {
+        warn!("No $ADMIN_TOKEN secret found");
+    }
+
+    let heroku_app_routes = env::var("HEROKU_APP_ROUTES")
+        .map(|x| heroku::routing::parse_app_routes(&x))
+        .unwrap_or_default();
+
+    let heroku_named_routes = env::var("HEROKU_NAMED_ROUTES")
+        .map(|x| heroku::routing::parse_app_routes(&x))
+        .unwrap_or_default();
+
+    let heroku_runbooks = env::var("HEROKU_RUNBOOKS")
+        .map(|x| heroku::runbook::Runbooks::parse(&x))
+        .unwrap_or_default();
+
+    let heroku_emoji = env::var("HEROKU_EMOJI")
+        .map(|x| heroku::emoji::EmojiRules::parse(&x).expect("Could not parse HEROKU_EMOJI"))
+        .unwrap_or_default();
+
+    let heroku_push = match env::var("HEROKU_PUSH_ROUTES") {
+        Err(_) => None,
+        Ok(x) => {
+            let routes =
+                heroku::push::parse_push_routes(&x).expect("Could not parse HEROKU_PUSH_ROUTES");
+            let ntfy_base =
+                env::var("NTFY_BASE").unwrap_or_else(|_| heroku::push::NTFY_BASE.into());
+            let mut pusher = heroku::push::Pusher::new(routes)
+                .with_ntfy(ntfy_base, load_secret("NTFY_TOKEN").await);
+
+            if pusher.needs_pushover() {
+                let token = load_secret("PUSHOVER_TOKEN")
+                    .await
+                    .expect("$HEROKU_PUSH_ROUTES to Pushover requires $PUSHOVER_TOKEN");
+                pusher = pusher.with_pushover(heroku::push::PUSHOVER_BASE.into(), token);
+            }
+
+            Some(Arc::new(pusher))
+        }
+    };
+
+    let heroku_jira = match env::var("JIRA_ROUTES") {
+        Err(_) => None,
+        Ok(x) => {
+            let routes = heroku::jira::parse_jira_routes(&x).expect("Could not parse JIRA_ROUTES");
+            let base_url = env::var("JIRA_BASE").expect("$JIRA_ROUTES requires $JIRA_BASE");
+            let email = env::var("JIRA_EMAIL").expect("$JIRA_ROUTES requires $JIRA_EMAIL");
+            let api_token = load_secret("JIRA_API_TOKEN")
+                .await
+                .expect("$JIRA_ROUTES requires $JIRA_API_TOKEN");
+
+            Some(Arc::new(heroku::jira::Jira::new(
+                routes, base_url, email, api_token,
+            )))
+        }
+    };
+
+    let heroku_linear = match env::var("LINEAR_TEAM_ID") {
+        Err(_) => None,
+        Ok(team_id) => {
+            let api_key = load_secret("LINEAR_API_KEY")
+                .await
+                .expect("$LINEAR_TEAM_ID requires $LINEAR_API_KEY");
+            let threshold = env::var("LINEAR_CRASH_THRESHOLD")
+                .map(|x| x.parse().expect("Could not parse LINEAR_CRASH_THRESHOLD"))
+                .unwrap_or(heroku::linear::DEFAULT_THRESHOLD);
+            let window = env::var("LINEAR_CRASH_WINDOW_MINS")
+                .map(|x| {
+                    Duration::from_secs(
+                        x.parse::<u64>()
+                            .expect("Could not parse LINEAR_CRASH_WINDOW_MINS")
+                            * 60,
+                    )
+                })
+                .unwrap_or(heroku::linear::DEFAULT_WINDOW);
+
+            Some(Arc::new(heroku::linear::Linear::new(
+                heroku::linear::API_URL.into(),
+                api_key,
+                team_id,
+                threshold,
+                window,
+            )))
+        }
+    };
+
+    let heroku_calendar = match env::var("GOOGLE_CALENDAR_ID") {
+        Err(_) => None,
+        Ok(x) => {
+            let credentials = heroku::calendar::GoogleCredentials {
+                client_id: env::var("GOOGLE_CLIENT_ID")
+                    .expect("$GOOGLE_CALENDAR_ID requires $GOOGLE_CLIENT_ID"),
+                client_secret: load_secret("GOOGLE_CLIENT_SECRET")
+                    .await
+                    .expect("$GOOGLE_CALENDAR_ID requires $GOOGLE_CLIENT_SECRET"),
+                refresh_token: load_secret("GOOGLE_REFRESH_TOKEN")
+                    .await
+                    .expect("$GOOGLE_CALENDAR_ID requires $GOOGLE_REFRESH_TOKEN"),
+            };
+            let apps = env::var("GOOGLE_CALENDAR_APPS")
+                .unwrap_or_else(|_| heroku::calendar::DEFAULT_APPS.into());
+
+            info!("Logging releases of {} to calendar {}", apps, x);
+
+            Some(Arc::new(heroku::calendar::Calendar::new(
+                heroku::calendar::API_BASE.into(),
+                heroku::calendar::TOKEN_URL.into(),
+                x,
+                &apps,
+                credentials,
+            )))
+        }
+    };
+
+    let heroku_otto = match env::var("OTTO_URL") {
+        Err(_) => None,
+        Ok(x) => {
+            info!("Relaying events to Otto at {}", x);
+
+            Some(Arc::new(heroku::otto::Otto::new(
+                x,
+                load_secret("OTTO_SECRET").await,
+                client_tls("OTTO"),
+            )))
+        }
+    };
+
+    let channel_locales = env::var("CHANNEL_LOCALES")
+        .map(|x| ChannelLocales::parse(&x).expect("Could not parse CHANNEL_LOCALES"))
+        .unwrap_or_default();
+
+    let heroku_description_patterns = env::var("HEROKU_DESCRIPTION_PATTERNS")
+        .map(|x| {
+            heroku::description::DescriptionPatterns::parse(&x)
+                .expect("Could not parse HEROKU_DESCRIPTION_PATTERNS")
+        })
+        .unwrap_or_default();
+
+    let max_backlog: usize = env::var("MAX_BACKLOG")
+        .map(|x| x.parse().expect("Could not parse MAX_BACKLOG to usize"))
+        .unwrap_or(priority::DEFAULT_MAX_BACKLOG);
+
+    let read_only: bool = env::var("READ_ONLY")
+        .map(|x| x.parse().expect("Could not parse READ_ONLY to bool"))
+        .unwrap_or(false);
+    if read_only {
+        warn!("Read-only mode enabled");
+    }
+
+    let warm_timeout = env::var("WARM_CHANNEL_CACHE_SECS").ok().map(|x| {
+        let secs: u64 = x
+            .parse()
+            .expect("Could not parse WARM_CHANNEL_CACHE_SECS to u64");
+
+        Duration::from_secs(secs)
+    });
+
+    let internal_addr: Option<SocketAddr> = env::var("INTERNAL_BIND_ADDR").ok().map(|x| {
+        x.parse()
+            .expect("Could not parse INTERNAL_BIND_ADDR to a socket address")
+    });
+
+    let grpc_port: Option<u16> = env::var("GRPC_PORT")
+        .ok()
+        .map(|x| x.parse().expect("Could not parse GRPC_PORT to u16"));
+
+    let shadow = env::var("SHADOW_CHANNEL").ok().map(|x| {
+        let sample_every: u64 = env::var("SHADOW_SAMPLE_EVERY")
+            .map(|x| {
+                x.parse()
+                    .expect("Could not parse SHADOW_SAMPLE_EVERY to u64")
+            })
+            .unwrap_or(1);
+
+        info!("Mirroring one in every {} messages to {}", sample_every, x);
+
+        Shadow::new(ChannelName(x), sample_every)
+    });
+
+    let api_tokens = load_secret("MERCURY_API_TOKEN")
+        .await
+        .map(|x| auth::parse_api_tokens(&x))
+        .unwrap_or_default();
+    if api_tokens.is_empty() {
+        warn!("No $MERCURY_API_TOKEN secret found");
+    }
+
+    let slack_token_compat: bool = env::var("SLACK_TOKEN_COMPAT")
+        .map(|x| {
+            x.parse()
+                .expect("Could not parse SLACK_TOKEN_COMPAT to bool")
+        })
+        .unwrap_or(api_tokens.is_empty());
+    if slack_token_compat {
+        warn!("Accepting $SLACK_TOKEN for inbound requests, clients should migrate to $MERCURY_API_TOKEN");
+    }
+
+    let mut tenants = Vec::new();
+    for name in env::var("TENANTS")
+        .map(|x| tenant::parse_tenants(&x))
+        .unwrap_or_default()
+    {
+        let var = |x: &str| format!("{}_{}", tenant::env_prefix(&name), x);
+
+        let slack_token = load_secret(&var("SLACK_TOKEN"))
+            .await
+            .map(SlackAccessToken)
+            .unwrap_or_else(|| panic!("Tenant {} requires ${}", name, var("SLACK_TOKEN")));
+        let hosts = env::var(var("HOSTS"))
+            .map(|x| tenant::parse_hosts(&x))
+            .unwrap_or_default();
+        let api_tokens = load_secret(&var("MERCURY_API_TOKEN"))
+            .await
+            .map(|x| auth::parse_api_tokens(&x))
+            .unwrap_or_default();
+        if hosts.is_empty() && api_tokens.is_empty() {
+            warn!(
+                "Tenant {} has neither hosts nor API tokens, so is unreachable",
+                name
+            );
+        }
+
+        tenants.push(TenantConfig {
+            name: name.clone(),
+            hosts,
+            slack_token,
+            slack_signing_secret: load_secret(&var("SLACK_SIGNING_SECRET"))
+                .await
+                .map(SlackSigningSecret),
+            api_tokens,
+            signing_secrets: load_secret(&var("SIGNING_SECRETS"))
+                .await
+                .map(|x| signing::parse_signing_secrets(&x))
+                .unwrap_or_default(),
+            admin_token: load_secret(&var("ADMIN_TOKEN")).await.map(AdminToken),
+            heroku_secret: load_secret(&var("HEROKU_SECRET")).await.map(HerokuSecret),
+            heroku_app_routes: env::var(var("HEROKU_APP_ROUTES"))
+                .map(|x| heroku::routing::parse_app_routes(&x))
+                .unwrap_or_default(),
+            heroku_named_routes: env::var(var("HEROKU_NAMED_ROUTES"))
+                .map(|x| heroku::routing::parse_app_routes(&x))
+                .unwrap_or_default(),
+        });
+        info!("Serving tenant {}", name);
+    }
+
+    let signing_secrets = load_secret("SIGNING_SECRETS")
+        .await
+        .map(|x| signing::parse_signing_secrets(&x))
+        .unwrap_or_default();
+
+    let debug_payloads: bool = env::var("DEBUG_PAYLOADS")
+        .map(|x| x.parse().expect("Could not parse DEBUG_PAYLOADS to bool"))
+        .unwrap_or(false);
+    if debug_payloads {
+        warn!("Logging redacted payloads");
+    }
+
+    let captures = env::var("CAPTURE_PAYLOADS")
+        .ok()
+        .map(|x| {
+            x.parse()
+                .expect("Could not parse CAPTURE_PAYLOADS to usize")
+        })
+        .filter(|n: &usize| *n > 0)
+        .map(|n| Arc::new(Captures::new(n)));
+
+    let fixtures = env::var("FIXTURE_DIR")
+        .ok()
+        .filter(|x| !x.is_empty())
+        .map(|x| Arc::new(Fixtures::new(x)));
+
+    let console_tee: bool = env::var("CONSOLE_TEE")
+        .map(|x| x.parse().expect("Could not parse CONSOLE_TEE to bool"))
+        .unwrap_or(false);
+    let console = match env::var("CONSOLE_OUTPUT") {
+        Err(_) => Console::default(),
+        Ok(x) => Console::open(&x).expect("Could not open CONSOLE_OUTPUT"),
+    }
+    .with_tee(console_tee);
+
+    let meta_alert_targets: Vec<_> = env::var("META_ALERT_URL")
+        .ok()
+        .map(meta::Target::Webhook)
+        .into_iter()
+        .chain(
+            load_secret("META_ALERT_PAGERDUTY_KEY")
+                .await
+                .map(|routing_key| meta::Target::PagerDuty {
+                    base_url: meta::PAGERDUTY_EVENTS_BASE.into(),
+                    routing_key,
+                }),
+        )
+        .collect();
+    let meta_alerts = (!meta_alert_targets.is_empty()).then(|| {
+        let threshold = env::var("META_ALERT_THRESHOLD")
+            .map(|x| {
+                x.parse()
+                    .expect("Could not parse META_ALERT_THRESHOLD to u32")
+            })
+            .unwrap_or(meta::DEFAULT_THRESHOLD);
+
+        Arc::new(MetaAlerts::new(meta_alert_targets, threshold))
+    });
+
+    let sms = match env::var("SMS_RECIPIENTS") {
+        Err(_) => None,
+        Ok(x) => {
+            let recipients = sms::parse_recipients(&x).expect("Could not parse SMS_RECIPIENTS");
+            let config = sms::TwilioConfig {
+                base_url: env::var("TWILIO_API_BASE").unwrap_or_else(|_| sms::API_BASE.into()),
+                account_sid: env::var("TWILIO_ACCOUNT_SID")
+                    .expect("$SMS_RECIPIENTS requires $TWILIO_ACCOUNT_SID"),
+                auth_token: load_secret("TWILIO_AUTH_TOKEN")
+                    .await
+                    .expect("$SMS_RECIPIENTS requires $TWILIO_AUTH_TOKEN"),
+                from: env::var("TWILIO_FROM").expect("$SMS_RECIPIENTS requires $TWILIO_FROM"),
+            };
+            let max_per_hour: usize = env::var("SMS_MAX_PER_HOUR")
+                .map(|x| {
+                    x.parse()
+                        .expect("Could not parse SMS_MAX_PER_HOUR to usize")
+                })
+                .unwrap_or(sms::DEFAULT_MAX_PER_HOUR);
+
+            info!(
+                "Texting critical messages which fail to deliver to {} recipients",
+                recipients.len()
+            );
+
+            Some(Arc::new(sms::Sms::new(config, recipients, max_per_hour)))
+        }
+    };
+
+    let matrix = match env::var("MATRIX_HOMESERVER") {
+        Err(_) => None,
+        Ok(x) => {
+            let token = load_secret("MATRIX_ACCESS_TOKEN")
+                .await
+                .expect("$MATRIX_HOMESERVER requires $MATRIX_ACCESS_TOKEN");
+
+            info!("Posting to Matrix rooms via {}", x);
+
+            Some(Arc::new(matrix::MatrixClient::new(
+                x,
+                token,
+                client_tls("MATRIX"),
+                StateMetrics::new(&metrics, "matrix_rooms"),
+            )))
+        }
+    };
+
+    let zulip = match env::var("ZULIP_SITE") {
+        Err(_) => None,
+        Ok(x) => {
+            let email = env::var("ZULIP_EMAIL").expect("$ZULIP_SITE requires $ZULIP_EMAIL");
+            let api_key = load_secret("ZULIP_API_KEY")
+                .await
+                .expect("$ZULIP_SITE requires $ZULIP_API_KEY");
+
+            info!("Posting to Zulip streams via {}", x);
+
+            Some(Arc::new(zulip::ZulipClient::new(
+                x,
+                email,
+                api_key,
+                client_tls("ZULIP"),
+            )))
+        }
+    };
+
+    let statuspage = match env::var("STATUSPAGE_PAGE_ID") {
+        Err(_) => None,
+        Ok(x) => {
+            let api_key = load_secret("STATUSPAGE_API_KEY")
+                .await
+                .expect("$STATUSPAGE_PAGE_ID requires $STATUSPAGE_API_KEY");
+
+            info!("Managing incidents on Statuspage page {}", x);
+
+            Some(Arc::new(statuspage::StatuspageClient::new(
+                statuspage::API_BASE.into(),
+                x,
+                api_key,
+            )))
+        }
+    };
+
+    let eventbridge = match env::var("EVENTBRIDGE_BUS") {
+        Err(_) => None,
+        Ok(bus) => {
+            let region = env::var("AWS_REGION").expect("$EVENTBRIDGE_BUS requires $AWS_REGION");
+            let credentials = eventbridge::AwsCredentials {
+                access_key_id: env::var("AWS_ACCESS_KEY_ID")
+                    .expect("$EVENTBRIDGE_BUS requires $AWS_ACCESS_KEY_ID"),
+                secret_access_key: load_secret("AWS_SECRET_ACCESS_KEY")
+                    .await
+                    .expect("$EVENTBRIDGE_BUS requires $AWS_SECRET_ACCESS_KEY"),
+                session_token: load_secret("AWS_SESSION_TOKEN").await,
+            };
+
+            info!("Publishing events to EventBridge bus {}", bus);
+
+            let mut x = eventbridge::EventBridge::new(region, credentials, bus);
+            if let Ok(endpoint) = env::var("EVENTBRIDGE_ENDPOINT") {
+                x = x.with_endpoint(
+                    endpoint
+                        .parse()
+                        .expect("Could not parse EVENTBRIDGE_ENDPOINT to URL"),
+                );
+            }
+
+            Some(Arc::new(x))
+        }
+    };
+
+    let grafana = match env::var("GRAFANA_URL") {
+        Err(_) => None,
+        Ok(x) => {
+            let api_key = load_secret("GRAFANA_API_KEY")
+                .await
+                .expect("$GRAFANA_URL requires $GRAFANA_API_KEY");
+
+            info!("Annotating deploys in Grafana at {}", x);
+
+            Some(Arc::new(grafana::Grafana::new(
+                x,
+                api_key,
+                client_tls("GRAFANA"),
+            )))
+        }
+    };
+
+    let datadog = load_secret("DATADOG_API_KEY").await.map(|x| {
+        let site = env::var("DATADOG_SITE").unwrap_or_else(|_| datadog::DEFAULT_SITE.into());
+
+        info!("Recording deploys in Datadog at {}", site);
+
+        Arc::new(datadog::Datadog::new(datadog::api_url(&site), x))
+    });
+
+    let honeycomb = load_secret("HONEYCOMB_API_KEY").await.map(|x| {
+        let base = env::var("HONEYCOMB_API").unwrap_or_else(|_| honeycomb::API_BASE.into());
+        let dataset =
+            env::var("HONEYCOMB_DATASET").unwrap_or_else(|_| honeycomb::ALL_DATASETS.into());
+
+        info!("Recording deploys in Honeycomb dataset {}", dataset);
+
+        Arc::new(honeycomb::Honeycomb::new(base, x, dataset))
+    });
+
+    let event_log = match env::var("EVENT_LOG") {
+        Err(_) => None,
+        Ok(x) => {
+            let target = eventlog::parse_target(&x).expect("Could not parse EVENT_LOG");
+            let x = eventlog::EventLog::open(target)
+                .await
+                .expect("Could not open EVENT_LOG");
+
+            Some(Arc::new(x))
+        }
+    };
+
+    let trace_filter = env::var("TRACE_EXCLUDE")
+        .map(|x| TraceFilter::parse(&x).expect("Could not parse TRACE_EXCLUDE"))
+        .unwrap_or_default();
+
+    let strict_sources: Vec<Source> = env::var("STRICT_SOURCES")
+        .map(|x| parse_list(&x).expect("Could not parse STRICT_SOURCES"))
+        .unwrap_or_default();
+
+    let trusted_proxies = env::var("TRUSTED_PROXIES")
+        .map(|x| TrustedProxies::parse(&x).expect("Could not parse TRUSTED_PROXIES"))
+        .unwrap_or_default();
+
+    let max_signature_skew = env::var("MAX_SIGNATURE_SKEW_SECS")
+        .map(|x| {
+            Duration::from_secs(
+                x.parse()
+                    .expect("Could not parse MAX_SIGNATURE_SKEW_SECS to u64"),
+            )
+        })
+        .unwrap_or(signing::DEFAULT_MAX_SKEW);
+
+    let slack_token_expires_at = env::var("SLACK_TOKEN_EXPIRES_AT").ok().map(|x| {
+        x.parse()
+            .ok()
+            .and_then(|x| DateTime::from_timestamp(x, 0))
+            .expect("Could not parse SLACK_TOKEN_EXPIRES_AT to Unix timestamp")
+    });
+
+    let audit_capacity: usize = env::var("AUDIT_CAPACITY")
+        .map(|x| x.parse().expect("Could not parse AUDIT_CAPACITY to usize"))
+        .unwrap_or(audit::DEFAULT_CAPACITY);
+    let audit_log = match env::var("AUDIT_LOG_PATH") {
+        Err(_) => AuditLog::new(audit_capacity),
+        Ok(x) => AuditLog::new(audit_capacity)
+            .with_persistence(x.into())
+            .expect("Could not open AUDIT_LOG_PATH"),
+    };
+
+    let response_cache = Arc::new(cache::ResponseCache::new(
+        env::var("RESPONSE_CACHE_SECS")
+            .map(|x| {
+                Duration::from_secs(
+                    x.parse()
+                        .expect("Could not parse RESPONSE_CACHE_SECS to seconds"),
+                )
+            })
+            .unwrap_or(cache::DEFAULT_TTL),
+        StateMetrics::new(&metrics, "responses"),
+    ));
+
+    let escalations = env::var("ESCALATION_POLICY").ok().map(|x| {
+        let policy =
+            escalation::parse_policy(&x).expect("Could not parse ESCALATION_POLICY to policy");
+
+        info!("Escalating critical messages in {} stages", policy.len());
+
+        Arc::new(Escalations::new(
+            policy,
+            StateMetrics::new(&metrics, "escalations"),
+        ))
+    });
+
+    let slack_signing_secret = load_secret("SLACK_SIGNING_SECRET")
+        .await
+        .map(SlackSigningSecret);
+    if escalations.is_some() && slack_signing_secret.is_none() {
+        warn!("No $SLACK_SIGNING_SECRET secret found, escalations can only be acknowledged by reaction");
+    }
+
+    let noise_budgets = env::var("NOISE_BUDGETS")
+        .ok()
+        .map(|x| Arc::new(NoiseBudgets::new(budget::parse_budget_limits(&x))));
+
+    let heartbeats = env::var("HEARTBEATS").ok().map(|x| {
+        let expectations = heartbeat::parse_heartbeats(&x).expect("Could not parse HEARTBEATS");
+
+        Arc::new(Heartbeats::new(expectations))
+    });
+
+    let threads = env::var("THREAD_WINDOW_MINS").ok().map(|x| {
+        let mins: u64 = x
+            .parse()
+            .expect("Could not parse THREAD_WINDOW_MINS to u64");
+
+        Arc::new(Threads::new(
+            Duration::from_secs(60 * mins),
+            StateMetrics::new(&metrics, "threads"),
+        ))
+    });
+
+    let status_boards = env::var("STATUS_CHANNELS").ok().map(|x| {
+        let boards = StatusBoards::parse(&x);
+
+        info!("Keeping status messages in {} channels", boards.len());
+
+        Arc::new(boards)
+    });
+
+    let pagerduty_token = load_secret("PAGERDUTY_TOKEN").await;
+    let opsgenie_token = load_secret("OPSGENIE_TOKEN").await;
+    let on_call = match (pagerduty_token, opsgenie_token) {
+        (Some(x), _) => Some(OnCallProvider::PagerDuty(PagerDutyClient::new(
+            oncall::pagerduty::API_BASE.into(),
+            PagerDutyToken(x),
+        ))),
+        (None, Some(x)) => Some(OnCallProvider::Opsgenie(OpsgenieClient::new(
+            oncall::opsgenie::API_BASE.into(),
+            OpsgenieApiKey(x),
+        ))),
+        (None, None) => None,
+    };
+
+    // Overridable for end-to-end tests against mocks. See `tests/`.
+    let github_api_base =
+        env::var("GITHUB_API_BASE").unwrap_or_else(|_| github::api::API_BASE.into());
+
+    let new_slack_client = || {
+        let client = SlackClient::new(slack_api_base.clone()).with_payload_logging(debug_payloads);
+        match on_call.clone() {
+            Some(x) => client.with_on_call(x),
+            None => client,
+        }
+    };
+    let slack_client = new_slack_client();
+    let github_client = GitHubClient::new(github_api_base);
+
+    let heroku_poll_apps = env::var("HEROKU_POLL_APPS")
+        .map(|x| heroku::poll::parse_poll_apps(&x))
+        .unwrap_or_default();
+    let heroku_poller = match heroku_poll_apps.is_empty() {
+        true => None,
+        false => {
+            let token = load_secret("HEROKU_API_TOKEN")
+                .await
+                .map(heroku::poll::HerokuApiToken)
+                .expect("$HEROKU_POLL_APPS requires $HEROKU_API_TOKEN");
+            let interval = env::var("HEROKU_POLL_INTERVAL_SECS")
+                .map(|x| {
+                    Duration::from_secs(
+                        x.parse()
+                            .expect("Could not parse HEROKU_POLL_INTERVAL_SECS to u64"),
+                    )
+                })
+                .unwrap_or(heroku::poll::DEFAULT_INTERVAL);
+            let api_base =
+                env::var("HEROKU_API_BASE").unwrap_or_else(|_| heroku::poll::API_BASE.into());
+
+            info!("Polling {} Heroku apps", heroku_poll_apps.len());
+
+            Some((
+                heroku::poll::Poller::new(api_base, token, heroku_poll_apps),
+                interval,
+            ))
+        }
+    };
+
+    let deps = Deps {
+        slack_history: slack_client.history(),
+        #[cfg(feature = "chaos")]
+        slack_chaos: slack_client.chaos(),
+        slack_client: Arc::new(Mutex::new(slack_client)),
+        slack_token: Arc::new(ArcSwap::from_pointee(slack_token)),
+        slack_token_expires_at: Arc::new(ArcSwapOption::from_pointee(slack_token_expires_at)),
+        heroku_secrets: Arc::new(ArcSwap::from_pointee(heroku_secret.into_iter().collect())),
+        github_client: Arc::new(github_client),
+        github_token,
+        release_commits: Arc::new(Mutex::new(ReleaseCommitMap::new(
+            heroku::webhook::MAX_RELEASE_COMMITS,
+            StateMetrics::new(&metrics, "release_commits"),
+        ))),
+        heroku_app_routes: Arc::new(heroku_app_routes),
+        heroku_named_routes: Arc::new(heroku_named_routes),
+        heroku_description_patterns: Arc::new(heroku_description_patterns),
+        heroku_runbooks: Arc::new(heroku_runbooks),
+        heroku_emoji: Arc::new(heroku_emoji),
+        heroku_push,
+        heroku_jira,
+        heroku_linear,
+        heroku_calendar,
+        heroku_otto,
+        channel_locales: Arc::new(channel_locales),
+        admin_token,
+        read_only: Arc::new(AtomicBool::new(read_only)),
+        shadow,
+        signing_secrets,
+        api_tokens: Arc::new(ArcSwap::from_pointee(api_tokens)),
+        slack_token_compat,
+        debug_payloads,
+        captures,
+        fixtures,
+        console: Arc::new(console),
+        metrics,
+        response_cache,
+        trace_filter: Arc::new(trace_filter),
+        meta_alerts,
+        sms,
+        matrix,
+        zulip,
+        statuspage,
+        eventbridge,
+        grafana,
+        datadog,
+        honeycomb,
+        event_log,
+        strict_sources: Arc::new(strict_sources),
+        max_signature_skew,
+        trusted_proxies: Arc::new(trusted_proxies),
+        selftest_channel: env::var("SELFTEST_CHANNEL").ok().map(ChannelName),
+        ops_channel: env::var("OPS_CHANNEL").ok().map(ChannelName),
+        audit: Arc::new(audit_log),
+        stats: Arc::new(Stats::default()),
+        events: EventStream::default(),
+        noise_budgets: noise_budgets.clone(),
+        lanes: Arc::new(Lanes::new(max_backlog)),
+        ingestion: Arc::new(Ingestion::default()),
+        heartbeats: heartbeats.clone(),
+        threads,
+        status_boards: status_boards.clone(),
+        escalations: escalations.clone(),
+        slack_signing_secret,
+    };
+
+    tokio::spawn(slack::expiry::watch_expiry(
+        deps.slack_token_expires_at.clone(),
+        deps.metrics.slack_token_expiry.clone(),
+    ));
+
+    if let Some((poller, interval)) = heroku_poller {
+        tokio::spawn(heroku::poll::watch(deps.clone(), poller, interval));
+    }
+
+    if let Some(x) = escalations {
+        tokio::spawn(escalation::watch_escalations(deps.clone(), x));
+    }
+
+    if let Some(x) = noise_budgets {
+        tokio::spawn(budget::watch_budgets(deps.clone(), x));
+    }
+
+    if let Some(x) = status_boards {
+        tokio::spawn(status::watch_status(deps.clone(), x));
+    }
+
+    if let Some(x) = heartbeats {
+        tokio::spawn(heartbeat::watch_heartbeats(deps.clone(), x));
+    }
+
+    if let Some(x) = grpc_port {
+        let addr = SocketAddr::new(addr.ip(), x);
+        let listener =
+            bind(addr).unwrap_or_else(|e| panic!("Failed to bind gRPC to {}: {}", addr, e));
+        tokio::spawn(grpc::serve(listener, deps.clone()));
+    }
+
+    let tenants: Vec<_> = tenants
+        .into_iter()
+        .map(|x| {
+            let hosts = x.hosts.clone();
+            (hosts, tenant::tenant_deps(&deps, x, new_slack_client()))
+        })
+        .collect();
+    let with_tenants = |app, routes| match tenants.is_empty() {
+        true => app,
+        false => tenant::router(tenants.iter().fold(Tenants::new(app), |acc, (hosts, x)| {
+            acc.with_tenant(hosts.clone(), x, router::with_routes(x.clone(), routes))
+        })),
+    };
+
+    // Operational routes are only served publicly absent a separate listener.
+    let app = match internal_addr {
+        None => with_tenants(router::new(deps.clone()), Routes::All),
+        Some(x) => {
+            let listener =
+                bind(x).unwrap_or_else(|e| panic!("Failed to bind internal to {}: {}", x, e));
+            info!("Internal routes listening on {}", x);
+
+            let app = with_tenants(
+                router::with_routes(deps.clone(), Routes::Internal),
+                Routes::Internal,
+            );
+            tokio::spawn(async move {
+                let app = app.into_make_service_with_connect_info::<SocketAddr>();
+                if let Err(e) = axum::serve(listener, app).await {
+                    warn!("Internal server failed: {}", e);
+                }
+            });
+
+            with_tenants(
+                router::with_routes(deps.clone(), Routes::Public),
+                Routes::Public,
+            )
+        }
+    };
+
+    // Heroku routes to a dyno once it's listening, so warm the cache first.
+    if let Some(x) = warm_timeout {
+        let token = deps.slack_token.load_full();
+        let mut client = deps.slack_client.lock().await;
+
+        match tokio::time::timeout(x, client.warm_channel_map(&token)).await {
+            Ok(Ok(n)) => info!("Warmed channel cache with {} channels", n),
+            Ok(Err(e)) => warn!("Failed to warm channel cache: {}", e),
+            Err(_) => warn!("Timed out warming channel cache after {:?}", x),
+        }
+    }
+
+    let listener = bind(addr).unwrap_or_else(|e| panic!("Failed to bind to {}: {}", addr, e));
+    info!("Listening on {}", addr.to_string());
+
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async {
+        rx.await.ok();
+    })
+    .await
+    .expect("Failed to start server");
+}
+
+/// Bind a TCP listener. Binding to the unspecified IPv6 address, `[::]`,
+/// additionally accepts IPv4 connections irrespective of the OS's default.
+fn bind(addr: SocketAddr) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() && addr.ip().is_unspecified() {
+        socket.set_only_v6(false)?;
+    }
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+/// Load a secret from any of the sources supported by [secrets]. Failing to
+/// load a secret that's been configured is fatal.
+async fn load_secret(name: &str) -> Option<String> {
+    secrets::load(name)
+        .await
+        .unwrap_or_else(|e| panic!("Could not load ${}: {}", name, e))
+}
+
+/// Load a platform's TLS configuration, panicking if it's invalid. See
+/// [tls].
+fn client_tls(prefix: &str) -> tls::ClientTls {
+    tls::ClientTls::from_env(|x| env::var(x).ok(), prefix)
+        .unwrap_or_else(|e| panic!("Could not configure TLS: {}", e))
+}
+
+/// We want pretty output in dev, however we don't want ANSI escape sequences in
+/// our production logs. Until tracing-subscriber handles this for us somehow,
+/// we'll check `TERM` and implement the `NO_COLOR` standard.
+///
+/// This implementation is borrowed from the `termcolor` crate, which is used by
+/// the likes of ripgrep.
+///
+/// See:
+///   - <https://no-color.org>
+///   - <https://github.com/tokio-rs/tracing/issues/2388>
+///   - <https://github.com/tokio-rs/tracing/issues/2214#issuecomment-1191729530>
+///   - <https://github.com/BurntSushi/termcolor/blob/fb5fb8bb62b0cf8a9623da557d2a4ed6a27b8c9f/src/lib.rs#L256>
+fn print_in_color() -> bool {
+    match env::var_os("TERM") {
+        None => return false,
+        Some(k) => {
+            if k == "dumb" {
+                return false;
+            }
+        }
+    }
+
+    if env::var_os("NO_COLOR").is_some() {
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_real_health_api() {
+        let (tx, rx) = oneshot::channel::<()>();
+
+        // Port 0 requests that the OS assigns us an available port.
+        let addr = std::net::TcpListener::bind("0.0.0.0:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        // Move the server into the background so that it's not blocking.
+        tokio::spawn(async move {
+            server(
+                addr,
+                SlackAccessToken("any".to_owned()),
+                API_BASE.into(),
+                rx,
+            )
+            .await
+        });
+
+        let res = reqwest::Client::new()
+            .get(format!("http://localhost:{}/api/v1/health", addr.port()))
+            .send()
+            .await
+            .unwrap();
+
+        tx.send(()).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.text().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bind_dual_stack() {
+        let listener = bind("[::]:0".parse().unwrap()).unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap();
+        listener.accept().await.unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+//! Localize the fixed strings Mercury emits, such as Heroku event summaries,
+//! for channels whose readers don't speak English.
+//!
+//! Translations are [Fluent](https://projectfluent.org/) files under
+//! `locales/`, compiled into the binary. English is the default, and the
+//! fallback for any message a locale lacks. To add a locale, add a file and
+//! register it in [BUNDLES].
+//!
+//! Channels' locales are configured via `$CHANNEL_LOCALES` as a comma-separated
+//! list of `channel:locale` pairs, for example `deploys-fr:fr,ventas:es-MX`.
+//! Regional variants fall back to their language if we've no translation
+//! specific to them.
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use tracing::warn;
+use unic_langid::LanguageIdentifier;
+
+/// The locale used unless configured otherwise.
+pub static DEFAULT: Lazy<LanguageIdentifier> = Lazy::new(|| "en".parse().unwrap());
+
+/// The translations for each supported locale, English first.
+static BUNDLES: Lazy<Vec<(LanguageIdentifier, FluentBundle<FluentResource>)>> = Lazy::new(|| {
+    [
+        ("en", include_str!("../locales/en.ftl")),
+        ("de", include_str!("../locales/de.ftl")),
+        ("es", include_str!("../locales/es.ftl")),
+        ("fr", include_str!("../locales/fr.ftl")),
+    ]
+    .into_iter()
+    .map(|(id, ftl)| {
+        let id: LanguageIdentifier = id.parse().unwrap();
+        let res = FluentResource::try_new(ftl.to_owned())
+            .unwrap_or_else(|(_, es)| panic!("Invalid {} translations: {:?}", id, es));
+
+        let mut bundle = FluentBundle::new_concurrent(vec![id.clone()]);
+        // Unicode isolation marks around arguments would show up in Slack.
+        bundle.set_use_isolating(false);
+        bundle
+            .add_resource(res)
+            .unwrap_or_else(|es| panic!("Conflicting {} translations: {:?}", id, es));
+
+        (id, bundle)
+    })
+    .collect()
+});
+
+/// Find the supported locale for an identifier, falling back from a regional
+/// variant to its language.
+fn find_supported(id: &LanguageIdentifier) -> Option<&'static LanguageIdentifier> {
+    let ids = || BUNDLES.iter().map(|(x, _)| x);
+
+    ids()
+        .find(|x| *x == id)
+        .or_else(|| ids().find(|x| x.language == id.language && x.region.is_none()))
+}
+
+/// Translate a message into a locale, interpolating arguments. Falls back to
+/// English should the locale lack the message.
+///
+/// ```
+/// let x = tr(&DEFAULT, "heroku-dyno-crash-unknown", &[("name", "web.1".into())]);
+/// assert_eq!(x, "Dyno web.1 crashed");
+/// ```
+pub fn tr(locale: &LanguageIdentifier, key: &str, args: &[(&str, FluentValue)]) -> String {
+    let args: FluentArgs = args.iter().cloned().collect();
+
+    let found = BUNDLES
+        .iter()
+        .filter(|(id, _)| id == locale || *id == *DEFAULT)
+        // The requested locale, if any, precedes English.
+        .filter_map(|(_, b)| Some((b, b.get_message(key)?.value()?)))
+        .min_by_key(|(b, _)| b.locales[0] == *DEFAULT);
+
+    let Some((bundle, pattern)) = found else {
+        warn!("No translation for {}", key);
+        return key.to_owned();
+    };
+
+    let mut errs = Vec::new();
+    let x = bundle.format_pattern(pattern, Some(&args), &mut errs);
+    if !errs.is_empty() {
+        warn!("Failed to translate {} into {}: {:?}", key, locale, errs);
+    }
+
+    x.into_owned()
+}
+
+/// Maps channel names, without any leading hash, to their locales.
+#[derive(Default)]
+pub struct ChannelLocales(HashMap<String, &'static LanguageIdentifier>);
+
+impl ChannelLocales {
+    /// Parse channel locales from their environment variable representation.
+    ///
+    /// ```
+    /// let xs = ChannelLocales::parse("deploys-fr:fr, ventas:es-MX").unwrap();
+    /// ```
+    pub fn parse(x: &str) -> Result<Self, String> {
+        x.split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|entry| {
+                let (channel, locale) = entry
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid entry: {}", entry))?;
+                let id: LanguageIdentifier = locale
+                    .trim()
+                    .parse()
+                    .map_err(|e| format!("invalid locale {}: {}", locale, e))?;
+                let id =
+                    find_supported(&id).ok_or_else(|| format!("unsupported locale: {}", id))?;
+
+                Ok((normalise(channel), id))
+            })
+            .collect::<Result<_, _>>()
+            .map(ChannelLocales)
+    }
+
+    /// Get a channel's locale.
+    pub fn get(&self, channel: &str) -> &'static LanguageIdentifier {
+        self.0.get(&normalise(channel)).copied().unwrap_or(&DEFAULT)
+    }
+}
+
+/// Channel names can't contain hashes, so this lets consumers supply a leading
+/// hash or not.
+fn normalise(channel: &str) -> String {
+    channel.trim().trim_start_matches('#').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(x: &str) -> LanguageIdentifier {
+        x.parse().unwrap()
+    }
+
+    #[test]
+    fn test_bundles_complete() {
+        let (_, en) = &BUNDLES[0];
+        let keys: Vec<_> = include_str!("../locales/en.ftl")
+            .lines()
+            .filter_map(|x| x.split_once(" = ").map(|(k, _)| k))
+            .collect();
+        assert!(!keys.is_empty());
+
+        for (id, b) in BUNDLES.iter() {
+            for k in &keys {
+                assert!(en.has_message(k));
+                assert!(b.has_message(k), "{} lacks {}", id, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tr() {
+        let crash = |locale| {
+            tr(
+                &id(locale),
+                "heroku-dyno-crash",
+                &[("name", "web.1".into()), ("code", "137".into())],
+            )
+        };
+
+        assert_eq!(crash("en"), "Dyno web.1 crashed with status code 137");
+        assert_eq!(crash("de"), "Dyno web.1 ist mit Statuscode 137 abgestürzt");
+        // Unsupported locales fall back to English.
+        assert_eq!(crash("ja"), "Dyno web.1 crashed with status code 137");
+        assert_eq!(tr(&DEFAULT, "nope", &[]), "nope");
+    }
+
+    #[test]
+    fn test_tr_plural() {
+        let includes =
+            |locale, n: usize| tr(&id(locale), "changelog-includes", &[("count", n.into())]);
+
+        assert_eq!(includes("en", 1), "Includes 1 commit:");
+        assert_eq!(includes("en", 2), "Includes 2 commits:");
+        // French treats zero as singular.
+        assert_eq!(includes("fr", 0), "Inclut 0 commit :");
+        assert_eq!(includes("fr", 2), "Inclut 2 commits :");
+    }
+
+    #[test]
+    fn test_parse() {
+        let xs = ChannelLocales::parse(" #deploys-fr:fr, ventas:es-MX,,").unwrap();
+
+        assert_eq!(xs.get("deploys-fr"), &id("fr"));
+        assert_eq!(xs.get("#ventas"), &id("es"));
+        assert_eq!(xs.get("other"), &*DEFAULT);
+
+        assert!(ChannelLocales::parse("deploys").is_err());
+        assert!(ChannelLocales::parse("deploys:???").is_err());
+        assert!(ChannelLocales::parse("deploys:ja").is_err());
+    }
+}
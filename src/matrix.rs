@@ -0,0 +1,358 @@
+//! Post events to [Matrix](https://matrix.org) rooms via the client-server API,
+//! for teams on self-hosted Matrix and Element rather than Slack.
+//!
+//! Enabled by configuring the homeserver's base URL at `$MATRIX_HOMESERVER`,
+//! for example `https://matrix.example.org`, and an access token for the user
+//! to post as at `$MATRIX_ACCESS_TOKEN`. The user must already have joined any
+//! rooms it's to post in. A private homeserver's TLS can be configured as per
+//! [crate::tls].
+//!
+//! Rooms are referred to by alias, for example `#deploys:example.org`, which
+//! are resolved to room IDs once and then cached for the life of the process.
+
+use crate::{
+    bounded::{BoundedMap, StateMetrics},
+    event::Event,
+    feed::escape_xml,
+    tls::ClientTls,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use url::form_urlencoded;
+
+/// How long to wait for the homeserver to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most room aliases whose IDs are cached at once.
+const MAX_ROOMS: usize = 1_000;
+
+/// A room alias such as `#deploys:example.org`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RoomAlias(String);
+
+impl TryFrom<String> for RoomAlias {
+    type Error = String;
+
+    fn try_from(x: String) -> Result<Self, Self::Error> {
+        match x.strip_prefix('#').and_then(|x| x.split_once(':')) {
+            Some((local, server)) if !local.is_empty() && !server.is_empty() => Ok(RoomAlias(x)),
+            _ => Err(format!("invalid room alias: {}", x)),
+        }
+    }
+}
+
+/// Format without the surrounding newtype wrapper.
+impl fmt::Display for RoomAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// What went wrong communicating with the homeserver.
+#[derive(Debug)]
+pub enum MatrixError {
+    /// Matrix was asked for however `$MATRIX_HOMESERVER` isn't configured.
+    Unconfigured,
+    RequestFailed(reqwest::Error),
+    /// Successfully decoded response error, for example `M_NOT_FOUND` for an
+    /// unknown alias or `M_FORBIDDEN` if we've not joined the room.
+    Response {
+        errcode: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Unconfigured => write!(f, "Matrix is not configured"),
+            MatrixError::RequestFailed(e) => write!(f, "Matrix request failed: {:?}", e),
+            MatrixError::Response { errcode, error } => {
+                write!(f, "Matrix returned error: {}: {}", errcode, error)
+            }
+        }
+    }
+}
+
+/// <https://spec.matrix.org/latest/client-server-api/#standard-error-response>
+#[derive(Deserialize)]
+struct ErrorResponse {
+    errcode: String,
+    #[serde(default)]
+    error: String,
+}
+
+/// <https://spec.matrix.org/latest/client-server-api/#get_matrixclientv3directoryroomroomalias>
+#[derive(Deserialize)]
+struct AliasResponse {
+    room_id: String,
+}
+
+/// <https://spec.matrix.org/latest/client-server-api/#mroommessage>
+#[derive(Serialize)]
+struct TextMessage {
+    msgtype: &'static str,
+    body: String,
+    format: &'static str,
+    formatted_body: String,
+}
+
+/// Posts to rooms as a single user, safe to share across requests.
+pub struct MatrixClient {
+    client: reqwest::Client,
+    homeserver: String,
+    access_token: String,
+    rooms: Mutex<BoundedMap<RoomAlias, String>>,
+    /// Transaction IDs must be unique per access token, including across
+    /// restarts, hence they're prefixed with when we started.
+    txn_prefix: String,
+    txn_count: AtomicU64,
+}
+
+impl MatrixClient {
+    pub fn new(
+        homeserver: String,
+        access_token: String,
+        tls: ClientTls,
+        metrics: StateMetrics,
+    ) -> Self {
+        MatrixClient {
+            client: tls
+                .apply(reqwest::Client::builder().timeout(TIMEOUT))
+                .build()
+                .unwrap(),
+            homeserver: homeserver.trim_end_matches('/').to_owned(),
+            access_token,
+            rooms: Mutex::new(BoundedMap::new(MAX_ROOMS, metrics)),
+            txn_prefix: format!("mercury-{}", Utc::now().timestamp_millis()),
+            txn_count: AtomicU64::new(0),
+        }
+    }
+
+    /// Post an event to a room.
+    pub async fn send(&self, room: &RoomAlias, evt: &Event) -> Result<(), MatrixError> {
+        let room_id = self.resolve(room).await?;
+        let txn_id = format!(
+            "{}-{}",
+            self.txn_prefix,
+            self.txn_count.fetch_add(1, Ordering::Relaxed)
+        );
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.homeserver,
+            encode(&room_id),
+            txn_id
+        );
+
+        let res = self
+            .client
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&to_message(evt))
+            .send()
+            .await
+            .map_err(MatrixError::RequestFailed)?;
+
+        check(res).await.map(|_| ())
+    }
+
+    /// Find a room's ID from its alias.
+    async fn resolve(&self, room: &RoomAlias) -> Result<String, MatrixError> {
+        if let Some(x) = self.rooms.lock().unwrap().get(room) {
+            return Ok(x.clone());
+        }
+
+        let url = format!(
+            "{}/_matrix/client/v3/directory/room/{}",
+            self.homeserver,
+            encode(&room.0)
+        );
+
+        let res = self
+            .client
+            .get(url)
+            .bearer_auth(&self.access_token)
+            .send()
+            .await
+            .map_err(MatrixError::RequestFailed)?;
+        let x: AliasResponse = check(res)
+            .await?
+            .json()
+            .await
+            .map_err(MatrixError::RequestFailed)?;
+
+        self.rooms
+            .lock()
+            .unwrap()
+            .insert(room.clone(), x.room_id.clone());
+
+        Ok(x.room_id)
+    }
+}
+
+/// Decode an error response, if it is one.
+async fn check(res: reqwest::Response) -> Result<reqwest::Response, MatrixError> {
+    if res.status().is_success() {
+        return Ok(res);
+    }
+
+    let status = res.status();
+    let x = res
+        .json::<ErrorResponse>()
+        .await
+        .unwrap_or_else(|_| ErrorResponse {
+            errcode: String::from("M_UNKNOWN"),
+            error: status.to_string(),
+        });
+
+    Err(MatrixError::Response {
+        errcode: x.errcode,
+        error: x.error,
+    })
+}
+
+/// Percent-encode a path segment. Room IDs and aliases contain `!`, `#`, and
+/// `:`.
+fn encode(x: &str) -> String {
+    form_urlencoded::byte_serialize(x.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20")
+}
+
+/// Render an event as plain text, with an HTML equivalent for clients which
+/// support it.
+fn to_message(evt: &Event) -> TextMessage {
+    let mut body = vec![evt.title.clone(), evt.summary.clone()];
+    let mut html = vec![
+        format!("<strong>{}</strong>", escape_xml(&evt.title)),
+        escape_xml(&evt.summary).replace('\n', "<br>"),
+    ];
+
+    for (k, v) in &evt.fields {
+        body.push(format!("{}: {}", k, v));
+        html.push(format!("<em>{}</em>: {}", escape_xml(k), escape_xml(v)));
+    }
+
+    if let Some(x) = evt.links.first() {
+        body.push(x.to_string());
+        html.push(format!(
+            "<a href=\"{}\">{}</a>",
+            escape_xml(x.as_str()),
+            escape_xml(x.as_str())
+        ));
+    }
+
+    TextMessage {
+        msgtype: "m.text",
+        body: body.join("\n"),
+        format: "org.matrix.custom.html",
+        formatted_body: html.join("<br>"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, event::EventKind, metrics::Metrics};
+    use mockito::Matcher;
+    use serde_json::json;
+
+    fn deploy() -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Deploy,
+            app: Some(String::from("api")),
+            version: None,
+            severity: None,
+            occurred_at: None,
+            title: String::from("api"),
+            summary: String::from("Deployed <abc123>"),
+            fields: vec![(String::from("Author"), String::from("sam"))],
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_room_alias() {
+        let parse = |x: &str| RoomAlias::try_from(x.to_owned());
+
+        assert!(parse("#deploys:example.org").is_ok());
+        assert!(parse("deploys:example.org").is_err());
+        assert!(parse("#deploys").is_err());
+        assert!(parse("#:example.org").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let alias = srv
+            .mock(
+                "GET",
+                "/_matrix/client/v3/directory/room/%23deploys%3Aexample.org",
+            )
+            .match_header("Authorization", "Bearer tk")
+            .with_body(r#"{"room_id": "!abc:example.org", "servers": []}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let send = srv
+            .mock(
+                "PUT",
+                Matcher::Regex(
+                    "^/_matrix/client/v3/rooms/%21abc%3Aexample.org/send/m.room.message/mercury-"
+                        .into(),
+                ),
+            )
+            .match_body(Matcher::Json(json!({
+                "msgtype": "m.text",
+                "body": "api\nDeployed <abc123>\nAuthor: sam",
+                "format": "org.matrix.custom.html",
+                "formatted_body": "<strong>api</strong><br>Deployed &lt;abc123&gt;<br><em>Author</em>: sam",
+            })))
+            .with_body(r#"{"event_id": "$1"}"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let unknown = srv
+            .mock(
+                "GET",
+                "/_matrix/client/v3/directory/room/%23nope%3Aexample.org",
+            )
+            .with_status(404)
+            .with_body(r#"{"errcode": "M_NOT_FOUND", "error": "Room alias not found"}"#)
+            .create_async()
+            .await;
+
+        let x = MatrixClient::new(
+            srv.url() + "/",
+            String::from("tk"),
+            ClientTls::default(),
+            StateMetrics::new(&Metrics::new(), "matrix_rooms"),
+        );
+        let room = RoomAlias::try_from(String::from("#deploys:example.org")).unwrap();
+
+        // The alias is only resolved once.
+        x.send(&room, &deploy()).await.unwrap();
+        x.send(&room, &deploy()).await.unwrap();
+
+        let nope = RoomAlias::try_from(String::from("#nope:example.org")).unwrap();
+        assert!(matches!(
+            x.send(&nope, &deploy()).await,
+            Err(MatrixError::Response { errcode, .. }) if errcode == "M_NOT_FOUND"
+        ));
+
+        alias.assert_async().await;
+        send.assert_async().await;
+        unknown.assert_async().await;
+    }
+}
@@ -0,0 +1,280 @@
+//! Alert about Mercury itself failing to deliver, so that notification outages
+//! are learnt about from something other than silence.
+//!
+//! Once deliveries have failed consecutively `$META_ALERT_THRESHOLD` times
+//! (5 by default) an alert is sent via whichever of these are configured:
+//!
+//! - `$META_ALERT_URL`: A JSON payload is POSTed to this URL.
+//! - `$META_ALERT_PAGERDUTY_KEY`: An incident is triggered via PagerDuty's
+//!   Events API, using this integration key.
+//!
+//! The alert is resolved upon the next successful delivery. Failures which
+//! are down to the request rather than Mercury, such as unknown channels,
+//! don't count.
+
+use crate::slack::{error::APIError, SlackError};
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// The base URL of PagerDuty's Events API.
+pub const PAGERDUTY_EVENTS_BASE: &str = "https://events.pagerduty.com";
+
+/// The default number of consecutive failures to alert after.
+pub const DEFAULT_THRESHOLD: u32 = 5;
+
+/// How long to wait for each target to respond.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Identifies Mercury's alert to PagerDuty, such that it's triggered at most
+/// once and can later be resolved.
+const DEDUP_KEY: &str = "mercury-delivery-failures";
+
+/// Where to send alerts.
+pub enum Target {
+    Webhook(String),
+    PagerDuty {
+        /// See [PAGERDUTY_EVENTS_BASE].
+        base_url: String,
+        routing_key: String,
+    },
+}
+
+/// The payload POSTed to [Target::Webhook].
+#[derive(Clone, Serialize)]
+struct WebhookPayload {
+    status: Status,
+    consecutive_failures: u32,
+    last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Failing,
+    Recovered,
+}
+
+/// <https://developer.pagerduty.com/docs/events-api-v2/trigger-events/>
+#[derive(Serialize)]
+struct PagerDutyEvent<'a> {
+    routing_key: &'a str,
+    event_action: &'static str,
+    dedup_key: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<PagerDutyPayload>,
+}
+
+#[derive(Serialize)]
+struct PagerDutyPayload {
+    summary: String,
+    source: &'static str,
+    severity: &'static str,
+}
+
+/// Tracks consecutive delivery failures, alerting once they cross a threshold.
+pub struct MetaAlerts {
+    client: reqwest::Client,
+    targets: Vec<Target>,
+    threshold: u32,
+    consecutive: AtomicU32,
+    /// Whether an alert is currently outstanding.
+    firing: AtomicBool,
+    last_error: Mutex<Option<String>>,
+}
+
+impl MetaAlerts {
+    pub fn new(targets: Vec<Target>, threshold: u32) -> Self {
+        MetaAlerts {
+            // Alerts are sent inline with deliveries, which mustn't hang.
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            targets,
+            // Guard against alerting without any failures.
+            threshold: threshold.max(1),
+            consecutive: AtomicU32::new(0),
+            firing: AtomicBool::new(false),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    /// Record a failed delivery, alerting if it's crossed the threshold.
+    pub async fn record_failure(&self, e: &SlackError) {
+        if !is_systemic(e) {
+            return;
+        }
+
+        *self.last_error.lock().unwrap() = Some(e.to_string());
+        let n = self.consecutive.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if n >= self.threshold && !self.firing.swap(true, Ordering::Relaxed) {
+            warn!("{} consecutive delivery failures, alerting", n);
+            self.send(Status::Failing, n).await;
+        }
+    }
+
+    /// Record a successful delivery, resolving any outstanding alert.
+    pub async fn record_success(&self) {
+        let n = self.consecutive.swap(0, Ordering::Relaxed);
+
+        if self.firing.swap(false, Ordering::Relaxed) {
+            info!("Delivery recovered, resolving alert");
+            self.send(Status::Recovered, n).await;
+        }
+    }
+
+    /// Best effort alert every target.
+    async fn send(&self, status: Status, consecutive_failures: u32) {
+        let last_error = self.last_error.lock().unwrap().clone();
+
+        for target in &self.targets {
+            let req = match target {
+                Target::Webhook(url) => self.client.post(url).json(&WebhookPayload {
+                    status,
+                    consecutive_failures,
+                    last_error: last_error.clone(),
+                }),
+                Target::PagerDuty {
+                    base_url,
+                    routing_key,
+                } => self
+                    .client
+                    .post(base_url.clone() + "/v2/enqueue")
+                    .json(&PagerDutyEvent {
+                        routing_key,
+                        dedup_key: DEDUP_KEY,
+                        event_action: match status {
+                            Status::Failing => "trigger",
+                            Status::Recovered => "resolve",
+                        },
+                        payload: match status {
+                            Status::Failing => Some(PagerDutyPayload {
+                                summary: format!(
+                                    "Mercury failed to deliver {} consecutive messages: {}",
+                                    consecutive_failures,
+                                    last_error.as_deref().unwrap_or("unknown error")
+                                ),
+                                source: "mercury",
+                                severity: "critical",
+                            }),
+                            Status::Recovered => None,
+                        },
+                    }),
+            };
+
+            let res = req.send().await.and_then(|x| x.error_for_status());
+            if let Err(e) = res {
+                warn!("Failed to send meta alert: {}", e);
+            }
+        }
+    }
+}
+
+/// Whether a failure is Mercury's rather than down to the request.
+pub fn is_systemic(e: &SlackError) -> bool {
+    !matches!(
+        e,
+        SlackError::UnknownChannel(_)
+            | SlackError::APIResponseError(
+                APIError::ChannelNotFound
+                    | APIError::NotInChannel
+                    | APIError::IsArchived
+                    | APIError::RestrictedAction
+                    | APIError::MsgTooLong
+            )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::channel::ChannelName;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    fn outage() -> SlackError {
+        SlackError::APIResponseError(APIError::InvalidAuth)
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let failing = srv
+            .mock("POST", "/hook")
+            .match_body(Matcher::PartialJson(json!({
+                "status": "failing",
+                "consecutive_failures": 2,
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+        let recovered = srv
+            .mock("POST", "/hook")
+            .match_body(Matcher::PartialJson(json!({ "status": "recovered" })))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = MetaAlerts::new(vec![Target::Webhook(srv.url() + "/hook")], 2);
+
+        x.record_failure(&outage()).await;
+        // Not Mercury's fault, so doesn't count.
+        x.record_failure(&SlackError::UnknownChannel(ChannelName("x".into())))
+            .await;
+        x.record_failure(&outage()).await;
+        // Already alerted.
+        x.record_failure(&outage()).await;
+        x.record_success().await;
+        // Nothing to resolve.
+        x.record_success().await;
+
+        failing.assert_async().await;
+        recovered.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_pagerduty() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let trigger = srv
+            .mock("POST", "/v2/enqueue")
+            .match_body(Matcher::PartialJson(json!({
+                "routing_key": "key",
+                "event_action": "trigger",
+                "dedup_key": DEDUP_KEY,
+                "payload": { "severity": "critical" },
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+        let resolve = srv
+            .mock("POST", "/v2/enqueue")
+            .match_body(Matcher::PartialJson(json!({
+                "event_action": "resolve",
+                "dedup_key": DEDUP_KEY,
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = MetaAlerts::new(
+            vec![Target::PagerDuty {
+                base_url: srv.url(),
+                routing_key: "key".into(),
+            }],
+            1,
+        );
+
+        x.record_failure(&outage()).await;
+        x.record_success().await;
+
+        trigger.assert_async().await;
+        resolve.assert_async().await;
+    }
+}
@@ -0,0 +1,146 @@
+//! Prometheus metrics, exposed in the text format at `/api/v1/metrics`.
+
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+
+/// Every metric Mercury exposes, and the registry they belong to.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// When the Slack access token expires as a Unix timestamp, or zero if it
+    /// doesn't or if we don't know. See [crate::slack::expiry].
+    pub slack_token_expiry: IntGauge,
+    /// How long requests take, by route, method, status, client, and channel.
+    /// See [crate::telemetry].
+    pub http_request_duration: HistogramVec,
+    /// Fields in inbound payloads we don't recognise, by source and path. See
+    /// [crate::telemetry::record_unknown_fields].
+    pub unknown_fields: IntCounterVec,
+    /// Signed requests rejected for being too old or too far in the future, by
+    /// source. See [crate::signing::is_fresh].
+    pub stale_signatures: IntCounterVec,
+    /// Request handlers which panicked. See [crate::panic].
+    pub panics: IntCounter,
+    /// Entries held in bounded in-memory state, by name. See [crate::bounded].
+    pub state_entries: IntGaugeVec,
+    /// Entries evicted from bounded in-memory state, by name. See
+    /// [crate::bounded].
+    pub state_evictions: IntCounterVec,
+}
+
+impl Metrics {
+    /// Instantiate and register every metric.
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        // These can only fail on invalid names or duplicate registrations.
+        let slack_token_expiry = IntGauge::new(
+            "mercury_slack_token_expiry_timestamp_seconds",
+            "When the Slack access token expires, or zero if unknown.",
+        )
+        .unwrap();
+        registry
+            .register(Box::new(slack_token_expiry.clone()))
+            .unwrap();
+
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "mercury_http_request_duration_seconds",
+                "How long HTTP requests take to respond to.",
+            ),
+            &["route", "method", "status", "client", "channel"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(http_request_duration.clone()))
+            .unwrap();
+
+        let unknown_fields = IntCounterVec::new(
+            Opts::new(
+                "mercury_unknown_fields_total",
+                "Fields in inbound payloads which weren't recognised.",
+            ),
+            &["source", "path"],
+        )
+        .unwrap();
+        registry.register(Box::new(unknown_fields.clone())).unwrap();
+
+        let stale_signatures = IntCounterVec::new(
+            Opts::new(
+                "mercury_stale_signatures_total",
+                "Signed requests whose timestamps exceeded the maximum clock skew.",
+            ),
+            &["source"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(stale_signatures.clone()))
+            .unwrap();
+
+        let panics = IntCounter::new(
+            "mercury_panics_total",
+            "Request handlers which panicked, responding with a 500.",
+        )
+        .unwrap();
+        registry.register(Box::new(panics.clone())).unwrap();
+
+        let state_entries = IntGaugeVec::new(
+            Opts::new(
+                "mercury_state_entries",
+                "Entries held in bounded in-memory state.",
+            ),
+            &["state"],
+        )
+        .unwrap();
+        registry.register(Box::new(state_entries.clone())).unwrap();
+
+        let state_evictions = IntCounterVec::new(
+            Opts::new(
+                "mercury_state_evictions_total",
+                "Entries evicted from bounded in-memory state for want of capacity.",
+            ),
+            &["state"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(state_evictions.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            slack_token_expiry,
+            http_request_duration,
+            unknown_fields,
+            stale_signatures,
+            panics,
+            state_entries,
+            state_evictions,
+        }
+    }
+
+    /// Encode every metric in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        // This can only fail on malformed metrics, which we'd have caught on
+        // registration.
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+        metrics.slack_token_expiry.set(1700000000);
+
+        assert!(metrics
+            .encode()
+            .contains("mercury_slack_token_expiry_timestamp_seconds 1700000000\n"));
+    }
+}
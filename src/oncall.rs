@@ -0,0 +1,33 @@
+//! Resolve whoever is currently on call for a schedule, so that alerts can
+//! mention them directly via `cc=oncall:<schedule>`.
+//!
+//! Either PagerDuty or Opsgenie is supported, configured via
+//! `$PAGERDUTY_TOKEN` or `$OPSGENIE_TOKEN` respectively. On-call users are
+//! matched to Slack users by email address.
+
+pub mod error;
+pub mod opsgenie;
+pub mod pagerduty;
+
+pub use error::OnCallError;
+pub use opsgenie::{OpsgenieApiKey, OpsgenieClient};
+pub use pagerduty::{PagerDutyClient, PagerDutyToken};
+
+/// A source of on-call schedules.
+#[derive(Clone)]
+pub enum OnCallProvider {
+    /// Schedules are referenced by ID, for example `P1ABCDE`.
+    PagerDuty(PagerDutyClient),
+    /// Schedules are referenced by name.
+    Opsgenie(OpsgenieClient),
+}
+
+impl OnCallProvider {
+    /// Get the email address of whoever is currently on call for a schedule.
+    pub async fn get_on_call_email(&self, schedule: &str) -> Result<String, OnCallError> {
+        match self {
+            OnCallProvider::PagerDuty(x) => x.get_on_call_email(schedule).await,
+            OnCallProvider::Opsgenie(x) => x.get_on_call_email(schedule).await,
+        }
+    }
+}
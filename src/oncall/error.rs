@@ -0,0 +1,28 @@
+//! Captures what failure can look like when resolving who is on call.
+
+use std::fmt;
+
+/// Every possible unexceptional fail case when resolving who is on call.
+pub enum OnCallError {
+    /// General request failure, including unsuccessful status codes.
+    APIRequestFailed(reqwest::Error),
+    /// Nobody is on call for the given schedule.
+    NobodyOnCall(String),
+}
+
+impl From<reqwest::Error> for OnCallError {
+    fn from(e: reqwest::Error) -> Self {
+        OnCallError::APIRequestFailed(e)
+    }
+}
+
+impl fmt::Display for OnCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = match self {
+            OnCallError::APIRequestFailed(e) => format!("On-call API request failed: {:?}", e),
+            OnCallError::NobodyOnCall(s) => format!("Nobody on call for schedule: {}", s),
+        };
+
+        write!(f, "{}", x)
+    }
+}
@@ -0,0 +1,115 @@
+//! Look up who is on call via Opsgenie's REST API.
+//!
+//! <https://docs.opsgenie.com/docs/who-is-on-call-api#get-on-calls>
+
+use super::OnCallError;
+use serde::Deserialize;
+use std::fmt;
+use url::form_urlencoded;
+
+/// The base URL of Opsgenie's REST API. EU accounts should instead use
+/// `https://api.eu.opsgenie.com`.
+pub const API_BASE: &str = "https://api.opsgenie.com";
+
+/// A newtype wrapper around an Opsgenie API key.
+#[derive(Clone)]
+pub struct OpsgenieApiKey(pub String);
+
+impl fmt::Debug for OpsgenieApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OpsgenieApiKey([REDACTED])")
+    }
+}
+
+/// Holds a client request pool and an API key against a base URL.
+#[derive(Clone)]
+pub struct OpsgenieClient {
+    client: reqwest::Client,
+    base_url: String,
+    key: OpsgenieApiKey,
+}
+
+/// <https://docs.opsgenie.com/docs/who-is-on-call-api#get-on-calls>
+#[derive(Deserialize)]
+struct OnCallsResponse {
+    data: OnCallsData,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OnCallsData {
+    /// Email addresses when flattened.
+    on_call_recipients: Vec<String>,
+}
+
+impl OpsgenieClient {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [API_BASE].
+    pub fn new(base_url: String, key: OpsgenieApiKey) -> Self {
+        OpsgenieClient {
+            client: reqwest::Client::new(),
+            base_url,
+            key,
+        }
+    }
+
+    /// Get the email address of whoever is currently on call for a schedule,
+    /// referenced by its name.
+    pub async fn get_on_call_email(&self, schedule_name: &str) -> Result<String, OnCallError> {
+        let res: OnCallsResponse = self
+            .client
+            .get(self.base_url.clone() + &to_on_calls_path(schedule_name))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("GenieKey {}", self.key.0),
+            )
+            .query(&[("scheduleIdentifierType", "name"), ("flat", "true")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        res.data
+            .on_call_recipients
+            .into_iter()
+            .next()
+            .ok_or_else(|| OnCallError::NobodyOnCall(schedule_name.to_owned()))
+    }
+}
+
+/// Get the path at which to find who is on call for a schedule. Schedule names
+/// can contain characters which need encoding, such as spaces.
+fn to_on_calls_path(schedule_name: &str) -> String {
+    // Form encoding would otherwise encode spaces as `+`, which is only valid
+    // in query strings.
+    let name = form_urlencoded::byte_serialize(schedule_name.as_bytes())
+        .collect::<String>()
+        .replace('+', "%20");
+
+    format!("/v2/schedules/{}/on-calls", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_debug() {
+        let x = OpsgenieApiKey(String::from("genie-key"));
+
+        assert_eq!(format!("{:?}", x), "OpsgenieApiKey([REDACTED])");
+    }
+
+    #[test]
+    fn test_to_on_calls_path() {
+        assert_eq!(
+            to_on_calls_path("Platform Primary"),
+            "/v2/schedules/Platform%20Primary/on-calls"
+        );
+        assert_eq!(
+            to_on_calls_path("a/b+c"),
+            "/v2/schedules/a%2Fb%2Bc/on-calls"
+        );
+    }
+}
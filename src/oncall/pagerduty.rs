@@ -0,0 +1,90 @@
+//! Look up who is on call via PagerDuty's REST API.
+//!
+//! <https://developer.pagerduty.com/api-reference/3a6b910f11050-list-all-of-the-on-calls>
+
+use super::OnCallError;
+use serde::Deserialize;
+use std::fmt;
+
+/// The base URL of PagerDuty's REST API.
+pub const API_BASE: &str = "https://api.pagerduty.com";
+
+/// A newtype wrapper around a PagerDuty API token.
+#[derive(Clone)]
+pub struct PagerDutyToken(pub String);
+
+impl fmt::Debug for PagerDutyToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PagerDutyToken([REDACTED])")
+    }
+}
+
+/// Holds a client request pool and an API token against a base URL.
+#[derive(Clone)]
+pub struct PagerDutyClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: PagerDutyToken,
+}
+
+/// <https://developer.pagerduty.com/api-reference/3a6b910f11050-list-all-of-the-on-calls>
+#[derive(Deserialize)]
+struct OnCallsResponse {
+    oncalls: Vec<OnCall>,
+}
+
+#[derive(Deserialize)]
+struct OnCall {
+    /// Only includes the email address when users are requested.
+    user: User,
+}
+
+#[derive(Deserialize)]
+struct User {
+    email: String,
+}
+
+impl PagerDutyClient {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [API_BASE].
+    pub fn new(base_url: String, token: PagerDutyToken) -> Self {
+        PagerDutyClient {
+            client: reqwest::Client::new(),
+            base_url,
+            token,
+        }
+    }
+
+    /// Get the email address of whoever is currently on call for a schedule,
+    /// referenced by its ID.
+    pub async fn get_on_call_email(&self, schedule_id: &str) -> Result<String, OnCallError> {
+        let res: OnCallsResponse = self
+            .client
+            .get(self.base_url.clone() + "/oncalls")
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Token token={}", self.token.0),
+            )
+            .header(
+                reqwest::header::ACCEPT,
+                "application/vnd.pagerduty+json;version=2",
+            )
+            .query(&[
+                ("schedule_ids[]", schedule_id),
+                ("include[]", "users"),
+                // The first escalation level is who we want.
+                ("earliest", "true"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        res.oncalls
+            .into_iter()
+            .next()
+            .map(|x| x.user.email)
+            .ok_or_else(|| OnCallError::NobodyOnCall(schedule_id.to_owned()))
+    }
+}
@@ -0,0 +1,95 @@
+//! Recover from panics in handlers, responding with a 500 rather than dropping
+//! the connection, and make sure somebody hears about it.
+//!
+//! Each panic is logged and counted as `mercury_panics_total`. The ops channel,
+//! if there is one, is notified at most once per [NOTIFY_INTERVAL] so that a
+//! panic on a hot path doesn't flood it.
+
+use crate::{delivery::notify_ops_channel, router::Deps};
+use axum::{
+    body::Body,
+    http::{Response, StatusCode},
+};
+use chrono::Utc;
+use std::{
+    any::Any,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+};
+use tower_http::catch_panic::{CatchPanicLayer, ResponseForPanic};
+use tracing::error;
+
+/// The minimum number of seconds between ops channel notifications.
+pub const NOTIFY_INTERVAL: i64 = 60;
+
+/// Responds to panics as per the module documentation.
+#[derive(Clone)]
+pub struct PanicHandler {
+    deps: Deps,
+    /// When the ops channel was last notified, as a Unix timestamp.
+    last_notified: Arc<AtomicI64>,
+}
+
+/// Instantiate a layer catching panics in the services it wraps.
+pub fn catch_panic(deps: Deps) -> CatchPanicLayer<PanicHandler> {
+    CatchPanicLayer::custom(PanicHandler {
+        deps,
+        last_notified: Arc::new(AtomicI64::new(0)),
+    })
+}
+
+/// The message a panic was raised with, if it's a string as it almost always
+/// is.
+fn panic_message(err: &(dyn Any + Send)) -> &str {
+    err.downcast_ref::<&str>()
+        .copied()
+        .or_else(|| err.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+impl ResponseForPanic for PanicHandler {
+    type ResponseBody = Body;
+
+    fn response_for_panic(&mut self, err: Box<dyn Any + Send + 'static>) -> Response<Body> {
+        let msg = panic_message(err.as_ref()).to_owned();
+        error!("Request handler panicked: {}", msg);
+
+        self.deps.metrics.panics.inc();
+
+        let now = Utc::now().timestamp();
+        let last = self.last_notified.load(Ordering::Relaxed);
+        let due = now - last >= NOTIFY_INTERVAL
+            && self
+                .last_notified
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok();
+
+        if let (true, Some(channel)) = (due, self.deps.ops_channel.clone()) {
+            let deps = self.deps.clone();
+            let desc = format!("A request handler panicked: {}", msg);
+
+            tokio::spawn(async move { notify_ops_channel(&deps, &channel, desc).await });
+        }
+
+        let mut res = Response::new(Body::empty());
+        *res.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_message() {
+        let msg =
+            |f: fn()| panic_message(std::panic::catch_unwind(f).unwrap_err().as_ref()).to_owned();
+
+        assert_eq!(msg(|| panic!("static")), "static");
+        assert_eq!(msg(|| panic!("formatted {}", 1)), "formatted 1");
+        assert_eq!(msg(|| std::panic::panic_any(1)), "unknown panic");
+    }
+}
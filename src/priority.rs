@@ -0,0 +1,355 @@
+//! Prioritise outbound messages so that, when Slack's rate limits leave us
+//! with a backlog, crash alerts jump ahead of less urgent messages such as
+//! replays and status updates.
+//!
+//! Deliveries take turns to post, each turn going to the highest priority lane
+//! with anyone waiting, and on a first come first served basis within a lane.
+//! Should Slack rate limit us, every lane is paused for as long as it asks,
+//! after which the backlog drains in order of priority.
+//!
+//! Whilst paused, or once the backlog's grown beyond a limit, inbound requests
+//! which would deliver anything but a critical message are turned away with a
+//! `429 Too Many Requests` status and a `Retry-After` header estimating how
+//! long the backlog will take to drain, so that well-behaved clients back off
+//! rather than compounding it.
+
+use crate::{
+    delivery::Source,
+    slack::{Message, Severity},
+};
+use axum::{
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// How many messages may be waiting to post before inbound requests are
+/// turned away.
+pub const DEFAULT_MAX_BACKLOG: usize = 50;
+
+/// Roughly how long each waiting message takes to post, Slack's rate limit
+/// being about one message per second.
+const PER_MESSAGE: Duration = Duration::from_secs(1);
+
+/// How urgently a message should be delivered, most urgent first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Critical,
+    Normal,
+    /// Messages nobody's waiting on, such as replays and status updates.
+    Bulk,
+}
+
+impl Priority {
+    /// Determine a message's priority from its severity and origin.
+    pub fn of(msg: &Message, source: Source) -> Self {
+        match (source, msg.severity) {
+            (Source::Replay, _) => Priority::Bulk,
+            (_, Some(Severity::Critical)) => Priority::Critical,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// Whether someone's currently taking their turn.
+    busy: bool,
+    /// How many are waiting in each lane, indexed by priority.
+    waiting: [usize; 3],
+    /// Whether Slack has asked us to hold off.
+    paused_until: Option<Instant>,
+}
+
+/// Hands out turns to post in order of priority, safe to share across
+/// requests.
+pub struct Lanes {
+    state: Mutex<State>,
+    /// Woken whenever a turn ends or someone stops waiting.
+    notify: Notify,
+    /// How many messages may be waiting before we apply backpressure.
+    max_backlog: usize,
+}
+
+impl Default for Lanes {
+    fn default() -> Self {
+        Lanes::new(DEFAULT_MAX_BACKLOG)
+    }
+}
+
+/// A turn to post, which ends when dropped.
+pub struct Turn<'a> {
+    lanes: &'a Lanes,
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.lanes.state.lock().unwrap().busy = false;
+        self.lanes.notify.notify_waiters();
+    }
+}
+
+/// A place in a lane, given up when dropped, including if the wait is
+/// cancelled.
+struct Waiting<'a> {
+    lanes: &'a Lanes,
+    priority: Priority,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.lanes.state.lock().unwrap().waiting[self.priority as usize] -= 1;
+        self.lanes.notify.notify_waiters();
+    }
+}
+
+impl Lanes {
+    pub fn new(max_backlog: usize) -> Self {
+        Lanes {
+            state: Mutex::new(State::default()),
+            notify: Notify::new(),
+            max_backlog,
+        }
+    }
+
+    /// How many messages may be waiting before we apply backpressure.
+    pub fn max_backlog(&self) -> usize {
+        self.max_backlog
+    }
+
+    /// Wait for a turn to post at a priority.
+    pub async fn turn(&self, priority: Priority) -> Turn<'_> {
+        self.state.lock().unwrap().waiting[priority as usize] += 1;
+        let _waiting = Waiting {
+            lanes: self,
+            priority,
+        };
+
+        loop {
+            // Registered before checking the state so as not to miss a wakeup.
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let paused_for = {
+                let mut s = self.state.lock().unwrap();
+                let now = Instant::now();
+
+                match s.paused_until.filter(|x| *x > now) {
+                    Some(x) => Some(x - now),
+                    None if !s.busy && s.waiting[..priority as usize].iter().all(|n| *n == 0) => {
+                        s.busy = true;
+
+                        return Turn { lanes: self };
+                    }
+                    None => None,
+                }
+            };
+
+            match paused_for {
+                Some(x) => tokio::select! {
+                    _ = notified => {},
+                    _ = tokio::time::sleep(x) => {},
+                },
+                None => notified.await,
+            }
+        }
+    }
+
+    /// Hold off every lane for a while, for example at Slack's request.
+    pub fn pause(&self, x: Duration) {
+        let mut s = self.state.lock().unwrap();
+        let until = Instant::now() + x;
+
+        s.paused_until = Some(s.paused_until.map_or(until, |y| y.max(until)));
+    }
+
+    /// How long a client should wait before retrying a request which would
+    /// deliver a message at a priority, if we're saturated. Critical messages
+    /// are never turned away.
+    pub fn backpressure(&self, priority: Priority) -> Option<Duration> {
+        if priority == Priority::Critical {
+            return None;
+        }
+
+        let s = self.state.lock().unwrap();
+        let paused_for = s
+            .paused_until
+            .map(|x| x.saturating_duration_since(Instant::now()))
+            .filter(|x| !x.is_zero());
+        let backlog = s.waiting.iter().sum::<usize>();
+
+        if paused_for.is_none() && backlog < self.max_backlog {
+            return None;
+        }
+
+        let drain = PER_MESSAGE.saturating_mul(backlog.try_into().unwrap_or(u32::MAX));
+        Some(paused_for.unwrap_or_default() + drain)
+    }
+}
+
+/// Turn a request away for a while, as per [Lanes::backpressure].
+pub fn too_many_requests(retry_after: Duration) -> Response {
+    // Whole seconds, rounded up so as not to invite a premature retry.
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, secs.max(1).to_string())],
+        "Too many messages are awaiting delivery, retry later",
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::channel::ChannelName;
+    use std::sync::Arc;
+
+    fn message(severity: Option<Severity>) -> Message {
+        Message {
+            severity,
+            ..Message::new(ChannelName("any".into()), "title", "desc")
+        }
+    }
+
+    #[test]
+    fn test_priority_of() {
+        let critical = message(Some(Severity::Critical));
+
+        assert_eq!(Priority::of(&critical, Source::Heroku), Priority::Critical);
+        assert_eq!(Priority::of(&critical, Source::Replay), Priority::Bulk);
+        assert_eq!(
+            Priority::of(&message(Some(Severity::Warning)), Source::Api),
+            Priority::Normal
+        );
+        assert_eq!(Priority::of(&message(None), Source::Api), Priority::Normal);
+    }
+
+    /// Queue a waiter in a lane, reporting when it gets its turn.
+    fn spawn_waiter(
+        lanes: &Arc<Lanes>,
+        priority: Priority,
+        tx: &tokio::sync::mpsc::UnboundedSender<Priority>,
+    ) {
+        let (lanes, tx) = (lanes.clone(), tx.clone());
+
+        tokio::spawn(async move {
+            let _turn = lanes.turn(priority).await;
+            tx.send(priority).unwrap();
+        });
+    }
+
+    async fn settle() {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn test_turn_order() {
+        let lanes = Arc::new(Lanes::default());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let first = lanes.turn(Priority::Bulk).await;
+        for p in [Priority::Bulk, Priority::Normal, Priority::Critical] {
+            spawn_waiter(&lanes, p, &tx);
+        }
+        settle().await;
+        assert!(rx.try_recv().is_err());
+
+        drop(first);
+        let mut order = Vec::new();
+        for _ in 0..3 {
+            order.push(rx.recv().await.unwrap());
+        }
+        assert_eq!(
+            order,
+            vec![Priority::Critical, Priority::Normal, Priority::Bulk]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_wait() {
+        let lanes = Arc::new(Lanes::default());
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let first = lanes.turn(Priority::Normal).await;
+        // A critical waiter which gives up mustn't hold back the others.
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(10), lanes.turn(Priority::Critical));
+        assert!(cancelled.await.is_err());
+
+        spawn_waiter(&lanes, Priority::Bulk, &tx);
+        drop(first);
+        assert_eq!(rx.recv().await, Some(Priority::Bulk));
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let lanes = Lanes::default();
+
+        lanes.pause(Duration::from_millis(50));
+        let start = Instant::now();
+        drop(lanes.turn(Priority::Critical).await);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        // Pauses only ever extend.
+        lanes.pause(Duration::from_millis(50));
+        lanes.pause(Duration::from_millis(1));
+        let start = Instant::now();
+        drop(lanes.turn(Priority::Critical).await);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let lanes = Arc::new(Lanes::new(2));
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+
+        assert_eq!(lanes.backpressure(Priority::Normal), None);
+
+        let _first = lanes.turn(Priority::Normal).await;
+        spawn_waiter(&lanes, Priority::Normal, &tx);
+        settle().await;
+        assert_eq!(lanes.backpressure(Priority::Normal), None);
+
+        spawn_waiter(&lanes, Priority::Bulk, &tx);
+        settle().await;
+        assert_eq!(
+            lanes.backpressure(Priority::Normal),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(lanes.backpressure(Priority::Critical), None);
+
+        // Pauses apply backpressure regardless of the backlog.
+        let lanes = Lanes::new(2);
+        lanes.pause(Duration::from_secs(30));
+        let x = lanes.backpressure(Priority::Normal).unwrap();
+        assert!(x > Duration::from_secs(29) && x <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_too_many_requests() {
+        let retry_after = |x| {
+            too_many_requests(x)
+                .headers()
+                .get(RETRY_AFTER)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+
+        assert_eq!(
+            too_many_requests(Duration::ZERO).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(retry_after(Duration::ZERO), "1");
+        assert_eq!(retry_after(Duration::from_millis(2500)), "3");
+        assert_eq!(retry_after(Duration::from_secs(30)), "30");
+    }
+}
@@ -0,0 +1,246 @@
+//! Find the real client IP of requests arriving via reverse proxies, such as
+//! Heroku's router, for logs and anything else which cares who's calling.
+//!
+//! Forwarding headers are trivially spoofed, so they're only believed if the
+//! immediate peer is a trusted proxy, configured via `$TRUSTED_PROXIES` as a
+//! comma-separated list of IP addresses and CIDR ranges. `*` trusts the
+//! immediate peer whoever it is, which is necessary on Heroku as its router
+//! has no fixed addresses.
+//!
+//! The `Forwarded` header is preferred, falling back to `X-Forwarded-For`.
+//! Addresses are read right to left, each hop having appended its peer, and
+//! the first which isn't a trusted proxy is the client.
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::FORWARDED, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+const X_FORWARDED_FOR: &str = "X-Forwarded-For";
+
+/// The real IP of the client making a request, inserted into request
+/// extensions by [client_ip].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// The proxies whose forwarding headers are believed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct TrustedProxies {
+    /// Whether to trust the immediate peer regardless of its address.
+    any_peer: bool,
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// Parse trusted proxies from their environment variable representation.
+    ///
+    /// ```
+    /// let x = TrustedProxies::parse("10.0.0.0/8, 127.0.0.1, *");
+    /// ```
+    pub fn parse(x: &str) -> Result<Self, String> {
+        let mut res = TrustedProxies::default();
+
+        for x in x.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            match x {
+                "*" => res.any_peer = true,
+                x => res.nets.push(
+                    x.parse::<IpNet>()
+                        .or_else(|_| x.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|_| format!("invalid address or range: {}", x))?,
+                ),
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn contains(&self, ip: &IpAddr) -> bool {
+        self.nets.iter().any(|x| x.contains(ip))
+    }
+
+    /// Find the client IP given the immediate peer and the request's headers.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.any_peer && !self.contains(&peer) {
+            return peer;
+        }
+
+        let hops = match forwarded_for(headers) {
+            Some(xs) => xs,
+            None => x_forwarded_for(headers),
+        };
+
+        // Malformed hops are as good as untrusted, so we stop at them rather
+        // than skipping past to addresses the client could have supplied.
+        let mut client = peer;
+        for hop in hops.into_iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client = ip;
+                    if !self.contains(&ip) {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        }
+
+        client
+    }
+}
+
+/// The `for` addresses in any `Forwarded` headers, if present, in order.
+///
+/// <https://www.rfc-editor.org/rfc/rfc7239>
+fn forwarded_for(headers: &HeaderMap) -> Option<Vec<Option<IpAddr>>> {
+    let xs: Vec<_> = headers
+        .get_all(FORWARDED)
+        .iter()
+        .flat_map(|x| x.to_str().unwrap_or_default().split(','))
+        .filter_map(|elem| {
+            elem.split(';').find_map(|pair| {
+                let (k, v) = pair.trim().split_once('=')?;
+                k.eq_ignore_ascii_case("for").then(|| parse_node(v))
+            })
+        })
+        .collect();
+
+    (!xs.is_empty()).then_some(xs)
+}
+
+/// The addresses in any `X-Forwarded-For` headers, in order.
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .flat_map(|x| x.to_str().unwrap_or_default().split(','))
+        .map(|x| x.trim().parse().ok())
+        .collect()
+}
+
+/// Parse a `Forwarded` node, which may be quoted, bracketed, and include a
+/// port, for example `"[2001:db8::1]:4711"`.
+fn parse_node(x: &str) -> Option<IpAddr> {
+    let x = x.trim().trim_matches('"');
+
+    match x.strip_prefix('[') {
+        Some(x) => x.split_once(']')?.0.parse().ok(),
+        None => x
+            .parse()
+            .ok()
+            .or_else(|| x.parse::<SocketAddr>().ok().map(|x| x.ip())),
+    }
+}
+
+/// Middleware inserting the [ClientIp] into request extensions. Requires the
+/// server to provide [ConnectInfo], without which it's a no-op.
+pub async fn client_ip(
+    State(proxies): State<Arc<TrustedProxies>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
+        let ip = proxies.resolve(peer.ip(), req.headers());
+        req.extensions_mut().insert(ClientIp(ip));
+    }
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(x: &str) -> IpAddr {
+        x.parse().unwrap()
+    }
+
+    fn headers(xs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut res = HeaderMap::new();
+        for (k, v) in xs {
+            res.append(*k, v.parse().unwrap());
+        }
+        res
+    }
+
+    #[test]
+    fn test_parse() {
+        let x = TrustedProxies::parse(" 10.0.0.0/8,, ::1, *").unwrap();
+
+        assert!(x.any_peer);
+        assert_eq!(
+            x.nets,
+            vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+        );
+        assert_eq!(TrustedProxies::parse(""), Ok(TrustedProxies::default()));
+        assert!(TrustedProxies::parse("10.0.0.0/33").is_err());
+        assert!(TrustedProxies::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn test_resolve_untrusted() {
+        let x = TrustedProxies::parse("10.0.0.0/8").unwrap();
+        let hs = headers(&[(X_FORWARDED_FOR, "1.1.1.1")]);
+
+        assert_eq!(x.resolve(ip("2.2.2.2"), &hs), ip("2.2.2.2"));
+        assert_eq!(
+            TrustedProxies::default().resolve(ip("10.0.0.1"), &hs),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_resolve_x_forwarded_for() {
+        let x = TrustedProxies::parse("10.0.0.0/8").unwrap();
+
+        // The client can't spoof its way past the first untrusted hop.
+        let hs = headers(&[(X_FORWARDED_FOR, "6.6.6.6, 1.1.1.1, 10.0.0.2")]);
+        assert_eq!(x.resolve(ip("10.0.0.1"), &hs), ip("1.1.1.1"));
+
+        let hs = headers(&[(X_FORWARDED_FOR, "6.6.6.6, nope, 10.0.0.2")]);
+        assert_eq!(x.resolve(ip("10.0.0.1"), &hs), ip("10.0.0.2"));
+
+        let hs = headers(&[(X_FORWARDED_FOR, "1.1.1.1"), (X_FORWARDED_FOR, "10.0.0.2")]);
+        assert_eq!(x.resolve(ip("10.0.0.1"), &hs), ip("1.1.1.1"));
+
+        assert_eq!(x.resolve(ip("10.0.0.1"), &HeaderMap::new()), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn test_resolve_any_peer() {
+        let x = TrustedProxies::parse("*").unwrap();
+        let hs = headers(&[(X_FORWARDED_FOR, "6.6.6.6, 1.1.1.1")]);
+
+        assert_eq!(x.resolve(ip("3.3.3.3"), &hs), ip("1.1.1.1"));
+    }
+
+    #[test]
+    fn test_resolve_forwarded() {
+        let x = TrustedProxies::parse("*, 10.0.0.0/8").unwrap();
+        let hs = headers(&[
+            (
+                "Forwarded",
+                r#"for=6.6.6.6, for="[2001:db8::1]:4711";proto=https, For=10.0.0.2"#,
+            ),
+            (X_FORWARDED_FOR, "1.1.1.1"),
+        ]);
+
+        assert_eq!(x.resolve(ip("10.0.0.1"), &hs), ip("2001:db8::1"));
+    }
+
+    #[test]
+    fn test_parse_node() {
+        assert_eq!(parse_node("1.1.1.1"), Some(ip("1.1.1.1")));
+        assert_eq!(parse_node("\"1.1.1.1:80\""), Some(ip("1.1.1.1")));
+        assert_eq!(parse_node("\"[::1]:80\""), Some(ip("::1")));
+        assert_eq!(parse_node("[::1]"), Some(ip("::1")));
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Redact sensitive values from text before it's logged, such as access
+//! tokens, secrets, and email addresses.
+//!
+//! Redaction is pattern-based and best effort. It's intended to make debug
+//! logging safe enough to enable in production, not to be relied upon for
+//! arbitrary data.
+//!
+//! Payloads destined to become test fixtures are instead [anonymise]d, which
+//! swaps email addresses for a placeholder so that they remain valid.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// What sensitive values are replaced with.
+const REDACTED: &str = "[REDACTED]";
+
+/// Patterns matching sensitive values, and what they're replaced with, applied
+/// in order.
+static PATTERNS: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+    // Values which are sensitive wherever they appear.
+    let values = [
+        // Slack tokens.
+        r"xox[a-z]-[A-Za-z0-9-]+",
+        // GitHub tokens.
+        r"gh[pousr]_[A-Za-z0-9]+",
+        r"github_pat_[A-Za-z0-9_]+",
+        // Bearer tokens, for example in echoed headers.
+        r"(?i)bearer\s+[^\s&,]+",
+    ]
+    .map(|x| (x.to_owned(), REDACTED));
+
+    // Fields whose values are sensitive irrespective of their format.
+    let fields = [
+        // JSON values may be strings or arrays thereof, and strings may contain
+        // escaped quotes.
+        (
+            format!(
+                r#"(?i)("{}"\s*:\s*)(?:{}|\[(?:[^\]"]|{})*\])"#,
+                SENSITIVE_KEY, JSON_STRING, JSON_STRING
+            ),
+            r#"${1}"[REDACTED]""#,
+        ),
+        (
+            format!(r"(?i)\b({}=)[^&\s]+", SENSITIVE_KEY),
+            "${1}[REDACTED]",
+        ),
+    ];
+
+    values
+        .into_iter()
+        .chain(fields)
+        .map(|(x, replacement)| (Regex::new(&x).unwrap(), replacement))
+        .collect()
+});
+
+/// Matches email addresses, including URL-encoded ones in form bodies.
+static EMAIL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"[A-Za-z0-9._%+-]+(@|%40)[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+
+/// Matches a JSON string, including any escaped characters.
+const JSON_STRING: &str = r#""(?:[^"\\]|\\.)*""#;
+
+/// Matches the name of any field whose value is sensitive irrespective of its
+/// format.
+const SENSITIVE_KEY: &str = r"\w*(?:token|secret|password|signature)\w*";
+
+/// Redact sensitive values from arbitrary text, including JSON and URL-encoded
+/// payloads.
+///
+/// ```
+/// assert_eq!(
+///     redact("channel=fp&cc=hodor%40unsplash.com"),
+///     "channel=fp&cc=[REDACTED]"
+/// );
+/// ```
+pub fn redact(x: &str) -> String {
+    scrub(x, REDACTED)
+}
+
+/// Like [redact], except email addresses are replaced with a placeholder
+/// address, encoded likewise.
+///
+/// ```
+/// assert_eq!(
+///     anonymise("channel=fp&cc=hodor%40unsplash.com"),
+///     "channel=fp&cc=user%40example.com"
+/// );
+/// ```
+pub fn anonymise(x: &str) -> String {
+    scrub(x, "user${1}example.com")
+}
+
+fn scrub(x: &str, email: &str) -> String {
+    let x = EMAIL.replace_all(x, email).into_owned();
+
+    PATTERNS.iter().fold(x, |acc, (re, replacement)| {
+        re.replace_all(&acc, *replacement).into_owned()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_patterns() {
+        assert_eq!(redact("xoxb-123-abc"), REDACTED);
+        assert_eq!(redact("ghp_abc123 and more"), "[REDACTED] and more");
+        assert_eq!(
+            redact("Authorization: Bearer foobar"),
+            "Authorization: [REDACTED]"
+        );
+        assert_eq!(redact("by hodor@unsplash.com."), "by [REDACTED].");
+        assert_eq!(redact("cc=hodor%40unsplash.com&x=y"), "cc=[REDACTED]&x=y");
+    }
+
+    #[test]
+    fn test_redact_fields() {
+        assert_eq!(
+            redact(r#"{"slack_token": "foo", "api_tokens":["bar"], "channel":"fp"}"#),
+            r#"{"slack_token": "[REDACTED]", "api_tokens":"[REDACTED]", "channel":"fp"}"#
+        );
+        assert_eq!(
+            redact(r#"{"token":"ab\"cd\\","secrets":["e]f\"g"],"channel":"fp"}"#),
+            r#"{"token":"[REDACTED]","secrets":"[REDACTED]","channel":"fp"}"#
+        );
+        assert_eq!(
+            redact("channel=fp&client_secret=foo&title=bar"),
+            "channel=fp&client_secret=[REDACTED]&title=bar"
+        );
+    }
+
+    #[test]
+    fn test_anonymise() {
+        assert_eq!(
+            anonymise(r#"{"email":"hodor@unsplash.com","token":"foo"}"#),
+            r#"{"email":"user@example.com","token":"[REDACTED]"}"#
+        );
+        assert_eq!(
+            anonymise("cc=hodor%40unsplash.com&x=xoxb-123"),
+            "cc=user%40example.com&x=[REDACTED]"
+        );
+    }
+
+    #[test]
+    fn test_redact_noop() {
+        let x = r#"{"channel":"fp","title":"Deploy abc123","desc":"All good"}"#;
+
+        assert_eq!(redact(x), x);
+    }
+
+    quickcheck! {
+      fn test_redact_never_panics(x: String) -> () {
+          redact(&x);
+      }
+    }
+}
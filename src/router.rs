@@ -3,40 +3,315 @@
 //! The following routes are supported:
 //!
 //! - GET: `/api/v1/health`
+//! - GET: `/api/v1/health/deep`
+//! - GET: `/api/v1/metrics`
 //! - POST: `/api/v1/slack`
+//! - GET: `/api/v1/slack/preview`
+//! - POST: `/api/v1/slack/explain`
+//! - POST: `/api/v1/slack/topic`
+//! - POST: `/api/v1/slack/interactivity`
 //! - POST: `/api/v1/heroku/hook`
+//! - POST: `/api/v1/heroku/hook/explain`
+//! - GET: `/api/v1/feeds/:source.atom`
+//! - GET: `/api/v1/stream`
+//! - GET: `/api/v1/stream/ws`
+//! - GET: `/api/v1/schemas`
+//! - GET: `/api/v1/schemas/message.json`
+//! - GET: `/api/v1/schemas/heroku/hook.json`
+//! - GET: `/api/v1/schemas/heroku/release.json`
+//! - GET: `/api/v1/schemas/heroku/dyno.json`
+//! - GET, PUT, DELETE: `/api/v1/admin/read-only`
+//! - GET: `/api/v1/admin/ingestion`
+//! - PUT, DELETE: `/api/v1/admin/ingestion/:source`
+//! - PUT: `/api/v1/admin/secrets`
+//! - POST: `/api/v1/admin/selftest`
+//! - GET: `/api/v1/admin/stats`
+//! - POST: `/api/v1/admin/graphql`
+//! - GET: `/api/v1/admin/ui`
+//! - GET: `/api/v1/admin/audit/export`
+//! - GET: `/api/v1/admin/audit/:id`
+//! - POST: `/api/v1/admin/audit/:id/replay`
+//! - GET: `/api/v1/admin/captures/:source`
+//! - GET, PUT, DELETE: `/api/v1/admin/chaos`, with the `chaos` feature
 
 use crate::{
-    heroku::{router::heroku_router, HerokuSecret},
-    slack::{router::slack_router, SlackAccessToken, SlackClient},
+    admin::{router::admin_router, AdminToken},
+    audit::AuditLog,
+    auth::ApiToken,
+    budget::NoiseBudgets,
+    cache::{self, ResponseCache},
+    capture::{capture_from, Captures},
+    console::Console,
+    datadog::Datadog,
+    debug::log_inbound,
+    delivery::{Shadow, Source},
+    escalation::Escalations,
+    eventbridge::EventBridge,
+    eventlog::EventLog,
+    feed::router::feed_router,
+    fixture::{record_from, Fixtures},
+    github::{GitHubClient, GitHubToken},
+    grafana::Grafana,
+    health::deep_health_handler,
+    heartbeat::Heartbeats,
+    heroku::{
+        calendar::Calendar, description::DescriptionPatterns, emoji::EmojiRules, jira::Jira,
+        linear::Linear, otto::Otto, push::Pusher, router::heroku_router, runbook::Runbooks,
+        AppRoutes, HerokuSecret, ReleaseCommitMap,
+    },
+    honeycomb::Honeycomb,
+    ingestion::Ingestion,
+    locale::ChannelLocales,
+    matrix::MatrixClient,
+    meta::MetaAlerts,
+    metrics::Metrics,
+    panic,
+    priority::Lanes,
+    proxy::{self, TrustedProxies},
+    schema::schema_router,
+    signing::SigningSecrets,
+    slack::{
+        channel::ChannelName, history::CallHistory, interactivity::SlackSigningSecret,
+        router::slack_router, SlackAccessToken, SlackClient,
+    },
+    sms::Sms,
+    stats::Stats,
+    status::StatusBoards,
+    statuspage::StatuspageClient,
+    stream::{stream_router, EventStream},
+    telemetry::{self, TraceFilter},
+    threading::Threads,
+    zulip::ZulipClient,
+};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware,
+    response::Response,
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
 };
-use axum::{http::StatusCode, routing::get, Router};
-use std::sync::Arc;
 use tokio::sync::Mutex;
-use tower_http::trace::{self, TraceLayer};
-use tracing::Level;
+use tower_http::trace::{self, OnResponse, TraceLayer};
+use tracing::{Level, Span};
+
+#[cfg(feature = "chaos")]
+use crate::slack::chaos::Chaos;
 
 /// Dependencies shared by routes across requests.
 #[derive(Clone)]
 pub struct Deps {
     pub slack_client: Arc<Mutex<SlackClient>>,
-    pub slack_token: SlackAccessToken,
-    pub heroku_secret: Option<HerokuSecret>,
+    /// Shared with the client. See [crate::slack::history].
+    pub slack_history: Arc<CallHistory>,
+    /// Shared with the client. See [crate::slack::chaos].
+    #[cfg(feature = "chaos")]
+    pub slack_chaos: Arc<Chaos>,
+    /// Rotatable at runtime, as are the other secrets.
+    pub slack_token: Arc<ArcSwap<SlackAccessToken>>,
+    /// See [crate::slack::expiry].
+    pub slack_token_expires_at: Arc<ArcSwapOption<DateTime<Utc>>>,
+    /// Any of which are accepted, typically at most one outside of rotation.
+    pub heroku_secrets: Arc<ArcSwap<Vec<HerokuSecret>>>,
+    pub github_client: Arc<GitHubClient>,
+    pub github_token: Option<GitHubToken>,
+    pub release_commits: Arc<Mutex<ReleaseCommitMap>>,
+    /// See [crate::heroku::routing].
+    pub heroku_app_routes: Arc<AppRoutes>,
+    /// Routes referenced by name. See [crate::heroku::routing].
+    pub heroku_named_routes: Arc<AppRoutes>,
+    /// See [crate::heroku::description].
+    pub heroku_description_patterns: Arc<DescriptionPatterns>,
+    /// See [crate::heroku::runbook].
+    pub heroku_runbooks: Arc<Runbooks>,
+    /// See [crate::heroku::emoji].
+    pub heroku_emoji: Arc<EmojiRules>,
+    /// See [crate::heroku::push].
+    pub heroku_push: Option<Arc<Pusher>>,
+    /// See [crate::heroku::jira].
+    pub heroku_jira: Option<Arc<Jira>>,
+    /// See [crate::heroku::linear].
+    pub heroku_linear: Option<Arc<Linear>>,
+    /// See [crate::heroku::calendar].
+    pub heroku_calendar: Option<Arc<Calendar>>,
+    /// See [crate::heroku::otto].
+    pub heroku_otto: Option<Arc<Otto>>,
+    /// See [crate::locale].
+    pub channel_locales: Arc<ChannelLocales>,
+    pub admin_token: Option<AdminToken>,
+    /// Whether onward delivery is currently suppressed.
+    pub read_only: Arc<AtomicBool>,
+    pub shadow: Option<Shadow>,
+    pub signing_secrets: SigningSecrets,
+    pub api_tokens: Arc<ArcSwap<Vec<ApiToken>>>,
+    /// Whether to additionally accept the Slack access token for inbound
+    /// requests, as was historically the only option.
+    pub slack_token_compat: bool,
+    /// Whether to log redacted inbound payloads. See [crate::debug].
+    pub debug_payloads: bool,
+    /// See [crate::capture].
+    pub captures: Option<Arc<Captures>>,
+    /// See [crate::fixture].
+    pub fixtures: Option<Arc<Fixtures>>,
+    /// See [crate::console].
+    pub console: Arc<Console>,
+    pub metrics: Metrics,
+    /// See [crate::cache].
+    pub response_cache: Arc<ResponseCache>,
+    /// Where to send canary messages, if anywhere.
+    pub selftest_channel: Option<ChannelName>,
+    /// Where to notify operators of deliveries which need their attention, if
+    /// anywhere. See [crate::delivery].
+    pub ops_channel: Option<ChannelName>,
+    pub audit: Arc<AuditLog>,
+    pub stats: Arc<Stats>,
+    /// See [crate::stream].
+    pub events: EventStream,
+    /// See [crate::budget].
+    pub noise_budgets: Option<Arc<NoiseBudgets>>,
+    /// See [crate::priority].
+    pub lanes: Arc<Lanes>,
+    /// See [crate::ingestion].
+    pub ingestion: Arc<Ingestion>,
+    /// See [crate::heartbeat].
+    pub heartbeats: Option<Arc<Heartbeats>>,
+    /// See [crate::threading].
+    pub threads: Option<Arc<Threads>>,
+    /// See [crate::status].
+    pub status_boards: Option<Arc<StatusBoards>>,
+    /// Whether and how to escalate critical messages. See [crate::escalation].
+    pub escalations: Option<Arc<Escalations>>,
+    /// Authenticates interactions from Slack.
+    pub slack_signing_secret: Option<SlackSigningSecret>,
+    /// Requests to exclude from access logs.
+    pub trace_filter: Arc<TraceFilter>,
+    /// Alerts about repeated delivery failures. See [crate::meta].
+    pub meta_alerts: Option<Arc<MetaAlerts>>,
+    /// See [crate::sms].
+    pub sms: Option<Arc<Sms>>,
+    /// See [crate::matrix].
+    pub matrix: Option<Arc<MatrixClient>>,
+    /// See [crate::zulip].
+    pub zulip: Option<Arc<ZulipClient>>,
+    /// See [crate::statuspage].
+    pub statuspage: Option<Arc<StatuspageClient>>,
+    /// See [crate::eventbridge].
+    pub eventbridge: Option<Arc<EventBridge>>,
+    /// See [crate::grafana].
+    pub grafana: Option<Arc<Grafana>>,
+    /// See [crate::datadog].
+    pub datadog: Option<Arc<Datadog>>,
+    /// See [crate::honeycomb].
+    pub honeycomb: Option<Arc<Honeycomb>>,
+    /// See [crate::eventlog].
+    pub event_log: Option<Arc<EventLog>>,
+    /// Sources whose payloads are rejected if they contain anything we don't
+    /// recognise, rather than parsed on a best effort basis.
+    pub strict_sources: Arc<Vec<Source>>,
+    /// How far signed requests' timestamps may drift from our own clock. See
+    /// [crate::signing].
+    pub max_signature_skew: Duration,
+    /// Whose forwarding headers to believe. See [crate::proxy].
+    pub trusted_proxies: Arc<TrustedProxies>,
+}
+
+/// Which routes a router serves, allowing operational routes to be served on a
+/// separate, internal-only listener.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Routes {
+    All,
+    /// Everything but the internal routes.
+    Public,
+    /// Deep health checks, metrics, and administration, alongside the shallow
+    /// health check.
+    Internal,
 }
 
-/// Instantiate a new router with tracing.
+/// Instantiate a new router serving every route with tracing.
 pub fn new(deps: Deps) -> Router {
+    with_routes(deps, Routes::All)
+}
+
+/// Instantiate a new router serving a subset of routes with tracing.
+pub fn with_routes(deps: Deps, routes: Routes) -> Router {
+    let trace_filter = deps.trace_filter.clone();
     let trace_layer = TraceLayer::new_for_http()
-        .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
-        .on_response(trace::DefaultOnResponse::new().level(Level::INFO));
+        .make_span_with(move |req: &Request| trace_filter.make_span(req))
+        .on_response(|res: &Response, latency: Duration, span: &Span| {
+            // Excluded requests have disabled spans. See [TraceFilter].
+            if !span.is_disabled() {
+                trace::DefaultOnResponse::new()
+                    .level(Level::INFO)
+                    .on_response(res, latency, span);
+            }
+        });
+
+    let public = routes != Routes::Internal;
+    let internal = routes != Routes::Public;
+
+    let mut v1 = Router::new().route("/health", get(|| async { StatusCode::OK }));
+
+    if public {
+        v1 = v1
+            .nest(
+                "/slack",
+                capture_from(
+                    record_from(slack_router(&deps), &deps, Source::Api),
+                    &deps,
+                    Source::Api,
+                ),
+            )
+            .nest(
+                "/heroku",
+                capture_from(
+                    record_from(heroku_router(), &deps, Source::Heroku),
+                    &deps,
+                    Source::Heroku,
+                ),
+            )
+            .nest("/feeds", feed_router(&deps))
+            .nest("/stream", stream_router(&deps))
+            .nest("/schemas", schema_router());
+    }
+
+    if internal {
+        let cache = middleware::from_fn_with_state(deps.response_cache.clone(), cache::cache);
+
+        v1 = v1
+            .route("/health/deep", get(deep_health_handler).layer(cache))
+            .route(
+                "/metrics",
+                get(|State(deps): State<Deps>| async move { deps.metrics.encode() }),
+            );
+    }
+
+    // Admin routes are entirely unavailable without a token to protect them.
+    if let (true, Some(t)) = (internal, &deps.admin_token) {
+        v1 = v1.nest("/admin", admin_router(t, &deps.response_cache));
+    }
 
-    let v1 = Router::new()
-        .nest("/slack", slack_router(&deps.slack_token))
-        .nest("/heroku", heroku_router())
+    if deps.debug_payloads {
+        v1 = v1.layer(middleware::from_fn(log_inbound));
+    }
+
+    let metrics = deps.metrics.clone();
+    let trusted_proxies = deps.trusted_proxies.clone();
+
+    let v1 = v1
+        .layer(panic::catch_panic(deps.clone()))
         .with_state(deps)
+        .layer(middleware::from_fn_with_state(metrics, telemetry::track))
         .layer(trace_layer)
-        // Exclude the health check route from tracing.
-        .route("/health", get(|| async { StatusCode::OK }));
+        .layer(middleware::from_fn_with_state(
+            trusted_proxies,
+            proxy::client_ip,
+        ));
 
     let api = Router::new().nest("/v1", v1);
 
@@ -44,25 +319,104 @@ pub fn new(deps: Deps) -> Router {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::{
+        audit::DEFAULT_CAPACITY, bounded::StateMetrics, heroku::webhook::MAX_RELEASE_COMMITS,
+        tls::ClientTls,
+    };
     use axum::{
         body::Body,
         http::{Request, StatusCode},
     };
     use mockito::Matcher;
+    use std::sync::atomic::Ordering;
     use tower::{Service, ServiceExt};
 
+    pub(crate) fn deps(
+        base_slack_url: String,
+        slack_token: SlackAccessToken,
+        heroku_secret: Option<HerokuSecret>,
+    ) -> Deps {
+        let slack_client = SlackClient::new(base_slack_url);
+        let metrics = Metrics::new();
+
+        Deps {
+            slack_history: slack_client.history(),
+            #[cfg(feature = "chaos")]
+            slack_chaos: slack_client.chaos(),
+            slack_client: Arc::new(Mutex::new(slack_client)),
+            slack_token: Arc::new(ArcSwap::from_pointee(slack_token)),
+            slack_token_expires_at: Arc::new(ArcSwapOption::empty()),
+            heroku_secrets: Arc::new(ArcSwap::from_pointee(heroku_secret.into_iter().collect())),
+            github_client: Arc::new(GitHubClient::new("any".to_owned())),
+            github_token: None,
+            release_commits: Arc::new(Mutex::new(ReleaseCommitMap::new(
+                MAX_RELEASE_COMMITS,
+                StateMetrics::new(&metrics, "release_commits"),
+            ))),
+            heroku_app_routes: Arc::new(AppRoutes::new()),
+            heroku_named_routes: Arc::new(AppRoutes::new()),
+            heroku_description_patterns: Arc::new(DescriptionPatterns::default()),
+            heroku_runbooks: Arc::new(Runbooks::default()),
+            heroku_emoji: Arc::new(EmojiRules::default()),
+            heroku_push: None,
+            heroku_jira: None,
+            heroku_linear: None,
+            heroku_calendar: None,
+            heroku_otto: None,
+            channel_locales: Arc::new(ChannelLocales::default()),
+            admin_token: Some(AdminToken("admin".to_owned())),
+            read_only: Arc::new(AtomicBool::new(false)),
+            shadow: None,
+            signing_secrets: SigningSecrets::new(),
+            api_tokens: Arc::new(ArcSwap::from_pointee(Vec::new())),
+            slack_token_compat: true,
+            debug_payloads: false,
+            captures: None,
+            fixtures: None,
+            console: Arc::new(Console::default()),
+            response_cache: Arc::new(ResponseCache::new(
+                Duration::ZERO,
+                StateMetrics::new(&metrics, "responses"),
+            )),
+            metrics,
+            trace_filter: Arc::new(TraceFilter::default()),
+            strict_sources: Arc::new(Vec::new()),
+            max_signature_skew: crate::signing::DEFAULT_MAX_SKEW,
+            trusted_proxies: Arc::new(TrustedProxies::default()),
+            meta_alerts: None,
+            sms: None,
+            matrix: None,
+            zulip: None,
+            statuspage: None,
+            eventbridge: None,
+            grafana: None,
+            datadog: None,
+            honeycomb: None,
+            event_log: None,
+            selftest_channel: None,
+            ops_channel: None,
+            audit: Arc::new(AuditLog::new(DEFAULT_CAPACITY)),
+            stats: Arc::new(Stats::default()),
+            events: EventStream::default(),
+            noise_budgets: None,
+            lanes: Arc::new(Lanes::default()),
+            ingestion: Arc::new(Ingestion::default()),
+            heartbeats: None,
+            threads: None,
+            status_boards: None,
+            escalations: None,
+            slack_signing_secret: None,
+        }
+    }
+
     fn router(
         base_slack_url: String,
         slack_token: SlackAccessToken,
         heroku_secret: Option<HerokuSecret>,
     ) -> Router {
-        super::new(Deps {
-            slack_client: Arc::new(Mutex::new(SlackClient::new(base_slack_url))),
-            slack_token,
-            heroku_secret,
-        })
+        super::new(deps(base_slack_url, slack_token, heroku_secret))
     }
 
     fn router_() -> Router {
@@ -96,10 +450,327 @@ mod tests {
 
             assert_eq!(res.status(), StatusCode::NOT_FOUND);
         }
+
+        #[tokio::test]
+        async fn test_deep_health() {
+            let health_req = || {
+                Request::builder()
+                    .uri("/api/v1/health/deep")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg_req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from("channel=any&title=any&desc=any"))
+                .unwrap();
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(r#"{"ok":false,"error":"invalid_auth"}"#)
+                .create_async()
+                .await;
+
+            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+
+            let res1 = rt.call(health_req()).await.unwrap();
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(
+                plaintext_body(res1.into_body()).await,
+                r#"{"read_only":false,"slack":{"healthy":true,"last_success":null,"last_failure":null}}"#
+            );
+
+            let res2 = rt.call(msg_req).await.unwrap();
+            assert_eq!(res2.status(), StatusCode::UNAUTHORIZED);
+
+            let res3 = rt.call(health_req()).await.unwrap();
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res3.into_body()).await).unwrap();
+            assert_eq!(body["slack"]["healthy"], false);
+            assert_eq!(
+                body["slack"]["last_failure"]["method"],
+                "conversations.list"
+            );
+            assert_eq!(
+                body["slack"]["last_failure"]["error"],
+                "Slack API returned error: invalid_auth"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_schemas() {
+            let req = |uri: &str| {
+                Request::builder()
+                    .uri(format!("/api/v1/schemas{}", uri))
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let res = router_().oneshot(req("/message.json")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(body["title"], "Message");
+            assert_eq!(
+                body["required"],
+                serde_json::json!(["channel", "title", "desc"])
+            );
+
+            let res = router_()
+                .oneshot(req("/heroku/release.json"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(body["properties"]["resource"]["const"], "release");
+
+            let res = router_().oneshot(req("")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_metrics() {
+            let req = Request::builder()
+                .uri("/api/v1/metrics")
+                .body(Body::empty())
+                .unwrap();
+
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.metrics.slack_token_expiry.set(1700000000);
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body())
+                .await
+                .contains("mercury_slack_token_expiry_timestamp_seconds 1700000000"));
+        }
+
+        #[tokio::test]
+        async fn test_routes() {
+            let status = |routes, uri: &str| {
+                let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+                let deps = deps(
+                    "any".to_owned(),
+                    SlackAccessToken("foobar".to_owned()),
+                    None,
+                );
+
+                async move {
+                    with_routes(deps, routes)
+                        .oneshot(req)
+                        .await
+                        .unwrap()
+                        .status()
+                }
+            };
+
+            assert_eq!(
+                status(Routes::Public, "/api/v1/health").await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(Routes::Public, "/api/v1/schemas").await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(Routes::Public, "/api/v1/metrics").await,
+                StatusCode::NOT_FOUND
+            );
+            assert_eq!(
+                status(Routes::Public, "/api/v1/admin/stats").await,
+                StatusCode::NOT_FOUND
+            );
+
+            assert_eq!(
+                status(Routes::Internal, "/api/v1/health").await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(Routes::Internal, "/api/v1/metrics").await,
+                StatusCode::OK
+            );
+            assert_eq!(
+                status(Routes::Internal, "/api/v1/admin/stats").await,
+                StatusCode::UNAUTHORIZED
+            );
+            assert_eq!(
+                status(Routes::Internal, "/api/v1/schemas").await,
+                StatusCode::NOT_FOUND
+            );
+        }
+
+        #[tokio::test]
+        async fn test_panic_recovery() {
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{ "id": "ops-id", "name": "ops-name" }],
+                "response_metadata": { "next_cursor": "" }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+            let ops_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "ops-id" }"#.to_owned(),
+                ))
+                .with_body(r#"{ "ok": true }"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.ops_channel = Some(ChannelName("ops-name".to_owned()));
+            let metrics = deps.metrics.clone();
+
+            let app = Router::new()
+                .route(
+                    "/slow",
+                    get(|| async {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        StatusCode::OK
+                    }),
+                )
+                .route(
+                    "/panic",
+                    get(|| async {
+                        panic!("oh no");
+                        #[allow(unreachable_code)]
+                        StatusCode::OK
+                    }),
+                )
+                .layer(panic::catch_panic(deps));
+
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(async move { axum::serve(listener, app).await });
+
+            let client = reqwest::Client::new();
+            let get = |path: &str| client.get(format!("http://{}{}", addr, path)).send();
+
+            let (slow1, panic1, slow2, panic2) =
+                tokio::join!(get("/slow"), get("/panic"), get("/slow"), get("/panic"));
+
+            assert_eq!(slow1.unwrap().status().as_u16(), 200);
+            assert_eq!(slow2.unwrap().status().as_u16(), 200);
+            assert_eq!(panic1.unwrap().status().as_u16(), 500);
+            assert_eq!(panic2.unwrap().status().as_u16(), 500);
+            assert_eq!(
+                get("/slow").await.unwrap().status().as_u16(),
+                200,
+                "server survives"
+            );
+            assert_eq!(metrics.panics.get(), 2);
+
+            // Notified in the background, and only once.
+            for _ in 0..50 {
+                if ops_mock.matched_async().await {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+            ops_mock.assert_async().await;
+        }
+
+        #[tokio::test]
+        async fn test_stream_missing_auth() {
+            let req = Request::builder()
+                .uri("/api/v1/stream")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[tokio::test]
+        async fn test_stream_bad_filter() {
+            let req = Request::builder()
+                .uri("/api/v1/stream?source=heroku,nope")
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn test_stream() {
+            use crate::{delivery::Source, slack::Message, stream::StreamEvent};
+            use tokio_stream::StreamExt;
+
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            let events = deps.events.clone();
+
+            let req = Request::builder()
+                .uri("/api/v1/stream")
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Content-Type"], "text/event-stream");
+
+            let msg = Message::new(
+                ChannelName("deploys".to_owned()),
+                "a title",
+                "a description",
+            );
+            events.publish(StreamEvent::new(7, Utc::now(), Source::Heroku, None, &msg));
+
+            let mut body = res.into_body().into_data_stream();
+            let frame = body.next().await.unwrap().unwrap();
+            let frame = String::from_utf8(frame.to_vec()).unwrap();
+
+            assert!(frame.starts_with("event: message\n"));
+            assert!(frame.contains("id: 7\n"));
+            assert!(frame.contains(r#""source":"heroku""#));
+            assert!(frame.contains(r#""channel":"deploys""#));
+        }
     }
 
     mod slack {
         use super::*;
+        use crate::{
+            audit::Outcome,
+            auth::parse_api_tokens,
+            budget::{parse_budget_limits, NoiseBudgets},
+            escalation::{parse_policy, Escalations},
+            oncall::{OnCallProvider, PagerDutyClient, PagerDutyToken},
+            signing::{gen_signature, parse_signing_secrets, SigningSecret},
+            slack::{
+                channel::ChannelId,
+                interactivity::{self, SlackSigningSecret},
+                message::MessageRef,
+            },
+        };
         use std::time::Duration;
 
         #[tokio::test]
@@ -182,7 +853,37 @@ mod tests {
             assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
             assert_eq!(
                 plaintext_body(res.into_body()).await,
-                "Failed to deserialize form body: missing field `title`"
+                r#"{"error":"Failed to deserialize form body","field":"title","received":"missing"}"#
+            );
+        }
+
+        #[tokio::test]
+        async fn test_strict() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("colour".to_owned(), "red".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let mut deps = deps(String::new(), SlackAccessToken("foobar".to_owned()), None);
+            deps.strict_sources = Arc::new(vec![Source::Api]);
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"error":"Unrecognised field or value","field":"colour"}"#
             );
         }
 
@@ -369,11 +1070,12 @@ mod tests {
         }
 
         #[tokio::test]
-        async fn test_success_with_join() {
+        async fn test_success_with_severity() {
             let fields = &[
                 ("channel".to_owned(), "channel-name".to_owned()),
                 ("title".to_owned(), "a title".to_owned()),
                 ("desc".to_owned(), "a description".to_owned()),
+                ("severity".to_owned(), "critical".to_owned()),
             ];
             let msg = serde_urlencoded::to_string(fields).unwrap();
 
@@ -396,16 +1098,7 @@ mod tests {
                 }
             }"#;
 
-            let msg1_res = r#"{
-                "ok": false,
-                "error": "not_in_channel"
-            }"#;
-
-            let join_res = r#"{
-                "ok": true
-            }"#;
-
-            let msg2_res = r#"{
+            let msg_res = r#"{
                 "ok": true
             }"#;
 
@@ -418,21 +1111,25 @@ mod tests {
                 .create_async()
                 .await;
 
-            let msg1_mock = srv
-                .mock("POST", "/chat.postMessage")
-                .with_body(msg1_res)
-                .create_async()
-                .await;
-
-            let join_mock = srv
-                .mock("POST", "/conversations.join")
-                .with_body(join_res)
-                .create_async()
-                .await;
-
-            let msg2_mock = srv
+            let msg_mock = srv
                 .mock("POST", "/chat.postMessage")
-                .with_body(msg2_res)
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "channel-id",
+                        "attachments": [{
+                            "color": "danger",
+                            "blocks": [{
+                                "type": "context",
+                                "elements": [{
+                                    "type": "plain_text",
+                                    "text": "a description"
+                                }]
+                            }]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
                 .create_async()
                 .await;
 
@@ -442,32 +1139,23 @@ mod tests {
                 .unwrap();
 
             list_mock.assert_async().await;
-            msg1_mock.assert_async().await;
-            join_mock.assert_async().await;
-            msg2_mock.assert_async().await;
+            msg_mock.assert_async().await;
 
             assert_eq!(res.status(), StatusCode::OK);
             assert!(plaintext_body(res.into_body()).await.is_empty());
         }
 
         #[tokio::test]
-        async fn test_success_cached_channel() {
+        async fn test_success_with_timestamp() {
             let fields = &[
                 ("channel".to_owned(), "channel-name".to_owned()),
                 ("title".to_owned(), "a title".to_owned()),
                 ("desc".to_owned(), "a description".to_owned()),
+                ("timestamp".to_owned(), "1691056830".to_owned()),
             ];
             let msg = serde_urlencoded::to_string(fields).unwrap();
 
-            let req1 = Request::builder()
-                .method("POST")
-                .uri("/api/v1/slack")
-                .header("Authorization", "Bearer foobar")
-                .header("Content-Type", "application/x-www-form-urlencoded")
-                .body(Body::from(msg.clone()))
-                .unwrap();
-
-            let req2 = Request::builder()
+            let req = Request::builder()
                 .method("POST")
                 .uri("/api/v1/slack")
                 .header("Authorization", "Bearer foobar")
@@ -486,11 +1174,7 @@ mod tests {
                 }
             }"#;
 
-            let msg1_res = r#"{
-                "ok": true
-            }"#;
-
-            let msg2_res = r#"{
+            let msg_res = r#"{
                 "ok": true
             }"#;
 
@@ -503,59 +1187,53 @@ mod tests {
                 .create_async()
                 .await;
 
-            let msg1_mock = srv
-                .mock("POST", "/chat.postMessage")
-                .with_body(msg1_res)
-                .create_async()
-                .await;
-
-            let msg2_mock = srv
+            let msg_mock = srv
                 .mock("POST", "/chat.postMessage")
-                .with_body(msg2_res)
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "blocks": [{
+                            "type": "context",
+                            "elements": [
+                                {
+                                    "type": "plain_text",
+                                    "text": "a description"
+                                },
+                                {
+                                    "type": "mrkdwn",
+                                    "text": "<!date^1691056830^{date_short_pretty} at {time}|2023-08-03 10:00 UTC>"
+                                }
+                            ]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
                 .create_async()
                 .await;
 
-            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
-            let res1 = rt.call(req1).await.unwrap();
-            let res2 = rt.call(req2).await.unwrap();
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
 
             list_mock.assert_async().await;
-            msg1_mock.assert_async().await;
-            msg2_mock.assert_async().await;
-
-            assert_eq!(res1.status(), StatusCode::OK);
-            assert!(plaintext_body(res1.into_body()).await.is_empty());
+            msg_mock.assert_async().await;
 
-            assert_eq!(res2.status(), StatusCode::OK);
-            assert!(plaintext_body(res2.into_body()).await.is_empty());
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
         }
 
         #[tokio::test]
-        async fn test_success_with_stale_cache() {
+        async fn test_success_in_thread() {
             let fields = &[
                 ("channel".to_owned(), "channel-name".to_owned()),
                 ("title".to_owned(), "a title".to_owned()),
                 ("desc".to_owned(), "a description".to_owned()),
+                ("thread_ts".to_owned(), "1700000000.000100".to_owned()),
             ];
             let msg = serde_urlencoded::to_string(fields).unwrap();
 
-            let req1 = Request::builder()
-                .method("POST")
-                .uri("/api/v1/slack")
-                .header("Authorization", "Bearer foobar")
-                .header("Content-Type", "application/x-www-form-urlencoded")
-                .body(Body::from(msg.clone()))
-                .unwrap();
-
-            let req2 = Request::builder()
-                .method("POST")
-                .uri("/api/v1/slack")
-                .header("Authorization", "Bearer foobar")
-                .header("Content-Type", "application/x-www-form-urlencoded")
-                .body(Body::from(msg.clone()))
-                .unwrap();
-
-            let req3 = Request::builder()
+            let req = Request::builder()
                 .method("POST")
                 .uri("/api/v1/slack")
                 .header("Authorization", "Bearer foobar")
@@ -563,10 +1241,10 @@ mod tests {
                 .body(Body::from(msg))
                 .unwrap();
 
-            let list1_res = r#"{
+            let list_res = r#"{
                 "ok": true,
                 "channels": [{
-                    "id": "old-channel-id",
+                    "id": "channel-id",
                     "name": "channel-name"
                 }],
                 "response_metadata": {
@@ -574,10 +1252,61 @@ mod tests {
                 }
             }"#;
 
-            let list2_res = r#"{
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "channel-id",
+                        "thread_ts": "1700000000.000100"
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(r#"{ "ok": true }"#)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_success_with_mention() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("cc".to_owned(), "@sre".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
                 "ok": true,
                 "channels": [{
-                    "id": "new-channel-id",
+                    "id": "channel-id",
                     "name": "channel-name"
                 }],
                 "response_metadata": {
@@ -585,445 +1314,3957 @@ mod tests {
                 }
             }"#;
 
-            let msg1_res = r#"{
-                "ok": true
-            }"#;
-
-            let msg2_res = r#"{
-                "ok": true
+            let groups_res = r#"{
+                "ok": true,
+                "usergroups": [{
+                    "id": "group-id",
+                    "handle": "sre"
+                }]
             }"#;
 
-            let msg3_res = r#"{
+            let msg_res = r#"{
                 "ok": true
             }"#;
 
             let mut srv = server().await;
 
-            let list1_mock = srv
+            let list_mock = srv
                 .mock("GET", "/conversations.list")
                 .match_query(Matcher::Any)
-                .with_body(list1_res)
+                .with_body(list_res)
                 .create_async()
                 .await;
 
-            let msg1_mock = srv
-                .mock("POST", "/chat.postMessage")
-                .with_body(msg1_res)
+            let groups_mock = srv
+                .mock("GET", "/usergroups.list")
+                .match_query(Matcher::Any)
+                .with_body(groups_res)
                 .create_async()
                 .await;
 
-            let msg2_mock = srv
+            let msg_mock = srv
                 .mock("POST", "/chat.postMessage")
-                .with_body(msg2_res)
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "blocks": [{
+                            "type": "context",
+                            "elements": [
+                                {
+                                    "type": "plain_text",
+                                    "text": "a description"
+                                },
+                                {
+                                    "type": "mrkdwn",
+                                    "text": "cc <!subteam^group-id>"
+                                }
+                            ]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
                 .create_async()
                 .await;
 
-            let list2_mock = srv
-                .mock("GET", "/conversations.list")
-                .match_query(Matcher::Any)
-                .with_body(list2_res)
-                .create_async()
-                .await;
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
 
-            let msg3_mock = srv
-                .mock("POST", "/chat.postMessage")
-                .with_body(msg3_res)
-                .create_async()
-                .await;
+            list_mock.assert_async().await;
+            groups_mock.assert_async().await;
+            msg_mock.assert_async().await;
 
-            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
 
-            let res1 = rt.call(req1).await.unwrap();
-            list1_mock.assert_async().await;
-            msg1_mock.assert_async().await;
+        #[tokio::test]
+        async fn test_success_with_unknown_mention() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("cc".to_owned(), "@nobody".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
 
-            // We currently evict the cache after 24 hours. It should be
-            // considered safe from eviction at this stage (23 hours).
-            let nearly_one_day = Duration::from_secs(60 * 60 * 23);
-            mock_instant::MockClock::advance(nearly_one_day);
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
 
-            let res2 = rt.call(req2).await.unwrap();
-            msg2_mock.assert_async().await;
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
 
-            // This knocks us over the 24 hour limit (23 hours + 2 hours).
-            let over_the_edge = Duration::from_secs(60 * 60 * 2);
-            mock_instant::MockClock::advance(over_the_edge);
+            let groups_res = r#"{
+                "ok": true,
+                "usergroups": [{
+                    "id": "group-id",
+                    "handle": "sre"
+                }]
+            }"#;
 
-            let res3 = rt.call(req3).await.unwrap();
-            list2_mock.assert_async().await;
-            msg3_mock.assert_async().await;
+            let msg_res = r#"{
+                "ok": true
+            }"#;
 
-            assert_eq!(res1.status(), StatusCode::OK);
-            assert!(plaintext_body(res1.into_body()).await.is_empty());
+            let mut srv = server().await;
 
-            assert_eq!(res2.status(), StatusCode::OK);
-            assert!(plaintext_body(res2.into_body()).await.is_empty());
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
 
-            assert_eq!(res3.status(), StatusCode::OK);
-            assert!(plaintext_body(res3.into_body()).await.is_empty());
-        }
-    }
+            let groups_mock = srv
+                .mock("GET", "/usergroups.list")
+                .match_query(Matcher::Any)
+                .with_body(groups_res)
+                .create_async()
+                .await;
 
-    mod heroku {
-        use super::*;
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "blocks": [{
+                            "type": "context",
+                            "elements": [
+                                {
+                                    "type": "plain_text",
+                                    "text": "a description"
+                                },
+                                {
+                                    "type": "mrkdwn",
+                                    "text": "cc @nobody"
+                                }
+                            ]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
 
-        #[tokio::test]
-        async fn test_not_found() {
-            let req = Request::builder()
-                .uri("/api/v1/heroku/oops")
-                .body(Body::empty())
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
                 .unwrap();
 
-            let res = router_().oneshot(req).await.unwrap();
+            list_mock.assert_async().await;
+            groups_mock.assert_async().await;
+            msg_mock.assert_async().await;
 
-            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
         }
 
         #[tokio::test]
-        async fn test_bad_method() {
+        async fn test_success_with_on_call() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("cc".to_owned(), "oncall:schedule-id".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
             let req = Request::builder()
-                .method("GET")
-                .uri("/api/v1/heroku/hook")
+                .method("POST")
+                .uri("/api/v1/slack")
                 .header("Authorization", "Bearer foobar")
-                .body(Body::empty())
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
                 .unwrap();
 
-            let res = router_().oneshot(req).await.unwrap();
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
 
-            assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
-        }
+            let oncalls_res = r#"{
+                "oncalls": [{
+                    "escalation_level": 1,
+                    "user": {
+                        "id": "pagerduty-id",
+                        "email": "someone@example.com"
+                    }
+                }]
+            }"#;
 
-        #[tokio::test]
-        async fn test_missing_content_type() {
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .body(Body::empty())
-                .unwrap();
+            let user_res = r#"{
+                "ok": true,
+                "user": {
+                    "id": "user-id"
+                }
+            }"#;
 
-            let res = router_().oneshot(req).await.unwrap();
+            let msg_res = r#"{
+                "ok": true
+            }"#;
 
-            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-            assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Header of type `content-type` was missing"
-            );
-        }
+            let mut srv = server().await;
 
-        #[tokio::test]
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let oncalls_mock = srv
+                .mock("GET", "/oncalls")
+                .match_query(Matcher::UrlEncoded(
+                    "schedule_ids[]".to_owned(),
+                    "schedule-id".to_owned(),
+                ))
+                .match_header("Authorization", "Token token=pd")
+                .with_body(oncalls_res)
+                .create_async()
+                .await;
+
+            let user_mock = srv
+                .mock("GET", "/users.lookupByEmail")
+                .match_query(Matcher::UrlEncoded(
+                    "email".to_owned(),
+                    "someone@example.com".to_owned(),
+                ))
+                .with_body(user_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "blocks": [{
+                            "type": "context",
+                            "elements": [
+                                {
+                                    "type": "plain_text",
+                                    "text": "a description"
+                                },
+                                {
+                                    "type": "mrkdwn",
+                                    "text": "cc <@user-id>"
+                                }
+                            ]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            let client = SlackClient::new(srv.url()).with_on_call(OnCallProvider::PagerDuty(
+                PagerDutyClient::new(srv.url(), PagerDutyToken("pd".to_owned())),
+            ));
+            deps.slack_client = Arc::new(Mutex::new(client));
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            list_mock.assert_async().await;
+            oncalls_mock.assert_async().await;
+            user_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_dry_run() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack?dry_run=true")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&plaintext_body(res.into_body()).await)
+                    .unwrap(),
+                serde_json::json!({
+                    "channel": "channel-id",
+                    "username": "a title",
+                    "blocks": [{
+                        "type": "context",
+                        "elements": [{
+                            "type": "plain_text",
+                            "text": "a description"
+                        }]
+                    }],
+                    "icon_url": null,
+                    "text": "a title: a description"
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_preview() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("severity".to_owned(), "success".to_owned()),
+            ];
+            let query = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("GET")
+                .uri(format!("/api/v1/slack/preview?{}", query))
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&plaintext_body(res.into_body()).await)
+                    .unwrap(),
+                serde_json::json!({
+                    "channel": "channel-id",
+                    "username": "a title",
+                    "attachments": [{
+                        "color": "good",
+                        "blocks": [{
+                            "type": "context",
+                            "elements": [{
+                                "type": "plain_text",
+                                "text": "a description"
+                            }]
+                        }]
+                    }],
+                    "icon_url": null,
+                    "text": "a title: a description"
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_preview_header() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("title_as_header".to_owned(), "true".to_owned()),
+            ];
+            let query = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("GET")
+                .uri(format!("/api/v1/slack/preview?{}", query))
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&plaintext_body(res.into_body()).await)
+                    .unwrap(),
+                serde_json::json!({
+                    "channel": "channel-id",
+                    "blocks": [{
+                        "type": "header",
+                        "text": {
+                            "type": "plain_text",
+                            "text": "a title"
+                        }
+                    }, {
+                        "type": "context",
+                        "elements": [{
+                            "type": "plain_text",
+                            "text": "a description"
+                        }]
+                    }],
+                    "icon_url": null,
+                    "text": "a title: a description"
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_preview_fields() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("fields".to_owned(), "App: web\nRegion: eu".to_owned()),
+            ];
+            let query = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("GET")
+                .uri(format!("/api/v1/slack/preview?{}", query))
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                serde_json::from_str::<serde_json::Value>(&plaintext_body(res.into_body()).await)
+                    .unwrap(),
+                serde_json::json!({
+                    "channel": "channel-id",
+                    "username": "a title",
+                    "blocks": [{
+                        "type": "context",
+                        "elements": [{
+                            "type": "plain_text",
+                            "text": "a description"
+                        }]
+                    }, {
+                        "type": "section",
+                        "fields": [{
+                            "type": "plain_text",
+                            "text": "App: web"
+                        }, {
+                            "type": "plain_text",
+                            "text": "Region: eu"
+                        }]
+                    }],
+                    "icon_url": null,
+                    "text": "a title: a description"
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_read_only() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let mut srv = server().await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Mercury-Suppressed"], "read-only");
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_explain() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack/explain")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let mut srv = server().await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"steps":[{"step":"form","ok":true,"detail":"Valid"},{"step":"ingestion","ok":true,"detail":"Not paused"},{"step":"backpressure","ok":true,"detail":"Admitted at Normal priority"},{"step":"read-only","ok":false,"detail":"Read-only mode is enabled"}],"would_deliver":false}"#
+            );
+        }
+
+        #[tokio::test]
+        async fn test_backpressure() {
+            let req = |severity: &str| {
+                let fields = &[
+                    ("channel".to_owned(), "channel-name".to_owned()),
+                    ("title".to_owned(), "a title".to_owned()),
+                    ("desc".to_owned(), "a description".to_owned()),
+                    ("severity".to_owned(), severity.to_owned()),
+                ];
+
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", "Bearer foobar")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(serde_urlencoded::to_string(fields).unwrap()))
+                    .unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.lanes.pause(Duration::from_millis(1500));
+            let mut rt = super::new(deps);
+
+            let res = rt.call(req("info")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+            assert_eq!(res.headers()["Retry-After"], "2");
+
+            // Critical messages wait their turn instead.
+            let res = rt.call(req("critical")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            msg_mock.assert_async().await;
+        }
+
+        #[tokio::test]
+        async fn test_topic() {
+            let req = |body: &'static str| {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack/topic")
+                    .header("Authorization", "Bearer foobar")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let set_mock = srv
+                .mock("POST", "/conversations.setTopic")
+                .match_body(Matcher::Json(serde_json::json!({
+                    "channel": "channel-id",
+                    "topic": "Incident in progress",
+                })))
+                .with_body(r#"{"ok": true}"#)
+                .create_async()
+                .await;
+
+            let clear_mock = srv
+                .mock("POST", "/conversations.setTopic")
+                .match_body(Matcher::Json(serde_json::json!({
+                    "channel": "channel-id",
+                    "topic": "",
+                })))
+                .with_body(r#"{"ok": true}"#)
+                .create_async()
+                .await;
+
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            let mut rt = super::new(deps.clone());
+
+            let res = rt
+                .call(req("channel=channel-name&topic=Incident+in+progress"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            set_mock.assert_async().await;
+
+            let res = rt.call(req("channel=channel-name")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            clear_mock.assert_async().await;
+
+            let res = rt.call(req("channel=nope")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+            deps.read_only.store(true, Ordering::Relaxed);
+            let res = rt.call(req("channel=channel-name")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Mercury-Suppressed"], "read-only");
+            clear_mock.assert_async().await;
+        }
+
+        fn api_token_req(token: &str) -> Request<Body> {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap()
+        }
+
+        fn api_token_deps(slack_token_compat: bool) -> Deps {
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.api_tokens
+                .store(Arc::new(parse_api_tokens("new,newer")));
+            deps.slack_token_compat = slack_token_compat;
+            // Avoids the need to mock Slack, we only care about authentication.
+            deps.read_only.store(true, Ordering::Relaxed);
+            deps
+        }
+
+        #[tokio::test]
+        async fn test_request_metrics() {
+            let deps = api_token_deps(false);
+            deps.api_tokens
+                .store(Arc::new(parse_api_tokens("ci:new,newer")));
+            let metrics = deps.metrics.clone();
+            let rt = super::new(deps);
+
+            let res = rt.clone().oneshot(api_token_req("new")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let res = rt.oneshot(api_token_req("nope")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+            let encoded = metrics.encode();
+            assert!(encoded.contains(
+                r#"mercury_http_request_duration_seconds_count{channel="channel-name",client="ci",method="POST",route="/api/v1/slack",status="200"} 1"#
+            ));
+            assert!(encoded.contains(
+                r#"mercury_http_request_duration_seconds_count{channel="unknown",client="unknown",method="POST",route="/api/v1/slack",status="401"} 1"#
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_noise_budget() {
+            let msg_req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", "Bearer foobar")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(
+                        "channel=channel-name&title=a+title&desc=a+description",
+                    ))
+                    .unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.noise_budgets = Some(Arc::new(NoiseBudgets::new(parse_budget_limits(
+                "channel-name:1",
+            ))));
+            let audit = deps.audit.clone();
+            let mut rt = super::new(deps);
+
+            let res1 = rt.call(msg_req()).await.unwrap();
+            let res2 = rt.call(msg_req()).await.unwrap();
+
+            msg_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert!(!res1.headers().contains_key("Mercury-Suppressed"));
+            assert_eq!(res2.status(), StatusCode::OK);
+            assert_eq!(res2.headers()["Mercury-Suppressed"], "noise-budget");
+
+            // Suppressed messages remain visible in the audit history.
+            assert!(matches!(
+                audit.get(2).unwrap().outcome,
+                Outcome::Suppressed { .. }
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_api_token() {
+            for token in ["new", "newer"] {
+                let res = super::new(api_token_deps(false))
+                    .oneshot(api_token_req(token))
+                    .await
+                    .unwrap();
+
+                assert_eq!(res.status(), StatusCode::OK);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_slack_token_without_compat() {
+            let res = super::new(api_token_deps(false))
+                .oneshot(api_token_req("foobar"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[tokio::test]
+        async fn test_slack_token_with_compat() {
+            let res = super::new(api_token_deps(true))
+                .oneshot(api_token_req("foobar"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_debug_payloads() {
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.debug_payloads = true;
+            // Avoids the need to mock Slack, we only care that the payload
+            // survives being logged.
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let res = super::new(deps)
+                .oneshot(api_token_req("foobar"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Mercury-Suppressed"], "read-only");
+        }
+
+        fn signed_req(client: &str, secret: &str) -> Request<Body> {
+            signed_req_at(client, secret, Utc::now().timestamp())
+        }
+
+        fn signed_req_at(client: &str, secret: &str, ts: i64) -> Request<Body> {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+            let ts = ts.to_string();
+            let sig =
+                gen_signature(&SigningSecret(secret.to_owned()), &ts, &msg.clone().into()).unwrap();
+
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .header("Mercury-Client", client)
+                .header("Mercury-Timestamp", ts)
+                .header("Mercury-Signature", sig)
+                .body(Body::from(msg))
+                .unwrap()
+        }
+
+        fn signing_deps() -> Deps {
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.signing_secrets = parse_signing_secrets("ci:foobarbaz");
+            // Avoids the need to mock Slack, we only care about authentication.
+            deps.read_only.store(true, Ordering::Relaxed);
+            deps
+        }
+
+        #[tokio::test]
+        async fn test_signed() {
+            let req = signed_req("ci", "foobarbaz");
+
+            let res = super::new(signing_deps()).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_bad_signature() {
+            let req = signed_req("ci", "oops");
+
+            let res = super::new(signing_deps()).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_signed_stale() {
+            let req = signed_req_at("ci", "foobarbaz", Utc::now().timestamp() - 60 * 10);
+            let deps = signing_deps();
+            let metrics = deps.metrics.clone();
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                metrics.stale_signatures.with_label_values(&["api"]).get(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn test_signed_unknown_client() {
+            let req = signed_req("other", "foobarbaz");
+
+            let res = super::new(signing_deps()).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        fn interaction_req(secret: &str) -> Request<Body> {
+            let payload = r#"{
+                "type": "block_actions",
+                "user": { "id": "user-id" },
+                "container": {
+                    "type": "message",
+                    "channel_id": "channel-id",
+                    "message_ts": "1700000000.000100"
+                },
+                "actions": [{ "action_id": "acknowledge" }]
+            }"#;
+            let body = serde_urlencoded::to_string([("payload", payload)]).unwrap();
+            let ts = Utc::now().timestamp().to_string();
+            let sig = interactivity::gen_signature(
+                &SlackSigningSecret(secret.to_owned()),
+                &ts,
+                &body.clone().into(),
+            )
+            .unwrap();
+
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack/interactivity")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .header("X-Slack-Request-Timestamp", ts)
+                .header("X-Slack-Signature", sig)
+                .body(Body::from(body))
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_interactivity_unconfigured() {
+            let res = router_().oneshot(interaction_req("foobar")).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        }
+
+        #[tokio::test]
+        async fn test_interactivity_bad_signature() {
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.slack_signing_secret = Some(SlackSigningSecret("foobar".to_owned()));
+
+            let res = super::new(deps)
+                .oneshot(interaction_req("oops"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[tokio::test]
+        async fn test_escalation_acknowledged() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+                ("severity".to_owned(), "critical".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true,
+                "channel": "channel-id",
+                "ts": "1700000000.000100"
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "attachments": [{
+                            "blocks": [
+                                { "type": "context" },
+                                {
+                                    "type": "actions",
+                                    "elements": [{
+                                        "type": "button",
+                                        "action_id": "acknowledge"
+                                    }]
+                                }
+                            ]
+                        }]
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let reply_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "channel-id",
+                        "thread_ts": "1700000000.000100",
+                        "text": "Acknowledged by <@user-id>."
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            let escalations = Arc::new(Escalations::new(
+                parse_policy("15:@sre").unwrap(),
+                StateMetrics::new(&deps.metrics, "escalations"),
+            ));
+            deps.escalations = Some(escalations.clone());
+            deps.slack_signing_secret = Some(SlackSigningSecret("foobar".to_owned()));
+
+            let mut rt = super::new(deps);
+
+            let res1 = rt.call(req).await.unwrap();
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            let res2 = rt.call(interaction_req("foobar")).await.unwrap();
+            reply_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(res2.status(), StatusCode::OK);
+
+            // No longer pending.
+            assert!(!escalations.acknowledge(&MessageRef {
+                channel_id: ChannelId("channel-id".to_owned()),
+                ts: "1700000000.000100".to_owned(),
+            }));
+        }
+
+        #[tokio::test]
+        async fn test_success_with_shadow() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [
+                    {
+                        "id": "channel-id",
+                        "name": "channel-name"
+                    },
+                    {
+                        "id": "shadow-id",
+                        "name": "shadow-name"
+                    }
+                ],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "channel-id", "username": "a title" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let shadow_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "shadow-id", "username": "a title" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.shadow = Some(Shadow::new(ChannelName("shadow-name".to_owned()), 1));
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+            shadow_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_success_with_join() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg1_res = r#"{
+                "ok": false,
+                "error": "not_in_channel"
+            }"#;
+
+            let join_res = r#"{
+                "ok": true
+            }"#;
+
+            let msg2_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg1_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg1_res)
+                .create_async()
+                .await;
+
+            let join_mock = srv
+                .mock("POST", "/conversations.join")
+                .with_body(join_res)
+                .create_async()
+                .await;
+
+            let msg2_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg2_res)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg1_mock.assert_async().await;
+            join_mock.assert_async().await;
+            msg2_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_success_without_customize_scope() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg1_res = r#"{
+                "ok": false,
+                "error": "missing_scope"
+            }"#;
+
+            let msg2_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg1_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "username": "a title" }"#.to_owned(),
+                ))
+                .with_body(msg1_res)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let msg2_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "blocks": [{ "type": "header" }] }"#.to_owned(),
+                ))
+                .with_body(msg2_res)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let res = router(srv.url(), SlackAccessToken("foobar".to_owned()), None)
+                .oneshot(req)
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg1_mock.assert_async().await;
+            msg2_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_archived_channel() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }, {
+                    "id": "ops-id",
+                    "name": "ops-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": false,
+                "error": "is_archived"
+            }"#;
+
+            let ops_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "channel-id" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let ops_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "ops-id", "username": "Mercury" }"#.to_owned(),
+                ))
+                .with_body(ops_res)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.ops_channel = Some(ChannelName("ops-name".to_owned()));
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+            ops_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::CONFLICT);
+
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(body["error"], "Slack API returned error: is_archived");
+            assert!(body["hint"].as_str().unwrap().contains("Unarchive"));
+        }
+
+        #[tokio::test]
+        async fn test_success_cached_channel() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req1 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg.clone()))
+                .unwrap();
+
+            let req2 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg1_res = r#"{
+                "ok": true
+            }"#;
+
+            let msg2_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg1_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg1_res)
+                .create_async()
+                .await;
+
+            let msg2_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg2_res)
+                .create_async()
+                .await;
+
+            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            let res1 = rt.call(req1).await.unwrap();
+            let res2 = rt.call(req2).await.unwrap();
+
+            list_mock.assert_async().await;
+            msg1_mock.assert_async().await;
+            msg2_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert!(plaintext_body(res1.into_body()).await.is_empty());
+
+            assert_eq!(res2.status(), StatusCode::OK);
+            assert!(plaintext_body(res2.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_success_with_stale_cache() {
+            let fields = &[
+                ("channel".to_owned(), "channel-name".to_owned()),
+                ("title".to_owned(), "a title".to_owned()),
+                ("desc".to_owned(), "a description".to_owned()),
+            ];
+            let msg = serde_urlencoded::to_string(fields).unwrap();
+
+            let req1 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg.clone()))
+                .unwrap();
+
+            let req2 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg.clone()))
+                .unwrap();
+
+            let req3 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(msg))
+                .unwrap();
+
+            let list1_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "old-channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let list2_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "new-channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg1_res = r#"{
+                "ok": true
+            }"#;
+
+            let msg2_res = r#"{
+                "ok": true
+            }"#;
+
+            let msg3_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list1_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list1_res)
+                .create_async()
+                .await;
+
+            let msg1_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg1_res)
+                .create_async()
+                .await;
+
+            let msg2_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg2_res)
+                .create_async()
+                .await;
+
+            let list2_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list2_res)
+                .create_async()
+                .await;
+
+            let msg3_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg3_res)
+                .create_async()
+                .await;
+
+            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+
+            let res1 = rt.call(req1).await.unwrap();
+            list1_mock.assert_async().await;
+            msg1_mock.assert_async().await;
+
+            // We currently evict the cache after 24 hours. It should be
+            // considered safe from eviction at this stage (23 hours).
+            let nearly_one_day = Duration::from_secs(60 * 60 * 23);
+            mock_instant::MockClock::advance(nearly_one_day);
+
+            let res2 = rt.call(req2).await.unwrap();
+            msg2_mock.assert_async().await;
+
+            // This knocks us over the 24 hour limit (23 hours + 2 hours).
+            let over_the_edge = Duration::from_secs(60 * 60 * 2);
+            mock_instant::MockClock::advance(over_the_edge);
+
+            let res3 = rt.call(req3).await.unwrap();
+            list2_mock.assert_async().await;
+            msg3_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert!(plaintext_body(res1.into_body()).await.is_empty());
+
+            assert_eq!(res2.status(), StatusCode::OK);
+            assert!(plaintext_body(res2.into_body()).await.is_empty());
+
+            assert_eq!(res3.status(), StatusCode::OK);
+            assert!(plaintext_body(res3.into_body()).await.is_empty());
+        }
+    }
+
+    mod heroku {
+        use super::*;
+        use crate::heroku::routing::parse_app_routes;
+        use std::time::Duration;
+
+        #[tokio::test]
+        async fn test_read_only() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let mut srv = server().await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Mercury-Suppressed"], "read-only");
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_not_found() {
+            let req = Request::builder()
+                .uri("/api/v1/heroku/oops")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        }
+
+        #[tokio::test]
+        async fn test_bad_method() {
+            let req = Request::builder()
+                .method("GET")
+                .uri("/api/v1/heroku/hook")
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        #[tokio::test]
+        async fn test_missing_content_type() {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Header of type `content-type` was missing"
+            );
+        }
+
+        #[tokio::test]
         async fn test_bad_content_type() {
             let req = Request::builder()
                 .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Content-Type", "application/xml")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Content-Type", "application/xml")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Requests must have `Content-Type: application/json`"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_missing_query() {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook")
+                .header("Content-Type", "application/json")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Failed to deserialize query string: missing field `platform`"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_bad_platform() {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=discord")
+                .header("Content-Type", "application/json")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Failed to deserialize query string: unknown variant `discord`, expected one of `slack`, `stdout`, `matrix`, `zulip`, `github`, `statuspage`, `otto`"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_missing_signature() {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{ "any": true }"#))
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_bad_signature() {
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", "bad signature")
+                .header("Content-Type", "application/json")
+                .body(Body::from(r#"{ "any": true }"#))
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_bad_field() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": false
+                    },
+                    "description": "any"
+                }
+            }"#;
+            let sig = "WVN5qPUl+QWmSYErNMSXhHAYiIZ/hwCsaiZNjESj7Xs=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"error":"Failed to deserialize payload","field":"data.app.name","received":"boolean"}"#
+            );
+        }
+
+        #[tokio::test]
+        async fn test_unsupported_event() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "any",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "0+jCzQsgvzi0SL0haDhB18ttbTNEYYlrwhtpL0FEVGw=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_ignored_action() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "any",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "create"
+            }"#;
+            let sig = "F5ArFnV9sfXsDmk9ubM24fu6gVVxEXl1TOdt1XTVokg=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_slack_failure_auth() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": false,
+                "error": "invalid_auth"
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let res = router(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            )
+            .oneshot(req)
+            .await
+            .unwrap();
+
+            list_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Slack API returned error: invalid_auth"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_slack_failure_unknown() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": false,
+                "error": "something else"
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let res = router(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            )
+            .oneshot(req)
+            .await
+            .unwrap();
+
+            list_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Slack API returned error: something else"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_slack_success() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let res = router(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            )
+            .oneshot(req)
+            .await
+            .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_with_templated_channel() {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=deploys-%7Bapp%7D")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "deploys-any"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "channel-id" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let res = router(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            )
+            .oneshot(req)
+            .await
+            .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        fn rollback_req(query: &str) -> Request<Body> {
+            let payload = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Rollback to v1234",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+
+            Request::builder()
+                .method("POST")
+                .uri(format!("/api/v1/heroku/hook?{}", query))
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap()
+        }
+
+        #[tokio::test]
+        async fn test_strict() {
+            let payload = r#"{"resource": "release", "action": "update", "data": {"app": {"name": "any"}, "description": "Deploy 69eec518", "user": {"email": "hodor@unsplash.com"}, "stack": "heroku-22"}}"#;
+            let sig = "GMmAX2+WyY6G7e8xIQip8A5x7JPyp0HXxVk6xtqzFbQ=";
+
+            let mut deps = deps(
+                String::new(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.strict_sources = Arc::new(vec![Source::Heroku]);
+            let metrics = deps.metrics.clone();
+
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=any")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"error":"Unrecognised field or value","field":"data.stack"}"#
+            );
+            assert_eq!(
+                metrics
+                    .unknown_fields
+                    .with_label_values(&["heroku", "data.stack"])
+                    .get(),
+                1
+            );
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_with_app_route() {
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            // The legacy shorthand `web` stands for `@web-team`.
+            let groups_res = r#"{
+                "ok": true,
+                "usergroups": [{
+                    "id": "group-id",
+                    "handle": "web-team"
+                }]
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let groups_mock = srv
+                .mock("GET", "/usergroups.list")
+                .match_query(Matcher::Any)
+                .with_body(groups_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::AllOf(vec![
+                    Matcher::PartialJsonString(r#"{ "channel": "channel-id" }"#.to_owned()),
+                    Matcher::Regex("subteam\\^group-id".to_owned()),
+                ]))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.heroku_app_routes =
+                Arc::new(parse_app_routes("other:elsewhere,an*:channel-name:web"));
+
+            let res = super::new(deps)
+                .oneshot(rollback_req("platform=slack"))
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            groups_mock.assert_async().await;
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(plaintext_body(res.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_unroutable() {
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.heroku_app_routes = Arc::new(parse_app_routes("other:elsewhere"));
+
+            let res = super::new(deps)
+                .oneshot(rollback_req("platform=slack"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "No channel supplied or routed for app: any"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_with_named_route() {
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "channel": "channel-id" }"#.to_owned(),
+                ))
+                .with_body(r#"{ "ok": true }"#)
+                .create_async()
+                .await;
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            // Named routes take precedence over app routes.
+            deps.heroku_app_routes = Arc::new(parse_app_routes("*:elsewhere"));
+            deps.heroku_named_routes = Arc::new(parse_app_routes("alerts:channel-name"));
+            let app = super::new(deps);
+
+            let res = app
+                .clone()
+                .oneshot(rollback_req("platform=slack&route=alerts"))
+                .await
+                .unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let res = app
+                .oneshot(rollback_req("platform=slack&route=unknown"))
+                .await
+                .unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "No route named: unknown"
+            );
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_threaded() {
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true,
+                "channel": "channel-id",
+                "ts": "1700000000.000100"
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let reply_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "thread_ts": "1700000000.000100" }"#.to_owned(),
+                ))
+                .with_body(r#"{"ok": true, "ts": "1700000001.000100"}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(msg_res)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.threads = Some(Arc::new(Threads::new(
+                Duration::from_secs(60),
+                StateMetrics::new(&deps.metrics, "threads"),
+            )));
+            let mut rt = super::new(deps);
+
+            let query = "platform=slack&channel=channel-name";
+            let res1 = rt.call(rollback_req(query)).await.unwrap();
+            let res2 = rt.call(rollback_req(query)).await.unwrap();
+
+            list_mock.assert_async().await;
+            msg_mock.assert_async().await;
+            reply_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(res2.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_with_runbook() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let sig = "zGmjxjTN9sV+9T5gqohfTQX3CAL8DGF7iX8+vlp6Rcs=";
+            let req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name")
+                    .header("Heroku-Webhook-Hmac-SHA256", sig)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(payload))
+                    .unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(r#"{"ok": true}"#)
+                .expect(2)
+                .create_async()
+                .await;
+
+            let bookmarks_mock = srv
+                .mock("POST", "/bookmarks.list")
+                .match_body(Matcher::Json(serde_json::json!({
+                    "channel_id": "channel-id",
+                })))
+                .with_body(r#"{"ok": true, "bookmarks": [{"link": "https://example.com/other"}]}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let add_mock = srv
+                .mock("POST", "/bookmarks.add")
+                .match_body(Matcher::Json(serde_json::json!({
+                    "channel_id": "channel-id",
+                    "title": "any runbook",
+                    "type": "link",
+                    "link": "https://example.com/runbook",
+                })))
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.heroku_runbooks = Arc::new(Runbooks::parse("an*:https://example.com/runbook"));
+            let mut rt = super::new(deps);
+
+            let res1 = rt.call(req()).await.unwrap();
+            // The runbook's only bookmarked once.
+            let res2 = rt.call(req()).await.unwrap();
+
+            msg_mock.assert_async().await;
+            bookmarks_mock.assert_async().await;
+            add_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(res2.status(), StatusCode::OK);
+        }
+
+        #[tokio::test]
+        async fn test_slack_success_with_changelog() {
+            let payload1 = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Deploy 1111111",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    },
+                    "slug": {
+                        "commit": "1111111111111111111111111111111111111111"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig1 = "BGIShHT8aceb503kPdBARXSA48xqL0ko2F+hapZIMRA=";
+
+            let payload2 = r#"{
+                "resource": "release",
+                "data": {
+                    "app": {
+                        "name": "any"
+                    },
+                    "description": "Deploy 2222222",
+                    "user": {
+                        "email": "hodor@unsplash.com"
+                    },
+                    "slug": {
+                        "commit": "2222222222222222222222222222222222222222"
+                    }
+                },
+                "action": "update"
+            }"#;
+            let sig2 = "LAMVCy8io4rWuYGOHUYP5WJxoIMdRQ3hIdnJ2VcLeDc=";
+
+            let req1 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name&repo=unsplash/any")
+                .header("Heroku-Webhook-Hmac-SHA256", sig1)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload1))
+                .unwrap();
+
+            let req2 = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name&repo=unsplash/any")
+                .header("Heroku-Webhook-Hmac-SHA256", sig2)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload2))
+                .unwrap();
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let compare_res = r#"{
+                "total_commits": 2,
+                "commits": [
+                    { "commit": { "message": "Add feature" } },
+                    { "commit": { "message": "Fix feature" } }
+                ]
+            }"#;
+
+            let msg_res = r#"{
+                "ok": true
+            }"#;
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let compare_mock = srv
+                .mock(
+                    "GET",
+                    "/repos/unsplash/any/compare/1111111111111111111111111111111111111111...2222222222222222222222222222222222222222",
+                )
+                .match_header("Authorization", "Bearer ghp_foobar")
+                .with_body(compare_res)
+                .create_async()
+                .await;
+
+            let msg1_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "text": "🚀 any: Deploy 1111111 (hodor@unsplash.com)" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let msg2_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{ "text": "🚀 any: Deploy 2222222 (hodor@unsplash.com)\nIncludes 2 commits:\n• Fix feature\n• Add feature" }"#.to_owned(),
+                ))
+                .with_body(msg_res)
+                .create_async()
+                .await;
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.github_client = Arc::new(GitHubClient::new(srv.url()));
+            deps.github_token = Some(GitHubToken("ghp_foobar".to_owned()));
+
+            let mut rt = super::new(deps);
+            let res1 = rt.call(req1).await.unwrap();
+            let res2 = rt.call(req2).await.unwrap();
+
+            list_mock.assert_async().await;
+            compare_mock.assert_async().await;
+            msg1_mock.assert_async().await;
+            msg2_mock.assert_async().await;
+
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert!(plaintext_body(res1.into_body()).await.is_empty());
+
+            assert_eq!(res2.status(), StatusCode::OK);
+            assert!(plaintext_body(res2.into_body()).await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn test_stdout_platform() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let sig = "zGmjxjTN9sV+9T5gqohfTQX3CAL8DGF7iX8+vlp6Rcs=";
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/heroku/hook?platform=stdout&channel=channel-name")
+                .header("Heroku-Webhook-Hmac-SHA256", sig)
+                .header("Content-Type", "application/json")
+                .body(Body::from(payload))
+                .unwrap();
+
+            let mut srv = server().await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let path = std::env::temp_dir()
+                .join(format!("mercury-stdout-platform-{}", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+
+            let mut deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            deps.console = Arc::new(Console::open(path.to_str().unwrap()).unwrap());
+
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            msg_mock.assert_async().await;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let written = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            let x: serde_json::Value = serde_json::from_str(written.trim()).unwrap();
+            assert_eq!(x["app"], "any");
+            assert_eq!(x["message"]["channel"], "channel-name");
+        }
+
+        #[tokio::test]
+        async fn test_matrix_platform() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let sig = "zGmjxjTN9sV+9T5gqohfTQX3CAL8DGF7iX8+vlp6Rcs=";
+            let req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/heroku/hook?platform=matrix&room=%23alerts:example.org")
+                    .header("Heroku-Webhook-Hmac-SHA256", sig)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(payload))
+                    .unwrap()
+            };
+
+            let mut srv = server().await;
+
+            let alias_mock = srv
+                .mock(
+                    "GET",
+                    "/_matrix/client/v3/directory/room/%23alerts%3Aexample.org",
+                )
+                .with_body(r#"{"room_id": "!abc:example.org"}"#)
+                .create_async()
+                .await;
+            let send_mock = srv
+                .mock(
+                    "PUT",
+                    Matcher::Regex("^/_matrix/client/v3/rooms/%21abc%3Aexample.org/send/".into()),
+                )
+                .match_body(Matcher::PartialJson(
+                    serde_json::json!({ "msgtype": "m.text" }),
+                ))
+                .with_body(r#"{"event_id": "$1"}"#)
+                .expect(1)
+                .create_async()
+                .await;
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+
+            let res = super::new(deps.clone()).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Matrix is not configured"
+            );
+
+            let mut deps = deps;
+            deps.matrix = Some(Arc::new(MatrixClient::new(
+                srv.url(),
+                "tk".into(),
+                ClientTls::default(),
+                StateMetrics::new(&deps.metrics, "matrix_rooms"),
+            )));
+
+            let res = super::new(deps).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            alias_mock.assert_async().await;
+            send_mock.assert_async().await;
+            msg_mock.assert_async().await;
+        }
+
+        #[tokio::test]
+        async fn test_github_platform() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let sig = "zGmjxjTN9sV+9T5gqohfTQX3CAL8DGF7iX8+vlp6Rcs=";
+            let req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/heroku/hook?platform=github&repo=unsplash/any")
+                    .header("Heroku-Webhook-Hmac-SHA256", sig)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(payload))
+                    .unwrap()
+            };
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/repos/unsplash/any/issues")
+                .match_query(Matcher::Any)
+                .with_body("[]")
+                .expect(1)
+                .create_async()
+                .await;
+            let create_mock = srv
+                .mock("POST", "/repos/unsplash/any/issues")
+                .match_header("Authorization", "Bearer ghp_foobar")
+                .match_body(Matcher::PartialJson(serde_json::json!({
+                    "labels": ["mercury-crash-any"],
+                })))
+                .with_status(201)
+                .with_body(r#"{"number": 1}"#)
+                .expect(1)
+                .create_async()
+                .await;
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+
+            let res = super::new(deps.clone()).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "GitHub is not configured"
+            );
+
+            let mut deps = deps;
+            deps.github_client = Arc::new(GitHubClient::new(srv.url()));
+            deps.github_token = Some(GitHubToken("ghp_foobar".to_owned()));
+
+            let res = super::new(deps).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            list_mock.assert_async().await;
+            create_mock.assert_async().await;
+            msg_mock.assert_async().await;
+        }
+
+        #[tokio::test]
+        async fn test_otto_platform() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let sig = "zGmjxjTN9sV+9T5gqohfTQX3CAL8DGF7iX8+vlp6Rcs=";
+            let req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/heroku/hook?platform=otto")
+                    .header("Heroku-Webhook-Hmac-SHA256", sig)
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(payload))
+                    .unwrap()
+            };
+
+            let mut srv = server().await;
+
+            let otto_mock = srv
+                .mock("POST", "/hooks/monitor")
+                .match_body(Matcher::PartialJson(serde_json::json!({
+                    "resource": "dyno",
+                    "data": { "app": { "name": "any" }, "state": "crashed", "exit_status": 137 },
+                })))
+                .expect(1)
+                .create_async()
+                .await;
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let deps = deps(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+
+            let res = super::new(deps.clone()).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                "Otto is not configured"
+            );
+
+            let mut deps = deps;
+            deps.heroku_otto = Some(Arc::new(Otto::new(srv.url(), None, ClientTls::default())));
+
+            let res = super::new(deps).oneshot(req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            otto_mock.assert_async().await;
+            msg_mock.assert_async().await;
+        }
+
+        #[tokio::test]
+        async fn test_explain() {
+            let payload = r#"{"resource": "dyno", "data": {"app": {"name": "any"}, "name": "web.1", "type": "web", "state": "crashed", "exit_status": 137}}"#;
+            let req = |auth: Option<&str>| {
+                let mut req = Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/heroku/hook/explain?platform=slack&channel=channel-name")
+                    .header("Content-Type", "application/json");
+                if let Some(x) = auth {
+                    req = req.header("Authorization", x);
+                }
+                req.body(Body::from(payload)).unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
+            }"#;
+
+            let mut srv = server().await;
+
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
+
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .expect(0)
+                .create_async()
+                .await;
+
+            let mut rt = router(
+                srv.url(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+
+            let res = rt.call(req(None)).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+            let res = rt.call(req(Some("Bearer foobar"))).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let trace: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            let steps = trace["steps"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| (x["step"].as_str().unwrap(), x["ok"].as_bool().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                steps,
+                vec![
+                    ("signature", false),
+                    ("payload", true),
+                    ("ingestion", true),
+                    ("backpressure", true),
+                    ("event", true),
+                    ("route", true),
+                    ("channel", true),
+                ]
+            );
+            // The unsigned payload wouldn't be delivered, though we can see what
+            // would have been.
+            assert_eq!(trace["would_deliver"], false);
+            assert!(trace["message"].is_object());
+
+            msg_mock.assert_async().await;
+        }
+    }
+
+    mod feeds {
+        use super::*;
+        use crate::{
+            delivery::{Delivery, Source},
+            slack::Message,
+        };
+
+        fn feed_req(uri: &str) -> Request<Body> {
+            Request::builder()
+                .uri(uri)
+                .header("Authorization", "Bearer foobar")
+                .body(Body::empty())
+                .unwrap()
+        }
+
+        fn msg(channel: &str, title: &str) -> Message {
+            Message::new(ChannelName(channel.to_owned()), title, "any")
+        }
+
+        #[tokio::test]
+        async fn test_missing_auth() {
+            let req = Request::builder()
+                .uri("/api/v1/feeds/heroku.atom")
                 .body(Body::empty())
                 .unwrap();
 
-            let res = router_().oneshot(req).await.unwrap();
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        #[tokio::test]
+        async fn test_not_found() {
+            let mut rt = router_();
+
+            for uri in ["/api/v1/feeds/heroku", "/api/v1/feeds/nope.atom"] {
+                let res = rt.call(feed_req(uri)).await.unwrap();
+
+                assert_eq!(res.status(), StatusCode::NOT_FOUND);
+            }
+        }
+
+        #[tokio::test]
+        async fn test_feed() {
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            let sent = Ok(Delivery::Sent);
+            deps.audit
+                .record(&msg("deploys", "first"), Source::Heroku, &sent);
+            deps.audit
+                .record(&msg("#alerts", "second"), Source::Heroku, &sent);
+            deps.audit
+                .record(&msg("deploys", "third"), Source::Api, &sent);
+            deps.audit.record(
+                &msg("deploys", "fourth"),
+                Source::Heroku,
+                &Ok(Delivery::Suppressed("read-only")),
+            );
+            let mut rt = super::new(deps);
 
-            assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            let res = rt
+                .call(feed_req("/api/v1/feeds/heroku.atom"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
             assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Requests must have `Content-Type: application/json`"
+                res.headers()["Content-Type"],
+                "application/atom+xml; charset=utf-8"
             );
+            let body = plaintext_body(res.into_body()).await;
+            assert!(body.contains("<title>second</title>"));
+            assert!(body.contains("<title>first</title>"));
+            assert!(body.find("second") < body.find("first"));
+            assert!(!body.contains("third"));
+            assert!(!body.contains("fourth"));
+
+            let res = rt
+                .call(feed_req("/api/v1/feeds/heroku.atom?channel=alerts"))
+                .await
+                .unwrap();
+            let body = plaintext_body(res.into_body()).await;
+            assert!(body.contains("<title>second</title>"));
+            assert!(!body.contains("first"));
         }
+    }
+
+    mod admin {
+        use super::*;
 
         #[tokio::test]
-        async fn test_missing_query() {
+        async fn test_missing_auth() {
             let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook")
-                .header("Content-Type", "application/json")
+                .uri("/api/v1/admin/read-only")
                 .body(Body::empty())
                 .unwrap();
 
             let res = router_().oneshot(req).await.unwrap();
 
-            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-            assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Failed to deserialize query string: missing field `platform`"
-            );
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
         }
 
         #[tokio::test]
-        async fn test_bad_platform() {
+        async fn test_bad_auth() {
             let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=discord")
-                .header("Content-Type", "application/json")
+                .uri("/api/v1/admin/read-only")
+                .header("Authorization", "Bearer foobar")
                 .body(Body::empty())
                 .unwrap();
 
             let res = router_().oneshot(req).await.unwrap();
 
-            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-            assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Failed to deserialize query string: unknown variant `discord`, expected `slack`"
-            );
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
         }
 
         #[tokio::test]
-        async fn test_missing_signature() {
+        async fn test_unavailable_without_token() {
             let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Content-Type", "application/json")
-                .body(Body::from(r#"{ "any": true }"#))
+                .uri("/api/v1/admin/read-only")
+                .header("Authorization", "Bearer admin")
+                .body(Body::empty())
                 .unwrap();
 
-            let res = router_().oneshot(req).await.unwrap();
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.admin_token = None;
 
-            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
-            assert!(plaintext_body(res.into_body()).await.is_empty());
+            let res = super::new(deps).oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
         }
 
+        #[cfg(feature = "chaos")]
         #[tokio::test]
-        async fn test_bad_signature() {
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", "bad signature")
-                .header("Content-Type", "application/json")
-                .body(Body::from(r#"{ "any": true }"#))
+        async fn test_chaos() {
+            let chaos_req = |method, body: &str| {
+                Request::builder()
+                    .method(method)
+                    .uri("/api/v1/admin/chaos")
+                    .header("Authorization", "Bearer admin")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_owned()))
+                    .unwrap()
+            };
+            let msg_req = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", "Bearer foobar")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(
+                        "channel=channel-name&title=a+title&desc=a+description",
+                    ))
+                    .unwrap()
+            };
+
+            let mut srv = server().await;
+
+            let list_mock = srv
+                .mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .expect(0)
+                .create_async()
+                .await;
+
+            let mut rt = super::new(deps(srv.url(), SlackAccessToken("foobar".to_owned()), None));
+
+            let res = rt
+                .call(chaos_req("PUT", r#"{"rate_limit_secs": 30, "calls": 1}"#))
+                .await
                 .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
 
-            let res = router_().oneshot(req).await.unwrap();
+            let res = rt.call(msg_req()).await.unwrap();
+            assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
 
-            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
-            assert!(plaintext_body(res.into_body()).await.is_empty());
+            // Exhausted after a single call.
+            let res = rt.call(chaos_req("GET", "")).await.unwrap();
+            assert_eq!(plaintext_body(res.into_body()).await, "null");
+
+            rt.call(chaos_req("PUT", r#"{"error": "invalid_auth"}"#))
+                .await
+                .unwrap();
+            let res = rt.call(chaos_req("DELETE", "")).await.unwrap();
+            assert_eq!(plaintext_body(res.into_body()).await, "null");
+
+            list_mock.assert_async().await;
         }
 
         #[tokio::test]
-        async fn test_bad_field() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": false
-                    },
-                    "description": "any"
+        async fn test_read_only() {
+            let req = |method| {
+                Request::builder()
+                    .method(method)
+                    .uri("/api/v1/admin/read-only")
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            let read_only = deps.read_only.clone();
+            let mut rt = super::new(deps);
+
+            let res1 = rt.call(req("GET")).await.unwrap();
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(
+                plaintext_body(res1.into_body()).await,
+                r#"{"read_only":false}"#
+            );
+
+            let res2 = rt.call(req("PUT")).await.unwrap();
+            assert_eq!(res2.status(), StatusCode::OK);
+            assert_eq!(
+                plaintext_body(res2.into_body()).await,
+                r#"{"read_only":true}"#
+            );
+            assert!(read_only.load(Ordering::Relaxed));
+
+            let res3 = rt.call(req("DELETE")).await.unwrap();
+            assert_eq!(res3.status(), StatusCode::OK);
+            assert_eq!(
+                plaintext_body(res3.into_body()).await,
+                r#"{"read_only":false}"#
+            );
+            assert!(!read_only.load(Ordering::Relaxed));
+        }
+
+        #[tokio::test]
+        async fn test_ingestion() {
+            let admin = |method, uri| {
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg = || {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", "Bearer foobar")
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from("channel=channel-name&title=any&desc=any"))
+                    .unwrap()
+            };
+
+            let list_res = r#"{
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
                 }
             }"#;
-            let sig = "WVN5qPUl+QWmSYErNMSXhHAYiIZ/hwCsaiZNjESj7Xs=";
 
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
-                .unwrap();
+            let mut srv = server().await;
 
-            let res = router_().oneshot(req).await.unwrap();
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(list_res)
+                .create_async()
+                .await;
 
-            assert_eq!(res.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
+
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            let mut rt = super::new(deps);
+
+            let res = rt
+                .call(admin("PUT", "/api/v1/admin/ingestion/api"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
             assert_eq!(
                 plaintext_body(res.into_body()).await,
-                "Failed to deserialize payload: invalid type: boolean `false`, expected a string"
+                r#"[{"source":"api","paused":true,"held":0},{"source":"heroku","paused":false,"held":0}]"#
             );
+
+            for _ in 0..2 {
+                let res = rt.call(msg()).await.unwrap();
+                assert_eq!(res.status(), StatusCode::OK);
+                assert_eq!(res.headers()["Mercury-Suppressed"], "paused");
+            }
+
+            let res = rt
+                .call(admin("GET", "/api/v1/admin/ingestion"))
+                .await
+                .unwrap();
+            assert!(plaintext_body(res.into_body())
+                .await
+                .contains(r#"{"source":"api","paused":true,"held":2}"#));
+
+            // Held messages are discarded unless caught up on.
+            let res = rt
+                .call(admin("DELETE", "/api/v1/admin/ingestion/api"))
+                .await
+                .unwrap();
+            assert!(plaintext_body(res.into_body())
+                .await
+                .contains(r#"{"source":"api","paused":false,"held":0}"#));
+
+            rt.call(admin("PUT", "/api/v1/admin/ingestion/api"))
+                .await
+                .unwrap();
+            rt.call(msg()).await.unwrap();
+            let res = rt
+                .call(admin("DELETE", "/api/v1/admin/ingestion/api?catch_up=true"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            // Catching up happens in the background.
+            for _ in 0..50 {
+                if msg_mock.matched_async().await {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            msg_mock.assert_async().await;
+
+            let res = rt
+                .call(admin("PUT", "/api/v1/admin/ingestion/replay"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST);
         }
 
         #[tokio::test]
-        async fn test_unsupported_event() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": "any"
-                    },
-                    "description": "any",
-                    "user": {
-                        "email": "hodor@unsplash.com"
-                    }
-                },
-                "action": "update"
-            }"#;
-            let sig = "0+jCzQsgvzi0SL0haDhB18ttbTNEYYlrwhtpL0FEVGw=";
+        async fn test_captures() {
+            let get = |source| {
+                Request::builder()
+                    .uri(format!("/api/v1/admin/captures/{}", source))
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg = |token| {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from("channel=any&title=any&desc=xoxb-123"))
+                    .unwrap()
+            };
+
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            // Avoids the need to mock Slack.
+            deps.read_only.store(true, Ordering::Relaxed);
 
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
+            let res = super::new(deps.clone()).oneshot(get("api")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+            deps.captures = Some(Arc::new(Captures::new(10)));
+            let mut rt = super::new(deps);
+
+            let res = rt.call(msg("foobar")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+
+            // Unauthenticated requests aren't captured.
+            let res = rt.call(msg("unknown")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+            let res = rt
+                .call(
+                    Request::builder()
+                        .method("POST")
+                        .uri("/api/v1/heroku/hook?platform=slack&channel=any")
+                        .header("Content-Type", "application/json")
+                        .body(Body::from("{}"))
+                        .unwrap(),
+                )
+                .await
                 .unwrap();
+            assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
 
-            let res = router_().oneshot(req).await.unwrap();
+            let res = rt.call(get("api")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            let xs: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(xs.as_array().unwrap().len(), 1);
+            assert_eq!(xs[0]["method"], "POST");
+            assert_eq!(xs[0]["uri"], "/");
+            assert_eq!(xs[0]["body"], "channel=any&title=any&desc=[REDACTED]");
+            assert_eq!(xs[0]["status"], 200);
+            assert_eq!(xs[0]["suppressed"], "read-only");
+
+            let res = rt.call(get("heroku")).await.unwrap();
+            assert_eq!(plaintext_body(res.into_body()).await, "[]");
+        }
+
+        #[tokio::test]
+        async fn test_fixtures() {
+            let admin = |method| {
+                Request::builder()
+                    .method(method)
+                    .uri("/api/v1/admin/fixtures")
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg = |token| {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from(
+                        "channel=any&title=any&desc=hodor%40unsplash.com",
+                    ))
+                    .unwrap()
+            };
+
+            let mut deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            // Avoids the need to mock Slack.
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let res = super::new(deps.clone())
+                .oneshot(admin("GET"))
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+            let dir = std::env::temp_dir().join(format!(
+                "mercury-router-fixtures-test-{}",
+                std::process::id()
+            ));
+            deps.fixtures = Some(Arc::new(Fixtures::new(&dir)));
+            let mut rt = super::new(deps);
+
+            // Not yet recording.
+            rt.call(msg("foobar")).await.unwrap();
+            assert!(!dir.exists());
+
+            let res = rt.call(admin("PUT")).await.unwrap();
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"recording":true}"#
+            );
 
+            let res = rt.call(msg("foobar")).await.unwrap();
             assert_eq!(res.status(), StatusCode::OK);
-            assert!(plaintext_body(res.into_body()).await.is_empty());
+
+            // Unauthenticated requests aren't recorded.
+            let res = rt.call(msg("unknown")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+            let xs: Vec<_> = std::fs::read_dir(dir.join("api"))
+                .unwrap()
+                .map(|x| x.unwrap().path())
+                .collect();
+            assert_eq!(xs.len(), 1);
+            let x: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(&xs[0]).unwrap()).unwrap();
+            assert_eq!(x["path"], "/api/v1/slack");
+            assert_eq!(
+                x["requests"][0],
+                "channel=any&title=any&desc=user%40example.com"
+            );
+            assert!(x["headers"].get("authorization").is_none());
+
+            let res = rt.call(admin("DELETE")).await.unwrap();
+            assert_eq!(
+                plaintext_body(res.into_body()).await,
+                r#"{"recording":false}"#
+            );
+
+            std::fs::remove_dir_all(dir).unwrap();
         }
 
         #[tokio::test]
-        async fn test_ignored_action() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": "any"
-                    },
-                    "description": "any",
-                    "user": {
-                        "email": "hodor@unsplash.com"
-                    }
-                },
-                "action": "create"
-            }"#;
-            let sig = "F5ArFnV9sfXsDmk9ubM24fu6gVVxEXl1TOdt1XTVokg=";
+        async fn test_rotate_secrets() {
+            let rotate = |body: &'static str| {
+                Request::builder()
+                    .method("PUT")
+                    .uri("/api/v1/admin/secrets")
+                    .header("Authorization", "Bearer admin")
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap()
+            };
+
+            let msg = |token: &str| {
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/v1/slack")
+                    .header("Authorization", format!("Bearer {}", token))
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Body::from("channel=any&title=any&desc=any"))
+                    .unwrap()
+            };
+
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                Some(HerokuSecret("foobarbaz".to_owned())),
+            );
+            // Avoids the need to mock Slack, we only care about authentication.
+            deps.read_only.store(true, Ordering::Relaxed);
+            let heroku_secrets = deps.heroku_secrets.clone();
+            let slack_token_expires_at = deps.slack_token_expires_at.clone();
+            let mut rt = super::new(deps);
+
+            let res1 = rt
+                .call(rotate(
+                    r#"{"slack_token":"xoxb-new","slack_token_expires_at":1700000000,"heroku_secrets":["new","foobarbaz"],"api_tokens":["api"]}"#,
+                ))
+                .await
+                .unwrap();
+            assert_eq!(res1.status(), StatusCode::NO_CONTENT);
+
+            assert_eq!(
+                slack_token_expires_at
+                    .load()
+                    .as_ref()
+                    .map(|x| x.timestamp()),
+                Some(1700000000)
+            );
+
+            let secrets = heroku_secrets.load();
+            assert_eq!(secrets.len(), 2);
+            assert_eq!(secrets[0].0, "new");
+            assert_eq!(secrets[1].0, "foobarbaz");
+
+            let res2 = rt.call(msg("api")).await.unwrap();
+            assert_eq!(res2.status(), StatusCode::OK);
+
+            // The Slack token is only accepted in compatibility mode, and only
+            // once rotated.
+            let res3 = rt.call(msg("xoxb-new")).await.unwrap();
+            assert_eq!(res3.status(), StatusCode::OK);
+
+            let res4 = rt.call(msg("foobar")).await.unwrap();
+            assert_eq!(res4.status(), StatusCode::UNAUTHORIZED);
 
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
-                .unwrap();
+            let res5 = rt.call(rotate(r#"{"api_tokens":[""]}"#)).await.unwrap();
+            assert_eq!(res5.status(), StatusCode::UNPROCESSABLE_ENTITY);
 
-            let res = router_().oneshot(req).await.unwrap();
+            let res6 = rt.call(msg("api")).await.unwrap();
+            assert_eq!(res6.status(), StatusCode::OK);
+        }
 
-            assert_eq!(res.status(), StatusCode::OK);
-            assert!(plaintext_body(res.into_body()).await.is_empty());
+        fn selftest_req() -> Request<Body> {
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/admin/selftest")
+                .header("Authorization", "Bearer admin")
+                .body(Body::empty())
+                .unwrap()
         }
 
         #[tokio::test]
-        async fn test_slack_failure_auth() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": "any"
-                    },
-                    "description": "Rollback to v1234",
-                    "user": {
-                        "email": "hodor@unsplash.com"
-                    }
-                },
-                "action": "update"
-            }"#;
-            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+        async fn test_selftest_unconfigured() {
+            let res = router_().oneshot(selftest_req()).await.unwrap();
 
-            let req = Request::builder()
-                .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
-                .unwrap();
+            assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+        }
 
+        #[tokio::test]
+        async fn test_selftest() {
             let list_res = r#"{
-                "ok": false,
-                "error": "invalid_auth"
+                "ok": true,
+                "channels": [{
+                    "id": "canary-id",
+                    "name": "canary"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
             }"#;
 
             let mut srv = server().await;
 
-            let list_mock = srv
-                .mock("GET", "/conversations.list")
+            srv.mock("GET", "/conversations.list")
                 .match_query(Matcher::Any)
                 .with_body(list_res)
                 .create_async()
                 .await;
 
-            let res = router(
-                srv.url(),
-                SlackAccessToken("foobar".to_owned()),
-                Some(HerokuSecret("foobarbaz".to_owned())),
-            )
-            .oneshot(req)
-            .await
-            .unwrap();
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "canary-id",
+                        "username": "Mercury self-test"
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(r#"{"ok": true}"#)
+                .create_async()
+                .await;
 
-            list_mock.assert_async().await;
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.selftest_channel = Some(ChannelName("canary".to_owned()));
 
-            assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
-            assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Slack API returned error: invalid_auth"
-            );
+            let res = super::new(deps).oneshot(selftest_req()).await.unwrap();
+
+            msg_mock.assert_async().await;
+
+            assert_eq!(res.status(), StatusCode::OK);
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(body["ok"], true);
+            assert!(body["latency_ms"].is_u64());
         }
 
         #[tokio::test]
-        async fn test_slack_failure_unknown() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": "any"
-                    },
-                    "description": "Rollback to v1234",
-                    "user": {
-                        "email": "hodor@unsplash.com"
-                    }
-                },
-                "action": "update"
-            }"#;
-            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+        async fn test_selftest_failure() {
+            let mut srv = server().await;
 
-            let req = Request::builder()
+            srv.mock("GET", "/conversations.list")
+                .match_query(Matcher::Any)
+                .with_body(r#"{"ok":false,"error":"invalid_auth"}"#)
+                .create_async()
+                .await;
+
+            let mut deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.selftest_channel = Some(ChannelName("canary".to_owned()));
+
+            let res = super::new(deps).oneshot(selftest_req()).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::BAD_GATEWAY);
+            let body: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(body["ok"], false);
+            assert_eq!(body["error"], "Slack API returned error: invalid_auth");
+        }
+
+        #[tokio::test]
+        async fn test_audit_replay() {
+            let admin_req = |method, uri| {
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg_req = Request::builder()
                 .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=foo")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(
+                    "channel=channel-name&title=a+title&desc=a+description",
+                ))
                 .unwrap();
 
             let list_res = r#"{
-                "ok": false,
-                "error": "something else"
+                "ok": true,
+                "channels": [{
+                    "id": "channel-id",
+                    "name": "channel-name"
+                }, {
+                    "id": "other-id",
+                    "name": "other-name"
+                }],
+                "response_metadata": {
+                    "next_cursor": ""
+                }
             }"#;
 
             let mut srv = server().await;
 
-            let list_mock = srv
-                .mock("GET", "/conversations.list")
+            srv.mock("GET", "/conversations.list")
                 .match_query(Matcher::Any)
                 .with_body(list_res)
                 .create_async()
                 .await;
 
-            let res = router(
-                srv.url(),
-                SlackAccessToken("foobar".to_owned()),
-                Some(HerokuSecret("foobarbaz".to_owned())),
-            )
-            .oneshot(req)
-            .await
-            .unwrap();
+            let msg_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "channel-id",
+                        "username": "a title"
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
 
-            list_mock.assert_async().await;
+            let redirected_mock = srv
+                .mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{
+                        "channel": "other-id",
+                        "username": "a title"
+                    }"#
+                    .to_owned(),
+                ))
+                .with_body(r#"{"ok": true}"#)
+                .expect(1)
+                .create_async()
+                .await;
 
-            assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
-            assert_eq!(
-                plaintext_body(res.into_body()).await,
-                "Slack API returned error: something else"
-            );
+            let deps = deps(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+            deps.read_only.store(true, Ordering::Relaxed);
+            let read_only = deps.read_only.clone();
+            let mut rt = super::new(deps);
+
+            let res1 = rt.call(msg_req).await.unwrap();
+            assert_eq!(res1.headers()["Mercury-Suppressed"], "read-only");
+
+            let res2 = rt
+                .call(admin_req("GET", "/api/v1/admin/audit/1"))
+                .await
+                .unwrap();
+            assert_eq!(res2.status(), StatusCode::OK);
+            let entry: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res2.into_body()).await).unwrap();
+            assert_eq!(entry["message"]["title"], "a title");
+            assert_eq!(entry["outcome"]["status"], "suppressed");
+            assert_eq!(entry["outcome"]["reason"], "read-only");
+
+            read_only.store(false, Ordering::Relaxed);
+
+            let res3 = rt
+                .call(admin_req("POST", "/api/v1/admin/audit/1/replay"))
+                .await
+                .unwrap();
+            assert_eq!(res3.status(), StatusCode::OK);
+
+            msg_mock.assert_async().await;
+
+            // The replay is itself recorded.
+            let res4 = rt
+                .call(admin_req("GET", "/api/v1/admin/audit/2"))
+                .await
+                .unwrap();
+            let entry: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res4.into_body()).await).unwrap();
+            assert_eq!(entry["outcome"]["status"], "sent");
+
+            let res5 = rt
+                .call(admin_req(
+                    "POST",
+                    "/api/v1/admin/audit/1/replay?channel=other-name",
+                ))
+                .await
+                .unwrap();
+            assert_eq!(res5.status(), StatusCode::OK);
+
+            redirected_mock.assert_async().await;
+
+            let res6 = rt
+                .call(admin_req("POST", "/api/v1/admin/audit/9/replay"))
+                .await
+                .unwrap();
+            assert_eq!(res6.status(), StatusCode::NOT_FOUND);
         }
 
         #[tokio::test]
-        async fn test_slack_success() {
-            let payload = r#"{
-                "resource": "release",
-                "data": {
-                    "app": {
-                        "name": "any"
-                    },
-                    "description": "Rollback to v1234",
-                    "user": {
-                        "email": "hodor@unsplash.com"
-                    }
-                },
-                "action": "update"
-            }"#;
-            let sig = "GxMZ9dos5w6r9V0JTDyeWprKmd3JW+i4otfkkDV463M=";
+        async fn test_audit_export() {
+            let export_req = |query: &str| {
+                Request::builder()
+                    .uri(format!("/api/v1/admin/audit/export{}", query))
+                    .header("Authorization", "Bearer admin")
+                    .body(Body::empty())
+                    .unwrap()
+            };
+
+            let msg_req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(
+                    "channel=channel-name&title=a+title&desc=a+description",
+                ))
+                .unwrap();
 
-            let req = Request::builder()
+            let deps = deps(
+                "any".to_owned(),
+                SlackAccessToken("foobar".to_owned()),
+                None,
+            );
+            deps.read_only.store(true, Ordering::Relaxed);
+            let mut rt = super::new(deps);
+
+            rt.call(msg_req).await.unwrap();
+
+            let res1 = rt.call(export_req("?format=csv")).await.unwrap();
+            assert_eq!(res1.status(), StatusCode::OK);
+            assert_eq!(res1.headers()["Content-Type"], "text/csv");
+            let body = plaintext_body(res1.into_body()).await;
+            let lines: Vec<_> = body.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert!(lines[1].ends_with(",channel-name,a title,a description,suppressed,read-only"));
+
+            let res2 = rt.call(export_req("")).await.unwrap();
+            assert_eq!(res2.headers()["Content-Type"], "application/x-ndjson");
+            assert_eq!(plaintext_body(res2.into_body()).await.lines().count(), 1);
+
+            let res3 = rt
+                .call(export_req("?from=2100-01-01T00:00:00Z"))
+                .await
+                .unwrap();
+            assert!(plaintext_body(res3.into_body()).await.is_empty());
+
+            let res4 = rt.call(export_req("?from=yesterday")).await.unwrap();
+            assert_eq!(res4.status(), StatusCode::BAD_REQUEST);
+        }
+
+        #[tokio::test]
+        async fn test_stats() {
+            let msg_req = Request::builder()
                 .method("POST")
-                .uri("/api/v1/heroku/hook?platform=slack&channel=channel-name")
-                .header("Heroku-Webhook-Hmac-SHA256", sig)
-                .header("Content-Type", "application/json")
-                .body(Body::from(payload))
+                .uri("/api/v1/slack")
+                .header("Authorization", "Bearer foobar")
+                .header("Content-Type", "application/x-www-form-urlencoded")
+                .body(Body::from(
+                    "channel=channel-name&title=a+title&desc=a+description",
+                ))
+                .unwrap();
+
+            let stats_req = Request::builder()
+                .uri("/api/v1/admin/stats")
+                .header("Authorization", "Bearer admin")
+                .body(Body::empty())
                 .unwrap();
 
             let list_res = r#"{
@@ -1037,39 +5278,128 @@ mod tests {
                 }
             }"#;
 
-            let msg_res = r#"{
-                "ok": true
-            }"#;
-
             let mut srv = server().await;
 
-            let list_mock = srv
-                .mock("GET", "/conversations.list")
+            srv.mock("GET", "/conversations.list")
                 .match_query(Matcher::Any)
                 .with_body(list_res)
                 .create_async()
                 .await;
 
-            let msg_mock = srv
-                .mock("POST", "/chat.postMessage")
-                .with_body(msg_res)
+            srv.mock("POST", "/chat.postMessage")
+                .with_body(r#"{"ok": true}"#)
                 .create_async()
                 .await;
 
-            let res = router(
-                srv.url(),
+            let mut rt = router(srv.url(), SlackAccessToken("foobar".to_owned()), None);
+
+            let res1 = rt.call(msg_req).await.unwrap();
+            assert_eq!(res1.status(), StatusCode::OK);
+
+            let res2 = rt.call(stats_req).await.unwrap();
+            assert_eq!(res2.status(), StatusCode::OK);
+
+            let stats: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res2.into_body()).await).unwrap();
+            assert_eq!(
+                stats["last_hour"],
+                serde_json::json!({
+                    "total": 1,
+                    "channels": { "channel-name": 1 },
+                    "sources": { "api": 1 }
+                })
+            );
+            assert_eq!(stats["last_day"]["total"], 1);
+        }
+
+        #[tokio::test]
+        async fn test_graphql() {
+            use crate::{
+                delivery::{Delivery, Source},
+                slack::{Message, Severity},
+            };
+
+            let mut deps = deps(
+                "any".to_owned(),
                 SlackAccessToken("foobar".to_owned()),
-                Some(HerokuSecret("foobarbaz".to_owned())),
-            )
-            .oneshot(req)
-            .await
-            .unwrap();
+                None,
+            );
+            deps.heroku_app_routes = Arc::new(crate::heroku::routing::parse_app_routes(
+                "api-*:api-deploys:@api-team",
+            ));
+            deps.read_only.store(true, Ordering::Relaxed);
+
+            let msg = |title: &str| Message {
+                severity: Some(Severity::Critical),
+                ..Message::new(ChannelName("deploys".to_owned()), title, "any")
+            };
+            deps.audit
+                .record(&msg("first"), Source::Heroku, &Ok(Delivery::Sent));
+            deps.audit.record(
+                &msg("second"),
+                Source::Api,
+                &Ok(Delivery::Suppressed("read-only")),
+            );
 
-            list_mock.assert_async().await;
-            msg_mock.assert_async().await;
+            let query = r#"{
+                auditEntries(status: SUPPRESSED) { id title source severity status detail }
+                config { readOnly appRoutes { pattern channel cc } escalationPolicy { afterMins } }
+                caches { channels { entries } }
+            }"#;
+            let req = Request::builder()
+                .method("POST")
+                .uri("/api/v1/admin/graphql")
+                .header("Authorization", "Bearer admin")
+                .header("Content-Type", "application/json")
+                .body(Body::from(
+                    serde_json::json!({ "query": query }).to_string(),
+                ))
+                .unwrap();
 
+            let res = super::new(deps).oneshot(req).await.unwrap();
             assert_eq!(res.status(), StatusCode::OK);
-            assert!(plaintext_body(res.into_body()).await.is_empty());
+
+            let res: serde_json::Value =
+                serde_json::from_str(&plaintext_body(res.into_body()).await).unwrap();
+            assert_eq!(
+                res["data"],
+                serde_json::json!({
+                    "auditEntries": [{
+                        "id": 2,
+                        "title": "second",
+                        "source": "API",
+                        "severity": "CRITICAL",
+                        "status": "SUPPRESSED",
+                        "detail": "read-only"
+                    }],
+                    "config": {
+                        "readOnly": true,
+                        "appRoutes": [{
+                            "pattern": "api-*",
+                            "channel": "api-deploys",
+                            "cc": "@api-team"
+                        }],
+                        "escalationPolicy": []
+                    },
+                    "caches": { "channels": null }
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_ui() {
+            let req = Request::builder()
+                .uri("/api/v1/admin/ui")
+                .body(Body::empty())
+                .unwrap();
+
+            let res = router_().oneshot(req).await.unwrap();
+
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers()["Content-Type"], "text/html; charset=utf-8");
+            assert!(plaintext_body(res.into_body())
+                .await
+                .contains("<title>Mercury</title>"));
         }
     }
 }
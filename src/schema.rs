@@ -0,0 +1,88 @@
+//! JSON Schemas of inbound payloads, enabling integrators to validate them
+//! client-side. Schemas are generated from the same types requests are
+//! deserialised into, so they can't drift.
+//!
+//! The following subroutes are supported:
+//!
+//! - GET: `/`
+//! - GET: `/message.json`
+//! - GET: `/heroku/hook.json`
+//! - GET: `/heroku/release.json`
+//! - GET: `/heroku/dyno.json`
+
+use crate::{
+    heroku::payload::{DynoHookPayload, HookPayload, ReleaseHookPayload},
+    router::Deps,
+    slack::Message,
+};
+use axum::{routing::get, Json, Router};
+use schemars::{json_schema, schema_for, JsonSchema, Schema};
+use serde_json::Value;
+
+/// The schemas available, relative to the subrouter.
+const SCHEMAS: [&str; 4] = [
+    "message.json",
+    "heroku/hook.json",
+    "heroku/release.json",
+    "heroku/dyno.json",
+];
+
+/// Instantiate a new schema subrouter. Schemas aren't sensitive, so aren't
+/// authenticated.
+pub fn schema_router() -> Router<Deps> {
+    Router::new()
+        .route("/", get(|| async { Json(SCHEMAS) }))
+        .route(
+            "/message.json",
+            get(|| async { Json(schema_for!(Message)) }),
+        )
+        .route(
+            "/heroku/hook.json",
+            get(|| async { Json(schema_for!(HookPayload)) }),
+        )
+        .route(
+            "/heroku/release.json",
+            get(|| async { Json(tagged::<ReleaseHookPayload>("release")) }),
+        )
+        .route(
+            "/heroku/dyno.json",
+            get(|| async { Json(tagged::<DynoHookPayload>("dyno")) }),
+        )
+}
+
+/// The schema of a single [HookPayload] variant, including the `resource` tag
+/// it's identified by.
+fn tagged<T: JsonSchema>(tag: &str) -> Schema {
+    let mut schema = schema_for!(T);
+
+    if let Some(Value::Object(xs)) = schema.get_mut("properties") {
+        xs.insert("resource".into(), json_schema!({ "const": tag }).to_value());
+    }
+
+    if let Some(Value::Array(xs)) = schema.get_mut("required") {
+        xs.push("resource".into());
+    }
+
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tagged() {
+        let schema = tagged::<DynoHookPayload>("dyno");
+
+        assert_eq!(
+            schema.get("properties").unwrap()["resource"]["const"],
+            "dyno"
+        );
+        assert!(schema
+            .get("required")
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .contains(&"resource".into()));
+    }
+}
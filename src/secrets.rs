@@ -0,0 +1,80 @@
+//! Load secrets from wherever they're kept, rather than requiring that they be
+//! injected directly into the environment.
+//!
+//! Any secret Mercury uses, for example `SLACK_TOKEN`, can be supplied in any
+//! of the following ways, checked in order:
+//!
+//! - `$SLACK_TOKEN`: The secret itself.
+//! - `$SLACK_TOKEN_FILE`: A path to a file containing the secret, as per
+//!   Docker and Kubernetes secret mounts. Surrounding whitespace is trimmed.
+//! - `$SLACK_TOKEN_AWS_SECRET`: The name or ARN of a secret in AWS Secrets
+//!   Manager. See [aws].
+//! - `$SLACK_TOKEN_VAULT`: The path to a secret in Vault and the key within
+//!   it. See [vault].
+
+pub mod aws;
+pub mod error;
+pub mod vault;
+
+pub use error::SecretError;
+
+use std::{env, fs};
+
+/// Load a secret by name from the first source it's configured for, if any.
+pub async fn load(name: &str) -> Result<Option<String>, SecretError> {
+    if let Ok(x) = env::var(name) {
+        return Ok(Some(x));
+    }
+
+    if let Ok(path) = env::var(format!("{}_FILE", name)) {
+        return read_file(&path).map(Some);
+    }
+
+    if let Ok(id) = env::var(format!("{}_AWS_SECRET", name)) {
+        let client = aws::SecretsManagerClient::from_env()?;
+        return client.get_secret(&id).await.map(Some);
+    }
+
+    if let Ok(path) = env::var(format!("{}_VAULT", name)) {
+        let client = vault::VaultClient::from_env()?;
+        return client.get_secret(&path).await.map(Some);
+    }
+
+    Ok(None)
+}
+
+/// Read a secret from a file, trimming surrounding whitespace such as the
+/// trailing newline most editors insert.
+fn read_file(path: &str) -> Result<String, SecretError> {
+    fs::read_to_string(path)
+        .map(|x| x.trim().to_owned())
+        .map_err(|e| SecretError::FileReadFailed(path.to_owned(), e))
+}
+
+/// Get a required environment variable for configuring a secret source.
+fn require_env(name: &'static str) -> Result<String, SecretError> {
+    env::var(name).map_err(|_| SecretError::MissingConfig(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_file() {
+        let path = env::temp_dir().join("mercury-test-read-file");
+        fs::write(&path, "  foobar\n").unwrap();
+
+        assert_eq!(read_file(path.to_str().unwrap()).unwrap(), "foobar");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        assert!(matches!(
+            read_file("/nonexistent/mercury"),
+            Err(SecretError::FileReadFailed(_, _))
+        ));
+    }
+}
@@ -0,0 +1,281 @@
+//! Fetch secrets from AWS Secrets Manager.
+//!
+//! Credentials are sourced from `$AWS_ACCESS_KEY_ID`, `$AWS_SECRET_ACCESS_KEY`,
+//! and optionally `$AWS_SESSION_TOKEN`, and the region from `$AWS_REGION`.
+//! Only secrets stored as strings are supported.
+//!
+//! Rather than pull in the AWS SDK for a single request at startup, requests
+//! are signed by hand.
+//!
+//! <https://docs.aws.amazon.com/secretsmanager/latest/apireference/API_GetSecretValue.html>
+//! <https://docs.aws.amazon.com/IAM/latest/UserGuide/create-signed-request.html>
+
+use super::{require_env, SecretError};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+/// The service name used in request signing.
+const SERVICE: &str = "secretsmanager";
+
+/// The API action we're performing.
+const TARGET: &str = "secretsmanager.GetSecretValue";
+
+const CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// Long or short-term AWS credentials.
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// Holds a client request pool against a base URL.
+pub struct SecretsManagerClient {
+    client: reqwest::Client,
+    base_url: String,
+    region: String,
+    credentials: AwsCredentials,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GetSecretValueResponse {
+    secret_string: Option<String>,
+}
+
+impl SecretsManagerClient {
+    /// Instantiate against a given base URL, enabling easy mocking.
+    pub fn new(base_url: String, region: String, credentials: AwsCredentials) -> Self {
+        SecretsManagerClient {
+            client: reqwest::Client::new(),
+            base_url,
+            region,
+            credentials,
+        }
+    }
+
+    /// Instantiate against the regional endpoint as configured by the
+    /// environment.
+    pub fn from_env() -> Result<Self, SecretError> {
+        let region = require_env("AWS_REGION")?;
+        let credentials = AwsCredentials {
+            access_key_id: require_env("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: require_env("AWS_SECRET_ACCESS_KEY")?,
+            session_token: require_env("AWS_SESSION_TOKEN").ok(),
+        };
+
+        Ok(Self::new(
+            format!("https://{}.{}.amazonaws.com", SERVICE, region),
+            region,
+            credentials,
+        ))
+    }
+
+    /// Get the string value of a secret by its name or ARN.
+    pub async fn get_secret(&self, id: &str) -> Result<String, SecretError> {
+        let body = serde_json::json!({ "SecretId": id }).to_string();
+        let now = Utc::now();
+
+        let mut req = self
+            .client
+            .post(&self.base_url)
+            .header(reqwest::header::CONTENT_TYPE, CONTENT_TYPE)
+            .header("X-Amz-Target", TARGET)
+            .header("X-Amz-Date", fmt_amz_date(&now))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                self.sign(&now, &host(&self.base_url), &body),
+            );
+
+        if let Some(t) = &self.credentials.session_token {
+            req = req.header("X-Amz-Security-Token", t);
+        }
+
+        let res: GetSecretValueResponse = req
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        res.secret_string
+            .ok_or_else(|| SecretError::NotFound(id.to_owned()))
+    }
+
+    /// Produce a Signature Version 4 `Authorization` header value for a
+    /// request.
+    fn sign(&self, now: &DateTime<Utc>, host: &str, body: &str) -> String {
+        let amz_date = fmt_amz_date(now);
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, SERVICE);
+
+        let mut headers = vec![
+            ("content-type", CONTENT_TYPE),
+            ("host", host),
+            ("x-amz-date", &amz_date),
+            ("x-amz-target", TARGET),
+        ];
+        if let Some(t) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", t));
+        }
+
+        let signed_headers = headers
+            .iter()
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect();
+
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            hex::encode(Sha256::digest(body))
+        );
+
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request))
+        );
+
+        let key = signing_key(
+            &self.credentials.secret_access_key,
+            &date,
+            &self.region,
+            SERVICE,
+        );
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.credentials.access_key_id,
+            scope,
+            signed_headers,
+            hex::encode(hmac(&key, &string_to_sign))
+        )
+    }
+}
+
+/// Derive the key used to sign requests for a given day, region, and service.
+fn signing_key(secret: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    [date, region, service, "aws4_request"]
+        .iter()
+        .fold(format!("AWS4{}", secret).into_bytes(), |k, x| hmac(&k, x))
+}
+
+fn hmac(key: &[u8], x: &str) -> Vec<u8> {
+    type HmacSha256 = Hmac<Sha256>;
+
+    // HMAC accepts keys of any length.
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(x.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn fmt_amz_date(x: &DateTime<Utc>) -> String {
+    x.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Get the host, including any non-default port, as it'll appear in the `Host`
+/// header.
+fn host(base_url: &str) -> String {
+    Url::parse(base_url)
+        .ok()
+        .and_then(|u| {
+            u.host_str().map(|h| match u.port() {
+                None => h.to_owned(),
+                Some(p) => format!("{}:{}", h, p),
+            })
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(base_url: String) -> SecretsManagerClient {
+        SecretsManagerClient::new(
+            base_url,
+            "us-east-1".to_owned(),
+            AwsCredentials {
+                access_key_id: "AKIDEXAMPLE".to_owned(),
+                secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+                session_token: None,
+            },
+        )
+    }
+
+    /// The example given in AWS' own documentation.
+    #[test]
+    fn test_signing_key() {
+        assert_eq!(
+            hex::encode(signing_key(
+                "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+                "20120215",
+                "us-east-1",
+                "iam"
+            )),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn test_sign() {
+        let now = DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .into();
+
+        assert_eq!(
+            client("any".to_owned()).sign(
+                &now,
+                "secretsmanager.us-east-1.amazonaws.com",
+                r#"{"SecretId":"mercury"}"#
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240102/us-east-1/secretsmanager/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-target, Signature=8f4ad8c7d3b544d94b252a4dc173372ea7793c9be3a43c62dc3fc1329577c05c"
+        );
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            host("https://secretsmanager.us-east-1.amazonaws.com"),
+            "secretsmanager.us-east-1.amazonaws.com"
+        );
+        assert_eq!(host("http://127.0.0.1:1234"), "127.0.0.1:1234");
+    }
+
+    #[tokio::test]
+    async fn test_get_secret() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/")
+            .match_header("X-Amz-Target", TARGET)
+            .match_header(
+                "Authorization",
+                mockito::Matcher::Regex("^AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/".to_owned()),
+            )
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"SecretId":"mercury"}"#.to_owned(),
+            ))
+            .with_body(r#"{"Name":"mercury","SecretString":"xoxb-foo"}"#)
+            .create_async()
+            .await;
+
+        assert_eq!(
+            client(srv.url()).get_secret("mercury").await.ok(),
+            Some("xoxb-foo".to_owned())
+        );
+
+        mock.assert_async().await;
+    }
+}
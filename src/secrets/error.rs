@@ -0,0 +1,36 @@
+//! Captures what failure can look like when loading secrets.
+
+use std::{fmt, io};
+
+/// Every possible unexceptional fail case when loading secrets.
+#[derive(Debug)]
+pub enum SecretError {
+    /// A file was configured as the source of a secret but couldn't be read.
+    FileReadFailed(String, io::Error),
+    /// A secret manager was configured without some necessary configuration,
+    /// as identified by its environment variable.
+    MissingConfig(&'static str),
+    /// General request failure, including unsuccessful status codes.
+    RequestFailed(reqwest::Error),
+    /// The secret manager responded successfully but without the secret.
+    NotFound(String),
+}
+
+impl From<reqwest::Error> for SecretError {
+    fn from(e: reqwest::Error) -> Self {
+        SecretError::RequestFailed(e)
+    }
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let x = match self {
+            SecretError::FileReadFailed(path, e) => format!("Could not read {}: {}", path, e),
+            SecretError::MissingConfig(x) => format!("No ${} environment variable found", x),
+            SecretError::RequestFailed(e) => format!("Secret request failed: {:?}", e),
+            SecretError::NotFound(x) => format!("Secret not found: {}", x),
+        };
+
+        write!(f, "{}", x)
+    }
+}
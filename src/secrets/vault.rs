@@ -0,0 +1,113 @@
+//! Fetch secrets from HashiCorp Vault's KV version 2 secrets engine.
+//!
+//! Secrets are referenced by their API path and the key within them, separated
+//! by `#`, for example `secret/data/mercury#slack_token`. The server and a
+//! token with which to read the secret are sourced from `$VAULT_ADDR` and
+//! `$VAULT_TOKEN`.
+//!
+//! <https://developer.hashicorp.com/vault/api-docs/secret/kv/kv-v2#read-secret-version>
+
+use super::{require_env, SecretError};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Holds a client request pool against a Vault server.
+pub struct VaultClient {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+#[derive(Deserialize)]
+struct ReadResponse {
+    data: ReadResponseData,
+}
+
+#[derive(Deserialize)]
+struct ReadResponseData {
+    data: HashMap<String, String>,
+}
+
+impl VaultClient {
+    /// Instantiate against a given base URL, enabling easy mocking.
+    pub fn new(base_url: String, token: String) -> Self {
+        VaultClient {
+            client: reqwest::Client::new(),
+            base_url,
+            token,
+        }
+    }
+
+    /// Instantiate as configured by the environment.
+    pub fn from_env() -> Result<Self, SecretError> {
+        Ok(Self::new(
+            require_env("VAULT_ADDR")?,
+            require_env("VAULT_TOKEN")?,
+        ))
+    }
+
+    /// Get a secret by its path and key, for example
+    /// `secret/data/mercury#slack_token`.
+    pub async fn get_secret(&self, reference: &str) -> Result<String, SecretError> {
+        let (path, key) = reference
+            .split_once('#')
+            .ok_or_else(|| SecretError::NotFound(reference.to_owned()))?;
+
+        let mut res: ReadResponse = self
+            .client
+            .get(format!(
+                "{}/v1/{}",
+                self.base_url.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            ))
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        res.data
+            .data
+            .remove(key)
+            .ok_or_else(|| SecretError::NotFound(reference.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_get_secret() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("GET", "/v1/secret/data/mercury")
+            .match_header("X-Vault-Token", "foobar")
+            .with_body(r#"{"data":{"data":{"slack_token":"xoxb-foo"},"metadata":{}}}"#)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = VaultClient::new(srv.url(), "foobar".to_owned());
+
+        assert_eq!(
+            client
+                .get_secret("secret/data/mercury#slack_token")
+                .await
+                .ok(),
+            Some("xoxb-foo".to_owned())
+        );
+        assert!(matches!(
+            client.get_secret("secret/data/mercury#other").await,
+            Err(SecretError::NotFound(_))
+        ));
+        assert!(matches!(
+            client.get_secret("secret/data/mercury").await,
+            Err(SecretError::NotFound(_))
+        ));
+
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,278 @@
+//! Print the headers with which to sign a request via `mercury sign`, so that
+//! integrators can test their own signing against a local Mercury.
+//!
+//! ```console
+//! $ MERCURY_SIGNING_SECRET=<SECRET> mercury sign heroku payload.json
+//! Heroku-Webhook-Hmac-SHA256: luDEVkRg2AxxcflGmamyN5mOPleyccUZdkg+C0MoRBY=
+//! ```
+//!
+//! The secret is read from [SECRET_VAR] rather than the command line, where
+//! it'd be visible to other processes and kept in shell history.
+//!
+//! The following schemes are supported:
+//!
+//! - `mercury`: Mercury's own API, as per [crate::signing], which requires
+//!   `--client <NAME>`.
+//! - `heroku`: Heroku webhooks, as per [crate::heroku::auth].
+//! - `slack`: Slack interactions, as per [crate::slack::interactivity].
+//! - `github` and `stripe`: GitHub's and Stripe's webhooks, for comparison
+//!   with integrations which already sign that way.
+//!
+//! The payload is read from the given path, or from stdin given `-`, and
+//! signed byte for byte, so take care that it's sent unchanged, for example
+//! with `curl --data-binary`. Timestamped schemes use the current time unless
+//! `--timestamp <SECS>` is supplied.
+
+use crate::{
+    heroku::{auth, HerokuSecret},
+    signing::{self, SigningSecret},
+    slack::interactivity::{self, SlackSigningSecret},
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use hyper::body::Bytes;
+use sha2::Sha256;
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+/// The environment variable holding the secret to sign with.
+pub const SECRET_VAR: &str = "MERCURY_SIGNING_SECRET";
+
+const USAGE: &str = "Usage: MERCURY_SIGNING_SECRET=<SECRET> mercury sign <mercury|heroku|slack|github|stripe> <PAYLOAD> [--client <NAME>] [--timestamp <SECS>]";
+
+/// A way of signing requests.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Scheme {
+    Mercury { client: String },
+    Heroku,
+    Slack,
+    GitHub,
+    Stripe,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Args {
+    scheme: Scheme,
+    secret: String,
+    /// A path, or `-` for stdin.
+    payload: String,
+    timestamp: Option<i64>,
+}
+
+/// Print the headers for the given arguments, those following `sign`,
+/// returning the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let args = match parse_args(args, std::env::var(SECRET_VAR).ok()) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+
+            return 2;
+        }
+    };
+
+    let payload = match read_payload(&args.payload) {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Could not read {}: {}", args.payload, e);
+
+            return 1;
+        }
+    };
+
+    let timestamp = args.timestamp.unwrap_or_else(|| Utc::now().timestamp());
+    for (k, v) in headers(&args.scheme, &args.secret, timestamp, &payload) {
+        println!("{}: {}", k, v);
+    }
+
+    0
+}
+
+fn parse_args(args: &[String], secret: Option<String>) -> Result<Args, String> {
+    let mut positional = Vec::new();
+    let mut client = None;
+    let mut timestamp = None;
+
+    let mut xs = args.iter();
+    while let Some(x) = xs.next() {
+        match x.as_str() {
+            "--client" => client = Some(xs.next().ok_or("--client requires a name")?.clone()),
+            "--timestamp" => {
+                let x = xs.next().ok_or("--timestamp requires seconds")?;
+                timestamp = Some(x.parse().map_err(|_| format!("Invalid timestamp: {}", x))?);
+            }
+            _ => positional.push(x.clone()),
+        }
+    }
+
+    let [scheme, payload] = match <[String; 2]>::try_from(positional) {
+        Ok(x) => x,
+        Err(xs) if xs.len() == 3 => {
+            return Err(format!(
+                "Secrets aren't accepted as arguments, set ${} instead",
+                SECRET_VAR
+            ))
+        }
+        Err(_) => return Err(String::from("Expected a scheme and payload")),
+    };
+    let secret = secret
+        .filter(|x| !x.is_empty())
+        .ok_or_else(|| format!("${} is required", SECRET_VAR))?;
+
+    let scheme = match (scheme.as_str(), client) {
+        ("mercury", Some(client)) => Scheme::Mercury { client },
+        ("mercury", None) => return Err(String::from("The mercury scheme requires --client")),
+        (_, Some(_)) => return Err(format!("The {} scheme takes no client", scheme)),
+        ("heroku", None) => Scheme::Heroku,
+        ("slack", None) => Scheme::Slack,
+        ("github", None) => Scheme::GitHub,
+        ("stripe", None) => Scheme::Stripe,
+        (x, None) => return Err(format!("Unknown scheme: {}", x)),
+    };
+
+    Ok(Args {
+        scheme,
+        secret,
+        payload,
+        timestamp,
+    })
+}
+
+fn read_payload(path: &str) -> io::Result<Bytes> {
+    match path {
+        "-" => {
+            let mut buf = Vec::new();
+            io::stdin().read_to_end(&mut buf)?;
+
+            Ok(Bytes::from(buf))
+        }
+        x => fs::read(x).map(Bytes::from),
+    }
+}
+
+/// The headers signing a payload as per a scheme.
+pub fn headers(
+    scheme: &Scheme,
+    secret: &str,
+    timestamp: i64,
+    payload: &Bytes,
+) -> Vec<(&'static str, String)> {
+    let ts = timestamp.to_string();
+    // HMAC accepts keys of any length.
+    let hex_hmac = |prefix: &str| {
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(prefix.as_bytes());
+        mac.update(payload);
+
+        hex::encode(mac.finalize().into_bytes())
+    };
+
+    match scheme {
+        Scheme::Mercury { client } => vec![
+            (signing::CLIENT_HEADER, client.clone()),
+            (signing::TIMESTAMP_HEADER, ts.clone()),
+            (
+                signing::SIGNATURE_HEADER,
+                signing::gen_signature(&SigningSecret(secret.to_owned()), &ts, payload).unwrap(),
+            ),
+        ],
+        Scheme::Heroku => vec![(
+            "Heroku-Webhook-Hmac-SHA256",
+            auth::gen_signature(&HerokuSecret(secret.to_owned()), payload).unwrap(),
+        )],
+        Scheme::Slack => vec![
+            (interactivity::TIMESTAMP_HEADER, ts.clone()),
+            (
+                interactivity::SIGNATURE_HEADER,
+                interactivity::gen_signature(&SlackSigningSecret(secret.to_owned()), &ts, payload)
+                    .unwrap(),
+            ),
+        ],
+        Scheme::GitHub => vec![("X-Hub-Signature-256", format!("sha256={}", hex_hmac("")))],
+        Scheme::Stripe => vec![(
+            "Stripe-Signature",
+            format!("t={},v1={}", ts, hex_hmac(&format!("{}.", ts))),
+        )],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(xs: &str) -> Result<Args, String> {
+        parse_args(
+            &xs.split_whitespace().map(String::from).collect::<Vec<_>>(),
+            Some(String::from("s3cr3t")),
+        )
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            args("mercury - --client ci --timestamp 1700000000"),
+            Ok(Args {
+                scheme: Scheme::Mercury {
+                    client: String::from("ci"),
+                },
+                secret: String::from("s3cr3t"),
+                payload: String::from("-"),
+                timestamp: Some(1700000000),
+            })
+        );
+        assert_eq!(args("heroku x.json").map(|x| x.scheme), Ok(Scheme::Heroku));
+
+        assert!(args("mercury x.json").is_err());
+        assert!(args("heroku x.json --client ci").is_err());
+        assert!(args("heroku").is_err());
+        assert!(args("gitlab x.json").is_err());
+        assert!(args("slack x.json --timestamp now").is_err());
+
+        // Secrets on the command line would leak.
+        assert_eq!(
+            args("heroku s3cr3t x.json").err(),
+            Some(String::from(
+                "Secrets aren't accepted as arguments, set $MERCURY_SIGNING_SECRET instead"
+            ))
+        );
+        assert!(parse_args(&[String::from("heroku"), String::from("x.json")], None).is_err());
+    }
+
+    #[test]
+    fn test_headers() {
+        let payload = Bytes::from("a wild payload appeared");
+        let sign = |x| headers(&x, "foobar", 1700000000, &payload);
+
+        assert_eq!(
+            sign(Scheme::Heroku),
+            vec![(
+                "Heroku-Webhook-Hmac-SHA256",
+                String::from("luDEVkRg2AxxcflGmamyN5mOPleyccUZdkg+C0MoRBY=")
+            )]
+        );
+        assert_eq!(
+            sign(Scheme::GitHub),
+            vec![(
+                "X-Hub-Signature-256",
+                String::from(
+                    "sha256=96e0c4564460d80c7171f94699a9b237998e3e57b271c51976483e0b43284416"
+                )
+            )]
+        );
+
+        let xs = sign(Scheme::Mercury {
+            client: String::from("ci"),
+        });
+        let names: Vec<_> = xs.iter().map(|(k, _)| *k).collect();
+        assert_eq!(
+            names,
+            ["Mercury-Client", "Mercury-Timestamp", "Mercury-Signature"]
+        );
+        assert_eq!(xs[1].1, "1700000000");
+
+        let xs = sign(Scheme::Stripe);
+        assert!(xs[0].1.starts_with("t=1700000000,v1="));
+    }
+}
@@ -0,0 +1,278 @@
+//! An extractor for inbound webhooks whose bodies are signed by their sender,
+//! such that each source needn't reimplement reading the body, verifying it,
+//! and only then parsing it.
+//!
+//! A source describes how its requests are signed by implementing [Scheme],
+//! and its payload by implementing [SignedPayload]. Handlers then extract a
+//! [SignedBody] as their final argument. For example, see
+//! [crate::heroku::auth::SchemeHeroku].
+//!
+//! Unrecognised fields in payloads are counted as per
+//! [crate::telemetry::record_unknown_fields], and rejected if the source is
+//! among `$STRICT_SOURCES`.
+
+use crate::{
+    auth::Authenticated,
+    delivery::Source,
+    router::Deps,
+    telemetry::record_unknown_fields,
+    validation::{deny_unknown, ValidationError},
+};
+use axum::{
+    async_trait,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header::HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use axum_extra::{headers, TypedHeader};
+use hyper::body::Bytes;
+use std::marker::PhantomData;
+use tracing::warn;
+
+/// How requests from a given source are signed.
+pub trait Scheme {
+    /// Where requests signed with this scheme come from.
+    const SOURCE: Source;
+
+    /// Whether requests can be verified at all, for example because a secret
+    /// has been configured. If not they're rejected with a 412 before anything
+    /// else about them is checked.
+    fn is_configured(deps: &Deps) -> bool;
+
+    /// Verify the signature of an unmodified request body, responding with the
+    /// given status if it's invalid or can't be checked.
+    fn verify(deps: &Deps, headers: &HeaderMap, body: &Bytes) -> Result<(), StatusCode>;
+}
+
+/// A payload which can be parsed from a verified JSON body.
+pub trait SignedPayload: Sized {
+    /// Parse the payload, additionally returning the paths of any fields which
+    /// weren't recognised.
+    fn from_json(bytes: &[u8]) -> Result<(Self, Vec<String>), ValidationError>;
+}
+
+/// A JSON request body of type `T` whose signature has been verified as per
+/// the [Scheme] `S`. Consumes the body, so must be the final extractor.
+pub struct SignedBody<T, S>(pub T, pub PhantomData<S>);
+
+#[async_trait]
+impl<T, S> FromRequest<Deps> for SignedBody<T, S>
+where
+    T: SignedPayload,
+    S: Scheme,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, deps: &Deps) -> Result<Self, Self::Rejection> {
+        let (mut parts, body) = req.into_parts();
+
+        let TypedHeader(content_type) =
+            TypedHeader::<headers::ContentType>::from_request_parts(&mut parts, deps)
+                .await
+                .map_err(IntoResponse::into_response)?;
+
+        if !S::is_configured(deps) {
+            return Err(StatusCode::PRECONDITION_FAILED.into_response());
+        }
+
+        if content_type != headers::ContentType::json() {
+            return Err((
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Requests must have `Content-Type: application/json`",
+            )
+                .into_response());
+        }
+
+        let extensions = parts.extensions.clone();
+        let req = Request::from_parts(parts, body);
+        let headers = req.headers().clone();
+        // We can't parse this at all yet as we need to compare signatures.
+        let bytes = Bytes::from_request(req, deps)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        S::verify(deps, &headers, &bytes).map_err(IntoResponse::into_response)?;
+        Authenticated::mark(&extensions);
+
+        let (payload, unknown) = T::from_json(&bytes).map_err(|e| {
+            warn!("Failed to deserialize payload: {:?}", e);
+
+            e.into_response()
+        })?;
+        record_unknown_fields(&deps.metrics, S::SOURCE.as_str(), &unknown);
+
+        if deps.strict_sources.contains(&S::SOURCE) {
+            deny_unknown(&unknown).map_err(|e| {
+                warn!("Rejecting payload in strict mode: {:?}", e);
+
+                e.into_response()
+            })?;
+        }
+
+        Ok(SignedBody(payload, PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        router::tests::deps,
+        signing::{self, gen_signature, parse_signing_secrets, SigningSecret},
+        slack::SlackAccessToken,
+        validation::{from_value_with_ignored, parse_json},
+    };
+    use axum::{body::Body, routing::post, Router};
+    use chrono::Utc;
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    /// Signed as per [crate::signing], which includes a timestamp.
+    struct SchemeTest;
+
+    impl Scheme for SchemeTest {
+        const SOURCE: Source = Source::Api;
+
+        fn is_configured(deps: &Deps) -> bool {
+            !deps.signing_secrets.is_empty()
+        }
+
+        fn verify(deps: &Deps, headers: &HeaderMap, body: &Bytes) -> Result<(), StatusCode> {
+            signing::validate_request_signature(
+                &deps.signing_secrets,
+                body,
+                headers,
+                Utc::now(),
+                deps.max_signature_skew,
+            )
+            .map_err(|_| StatusCode::UNAUTHORIZED)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct Payload {
+        title: String,
+    }
+
+    impl SignedPayload for Payload {
+        fn from_json(bytes: &[u8]) -> Result<(Self, Vec<String>), ValidationError> {
+            from_value_with_ignored(&parse_json(bytes)?)
+        }
+    }
+
+    const BODY: &str = r#"{"title":"a title"}"#;
+
+    fn router(signing_secrets: &str) -> Router {
+        let mut deps = deps(
+            "any".to_owned(),
+            SlackAccessToken("foobar".to_owned()),
+            None,
+        );
+        deps.signing_secrets = parse_signing_secrets(signing_secrets);
+
+        Router::new()
+            .route(
+                "/",
+                post(|SignedBody(x, _): SignedBody<Payload, SchemeTest>| async move { x.title }),
+            )
+            .with_state(deps)
+    }
+
+    /// A request signed at the given time by the given secret, if any.
+    fn req(content_type: &str, signed: Option<(&str, i64)>) -> Request {
+        let mut req = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("Content-Type", content_type);
+
+        if let Some((secret, ts)) = signed {
+            let ts = ts.to_string();
+            let sig =
+                gen_signature(&SigningSecret(secret.to_owned()), &ts, &Bytes::from(BODY)).unwrap();
+
+            req = req
+                .header(signing::CLIENT_HEADER, "ci")
+                .header(signing::TIMESTAMP_HEADER, ts)
+                .header(signing::SIGNATURE_HEADER, sig);
+        }
+
+        req.body(Body::from(BODY)).unwrap()
+    }
+
+    async fn status(router: Router, req: Request) -> StatusCode {
+        router.oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_valid_signature() {
+        let now = Utc::now().timestamp();
+        let res = router("ci:s3cr3t")
+            .oneshot(req("application/json", Some(("s3cr3t", now))))
+            .await
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+            "a title"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_signature() {
+        let now = Utc::now().timestamp();
+
+        assert_eq!(
+            status(
+                router("ci:s3cr3t"),
+                req("application/json", Some(("wrong", now)))
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_signature() {
+        assert_eq!(
+            status(router("ci:s3cr3t"), req("application/json", None)).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_signature() {
+        let stale = Utc::now().timestamp() - 60 * 60;
+
+        assert_eq!(
+            status(
+                router("ci:s3cr3t"),
+                req("application/json", Some(("s3cr3t", stale)))
+            )
+            .await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn test_content_type() {
+        let now = Utc::now().timestamp();
+
+        assert_eq!(
+            status(
+                router("ci:s3cr3t"),
+                req("text/plain", Some(("s3cr3t", now)))
+            )
+            .await,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        // Unconfigured schemes are reported ahead of anything else.
+        assert_eq!(
+            status(router(""), req("text/plain", None)).await,
+            StatusCode::PRECONDITION_FAILED
+        );
+    }
+}
@@ -14,33 +14,70 @@
 //! oauth_config:
 //!   scopes:
 //!     bot:
+//!       - bookmarks:read
+//!       - bookmarks:write
 //!       - channels:read
 //!       - channels:join
+//!       - channels:write.topic
 //!       - chat:write
 //!       - chat:write.customize
+//!       - pins:write
+//!       - reactions:read
+//!       - usergroups:read
+//!       - users:read
+//!       - users:read.email
+//! settings:
+//!   interactivity:
+//!     is_enabled: true
+//!     request_url: https://<HOST>/api/v1/slack/interactivity
 //! ```
 //!
 //! The permission scopes serve the following purposes:
 //!
+//! - `bookmarks:read`, `bookmarks:write`: Bookmark apps' runbooks.
 //! - `channels:read`: Map channel names to channel IDs.
 //! - `channels:join`: Join channels automatically.
+//! - `channels:write.topic`: Set channel topics on request.
 //! - `chat:write`: Send messages to channels.
 //! - `chat:write.customize`: Terser messages utilising the username, and custom
-//! avatars.
+//!   avatars.
+//! - `pins:write`: Pin status messages.
+//! - `reactions:read`: Treat reactions as acknowledgements when escalating.
+//! - `usergroups:read`: Map user group handles to user group IDs for mentions.
+//! - `users:read`, `users:read.email`: Map on-call users' email addresses to
+//!   user IDs for mentions.
 //!
 //! `channels:join` is optional if you manually add the bot to the channels
-//! you'd like to post to.
+//! you'd like to post to. `channels:write.topic` is only needed for
+//! [topic], `pins:write` for [crate::status], and the bookmark scopes for
+//! [crate::heroku::runbook]. Interactivity is only needed for
+//! [crate::escalation].
 
 pub mod api;
 pub mod auth;
 mod block;
+pub mod bookmark;
 pub mod channel;
+#[cfg(feature = "chaos")]
+pub mod chaos;
 pub mod error;
-mod mention;
+pub mod expiry;
+pub mod fake;
+pub mod history;
+pub mod interactivity;
+pub mod mention;
 pub mod message;
+pub mod permalink;
+pub mod pin;
+pub mod reaction;
 pub mod router;
+pub mod severity;
+pub mod topic;
+pub mod user;
+pub mod usergroup;
 
 pub use api::SlackClient;
 pub use auth::SlackAccessToken;
 pub use error::SlackError;
 pub use message::Message;
+pub use severity::Severity;
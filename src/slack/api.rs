@@ -1,7 +1,15 @@
 //! Type definitions and helpers for the Slack API.
 
-use super::{auth::*, channel::ChannelMap};
-use serde::Deserialize;
+use super::{
+    auth::*, channel::ChannelMap, error::APIError, history::CallHistory, usergroup::UserGroupMap,
+    SlackError,
+};
+use crate::oncall::OnCallProvider;
+
+#[cfg(feature = "chaos")]
+use super::chaos::Chaos;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{sync::Arc, time::Duration};
 
 #[cfg(test)]
 use mock_instant::Instant;
@@ -15,7 +23,15 @@ pub const API_BASE: &str = "https://slack.com/api";
 pub struct SlackClient {
     client: reqwest::Client,
     base_url: String,
-    pub(super) channel_map: Option<(ChannelMap, Instant)>,
+    pub(super) channel_map: Option<(Arc<ChannelMap>, Instant)>,
+    pub(super) user_group_map: Option<(UserGroupMap, Instant)>,
+    /// Whether to log redacted outbound payloads. See [crate::debug].
+    pub(super) log_payloads: bool,
+    pub(super) history: Arc<CallHistory>,
+    /// Where to find who is on call, if anywhere. See [crate::oncall].
+    pub(super) on_call: Option<OnCallProvider>,
+    #[cfg(feature = "chaos")]
+    chaos: Arc<Chaos>,
 }
 
 impl SlackClient {
@@ -26,9 +42,56 @@ impl SlackClient {
             client: reqwest::Client::new(),
             base_url,
             channel_map: None,
+            user_group_map: None,
+            log_payloads: false,
+            history: Arc::new(CallHistory::default()),
+            on_call: None,
+            #[cfg(feature = "chaos")]
+            chaos: Arc::new(Chaos::default()),
         }
     }
 
+    /// Get a handle on the outcomes of recent calls, which remains accessible
+    /// without locking the client.
+    pub fn history(&self) -> Arc<CallHistory> {
+        self.history.clone()
+    }
+
+    /// Get a handle on the faults to inject, which remains accessible without
+    /// locking the client.
+    #[cfg(feature = "chaos")]
+    pub fn chaos(&self) -> Arc<Chaos> {
+        self.chaos.clone()
+    }
+
+    /// Enable or disable logging of redacted outbound payloads.
+    pub fn with_payload_logging(mut self, x: bool) -> Self {
+        self.log_payloads = x;
+        self
+    }
+
+    /// Resolve on-call mentions via the given provider.
+    pub fn with_on_call(mut self, x: OnCallProvider) -> Self {
+        self.on_call = Some(x);
+        self
+    }
+
+    /// Report on the channel cache, if it's been populated.
+    pub fn channel_cache(&self) -> Option<CacheState> {
+        self.channel_map.as_ref().map(|(x, at)| CacheState {
+            entries: x.len(),
+            age: at.elapsed(),
+        })
+    }
+
+    /// Report on the user group cache, if it's been populated.
+    pub fn user_group_cache(&self) -> Option<CacheState> {
+        self.user_group_map.as_ref().map(|(x, at)| CacheState {
+            entries: x.len(),
+            age: at.elapsed(),
+        })
+    }
+
     /// Create a GET request to any Slack API endpoint, handling authentication.
     pub fn get<T: ToString>(&self, path: T, token: &SlackAccessToken) -> reqwest::RequestBuilder {
         self.client
@@ -44,6 +107,12 @@ impl SlackClient {
     }
 }
 
+/// The size and age of a cache.
+pub struct CacheState {
+    pub entries: usize,
+    pub age: Duration,
+}
+
 /// Slack's API returns a common "untagged" response, representing whether a
 /// request was successful.
 ///
@@ -77,4 +146,82 @@ pub struct ErrorResponse {
     #[serde(deserialize_with = "crate::de::only_false")]
     ok: bool,
     pub error: String,
+    /// The scope lacking, if that's the error.
+    needed: Option<String>,
+    /// From the `Retry-After` header, if we're rate limited.
+    #[serde(skip)]
+    retry_after: Option<Duration>,
+}
+
+#[cfg(feature = "chaos")]
+impl ErrorResponse {
+    /// An error response as Slack would send when rate limiting us.
+    pub fn rate_limited(retry_after: Duration) -> Self {
+        ErrorResponse {
+            retry_after: Some(retry_after),
+            ..ErrorResponse::from(String::from("ratelimited"))
+        }
+    }
+}
+
+/// An error response with the given error code and nothing else.
+#[cfg(feature = "chaos")]
+impl From<String> for ErrorResponse {
+    fn from(error: String) -> Self {
+        ErrorResponse {
+            ok: false,
+            error,
+            needed: None,
+            retry_after: None,
+        }
+    }
+}
+
+impl From<ErrorResponse> for SlackError {
+    fn from(x: ErrorResponse) -> Self {
+        let e = match APIError::from(x.error.as_str()) {
+            APIError::RateLimited { .. } => APIError::RateLimited {
+                retry_after: x.retry_after,
+            },
+            APIError::MissingScope { .. } => APIError::MissingScope { scope: x.needed },
+            e => e,
+        };
+
+        SlackError::APIResponseError(e)
+    }
+}
+
+impl SlackClient {
+    /// Send a request to the Slack API and decode its response as per
+    /// [decode]. With the `chaos` feature, faults may be injected instead. See
+    /// [super::chaos].
+    pub async fn send<T: DeserializeOwned>(
+        &self,
+        req: reqwest::RequestBuilder,
+    ) -> Result<APIResult<T>, SlackError> {
+        #[cfg(feature = "chaos")]
+        if let Some(e) = self.chaos.inject().await {
+            return Ok(APIResult::Err(e));
+        }
+
+        decode(req.send().await?).await
+    }
+}
+
+/// Decode a response from the Slack API, retaining any `Retry-After` header
+/// for the benefit of the caller should we be rate limited.
+pub async fn decode<T: DeserializeOwned>(
+    res: reqwest::Response,
+) -> Result<APIResult<T>, SlackError> {
+    let retry_after = res
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse().ok())
+        .map(Duration::from_secs);
+
+    Ok(match res.json().await? {
+        APIResult::Err(e) => APIResult::Err(ErrorResponse { retry_after, ..e }),
+        x => x,
+    })
 }
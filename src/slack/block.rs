@@ -4,11 +4,14 @@
 //! It has some limitations however:
 //! - It doesn't allow one to mix rich text and plaintext in a single "section".
 //!   - `rich_text` et al are unsupported as inputs to the API.
-//! - Accessory buttons insist upon on onward webhook URL [^button-webhook].
+//! - Buttons insist upon on onward webhook URL [^button-webhook], so are only
+//!   used where we handle interactivity ourselves. See [super::interactivity].
 //! - The messages tend towards being very large.
 //!
 //! Considering the alternative, "attachments", are deprecated, we'll make do
-//! with some basic blocks, utilising context blocks for smaller copy.
+//! with some basic blocks, utilising context blocks for smaller copy. The one
+//! exception is the color bar, which is only available to attachments; blocks
+//! can be nested inside an attachment to get the best of both worlds.
 //!
 //! [^blocks-api]: <https://api.slack.com/reference/block-kit/blocks>
 //!
@@ -17,14 +20,30 @@
 use serde::ser::SerializeStruct;
 use serde::{ser, Serialize};
 
+/// The longest text Slack permits in a header.
+pub const MAX_HEADER_LEN: usize = 150;
+
+/// The most fields Slack permits in a single section.
+pub const MAX_FIELDS: usize = 10;
+
 /// A simplified representation of Slack's "blocks", supporting only the bare
 /// minimum we need to achieve our desired outcome.
 #[allow(dead_code)]
 pub enum Block {
+    /// Large, bold copy. Must be [TextObject::Plaintext] of at most
+    /// [MAX_HEADER_LEN] characters.
+    Header(TextObject),
+    /// A horizontal rule.
+    Divider,
     /// Ordinary, standalone copy.
     Section(TextObject),
+    /// Copy laid out in two columns, for example key-value pairs. At most
+    /// [MAX_FIELDS] are permitted.
+    Fields(Vec<TextObject>),
     /// Small copy. The items are rendered compactly together.
     Context(Vec<TextObject>),
+    /// Interactive elements.
+    Actions(Vec<Button>),
 }
 
 impl ser::Serialize for Block {
@@ -35,20 +54,53 @@ impl ser::Serialize for Block {
         let mut state = serializer.serialize_struct("Block", 2)?;
 
         match self {
+            Block::Header(x) => {
+                state.serialize_field("type", "header")?;
+                state.serialize_field("text", x)?;
+            }
+            Block::Divider => {
+                state.serialize_field("type", "divider")?;
+            }
             Block::Section(x) => {
                 state.serialize_field("type", "section")?;
                 state.serialize_field("text", x)?;
             }
+            Block::Fields(xs) => {
+                state.serialize_field("type", "section")?;
+                state.serialize_field("fields", xs)?;
+            }
             Block::Context(xs) => {
                 state.serialize_field("type", "context")?;
                 state.serialize_field("elements", xs)?;
             }
+            Block::Actions(xs) => {
+                state.serialize_field("type", "actions")?;
+                state.serialize_field("elements", xs)?;
+            }
         };
 
         state.end()
     }
 }
 
+/// A legacy "attachment", used solely to wrap blocks in a color bar.
+#[derive(Serialize)]
+pub struct Attachment {
+    /// Either a hex code or one of Slack's named colors.
+    pub color: &'static str,
+    pub blocks: Vec<Block>,
+}
+
+/// A button, clicks on which are sent to the interactivity request URL.
+#[derive(Serialize)]
+#[serde(tag = "type", rename = "button")]
+pub struct Button {
+    /// Identifies the action in the resulting interaction payload.
+    pub action_id: &'static str,
+    /// Must be [TextObject::Plaintext].
+    pub text: TextObject,
+}
+
 #[derive(Serialize)]
 #[serde(tag = "type", content = "text")]
 pub enum TextObject {
@@ -0,0 +1,108 @@
+//! Bookmark links in channels, such that they're one click away for anyone in
+//! the channel. See [crate::heroku::runbook].
+
+use super::{api::*, channel::ChannelId, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// <https://api.slack.com/methods/bookmarks.list#args>
+#[derive(Serialize)]
+struct ListRequest<'a> {
+    channel_id: &'a ChannelId,
+}
+
+/// <https://api.slack.com/methods/bookmarks.list#examples>
+#[derive(Deserialize)]
+struct ListResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+    bookmarks: Vec<Bookmark>,
+}
+
+/// The metadata we care about per-bookmark within [ListResponse].
+#[derive(Deserialize)]
+struct Bookmark {
+    /// Absent for bookmarks which aren't links, such as folders.
+    link: Option<String>,
+}
+
+/// <https://api.slack.com/methods/bookmarks.add#args>
+#[derive(Serialize)]
+struct AddRequest<'a> {
+    channel_id: &'a ChannelId,
+    title: &'a str,
+    #[serde(rename = "type")]
+    typ: &'static str,
+    link: &'a Url,
+}
+
+/// <https://api.slack.com/methods/bookmarks.add#examples>
+#[derive(Deserialize)]
+struct AddResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+}
+
+impl SlackClient {
+    /// Bookmark a link in a channel, unless it's already bookmarked there.
+    /// Returns whether it was added.
+    pub async fn ensure_bookmark(
+        &self,
+        channel: &ChannelId,
+        title: &str,
+        link: &Url,
+        token: &SlackAccessToken,
+    ) -> Result<bool, SlackError> {
+        let res = self.try_list_bookmarks(channel, token).await;
+        self.history.record("bookmarks.list", &res);
+
+        if res?.iter().any(|x| x == link.as_str()) {
+            return Ok(false);
+        }
+
+        let res = self.try_add_bookmark(channel, title, link, token).await;
+        self.history.record("bookmarks.add", &res);
+        res.map(|_| true)
+    }
+
+    async fn try_list_bookmarks(
+        &self,
+        channel: &ChannelId,
+        token: &SlackAccessToken,
+    ) -> Result<Vec<String>, SlackError> {
+        let res: APIResult<ListResponse> = self
+            .send(self.post("/bookmarks.list", token).json(&ListRequest {
+                channel_id: channel,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => Ok(res.bookmarks.into_iter().filter_map(|x| x.link).collect()),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+
+    async fn try_add_bookmark(
+        &self,
+        channel: &ChannelId,
+        title: &str,
+        link: &Url,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res: APIResult<AddResponse> = self
+            .send(self.post("/bookmarks.add", token).json(&AddRequest {
+                channel_id: channel,
+                title,
+                typ: "link",
+                link,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(_) => Ok(()),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
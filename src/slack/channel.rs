@@ -2,9 +2,10 @@
 //! join them.
 
 use super::{api::*, SlackAccessToken, SlackError};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, NoneAsEmptyString};
-use std::{collections::HashMap, fmt, time::Duration};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
 use tracing::info;
 
 #[cfg(test)]
@@ -19,7 +20,8 @@ use std::time::Instant;
 /// let with =    ChannelName("#playground".into());
 /// let without = ChannelName("playground".into());
 /// ```
-#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[schemars(description = "A channel name, with or without the leading hash.")]
 pub struct ChannelName(pub String);
 
 /// Format without the surrounding newtype wrapper.
@@ -37,7 +39,7 @@ impl fmt::Display for ChannelName {
 /// Because channel names can change, channels are generally referred to by
 /// their underlying ID. This can be found in the UI by copying a link to the
 /// channel.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChannelId(pub String);
 
 /// Maps Slack channel names to channel IDs; Slack's API expects channel IDs,
@@ -72,18 +74,27 @@ impl SlackClient {
         &self,
         channel: &ChannelId,
         token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res = self.try_join_channel(channel, token).await;
+        self.history.record("conversations.join", &res);
+        res
+    }
+
+    async fn try_join_channel(
+        &self,
+        channel: &ChannelId,
+        token: &SlackAccessToken,
     ) -> Result<(), SlackError> {
         let res: APIResult<JoinResponse> = self
-            .post("/conversations.join", token)
-            .json(&JoinRequest { channel })
-            .send()
-            .await?
-            .json()
+            .send(
+                self.post("/conversations.join", token)
+                    .json(&JoinRequest { channel }),
+            )
             .await?;
 
         match res {
             APIResult::Ok(_) => Ok(()),
-            APIResult::Err(res) => Err(SlackError::APIResponseError(res.error)),
+            APIResult::Err(res) => Err(res.into()),
         }
     }
 
@@ -104,6 +115,15 @@ impl SlackClient {
             .ok_or(SlackError::UnknownChannel(channel_name.clone()))
             .cloned()
     }
+
+    /// Populate the channel map cache ahead of time, so that the first message
+    /// doesn't have to wait on pagination. Returns how many channels there are.
+    pub async fn warm_channel_map(
+        &mut self,
+        token: &SlackAccessToken,
+    ) -> Result<usize, SlackError> {
+        self.get_channel_map(token).await.map(|x| x.len())
+    }
 }
 
 /// <https://api.slack.com/methods/conversations.list#args>
@@ -147,56 +167,145 @@ impl SlackClient {
     /// of this function is cached, meaning that there's a risk of the map
     /// becoming stale should channels be renamed. The cache is evicted
     /// periodically to mitigate this.
+    ///
+    /// The map is shared rather than cloned, as it's consulted for every
+    /// message and workspaces can have tens of thousands of channels.
     async fn get_channel_map(
         &mut self,
         token: &SlackAccessToken,
-    ) -> Result<ChannelMap, SlackError> {
+    ) -> Result<Arc<ChannelMap>, SlackError> {
         match self
             .channel_map
             .as_ref()
             .filter(|(_, x)| !should_evict_channel_map_cache(x))
         {
-            Some((x, _)) => Ok(x.to_owned()),
+            Some((x, _)) => Ok(Arc::clone(x)),
             None => {
-                let mut channels: Vec<ChannelMeta> = Vec::new();
-                let mut cursor: Option<String> = None;
-
-                loop {
-                    let res: APIResult<ListResponse> = self
-                        .get("/conversations.list", token)
-                        .query(&ListRequest {
-                            limit: 200,
-                            exclude_archived: true,
-                            cursor,
-                        })
-                        .send()
-                        .await?
-                        .json()
-                        .await?;
-
-                    match res {
-                        APIResult::Ok(mut res) => {
-                            channels.append(&mut res.channels);
-
-                            cursor = res.response_metadata.next_cursor;
-                            if cursor.is_some() {
-                                continue;
-                            }
-
-                            let map: ChannelMap = channels
-                                .into_iter()
-                                .map(|meta| (meta.name, meta.id))
-                                .collect();
-
-                            self.channel_map = Some((map.to_owned(), Instant::now()));
-                            info!("{} channels cached", map.len());
-
-                            break Ok(map);
-                        }
-                        APIResult::Err(res) => break Err(SlackError::APIResponseError(res.error)),
+                let res = self.fetch_channel_map(token).await;
+                self.history.record("conversations.list", &res);
+                res
+            }
+        }
+    }
+
+    /// Fetch and cache a fresh map from channel names to channel IDs.
+    async fn fetch_channel_map(
+        &mut self,
+        token: &SlackAccessToken,
+    ) -> Result<Arc<ChannelMap>, SlackError> {
+        let mut map = ChannelMap::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let res: APIResult<ListResponse> = self
+                .send(self.get("/conversations.list", token).query(&ListRequest {
+                    limit: 200,
+                    exclude_archived: true,
+                    cursor,
+                }))
+                .await?;
+
+            match res {
+                APIResult::Ok(res) => {
+                    map.extend(res.channels.into_iter().map(|meta| (meta.name, meta.id)));
+
+                    cursor = res.response_metadata.next_cursor;
+                    if cursor.is_some() {
+                        continue;
                     }
+
+                    let map = Arc::new(map);
+                    self.channel_map = Some((Arc::clone(&map), Instant::now()));
+                    info!("{} channels cached", map.len());
+
+                    break Ok(map);
                 }
+                APIResult::Err(res) => break Err(res.into()),
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{Matcher, Mock, ServerGuard};
+    use serde_json::json;
+
+    const PAGE_SIZE: usize = 200;
+
+    /// Serve a synthetic workspace of `n` channels named `channel-{i}`, paginated
+    /// as Slack would.
+    async fn mock_channels(srv: &mut ServerGuard, n: usize) -> Vec<Mock> {
+        // At least one page, even if it's empty.
+        let pages = n.saturating_sub(1) / PAGE_SIZE + 1;
+        let mut mocks = Vec::with_capacity(pages);
+
+        for page in 0..pages {
+            let channels: Vec<_> = (page * PAGE_SIZE..n.min((page + 1) * PAGE_SIZE))
+                .map(|i| json!({ "id": format!("C{:010}", i), "name": format!("channel-{}", i) }))
+                .collect();
+            let next_cursor = match page + 1 {
+                x if x < pages => x.to_string(),
+                _ => String::new(),
+            };
+            let cursor = match page {
+                0 => String::new(),
+                x => format!("&cursor={}", x),
+            };
+
+            mocks.push(
+                srv.mock("GET", "/conversations.list")
+                    .match_query(Matcher::Exact(format!(
+                        "limit={}&exclude_archived=true{}",
+                        PAGE_SIZE, cursor
+                    )))
+                    .with_body(
+                        json!({
+                            "ok": true,
+                            "channels": channels,
+                            "response_metadata": { "next_cursor": next_cursor },
+                        })
+                        .to_string(),
+                    )
+                    .expect(1)
+                    .create_async()
+                    .await,
+            );
+        }
+
+        mocks
+    }
+
+    fn token() -> SlackAccessToken {
+        SlackAccessToken("foobar".to_owned())
+    }
+
+    #[tokio::test]
+    async fn test_get_channel_map_paginated() {
+        let mut srv = mockito::Server::new_async().await;
+        let mocks = mock_channels(&mut srv, PAGE_SIZE * 2 + 1).await;
+        let mut client = SlackClient::new(srv.url());
+
+        assert_eq!(client.warm_channel_map(&token()).await.ok(), Some(401));
+        for x in &mocks {
+            x.assert_async().await;
+        }
+
+        // Served from the cache.
+        assert_eq!(
+            client
+                .get_channel_id(&ChannelName("#channel-400".into()), &token())
+                .await
+                .ok()
+                .map(|x| x.0),
+            Some("C0000000400".into())
+        );
+        assert!(matches!(
+            client
+                .get_channel_id(&ChannelName("channel-401".into()), &token())
+                .await,
+            Err(SlackError::UnknownChannel(_))
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+//! Inject artificial failures, latency, and rate limits into calls to Slack's
+//! API, in order to rehearse incidents and exercise retries end-to-end.
+//!
+//! Only compiled with the `chaos` feature, and configured via the admin API.
+//! Faults are injected in place of calling Slack, so nothing is sent whilst
+//! an error or rate limit is configured.
+
+use super::api::ErrorResponse;
+use serde::{Deserialize, Serialize};
+use std::{sync::Mutex, time::Duration};
+use tracing::warn;
+
+/// The faults to inject into each call.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChaosConfig {
+    /// A Slack API error code to fail with, for example `invalid_auth`.
+    pub error: Option<String>,
+    /// How long to delay each call by before it's made, or fails.
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Fail as rate limited, to retry after this many seconds. Takes
+    /// precedence over `error`.
+    pub rate_limit_secs: Option<u64>,
+    /// How many more calls to affect, after which faults are cleared.
+    /// Unlimited if absent.
+    pub calls: Option<u32>,
+}
+
+/// The current [ChaosConfig], if any, shared between the client and the admin
+/// API.
+#[derive(Default)]
+pub struct Chaos(Mutex<Option<ChaosConfig>>);
+
+impl Chaos {
+    pub fn get(&self) -> Option<ChaosConfig> {
+        self.0.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, x: Option<ChaosConfig>) {
+        *self.0.lock().unwrap() = x;
+    }
+
+    /// Apply any faults to the current call, returning the error response to
+    /// fail it with if there is one.
+    pub async fn inject(&self) -> Option<ErrorResponse> {
+        let cfg = {
+            let mut guard = self.0.lock().unwrap();
+            let cfg = guard.clone()?;
+
+            match cfg.calls {
+                Some(n) if n <= 1 => *guard = None,
+                Some(n) => {
+                    if let Some(x) = guard.as_mut() {
+                        x.calls = Some(n - 1);
+                    }
+                }
+                None => {}
+            }
+
+            cfg
+        };
+
+        if cfg.latency_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(cfg.latency_ms)).await;
+        }
+
+        let res = match (cfg.rate_limit_secs, cfg.error) {
+            (Some(x), _) => Some(ErrorResponse::rate_limited(Duration::from_secs(x))),
+            (None, Some(e)) => Some(ErrorResponse::from(e)),
+            (None, None) => None,
+        };
+
+        if let Some(e) = &res {
+            warn!("Injecting Slack API error: {}", e.error);
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inject() {
+        let x = Chaos::default();
+        assert!(x.inject().await.is_none());
+
+        x.set(Some(ChaosConfig {
+            error: Some("invalid_auth".into()),
+            calls: Some(2),
+            ..Default::default()
+        }));
+        assert_eq!(x.inject().await.unwrap().error, "invalid_auth");
+        assert_eq!(x.get().unwrap().calls, Some(1));
+        assert_eq!(x.inject().await.unwrap().error, "invalid_auth");
+        assert!(x.get().is_none());
+        assert!(x.inject().await.is_none());
+
+        x.set(Some(ChaosConfig {
+            error: Some("invalid_auth".into()),
+            rate_limit_secs: Some(30),
+            ..Default::default()
+        }));
+        assert_eq!(x.inject().await.unwrap().error, "ratelimited");
+        assert!(x.get().is_some());
+    }
+}
@@ -1,19 +1,105 @@
 //! Captures what failure can look like when making requests to the Slack API.
 
 use crate::slack::channel::ChannelName;
-use std::fmt;
+use std::{fmt, time::Duration};
 
 /// Every possible unexceptional fail case when making requests to the Slack API.
 pub enum SlackError {
     /// General request failure.
     APIRequestFailed(reqwest::Error),
     /// Successfully decoded response error message.
-    APIResponseError(String),
+    APIResponseError(APIError),
     /// Unable to find the requested channel in our channel <-> id map. It's
     /// possible that the cache is stale.
     UnknownChannel(ChannelName),
 }
 
+/// The errors Slack responds with which we handle specifically, with any
+/// others preserved as-is. See <https://api.slack.com/methods/chat.postMessage#errors>.
+#[derive(Debug, PartialEq, Eq)]
+pub enum APIError {
+    /// Too many requests, optionally with how long to wait before retrying.
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    /// The token lacks a scope, which Slack usually names.
+    MissingScope {
+        scope: Option<String>,
+    },
+    ChannelNotFound,
+    NotInChannel,
+    IsArchived,
+    /// A workspace policy prohibits posting, for example in `#general`.
+    RestrictedAction,
+    MsgTooLong,
+    InvalidAuth,
+    Other(String),
+}
+
+impl APIError {
+    /// The error code as Slack expresses it.
+    pub fn code(&self) -> &str {
+        match self {
+            APIError::RateLimited { .. } => "ratelimited",
+            APIError::MissingScope { .. } => "missing_scope",
+            APIError::ChannelNotFound => "channel_not_found",
+            APIError::NotInChannel => "not_in_channel",
+            APIError::IsArchived => "is_archived",
+            APIError::RestrictedAction => "restricted_action",
+            APIError::MsgTooLong => "msg_too_long",
+            APIError::InvalidAuth => "invalid_auth",
+            APIError::Other(x) => x,
+        }
+    }
+}
+
+/// Parse an error code, without any accompanying detail.
+impl From<&str> for APIError {
+    fn from(x: &str) -> Self {
+        match x {
+            "ratelimited" => APIError::RateLimited { retry_after: None },
+            "missing_scope" => APIError::MissingScope { scope: None },
+            "channel_not_found" => APIError::ChannelNotFound,
+            "not_in_channel" => APIError::NotInChannel,
+            "is_archived" => APIError::IsArchived,
+            "restricted_action" => APIError::RestrictedAction,
+            "msg_too_long" => APIError::MsgTooLong,
+            "invalid_auth" => APIError::InvalidAuth,
+            x => APIError::Other(x.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for APIError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            APIError::RateLimited {
+                retry_after: Some(x),
+            } => write!(f, "{} (retry after {}s)", self.code(), x.as_secs()),
+            APIError::MissingScope { scope: Some(x) } => {
+                write!(f, "{} (needed: {})", self.code(), x)
+            }
+            _ => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+impl SlackError {
+    /// How to remedy errors which are down to the channel's configuration
+    /// rather than Mercury or the message, if this is one.
+    pub fn remediation(&self) -> Option<&'static str> {
+        match self {
+            SlackError::APIResponseError(APIError::IsArchived) => {
+                Some("The channel is archived. Unarchive it, or send to another channel.")
+            }
+            SlackError::APIResponseError(APIError::RestrictedAction) => Some(
+                "A workspace policy prohibits posting in the channel. Ask a workspace admin to permit Mercury, or send to another channel.",
+            ),
+            _ => None,
+        }
+    }
+}
+
 impl From<reqwest::Error> for SlackError {
     fn from(e: reqwest::Error) -> Self {
         SlackError::APIRequestFailed(e)
@@ -31,3 +117,36 @@ impl fmt::Display for SlackError {
         write!(f, "{}", x)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_roundtrip() {
+        for x in [
+            "ratelimited",
+            "missing_scope",
+            "is_archived",
+            "something_else",
+        ] {
+            assert_eq!(APIError::from(x).code(), x);
+        }
+    }
+
+    #[test]
+    fn test_api_error_display() {
+        let x = APIError::MissingScope {
+            scope: Some("chat:write.customize".into()),
+        };
+        assert_eq!(
+            x.to_string(),
+            "missing_scope (needed: chat:write.customize)"
+        );
+
+        let x = APIError::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert_eq!(x.to_string(), "ratelimited (retry after 30s)");
+    }
+}
@@ -0,0 +1,86 @@
+//! Monitor the Slack access token's expiry, if it has one.
+//!
+//! Tokens with Slack's token rotation enabled expire after twelve hours. As
+//! Slack's API offers no way of looking up a token's expiry, it's supplied
+//! alongside the token, either at `$SLACK_TOKEN_EXPIRES_AT` as a Unix
+//! timestamp or when rotating the token at runtime.
+//!
+//! <https://api.slack.com/authentication/rotation>
+
+use arc_swap::ArcSwapOption;
+use chrono::{DateTime, Utc};
+use prometheus::IntGauge;
+use std::{sync::Arc, time::Duration};
+use tracing::{error, warn};
+
+/// How often to check the expiry.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long before expiry to warn, most severe last.
+const THRESHOLDS: &[chrono::Duration] = &[
+    chrono::Duration::days(7),
+    chrono::Duration::days(1),
+    chrono::Duration::hours(1),
+];
+
+/// Find the most severe threshold crossed, if any, as an index into
+/// [THRESHOLDS]. An expired token has crossed every threshold.
+fn threshold_crossed(remaining: chrono::Duration) -> Option<usize> {
+    THRESHOLDS.iter().rposition(|t| remaining <= *t)
+}
+
+/// Indefinitely keep the expiry metric up to date and log warnings as each
+/// threshold is crossed. Warnings are logged once per threshold, resetting if
+/// the expiry changes.
+pub async fn watch_expiry(expires_at: Arc<ArcSwapOption<DateTime<Utc>>>, gauge: IntGauge) {
+    let mut interval = tokio::time::interval(INTERVAL);
+    let mut last: Option<(DateTime<Utc>, usize)> = None;
+
+    loop {
+        interval.tick().await;
+
+        let Some(exp) = expires_at.load_full().map(|x| *x) else {
+            gauge.set(0);
+            last = None;
+            continue;
+        };
+
+        gauge.set(exp.timestamp());
+
+        let remaining = exp - Utc::now();
+
+        let Some(crossed) = threshold_crossed(remaining) else {
+            last = None;
+            continue;
+        };
+
+        if last.is_some_and(|(x, i)| x == exp && i >= crossed) {
+            continue;
+        }
+        last = Some((exp, crossed));
+
+        if remaining <= chrono::Duration::zero() {
+            error!("Slack token expired at {}", exp);
+        } else {
+            warn!(
+                "Slack token expires at {}, in {} minutes",
+                exp,
+                remaining.num_minutes()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_crossed() {
+        assert_eq!(threshold_crossed(chrono::Duration::days(30)), None);
+        assert_eq!(threshold_crossed(chrono::Duration::days(7)), Some(0));
+        assert_eq!(threshold_crossed(chrono::Duration::hours(2)), Some(1));
+        assert_eq!(threshold_crossed(chrono::Duration::minutes(5)), Some(2));
+        assert_eq!(threshold_crossed(chrono::Duration::minutes(-5)), Some(2));
+    }
+}
@@ -0,0 +1,156 @@
+//! A fake Slack API served in-process by `mercury dev --fake-slack`, so that
+//! contributors can exercise the full flow locally without a real workspace or
+//! access token.
+//!
+//! The fake workspace has a fixed set of channels. Everything Mercury would
+//! post, update, or otherwise change is printed to stdout instead, and every
+//! call otherwise succeeds with the least Slack would respond with.
+
+use super::channel::ChannelName;
+use axum::{
+    extract::{Path, State},
+    routing::any,
+    Json, Router,
+};
+use hyper::body::Bytes;
+use serde_json::{json, Value};
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::net::TcpListener;
+use tracing::warn;
+
+/// The channels which exist in the fake workspace, and the state needed to
+/// respond plausibly.
+struct Workspace {
+    channels: Vec<ChannelName>,
+    /// The last message timestamp handed out.
+    ts: AtomicU64,
+}
+
+impl Workspace {
+    /// Channel IDs are derived from their position.
+    fn id(i: usize) -> String {
+        format!("C{:08}", i)
+    }
+
+    /// Describe a channel by name given its ID, falling back to the ID itself.
+    fn describe(&self, id: &str) -> String {
+        id.strip_prefix('C')
+            .and_then(|x| x.parse::<usize>().ok())
+            .and_then(|i| self.channels.get(i))
+            .map(|x| format!("#{}", x.0))
+            .unwrap_or_else(|| id.to_owned())
+    }
+}
+
+/// Serve a fake workspace containing the given channels on an available local
+/// port, returning its base URL. See [crate::slack::api::API_BASE].
+pub async fn serve(channels: Vec<ChannelName>) -> io::Result<String> {
+    let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+
+    let app = router(channels);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            warn!("Fake Slack failed: {}", e);
+        }
+    });
+
+    Ok(base_url)
+}
+
+fn router(channels: Vec<ChannelName>) -> Router {
+    let workspace = Workspace {
+        channels,
+        ts: AtomicU64::new(0),
+    };
+
+    Router::new()
+        .route("/:method", any(handler))
+        .with_state(Arc::new(workspace))
+}
+
+async fn handler(
+    State(ws): State<Arc<Workspace>>,
+    Path(method): Path<String>,
+    body: Bytes,
+) -> Json<Value> {
+    // Only GET requests lack a JSON body, and they're all reads.
+    let req: Value = serde_json::from_slice(&body).unwrap_or_default();
+    let channel = req["channel"]
+        .as_str()
+        .or(req["channel_id"].as_str())
+        .map(|x| ws.describe(x))
+        .unwrap_or_default();
+
+    let res = match method.as_str() {
+        "conversations.list" => json!({
+            "ok": true,
+            "channels": ws
+                .channels
+                .iter()
+                .enumerate()
+                .map(|(i, x)| json!({ "id": Workspace::id(i), "name": x.0 }))
+                .collect::<Vec<_>>(),
+            "response_metadata": { "next_cursor": "" },
+        }),
+        "chat.postMessage" | "chat.update" => {
+            print(&method, &channel, &req);
+
+            let ts = match req["ts"].as_str() {
+                Some(x) => x.to_owned(),
+                None => format!("{}.000000", ws.ts.fetch_add(1, Ordering::Relaxed) + 1),
+            };
+
+            json!({ "ok": true, "ts": ts })
+        }
+        "conversations.setTopic" | "pins.add" | "pins.remove" | "bookmarks.add" => {
+            print(&method, &channel, &req);
+
+            json!({ "ok": true })
+        }
+        "conversations.join" => json!({ "ok": true }),
+        "chat.getPermalink" => {
+            json!({ "ok": true, "permalink": "https://fake.slack.com/archives" })
+        }
+        "bookmarks.list" => json!({ "ok": true, "bookmarks": [] }),
+        "reactions.get" => json!({ "ok": true, "message": {} }),
+        "usergroups.list" => json!({ "ok": true, "usergroups": [] }),
+        "users.lookupByEmail" => json!({ "ok": false, "error": "users_not_found" }),
+        _ => json!({ "ok": false, "error": "unknown_method" }),
+    };
+
+    Json(res)
+}
+
+/// Print what would have been sent to Slack.
+fn print(method: &str, channel: &str, req: &Value) {
+    // Serializing a `Value` can't fail.
+    let req = serde_json::to_string_pretty(req).unwrap();
+
+    println!("{} to {}:\n{}", method, channel, req);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::{Message, SlackAccessToken, SlackClient};
+
+    #[tokio::test]
+    async fn test_post_message() {
+        let base_url = serve(vec![ChannelName("dev".into())]).await.unwrap();
+        let mut client = SlackClient::new(base_url);
+        let token = SlackAccessToken("fake".into());
+
+        let msg = |channel: &str| Message::new(ChannelName(channel.into()), "title", "desc");
+
+        assert!(client.post_message(&msg("dev"), &token).await.is_ok());
+        assert!(client.post_message(&msg("other"), &token).await.is_err());
+    }
+}
@@ -0,0 +1,97 @@
+//! Track the outcomes of recent Slack API calls, making it possible to tell
+//! whether Slack is currently broken for us without grepping logs.
+
+use super::SlackError;
+use arc_swap::ArcSwapOption;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// The outcome of a single Slack API call.
+#[derive(Clone, Serialize)]
+pub struct CallOutcome {
+    pub at: DateTime<Utc>,
+    /// The Slack API method, for example `chat.postMessage`.
+    pub method: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The most recent successful and failed Slack API calls, if any.
+#[derive(Default)]
+pub struct CallHistory {
+    last_success: ArcSwapOption<CallOutcome>,
+    last_failure: ArcSwapOption<CallOutcome>,
+}
+
+/// A point-in-time view of [CallHistory].
+#[derive(Serialize)]
+pub struct CallReport {
+    /// Whether the most recent call, if any, succeeded.
+    pub healthy: bool,
+    pub last_success: Option<CallOutcome>,
+    pub last_failure: Option<CallOutcome>,
+}
+
+impl CallHistory {
+    /// Record the outcome of a call.
+    pub fn record<T>(&self, method: &'static str, res: &Result<T, SlackError>) {
+        let outcome = CallOutcome {
+            at: Utc::now(),
+            method,
+            error: res.as_ref().err().map(|e| e.to_string()),
+        };
+
+        match res {
+            Ok(_) => self.last_success.store(Some(Arc::new(outcome))),
+            Err(_) => self.last_failure.store(Some(Arc::new(outcome))),
+        }
+    }
+
+    /// Summarise the recorded outcomes.
+    pub fn report(&self) -> CallReport {
+        let last_success = self.last_success.load_full().map(|x| (*x).clone());
+        let last_failure = self.last_failure.load_full().map(|x| (*x).clone());
+
+        let healthy = match (&last_success, &last_failure) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(s), Some(f)) => s.at >= f.at,
+        };
+
+        CallReport {
+            healthy,
+            last_success,
+            last_failure,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let history = CallHistory::default();
+        assert!(history.report().healthy);
+
+        history.record::<()>(
+            "chat.postMessage",
+            &Err(SlackError::APIResponseError("oops".into())),
+        );
+        let report = history.report();
+        assert!(!report.healthy);
+        assert!(report.last_success.is_none());
+        assert_eq!(
+            report.last_failure.unwrap().error.as_deref(),
+            Some("Slack API returned error: oops")
+        );
+
+        history.record("chat.postMessage", &Ok(()));
+        let report = history.report();
+        assert!(report.healthy);
+        assert_eq!(report.last_success.unwrap().method, "chat.postMessage");
+        assert!(report.last_failure.is_some());
+    }
+}
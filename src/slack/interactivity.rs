@@ -0,0 +1,230 @@
+//! Receive interactions with our messages, such as button clicks, from Slack.
+//!
+//! Slack must be configured with the request URL `/api/v1/slack/interactivity`
+//! under "Interactivity & Shortcuts". Requests are authenticated with the
+//! app's signing secret, sourced from `$SLACK_SIGNING_SECRET`, subject to the
+//! same maximum clock skew as [crate::signing].
+//!
+//! <https://api.slack.com/authentication/verifying-requests-from-slack>
+
+use super::{channel::ChannelId, message::*};
+use crate::{auth::constant_time_eq, signing::is_fresh};
+use axum::http::header::HeaderMap;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use hyper::body::Bytes;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::time::Duration;
+
+/// The header containing the timestamp included in the signature.
+pub const TIMESTAMP_HEADER: &str = "X-Slack-Request-Timestamp";
+
+/// The header containing the signature itself.
+pub const SIGNATURE_HEADER: &str = "X-Slack-Signature";
+
+/// A newtype wrapper around the Slack app's signing secret.
+#[derive(Clone)]
+pub struct SlackSigningSecret(pub String);
+
+/// What can go wrong when validating a request's signature.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    Missing,
+    /// The timestamp exceeded the maximum clock skew.
+    Stale,
+    Invalid,
+}
+
+/// The form in which interactions are sent, wrapping a JSON [Interaction].
+#[derive(Deserialize)]
+pub struct InteractionForm {
+    pub payload: String,
+}
+
+/// <https://api.slack.com/reference/interaction-payloads>
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+pub enum Interaction {
+    /// <https://api.slack.com/reference/interaction-payloads/block-actions>
+    #[serde(rename = "block_actions")]
+    BlockActions {
+        user: User,
+        container: Container,
+        actions: Vec<Action>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+pub struct User {
+    pub id: String,
+}
+
+/// Where the interaction occurred.
+#[derive(Deserialize)]
+pub struct Container {
+    pub channel_id: Option<ChannelId>,
+    pub message_ts: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Action {
+    pub action_id: String,
+}
+
+/// If an interaction is a click of the acknowledge button, get the message
+/// acknowledged and the ID of the user who acknowledged it.
+pub fn to_acknowledgement(x: &Interaction) -> Option<(MessageRef, &str)> {
+    match x {
+        Interaction::BlockActions {
+            user,
+            container,
+            actions,
+        } if actions.iter().any(|a| a.action_id == ACKNOWLEDGE_ACTION_ID) => {
+            let m = MessageRef {
+                channel_id: container.channel_id.clone()?,
+                ts: container.message_ts.clone()?,
+            };
+
+            Some((m, &user.id))
+        }
+        _ => None,
+    }
+}
+
+/// Test a request's headers for a valid, recent signature.
+///
+/// The body should be supplied entirely unmodified from the request.
+pub fn validate_request_signature(
+    secret: &SlackSigningSecret,
+    body: &Bytes,
+    headers: &HeaderMap,
+    now: DateTime<Utc>,
+    max_skew: Duration,
+) -> Result<(), SignatureError> {
+    let get = |k| {
+        headers
+            .get(k)
+            .ok_or(SignatureError::Missing)
+            .and_then(|v| v.to_str().map_err(|_| SignatureError::Invalid))
+    };
+
+    let timestamp = get(TIMESTAMP_HEADER)?;
+    let sig = get(SIGNATURE_HEADER)?;
+
+    let ts: i64 = timestamp.parse().map_err(|_| SignatureError::Invalid)?;
+    if !is_fresh(ts, now, max_skew) {
+        return Err(SignatureError::Stale);
+    }
+
+    match gen_signature(secret, timestamp, body).is_some_and(|x| constant_time_eq(&x, sig)) {
+        false => Err(SignatureError::Invalid),
+        true => Ok(()),
+    }
+}
+
+/// Generate a valid signature with our secret for a timestamp and payload.
+pub fn gen_signature(
+    secret: &SlackSigningSecret,
+    timestamp: &str,
+    payload: &Bytes,
+) -> Option<String> {
+    type HmacSha256 = Hmac<Sha256>;
+
+    HmacSha256::new_from_slice(secret.0.as_bytes())
+        .map(|mut mac| {
+            mac.update(b"v0:");
+            mac.update(timestamp.as_bytes());
+            mac.update(b":");
+            mac.update(payload);
+            format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+        })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signing::DEFAULT_MAX_SKEW;
+
+    fn headers(timestamp: &str, sig: &str) -> HeaderMap {
+        let mut xs = HeaderMap::new();
+        xs.insert(TIMESTAMP_HEADER, timestamp.parse().unwrap());
+        xs.insert(SIGNATURE_HEADER, sig.parse().unwrap());
+        xs
+    }
+
+    /// The example from Slack's documentation.
+    #[test]
+    fn test_validate_request_signature() {
+        let secret = SlackSigningSecret("8f742231b10e8888abcd99yyyzzz85a5".into());
+        let body = Bytes::from("token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2FT1DC2JH3J%2F397700885554%2F96rGlfmibIGlgcZRskXaIFfN&trigger_id=398738663015.47445629121.803a0bc887a14d10d2c447fce8b6703c");
+        let sig = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+        let now = DateTime::from_timestamp(1531420618, 0).unwrap();
+
+        assert_eq!(
+            validate_request_signature(
+                &secret,
+                &body,
+                &headers("1531420618", sig),
+                now,
+                DEFAULT_MAX_SKEW
+            ),
+            Ok(())
+        );
+
+        assert_eq!(
+            validate_request_signature(
+                &secret,
+                &body,
+                &headers("1531420619", sig),
+                now,
+                DEFAULT_MAX_SKEW
+            ),
+            Err(SignatureError::Invalid)
+        );
+
+        assert_eq!(
+            validate_request_signature(
+                &secret,
+                &body,
+                &headers("1531420000", sig),
+                now,
+                DEFAULT_MAX_SKEW
+            ),
+            Err(SignatureError::Stale)
+        );
+
+        assert_eq!(
+            validate_request_signature(&secret, &body, &HeaderMap::new(), now, DEFAULT_MAX_SKEW),
+            Err(SignatureError::Missing)
+        );
+    }
+
+    #[test]
+    fn test_to_acknowledgement() {
+        let x: Interaction = serde_json::from_str(
+            r#"{
+                "type": "block_actions",
+                "user": { "id": "U123" },
+                "container": {
+                    "type": "message",
+                    "channel_id": "C123",
+                    "message_ts": "1700000000.000100"
+                },
+                "actions": [{ "action_id": "acknowledge" }]
+            }"#,
+        )
+        .unwrap();
+
+        let (m, user) = to_acknowledgement(&x).unwrap();
+        assert_eq!(m.channel_id.0, "C123");
+        assert_eq!(m.ts, "1700000000.000100");
+        assert_eq!(user, "U123");
+
+        let other: Interaction = serde_json::from_str(r#"{ "type": "view_submission" }"#).unwrap();
+        assert!(to_acknowledgement(&other).is_none());
+    }
+}
@@ -1,29 +1,126 @@
-//! Supporting Slack mentions for the Web & API teams.
-
-use serde::Deserialize;
-
-/// The fixed, supported mention targets.
-// We could potentially reverse engineer user group IDs from friendly names
-// like we do for channels as per:
-//   <https://api.slack.com/reference/surfaces/formatting#mentioning-groups>
-//
-// However that'd imply that all consumers have to keep track of group names
-// and couldn't supply a shorthand to our API. Additionally, exact names aside,
-// groups are unlikely to change very often. Thus we'll hardcode some supported
-// groups instead.
-#[derive(Deserialize)]
+//! Supporting Slack mentions of user groups and on-call users.
+//!
+//! Consumers supply a user group's handle, for example `@web-team`, which is
+//! resolved to its ID via a periodically refreshed cache. See
+//! [super::usergroup]. Alternatively `oncall:<schedule>` mentions whoever is
+//! currently on call. See [crate::oncall].
+
+use super::{api::SlackClient, SlackAccessToken};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// Legacy shorthands, predating support for arbitrary handles, and the
+/// handles they stand for.
+const LEGACY_HANDLES: [(&str, &str); 2] = [("web", "web-team"), ("api", "api-team")];
+
+/// A user group to mention.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum Mention {
-    #[serde(rename = "web")]
-    WebTeam,
-    #[serde(rename = "api")]
-    APITeam,
+    /// A user group handle, without the leading `@`.
+    Handle(String),
+    /// Whoever is on call for the given schedule.
+    OnCall(String),
+}
+
+/// Parse from the legacy shorthands, an on-call schedule, or a handle with or
+/// without the leading `@`.
+///
+/// ```
+/// assert!(matches!(Mention::from("web".to_owned()), Mention::Handle(x) if x == "web-team"));
+/// assert!(matches!(Mention::from("@sre".to_owned()), Mention::Handle(x) if x == "sre"));
+/// assert!(matches!(Mention::from("oncall:P1ABCDE".to_owned()), Mention::OnCall(x) if x == "P1ABCDE"));
+/// ```
+impl From<String> for Mention {
+    fn from(x: String) -> Self {
+        if let Some((_, handle)) = LEGACY_HANDLES.iter().find(|(k, _)| *k == x) {
+            return Mention::Handle((*handle).to_owned());
+        }
+
+        match x.strip_prefix("oncall:") {
+            Some(schedule) => Mention::OnCall(schedule.to_owned()),
+            None => Mention::Handle(x.trim_start_matches('@').to_owned()),
+        }
+    }
+}
+
+impl From<Mention> for String {
+    fn from(x: Mention) -> Self {
+        match x {
+            Mention::Handle(x) => format!("@{}", x),
+            Mention::OnCall(x) => format!("oncall:{}", x),
+        }
+    }
+}
+
+/// A [Mention] resolved as far as possible.
+pub enum ResolvedMention {
+    /// A user group ID, which Slack will notify.
+    UserGroup(String),
+    /// A user ID, which Slack will notify.
+    User(String),
+    /// A mention we couldn't resolve, which we can only display as it was
+    /// supplied.
+    Unknown(String),
 }
 
-/// Convert a mention target to its Slack user group ID. These were manually
-/// populated.
-pub fn to_user_group_id(m: &Mention) -> &'static str {
-    match m {
-        Mention::WebTeam => "SAWPVDSUW",
-        Mention::APITeam => "SAVLBV4J0",
+impl SlackClient {
+    /// Resolve a [Mention] to a user group or user ID if possible. Failure is
+    /// tolerated, as a message is still worth sending without its mention.
+    pub async fn resolve_mention(
+        &mut self,
+        m: &Mention,
+        token: &SlackAccessToken,
+    ) -> ResolvedMention {
+        let res = match m {
+            Mention::OnCall(schedule) => self
+                .resolve_on_call(schedule, token)
+                .await
+                .map(|x| x.map(ResolvedMention::User)),
+            Mention::Handle(handle) => self
+                .get_user_group_id(handle, token)
+                .await
+                .map(|x| x.map(ResolvedMention::UserGroup))
+                .map_err(|e| e.to_string()),
+        };
+
+        let unknown = || ResolvedMention::Unknown(String::from(m.clone()));
+
+        match res {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                warn!("Unknown Slack mention: {}", String::from(m.clone()));
+                unknown()
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to resolve Slack mention {}: {}",
+                    String::from(m.clone()),
+                    e
+                );
+                unknown()
+            }
+        }
+    }
+
+    /// Get the Slack user ID of whoever is on call for a schedule, if anyone.
+    async fn resolve_on_call(
+        &self,
+        schedule: &str,
+        token: &SlackAccessToken,
+    ) -> Result<Option<String>, String> {
+        let Some(provider) = &self.on_call else {
+            return Err(String::from("No on-call provider configured"));
+        };
+
+        let email = provider
+            .get_on_call_email(schedule)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.lookup_user_id(&email, token)
+            .await
+            .map(Some)
+            .map_err(|e| e.to_string())
     }
 }
@@ -1,32 +1,157 @@
 //! Send structured messages to any given Slack channel.
 
-use super::{api::*, block::*, channel::*, mention::*, SlackAccessToken, SlackError};
+use super::{
+    api::*, block::*, channel::*, error::APIError, mention::*, severity::*, SlackAccessToken,
+    SlackError,
+};
+use crate::{
+    event::{Event, EventKind},
+    redact::redact,
+};
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 use url::Url;
 
 /// A structured message which does not permit custom formatting.
 ///
 /// The definition is intentionally a little generalised to reduce coupling to
 /// Slack and avoid any issues with escaping with the fewest compromises.
-#[derive(Deserialize)]
+#[derive(Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Message {
     pub channel: ChannelName,
     pub title: String,
     pub desc: String,
     pub link: Option<Url>,
+    #[schemars(with = "Option<String>")]
     pub cc: Option<Mention>,
     pub avatar: Option<Url>,
+    /// Matched case-insensitively, for example `Warning`.
+    #[serde(default, deserialize_with = "crate::de::case_insensitive")]
+    #[schemars(with = "Option<Severity>")]
+    pub severity: Option<Severity>,
+    /// When the subject of the message occurred, supplied as a Unix timestamp.
+    /// Rendered in each recipient's own timezone.
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    #[schemars(with = "Option<i64>")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Structured metadata rendered in two columns, in order. Supplied to the
+    /// API as newline-separated `key: value` lines. See [crate::de::fields].
+    #[serde(default, deserialize_with = "crate::de::fields")]
+    #[schemars(schema_with = "crate::de::fields_schema")]
+    pub fields: Vec<(String, String)>,
+    /// Render the title as a header rather than as the username, which
+    /// requires the `chat:write.customize` scope some workspaces don't grant.
+    #[serde(default)]
+    pub title_as_header: bool,
+    /// Reply in the thread of the message with this timestamp, for example
+    /// `1700000000.000100`, rather than posting top-level.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thread_ts: Option<String>,
+}
+
+impl Message {
+    /// A plain message, to which optional fields can be added via struct
+    /// update syntax.
+    ///
+    /// ```
+    /// let msg = Message {
+    ///     severity: Some(Severity::Warning),
+    ///     ..Message::new(ChannelName("deploys".into()), "Title", "Description")
+    /// };
+    /// ```
+    pub fn new(channel: ChannelName, title: impl Into<String>, desc: impl Into<String>) -> Self {
+        Message {
+            channel,
+            title: title.into(),
+            desc: desc.into(),
+            link: None,
+            cc: None,
+            avatar: None,
+            severity: None,
+            timestamp: None,
+            fields: Vec::new(),
+            title_as_header: false,
+            thread_ts: None,
+        }
+    }
+
+    /// Render an [Event] as a message to the given channel, optionally
+    /// mentioning someone.
+    ///
+    /// The title is prefixed with an emoji per the event's kind, unless one's
+    /// supplied, which may be a shortcode such as a workspace's custom emoji.
+    /// An empty emoji omits the prefix.
+    pub fn from_event(
+        evt: &Event,
+        channel: ChannelName,
+        cc: Option<Mention>,
+        emoji: Option<&str>,
+    ) -> Self {
+        let prefix = match emoji {
+            None => match evt.kind {
+                EventKind::Deploy => "🚀 ",
+                EventKind::Rollback => "🏳️ ",
+                EventKind::ConfigChange => "⚙️  ",
+                EventKind::Crash => "☢️  ",
+            }
+            .to_owned(),
+            Some("") => String::new(),
+            Some(x) => format!("{} ", x),
+        };
+
+        Message {
+            link: evt.links.first().cloned(),
+            cc,
+            severity: evt.severity,
+            timestamp: evt.occurred_at,
+            fields: evt.fields.clone(),
+            ..Message::new(
+                channel,
+                format!("{}{}", prefix, evt.title),
+                evt.summary.clone(),
+            )
+        }
+    }
 }
 
 /// <https://api.slack.com/methods/chat.postMessage#args>
 #[derive(Serialize)]
 struct MessageRequest<'a> {
     channel: &'a ChannelId,
-    username: String,
-    blocks: Vec<Block>,
+    /// Absent if the title is instead rendered as a header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    username: Option<String>,
+    /// Mutually exclusive with `attachments`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blocks: Option<Vec<Block>>,
+    /// Mutually exclusive with `blocks`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attachments: Option<Vec<Attachment>>,
     icon_url: Option<Url>,
     // Used for notifications in the presence of `blocks`.
     text: String,
+    /// The timestamp of the message to reply to, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_ts: Option<&'a str>,
+}
+
+/// <https://api.slack.com/methods/chat.postMessage#args>
+#[derive(Serialize)]
+struct ReplyRequest<'a> {
+    channel: &'a ChannelId,
+    thread_ts: &'a str,
+    /// Interpreted as mrkdwn.
+    text: String,
+}
+
+/// <https://api.slack.com/methods/chat.update#args>
+#[derive(Serialize)]
+struct UpdateRequest<'a> {
+    #[serde(flatten)]
+    msg: MessageRequest<'a>,
+    ts: &'a str,
 }
 
 /// <https://api.slack.com/methods/chat.postMessage#examples>
@@ -35,6 +160,29 @@ struct MessageResponse {
     #[allow(dead_code)]
     #[serde(deserialize_with = "crate::de::only_true")]
     ok: bool,
+    /// The message's timestamp, which doubles as its ID within the channel.
+    ts: Option<String>,
+}
+
+/// Identifies a message which has been posted, for example in order to reply
+/// to it in a thread.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MessageRef {
+    pub channel_id: ChannelId,
+    pub ts: String,
+}
+
+/// The action ID of the button rendered on acknowledgeable messages.
+pub const ACKNOWLEDGE_ACTION_ID: &str = "acknowledge";
+
+/// How to post a [Message], beyond its contents.
+#[derive(Default)]
+pub struct PostOptions<'a> {
+    /// Include a button with which to acknowledge the message. See
+    /// [crate::escalation].
+    pub acknowledgeable: bool,
+    /// Reply in the thread of the message with this timestamp.
+    pub thread_ts: Option<&'a str>,
 }
 
 impl SlackClient {
@@ -44,66 +192,265 @@ impl SlackClient {
         msg: &Message,
         token: &SlackAccessToken,
     ) -> Result<(), SlackError> {
+        self.post_message_with(msg, &PostOptions::default(), token)
+            .await
+            .map(|_| ())
+    }
+
+    /// Post a message as per [SlackClient::post_message] with [PostOptions]. A
+    /// reference to the posted message is returned if Slack supplied one.
+    pub async fn post_message_with(
+        &mut self,
+        msg: &Message,
+        opts: &PostOptions<'_>,
+        token: &SlackAccessToken,
+    ) -> Result<Option<MessageRef>, SlackError> {
         let channel_id = self.get_channel_id(&msg.channel, token).await?;
+        let cc = self.resolve_cc(msg, token).await;
 
-        let res = self.try_post_message(&channel_id, msg, token).await;
+        let res = self
+            .try_post_message(&channel_id, msg, cc.as_ref(), opts, token)
+            .await;
 
-        match res {
-            Ok(_) => Ok(()),
+        let res = match res {
+            Ok(x) => Ok(x),
             Err(e) => {
                 // If we've failed to post the message because we're not in the
                 // channel, try joining the channel and posting the message again.
                 if is_not_in_channel(&e) {
                     self.join_channel(&channel_id, token).await?;
-                    self.try_post_message(&channel_id, msg, token).await
+                    self.try_post_message(&channel_id, msg, cc.as_ref(), opts, token)
+                        .await
                 } else {
                     Err(e)
                 }
             }
+        };
+
+        // Without the scope to customize the username and icon, fold the
+        // title into the message itself rather than losing the message.
+        let res = match res {
+            Err(e) if is_missing_scope(&e) && !msg.title_as_header => {
+                warn!(
+                    "Missing scope to customize messages to {}, retrying with a header",
+                    msg.channel
+                );
+
+                let fallback = Message {
+                    avatar: None,
+                    title_as_header: true,
+                    ..msg.clone()
+                };
+                self.try_post_message(&channel_id, &fallback, cc.as_ref(), opts, token)
+                    .await
+            }
+            x => x,
+        };
+
+        res.map(|ts| ts.map(|ts| MessageRef { channel_id, ts }))
+    }
+
+    /// Reply to a message in its thread.
+    pub async fn post_reply(
+        &self,
+        parent: &MessageRef,
+        text: String,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res = self.try_post_reply(parent, text, token).await;
+        self.history.record("chat.postMessage", &res);
+        res
+    }
+
+    async fn try_post_reply(
+        &self,
+        parent: &MessageRef,
+        text: String,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res: APIResult<MessageResponse> = self
+            .send(self.post("/chat.postMessage", token).json(&ReplyRequest {
+                channel: &parent.channel_id,
+                thread_ts: &parent.ts,
+                text,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(_) => Ok(()),
+            APIResult::Err(res) => Err(res.into()),
         }
     }
 
-    /// Try to post a message assuming we've already joined the channel.
-    async fn try_post_message(
+    /// Replace the contents of a message we've posted, as if it had originally
+    /// been posted as `msg`. Mentions aren't resolved, as they wouldn't notify
+    /// anybody anew.
+    pub async fn update_message(
         &self,
-        channel_id: &ChannelId,
+        m: &MessageRef,
+        msg: &Message,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res = self.try_update_message(m, msg, token).await;
+        self.history.record("chat.update", &res);
+        res
+    }
+
+    async fn try_update_message(
+        &self,
+        m: &MessageRef,
         msg: &Message,
         token: &SlackAccessToken,
     ) -> Result<(), SlackError> {
         let res: APIResult<MessageResponse> = self
-            .post("/chat.postMessage", token)
-            .json(&MessageRequest {
-                channel: channel_id,
-                username: msg.title.to_owned(),
-                blocks: build_blocks(msg),
-                icon_url: msg.avatar.to_owned(),
-                text: build_notif_text(msg),
-            })
-            .send()
-            .await?
-            .json()
+            .send(self.post("/chat.update", token).json(&UpdateRequest {
+                msg: build_request(&m.channel_id, msg, None, &PostOptions::default()),
+                ts: &m.ts,
+            }))
             .await?;
 
         match res {
             APIResult::Ok(_) => Ok(()),
-            APIResult::Err(res) => Err(SlackError::APIResponseError(res.error)),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+
+    /// Get the exact payload that would be sent to Slack to post a message,
+    /// without posting it. The channel must nonetheless exist.
+    pub async fn preview_message(
+        &mut self,
+        msg: &Message,
+        token: &SlackAccessToken,
+    ) -> Result<serde_json::Value, SlackError> {
+        let channel_id = self.get_channel_id(&msg.channel, token).await?;
+        let cc = self.resolve_cc(msg, token).await;
+
+        // This can only fail on non-string map keys, of which we have none.
+        Ok(serde_json::to_value(build_request(
+            &channel_id,
+            msg,
+            cc.as_ref(),
+            &PostOptions::default(),
+        ))
+        .unwrap())
+    }
+
+    async fn resolve_cc(
+        &mut self,
+        msg: &Message,
+        token: &SlackAccessToken,
+    ) -> Option<ResolvedMention> {
+        match &msg.cc {
+            Some(m) if permits_mentions(msg.severity) => Some(self.resolve_mention(m, token).await),
+            _ => None,
+        }
+    }
+
+    /// Try to post a message assuming we've already joined the channel,
+    /// returning its timestamp if Slack supplied it.
+    async fn try_post_message(
+        &self,
+        channel_id: &ChannelId,
+        msg: &Message,
+        cc: Option<&ResolvedMention>,
+        opts: &PostOptions<'_>,
+        token: &SlackAccessToken,
+    ) -> Result<Option<String>, SlackError> {
+        let res = self
+            .try_post_message_(channel_id, msg, cc, opts, token)
+            .await;
+        self.history.record("chat.postMessage", &res);
+        res
+    }
+
+    async fn try_post_message_(
+        &self,
+        channel_id: &ChannelId,
+        msg: &Message,
+        cc: Option<&ResolvedMention>,
+        opts: &PostOptions<'_>,
+        token: &SlackAccessToken,
+    ) -> Result<Option<String>, SlackError> {
+        let req = build_request(channel_id, msg, cc, opts);
+
+        if self.log_payloads {
+            // This can only fail on non-string map keys, of which we have none.
+            let x = serde_json::to_string(&req).unwrap();
+            info!("Outbound Slack payload: {}", redact(&x));
+        }
+
+        let res: APIResult<MessageResponse> = self
+            .send(self.post("/chat.postMessage", token).json(&req))
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => Ok(res.ts),
+            APIResult::Err(res) => Err(res.into()),
         }
     }
 }
 
-/// Parse Slack's API response error to determine if the issue is that we need
-/// to join the channel.
-fn is_not_in_channel(res: &SlackError) -> bool {
+/// Determine if the issue is that we need to join the channel.
+pub(super) fn is_not_in_channel(res: &SlackError) -> bool {
+    matches!(res, SlackError::APIResponseError(APIError::NotInChannel))
+}
+
+/// Determine if the issue is that we lack the `chat:write.customize` scope.
+/// Slack doesn't always say which scope is missing, in which case we assume.
+fn is_missing_scope(res: &SlackError) -> bool {
     match res {
-        SlackError::APIResponseError(e) => e == "not_in_channel",
+        SlackError::APIResponseError(APIError::MissingScope { scope }) => scope
+            .as_deref()
+            .map_or(true, |x| x.contains("chat:write.customize")),
         _ => false,
     }
 }
 
+/// Put together the full request, mapping [Message] to its format on Slack's
+/// end. Its mention, if any, must have already been resolved.
+fn build_request<'a>(
+    channel_id: &'a ChannelId,
+    msg: &'a Message,
+    cc: Option<&ResolvedMention>,
+    opts: &PostOptions<'a>,
+) -> MessageRequest<'a> {
+    let mut blocks = build_blocks(msg, cc);
+
+    if opts.acknowledgeable {
+        blocks.push(Block::Actions(vec![Button {
+            action_id: ACKNOWLEDGE_ACTION_ID,
+            text: TextObject::Plaintext(String::from("Acknowledge")),
+        }]));
+    }
+
+    // Blocks can't have a color bar of their own, so if we need one we'll wrap
+    // them in an attachment.
+    let (blocks, attachments) = match &msg.severity {
+        None => (Some(blocks), None),
+        Some(s) => (
+            None,
+            Some(vec![Attachment {
+                color: to_color(s),
+                blocks,
+            }]),
+        ),
+    };
+
+    MessageRequest {
+        channel: channel_id,
+        username: (!msg.title_as_header).then(|| msg.title.to_owned()),
+        blocks,
+        attachments,
+        icon_url: msg.avatar.to_owned(),
+        text: build_notif_text(msg),
+        thread_ts: msg.thread_ts.as_deref().or(opts.thread_ts),
+    }
+}
+
 /// Put together the blocks, mapping [Message] to its format on Slack's end,
 /// including formatting.
-fn build_blocks(msg: &Message) -> Vec<Block> {
-    let mut xs = Vec::with_capacity(3);
+fn build_blocks(msg: &Message, cc: Option<&ResolvedMention>) -> Vec<Block> {
+    let mut xs = Vec::with_capacity(4);
 
     xs.push(TextObject::Plaintext(msg.desc.to_owned()));
 
@@ -113,20 +460,68 @@ fn build_blocks(msg: &Message) -> Vec<Block> {
         xs.push(TextObject::Mrkdwn(fmt_link(link)));
     }
 
-    if let Some(cc) = &msg.cc {
+    if let Some(ts) = &msg.timestamp {
+        xs.push(TextObject::Mrkdwn(fmt_timestamp(ts)));
+    }
+
+    if let Some(cc) = cc {
         xs.push(TextObject::Mrkdwn(fmt_mention(cc)));
     }
 
-    vec![Block::Context(xs)]
+    let mut blocks = Vec::with_capacity(3);
+
+    if msg.title_as_header {
+        let title = msg.title.chars().take(MAX_HEADER_LEN).collect();
+        blocks.push(Block::Header(TextObject::Plaintext(title)));
+    }
+
+    blocks.push(Block::Context(xs));
+
+    // Plaintext, as both keys and values may be foreign input.
+    blocks.extend(msg.fields.chunks(MAX_FIELDS).map(|xs| {
+        Block::Fields(
+            xs.iter()
+                .map(|(k, v)| TextObject::Plaintext(format!("{}: {}", k, v)))
+                .collect(),
+        )
+    }));
+
+    blocks
 }
 
 fn build_notif_text(msg: &Message) -> String {
-    format!("{}: {}", msg.title, msg.desc)
+    match msg.severity.as_ref().and_then(to_notif_prefix) {
+        Some(x) => format!("{} {}: {}", x, msg.title, msg.desc),
+        None => format!("{}: {}", msg.title, msg.desc),
+    }
 }
 
-/// Format a [Mention] to the syntax Slack expects, and stylise it.
-fn fmt_mention(m: &Mention) -> String {
-    format!("cc <!subteam^{}>", to_user_group_id(m))
+/// Format a [ResolvedMention] to the syntax Slack expects, and stylise it.
+/// Unknown mentions are displayed without notifying anyone.
+pub fn fmt_mention(m: &ResolvedMention) -> String {
+    match m {
+        ResolvedMention::UserGroup(id) => format!("cc <!subteam^{}>", id),
+        ResolvedMention::User(id) => format!("cc <@{}>", id),
+        ResolvedMention::Unknown(x) => format!("cc {}", x),
+    }
+}
+
+/// Format a timestamp to Slack's date syntax, which each client renders in its
+/// own timezone and locale. The fallback is used by clients which can't.
+///
+/// ```
+/// let ts = DateTime::parse_from_rfc3339("2023-08-03T10:00:30Z").unwrap();
+/// assert_eq!(
+///     fmt_timestamp(&ts.into()),
+///     "<!date^1691056830^{date_short_pretty} at {time}|2023-08-03 10:00 UTC>"
+/// );
+/// ```
+fn fmt_timestamp(ts: &DateTime<Utc>) -> String {
+    format!(
+        "<!date^{}^{{date_short_pretty}} at {{time}}|{}>",
+        ts.timestamp(),
+        ts.format("%Y-%m-%d %H:%M UTC")
+    )
 }
 
 /// Prettify a URL, reducing verbosity.
@@ -142,3 +537,114 @@ fn fmt_mention(m: &Mention) -> String {
 fn fmt_link(u: &Url) -> String {
     format!("<{}|{}>", u, "↗")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, snapshot::assert_snapshot};
+
+    fn msg() -> Message {
+        Message::new(ChannelName("any".to_owned()), "Title", "Some description.")
+    }
+
+    fn event(kind: EventKind, severity: Severity) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind,
+            app: Some("my-app".to_owned()),
+            version: None,
+            severity: Some(severity),
+            occurred_at: Some("2023-08-03T10:00:30Z".parse().unwrap()),
+            title: "my-app".to_owned(),
+            summary: "Something happened (hodor@unsplash.com)".to_owned(),
+            fields: Vec::new(),
+            links: vec![Url::parse("https://dashboard.heroku.com/apps/my-app/activity").unwrap()],
+        }
+    }
+
+    fn render(
+        msg: &Message,
+        cc: Option<&ResolvedMention>,
+        opts: &PostOptions,
+    ) -> serde_json::Value {
+        let channel_id = ChannelId("C0123456789".to_owned());
+        serde_json::to_value(build_request(&channel_id, msg, cc, opts)).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_events() {
+        let xs = [
+            ("deploy", EventKind::Deploy, Severity::Success),
+            ("rollback", EventKind::Rollback, Severity::Warning),
+            ("config_change", EventKind::ConfigChange, Severity::Info),
+            ("crash", EventKind::Crash, Severity::Critical),
+        ];
+
+        for (name, kind, severity) in xs {
+            let msg = Message::from_event(
+                &event(kind, severity),
+                ChannelName("any".to_owned()),
+                None,
+                None,
+            );
+
+            assert_snapshot(
+                &format!("slack_message__event_{}", name),
+                &render(&msg, None, &PostOptions::default()),
+            );
+        }
+    }
+
+    #[test]
+    fn test_snapshot_messages() {
+        assert_snapshot(
+            "slack_message__plain",
+            &render(&msg(), None, &PostOptions::default()),
+        );
+
+        let full = Message {
+            link: Some(Url::parse("https://unsplash.com").unwrap()),
+            avatar: Some(Url::parse("https://unsplash.com/favicon.ico").unwrap()),
+            severity: Some(Severity::Critical),
+            timestamp: Some("2023-08-03T10:00:30Z".parse().unwrap()),
+            fields: (1..=12)
+                .map(|i| (format!("key{}", i), format!("value{}", i)))
+                .collect(),
+            ..msg()
+        };
+        assert_snapshot(
+            "slack_message__full",
+            &render(
+                &full,
+                Some(&ResolvedMention::UserGroup("S0123456789".to_owned())),
+                &PostOptions::default(),
+            ),
+        );
+
+        let header = Message {
+            title: "x".repeat(MAX_HEADER_LEN + 1),
+            title_as_header: true,
+            ..msg()
+        };
+        assert_snapshot(
+            "slack_message__header",
+            &render(&header, None, &PostOptions::default()),
+        );
+
+        let acknowledgeable = Message {
+            severity: Some(Severity::Critical),
+            ..msg()
+        };
+        assert_snapshot(
+            "slack_message__acknowledgeable_reply",
+            &render(
+                &acknowledgeable,
+                Some(&ResolvedMention::Unknown("@nobody".to_owned())),
+                &PostOptions {
+                    acknowledgeable: true,
+                    thread_ts: Some("1234.5678"),
+                },
+            ),
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Link to messages we've posted, for example from issues filed elsewhere. See
+//! [crate::heroku::linear].
+
+use super::{api::*, message::MessageRef, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// <https://api.slack.com/methods/chat.getPermalink#args>
+#[derive(Serialize)]
+struct PermalinkRequest<'a> {
+    channel: &'a str,
+    message_ts: &'a str,
+}
+
+/// <https://api.slack.com/methods/chat.getPermalink#examples>
+#[derive(Deserialize)]
+struct PermalinkResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+    permalink: Url,
+}
+
+impl SlackClient {
+    /// Get a link to a message which works for anyone who can see it.
+    pub async fn get_permalink(
+        &self,
+        m: &MessageRef,
+        token: &SlackAccessToken,
+    ) -> Result<Url, SlackError> {
+        let res = self.try_get_permalink(m, token).await;
+        self.history.record("chat.getPermalink", &res);
+        res
+    }
+
+    async fn try_get_permalink(
+        &self,
+        m: &MessageRef,
+        token: &SlackAccessToken,
+    ) -> Result<Url, SlackError> {
+        let res: APIResult<PermalinkResponse> = self
+            .send(
+                self.get("/chat.getPermalink", token)
+                    .query(&PermalinkRequest {
+                        channel: &m.channel_id.0,
+                        message_ts: &m.ts,
+                    }),
+            )
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => Ok(res.permalink),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
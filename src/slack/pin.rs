@@ -0,0 +1,56 @@
+//! Pin messages we've posted to their channels, such that they're always to
+//! hand. See [crate::status].
+
+use super::{api::*, message::MessageRef, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+
+/// <https://api.slack.com/methods/pins.add#args>, and likewise `pins.remove`.
+#[derive(Serialize)]
+struct PinRequest<'a> {
+    channel: &'a str,
+    timestamp: &'a str,
+}
+
+/// <https://api.slack.com/methods/pins.add#examples>, and likewise
+/// `pins.remove`.
+#[derive(Deserialize)]
+struct PinResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+}
+
+impl SlackClient {
+    /// Pin a message to its channel.
+    pub async fn pin(&self, m: &MessageRef, token: &SlackAccessToken) -> Result<(), SlackError> {
+        let res = self.try_pin("/pins.add", m, token).await;
+        self.history.record("pins.add", &res);
+        res
+    }
+
+    /// Unpin a message from its channel.
+    pub async fn unpin(&self, m: &MessageRef, token: &SlackAccessToken) -> Result<(), SlackError> {
+        let res = self.try_pin("/pins.remove", m, token).await;
+        self.history.record("pins.remove", &res);
+        res
+    }
+
+    async fn try_pin(
+        &self,
+        path: &str,
+        m: &MessageRef,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res: APIResult<PinResponse> = self
+            .send(self.post(path, token).json(&PinRequest {
+                channel: &m.channel_id.0,
+                timestamp: &m.ts,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(_) => Ok(()),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
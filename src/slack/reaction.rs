@@ -0,0 +1,66 @@
+//! Inspect reactions to messages, which we treat as a lightweight form of
+//! acknowledgement.
+
+use super::{api::*, message::MessageRef, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+
+/// <https://api.slack.com/methods/reactions.get#args>
+#[derive(Serialize)]
+struct GetRequest<'a> {
+    channel: &'a str,
+    timestamp: &'a str,
+}
+
+/// <https://api.slack.com/methods/reactions.get#examples>
+#[derive(Deserialize)]
+struct GetResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+    message: ReactedMessage,
+}
+
+/// The metadata we care about within [GetResponse].
+#[derive(Deserialize)]
+struct ReactedMessage {
+    /// Omitted entirely if there are no reactions.
+    #[serde(default)]
+    reactions: Vec<Reaction>,
+}
+
+#[derive(Deserialize)]
+struct Reaction {
+    #[allow(dead_code)]
+    name: String,
+}
+
+impl SlackClient {
+    /// Whether anyone has reacted to a message.
+    pub async fn has_reactions(
+        &self,
+        m: &MessageRef,
+        token: &SlackAccessToken,
+    ) -> Result<bool, SlackError> {
+        let res = self.try_has_reactions(m, token).await;
+        self.history.record("reactions.get", &res);
+        res
+    }
+
+    async fn try_has_reactions(
+        &self,
+        m: &MessageRef,
+        token: &SlackAccessToken,
+    ) -> Result<bool, SlackError> {
+        let res: APIResult<GetResponse> = self
+            .send(self.get("/reactions.get", token).query(&GetRequest {
+                channel: &m.channel_id.0,
+                timestamp: &m.ts,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => Ok(!res.message.reactions.is_empty()),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
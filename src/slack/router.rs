@@ -1,54 +1,440 @@
 //! Slack subrouter definition.
 //!
-//! The following subroute is supported:
+//! The following subroutes are supported:
 //!
 //! - POST: `/`
+//! - GET: `/preview`
+//! - POST: `/explain`
+//! - POST: `/topic`
+//! - POST: `/interactivity`
 
 use crate::{
+    auth::{find_bearer, Authenticated},
+    delivery::{deliver, Delivery, Source, SUPPRESSED_HEADER},
+    explain::{explain_admission, explain_delivery, Trace},
+    heartbeat::beat,
+    ingestion::Held,
+    priority::{too_many_requests, Priority},
     router::Deps,
-    slack::{Message, SlackAccessToken, SlackError},
+    signing::{is_signed, validate_request_signature, SignatureError, CLIENT_HEADER},
+    slack::{
+        channel::ChannelName,
+        error::APIError,
+        interactivity::{self, to_acknowledgement, Interaction, InteractionForm},
+        Message, SlackError,
+    },
+    telemetry::record_client,
+    validation::{deny_unknown, ValidatedForm, ValidationError},
 };
 use axum::{
-    extract::{self, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::post,
-    Router,
+    body::{self, Body},
+    extract::{self, Request, State},
+    http::{
+        header::{HeaderMap, HeaderValue, AUTHORIZATION},
+        Extensions, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
 };
-use axum_extra::{headers, TypedHeader};
-use tower_http::validate_request::ValidateRequestHeaderLayer;
-use tracing::error;
+use chrono::Utc;
+use hyper::body::Bytes;
+use serde::Deserialize;
+use std::sync::atomic::Ordering;
+use tracing::{error, info, warn};
+
+/// The largest request body we'll buffer in order to validate its signature,
+/// matching Axum's default limit for extractors.
+const MAX_SIGNED_BODY_BYTES: usize = 2 * 1024 * 1024;
 
 /// Instantiate a new Slack subrouter.
-pub fn slack_router(slack_token: &SlackAccessToken) -> Router<Deps> {
+pub fn slack_router(deps: &Deps) -> Router<Deps> {
     Router::new()
         .route("/", post(msg_handler))
-        // Unsure how to access `Deps` here to obviate the need for the function
-        // parameter.
-        .layer(ValidateRequestHeaderLayer::bearer(&slack_token.0))
+        .route("/preview", get(preview_handler))
+        .route("/explain", post(explain_handler))
+        .route("/topic", post(topic_handler))
+        .layer(middleware::from_fn_with_state(deps.clone(), authenticate))
+        // Authenticated independently, as these requests come from Slack.
+        .route("/interactivity", post(interactivity_handler))
+}
+
+/// Authenticate requests either by a `Bearer` `Authorization` header matching
+/// one of `$MERCURY_API_TOKEN` (or `$SLACK_TOKEN` in compatibility mode), or
+/// by a valid signature from a known client as per [crate::signing]. Requests
+/// which purport to be signed must be validly signed, irrespective of any
+/// bearer token.
+pub async fn authenticate(State(deps): State<Deps>, req: Request, next: Next) -> Response {
+    if is_signed(req.headers()) {
+        let (parts, body) = req.into_parts();
+
+        let Ok(bytes) = body::to_bytes(body, MAX_SIGNED_BODY_BYTES).await else {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        };
+
+        let res = validate_request_signature(
+            &deps.signing_secrets,
+            &bytes,
+            &parts.headers,
+            Utc::now(),
+            deps.max_signature_skew,
+        );
+
+        return match res {
+            Ok(_) => {
+                Authenticated::mark(&parts.extensions);
+
+                if let Some(x) = parts
+                    .headers
+                    .get(CLIENT_HEADER)
+                    .and_then(|x| x.to_str().ok())
+                {
+                    record_client(x);
+                }
+
+                next.run(Request::from_parts(parts, Body::from(bytes)))
+                    .await
+            }
+            Err(e) => {
+                warn!("Invalid request signature: {:?}", e);
+
+                if e == SignatureError::Stale {
+                    deps.metrics
+                        .stale_signatures
+                        .with_label_values(&["api"])
+                        .inc();
+                }
+
+                StatusCode::UNAUTHORIZED.into_response()
+            }
+        };
+    }
+
+    match req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|x| find_accepted_bearer(&deps, x))
+    {
+        Some(client) => {
+            Authenticated::mark(req.extensions());
+            record_client(&client);
+
+            next.run(req).await
+        }
+        None => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Test an `Authorization` header value against `$MERCURY_API_TOKEN`, or
+/// `$SLACK_TOKEN` in compatibility mode.
+pub fn is_accepted_bearer(deps: &Deps, val: &HeaderValue) -> bool {
+    find_accepted_bearer(deps, val).is_some()
+}
+
+/// Find the name of the client an `Authorization` header value identifies as
+/// per [is_accepted_bearer], if any.
+fn find_accepted_bearer(deps: &Deps, val: &HeaderValue) -> Option<String> {
+    let api_tokens = deps.api_tokens.load();
+    let slack_token = deps.slack_token.load();
+    let accepted = api_tokens
+        .iter()
+        .map(|t| (t.name.as_str(), t.token.as_str()))
+        .chain(
+            deps.slack_token_compat
+                .then_some(("slack-token", slack_token.0.as_str())),
+        );
+
+    find_bearer(val, accepted).map(str::to_owned)
+}
+
+/// Optional query params for the POST subroute `/`.
+#[derive(Deserialize)]
+struct MsgOptions {
+    /// Respond with the payload that would be sent to Slack instead of sending
+    /// it, as per [preview_handler].
+    #[serde(default)]
+    dry_run: bool,
 }
 
 /// Handler for the POST subroute `/`.
 ///
-/// A `Bearer` `Authorization` header containing a Slack access token must be
-/// present and must match that found in `$SLACK_TOKEN`.
+/// Requests must be authenticated, either with a `Bearer` token or a
+/// signature. See [authenticate].
 ///
-/// Accepts a [Message] in `application/x-www-form-urlencoded` format.
+/// Accepts a [Message] in `application/x-www-form-urlencoded` format, and
+/// optionally a `dry_run` query param. Delivery is subject to read-only mode.
+/// Messages are held whilst ingestion from the API is paused, as per
+/// [crate::ingestion]. Non-critical messages are turned away with a `429` status should there be
+/// too many awaiting delivery. See [crate::priority].
 async fn msg_handler(
     State(deps): State<Deps>,
-    TypedHeader(t): TypedHeader<headers::Authorization<headers::authorization::Bearer>>,
-    extract::Form(m): extract::Form<Message>,
-) -> impl IntoResponse {
+    extract::Query(opts): extract::Query<MsgOptions>,
+    ValidatedForm(m, ignored): ValidatedForm<Message>,
+) -> Response {
+    if let Err(e) = strict(&deps, &ignored) {
+        return e.into_response();
+    }
+    beat(&deps, Source::Api, None);
+
+    if opts.dry_run {
+        return preview(&deps, &m).await;
+    }
+
+    let m = match deps.ingestion.hold(Source::Api, m, Held::Message) {
+        Ok(()) => {
+            info!("Ingestion from API paused, holding message");
+
+            return (
+                StatusCode::OK,
+                [(SUPPRESSED_HEADER, "paused")],
+                String::new(),
+            )
+                .into_response();
+        }
+        Err(m) => m,
+    };
+
+    if let Some(x) = deps.lanes.backpressure(Priority::of(&m, Source::Api)) {
+        warn!("Backlogged, asking client to retry in {}s", x.as_secs());
+
+        return too_many_requests(x);
+    }
+
+    match deliver(&deps, &m, Source::Api).await {
+        Ok(Delivery::Sent) => (StatusCode::OK, String::new()).into_response(),
+        Ok(Delivery::Suppressed(reason)) => {
+            (StatusCode::OK, [(SUPPRESSED_HEADER, reason)], String::new()).into_response()
+        }
+        Err(e) => slack_err_response(&e),
+    }
+}
+
+/// Handler for the GET subroute `/preview`.
+///
+/// Authenticated as per [msg_handler].
+///
+/// Accepts a [Message] in the query string, responding with the exact payload
+/// that would be sent to Slack in `application/json` format. Nothing is sent,
+/// making this a safe way to iterate on formatting.
+async fn preview_handler(
+    State(deps): State<Deps>,
+    ValidatedForm(m, ignored): ValidatedForm<Message>,
+) -> Response {
+    if let Err(e) = strict(&deps, &ignored) {
+        return e.into_response();
+    }
+
+    preview(&deps, &m).await
+}
+
+/// Handler for the POST subroute `/explain`.
+///
+/// Authenticated as per [msg_handler].
+///
+/// Accepts a [Message] as per [msg_handler], responding with a [Trace] of how
+/// it would be handled in `application/json` format, without delivering it.
+/// See [crate::explain].
+async fn explain_handler(
+    State(deps): State<Deps>,
+    ValidatedForm(m, ignored): ValidatedForm<Message>,
+) -> Json<Trace> {
+    let mut trace = Trace::default();
+
+    match strict(&deps, &ignored) {
+        Err(_) => {
+            trace.fail(
+                "form",
+                format!(
+                    "Unrecognised fields, rejected in strict mode: {}",
+                    ignored.join(", ")
+                ),
+            );
+
+            return Json(trace);
+        }
+        Ok(()) if ignored.is_empty() => trace.pass("form", "Valid"),
+        Ok(()) => trace.pass(
+            "form",
+            format!(
+                "Valid, ignoring unrecognised fields: {}",
+                ignored.join(", ")
+            ),
+        ),
+    }
+
+    if explain_admission(
+        &deps,
+        &mut trace,
+        Source::Api,
+        Priority::of(&m, Source::Api),
+    ) {
+        explain_delivery(&deps, &mut trace, m).await;
+    }
+
+    Json(trace)
+}
+
+/// The form accepted by the POST subroute `/topic`.
+#[derive(Deserialize)]
+struct TopicForm {
+    channel: ChannelName,
+    /// An empty topic clears it.
+    #[serde(default)]
+    topic: String,
+}
+
+/// Handler for the POST subroute `/topic`.
+///
+/// Authenticated as per [msg_handler].
+///
+/// Accepts a `channel` and `topic` in `application/x-www-form-urlencoded`
+/// format, setting the channel's topic, for example to flag an incident in
+/// progress. An empty or absent topic clears it. Subject to read-only mode.
+async fn topic_handler(
+    State(deps): State<Deps>,
+    ValidatedForm(x, ignored): ValidatedForm<TopicForm>,
+) -> Response {
+    if let Err(e) = strict(&deps, &ignored) {
+        return e.into_response();
+    }
+    beat(&deps, Source::Api, None);
+
+    if deps.read_only.load(Ordering::Relaxed) {
+        info!("Read-only mode, not setting topic of {}", x.channel);
+
+        return (
+            StatusCode::OK,
+            [(SUPPRESSED_HEADER, "read-only")],
+            String::new(),
+        )
+            .into_response();
+    }
+
+    let token = deps.slack_token.load_full();
     let res = deps
         .slack_client
         .lock()
         .await
-        .post_message(&m, &SlackAccessToken(t.token().into()))
+        .set_topic(&x.channel, &x.topic, &token)
         .await;
 
     match res {
-        Ok(_) => (StatusCode::OK, String::new()),
-        Err(e) => handle_slack_err(&e),
+        Ok(()) => (StatusCode::OK, String::new()).into_response(),
+        Err(e) => slack_err_response(&e),
+    }
+}
+
+/// Reject ignored fields if the API is in strict mode.
+fn strict(deps: &Deps, ignored: &[String]) -> Result<(), ValidationError> {
+    if deps.strict_sources.contains(&Source::Api) {
+        deny_unknown(ignored)
+    } else {
+        Ok(())
+    }
+}
+
+/// Build the payload that would be sent to Slack for a [Message].
+async fn preview(deps: &Deps, m: &Message) -> Response {
+    let res = deps
+        .slack_client
+        .lock()
+        .await
+        .preview_message(m, &deps.slack_token.load_full())
+        .await;
+
+    match res {
+        Ok(x) => Json(x).into_response(),
+        Err(e) => handle_slack_err(&e).into_response(),
+    }
+}
+
+/// Handler for the POST subroute `/interactivity`.
+///
+/// Requests must be signed by Slack. See [crate::slack::interactivity].
+///
+/// Accepts an [InteractionForm] in `application/x-www-form-urlencoded` format.
+/// Clicks of the acknowledge button stop the message from escalating, and are
+/// acknowledged in the message's thread. Other interactions are ignored.
+async fn interactivity_handler(
+    State(deps): State<Deps>,
+    extensions: Extensions,
+    headers: HeaderMap,
+    // We can't parse this at all yet as we need to compare signatures.
+    body_bytes: Bytes,
+) -> Response {
+    let Some(secret) = &deps.slack_signing_secret else {
+        return StatusCode::PRECONDITION_FAILED.into_response();
+    };
+
+    if let Err(e) = interactivity::validate_request_signature(
+        secret,
+        &body_bytes,
+        &headers,
+        Utc::now(),
+        deps.max_signature_skew,
+    ) {
+        warn!("Invalid Slack request signature: {:?}", e);
+
+        if e == interactivity::SignatureError::Stale {
+            deps.metrics
+                .stale_signatures
+                .with_label_values(&["slack"])
+                .inc();
+        }
+
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    Authenticated::mark(&extensions);
+
+    let interaction = serde_urlencoded::from_bytes::<InteractionForm>(&body_bytes)
+        .map_err(|e| e.to_string())
+        .and_then(|x| serde_json::from_str::<Interaction>(&x.payload).map_err(|e| e.to_string()));
+    let interaction = match interaction {
+        Ok(x) => x,
+        Err(e) => {
+            let msg = format!("Failed to deserialize interaction: {}", e);
+            warn!(msg);
+
+            return (StatusCode::UNPROCESSABLE_ENTITY, msg).into_response();
+        }
+    };
+
+    let Some((m, user)) = to_acknowledgement(&interaction) else {
+        return StatusCode::OK.into_response();
+    };
+
+    // Repeat clicks are harmless but needn't be acknowledged again.
+    if deps.escalations.as_ref().is_some_and(|x| x.acknowledge(&m)) {
+        info!("Message {} acknowledged by {}", m.ts, user);
+
+        let text = format!("Acknowledged by <@{}>.", user);
+        let res = deps
+            .slack_client
+            .lock()
+            .await
+            .post_reply(&m, text, &deps.slack_token.load_full())
+            .await;
+        if let Err(e) = res {
+            warn!("Failed to reply to acknowledged message {}: {}", m.ts, e);
+        }
+    }
+
+    StatusCode::OK.into_response()
+}
+
+/// Respond to a Slack error as per [handle_slack_err]. Errors an operator can
+/// remedy are instead responded to in `application/json` format, with a hint
+/// as to how.
+pub fn slack_err_response(e: &SlackError) -> Response {
+    let (code, es) = handle_slack_err(e);
+
+    match e.remediation() {
+        Some(hint) => {
+            (code, Json(serde_json::json!({ "error": es, "hint": hint }))).into_response()
+        }
+        None => (code, es).into_response(),
     }
 }
 
@@ -56,6 +442,12 @@ pub fn handle_slack_err(e: &SlackError) -> (StatusCode, String) {
     let code = match &e {
         e if is_unauthenticated(e) => StatusCode::UNAUTHORIZED,
         SlackError::APIRequestFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        SlackError::APIResponseError(APIError::RateLimited { .. }) => StatusCode::TOO_MANY_REQUESTS,
+        SlackError::APIResponseError(APIError::ChannelNotFound | APIError::MsgTooLong) => {
+            StatusCode::BAD_REQUEST
+        }
+        SlackError::APIResponseError(APIError::IsArchived) => StatusCode::CONFLICT,
+        SlackError::APIResponseError(APIError::RestrictedAction) => StatusCode::FORBIDDEN,
         SlackError::APIResponseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         SlackError::UnknownChannel(_) => StatusCode::BAD_REQUEST,
     };
@@ -66,11 +458,8 @@ pub fn handle_slack_err(e: &SlackError) -> (StatusCode, String) {
     (code, es)
 }
 
-/// Parse Slack's API response error to determine if the issue is that the
-/// access token failed to provide authentication.
+/// Determine if the issue is that the access token failed to provide
+/// authentication.
 fn is_unauthenticated(res: &SlackError) -> bool {
-    match res {
-        SlackError::APIResponseError(e) => e == "invalid_auth",
-        _ => false,
-    }
+    matches!(res, SlackError::APIResponseError(APIError::InvalidAuth))
 }
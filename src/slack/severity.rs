@@ -0,0 +1,69 @@
+//! Conveying the severity of a message at a glance.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How severe, or otherwise, the subject of a message is. Ordered from least
+/// to most severe.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+pub enum Severity {
+    #[serde(rename = "debug")]
+    Debug,
+    #[serde(rename = "info")]
+    Info,
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "warning")]
+    Warning,
+    #[serde(rename = "critical")]
+    Critical,
+}
+
+/// Convert a severity to the color of the bar rendered alongside an
+/// attachment. Slack supports these named colors in addition to hex codes.
+pub fn to_color(s: &Severity) -> &'static str {
+    match s {
+        Severity::Debug => "#9e9e9e",
+        Severity::Info => "#439fe0",
+        Severity::Success => "good",
+        Severity::Warning => "warning",
+        Severity::Critical => "danger",
+    }
+}
+
+/// Convert a severity to a prefix for notification text, in which there's no
+/// color bar. Only those demanding attention have one.
+pub fn to_notif_prefix(s: &Severity) -> Option<&'static str> {
+    match s {
+        Severity::Debug | Severity::Info | Severity::Success => None,
+        Severity::Warning => Some("⚠️"),
+        Severity::Critical => Some("🚨"),
+    }
+}
+
+/// Whether a message of this severity may mention anyone. Debug messages are
+/// for reading at leisure, so shouldn't notify anybody regardless of `cc`.
+pub fn permits_mentions(s: Option<Severity>) -> bool {
+    s != Some(Severity::Debug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering() {
+        assert!(Severity::Debug < Severity::Info);
+        assert!(Severity::Info < Severity::Warning);
+        assert!(Severity::Warning < Severity::Critical);
+    }
+
+    #[test]
+    fn test_permits_mentions() {
+        assert!(permits_mentions(None));
+        assert!(permits_mentions(Some(Severity::Info)));
+        assert!(!permits_mentions(Some(Severity::Debug)));
+    }
+}
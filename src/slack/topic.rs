@@ -0,0 +1,76 @@
+//! Set channels' topics, for example to flag an incident in progress to anyone
+//! glancing at the channel, clearing it again upon recovery.
+
+use super::{
+    api::*,
+    channel::{ChannelId, ChannelName},
+    message::is_not_in_channel,
+    SlackAccessToken, SlackError,
+};
+use serde::{Deserialize, Serialize};
+
+/// <https://api.slack.com/methods/conversations.setTopic#args>
+#[derive(Serialize)]
+struct SetTopicRequest<'a> {
+    channel: &'a ChannelId,
+    topic: &'a str,
+}
+
+/// <https://api.slack.com/methods/conversations.setTopic#examples>
+#[derive(Deserialize)]
+struct SetTopicResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+}
+
+impl SlackClient {
+    /// Set a channel's topic, joining it if necessary. An empty topic clears
+    /// it.
+    pub async fn set_topic(
+        &mut self,
+        channel: &ChannelName,
+        topic: &str,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let channel_id = self.get_channel_id(channel, token).await?;
+
+        match self.try_set_topic(&channel_id, topic, token).await {
+            Err(e) if is_not_in_channel(&e) => {
+                self.join_channel(&channel_id, token).await?;
+                self.try_set_topic(&channel_id, topic, token).await
+            }
+            x => x,
+        }
+    }
+
+    async fn try_set_topic(
+        &self,
+        channel: &ChannelId,
+        topic: &str,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res = self.try_set_topic_(channel, topic, token).await;
+        self.history.record("conversations.setTopic", &res);
+        res
+    }
+
+    async fn try_set_topic_(
+        &self,
+        channel: &ChannelId,
+        topic: &str,
+        token: &SlackAccessToken,
+    ) -> Result<(), SlackError> {
+        let res: APIResult<SetTopicResponse> = self
+            .send(
+                self.post("/conversations.setTopic", token)
+                    .json(&SetTopicRequest { channel, topic }),
+            )
+            .await?;
+
+        match res {
+            APIResult::Ok(_) => Ok(()),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
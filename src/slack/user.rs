@@ -0,0 +1,56 @@
+//! Look up Slack users, enabling mentions of individuals.
+
+use super::{api::*, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+
+/// <https://api.slack.com/methods/users.lookupByEmail#args>
+#[derive(Serialize)]
+struct LookupRequest<'a> {
+    email: &'a str,
+}
+
+/// <https://api.slack.com/methods/users.lookupByEmail#examples>
+#[derive(Deserialize)]
+struct LookupResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+    user: UserMeta,
+}
+
+/// The metadata we care about within [LookupResponse].
+#[derive(Deserialize)]
+struct UserMeta {
+    id: String,
+}
+
+impl SlackClient {
+    /// Get the ID of the user with the given email address.
+    pub async fn lookup_user_id(
+        &self,
+        email: &str,
+        token: &SlackAccessToken,
+    ) -> Result<String, SlackError> {
+        let res = self.try_lookup_user_id(email, token).await;
+        self.history.record("users.lookupByEmail", &res);
+        res
+    }
+
+    async fn try_lookup_user_id(
+        &self,
+        email: &str,
+        token: &SlackAccessToken,
+    ) -> Result<String, SlackError> {
+        let res: APIResult<LookupResponse> = self
+            .send(
+                self.get("/users.lookupByEmail", token)
+                    .query(&LookupRequest { email }),
+            )
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => Ok(res.user.id),
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Resolve user group handles to IDs, enabling mentions of arbitrary user
+//! groups.
+
+use super::{api::*, SlackAccessToken, SlackError};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use tracing::info;
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// Maps user group handles, without the leading `@`, to user group IDs.
+pub type UserGroupMap = HashMap<String, String>;
+
+/// <https://api.slack.com/methods/usergroups.list#args>
+#[derive(Serialize)]
+struct ListRequest {
+    include_disabled: bool,
+}
+
+/// <https://api.slack.com/methods/usergroups.list#examples>
+#[derive(Deserialize)]
+struct ListResponse {
+    #[allow(dead_code)]
+    #[serde(deserialize_with = "crate::de::only_true")]
+    ok: bool,
+    usergroups: Vec<UserGroupMeta>,
+}
+
+/// The metadata we care about per-user group within [ListResponse].
+#[derive(Deserialize)]
+struct UserGroupMeta {
+    id: String,
+    handle: String,
+}
+
+/// Predicate on whether the user group map cache should be evicted based upon
+/// the age of the cache, represented by `then`. User groups are synced more
+/// often than channels as they're cheaper to fetch.
+fn should_evict_user_group_map_cache(then: &Instant) -> bool {
+    then.elapsed() > Duration::from_secs(60 * 60)
+}
+
+impl SlackClient {
+    /// Get the ID of the user group with the given handle, if it exists.
+    pub(super) async fn get_user_group_id(
+        &mut self,
+        handle: &str,
+        token: &SlackAccessToken,
+    ) -> Result<Option<String>, SlackError> {
+        let map = self.get_user_group_map(token).await?;
+
+        Ok(map.get(handle).cloned())
+    }
+
+    /// Get a map from user group handles to IDs, cached as per
+    /// [should_evict_user_group_map_cache].
+    async fn get_user_group_map(
+        &mut self,
+        token: &SlackAccessToken,
+    ) -> Result<UserGroupMap, SlackError> {
+        match self
+            .user_group_map
+            .as_ref()
+            .filter(|(_, x)| !should_evict_user_group_map_cache(x))
+        {
+            Some((x, _)) => Ok(x.to_owned()),
+            None => {
+                let res = self.fetch_user_group_map(token).await;
+                self.history.record("usergroups.list", &res);
+                res
+            }
+        }
+    }
+
+    /// Fetch and cache a fresh map from user group handles to IDs.
+    async fn fetch_user_group_map(
+        &mut self,
+        token: &SlackAccessToken,
+    ) -> Result<UserGroupMap, SlackError> {
+        let res: APIResult<ListResponse> = self
+            .send(self.get("/usergroups.list", token).query(&ListRequest {
+                include_disabled: false,
+            }))
+            .await?;
+
+        match res {
+            APIResult::Ok(res) => {
+                let map: UserGroupMap = res
+                    .usergroups
+                    .into_iter()
+                    .map(|x| (x.handle, x.id))
+                    .collect();
+
+                self.user_group_map = Some((map.to_owned(), Instant::now()));
+                info!("{} user groups cached", map.len());
+
+                Ok(map)
+            }
+            APIResult::Err(res) => Err(res.into()),
+        }
+    }
+}
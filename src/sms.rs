@@ -0,0 +1,261 @@
+//! Text critical messages via [Twilio](https://www.twilio.com) as a channel of
+//! last resort, should they fail to reach Slack because Slack, or Mercury's
+//! access to it, is down.
+//!
+//! Enabled by configuring `$TWILIO_ACCOUNT_SID`, `$TWILIO_AUTH_TOKEN`, a
+//! sending number at `$TWILIO_FROM`, and comma-separated recipients at
+//! `$SMS_RECIPIENTS`, all numbers in E.164 format, for example
+//! `+14155550100`. Only messages with `critical` severity are texted, and
+//! only upon failures which are Mercury's rather than down to the request,
+//! such as an unknown channel. See [crate::meta].
+//!
+//! Texts are strictly rate limited to `$SMS_MAX_PER_HOUR` (3 by default)
+//! irrespective of how many recipients each is sent to, beyond which they're
+//! dropped, so that an outage can't run up a bill or a pager-weary phone.
+
+use crate::{
+    meta::is_systemic,
+    slack::{Message, Severity, SlackError},
+};
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+use tracing::{info, warn};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// The base URL of Twilio's API.
+pub const API_BASE: &str = "https://api.twilio.com";
+
+/// The default number of texts to send per [WINDOW].
+pub const DEFAULT_MAX_PER_HOUR: usize = 3;
+
+/// The window over which texts are rate limited.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// How long to wait for Twilio to accept each text.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How much of a message is texted, in characters, keeping it to a few SMS
+/// segments.
+const MAX_BODY_CHARS: usize = 300;
+
+/// Twilio credentials and numbers.
+pub struct TwilioConfig {
+    /// See [API_BASE].
+    pub base_url: String,
+    pub account_sid: String,
+    pub auth_token: String,
+    pub from: String,
+}
+
+/// Texts critical messages to a fixed list of recipients, safe to share
+/// across requests.
+pub struct Sms {
+    client: reqwest::Client,
+    config: TwilioConfig,
+    recipients: Vec<String>,
+    max_per_hour: usize,
+    /// When each text within the current window was sent, oldest first.
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+/// Parse recipients from their environment variable representation.
+///
+/// ```
+/// let xs = parse_recipients("+14155550100, +447700900123").unwrap();
+/// ```
+pub fn parse_recipients(x: &str) -> Result<Vec<String>, String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let digits = x.strip_prefix('+').unwrap_or_default();
+            let is_valid = (8..=15).contains(&digits.len())
+                && digits.chars().all(|c| c.is_ascii_digit())
+                && !digits.starts_with('0');
+
+            match is_valid {
+                true => Ok(x.to_owned()),
+                false => Err(format!("invalid E.164 number: {}", x)),
+            }
+        })
+        .collect()
+}
+
+impl Sms {
+    pub fn new(config: TwilioConfig, recipients: Vec<String>, max_per_hour: usize) -> Self {
+        Sms {
+            // Texts are sent inline with deliveries, which mustn't hang.
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            config,
+            recipients,
+            max_per_hour,
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Text a message which failed to be delivered, if it's critical and the
+    /// failure's systemic.
+    pub async fn fallback(&self, msg: &Message, e: &SlackError) {
+        if msg.severity != Some(Severity::Critical) || !is_systemic(e) {
+            return;
+        }
+
+        if !self.admit() {
+            warn!(
+                "Too many texts sent in the last hour, not texting \"{}\"",
+                msg.title
+            );
+            return;
+        }
+
+        let body = to_body(msg);
+        let url = format!(
+            "{}/2010-04-01/Accounts/{}/Messages.json",
+            self.config.base_url, self.config.account_sid
+        );
+
+        for to in &self.recipients {
+            let res = self
+                .client
+                .post(&url)
+                .basic_auth(&self.config.account_sid, Some(&self.config.auth_token))
+                .form(&[
+                    ("To", to.as_str()),
+                    ("From", &self.config.from),
+                    ("Body", &body),
+                ])
+                .send()
+                .await
+                .and_then(|x| x.error_for_status());
+
+            match res {
+                Ok(_) => info!("Texted \"{}\" to {}", msg.title, to),
+                Err(e) => warn!("Failed to text \"{}\" to {}: {}", msg.title, to, e),
+            }
+        }
+    }
+
+    /// Count a text against the rate limit, returning whether it's within it.
+    fn admit(&self) -> bool {
+        let now = Instant::now();
+        let mut sent = self.sent.lock().unwrap();
+
+        while sent
+            .front()
+            .is_some_and(|x| now.duration_since(*x) >= WINDOW)
+        {
+            sent.pop_front();
+        }
+
+        if sent.len() >= self.max_per_hour {
+            return false;
+        }
+
+        sent.push_back(now);
+
+        true
+    }
+}
+
+/// Render a message as plain text, truncated to [MAX_BODY_CHARS].
+fn to_body(msg: &Message) -> String {
+    let x = format!("[Mercury] {}: {}", msg.title, msg.desc);
+
+    match x.chars().count() > MAX_BODY_CHARS {
+        false => x,
+        true => x.chars().take(MAX_BODY_CHARS - 1).collect::<String>() + "…",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slack::{channel::ChannelName, error::APIError};
+    use mock_instant::MockClock;
+    use mockito::Matcher;
+
+    fn message(severity: Option<Severity>) -> Message {
+        Message {
+            severity,
+            ..Message::new(ChannelName("alerts".into()), "api", "Database unreachable")
+        }
+    }
+
+    fn outage() -> SlackError {
+        SlackError::APIResponseError(APIError::InvalidAuth)
+    }
+
+    #[test]
+    fn test_parse_recipients() {
+        assert_eq!(
+            parse_recipients(" +14155550100,, +447700900123 ").unwrap(),
+            vec!["+14155550100", "+447700900123"]
+        );
+        assert!(parse_recipients("").unwrap().is_empty());
+        assert!(parse_recipients("14155550100").is_err());
+        assert!(parse_recipients("+1415555O100").is_err());
+        assert!(parse_recipients("+0123456789").is_err());
+    }
+
+    #[test]
+    fn test_to_body() {
+        assert_eq!(
+            to_body(&message(None)),
+            "[Mercury] api: Database unreachable"
+        );
+
+        let mut msg = message(None);
+        msg.desc = "x".repeat(1000);
+        assert_eq!(to_body(&msg).chars().count(), MAX_BODY_CHARS);
+    }
+
+    #[tokio::test]
+    async fn test_fallback() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let mock = srv
+            .mock("POST", "/2010-04-01/Accounts/AC123/Messages.json")
+            .match_header("Authorization", Matcher::Regex("^Basic ".into()))
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("From".into(), "+15005550006".into()),
+                Matcher::UrlEncoded("Body".into(), "[Mercury] api: Database unreachable".into()),
+            ]))
+            .expect(4)
+            .create_async()
+            .await;
+
+        let x = Sms::new(
+            TwilioConfig {
+                base_url: srv.url(),
+                account_sid: String::from("AC123"),
+                auth_token: String::from("secret"),
+                from: String::from("+15005550006"),
+            },
+            vec![String::from("+14155550100"), String::from("+447700900123")],
+            2,
+        );
+
+        // Neither critical nor systemic, respectively.
+        x.fallback(&message(Some(Severity::Warning)), &outage())
+            .await;
+        x.fallback(
+            &message(Some(Severity::Critical)),
+            &SlackError::UnknownChannel(ChannelName("alerts".into())),
+        )
+        .await;
+
+        // Rate limited beyond the second.
+        for _ in 0..3 {
+            x.fallback(&message(Some(Severity::Critical)), &outage())
+                .await;
+        }
+
+        mock.assert_async().await;
+
+        MockClock::advance(WINDOW);
+        assert!(x.admit());
+    }
+}
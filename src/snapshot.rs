@@ -0,0 +1,54 @@
+//! Golden tests, such that changes to how messages are rendered are reviewed
+//! as diffs of the snapshots in `src/snapshots/` rather than discovered in
+//! production channels.
+//!
+//! A snapshot is the pretty-printed JSON of a value. Mismatches fail with a
+//! diff. To accept them, or to create new snapshots, run the tests with
+//! `$UPDATE_SNAPSHOTS` set and commit the result:
+//!
+//! ```console
+//! $ UPDATE_SNAPSHOTS=1 cargo test
+//! ```
+
+use serde::Serialize;
+use similar::TextDiff;
+use std::{env, fs, path::PathBuf};
+
+fn path(name: &str) -> PathBuf {
+    [env!("CARGO_MANIFEST_DIR"), "src", "snapshots"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{}.json", name))
+}
+
+/// Assert that `x` matches the snapshot with the given name, as per the module
+/// documentation.
+pub fn assert_snapshot<T: Serialize>(name: &str, x: &T) {
+    let path = path(name);
+    let actual = serde_json::to_string_pretty(x).unwrap() + "\n";
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "No snapshot {} found, run with $UPDATE_SNAPSHOTS to create it",
+            path.display()
+        )
+    });
+
+    if expected != actual {
+        let diff = TextDiff::from_lines(&expected, &actual)
+            .unified_diff()
+            .header("snapshot", "actual")
+            .to_string();
+
+        panic!(
+            "Snapshot {} doesn't match, run with $UPDATE_SNAPSHOTS to accept:\n{}",
+            name, diff
+        );
+    }
+}
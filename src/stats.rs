@@ -0,0 +1,149 @@
+//! Rolling counts of the messages delivered per channel and per [Source],
+//! making it easy to identify noisy sources and channels worth filtering.
+//!
+//! Counts are kept in per-minute buckets for a day, and are lost on restart.
+
+use crate::{delivery::Source, slack::channel::ChannelName};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+};
+
+/// How many minutes of buckets are retained, bounding the longest window.
+const RETENTION_MINS: i64 = 60 * 24;
+
+/// The counts for a single minute.
+struct Bucket {
+    /// Minutes since the Unix epoch.
+    minute: i64,
+    counts: HashMap<(String, Source), u64>,
+}
+
+/// The counts themselves, safe to share across requests.
+#[derive(Default)]
+pub struct Stats {
+    /// Ordered oldest first.
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+/// Counts over each supported window.
+#[derive(Serialize)]
+pub struct StatsReport {
+    pub last_hour: WindowStats,
+    pub last_day: WindowStats,
+}
+
+/// Counts over a single window.
+#[derive(Default, Serialize)]
+pub struct WindowStats {
+    pub total: u64,
+    pub channels: BTreeMap<String, u64>,
+    pub sources: BTreeMap<Source, u64>,
+}
+
+impl Stats {
+    /// Count a message delivered to a channel.
+    pub fn record(&self, at: DateTime<Utc>, channel: &ChannelName, source: Source) {
+        let minute = to_minute(at);
+        // Consumers may or may not supply a leading hash.
+        let channel = channel.0.trim_start_matches('#').to_owned();
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.back().map_or(true, |b| b.minute != minute) {
+            buckets.push_back(Bucket {
+                minute,
+                counts: HashMap::new(),
+            });
+        }
+
+        // Just pushed if it didn't already exist.
+        if let Some(b) = buckets.back_mut() {
+            *b.counts.entry((channel, source)).or_default() += 1;
+        }
+
+        while buckets
+            .front()
+            .is_some_and(|b| b.minute <= minute - RETENTION_MINS)
+        {
+            buckets.pop_front();
+        }
+    }
+
+    /// Summarise counts over each window ending `now`.
+    pub fn report(&self, now: DateTime<Utc>) -> StatsReport {
+        let buckets = self.buckets.lock().unwrap();
+
+        StatsReport {
+            last_hour: summarise(&buckets, to_minute(now) - 60),
+            last_day: summarise(&buckets, to_minute(now) - RETENTION_MINS),
+        }
+    }
+}
+
+/// Summarise the buckets more recent than `since`, in minutes.
+fn summarise(buckets: &VecDeque<Bucket>, since: i64) -> WindowStats {
+    buckets
+        .iter()
+        .filter(|b| b.minute > since)
+        .flat_map(|b| b.counts.iter())
+        .fold(WindowStats::default(), |mut acc, ((channel, source), n)| {
+            acc.total += n;
+            *acc.channels.entry(channel.to_owned()).or_default() += n;
+            *acc.sources.entry(*source).or_default() += n;
+            acc
+        })
+}
+
+fn to_minute(x: DateTime<Utc>) -> i64 {
+    x.timestamp().div_euclid(60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_report() {
+        let stats = Stats::default();
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+        let c = |x: &str| ChannelName(x.into());
+
+        stats.record(now - Duration::hours(2), &c("deploys"), Source::Heroku);
+        stats.record(now - Duration::minutes(5), &c("#deploys"), Source::Heroku);
+        stats.record(now - Duration::minutes(5), &c("alerts"), Source::Api);
+        stats.record(now, &c("alerts"), Source::Api);
+
+        let x = stats.report(now);
+
+        assert_eq!(x.last_hour.total, 3);
+        assert_eq!(x.last_hour.channels["deploys"], 1);
+        assert_eq!(x.last_hour.channels["alerts"], 2);
+        assert_eq!(x.last_hour.sources[&Source::Api], 2);
+
+        assert_eq!(x.last_day.total, 4);
+        assert_eq!(x.last_day.channels["deploys"], 2);
+        assert_eq!(x.last_day.sources[&Source::Heroku], 2);
+
+        let later = stats.report(now + Duration::days(1));
+        assert_eq!(later.last_day.total, 0);
+    }
+
+    #[test]
+    fn test_retention() {
+        let stats = Stats::default();
+        let now = DateTime::from_timestamp(1700000000, 0).unwrap();
+
+        stats.record(now, &ChannelName("any".into()), Source::Api);
+        stats.record(
+            now + Duration::days(1),
+            &ChannelName("any".into()),
+            Source::Api,
+        );
+
+        assert_eq!(stats.buckets.lock().unwrap().len(), 1);
+    }
+}
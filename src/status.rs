@@ -0,0 +1,369 @@
+//! Keep a pinned status message in designated channels summarising the health
+//! of each app they've recently heard about, giving each a live status header.
+//!
+//! Enabled by setting `$STATUS_CHANNELS` to a comma-separated list of
+//! channels, for example `deploys,alerts`. Each app's status is that of the
+//! most recent event about it delivered to the channel, for example red after
+//! a crash until its next deploy. Apps which haven't been heard from in a day
+//! drop off.
+//!
+//! The message is posted and pinned upon the first such event after startup,
+//! and thereafter updated in place, batching bursts of events. Should it have
+//! been deleted in the meantime, a new one is posted and pinned, and the old
+//! one unpinned. Updates are held back whilst in read-only mode, and yield to
+//! other messages as per [crate::priority].
+
+use crate::{
+    event::Event,
+    priority::Priority,
+    router::Deps,
+    slack::{
+        channel::ChannelName,
+        message::{MessageRef, PostOptions},
+        Message, Severity,
+    },
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+/// How often to update status messages whose statuses have changed.
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long an app's status is shown for without hearing from it again.
+const RECENT: ChronoDuration = ChronoDuration::days(1);
+
+/// The most recent event about an app.
+struct AppStatus {
+    severity: Option<Severity>,
+    /// The first line of the event's summary.
+    summary: String,
+    at: DateTime<Utc>,
+}
+
+/// A channel's status message and what it should show.
+#[derive(Default)]
+struct Board {
+    apps: BTreeMap<String, AppStatus>,
+    /// Absent until first posted.
+    message: Option<MessageRef>,
+    /// Whether the message is out of date.
+    dirty: bool,
+}
+
+/// Tracks the status of each designated channel, safe to share across
+/// requests.
+pub struct StatusBoards {
+    /// Keyed by channel name, without any leading hash.
+    boards: Mutex<HashMap<String, Board>>,
+}
+
+impl StatusBoards {
+    /// Parse designated channels from their environment variable
+    /// representation.
+    ///
+    /// ```
+    /// let x = StatusBoards::parse("deploys, #alerts");
+    /// ```
+    pub fn parse(x: &str) -> Self {
+        let boards = x
+            .split(',')
+            .map(normalise)
+            .filter(|x| !x.is_empty())
+            .map(|x| (x, Board::default()))
+            .collect();
+
+        StatusBoards {
+            boards: Mutex::new(boards),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.boards.lock().unwrap().len()
+    }
+
+    /// Record an event delivered to a channel, if it's designated and the event
+    /// is about an app.
+    pub fn record(&self, channel: &ChannelName, evt: &Event) {
+        let Some(app) = &evt.app else {
+            return;
+        };
+        let mut boards = self.boards.lock().unwrap();
+        let Some(board) = boards.get_mut(&normalise(&channel.0)) else {
+            return;
+        };
+
+        board.apps.insert(
+            app.to_owned(),
+            AppStatus {
+                severity: evt.severity,
+                summary: evt.summary.lines().next().unwrap_or_default().to_owned(),
+                at: evt.occurred_at.unwrap_or_else(Utc::now),
+            },
+        );
+        board.dirty = true;
+    }
+
+    /// Take the boards whose messages are out of date as of `now`, alongside
+    /// their current messages if any, forgetting apps which are no longer
+    /// recent.
+    fn take_dirty(&self, now: DateTime<Utc>) -> Vec<(Message, Option<MessageRef>)> {
+        let mut boards = self.boards.lock().unwrap();
+
+        boards
+            .iter_mut()
+            .filter_map(|(channel, b)| {
+                let n = b.apps.len();
+                b.apps.retain(|_, x| now - x.at < RECENT);
+                // Dropping apps only matters to a message which exists.
+                b.dirty |= b.apps.len() < n && b.message.is_some();
+
+                if !b.dirty {
+                    return None;
+                }
+                b.dirty = false;
+
+                let msg = render(ChannelName(channel.to_owned()), &b.apps, now);
+                Some((msg, b.message.clone()))
+            })
+            .collect()
+    }
+
+    /// Record a channel's new status message, returning the one it replaces.
+    fn replace(&self, channel: &ChannelName, m: MessageRef) -> Option<MessageRef> {
+        let mut boards = self.boards.lock().unwrap();
+
+        boards
+            .get_mut(&normalise(&channel.0))
+            .and_then(|b| b.message.replace(m))
+    }
+
+    /// Have a board's message updated again, for example after failing to.
+    fn mark_dirty(&self, channel: &ChannelName) {
+        if let Some(b) = self.boards.lock().unwrap().get_mut(&normalise(&channel.0)) {
+            b.dirty = true;
+        }
+    }
+}
+
+/// Channel names can't contain hashes, so this lets consumers supply a leading
+/// hash or not.
+fn normalise(channel: &str) -> String {
+    channel.trim().trim_start_matches('#').to_owned()
+}
+
+fn to_indicator(x: Option<Severity>) -> &'static str {
+    match x {
+        Some(Severity::Critical) => "🔴",
+        Some(Severity::Warning) => "🟡",
+        _ => "🟢",
+    }
+}
+
+/// Build a status message for a channel, one line per app. The message's
+/// severity is that of the least healthy app.
+fn render(channel: ChannelName, apps: &BTreeMap<String, AppStatus>, now: DateTime<Utc>) -> Message {
+    let desc = match apps.is_empty() {
+        true => String::from("No recent events"),
+        false => apps
+            .iter()
+            .map(|(app, x)| {
+                format!(
+                    "{} {}: {} ({})",
+                    to_indicator(x.severity),
+                    app,
+                    x.summary,
+                    x.at.format("%H:%M UTC")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    let severity = apps
+        .values()
+        .filter_map(|x| x.severity)
+        .filter(|x| *x >= Severity::Warning)
+        .max()
+        .unwrap_or(Severity::Success);
+
+    Message {
+        severity: Some(severity),
+        timestamp: Some(now),
+        // Usernames can't be updated, so keep the title within the message.
+        title_as_header: true,
+        ..Message::new(channel, "Status", desc)
+    }
+}
+
+/// Update a channel's status message, posting and pinning a new one if it
+/// doesn't yet exist or can no longer be updated.
+async fn upsert(deps: &Deps, boards: &StatusBoards, msg: Message, existing: Option<MessageRef>) {
+    let _turn = deps.lanes.turn(Priority::Bulk).await;
+    let token = deps.slack_token.load_full();
+    let mut client = deps.slack_client.lock().await;
+
+    if let Some(m) = &existing {
+        match client.update_message(m, &msg, &token).await {
+            Ok(()) => return,
+            Err(e) => warn!(
+                "Failed to update status message in {}, reposting: {}",
+                msg.channel, e
+            ),
+        }
+    }
+
+    let m = match client
+        .post_message_with(&msg, &PostOptions::default(), &token)
+        .await
+    {
+        Ok(Some(m)) => m,
+        Ok(None) => {
+            warn!("No timestamp for status message in {}", msg.channel);
+
+            return;
+        }
+        Err(e) => {
+            warn!("Failed to post status message in {}: {}", msg.channel, e);
+            boards.mark_dirty(&msg.channel);
+
+            return;
+        }
+    };
+
+    // Best effort, an unpinned status message is still a status message.
+    if let Err(e) = client.pin(&m, &token).await {
+        warn!("Failed to pin status message in {}: {}", msg.channel, e);
+    }
+
+    if let Some(old) = boards.replace(&msg.channel, m) {
+        if let Err(e) = client.unpin(&old, &token).await {
+            warn!(
+                "Failed to unpin old status message in {}: {}",
+                msg.channel, e
+            );
+        }
+    }
+
+    info!("Posted status message in {}", msg.channel);
+}
+
+/// Indefinitely update status messages whose statuses have changed.
+pub async fn watch_status(deps: Deps, boards: Arc<StatusBoards>) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if deps.read_only.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        for (msg, existing) in boards.take_dirty(Utc::now()) {
+            upsert(&deps, &boards, msg, existing).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, event::EventKind, slack::channel::ChannelId};
+
+    fn event(app: Option<&str>, severity: Severity, summary: &str, at: DateTime<Utc>) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Deploy,
+            app: app.map(str::to_owned),
+            version: None,
+            severity: Some(severity),
+            occurred_at: Some(at),
+            title: String::from("title"),
+            summary: summary.to_owned(),
+            fields: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let x = StatusBoards::parse(" deploys, #alerts,,");
+
+        assert_eq!(x.len(), 2);
+        assert!(x.boards.lock().unwrap().contains_key("alerts"));
+    }
+
+    #[test]
+    fn test_take_dirty() {
+        let x = StatusBoards::parse("deploys");
+        let deploys = ChannelName("#deploys".into());
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let an_hour_ago = now - ChronoDuration::hours(1);
+
+        assert!(x.take_dirty(now).is_empty());
+
+        // Undesignated channels and events about no app are ignored.
+        x.record(
+            &ChannelName("other".into()),
+            &event(Some("web"), Severity::Success, "Deploy", now),
+        );
+        x.record(&deploys, &event(None, Severity::Success, "Deploy", now));
+        assert!(x.take_dirty(now).is_empty());
+
+        x.record(
+            &deploys,
+            &event(
+                Some("web"),
+                Severity::Success,
+                "Deployed\nmore",
+                an_hour_ago,
+            ),
+        );
+        x.record(
+            &deploys,
+            &event(Some("api"), Severity::Critical, "Dyno crashed", now),
+        );
+
+        let xs = x.take_dirty(now);
+        assert_eq!(xs.len(), 1);
+        let (msg, existing) = &xs[0];
+        assert!(existing.is_none());
+        assert_eq!(msg.channel.0, "deploys");
+        assert_eq!(
+            msg.desc,
+            "🔴 api: Dyno crashed (22:13 UTC)\n🟢 web: Deployed (21:13 UTC)"
+        );
+        assert_eq!(msg.severity, Some(Severity::Critical));
+
+        // Each change is only taken once.
+        assert!(x.take_dirty(now).is_empty());
+
+        let m = MessageRef {
+            channel_id: ChannelId("C123".into()),
+            ts: "1700000000.000100".into(),
+        };
+        assert!(x.replace(&deploys, m.clone()).is_none());
+
+        // Apps drop off once they're no longer recent.
+        let later = an_hour_ago + RECENT;
+        let xs = x.take_dirty(later);
+        assert_eq!(xs.len(), 1);
+        assert!(xs[0].1 == Some(m));
+        assert_eq!(xs[0].0.desc, "🔴 api: Dyno crashed (22:13 UTC)");
+        assert!(x.take_dirty(later).is_empty());
+    }
+
+    #[test]
+    fn test_render() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let msg = render(ChannelName("deploys".into()), &BTreeMap::new(), now);
+
+        assert_eq!(msg.desc, "No recent events");
+        assert_eq!(msg.severity, Some(Severity::Success));
+        assert!(msg.title_as_header);
+    }
+}
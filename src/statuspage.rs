@@ -0,0 +1,301 @@
+//! Open, update, and resolve incidents on an [Atlassian Statuspage](https://www.atlassian.com/software/statuspage)
+//! page, so that a public status page reflects incidents as they happen.
+//!
+//! Enabled by configuring the page's ID at `$STATUSPAGE_PAGE_ID` and an API key
+//! at `$STATUSPAGE_API_KEY`.
+//!
+//! Incidents are identified by name, one per app, so that they can be found
+//! again to update or resolve without keeping any state of our own. Only the
+//! incident's creation notifies the page's subscribers.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+/// The base URL of the Statuspage API.
+pub const API_BASE: &str = "https://api.statuspage.io/v1";
+
+/// How long to wait for Statuspage to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What went wrong communicating with Statuspage.
+#[derive(Debug)]
+pub enum StatuspageError {
+    /// Statuspage was asked for however `$STATUSPAGE_PAGE_ID` isn't
+    /// configured.
+    Unconfigured,
+    /// General request failure, including unsuccessful status codes.
+    RequestFailed(reqwest::Error),
+}
+
+impl From<reqwest::Error> for StatuspageError {
+    fn from(e: reqwest::Error) -> Self {
+        StatuspageError::RequestFailed(e)
+    }
+}
+
+impl fmt::Display for StatuspageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatuspageError::Unconfigured => write!(f, "Statuspage is not configured"),
+            StatuspageError::RequestFailed(e) => write!(f, "Statuspage request failed: {:?}", e),
+        }
+    }
+}
+
+/// <https://developer.statuspage.io/#operation/getPagesPageIdIncidentsUnresolved>
+#[derive(Deserialize)]
+struct Incident {
+    id: String,
+    name: String,
+}
+
+/// <https://developer.statuspage.io/#operation/postPagesPageIdIncidents>
+#[derive(Serialize)]
+struct IncidentRequest<'a> {
+    incident: IncidentFields<'a>,
+}
+
+#[derive(Serialize)]
+struct IncidentFields<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+    status: &'static str,
+    body: &'a str,
+    deliver_notifications: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component_ids: Option<[&'a str; 1]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    components: Option<serde_json::Value>,
+}
+
+/// What's been done about an incident.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Opened,
+    Updated,
+    Resolved,
+    /// There was nothing to resolve.
+    Unchanged,
+}
+
+/// Manages incidents on a single page, safe to share across requests.
+pub struct StatuspageClient {
+    client: reqwest::Client,
+    base_url: String,
+    page_id: String,
+    api_key: String,
+}
+
+impl StatuspageClient {
+    /// Instantiate against a given base URL, enabling easy mocking. For
+    /// real-world usage see [API_BASE].
+    pub fn new(base_url: String, page_id: String, api_key: String) -> Self {
+        StatuspageClient {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+            base_url,
+            page_id,
+            api_key,
+        }
+    }
+
+    /// Open an incident about an app, or add an update to the one already
+    /// open, marking the component if any as suffering a major outage.
+    pub async fn open(
+        &self,
+        app_name: &str,
+        component: Option<&str>,
+        body: &str,
+    ) -> Result<Outcome, StatuspageError> {
+        let name = to_incident_name(app_name);
+        let existing = self.find_unresolved(&name).await?;
+
+        let fields = IncidentFields {
+            name: existing.is_none().then_some(name.as_str()),
+            status: "investigating",
+            body,
+            deliver_notifications: existing.is_none(),
+            component_ids: component.map(|x| [x]),
+            components: component.map(|x| serde_json::json!({ x: "major_outage" })),
+        };
+
+        match existing {
+            Some(x) => {
+                self.patch(&x.id, fields).await?;
+
+                Ok(Outcome::Updated)
+            }
+            None => {
+                self.client
+                    .post(format!(
+                        "{}/pages/{}/incidents",
+                        self.base_url, self.page_id
+                    ))
+                    .header(reqwest::header::AUTHORIZATION, self.auth())
+                    .json(&IncidentRequest { incident: fields })
+                    .send()
+                    .await?
+                    .error_for_status()?;
+
+                Ok(Outcome::Opened)
+            }
+        }
+    }
+
+    /// Resolve the incident open about an app if there is one, marking the
+    /// component if any as operational.
+    pub async fn resolve(
+        &self,
+        app_name: &str,
+        component: Option<&str>,
+        body: &str,
+    ) -> Result<Outcome, StatuspageError> {
+        let Some(x) = self.find_unresolved(&to_incident_name(app_name)).await? else {
+            return Ok(Outcome::Unchanged);
+        };
+
+        self.patch(
+            &x.id,
+            IncidentFields {
+                name: None,
+                status: "resolved",
+                body,
+                deliver_notifications: true,
+                component_ids: component.map(|x| [x]),
+                components: component.map(|x| serde_json::json!({ x: "operational" })),
+            },
+        )
+        .await?;
+
+        Ok(Outcome::Resolved)
+    }
+
+    async fn find_unresolved(&self, name: &str) -> Result<Option<Incident>, StatuspageError> {
+        let xs: Vec<Incident> = self
+            .client
+            .get(format!(
+                "{}/pages/{}/incidents/unresolved",
+                self.base_url, self.page_id
+            ))
+            .header(reqwest::header::AUTHORIZATION, self.auth())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(xs.into_iter().find(|x| x.name == name))
+    }
+
+    async fn patch(&self, id: &str, fields: IncidentFields<'_>) -> Result<(), StatuspageError> {
+        self.client
+            .patch(format!(
+                "{}/pages/{}/incidents/{}",
+                self.base_url, self.page_id, id
+            ))
+            .header(reqwest::header::AUTHORIZATION, self.auth())
+            .json(&IncidentRequest { incident: fields })
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    fn auth(&self) -> String {
+        format!("OAuth {}", self.api_key)
+    }
+}
+
+/// Incidents are found again by name, so it mustn't change.
+fn to_incident_name(app_name: &str) -> String {
+    format!("Issues affecting {}", app_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_open_and_resolve() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let none = srv
+            .mock("GET", "/pages/p1/incidents/unresolved")
+            .match_header("Authorization", "OAuth key")
+            .with_body(r#"[{"id": "i0", "name": "Issues affecting web"}]"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let create = srv
+            .mock("POST", "/pages/p1/incidents")
+            .match_body(Matcher::Json(json!({
+                "incident": {
+                    "name": "Issues affecting api",
+                    "status": "investigating",
+                    "body": "Dyno web.1 crashed",
+                    "deliver_notifications": true,
+                    "component_ids": ["c1"],
+                    "components": { "c1": "major_outage" },
+                },
+            })))
+            .with_status(201)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let x = StatuspageClient::new(srv.url(), "p1".into(), "key".into());
+
+        assert_eq!(
+            x.open("api", Some("c1"), "Dyno web.1 crashed")
+                .await
+                .unwrap(),
+            Outcome::Opened
+        );
+
+        none.assert_async().await;
+        create.assert_async().await;
+        none.remove_async().await;
+
+        let some = srv
+            .mock("GET", "/pages/p1/incidents/unresolved")
+            .with_body(r#"[{"id": "i1", "name": "Issues affecting api"}]"#)
+            .expect(2)
+            .create_async()
+            .await;
+        let update = srv
+            .mock("PATCH", "/pages/p1/incidents/i1")
+            .match_body(Matcher::PartialJson(json!({
+                "incident": { "status": "investigating", "deliver_notifications": false },
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+        let resolve = srv
+            .mock("PATCH", "/pages/p1/incidents/i1")
+            .match_body(Matcher::Json(json!({
+                "incident": {
+                    "status": "resolved",
+                    "body": "Deployed abc123",
+                    "deliver_notifications": true,
+                },
+            })))
+            .expect(1)
+            .create_async()
+            .await;
+
+        assert_eq!(
+            x.open("api", None, "Dyno web.2 crashed").await.unwrap(),
+            Outcome::Updated
+        );
+        assert_eq!(
+            x.resolve("api", None, "Deployed abc123").await.unwrap(),
+            Outcome::Resolved
+        );
+
+        some.assert_async().await;
+        update.assert_async().await;
+        resolve.assert_async().await;
+    }
+}
@@ -0,0 +1,322 @@
+//! Broadcast messages as they're sent to any number of consumers via
+//! Server-Sent Events or WebSockets, enabling live tickers on dashboards
+//! without polling Slack.
+//!
+//! Consumers can subscribe to a subset of events with a [Filter]. Events are
+//! only broadcast to consumers connected at the time. Consumers which fall
+//! more than [CAPACITY] events behind skip those they've missed.
+
+use crate::{
+    delivery::Source,
+    router::Deps,
+    slack::{channel::ChannelName, router::authenticate, Message, Severity},
+};
+use axum::{
+    extract::{
+        ws::{self, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{
+    de::{value, DeserializeOwned, IntoDeserializer},
+    Deserialize, Serialize,
+};
+use std::convert::Infallible;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+use tracing::warn;
+use url::Url;
+
+/// How many events are buffered for each consumer.
+pub const CAPACITY: usize = 256;
+
+/// A message which has been sent, normalized irrespective of its source.
+#[derive(Clone, Serialize)]
+pub struct StreamEvent {
+    /// The ID of the message's [crate::audit] entry.
+    pub id: u64,
+    pub at: DateTime<Utc>,
+    pub source: Source,
+    /// The app the message is about, if known. See [crate::heroku].
+    pub app: Option<String>,
+    pub channel: ChannelName,
+    pub title: String,
+    pub desc: String,
+    pub link: Option<Url>,
+    pub severity: Option<Severity>,
+}
+
+impl StreamEvent {
+    pub fn new(
+        id: u64,
+        at: DateTime<Utc>,
+        source: Source,
+        app: Option<&str>,
+        msg: &Message,
+    ) -> Self {
+        StreamEvent {
+            id,
+            at,
+            source,
+            app: app.map(str::to_owned),
+            channel: msg.channel.clone(),
+            title: msg.title.clone(),
+            desc: msg.desc.clone(),
+            link: msg.link.clone(),
+            severity: msg.severity,
+        }
+    }
+}
+
+/// The broadcast channel itself, cheaply cloneable.
+#[derive(Clone)]
+pub struct EventStream {
+    sender: broadcast::Sender<StreamEvent>,
+}
+
+impl Default for EventStream {
+    fn default() -> Self {
+        EventStream {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl EventStream {
+    /// Broadcast an event to every connected consumer, if any.
+    pub fn publish(&self, x: StreamEvent) {
+        // This only fails if there are no consumers, which is fine.
+        let _ = self.sender.send(x);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<StreamEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// A subset of events to subscribe to. Each criterion is satisfied by any of
+/// its values, and absent criteria are satisfied by any event.
+#[derive(Default)]
+pub struct Filter {
+    apps: Option<Vec<String>>,
+    sources: Option<Vec<Source>>,
+    severities: Option<Vec<Severity>>,
+}
+
+/// Query params from which a [Filter] is parsed, each a comma-separated list.
+#[derive(Deserialize)]
+pub struct FilterQuery {
+    app: Option<String>,
+    source: Option<String>,
+    severity: Option<String>,
+}
+
+impl Filter {
+    pub fn parse(q: &FilterQuery) -> Result<Self, value::Error> {
+        Ok(Filter {
+            apps: q
+                .app
+                .as_deref()
+                .map(|x| split(x).map(str::to_owned).collect()),
+            sources: q.source.as_deref().map(parse_list).transpose()?,
+            severities: q.severity.as_deref().map(parse_list).transpose()?,
+        })
+    }
+
+    pub fn matches(&self, x: &StreamEvent) -> bool {
+        let app = |xs: &Vec<String>| x.app.as_ref().is_some_and(|a| xs.contains(a));
+        let severity = |xs: &Vec<Severity>| x.severity.is_some_and(|s| xs.contains(&s));
+
+        self.apps.as_ref().map_or(true, app)
+            && self
+                .sources
+                .as_ref()
+                .map_or(true, |xs| xs.contains(&x.source))
+            && self.severities.as_ref().map_or(true, severity)
+    }
+}
+
+fn split(x: &str) -> impl Iterator<Item = &str> {
+    x.split(',').map(str::trim).filter(|x| !x.is_empty())
+}
+
+/// Parse a comma-separated list of values as they're serialized.
+///
+/// ```
+/// assert_eq!(parse_list::<Source>("api, heroku"), Ok(vec![Source::Api, Source::Heroku]));
+/// ```
+pub fn parse_list<T: DeserializeOwned>(x: &str) -> Result<Vec<T>, value::Error> {
+    split(x)
+        .map(|x| T::deserialize(x.into_deserializer()))
+        .collect()
+}
+
+/// Instantiate a new stream subrouter, authenticated as per the Slack
+/// subrouter.
+///
+/// The following subroutes are supported:
+///
+/// - GET: `/`
+/// - GET: `/ws`
+pub fn stream_router(deps: &Deps) -> Router<Deps> {
+    Router::new()
+        .route("/", get(sse_handler))
+        .route("/ws", get(ws_handler))
+        .layer(middleware::from_fn_with_state(deps.clone(), authenticate))
+}
+
+/// Handler for the GET route `/api/v1/stream`.
+///
+/// Responds with an indefinite `text/event-stream` of [StreamEvent]s in JSON
+/// format, each identified by its audit ID, optionally narrowed by a [Filter].
+async fn sse_handler(State(deps): State<Deps>, Query(q): Query<FilterQuery>) -> Response {
+    let filter = match Filter::parse(&q) {
+        Ok(x) => x,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let xs = BroadcastStream::new(deps.events.subscribe()).filter_map(move |x| match x {
+        Ok(x) => filter
+            .matches(&x)
+            .then(|| Ok::<_, Infallible>(to_sse_event(&x))),
+        Err(e) => {
+            warn!("Stream consumer lagged: {}", e);
+
+            None
+        }
+    });
+
+    Sse::new(xs)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+fn to_sse_event(x: &StreamEvent) -> Event {
+    Event::default()
+        .event("message")
+        .id(x.id.to_string())
+        // This can only fail on non-string map keys, of which we have none.
+        .json_data(x)
+        .unwrap()
+}
+
+/// Handler for the GET route `/api/v1/stream/ws`.
+///
+/// Upgrades to a WebSocket over which [StreamEvent]s are sent in JSON format
+/// as text messages, optionally narrowed by a [Filter]. Anything received from
+/// the consumer besides closing the connection is ignored.
+async fn ws_handler(
+    State(deps): State<Deps>,
+    Query(q): Query<FilterQuery>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    let filter = match Filter::parse(&q) {
+        Ok(x) => x,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let rx = deps.events.subscribe();
+
+    ws.on_upgrade(move |socket| forward(socket, rx, filter))
+}
+
+/// Forward events to a WebSocket until either end closes.
+async fn forward(mut socket: WebSocket, mut rx: broadcast::Receiver<StreamEvent>, filter: Filter) {
+    loop {
+        tokio::select! {
+            x = rx.recv() => match x {
+                Ok(x) if filter.matches(&x) => {
+                    // This can only fail on non-string map keys, of which we
+                    // have none.
+                    let json = serde_json::to_string(&x).unwrap();
+
+                    if socket.send(ws::Message::Text(json)).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => warn!("WebSocket consumer lagged by {} events", n),
+                Err(RecvError::Closed) => break,
+            },
+            x = socket.recv() => match x {
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(app: Option<&str>, source: Source, severity: Option<Severity>) -> StreamEvent {
+        StreamEvent {
+            id: 1,
+            at: Utc::now(),
+            source,
+            app: app.map(str::to_owned),
+            channel: ChannelName("any".into()),
+            title: "any".into(),
+            desc: "any".into(),
+            link: None,
+            severity,
+        }
+    }
+
+    fn filter(app: Option<&str>, source: Option<&str>, severity: Option<&str>) -> Filter {
+        Filter::parse(&FilterQuery {
+            app: app.map(str::to_owned),
+            source: source.map(str::to_owned),
+            severity: severity.map(str::to_owned),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_list::<Source>("api, heroku,").unwrap(),
+            vec![Source::Api, Source::Heroku]
+        );
+        assert_eq!(
+            parse_list::<Severity>("critical").unwrap(),
+            vec![Severity::Critical]
+        );
+        assert!(parse_list::<Source>("api,nope").is_err());
+    }
+
+    #[test]
+    fn test_filter() {
+        let crash = event(Some("web"), Source::Heroku, Some(Severity::Critical));
+        let api = event(None, Source::Api, None);
+
+        let any = Filter::default();
+        assert!(any.matches(&crash));
+        assert!(any.matches(&api));
+
+        let web = filter(Some("api,web"), None, None);
+        assert!(web.matches(&crash));
+        assert!(!web.matches(&api));
+
+        let heroku = filter(None, Some("heroku"), None);
+        assert!(heroku.matches(&crash));
+        assert!(!heroku.matches(&api));
+
+        let critical = filter(None, None, Some("warning,critical"));
+        assert!(critical.matches(&crash));
+        assert!(!critical.matches(&api));
+
+        let all = filter(Some("web"), Some("heroku"), Some("success"));
+        assert!(!all.matches(&crash));
+    }
+}
@@ -0,0 +1,241 @@
+//! Per-request telemetry, enriching trace spans and request metrics with the
+//! matched route, the client's identity, and the channel messaged, if any.
+//! Spans additionally include the client's IP as per [crate::proxy].
+//!
+//! Handlers and middleware deeper in the stack contribute what they learn via
+//! [record_client] and [record_channel], which are no-ops outside of a request.
+//!
+//! Noisy requests such as health checks can be excluded from access logs with
+//! a [TraceFilter]. They're still measured.
+
+use crate::{metrics::Metrics, proxy::ClientIp, slack::channel::ChannelName};
+use axum::{
+    extract::{MatchedPath, OriginalUri, Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tracing::{debug, field, info_span, Span};
+
+/// The label used when something is unknown, for example if the request was
+/// unauthenticated.
+const UNKNOWN: &str = "unknown";
+
+tokio::task_local! {
+    static LABELS: Arc<Mutex<Labels>>;
+}
+
+/// What's been learnt about the current request.
+#[derive(Default)]
+struct Labels {
+    client: Option<String>,
+    channel: Option<String>,
+}
+
+fn route(req: &Request) -> &str {
+    req.extensions()
+        .get::<MatchedPath>()
+        .map_or(UNKNOWN, |x| x.as_str())
+}
+
+/// Requests to exclude from access logs, matched by method and route. Routes
+/// are matched by their template, for example `/api/v1/admin/audit/:id`, or
+/// by path for unmatched requests.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TraceFilter(Vec<Exclusion>);
+
+/// A single exclusion within a [TraceFilter].
+#[derive(Debug, PartialEq, Eq)]
+struct Exclusion {
+    /// Any method if absent.
+    method: Option<Method>,
+    path: String,
+}
+
+impl TraceFilter {
+    /// Parse exclusions from their environment variable representation, a
+    /// comma-separated list of paths each optionally preceded by a method.
+    ///
+    /// ```
+    /// let x = TraceFilter::parse("GET /api/v1/health, /api/v1/metrics");
+    /// ```
+    pub fn parse(x: &str) -> Result<Self, String> {
+        x.split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| match x.split_once(' ') {
+                Some((m, path)) => Method::from_bytes(m.as_bytes())
+                    .map(|m| Exclusion {
+                        method: Some(m),
+                        path: path.trim().to_owned(),
+                    })
+                    .map_err(|_| format!("invalid method: {}", m)),
+                None => Ok(Exclusion {
+                    method: None,
+                    path: x.to_owned(),
+                }),
+            })
+            .collect::<Result<_, _>>()
+            .map(TraceFilter)
+    }
+
+    /// Whether a request should be excluded from access logs.
+    fn excludes(&self, req: &Request) -> bool {
+        let path = match req.extensions().get::<MatchedPath>() {
+            Some(x) => x.as_str(),
+            None => req
+                .extensions()
+                .get::<OriginalUri>()
+                .map_or(req.uri().path(), |x| x.path()),
+        };
+
+        self.0
+            .iter()
+            .any(|x| x.path == path && x.method.as_ref().map_or(true, |m| m == req.method()))
+    }
+
+    /// Make a span for a request as per [make_span], unless it's excluded, in
+    /// which case the span is disabled.
+    pub fn make_span(&self, req: &Request) -> Span {
+        if self.excludes(req) {
+            Span::none()
+        } else {
+            make_span(req)
+        }
+    }
+}
+
+/// Health checks and metrics scrapes are frequent and uninteresting.
+impl Default for TraceFilter {
+    fn default() -> Self {
+        TraceFilter::parse("GET /api/v1/health, GET /api/v1/health/deep, GET /api/v1/metrics")
+            .unwrap()
+    }
+}
+
+/// Make a span for a request, leaving the client and channel to be recorded
+/// later.
+pub fn make_span(req: &Request) -> Span {
+    info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        route = route(req),
+        client_ip = req.extensions().get::<ClientIp>().map(|x| field::display(x.0)),
+        client = field::Empty,
+        channel = field::Empty,
+    )
+}
+
+/// Record the identity of the client making the current request.
+pub fn record_client(x: &str) {
+    Span::current().record("client", x);
+    let _ = LABELS.try_with(|l| l.lock().unwrap().client = Some(x.to_owned()));
+}
+
+/// Record the channel the current request is messaging.
+pub fn record_channel(x: &ChannelName) {
+    let x = x.0.trim_start_matches('#');
+
+    Span::current().record("channel", x);
+    let _ = LABELS.try_with(|l| l.lock().unwrap().channel = Some(x.to_owned()));
+}
+
+/// Log one in this many occurrences of each unknown field, starting with the
+/// first.
+const UNKNOWN_FIELD_LOG_EVERY: u64 = 100;
+
+/// Count fields we don't recognise in a payload from some source, logging a
+/// sample of them. New paths suggest the source has changed its payloads.
+pub fn record_unknown_fields(metrics: &Metrics, source: &str, paths: &[String]) {
+    for path in paths {
+        let counter = metrics.unknown_fields.with_label_values(&[source, path]);
+        counter.inc();
+
+        if counter.get() % UNKNOWN_FIELD_LOG_EVERY == 1 {
+            debug!(
+                "Unknown field in {} payload: {} (seen {} times)",
+                source,
+                path,
+                counter.get()
+            );
+        }
+    }
+}
+
+/// Middleware recording how long each request takes, labelled by what we've
+/// learnt about it.
+pub async fn track(State(metrics): State<Metrics>, req: Request, next: Next) -> Response {
+    let route = route(&req).to_owned();
+    let method = req.method().to_string();
+    let labels = Arc::new(Mutex::new(Labels::default()));
+    let start = Instant::now();
+
+    let res = LABELS.scope(labels.clone(), next.run(req)).await;
+
+    let labels = labels.lock().unwrap();
+    metrics
+        .http_request_duration
+        .with_label_values(&[
+            &route,
+            &method,
+            res.status().as_str(),
+            labels.client.as_deref().unwrap_or(UNKNOWN),
+            labels.channel.as_deref().unwrap_or(UNKNOWN),
+        ])
+        .observe(start.elapsed().as_secs_f64());
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn req(method: Method, path: &str) -> Request {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(path)
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(OriginalUri(path.parse().unwrap()));
+        req
+    }
+
+    #[test]
+    fn test_trace_filter_parse() {
+        assert_eq!(
+            TraceFilter::parse(" GET /foo,, /bar "),
+            Ok(TraceFilter(vec![
+                Exclusion {
+                    method: Some(Method::GET),
+                    path: "/foo".into(),
+                },
+                Exclusion {
+                    method: None,
+                    path: "/bar".into(),
+                },
+            ]))
+        );
+        assert_eq!(TraceFilter::parse(""), Ok(TraceFilter(Vec::new())));
+        assert!(TraceFilter::parse("G@T /foo").is_err());
+    }
+
+    #[test]
+    fn test_trace_filter_excludes() {
+        let x = TraceFilter::parse("GET /foo, /bar").unwrap();
+
+        assert!(x.excludes(&req(Method::GET, "/foo")));
+        assert!(!x.excludes(&req(Method::POST, "/foo")));
+        assert!(!x.excludes(&req(Method::GET, "/foo/baz")));
+        assert!(x.excludes(&req(Method::GET, "/bar")));
+        assert!(x.excludes(&req(Method::DELETE, "/bar")));
+    }
+}
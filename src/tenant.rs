@@ -0,0 +1,471 @@
+//! Serve several tenants, for example subsidiaries each with their own Slack
+//! workspace, from a single instance rather than a deployment apiece.
+//!
+//! Tenants are named at `$TENANTS` as a comma-separated list, for example
+//! `acme, globex`. Each is configured by environment variables prefixed by
+//! `TENANT_` and its name in upper case, for `acme` namely:
+//!
+//! - `$TENANT_ACME_SLACK_TOKEN`: Required, the Slack access token for its
+//!   workspace.
+//! - `$TENANT_ACME_SLACK_SIGNING_SECRET`: Authenticates its interactions.
+//! - `$TENANT_ACME_MERCURY_API_TOKEN`: Its API tokens, as per [crate::auth].
+//! - `$TENANT_ACME_SIGNING_SECRETS`: Its clients' signing secrets, as per
+//!   [crate::signing].
+//! - `$TENANT_ACME_ADMIN_TOKEN`: Authenticates its admin API, as per
+//!   [crate::admin]. Without one it has none.
+//! - `$TENANT_ACME_HEROKU_SECRET`: Its Heroku webhook secret.
+//! - `$TENANT_ACME_HEROKU_APP_ROUTES` and `$TENANT_ACME_HEROKU_NAMED_ROUTES`:
+//!   Its app and named routes, as per [crate::heroku::routing].
+//! - `$TENANT_ACME_HOSTS`: A comma-separated list of hostnames serving it, for
+//!   example `mercury.acme.com`.
+//!
+//! Requests are served on behalf of the tenant whose hosts include the request's
+//! `Host`, else the tenant whose API tokens include its bearer token, else the
+//! default tenant, configured as usual. Heroku webhooks, signed requests, and
+//! admin requests carry no API token, so tenants receiving them need a host of
+//! their own.
+//!
+//! Each tenant has its own audit history, event stream, response cache,
+//! ingestion state, stats, and read-only mode, so that neither its messages
+//! nor its administration are visible to other tenants. Its audit history is
+//! only kept in memory. Each also has its own priority lanes, so that Slack
+//! rate limiting one workspace doesn't hold up the others.
+//!
+//! On-call mentions are resolved via the same provider for every tenant, each
+//! matching on-call users to members of its own workspace. Tenants are served
+//! on the internal listener too, if there is one, where admin requests
+//! likewise need a host of their own.
+//!
+//! Integrations beyond Slack, such as GitHub, Jira, Statuspage, SMS, and push
+//! notifications, are configured for the default tenant alone, and so are
+//! disabled for others, as is recording captures and fixtures. Work done in
+//! the background, such as escalations, noise budgets, status boards,
+//! heartbeats, and threading, is likewise only done for the default tenant, as
+//! are shadowing, self-tests, and operator notifications. The gRPC API only
+//! serves the default tenant.
+
+use crate::{
+    admin::AdminToken,
+    audit::AuditLog,
+    auth::{find_bearer, ApiToken},
+    bounded::StateMetrics,
+    cache::ResponseCache,
+    heroku::{webhook::MAX_RELEASE_COMMITS, AppRoutes, HerokuSecret, ReleaseCommitMap},
+    ingestion::Ingestion,
+    priority::Lanes,
+    router::Deps,
+    signing::SigningSecrets,
+    slack::{interactivity::SlackSigningSecret, SlackAccessToken, SlackClient},
+    stats::Stats,
+    stream::EventStream,
+};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use axum::{
+    extract::{Request, State},
+    http::header::{AUTHORIZATION, HOST},
+    response::{IntoResponse, Response},
+    Router,
+};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tokio::sync::Mutex;
+use tower::ServiceExt;
+
+/// Parse tenant names from their environment variable representation.
+///
+/// ```
+/// let xs = parse_tenants("acme, globex");
+/// assert_eq!(xs.len(), 2);
+/// ```
+pub fn parse_tenants(x: &str) -> Vec<String> {
+    x.split(',')
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The prefix of a tenant's environment variables, for example `TENANT_ACME`.
+pub fn env_prefix(name: &str) -> String {
+    format!("TENANT_{}", name.to_uppercase().replace('-', "_"))
+}
+
+/// A tenant's own configuration.
+pub struct TenantConfig {
+    pub name: String,
+    /// Normalised as per [normalise_host].
+    pub hosts: Vec<String>,
+    pub slack_token: SlackAccessToken,
+    pub slack_signing_secret: Option<SlackSigningSecret>,
+    pub api_tokens: Vec<ApiToken>,
+    pub signing_secrets: SigningSecrets,
+    pub admin_token: Option<AdminToken>,
+    pub heroku_secret: Option<HerokuSecret>,
+    pub heroku_app_routes: AppRoutes,
+    pub heroku_named_routes: AppRoutes,
+}
+
+/// Parse hostnames from their environment variable representation.
+pub fn parse_hosts(x: &str) -> Vec<String> {
+    x.split(',')
+        .map(normalise_host)
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Hostnames are case insensitive, and ports aren't part of them.
+fn normalise_host(x: &str) -> String {
+    let x = x.trim();
+    let x = match x.rsplit_once(':') {
+        // Bracketed IPv6 addresses contain colons of their own.
+        Some((host, port)) if !port.contains(']') => host,
+        _ => x,
+    };
+
+    x.to_lowercase()
+}
+
+/// Derive a tenant's dependencies from those of the default tenant, the tenant
+/// talking to Slack via the given client. Only configuration is shared, as
+/// described above.
+pub fn tenant_deps(base: &Deps, x: TenantConfig, slack_client: SlackClient) -> Deps {
+    let state_metrics =
+        |kind: &str| StateMetrics::new(&base.metrics, &format!("{}_{}", kind, x.name));
+
+    Deps {
+        slack_history: slack_client.history(),
+        #[cfg(feature = "chaos")]
+        slack_chaos: slack_client.chaos(),
+        slack_client: Arc::new(Mutex::new(slack_client)),
+        slack_token: Arc::new(ArcSwap::from_pointee(x.slack_token)),
+        slack_token_expires_at: Arc::new(ArcSwapOption::empty()),
+        slack_signing_secret: x.slack_signing_secret,
+        heroku_secrets: Arc::new(ArcSwap::from_pointee(x.heroku_secret.into_iter().collect())),
+        heroku_app_routes: Arc::new(x.heroku_app_routes),
+        heroku_named_routes: Arc::new(x.heroku_named_routes),
+        api_tokens: Arc::new(ArcSwap::from_pointee(x.api_tokens)),
+        slack_token_compat: false,
+        signing_secrets: x.signing_secrets,
+        admin_token: x.admin_token,
+        audit: Arc::new(AuditLog::new(base.audit.capacity())),
+        events: EventStream::default(),
+        response_cache: Arc::new(ResponseCache::new(
+            base.response_cache.ttl(),
+            state_metrics("responses"),
+        )),
+        ingestion: Arc::new(Ingestion::default()),
+        lanes: Arc::new(Lanes::new(base.lanes.max_backlog())),
+        stats: Arc::new(Stats::default()),
+        read_only: Arc::new(AtomicBool::new(base.read_only.load(Ordering::Relaxed))),
+        release_commits: Arc::new(Mutex::new(ReleaseCommitMap::new(
+            MAX_RELEASE_COMMITS,
+            state_metrics("release_commits"),
+        ))),
+        github_token: None,
+        heroku_push: None,
+        heroku_jira: None,
+        heroku_linear: None,
+        heroku_calendar: None,
+        heroku_otto: None,
+        sms: None,
+        matrix: None,
+        zulip: None,
+        statuspage: None,
+        eventbridge: None,
+        grafana: None,
+        datadog: None,
+        honeycomb: None,
+        event_log: None,
+        meta_alerts: None,
+        captures: None,
+        fixtures: None,
+        escalations: None,
+        noise_budgets: None,
+        status_boards: None,
+        heartbeats: None,
+        threads: None,
+        shadow: None,
+        selftest_channel: None,
+        ops_channel: None,
+        ..base.clone()
+    }
+}
+
+/// A tenant as it's served.
+struct Tenant {
+    hosts: Vec<String>,
+    /// Shared with the tenant's dependencies, so as to follow rotation.
+    api_tokens: Arc<ArcSwap<Vec<ApiToken>>>,
+    router: Router,
+}
+
+/// Every tenant's router, and that of the default tenant.
+pub struct Tenants {
+    tenants: Vec<Tenant>,
+    default: Router,
+}
+
+impl Tenants {
+    pub fn new(default: Router) -> Self {
+        Tenants {
+            tenants: Vec::new(),
+            default,
+        }
+    }
+
+    /// Add a tenant served by the given hosts and router, whose API tokens are
+    /// those of its dependencies.
+    pub fn with_tenant(mut self, hosts: Vec<String>, deps: &Deps, router: Router) -> Self {
+        self.tenants.push(Tenant {
+            hosts,
+            api_tokens: deps.api_tokens.clone(),
+            router,
+        });
+
+        self
+    }
+
+    fn select(&self, req: &Request) -> &Router {
+        let host = req
+            .headers()
+            .get(HOST)
+            .and_then(|x| x.to_str().ok())
+            .or_else(|| req.uri().host())
+            .map(normalise_host);
+
+        if let Some(x) = host.and_then(|h| self.tenants.iter().find(|t| t.hosts.contains(&h))) {
+            return &x.router;
+        }
+
+        let by_token = req.headers().get(AUTHORIZATION).and_then(|val| {
+            let api_tokens: Vec<_> = self.tenants.iter().map(|t| t.api_tokens.load()).collect();
+            let accepted = api_tokens
+                .iter()
+                .enumerate()
+                .flat_map(|(i, xs)| xs.iter().map(move |t| (i, t.token.as_str())));
+
+            find_bearer(val, accepted)
+        });
+
+        match by_token {
+            Some(i) => &self.tenants[i].router,
+            None => &self.default,
+        }
+    }
+}
+
+/// Instantiate a router serving each request on behalf of its tenant.
+pub fn router(tenants: Tenants) -> Router {
+    Router::new()
+        .fallback(dispatch)
+        .with_state(Arc::new(tenants))
+}
+
+async fn dispatch(State(tenants): State<Arc<Tenants>>, req: Request) -> Response {
+    // Routers are infallible.
+    let Ok(res) = tenants.select(&req).clone().oneshot(req).await;
+
+    res.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        auth::parse_api_tokens,
+        delivery::{Delivery, Source},
+        slack::{channel::ChannelName, Message},
+    };
+    use axum::{body::Body, http::StatusCode, routing::get};
+
+    fn tenants() -> Router {
+        let tenant =
+            |name: &'static str| Router::new().route("/", get(move || async move { name }));
+        let deps = |tokens: &str| Arc::new(ArcSwap::from_pointee(parse_api_tokens(tokens)));
+
+        let mut xs = Tenants::new(tenant("default"));
+        for (name, hosts, tokens) in [
+            ("acme", "mercury.acme.com", "ci:acme-token"),
+            (
+                "globex",
+                "mercury.globex.com, globex.internal",
+                "globex-token",
+            ),
+        ] {
+            xs.tenants.push(Tenant {
+                hosts: parse_hosts(hosts),
+                api_tokens: deps(tokens),
+                router: tenant(name),
+            });
+        }
+
+        router(xs)
+    }
+
+    async fn get_tenant(host: Option<&str>, token: Option<&str>) -> String {
+        let mut req = Request::builder().uri("/");
+        if let Some(x) = host {
+            req = req.header(HOST, x);
+        }
+        if let Some(x) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", x));
+        }
+
+        let res = tenants()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        String::from_utf8(
+            axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap()
+                .to_vec(),
+        )
+        .unwrap()
+    }
+
+    fn config(name: &str) -> TenantConfig {
+        TenantConfig {
+            name: name.to_owned(),
+            hosts: Vec::new(),
+            slack_token: SlackAccessToken(format!("{}-slack", name)),
+            slack_signing_secret: None,
+            api_tokens: parse_api_tokens(&format!("{}-token", name)),
+            signing_secrets: SigningSecrets::new(),
+            admin_token: Some(AdminToken(format!("{}-admin", name))),
+            heroku_secret: None,
+            heroku_app_routes: AppRoutes::new(),
+            heroku_named_routes: AppRoutes::new(),
+        }
+    }
+
+    async fn get_body(
+        rt: &Router,
+        host: Option<&str>,
+        uri: &str,
+        token: &str,
+    ) -> (StatusCode, String) {
+        let mut req = Request::builder()
+            .uri(uri)
+            .header(AUTHORIZATION, format!("Bearer {}", token));
+        if let Some(x) = host {
+            req = req.header(HOST, x);
+        }
+
+        let res = rt
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn test_parse_tenants() {
+        assert_eq!(parse_tenants("acme, globex,,"), vec!["acme", "globex"]);
+        assert_eq!(env_prefix("acme-eu"), "TENANT_ACME_EU");
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        assert_eq!(
+            parse_hosts("Mercury.Acme.com, localhost:8080,[::1]:80, [::1]"),
+            vec!["mercury.acme.com", "localhost", "[::1]", "[::1]"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select() {
+        assert_eq!(get_tenant(None, None).await, "default");
+        assert_eq!(get_tenant(Some("mercury.acme.com"), None).await, "acme");
+        assert_eq!(
+            get_tenant(Some("GLOBEX.internal:443"), None).await,
+            "globex"
+        );
+        assert_eq!(get_tenant(None, Some("globex-token")).await, "globex");
+        assert_eq!(get_tenant(None, Some("unknown")).await, "default");
+
+        // Hosts take precedence, leaving the tenant to reject others' tokens.
+        assert_eq!(
+            get_tenant(Some("mercury.acme.com"), Some("globex-token")).await,
+            "acme"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_isolation() {
+        let base = crate::router::tests::deps(
+            "any".to_owned(),
+            SlackAccessToken("foobar".to_owned()),
+            None,
+        );
+        let acme = tenant_deps(&base, config("acme"), SlackClient::new("any".to_owned()));
+        let globex = tenant_deps(&base, config("globex"), SlackClient::new("any".to_owned()));
+
+        // Slack rate limiting one workspace mustn't pause the others.
+        assert!(!Arc::ptr_eq(&acme.lanes, &base.lanes));
+        assert!(!Arc::ptr_eq(&acme.lanes, &globex.lanes));
+        assert_eq!(acme.lanes.max_backlog(), base.lanes.max_backlog());
+
+        acme.audit.record(
+            &Message::new(ChannelName("deploys".into()), "acme only", "any"),
+            Source::Api,
+            &Ok(Delivery::Sent),
+        );
+
+        let rt = router(
+            Tenants::new(crate::router::new(base.clone()))
+                .with_tenant(
+                    parse_hosts("mercury.acme.com"),
+                    &acme,
+                    crate::router::new(acme.clone()),
+                )
+                .with_tenant(
+                    parse_hosts("mercury.globex.com"),
+                    &globex,
+                    crate::router::new(globex.clone()),
+                ),
+        );
+
+        // Selected by token.
+        let feed = "/api/v1/feeds/api.atom";
+        let (status, body) = get_body(&rt, None, feed, "acme-token").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("acme only"));
+
+        for token in ["globex-token", "foobar"] {
+            let (status, body) = get_body(&rt, None, feed, token).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(!body.contains("acme only"));
+        }
+
+        // Selected by host, where others' tokens are rejected.
+        let (status, _) = get_body(&rt, Some("mercury.acme.com"), feed, "globex-token").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let entry = "/api/v1/admin/audit/1";
+        let admin = |host, token| get_body(&rt, host, entry, token);
+        assert_eq!(
+            admin(Some("mercury.acme.com"), "acme-admin").await.0,
+            StatusCode::OK
+        );
+        assert_eq!(
+            admin(Some("mercury.acme.com"), "globex-admin").await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            admin(Some("mercury.globex.com"), "globex-admin").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(admin(None, "admin").await.0, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,135 @@
+//! Group related messages into threads, keeping channels readable during
+//! bursts of activity such as deploy storms.
+//!
+//! Enabled by setting `$THREAD_WINDOW_MINS`. The first message for a given key,
+//! for example a Heroku app, is posted to the channel as usual. Subsequent
+//! messages for the same key in the same channel are posted as replies in its
+//! thread, for as long as each arrives within the window of the last.
+
+use crate::{
+    bounded::{BoundedMap, StateMetrics},
+    slack::{channel::ChannelName, message::MessageRef},
+};
+use std::{sync::Mutex, time::Duration};
+
+#[cfg(test)]
+use mock_instant::Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// The most threads tracked at once, beyond which the least recently posted to
+/// are forgotten.
+const MAX_THREADS: usize = 10_000;
+
+/// A thread and when it was last posted to.
+struct Thread {
+    parent: MessageRef,
+    last_at: Instant,
+}
+
+/// Tracks the active thread per channel and key, safe to share across
+/// requests.
+pub struct Threads {
+    window: Duration,
+    /// Keyed by channel name, without any leading hash, and grouping key.
+    threads: Mutex<BoundedMap<(String, String), Thread>>,
+}
+
+impl Threads {
+    pub fn new(window: Duration, metrics: StateMetrics) -> Self {
+        Threads {
+            window,
+            threads: Mutex::new(BoundedMap::new(MAX_THREADS, metrics)),
+        }
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Get the timestamp of the message to reply to for a key, if there's an
+    /// active thread, keeping the thread active.
+    pub fn get_parent_ts(&self, channel: &ChannelName, key: &str) -> Option<String> {
+        let mut threads = self.threads.lock().unwrap();
+
+        let t = threads
+            .get_mut(&to_key(channel, key))
+            .filter(|t| t.last_at.elapsed() < self.window)?;
+        t.last_at = Instant::now();
+
+        Some(t.parent.ts.to_owned())
+    }
+
+    /// Get the message an active thread for a key replies to, if there is one,
+    /// without keeping it active.
+    pub fn parent(&self, channel: &ChannelName, key: &str) -> Option<MessageRef> {
+        let threads = self.threads.lock().unwrap();
+
+        threads
+            .get(&to_key(channel, key))
+            .filter(|t| t.last_at.elapsed() < self.window)
+            .map(|t| t.parent.clone())
+    }
+
+    /// Start a new thread for a key, replacing any inactive one.
+    pub fn start(&self, channel: &ChannelName, key: &str, parent: MessageRef) {
+        let mut threads = self.threads.lock().unwrap();
+
+        threads.retain(|_, t| t.last_at.elapsed() < self.window);
+        threads.insert(
+            to_key(channel, key),
+            Thread {
+                parent,
+                last_at: Instant::now(),
+            },
+        );
+    }
+}
+
+fn to_key(channel: &ChannelName, key: &str) -> (String, String) {
+    // Consumers may or may not supply a leading hash.
+    (channel.0.trim_start_matches('#').to_owned(), key.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::Metrics, slack::channel::ChannelId};
+    use mock_instant::MockClock;
+
+    #[test]
+    fn test_threads() {
+        let x = Threads::new(
+            Duration::from_secs(60 * 10),
+            StateMetrics::new(&Metrics::new(), "threads"),
+        );
+        let deploys = ChannelName("deploys".into());
+        let parent = MessageRef {
+            channel_id: ChannelId("C123".into()),
+            ts: "1700000000.000100".into(),
+        };
+
+        assert_eq!(x.get_parent_ts(&deploys, "web"), None);
+
+        x.start(&deploys, "web", parent);
+        assert_eq!(
+            x.get_parent_ts(&ChannelName("#deploys".into()), "web"),
+            Some("1700000000.000100".into())
+        );
+        assert_eq!(x.get_parent_ts(&deploys, "api"), None);
+        assert_eq!(
+            x.parent(&deploys, "web").map(|x| x.ts),
+            Some("1700000000.000100".into())
+        );
+        assert_eq!(x.get_parent_ts(&ChannelName("other".into()), "web"), None);
+
+        // Each message keeps the thread active.
+        MockClock::advance(Duration::from_secs(60 * 9));
+        assert!(x.get_parent_ts(&deploys, "web").is_some());
+        MockClock::advance(Duration::from_secs(60 * 9));
+        assert!(x.get_parent_ts(&deploys, "web").is_some());
+
+        MockClock::advance(Duration::from_secs(60 * 10));
+        assert_eq!(x.get_parent_ts(&deploys, "web"), None);
+    }
+}
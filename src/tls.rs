@@ -0,0 +1,169 @@
+//! Configure TLS for requests to platforms hosted within a private network,
+//! trusting a custom certificate authority and optionally authenticating with
+//! a client certificate, that is mutual TLS.
+//!
+//! Configured per platform via environment variables prefixed by its own, for
+//! example for Matrix `$MATRIX_TLS_CA` is the path to a PEM bundle of CA
+//! certificates to trust in addition to the system's, and `$MATRIX_TLS_CERT`
+//! and `$MATRIX_TLS_KEY` are the paths to a PEM client certificate chain and
+//! its PKCS #8 private key. Supported by each of [PLATFORMS].
+
+use reqwest::{Certificate, ClientBuilder, Identity};
+use std::{fmt, fs};
+
+/// The prefixes of the platforms whose TLS can be configured.
+pub const PLATFORMS: [&str; 4] = ["MATRIX", "ZULIP", "GRAFANA", "OTTO"];
+
+/// Something wrong with a platform's TLS configuration.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TlsError {
+    /// The environment variable at fault, without its leading `$`.
+    pub var: String,
+    pub error: String,
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "${}: {}", self.var, self.error)
+    }
+}
+
+/// A platform's TLS configuration, by default that of the system.
+#[derive(Clone, Default)]
+pub struct ClientTls {
+    ca: Vec<Certificate>,
+    identity: Option<Identity>,
+}
+
+impl ClientTls {
+    /// Load the configuration of the platform with the given prefix, for
+    /// example `MATRIX`.
+    pub fn from_env(get: impl Fn(&str) -> Option<String>, prefix: &str) -> Result<Self, TlsError> {
+        let var = |x: &str| format!("{}_TLS_{}", prefix, x);
+        let read = |var: &str| {
+            get(var)
+                .map(|path| {
+                    fs::read(&path).map_err(|e| TlsError {
+                        var: var.to_owned(),
+                        error: format!("could not read {}: {}", path, e),
+                    })
+                })
+                .transpose()
+        };
+
+        let ca = match read(&var("CA"))? {
+            None => Vec::new(),
+            Some(x) => match Certificate::from_pem_bundle(&x) {
+                Ok(xs) if !xs.is_empty() => xs,
+                _ => {
+                    return Err(TlsError {
+                        var: var("CA"),
+                        error: String::from("expected a PEM bundle of certificates"),
+                    })
+                }
+            },
+        };
+
+        let identity = match (read(&var("CERT"))?, read(&var("KEY"))?) {
+            (None, None) => None,
+            (Some(_), None) => {
+                return Err(TlsError {
+                    var: var("KEY"),
+                    error: format!("required by ${}", var("CERT")),
+                })
+            }
+            (None, Some(_)) => {
+                return Err(TlsError {
+                    var: var("CERT"),
+                    error: format!("required by ${}", var("KEY")),
+                })
+            }
+            (Some(cert), Some(key)) => {
+                Some(Identity::from_pkcs8_pem(&cert, &key).map_err(|e| TlsError {
+                    var: var("CERT"),
+                    error: format!("could not load certificate and key: {}", e),
+                })?)
+            }
+        };
+
+        let x = ClientTls { ca, identity };
+
+        // Some problems, such as a key not matching its certificate, are only
+        // found when building a client.
+        x.apply(reqwest::Client::builder())
+            .build()
+            .map_err(|e| TlsError {
+                var: var("CERT"),
+                error: format!("could not configure TLS: {}", e),
+            })?;
+
+        Ok(x)
+    }
+
+    pub fn apply(&self, builder: ClientBuilder) -> ClientBuilder {
+        let builder = self
+            .ca
+            .iter()
+            .fold(builder, |b, x| b.add_root_certificate(x.clone()));
+
+        match &self.identity {
+            Some(x) => builder.identity(x.clone()),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn env(xs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let xs: HashMap<String, String> = xs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        move |x| xs.get(x).cloned()
+    }
+
+    #[test]
+    fn test_from_env() {
+        assert!(ClientTls::from_env(env(&[]), "MATRIX").is_ok());
+
+        assert_eq!(
+            ClientTls::from_env(env(&[("MATRIX_TLS_CA", "/nonexistent.pem")]), "MATRIX")
+                .err()
+                .map(|e| e.var),
+            Some(String::from("MATRIX_TLS_CA"))
+        );
+
+        let path = std::env::temp_dir().join("mercury-tls-test-not-a-bundle.pem");
+        fs::write(&path, "not a certificate").unwrap();
+        let path = path.to_str().unwrap();
+
+        assert_eq!(
+            ClientTls::from_env(env(&[("ZULIP_TLS_CA", path)]), "ZULIP").err(),
+            Some(TlsError {
+                var: String::from("ZULIP_TLS_CA"),
+                error: String::from("expected a PEM bundle of certificates"),
+            })
+        );
+        assert_eq!(
+            ClientTls::from_env(env(&[("OTTO_TLS_CERT", path)]), "OTTO").err(),
+            Some(TlsError {
+                var: String::from("OTTO_TLS_KEY"),
+                error: String::from("required by $OTTO_TLS_CERT"),
+            })
+        );
+        assert_eq!(
+            ClientTls::from_env(
+                env(&[("OTTO_TLS_CERT", path), ("OTTO_TLS_KEY", path)]),
+                "OTTO"
+            )
+            .err()
+            .map(|e| e.var),
+            Some(String::from("OTTO_TLS_CERT"))
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Structured errors for requests which fail to deserialise.
+//!
+//! Serde's error prose echoes the values it received, which may be sensitive,
+//! and leaves clients guessing at which field was malformed. Instead we respond
+//! with a [ValidationError] naming the field and the type of value received,
+//! never the value itself.
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, RawForm, Request},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use serde_path_to_error::{Path, Segment};
+
+/// The body of a `422 Unprocessable Entity` response.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ValidationError {
+    pub error: String,
+    /// The dot-separated path to the offending field, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// The type of value received for the offending field, or `missing`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received: Option<&'static str>,
+}
+
+impl IntoResponse for ValidationError {
+    fn into_response(self) -> Response {
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(self)).into_response()
+    }
+}
+
+/// The field a missing field error refers to, relying upon the format of
+/// Serde's [serde::de::Error::missing_field].
+fn missing_field(e: &str) -> Option<&str> {
+    e.strip_prefix("missing field `")?.strip_suffix('`')
+}
+
+/// Join a path and a field within it, if any, ignoring the root path `.`.
+fn join(path: &Path, field: Option<&str>) -> Option<String> {
+    let path = path.to_string();
+    let path = Some(path).filter(|x| x != ".");
+
+    match (path, field) {
+        (Some(p), Some(f)) => Some(format!("{}.{}", p, f)),
+        (p, f) => p.or(f.map(str::to_owned)),
+    }
+}
+
+/// Deserialise `application/x-www-form-urlencoded` bytes, in which any value
+/// present is a string, additionally returning the names of any fields which
+/// were ignored.
+pub fn from_form_with_ignored<T: DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<(T, Vec<String>), ValidationError> {
+    let mut ignored = Vec::new();
+    let mut on_ignored = |path: serde_ignored::Path| ignored.push(normalise(&path));
+    let de = serde_urlencoded::Deserializer::new(form_urlencoded::parse(bytes));
+    let de = serde_ignored::Deserializer::new(de, &mut on_ignored);
+
+    let res = serde_path_to_error::deserialize(de).map_err(|e| {
+        let missing = missing_field(&e.inner().to_string()).map(str::to_owned);
+
+        ValidationError {
+            error: String::from("Failed to deserialize form body"),
+            field: join(e.path(), missing.as_deref()),
+            received: Some(if missing.is_some() {
+                "missing"
+            } else {
+                "string"
+            }),
+        }
+    });
+
+    res.map(|x| (x, ignored))
+}
+
+const JSON_ERROR: &str = "Failed to deserialize payload";
+
+/// Parse `application/json` bytes without yet deserialising them. Syntax
+/// errors are detected here, before there's any field to speak of.
+pub fn parse_json(bytes: &[u8]) -> Result<Value, ValidationError> {
+    serde_json::from_slice(bytes).map_err(|_| ValidationError {
+        error: String::from(JSON_ERROR),
+        field: None,
+        received: None,
+    })
+}
+
+/// Deserialise a parsed JSON value.
+///
+/// Paths aren't tracked through internally tagged or untagged enums, so
+/// callers should dispatch on the tag themselves where it matters.
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, ValidationError> {
+    from_value_with_ignored(value).map(|(x, _)| x)
+}
+
+/// Deserialise a parsed JSON value as per [from_value], additionally returning
+/// the paths of any fields which were ignored. Array indices are replaced with
+/// `[]`, such that the paths are bounded by the shape of the payload.
+pub fn from_value_with_ignored<T: DeserializeOwned>(
+    value: &Value,
+) -> Result<(T, Vec<String>), ValidationError> {
+    let mut ignored = Vec::new();
+    let mut on_ignored = |path: serde_ignored::Path| ignored.push(normalise(&path));
+    let de = serde_ignored::Deserializer::new(value, &mut on_ignored);
+
+    let res = serde_path_to_error::deserialize(de).map_err(|e| {
+        let missing = missing_field(&e.inner().to_string()).map(str::to_owned);
+
+        ValidationError {
+            error: String::from(JSON_ERROR),
+            field: join(e.path(), missing.as_deref()),
+            received: match missing {
+                Some(_) => Some("missing"),
+                None => lookup(value, e.path()).map(type_name),
+            },
+        }
+    });
+
+    res.map(|x| (x, ignored))
+}
+
+/// Format an ignored path, replacing array indices with `[]`.
+fn normalise(path: &serde_ignored::Path) -> String {
+    path.to_string()
+        .split('.')
+        // Options are denoted `?`.
+        .filter(|x| *x != "?")
+        .map(|x| match x.parse::<usize>() {
+            Ok(_) => "[]",
+            Err(_) => x,
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Reject a payload which had any fields or values ignored or unrecognised
+/// whilst deserialising, for sources in strict mode. The first such path is
+/// reported.
+pub fn deny_unknown(unknown: &[String]) -> Result<(), ValidationError> {
+    match unknown.first() {
+        None => Ok(()),
+        Some(x) => Err(ValidationError {
+            error: String::from("Unrecognised field or value"),
+            field: Some(x.clone()),
+            received: None,
+        }),
+    }
+}
+
+/// Find the value at a path, if it exists.
+fn lookup<'a>(value: &'a Value, path: &Path) -> Option<&'a Value> {
+    path.iter().try_fold(value, |v, seg| match seg {
+        Segment::Seq { index } => v.get(index),
+        Segment::Map { key } => v.get(key),
+        Segment::Enum { .. } | Segment::Unknown => Some(v),
+    })
+}
+
+fn type_name(x: &Value) -> &'static str {
+    match x {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Like [axum::Form], responding with a [ValidationError] upon failure to
+/// deserialise. Reads the query string for `GET` requests. Alongside the form
+/// are the names of any fields which were ignored, as per
+/// [from_form_with_ignored].
+pub struct ValidatedForm<T>(pub T, pub Vec<String>);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedForm<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let RawForm(bytes) = RawForm::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        from_form_with_ignored(&bytes)
+            .map(|(x, ignored)| ValidatedForm(x, ignored))
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct T {
+        name: String,
+        inner: Option<Inner>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Inner {
+        count: u8,
+    }
+
+    fn from_form<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ValidationError> {
+        from_form_with_ignored(bytes).map(|(x, _)| x)
+    }
+
+    #[test]
+    fn test_from_form() {
+        assert!(from_form::<T>(b"name=x").is_ok());
+
+        assert_eq!(
+            from_form::<T>(b"").unwrap_err(),
+            ValidationError {
+                error: "Failed to deserialize form body".into(),
+                field: Some("name".into()),
+                received: Some("missing"),
+            }
+        );
+
+        let (_, ignored) = from_form_with_ignored::<T>(b"name=x&extra=1").unwrap();
+        assert_eq!(ignored, vec!["extra"]);
+    }
+
+    #[test]
+    fn test_deny_unknown() {
+        assert!(deny_unknown(&[]).is_ok());
+
+        assert_eq!(
+            deny_unknown(&["id".into(), "inner.tags".into()]).unwrap_err(),
+            ValidationError {
+                error: "Unrecognised field or value".into(),
+                field: Some("id".into()),
+                received: None,
+            }
+        );
+    }
+
+    fn from_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ValidationError> {
+        from_value(&parse_json(bytes)?)
+    }
+
+    #[test]
+    fn test_from_json() {
+        assert!(from_json::<T>(br#"{"name": "x", "inner": {"count": 1}}"#).is_ok());
+
+        let err = |x: &[u8]| {
+            let e = from_json::<T>(x).unwrap_err();
+            (e.field, e.received)
+        };
+
+        assert_eq!(err(br#"{}"#), (Some("name".into()), Some("missing")));
+        assert_eq!(
+            err(br#"{"name": "x", "inner": {"count": "secret"}}"#),
+            (Some("inner.count".into()), Some("string"))
+        );
+        assert_eq!(
+            err(br#"{"name": "x", "inner": {}}"#),
+            (Some("inner.count".into()), Some("missing"))
+        );
+        assert_eq!(
+            err(br#"{"name": false}"#),
+            (Some("name".into()), Some("boolean"))
+        );
+        assert_eq!(err(b"oops"), (None, None));
+    }
+
+    #[test]
+    fn test_from_value_with_ignored() {
+        let value = serde_json::json!({
+            "name": "x",
+            "id": 1,
+            "inner": { "count": 1, "tags": [{ "k": "v" }] },
+        });
+
+        let (_, ignored) = from_value_with_ignored::<T>(&value).unwrap();
+
+        assert_eq!(ignored, vec!["id", "inner.tags"]);
+    }
+}
@@ -0,0 +1,217 @@
+//! Post events to [Zulip](https://zulip.com) streams, with each event's title,
+//! typically the app's name, as the topic. Zulip's topics then group events
+//! about each app much as [crate::threading] does in Slack.
+//!
+//! Enabled by configuring the organization's URL at `$ZULIP_SITE`, for example
+//! `https://example.zulipchat.com`, and a bot's email and API key at
+//! `$ZULIP_EMAIL` and `$ZULIP_API_KEY`. The bot must be permitted to post in
+//! any streams it's to post in. A self-hosted organization's TLS can be
+//! configured as per [crate::tls].
+
+use crate::{event::Event, tls::ClientTls};
+use serde::Deserialize;
+use std::{fmt, time::Duration};
+
+/// How long to wait for Zulip to respond to each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Zulip's limit on the length of topics, in characters.
+const MAX_TOPIC_CHARS: usize = 60;
+
+/// What went wrong communicating with Zulip.
+#[derive(Debug)]
+pub enum ZulipError {
+    /// Zulip was asked for however `$ZULIP_SITE` isn't configured.
+    Unconfigured,
+    RequestFailed(reqwest::Error),
+    /// Successfully decoded response error, for example `STREAM_DOES_NOT_EXIST`.
+    Response {
+        code: String,
+        msg: String,
+    },
+}
+
+impl fmt::Display for ZulipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZulipError::Unconfigured => write!(f, "Zulip is not configured"),
+            ZulipError::RequestFailed(e) => write!(f, "Zulip request failed: {:?}", e),
+            ZulipError::Response { code, msg } => {
+                write!(f, "Zulip returned error: {}: {}", code, msg)
+            }
+        }
+    }
+}
+
+/// <https://zulip.com/api/rest-error-handling>
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    msg: String,
+}
+
+/// Posts to streams as a single bot, safe to share across requests.
+pub struct ZulipClient {
+    client: reqwest::Client,
+    site: String,
+    email: String,
+    api_key: String,
+}
+
+impl ZulipClient {
+    pub fn new(site: String, email: String, api_key: String, tls: ClientTls) -> Self {
+        ZulipClient {
+            client: tls
+                .apply(reqwest::Client::builder().timeout(TIMEOUT))
+                .build()
+                .unwrap(),
+            site: site.trim_end_matches('/').to_owned(),
+            email,
+            api_key,
+        }
+    }
+
+    /// Post an event to a stream under a topic of its title.
+    ///
+    /// <https://zulip.com/api/send-message>
+    pub async fn send(&self, stream: &str, evt: &Event) -> Result<(), ZulipError> {
+        let res = self
+            .client
+            .post(format!("{}/api/v1/messages", self.site))
+            .basic_auth(&self.email, Some(&self.api_key))
+            .form(&[
+                ("type", "stream"),
+                ("to", stream),
+                ("topic", &to_topic(evt)),
+                ("content", &to_content(evt)),
+            ])
+            .send()
+            .await
+            .map_err(ZulipError::RequestFailed)?;
+
+        if res.status().is_success() {
+            return Ok(());
+        }
+
+        let status = res.status();
+        let x = res
+            .json::<ErrorResponse>()
+            .await
+            .unwrap_or_else(|_| ErrorResponse {
+                code: String::from("BAD_REQUEST"),
+                msg: status.to_string(),
+            });
+
+        Err(ZulipError::Response {
+            code: x.code,
+            msg: x.msg,
+        })
+    }
+}
+
+/// Zulip rejects topics which are too long rather than truncating them.
+fn to_topic(evt: &Event) -> String {
+    match evt.title.chars().count() > MAX_TOPIC_CHARS {
+        false => evt.title.clone(),
+        true => {
+            evt.title
+                .chars()
+                .take(MAX_TOPIC_CHARS - 1)
+                .collect::<String>()
+                + "…"
+        }
+    }
+}
+
+/// Render an event's body as Zulip-flavoured Markdown, the title being the
+/// topic.
+fn to_content(evt: &Event) -> String {
+    let mut xs = vec![evt.summary.clone()];
+
+    for (k, v) in &evt.fields {
+        xs.push(format!("**{}**: {}", k, v));
+    }
+
+    if let Some(x) = evt.links.first() {
+        xs.push(format!("[View]({})", x));
+    }
+
+    xs.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{delivery::Source, event::EventKind};
+    use mockito::Matcher;
+
+    fn deploy(title: &str) -> Event {
+        Event {
+            source: Source::Heroku,
+            kind: EventKind::Deploy,
+            app: Some(String::from("api")),
+            version: None,
+            severity: None,
+            occurred_at: None,
+            title: title.to_owned(),
+            summary: String::from("Deployed abc123"),
+            fields: vec![(String::from("Author"), String::from("sam"))],
+            links: vec!["https://dashboard.heroku.com/apps/api/activity"
+                .parse()
+                .unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_to_topic() {
+        assert_eq!(to_topic(&deploy("api")), "api");
+        assert_eq!(to_topic(&deploy(&"x".repeat(100))).chars().count(), 60);
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let mut srv = mockito::Server::new_async().await;
+
+        let ok = srv
+            .mock("POST", "/api/v1/messages")
+            .match_header("Authorization", Matcher::Regex("^Basic ".into()))
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("type".into(), "stream".into()),
+                Matcher::UrlEncoded("to".into(), "deploys".into()),
+                Matcher::UrlEncoded("topic".into(), "api".into()),
+                Matcher::UrlEncoded(
+                    "content".into(),
+                    "Deployed abc123\n**Author**: sam\n[View](https://dashboard.heroku.com/apps/api/activity)".into(),
+                ),
+            ]))
+            .with_body(r#"{"result": "success", "msg": "", "id": 42}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let err = srv
+            .mock("POST", "/api/v1/messages")
+            .match_body(Matcher::UrlEncoded("to".into(), "nope".into()))
+            .with_status(400)
+            .with_body(r#"{"result": "error", "msg": "Stream 'nope' does not exist", "code": "STREAM_DOES_NOT_EXIST"}"#)
+            .create_async()
+            .await;
+
+        let x = ZulipClient::new(
+            srv.url() + "/",
+            "bot@example.com".into(),
+            "key".into(),
+            ClientTls::default(),
+        );
+
+        x.send("deploys", &deploy("api")).await.unwrap();
+        assert!(matches!(
+            x.send("nope", &deploy("api")).await,
+            Err(ZulipError::Response { code, .. }) if code == "STREAM_DOES_NOT_EXIST"
+        ));
+
+        ok.assert_async().await;
+        err.assert_async().await;
+    }
+}
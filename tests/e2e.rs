@@ -0,0 +1,204 @@
+//! End-to-end tests booting the real server against mock Slack and GitHub APIs.
+//!
+//! Each file in `tests/fixtures/heroku/` is a [Case]: recorded Heroku webhook
+//! payloads to replay in order, any GitHub API responses they'll need, and the
+//! `chat.postMessage` bodies we expect to be sent to Slack as a result. Bodies
+//! are matched partially, so fixtures need only include what they care about.
+//!
+//! To add a case, drop a new fixture in alongside the others.
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use mockito::{Matcher, Mock, ServerGuard};
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+const HEROKU_SECRET: &str = "foobarbaz";
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[derive(Deserialize)]
+struct Case {
+    /// The query string for `/api/v1/heroku/hook`.
+    query: String,
+    /// Webhook payloads, replayed in order.
+    requests: Vec<Value>,
+    /// GitHub API responses by path.
+    #[serde(default)]
+    github: HashMap<String, Value>,
+    /// The expected `chat.postMessage` bodies, matched partially.
+    slack: Vec<Value>,
+}
+
+/// A running instance of the server binary, killed on drop.
+struct Mercury {
+    child: Child,
+    base: String,
+}
+
+impl Mercury {
+    /// Boot the server, waiting until it's ready to accept requests.
+    async fn start(slack: &ServerGuard, github: &ServerGuard) -> Self {
+        // There's a small window for someone else to take the port, which
+        // we'll live with.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        // Running from a temporary directory ensures no `.env` is picked up.
+        let child = Command::new(env!("CARGO_BIN_EXE_mercury"))
+            .current_dir(std::env::temp_dir())
+            .env_clear()
+            .env("BIND_ADDR", format!("127.0.0.1:{}", port))
+            .env("SLACK_TOKEN", "foobar")
+            .env("HEROKU_SECRET", HEROKU_SECRET)
+            .env("GITHUB_TOKEN", "foobar")
+            .env("SLACK_API_BASE", slack.url())
+            .env("GITHUB_API_BASE", github.url())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let res = Mercury {
+            child,
+            base: format!("http://127.0.0.1:{}", port),
+        };
+
+        let client = reqwest::Client::new();
+        for _ in 0..100 {
+            let health = client
+                .get(format!("{}/api/v1/health", res.base))
+                .send()
+                .await;
+            if health.is_ok_and(|x| x.status().is_success()) {
+                return res;
+            }
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        panic!("Server didn't become ready");
+    }
+}
+
+impl Drop for Mercury {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn sign(body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(HEROKU_SECRET.as_bytes()).unwrap();
+    mac.update(body);
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+fn fixtures(dir: &str) -> Vec<PathBuf> {
+    let mut xs: Vec<_> = fs::read_dir(Path::new(FIXTURES).join(dir))
+        .unwrap()
+        .map(|x| x.unwrap().path())
+        .filter(|x| x.extension().is_some_and(|x| x == "json"))
+        .collect();
+    xs.sort();
+    xs
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> T {
+    serde_json::from_slice(&fs::read(path).unwrap())
+        .unwrap_or_else(|e| panic!("Invalid fixture {}: {}", path.display(), e))
+}
+
+async fn mock_slack(srv: &mut ServerGuard, case: &Case) -> Vec<Mock> {
+    srv.mock("GET", "/conversations.list")
+        .match_query(Matcher::Any)
+        .with_body(
+            serde_json::to_string(&read_json::<Value>(
+                &Path::new(FIXTURES).join("slack/conversations.list.json"),
+            ))
+            .unwrap(),
+        )
+        .create_async()
+        .await;
+
+    let mut mocks = Vec::new();
+    for body in &case.slack {
+        mocks.push(
+            srv.mock("POST", "/chat.postMessage")
+                .match_body(Matcher::PartialJson(body.clone()))
+                .with_body(r#"{"ok":true,"channel":"C1","ts":"1234.5678"}"#)
+                .expect(1)
+                .create_async()
+                .await,
+        );
+    }
+
+    mocks
+}
+
+async fn mock_github(srv: &mut ServerGuard, case: &Case) -> Vec<Mock> {
+    let mut mocks = Vec::new();
+    for (path, body) in &case.github {
+        mocks.push(
+            srv.mock("GET", path.as_str())
+                .with_body(serde_json::to_string(body).unwrap())
+                .expect(1)
+                .create_async()
+                .await,
+        );
+    }
+
+    mocks
+}
+
+#[tokio::test]
+async fn test_heroku_fixtures() {
+    for path in fixtures("heroku") {
+        let case: Case = read_json(&path);
+        let name = path.file_name().unwrap().to_string_lossy();
+
+        let mut slack = mockito::Server::new_async().await;
+        let mut github = mockito::Server::new_async().await;
+        let slack_mocks = mock_slack(&mut slack, &case).await;
+        let github_mocks = mock_github(&mut github, &case).await;
+
+        let mercury = Mercury::start(&slack, &github).await;
+        let client = reqwest::Client::new();
+
+        for payload in &case.requests {
+            let body = serde_json::to_vec(payload).unwrap();
+            let res = client
+                .post(format!(
+                    "{}/api/v1/heroku/hook?{}",
+                    mercury.base, case.query
+                ))
+                .header("Heroku-Webhook-Hmac-SHA256", sign(&body))
+                .header("Content-Type", "application/json")
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+
+            assert!(
+                res.status().is_success(),
+                "{}: unexpected status {}, perhaps from an unmatched Slack payload",
+                name,
+                res.status()
+            );
+        }
+
+        for mock in slack_mocks.iter().chain(&github_mocks) {
+            assert!(mock.matched_async().await, "{}: unmatched mock", name);
+        }
+    }
+}